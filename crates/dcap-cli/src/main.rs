@@ -0,0 +1,288 @@
+//! Command line tool to inspect DCAP quotes, fetch their collateral and
+//! verify them against an appraisal policy.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use dcap::pcs::blocking::PcsClient;
+use dcap::{
+    tee_type, verify_quote_with_anchors, CollateralCache, CollateralKey, PckCaType, Policy, Quote,
+    QuoteCollateral, TrustAnchors, VerifyOptions,
+};
+use serde::Deserialize;
+use tee_config::{Config, CONFIG_ENV};
+
+mod report;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Configuration file, for the collateral source and cache, the roots
+    /// of trust and the policy. Flags take precedence over it.
+    #[arg(long, global = true, env = CONFIG_ENV)]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Print the contents of a quote.
+    ParseQuote {
+        /// Raw or hex encoded quote.
+        quote: PathBuf,
+    },
+    /// Fetch the collateral of a platform from the PCS or a PCCS.
+    FetchCollateral {
+        #[arg(long)]
+        fmspc: String,
+        #[arg(long, value_enum, default_value_t = Tee::Sgx)]
+        tee: Tee,
+        #[arg(long, value_enum, default_value_t = Ca::Platform)]
+        ca: Ca,
+        #[command(flatten)]
+        pcs: PcsArgs,
+        /// Where to write the collateral as JSON, stdout by default.
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Verify a quote and appraise it, exiting non-zero if the policy
+    /// rejects it.
+    Verify {
+        /// Raw or hex encoded quote.
+        #[arg(long)]
+        quote: PathBuf,
+        /// Collateral as JSON or in the QVL layout; fetched if omitted.
+        #[arg(long)]
+        collateral: Option<PathBuf>,
+        /// Appraisal policy as JSON or TOML; only accepts up to date
+        /// platforms if omitted.
+        #[arg(long)]
+        policy: Option<PathBuf>,
+        /// PEM bundle of the trusted roots, the Intel SGX Root CA by default.
+        #[arg(long)]
+        roots: Option<PathBuf>,
+        /// Verification time as RFC 3339, now by default.
+        #[arg(long)]
+        at: Option<DateTime<Utc>>,
+        #[command(flatten)]
+        pcs: PcsArgs,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Tee {
+    Sgx,
+    Tdx,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Ca {
+    Platform,
+    Processor,
+}
+
+#[derive(Debug, clap::Args)]
+struct PcsArgs {
+    /// Fetch from the PCCS at this URL instead of the Intel PCS.
+    #[arg(long)]
+    pccs: Option<String>,
+    #[arg(long, env = "PCS_API_KEY")]
+    api_key: Option<String>,
+}
+
+impl PcsArgs {
+    fn client(&self, config: &Config) -> PcsClient {
+        let mut pcs = config.pcs.clone();
+        if let Some(url) = &self.pccs {
+            pcs.pccs_url = Some(url.clone());
+        }
+        if let Some(api_key) = &self.api_key {
+            pcs.api_key = Some(api_key.clone());
+        }
+        PcsClient::with_config(pcs.config())
+    }
+}
+
+/// The policy of the configuration file, whose `dcap` section appraises
+/// quotes.
+#[derive(Debug, Default, Deserialize)]
+struct ConfigPolicy {
+    #[serde(default)]
+    dcap: Policy,
+}
+
+fn main() -> eyre::Result<ExitCode> {
+    let cli = Cli::parse();
+    let config = Config::load_or_default(cli.config.as_deref())?;
+    match cli.command {
+        Command::ParseQuote { quote } => {
+            let quote = Quote::parse(&read_quote(&quote)?)?;
+            print!("{}", report::quote(&quote));
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::FetchCollateral {
+            fmspc,
+            tee,
+            ca,
+            pcs,
+            out,
+        } => {
+            let key = CollateralKey {
+                tee_type: match tee {
+                    Tee::Sgx => tee_type::SGX,
+                    Tee::Tdx => tee_type::TDX,
+                },
+                fmspc: hex::decode(&fmspc)?
+                    .try_into()
+                    .map_err(|_| eyre::eyre!("FMSPC must be 6 bytes"))?,
+                ca: match ca {
+                    Ca::Platform => PckCaType::Platform,
+                    Ca::Processor => PckCaType::Processor,
+                },
+            };
+            let collateral = pcs.client(&config).quote_collateral(&key)?;
+            let json = serde_json::to_string_pretty(&collateral)?;
+            match out {
+                Some(path) => std::fs::write(path, json)?,
+                None => println!("{}", json),
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Verify {
+            quote,
+            collateral,
+            policy,
+            roots,
+            at,
+            pcs,
+        } => {
+            let quote = read_quote(&quote)?;
+            let collateral = match collateral {
+                Some(path) => read_collateral(&path)?,
+                None => {
+                    let key = CollateralKey::from_quote(&Quote::parse(&quote)?)?;
+                    let cache = config
+                        .cache
+                        .apply(CollateralCache::new(pcs.client(&config)));
+                    cache.get(&key)?.as_ref().clone()
+                }
+            };
+            let policy = match policy {
+                Some(path) => read_policy(&path)?,
+                None => {
+                    let policy = config.policy::<ConfigPolicy>()?.unwrap_or_default().dcap;
+                    policy.validate()?;
+                    policy
+                }
+            };
+            let anchors = match roots {
+                Some(path) => TrustAnchors::from_pem(&std::fs::read(path)?)?,
+                None => config.trust.anchors()?,
+            };
+            let at = at.unwrap_or_else(Utc::now);
+
+            let result = verify_quote_with_anchors(
+                &quote,
+                &collateral,
+                &anchors,
+                &VerifyOptions::default(),
+                at,
+            )?;
+            let verdict = policy.evaluate_at(&result, at);
+            print!("{}", report::verification(&result, &verdict));
+            Ok(if verdict.is_accepted() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            })
+        }
+    }
+}
+
+/// Read a quote, raw or hex encoded.
+fn read_quote(path: &Path) -> eyre::Result<Vec<u8>> {
+    decode_quote(std::fs::read(path)?)
+}
+
+fn decode_quote(bytes: Vec<u8>) -> eyre::Result<Vec<u8>> {
+    let text = bytes.trim_ascii();
+    if !text.is_empty() && text.iter().all(u8::is_ascii_hexdigit) {
+        return Ok(hex::decode(text)?);
+    }
+    Ok(bytes)
+}
+
+/// Read collateral as JSON or in the layout of the QVL.
+fn read_collateral(path: &Path) -> eyre::Result<QuoteCollateral> {
+    let bytes = std::fs::read(path)?;
+    if bytes.trim_ascii_start().starts_with(b"{") {
+        Ok(serde_json::from_slice(&bytes)?)
+    } else {
+        QuoteCollateral::from_bytes(&bytes)
+    }
+}
+
+/// Read a policy as TOML if the file name says so, JSON otherwise.
+fn read_policy(path: &Path) -> eyre::Result<Policy> {
+    let text = std::fs::read_to_string(path)?;
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => Policy::from_toml(&text),
+        _ => Policy::from_json(&text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_quote() -> eyre::Result<()> {
+        assert_eq!(decode_quote(b"0300\n".to_vec())?, vec![3, 0]);
+        assert_eq!(decode_quote(vec![3, 0])?, vec![3, 0]);
+        assert!(decode_quote(b"030".to_vec()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_arguments() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+
+        let cli = Cli::parse_from([
+            "dcap-cli",
+            "verify",
+            "--quote",
+            "quote.bin",
+            "--policy",
+            "policy.toml",
+            "--at",
+            "2025-03-01T00:00:00Z",
+        ]);
+        let Command::Verify { at, policy, .. } = cli.command else {
+            panic!("unexpected command {:?}", cli.command);
+        };
+        assert_eq!(at.unwrap().timestamp(), 1_740_787_200);
+        assert_eq!(policy.unwrap(), PathBuf::from("policy.toml"));
+
+        let cli = Cli::parse_from([
+            "dcap-cli",
+            "parse-quote",
+            "quote.bin",
+            "--config",
+            "tee-ware.toml",
+        ]);
+        assert_eq!(cli.config.unwrap(), PathBuf::from("tee-ware.toml"));
+    }
+
+    #[test]
+    fn test_config_policy() -> eyre::Result<()> {
+        let config = Config::from_toml("[policy.dcap]\nmin_isv_svn = 3\n[policy.tpm]\n")?;
+        let policy = config.policy::<ConfigPolicy>()?.unwrap().dcap;
+        assert_eq!(policy.min_isv_svn, Some(3));
+        assert_eq!(policy.tcb_statuses, Policy::default().tcb_statuses);
+        Ok(())
+    }
+}
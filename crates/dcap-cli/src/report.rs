@@ -0,0 +1,111 @@
+//! Human-readable reports.
+
+use std::fmt::Write;
+
+use dcap::{tee_type, PckChain, PolicyVerdict, Quote, QuoteBody, VerificationResult};
+
+/// The contents of `quote`, one field per line.
+pub fn quote(quote: &Quote) -> String {
+    let mut out = String::new();
+    let header = &quote.header;
+    field(&mut out, "Version", header.version);
+    field(&mut out, "TEE", tee_name(header.tee_type));
+    field(&mut out, "QE SVN", header.qe_svn);
+    field(&mut out, "PCE SVN", header.pce_svn);
+    field(&mut out, "QE vendor ID", hex::encode(header.qe_vendor_id));
+    body(&mut out, &quote.body);
+
+    let certification = &quote.signature.qe_report_certification;
+    field(
+        &mut out,
+        "Certification data",
+        certification.certification_data.cert_type(),
+    );
+    match PckChain::from_quote(quote).and_then(|chain| Ok((chain.sgx_extensions()?, chain))) {
+        Ok((extensions, chain)) => {
+            field(&mut out, "FMSPC", hex::encode(extensions.fmspc));
+            match chain.ca_type() {
+                Ok(ca) => field(&mut out, "PCK CA", ca.as_str()),
+                Err(err) => field(&mut out, "PCK CA", err),
+            }
+        }
+        Err(err) => field(&mut out, "PCK chain", err),
+    }
+    out
+}
+
+/// The outcome of a verification and its appraisal.
+pub fn verification(result: &VerificationResult, verdict: &PolicyVerdict) -> String {
+    let mut out = String::new();
+    field(&mut out, "TEE", tee_name(result.quote.header.tee_type));
+    field(&mut out, "FMSPC", hex::encode(result.fmspc));
+    field(&mut out, "TCB status", format!("{:?}", result.status));
+    field(&mut out, "QE status", format!("{:?}", result.qe_status));
+    if let Some(module) = &result.tdx_module {
+        field(
+            &mut out,
+            "TDX module status",
+            format!("{:?}", module.status),
+        );
+    }
+    field(&mut out, "TCB date", result.tcb_date.to_rfc3339());
+    field(
+        &mut out,
+        "Collateral issued",
+        result.collateral_issue_date.to_rfc3339(),
+    );
+    if result.advisory_ids.is_empty() {
+        field(&mut out, "Advisories", "none");
+    } else {
+        field(&mut out, "Advisories", result.advisory_ids.join(", "));
+    }
+    body(&mut out, &result.quote.body);
+    field(&mut out, "Policy", verdict);
+    out
+}
+
+fn body(out: &mut String, body: &QuoteBody) {
+    if let Some(report) = body.as_enclave_report() {
+        field(out, "MRENCLAVE", hex::encode(report.mr_enclave));
+        field(out, "MRSIGNER", hex::encode(report.mr_signer));
+        field(out, "ISV product ID", report.isv_prod_id);
+        field(out, "ISV SVN", report.isv_svn);
+    }
+    if let Some(report) = body.as_td_report() {
+        field(out, "MRTD", hex::encode(report.mr_td));
+        for (i, rtmr) in report.rtmrs.iter().enumerate() {
+            field(out, &format!("RTMR{}", i), hex::encode(rtmr));
+        }
+    }
+    field(out, "Report data", hex::encode(body.report_data()));
+}
+
+fn field(out: &mut String, name: &str, value: impl std::fmt::Display) {
+    writeln!(out, "{:<20}{}", format!("{}:", name), value).expect("writing to a string");
+}
+
+fn tee_name(tee: u32) -> String {
+    match tee {
+        tee_type::SGX => "SGX".to_string(),
+        tee_type::TDX => "TDX".to_string(),
+        other => format!("{:#x}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_alignment() {
+        let mut out = String::new();
+        field(&mut out, "TCB status", "UpToDate");
+        field(&mut out, "Report data", "00");
+        assert_eq!(
+            out,
+            "TCB status:         UpToDate\nReport data:        00\n"
+        );
+        assert_eq!(tee_name(tee_type::TDX), "TDX");
+        assert_eq!(tee_name(1), "0x1");
+    }
+}
@@ -0,0 +1,203 @@
+//! The quote verification of `dcap` built for `wasm32-unknown-unknown`, for
+//! audit tools that verify a pasted quote in the browser with the same code
+//! as production verifiers.
+//!
+//! Nothing here reaches the network or the clock: the page fetches the
+//! collateral, e.g. from the PCS for the [`quote_info`] of the quote, and
+//! passes the verification time.
+//!
+//! ```sh
+//! cargo build -p dcap-wasm --release --target wasm32-unknown-unknown
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/dcap_wasm.wasm
+//! ```
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use dcap::primitives::tcb_info::TcbStatus;
+use dcap::{
+    tee_type, verify_quote_with_anchors, CollateralKey, Policy, Quote, QuoteBody, QuoteCollateral,
+    TrustAnchors, VerifyOptions,
+};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// What the collateral of a quote is keyed by.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuoteInfo {
+    /// `sgx` or `tdx`.
+    pub tee: String,
+    /// Hex encoded.
+    pub fmspc: String,
+    /// The CA of the PCK certificate, `platform` or `processor`.
+    pub ca: String,
+}
+
+/// A verified quote and its appraisal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Verdict {
+    pub tee: String,
+    pub accepted: bool,
+    pub tcb_status: TcbStatus,
+    pub qe_status: TcbStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tdx_module_status: Option<TcbStatus>,
+    pub advisory_ids: Vec<String>,
+    pub tcb_date: DateTime<Utc>,
+    pub collateral_issue_date: DateTime<Utc>,
+    /// Hex encoded.
+    pub report_data: String,
+    /// Hex encoded measurements by name, e.g. `mr_td` or `rtmr0`.
+    pub measurements: BTreeMap<String, String>,
+    pub violations: Vec<String>,
+}
+
+/// The [`QuoteInfo`] of `quote`.
+pub fn parse_quote_info(quote: &[u8]) -> eyre::Result<QuoteInfo> {
+    let key = CollateralKey::from_quote(&Quote::parse(quote)?)?;
+    Ok(QuoteInfo {
+        tee: tee_type::name(key.tee_type).to_string(),
+        fmspc: hex::encode(key.fmspc),
+        ca: key.ca.as_str().to_string(),
+    })
+}
+
+/// Verify `quote` with `collateral` at `at`, trusting `anchors`, and
+/// appraise it with `policy`.
+pub fn verify(
+    quote: &[u8],
+    collateral: &QuoteCollateral,
+    policy: &Policy,
+    anchors: &TrustAnchors,
+    at: DateTime<Utc>,
+) -> eyre::Result<Verdict> {
+    let result =
+        verify_quote_with_anchors(quote, collateral, anchors, &VerifyOptions::default(), at)?;
+    let verdict = policy.evaluate_at(&result, at);
+
+    let body = &result.quote.body;
+    let mut measurements = BTreeMap::new();
+    match body {
+        QuoteBody::SgxEnclave(report) => {
+            measurements.insert("mr_enclave".to_string(), hex::encode(report.mr_enclave));
+            measurements.insert("mr_signer".to_string(), hex::encode(report.mr_signer));
+        }
+        body => {
+            if let Some(report) = body.as_td_report() {
+                measurements.insert("mr_td".to_string(), hex::encode(report.mr_td));
+                for (i, rtmr) in report.rtmrs.iter().enumerate() {
+                    measurements.insert(format!("rtmr{}", i), hex::encode(rtmr));
+                }
+            }
+        }
+    }
+    Ok(Verdict {
+        tee: tee_type::name(result.quote.header.tee_type).to_string(),
+        accepted: verdict.is_accepted(),
+        tcb_status: result.status,
+        qe_status: result.qe_status,
+        tdx_module_status: result.tdx_module.as_ref().map(|module| module.status),
+        advisory_ids: result.advisory_ids.clone(),
+        tcb_date: result.tcb_date,
+        collateral_issue_date: result.collateral_issue_date,
+        report_data: hex::encode(body.report_data()),
+        measurements,
+        violations: verdict.violations.iter().map(ToString::to_string).collect(),
+    })
+}
+
+/// `at` in milliseconds since the epoch, as returned by `Date.now()`.
+fn from_millis(at: f64) -> eyre::Result<DateTime<Utc>> {
+    if !at.is_finite() {
+        eyre::bail!("invalid time {}", at);
+    }
+    DateTime::from_timestamp_millis(at as i64).ok_or_else(|| eyre::eyre!("invalid time {}", at))
+}
+
+/// Verify `quote` with `collateral_json`, the collateral as JSON, at `at`
+/// in milliseconds since the epoch, and appraise it with `policy_json`, or
+/// a policy only accepting up to date platforms if omitted.
+///
+/// `roots_pem` replaces the Intel SGX Root CA. Returns a [`Verdict`] as
+/// JSON.
+#[wasm_bindgen(js_name = verifyQuote)]
+pub fn verify_quote(
+    quote: &[u8],
+    collateral_json: &str,
+    policy_json: Option<String>,
+    roots_pem: Option<String>,
+    at: f64,
+) -> Result<String, JsError> {
+    let verdict = (|| {
+        let collateral: QuoteCollateral = serde_json::from_str(collateral_json)
+            .map_err(|err| eyre::eyre!("invalid collateral: {}", err))?;
+        let policy = match &policy_json {
+            Some(json) => Policy::from_json(json)?,
+            None => Policy::default(),
+        };
+        let anchors = match &roots_pem {
+            Some(pem) => TrustAnchors::from_pem(pem.as_bytes())?,
+            None => TrustAnchors::default(),
+        };
+        let verdict = verify(quote, &collateral, &policy, &anchors, from_millis(at)?)?;
+        Ok(serde_json::to_string(&verdict)?)
+    })();
+    verdict.map_err(js_error)
+}
+
+/// The [`QuoteInfo`] of `quote` as JSON, to fetch its collateral.
+#[wasm_bindgen(js_name = quoteInfo)]
+pub fn quote_info(quote: &[u8]) -> Result<String, JsError> {
+    parse_quote_info(quote)
+        .and_then(|info| Ok(serde_json::to_string(&info)?))
+        .map_err(js_error)
+}
+
+fn js_error(err: eyre::Report) -> JsError {
+    JsError::new(&format!("{:#}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_millis() -> eyre::Result<()> {
+        assert_eq!(
+            from_millis(1_740_787_200_123.0)?,
+            DateTime::from_timestamp_millis(1_740_787_200_123).unwrap()
+        );
+        assert!(from_millis(f64::NAN).is_err());
+        assert!(from_millis(f64::INFINITY).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_malformed_quotes() {
+        assert!(parse_quote_info(&[]).is_err());
+        assert!(parse_quote_info(&[0; 64]).is_err());
+    }
+
+    #[test]
+    fn test_verdict_json() -> eyre::Result<()> {
+        let verdict = Verdict {
+            tee: "tdx".to_string(),
+            accepted: false,
+            tcb_status: TcbStatus::OutOfDate,
+            qe_status: TcbStatus::UpToDate,
+            tdx_module_status: None,
+            advisory_ids: vec!["INTEL-SA-00837".to_string()],
+            tcb_date: from_millis(1_700_000_000_000.0)?,
+            collateral_issue_date: from_millis(1_740_787_200_000.0)?,
+            report_data: "00".repeat(64),
+            measurements: BTreeMap::from([("mr_td".to_string(), "11".repeat(48))]),
+            violations: vec!["TCB status OutOfDate is not accepted".to_string()],
+        };
+        let json: serde_json::Value = serde_json::from_str(&serde_json::to_string(&verdict)?)?;
+        assert_eq!(json["tcb_status"], "OutOfDate");
+        assert_eq!(json["tcb_date"], "2023-11-14T22:13:20Z");
+        assert!(json.get("tdx_module_status").is_none());
+        assert_eq!(serde_json::from_value::<Verdict>(json)?, verdict);
+        Ok(())
+    }
+}
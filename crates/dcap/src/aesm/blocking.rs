@@ -0,0 +1,66 @@
+//! Blocking variant of [`AesmClient`](super::AesmClient).
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::{AttestationKeyId, QuoteInit};
+use crate::runtime::BlockingRuntime;
+
+/// Blocking AESM client, running the async [`AesmClient`](super::AesmClient)
+/// on a runtime of its own.
+#[derive(Debug, Clone)]
+pub struct AesmClient {
+    client: super::AesmClient,
+    runtime: BlockingRuntime,
+}
+
+impl Default for AesmClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AesmClient {
+    pub fn new() -> Self {
+        Self::from_async(super::AesmClient::new())
+    }
+
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
+        Self::from_async(super::AesmClient::with_path(path))
+    }
+
+    /// Wrap a configured async client.
+    pub fn from_async(client: super::AesmClient) -> Self {
+        Self {
+            client,
+            runtime: BlockingRuntime::new(),
+        }
+    }
+
+    /// How long AESM and the socket may take per request.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = self.client.with_timeout(timeout);
+        self
+    }
+
+    pub fn with_att_key_id(mut self, att_key_id: AttestationKeyId) -> Self {
+        self.client = self.client.with_att_key_id(att_key_id);
+        self
+    }
+
+    /// Initialize the quoting enclave and return its target info.
+    pub fn init_quote(&self) -> eyre::Result<QuoteInit> {
+        self.runtime.block_on(self.client.init_quote())
+    }
+
+    /// Size in bytes of the quotes produced by the quoting enclave.
+    pub fn quote_size(&self) -> eyre::Result<u32> {
+        self.runtime.block_on(self.client.quote_size())
+    }
+
+    /// Turn `report`, an `sgx_report_t` targeted at the quoting enclave, into
+    /// a quote.
+    pub fn get_quote(&self, report: &[u8]) -> eyre::Result<Vec<u8>> {
+        self.runtime.block_on(self.client.get_quote(report))
+    }
+}
@@ -0,0 +1,429 @@
+//! Client for the SGX Architectural Enclave Service Manager (AESM), which
+//! hosts the quoting enclave on SGX hosts.
+//!
+//! Requests are protobuf messages framed by a little-endian `u32` length,
+//! exchanged over the AESM UNIX socket.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use prost::Message;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+use crate::SGX_REPORT_SIZE;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+/// Where `aesmd` listens by default.
+pub const AESM_SOCKET_PATH: &str = "/var/run/aesmd/aesm.socket";
+
+/// Size in bytes of an `sgx_att_key_id_ext_t`.
+pub const ATT_KEY_ID_SIZE: usize = 256;
+
+/// MRSIGNER of the Intel ECDSA quoting enclave.
+pub const INTEL_QE3_MRSIGNER: [u8; 32] = [
+    0x8c, 0x4f, 0x57, 0x75, 0xd7, 0x96, 0x50, 0x3e, 0x96, 0x13, 0x7f, 0x77, 0xc6, 0x8a, 0x82, 0x9a,
+    0x00, 0x56, 0xac, 0x8d, 0xed, 0x70, 0x14, 0x0b, 0x08, 0x1b, 0x09, 0x44, 0x90, 0xc5, 0x7b, 0xff,
+];
+
+const SGX_QL_ALG_ECDSA_P256: u32 = 2;
+const MAX_RESPONSE_SIZE: usize = 1 << 20;
+
+/// An `sgx_att_key_id_ext_t`, selecting the quoting enclave and attestation
+/// key algorithm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestationKeyId(pub [u8; ATT_KEY_ID_SIZE]);
+
+impl AttestationKeyId {
+    /// The ECDSA P-256 key of the Intel quoting enclave, used for DCAP
+    /// quotes.
+    pub fn ecdsa_p256() -> Self {
+        let mut id = [0u8; ATT_KEY_ID_SIZE];
+        // id and version are 0.
+        id[4..6].copy_from_slice(&(INTEL_QE3_MRSIGNER.len() as u16).to_le_bytes());
+        id[6..38].copy_from_slice(&INTEL_QE3_MRSIGNER);
+        id[54..58].copy_from_slice(&1u32.to_le_bytes()); // prod_id
+        id[154..158].copy_from_slice(&SGX_QL_ALG_ECDSA_P256.to_le_bytes());
+        Self(id)
+    }
+}
+
+/// AESM rejected a request.
+///
+/// Returned wrapped in an [`eyre::Report`]; use `downcast_ref::<AesmError>()`
+/// to inspect the `aesm_error_t` code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AesmError {
+    pub code: u32,
+}
+
+impl std::fmt::Display for AesmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AESM request failed with error code {}", self.code)
+    }
+}
+
+impl std::error::Error for AesmError {}
+
+/// The outcome of [`AesmClient::init_quote`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuoteInit {
+    /// `sgx_target_info_t` of the quoting enclave, which the application
+    /// enclave must target its report at.
+    pub target_info: Vec<u8>,
+    /// Hash of the attestation public key.
+    pub pub_key_id: Vec<u8>,
+}
+
+/// Requests ECDSA quotes from the AESM.
+#[derive(Debug, Clone)]
+pub struct AesmClient {
+    path: PathBuf,
+    timeout: Duration,
+    att_key_id: AttestationKeyId,
+}
+
+impl Default for AesmClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AesmClient {
+    pub fn new() -> Self {
+        Self::with_path(AESM_SOCKET_PATH)
+    }
+
+    pub fn with_path(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            timeout: Duration::from_secs(60),
+            att_key_id: AttestationKeyId::ecdsa_p256(),
+        }
+    }
+
+    /// How long AESM and the socket may take per request.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_att_key_id(mut self, att_key_id: AttestationKeyId) -> Self {
+        self.att_key_id = att_key_id;
+        self
+    }
+
+    /// Initialize the quoting enclave and return its target info.
+    pub async fn init_quote(&self) -> eyre::Result<QuoteInit> {
+        let request = Request {
+            init_quote_ex_req: Some(InitQuoteExRequest {
+                att_key_id: Some(self.att_key_id.0.to_vec()),
+                b_pub_key_id: true,
+                buf_size: Some(0),
+                timeout: Some(self.timeout_ms()),
+            }),
+            ..Default::default()
+        };
+        let response = self
+            .transact(&request)
+            .await?
+            .init_quote_ex_res
+            .ok_or_else(|| eyre::eyre!("AESM sent no InitQuoteEx response"))?;
+        check(response.error_code)?;
+        Ok(QuoteInit {
+            target_info: response.target_info.unwrap_or_default(),
+            pub_key_id: response.pub_key_id.unwrap_or_default(),
+        })
+    }
+
+    /// Size in bytes of the quotes produced by the quoting enclave.
+    pub async fn quote_size(&self) -> eyre::Result<u32> {
+        let request = Request {
+            get_quote_size_ex_req: Some(GetQuoteSizeExRequest {
+                att_key_id: Some(self.att_key_id.0.to_vec()),
+                timeout: Some(self.timeout_ms()),
+            }),
+            ..Default::default()
+        };
+        let response = self
+            .transact(&request)
+            .await?
+            .get_quote_size_ex_res
+            .ok_or_else(|| eyre::eyre!("AESM sent no GetQuoteSizeEx response"))?;
+        check(response.error_code)?;
+        response
+            .quote_size
+            .ok_or_else(|| eyre::eyre!("AESM sent no quote size"))
+    }
+
+    /// Turn `report`, an `sgx_report_t` targeted at the quoting enclave (see
+    /// [`AesmClient::init_quote`]), into a quote.
+    pub async fn get_quote(&self, report: &[u8]) -> eyre::Result<Vec<u8>> {
+        if report.len() != SGX_REPORT_SIZE {
+            eyre::bail!(
+                "SGX report must be {} bytes, got {}",
+                SGX_REPORT_SIZE,
+                report.len()
+            );
+        }
+        let request = Request {
+            get_quote_ex_req: Some(GetQuoteExRequest {
+                report: report.to_vec(),
+                att_key_id: Some(self.att_key_id.0.to_vec()),
+                qe_report_info: None,
+                buf_size: self.quote_size().await?,
+                timeout: Some(self.timeout_ms()),
+            }),
+            ..Default::default()
+        };
+        let response = self
+            .transact(&request)
+            .await?
+            .get_quote_ex_res
+            .ok_or_else(|| eyre::eyre!("AESM sent no GetQuoteEx response"))?;
+        check(response.error_code)?;
+        response
+            .quote
+            .ok_or_else(|| eyre::eyre!("AESM sent no quote"))
+    }
+
+    fn timeout_ms(&self) -> u32 {
+        self.timeout.as_millis().try_into().unwrap_or(u32::MAX)
+    }
+
+    async fn transact(&self, request: &Request) -> eyre::Result<Response> {
+        tokio::time::timeout(self.timeout, self.exchange(request))
+            .await
+            .map_err(|_| eyre::eyre!("AESM did not respond within {:?}", self.timeout))?
+    }
+
+    async fn exchange(&self, request: &Request) -> eyre::Result<Response> {
+        let mut stream = UnixStream::connect(&self.path).await?;
+        let body = request.encode_to_vec();
+        stream.write_all(&(body.len() as u32).to_le_bytes()).await?;
+        stream.write_all(&body).await?;
+        stream.flush().await?;
+
+        let size = stream.read_u32_le().await? as usize;
+        if size > MAX_RESPONSE_SIZE {
+            eyre::bail!("AESM response of {} bytes is too large", size);
+        }
+        let mut body = vec![0u8; size];
+        stream.read_exact(&mut body).await?;
+        Ok(Response::decode(body.as_slice())?)
+    }
+}
+
+fn check(error_code: u32) -> eyre::Result<()> {
+    match error_code {
+        0 => Ok(()),
+        code => Err(AesmError { code }.into()),
+    }
+}
+
+// The subset of the AESM protocol (`messages.proto` in linux-sgx) used for
+// ECDSA quotes.
+
+#[derive(Clone, PartialEq, Message)]
+struct Request {
+    #[prost(message, optional, tag = "15")]
+    init_quote_ex_req: Option<InitQuoteExRequest>,
+    #[prost(message, optional, tag = "16")]
+    get_quote_size_ex_req: Option<GetQuoteSizeExRequest>,
+    #[prost(message, optional, tag = "17")]
+    get_quote_ex_req: Option<GetQuoteExRequest>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct InitQuoteExRequest {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    att_key_id: Option<Vec<u8>>,
+    #[prost(bool, required, tag = "3")]
+    b_pub_key_id: bool,
+    #[prost(uint64, optional, tag = "4")]
+    buf_size: Option<u64>,
+    #[prost(uint32, optional, tag = "9")]
+    timeout: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct GetQuoteSizeExRequest {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    att_key_id: Option<Vec<u8>>,
+    #[prost(uint32, optional, tag = "9")]
+    timeout: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct GetQuoteExRequest {
+    #[prost(bytes = "vec", required, tag = "1")]
+    report: Vec<u8>,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    att_key_id: Option<Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    qe_report_info: Option<Vec<u8>>,
+    #[prost(uint32, required, tag = "4")]
+    buf_size: u32,
+    #[prost(uint32, optional, tag = "9")]
+    timeout: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Response {
+    #[prost(message, optional, tag = "15")]
+    init_quote_ex_res: Option<InitQuoteExResponse>,
+    #[prost(message, optional, tag = "16")]
+    get_quote_size_ex_res: Option<GetQuoteSizeExResponse>,
+    #[prost(message, optional, tag = "17")]
+    get_quote_ex_res: Option<GetQuoteExResponse>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct InitQuoteExResponse {
+    #[prost(uint32, required, tag = "1")]
+    error_code: u32,
+    #[prost(uint64, optional, tag = "2")]
+    pub_key_id_size: Option<u64>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub_key_id: Option<Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    target_info: Option<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct GetQuoteSizeExResponse {
+    #[prost(uint32, required, tag = "1")]
+    error_code: u32,
+    #[prost(uint32, optional, tag = "2")]
+    quote_size: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct GetQuoteExResponse {
+    #[prost(uint32, required, tag = "1")]
+    error_code: u32,
+    #[prost(bytes = "vec", optional, tag = "2")]
+    quote: Option<Vec<u8>>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    qe_report_info: Option<Vec<u8>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+
+    /// Serve `responses`, one per connection, and return the requests.
+    fn fake_aesm(
+        path: &std::path::Path,
+        responses: Vec<Response>,
+    ) -> std::thread::JoinHandle<Vec<Request>> {
+        let listener = UnixListener::bind(path).unwrap();
+        std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut size = [0u8; 4];
+                stream.read_exact(&mut size).unwrap();
+                let mut body = vec![0u8; u32::from_le_bytes(size) as usize];
+                stream.read_exact(&mut body).unwrap();
+                requests.push(Request::decode(body.as_slice()).unwrap());
+
+                let body = response.encode_to_vec();
+                stream
+                    .write_all(&(body.len() as u32).to_le_bytes())
+                    .unwrap();
+                stream.write_all(&body).unwrap();
+            }
+            requests
+        })
+    }
+
+    #[test]
+    fn test_ecdsa_att_key_id() {
+        let id = AttestationKeyId::ecdsa_p256();
+        assert_eq!(&id.0[4..6], &[32, 0]);
+        assert!(hex::encode(&id.0[6..38]).eq_ignore_ascii_case(crate::test_utils::QE_MRSIGNER));
+        assert_eq!(&id.0[154..158], &[2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_get_quote() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("aesm.socket");
+        let server = fake_aesm(
+            &path,
+            vec![
+                Response {
+                    get_quote_size_ex_res: Some(GetQuoteSizeExResponse {
+                        error_code: 0,
+                        quote_size: Some(4),
+                    }),
+                    ..Default::default()
+                },
+                Response {
+                    get_quote_ex_res: Some(GetQuoteExResponse {
+                        error_code: 0,
+                        quote: Some(vec![1, 2, 3, 4]),
+                        qe_report_info: None,
+                    }),
+                    ..Default::default()
+                },
+            ],
+        );
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let client = AesmClient::with_path(&path);
+        assert_eq!(
+            runtime.block_on(client.get_quote(&[0x5a; SGX_REPORT_SIZE]))?,
+            [1, 2, 3, 4]
+        );
+
+        let requests = server.join().unwrap();
+        let request = requests[1].get_quote_ex_req.as_ref().unwrap();
+        assert_eq!(request.report, [0x5a; SGX_REPORT_SIZE]);
+        assert_eq!(request.buf_size, 4);
+        assert_eq!(
+            request.att_key_id.as_deref(),
+            Some(&AttestationKeyId::ecdsa_p256().0[..])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_aesm_error() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("aesm.socket");
+        let server = fake_aesm(
+            &path,
+            vec![Response {
+                init_quote_ex_res: Some(InitQuoteExResponse {
+                    error_code: 30,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+        );
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let client = AesmClient::with_path(&path);
+        let err = runtime.block_on(client.init_quote()).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<AesmError>(),
+            Some(&AesmError { code: 30 })
+        );
+        server.join().unwrap();
+
+        assert!(runtime
+            .block_on(client.get_quote(&[0; SGX_REPORT_SIZE - 1]))
+            .is_err());
+        Ok(())
+    }
+}
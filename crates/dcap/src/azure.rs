@@ -0,0 +1,268 @@
+//! TDX evidence on Azure confidential VMs.
+//!
+//! The paravisor (HCL) does not expose the TDX guest device. Instead it
+//! publishes an HCL report in a vTPM NV index: the TDREPORT of the VM plus
+//! runtime data (a JSON document with the vTPM keys and user data) whose hash
+//! is the TDREPORT's report data. The TDREPORT is turned into a quote by the
+//! instance metadata service (IMDS).
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use tss_client::{nv_attributes, DeviceTransport, NvPublic, Transport, TssClient};
+use tss_serde::TssReader;
+
+use crate::quote::read_u32_le;
+use crate::{TdReport, VerificationResult, REPORT_DATA_SIZE, TDREPORT_SIZE};
+
+/// NV index holding the HCL report.
+pub const HCL_REPORT_NV_INDEX: u32 = 0x01400001;
+
+/// NV index the HCL report's user data is written to; writing it refreshes
+/// the report.
+pub const USER_DATA_NV_INDEX: u32 = 0x01400002;
+
+/// IMDS endpoint turning a TDREPORT into a quote.
+pub const AZURE_TD_QUOTE_URL: &str = "http://169.254.169.254/acc/tdquote";
+
+const HCL_REPORT_SIGNATURE: &[u8; 4] = b"HCLA";
+const HCL_HEADER_SIZE: usize = 32;
+/// Room reserved for the hardware report, the size of an SNP report.
+const HW_REPORT_SIZE: usize = 1184;
+
+/// `IGVM_REPORT_TYPE` of the hardware report in an HCL report.
+pub mod hcl_report_type {
+    pub const SNP: u32 = 2;
+    pub const TVM: u32 = 3;
+    pub const TDX: u32 = 4;
+}
+
+/// How the runtime data is hashed into the hardware report's report data.
+pub mod hcl_hash_type {
+    pub const SHA256: u32 = 1;
+    pub const SHA384: u32 = 2;
+    pub const SHA512: u32 = 3;
+}
+
+/// An HCL report, as read from [`HCL_REPORT_NV_INDEX`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HclReport {
+    pub report_type: u32,
+    pub hash_type: u32,
+    /// The hardware report; the TDREPORT for TDX, padded with zeros.
+    pub hw_report: Vec<u8>,
+    /// The JSON runtime claims covered by the hardware report.
+    pub runtime_data: Vec<u8>,
+}
+
+impl HclReport {
+    pub fn parse(bytes: &[u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+        if &reader.read_array::<4>()? != HCL_REPORT_SIGNATURE {
+            eyre::bail!("not an HCL report");
+        }
+        reader.skip(HCL_HEADER_SIZE - 4)?;
+        let hw_report = reader.read_bytes(HW_REPORT_SIZE)?;
+
+        let _data_size = read_u32_le(&mut reader)?;
+        let _version = read_u32_le(&mut reader)?;
+        let report_type = read_u32_le(&mut reader)?;
+        let hash_type = read_u32_le(&mut reader)?;
+        let runtime_data_size = read_u32_le(&mut reader)?;
+        let runtime_data = reader.read_bytes(runtime_data_size as usize)?;
+        Ok(Self {
+            report_type,
+            hash_type,
+            hw_report,
+            runtime_data,
+        })
+    }
+
+    /// The TDREPORT of a TDX VM.
+    pub fn tdreport(&self) -> eyre::Result<TdReport> {
+        if self.report_type != hcl_report_type::TDX {
+            eyre::bail!("HCL report is of type {}, not TDX", self.report_type);
+        }
+        TdReport::parse(&self.hw_report[..TDREPORT_SIZE])
+    }
+
+    /// The report data binding [`HclReport::runtime_data`].
+    pub fn expected_report_data(&self) -> eyre::Result<[u8; REPORT_DATA_SIZE]> {
+        let hash = match self.hash_type {
+            hcl_hash_type::SHA256 => Sha256::digest(&self.runtime_data).to_vec(),
+            hcl_hash_type::SHA384 => Sha384::digest(&self.runtime_data).to_vec(),
+            hcl_hash_type::SHA512 => Sha512::digest(&self.runtime_data).to_vec(),
+            other => eyre::bail!("unsupported HCL hash type {}", other),
+        };
+        let mut report_data = [0u8; REPORT_DATA_SIZE];
+        report_data[..hash.len()].copy_from_slice(&hash);
+        Ok(report_data)
+    }
+
+    /// The runtime claims, e.g. `user-data` and the vTPM `keys`.
+    pub fn runtime_claims(&self) -> eyre::Result<serde_json::Value> {
+        Ok(serde_json::from_slice(&self.runtime_data)?)
+    }
+}
+
+/// A TD quote and the HCL report whose runtime data it covers.
+#[derive(Debug, Clone)]
+pub struct AzureTdxEvidence {
+    pub quote: Vec<u8>,
+    pub hcl_report: HclReport,
+}
+
+impl AzureTdxEvidence {
+    /// Check that the verified quote covers the runtime data of the HCL
+    /// report, so its claims (vTPM keys, user data) can be trusted.
+    pub fn verify_runtime_data(&self, result: &VerificationResult) -> eyre::Result<()> {
+        if result.quote.body.report_data() != &self.hcl_report.expected_report_data()? {
+            eyre::bail!("quote does not cover the HCL runtime data");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct TdQuoteRequest {
+    report: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TdQuoteResponse {
+    quote: String,
+}
+
+/// Collects TDX evidence through the vTPM and IMDS of an Azure confidential
+/// VM.
+pub struct AzureVtpm<T> {
+    tpm: TssClient<T>,
+    http: reqwest::blocking::Client,
+    quote_url: String,
+}
+
+impl AzureVtpm<DeviceTransport> {
+    /// Use the vTPM through the kernel resource manager.
+    pub fn open() -> eyre::Result<Self> {
+        Ok(Self::new(TssClient::new(DeviceTransport::open_default()?)))
+    }
+}
+
+impl<T: Transport> AzureVtpm<T> {
+    pub fn new(tpm: TssClient<T>) -> Self {
+        Self {
+            tpm,
+            http: reqwest::blocking::Client::new(),
+            quote_url: AZURE_TD_QUOTE_URL.to_string(),
+        }
+    }
+
+    pub fn with_quote_url(mut self, url: impl Into<String>) -> Self {
+        self.quote_url = url.into();
+        self
+    }
+
+    /// Read the HCL report, after refreshing it with `user_data` if given.
+    pub fn hcl_report(&mut self, user_data: Option<&[u8; 64]>) -> eyre::Result<HclReport> {
+        if let Some(user_data) = user_data {
+            self.write_user_data(user_data)?;
+        }
+        HclReport::parse(&self.tpm.nv_read(HCL_REPORT_NV_INDEX)?)
+    }
+
+    /// Get a TD quote over a fresh HCL report carrying `user_data`.
+    pub fn tdx_evidence(&mut self, user_data: Option<&[u8; 64]>) -> eyre::Result<AzureTdxEvidence> {
+        let hcl_report = self.hcl_report(user_data)?;
+        let tdreport = hcl_report.tdreport()?;
+        if tdreport.report_data() != &hcl_report.expected_report_data()? {
+            eyre::bail!("TDREPORT does not cover the HCL runtime data");
+        }
+        let quote = self.td_quote(&hcl_report.hw_report[..TDREPORT_SIZE])?;
+        Ok(AzureTdxEvidence { quote, hcl_report })
+    }
+
+    /// Have IMDS turn `tdreport` into a quote.
+    pub fn td_quote(&self, tdreport: &[u8]) -> eyre::Result<Vec<u8>> {
+        let response: TdQuoteResponse = self
+            .http
+            .post(&self.quote_url)
+            .json(&td_quote_request(tdreport))
+            .send()?
+            .error_for_status()?
+            .json()?;
+        Base64UrlUnpadded::decode_vec(response.quote.trim_end_matches('='))
+            .map_err(|_| eyre::eyre!("IMDS returned a malformed quote"))
+    }
+
+    fn write_user_data(&mut self, user_data: &[u8; 64]) -> eyre::Result<()> {
+        if self.tpm.nv_read_public(USER_DATA_NV_INDEX).is_err() {
+            self.tpm.nv_define_space(NvPublic {
+                nv_index: USER_DATA_NV_INDEX,
+                name_alg: tss_client::algorithms::SHA256,
+                attributes: nv_attributes::OWNERWRITE
+                    | nv_attributes::AUTHWRITE
+                    | nv_attributes::OWNERREAD
+                    | nv_attributes::AUTHREAD,
+                auth_policy: Vec::new(),
+                data_size: user_data.len() as u16,
+            })?;
+        }
+        self.tpm.nv_write(USER_DATA_NV_INDEX, user_data)
+    }
+}
+
+fn td_quote_request(tdreport: &[u8]) -> TdQuoteRequest {
+    TdQuoteRequest {
+        report: Base64UrlUnpadded::encode_string(tdreport),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hcl_report(runtime_data: &[u8]) -> Vec<u8> {
+        let mut report_data = [0u8; 64];
+        report_data[..32].copy_from_slice(&Sha256::digest(runtime_data));
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(HCL_REPORT_SIGNATURE);
+        bytes.extend_from_slice(&[0; HCL_HEADER_SIZE - 4]);
+        let mut hw_report = [0u8; HW_REPORT_SIZE];
+        hw_report[0] = 0x81;
+        hw_report[128..192].copy_from_slice(&report_data);
+        bytes.extend_from_slice(&hw_report);
+        bytes.extend_from_slice(&(20 + runtime_data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&hcl_report_type::TDX.to_le_bytes());
+        bytes.extend_from_slice(&hcl_hash_type::SHA256.to_le_bytes());
+        bytes.extend_from_slice(&(runtime_data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(runtime_data);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_hcl_report() -> eyre::Result<()> {
+        let runtime_data = br#"{"user-data":"00"}"#;
+        let report = HclReport::parse(&hcl_report(runtime_data))?;
+        assert_eq!(report.runtime_data, runtime_data);
+        assert_eq!(report.runtime_claims()?["user-data"], "00");
+
+        let tdreport = report.tdreport()?;
+        assert_eq!(tdreport.report_mac.report_type[0], 0x81);
+        assert_eq!(tdreport.report_data(), &report.expected_report_data()?);
+
+        let mut other = report.clone();
+        other.report_type = hcl_report_type::SNP;
+        assert!(other.tdreport().is_err());
+        assert!(HclReport::parse(&hcl_report(runtime_data)[1..]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_td_quote_request() -> eyre::Result<()> {
+        let request = serde_json::to_value(td_quote_request(&[0xfb; 3]))?;
+        assert_eq!(request, serde_json::json!({"report": "-_v7"}));
+        Ok(())
+    }
+}
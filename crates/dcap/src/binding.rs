@@ -0,0 +1,95 @@
+//! Binding a verifier nonce and a public key into report data.
+//!
+//! The layout is fixed so that producer and verifier share one definition:
+//! bytes 0..32 hold the SHA-256 of the public key and bytes 32..64 the
+//! nonce. An unused half is all zeros.
+
+use sha2::{Digest, Sha256};
+
+use crate::{ReportData, VerificationResult, REPORT_DATA_SIZE};
+
+/// Size in bytes of a binding nonce.
+pub const NONCE_SIZE: usize = 32;
+
+const KEY_HASH_RANGE: std::ops::Range<usize> = 0..32;
+const NONCE_RANGE: std::ops::Range<usize> = 32..REPORT_DATA_SIZE;
+
+impl ReportData {
+    /// Report data binding `nonce` and `public_key`, in whatever encoding the
+    /// verifier will see it (e.g. SubjectPublicKeyInfo DER).
+    pub fn bind(nonce: Option<&[u8; NONCE_SIZE]>, public_key: Option<&[u8]>) -> Self {
+        let mut report_data = [0u8; REPORT_DATA_SIZE];
+        if let Some(public_key) = public_key {
+            report_data[KEY_HASH_RANGE].copy_from_slice(&Sha256::digest(public_key));
+        }
+        if let Some(nonce) = nonce {
+            report_data[NONCE_RANGE].copy_from_slice(nonce);
+        }
+        Self(report_data)
+    }
+
+    /// Check that the report data is exactly [`ReportData::bind`] of `nonce`
+    /// and `public_key`.
+    pub fn verify_binding(
+        &self,
+        nonce: Option<&[u8; NONCE_SIZE]>,
+        public_key: Option<&[u8]>,
+    ) -> eyre::Result<()> {
+        let expected = Self::bind(nonce, public_key);
+        if !constant_time_eq(&self.0[KEY_HASH_RANGE], &expected.0[KEY_HASH_RANGE]) {
+            eyre::bail!("report data is not bound to the expected public key");
+        }
+        if !constant_time_eq(&self.0[NONCE_RANGE], &expected.0[NONCE_RANGE]) {
+            eyre::bail!("report data is not bound to the expected nonce");
+        }
+        Ok(())
+    }
+}
+
+impl VerificationResult {
+    /// Check the report data of the verified quote with
+    /// [`ReportData::verify_binding`].
+    pub fn verify_binding(
+        &self,
+        nonce: Option<&[u8; NONCE_SIZE]>,
+        public_key: Option<&[u8]>,
+    ) -> eyre::Result<()> {
+        ReportData(*self.quote.body.report_data()).verify_binding(nonce, public_key)
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_layout() {
+        let report_data = ReportData::bind(Some(&[7; NONCE_SIZE]), Some(b"key"));
+        assert_eq!(report_data.0[..32], Sha256::digest(b"key")[..]);
+        assert_eq!(report_data.0[32..], [7; NONCE_SIZE]);
+
+        let nonce_only = ReportData::bind(Some(&[7; NONCE_SIZE]), None);
+        assert_eq!(nonce_only.0[..32], [0; 32]);
+    }
+
+    #[test]
+    fn test_verify_binding() -> eyre::Result<()> {
+        let nonce = [7; NONCE_SIZE];
+        let report_data = ReportData::bind(Some(&nonce), Some(b"key"));
+        report_data.verify_binding(Some(&nonce), Some(b"key"))?;
+
+        assert!(report_data
+            .verify_binding(Some(&[8; NONCE_SIZE]), Some(b"key"))
+            .is_err());
+        assert!(report_data
+            .verify_binding(Some(&nonce), Some(b"other key"))
+            .is_err());
+        // A binding cannot be checked partially.
+        assert!(report_data.verify_binding(Some(&nonce), None).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::{tee_type, PckCaType, PckChain, Quote, QuoteCollateral};
+
+/// Default time before `nextUpdate` at which cached collateral is refreshed.
+pub const DEFAULT_REFRESH_MARGIN: chrono::Duration = chrono::Duration::hours(1);
+
+/// Identifies the collateral of a platform: the TCB Info is per FMSPC and the
+/// PCK CRL per issuing CA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CollateralKey {
+    /// [`tee_type::SGX`] or [`tee_type::TDX`].
+    pub tee_type: u32,
+    pub fmspc: [u8; 6],
+    pub ca: PckCaType,
+}
+
+impl CollateralKey {
+    /// The key of the collateral needed to verify `quote`, read from its PCK
+    /// certificate.
+    pub fn from_quote(quote: &Quote) -> eyre::Result<Self> {
+        Self::from_pck_chain(quote.header.tee_type, &PckChain::from_quote(quote)?)
+    }
+
+    /// The key of the collateral needed to verify quotes of `tee_type` signed
+    /// through `chain`, for quotes that do not embed their PCK chain.
+    pub fn from_pck_chain(tee_type: u32, chain: &PckChain) -> eyre::Result<Self> {
+        Ok(Self {
+            tee_type,
+            fmspc: chain.sgx_extensions()?.fmspc,
+            ca: chain.ca_type()?,
+        })
+    }
+
+    /// The key of the platform `collateral` was issued for, read from its
+    /// TCB Info and PCK CRL.
+    pub fn from_collateral(collateral: &QuoteCollateral) -> eyre::Result<Self> {
+        let fmspc = hex::decode(&collateral.tcb_info()?.tcb_info.fmspc)?;
+        Ok(Self {
+            tee_type: collateral.tee_type,
+            fmspc: fmspc
+                .try_into()
+                .map_err(|_| eyre::eyre!("FMSPC must be 6 bytes"))?,
+            ca: PckCaType::from_subject(collateral.pck_crl()?.issuer())?,
+        })
+    }
+
+    fn file_name(&self) -> String {
+        let tee = match self.tee_type {
+            tee_type::SGX => "sgx".to_string(),
+            tee_type::TDX => "tdx".to_string(),
+            other => format!("{:08x}", other),
+        };
+        format!(
+            "{}-{}-{}.bin",
+            tee,
+            hex::encode(self.fmspc),
+            self.ca.as_str()
+        )
+    }
+}
+
+impl std::fmt::Display for CollateralKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tee = match self.tee_type {
+            tee_type::SGX => "SGX",
+            tee_type::TDX => "TDX",
+            _ => "unknown TEE",
+        };
+        write!(
+            f,
+            "{} FMSPC {} ({} CA)",
+            tee,
+            hex::encode(self.fmspc),
+            self.ca.as_str()
+        )
+    }
+}
+
+/// Source of fresh collateral for a [`CollateralCache`], typically a PCS or
+/// PCCS client.
+pub trait CollateralFetcher {
+    fn fetch_collateral(&self, key: &CollateralKey) -> eyre::Result<QuoteCollateral>;
+}
+
+impl<F> CollateralFetcher for F
+where
+    F: Fn(&CollateralKey) -> eyre::Result<QuoteCollateral>,
+{
+    fn fetch_collateral(&self, key: &CollateralKey) -> eyre::Result<QuoteCollateral> {
+        self(key)
+    }
+}
+
+/// Called with the outcome of every fetch made by a [`CollateralCache`].
+pub type RefreshHook =
+    Box<dyn Fn(&CollateralKey, &eyre::Result<Arc<QuoteCollateral>>) + Send + Sync>;
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    collateral: Arc<QuoteCollateral>,
+    next_update: DateTime<Utc>,
+}
+
+/// Keeps collateral in memory, and optionally on disk, until shortly before
+/// its `nextUpdate`, so verifiers only go to the PCS when something changed.
+///
+/// Lookups fetch on a miss or once an entry is within the refresh margin of
+/// its `nextUpdate`. To keep fetches off the verification path, call
+/// [`CollateralCache::refresh_expiring`] periodically or start
+/// [`CollateralCache::spawn_refresher`].
+pub struct CollateralCache<F> {
+    fetcher: F,
+    entries: Mutex<HashMap<CollateralKey, CacheEntry>>,
+    store: Option<PathBuf>,
+    refresh_margin: chrono::Duration,
+    on_refresh: Option<RefreshHook>,
+}
+
+impl<F: CollateralFetcher> CollateralCache<F> {
+    pub fn new(fetcher: F) -> Self {
+        Self {
+            fetcher,
+            entries: Mutex::new(HashMap::new()),
+            store: None,
+            refresh_margin: DEFAULT_REFRESH_MARGIN,
+            on_refresh: None,
+        }
+    }
+
+    /// Persist collateral under `dir`, so it survives restarts. The directory
+    /// is created on first write.
+    pub fn with_store(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.store = Some(dir.into());
+        self
+    }
+
+    pub fn with_refresh_margin(mut self, margin: chrono::Duration) -> Self {
+        self.refresh_margin = margin;
+        self
+    }
+
+    pub fn with_refresh_hook(
+        mut self,
+        hook: impl Fn(&CollateralKey, &eyre::Result<Arc<QuoteCollateral>>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_refresh = Some(Box::new(hook));
+        self
+    }
+
+    pub fn fetcher(&self) -> &F {
+        &self.fetcher
+    }
+
+    /// The collateral for `key`, fetched if missing or about to expire.
+    ///
+    /// If the fetch fails, a cached copy is returned as long as it has not
+    /// passed its `nextUpdate`.
+    pub fn get(&self, key: &CollateralKey) -> eyre::Result<Arc<QuoteCollateral>> {
+        self.get_at(key, Utc::now())
+    }
+
+    /// Like [`CollateralCache::get`], at `at` instead of the current time.
+    pub fn get_at(
+        &self,
+        key: &CollateralKey,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<Arc<QuoteCollateral>> {
+        let mut cached = self.entries().get(key).cloned();
+        if cached.is_none() {
+            cached = self.load(key);
+            if let Some(entry) = &cached {
+                self.entries().insert(*key, entry.clone());
+            }
+        }
+
+        match cached {
+            Some(entry) if !self.is_due(&entry, at) => Ok(entry.collateral),
+            Some(entry) => self.refresh(key).or_else(|err| {
+                if at <= entry.next_update {
+                    Ok(entry.collateral)
+                } else {
+                    Err(err)
+                }
+            }),
+            None => self.refresh(key),
+        }
+    }
+
+    /// Fetch `key` now, replacing any cached copy.
+    pub fn refresh(&self, key: &CollateralKey) -> eyre::Result<Arc<QuoteCollateral>> {
+        let result = self.fetch(key);
+        if let Some(hook) = &self.on_refresh {
+            hook(key, &result);
+        }
+        result
+    }
+
+    /// Refresh every cached entry within the refresh margin of its
+    /// `nextUpdate` at `at`, and return the keys that failed to refresh.
+    ///
+    /// Failed entries stay cached and are retried on the next call.
+    pub fn refresh_expiring(&self, at: DateTime<Utc>) -> Vec<(CollateralKey, eyre::Report)> {
+        let due: Vec<CollateralKey> = self
+            .entries()
+            .iter()
+            .filter(|(_, entry)| self.is_due(entry, at))
+            .map(|(key, _)| *key)
+            .collect();
+        due.into_iter()
+            .filter_map(|key| self.refresh(&key).err().map(|err| (key, err)))
+            .collect()
+    }
+
+    /// Refresh every cached entry now, whatever its `nextUpdate`, e.g. when
+    /// Intel publishes TCB info ahead of schedule, and return the keys that
+    /// failed to refresh. Failed entries keep their cached copy.
+    pub fn refresh_all(&self) -> Vec<(CollateralKey, eyre::Report)> {
+        self.keys()
+            .into_iter()
+            .filter_map(|key| self.refresh(&key).err().map(|err| (key, err)))
+            .collect()
+    }
+
+    /// Drop `key` from memory and from the store.
+    pub fn invalidate(&self, key: &CollateralKey) {
+        self.entries().remove(key);
+        if let Some(dir) = &self.store {
+            let _ = std::fs::remove_file(dir.join(key.file_name()));
+        }
+    }
+
+    /// The keys currently held in memory.
+    pub fn keys(&self) -> Vec<CollateralKey> {
+        self.entries().keys().copied().collect()
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, HashMap<CollateralKey, CacheEntry>> {
+        self.entries.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn is_due(&self, entry: &CacheEntry, at: DateTime<Utc>) -> bool {
+        at + self.refresh_margin >= entry.next_update
+    }
+
+    fn fetch(&self, key: &CollateralKey) -> eyre::Result<Arc<QuoteCollateral>> {
+        let collateral = self.fetcher.fetch_collateral(key)?;
+        let entry = CacheEntry {
+            next_update: collateral.next_update()?,
+            collateral: Arc::new(collateral),
+        };
+        if let Some(dir) = &self.store {
+            store(dir, key, &entry.collateral)?;
+        }
+        let collateral = entry.collateral.clone();
+        self.entries().insert(*key, entry);
+        Ok(collateral)
+    }
+
+    /// Read `key` from the store. Unreadable files are ignored and replaced
+    /// on the next fetch.
+    fn load(&self, key: &CollateralKey) -> Option<CacheEntry> {
+        let bytes = std::fs::read(self.store.as_ref()?.join(key.file_name())).ok()?;
+        let collateral = QuoteCollateral::from_bytes(&bytes).ok()?;
+        Some(CacheEntry {
+            next_update: collateral.next_update().ok()?,
+            collateral: Arc::new(collateral),
+        })
+    }
+}
+
+/// Lets a cache stand in wherever a fetcher is expected, e.g. behind a
+/// verifier, so lookups go through the cache.
+impl<F: CollateralFetcher> CollateralFetcher for CollateralCache<F> {
+    fn fetch_collateral(&self, key: &CollateralKey) -> eyre::Result<QuoteCollateral> {
+        Ok(self.get(key)?.as_ref().clone())
+    }
+}
+
+impl<F: CollateralFetcher + Send + Sync + 'static> CollateralCache<F> {
+    /// Run [`CollateralCache::refresh_expiring`] every `interval` on a
+    /// background thread. Failures are reported through the refresh hook.
+    ///
+    /// The thread exits once the cache is dropped.
+    pub fn spawn_refresher(self: &Arc<Self>, interval: Duration) -> std::thread::JoinHandle<()> {
+        let cache: Weak<Self> = Arc::downgrade(self);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(interval);
+            let Some(cache) = cache.upgrade() else {
+                return;
+            };
+            cache.refresh_expiring(Utc::now());
+        })
+    }
+}
+
+/// Write `collateral` to the store, replacing the previous copy atomically.
+fn store(dir: &Path, key: &CollateralKey, collateral: &QuoteCollateral) -> eyre::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(key.file_name());
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, collateral.to_bytes())?;
+    std::fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{quote_collateral, sgx_quote, verification_time, TestPki};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingFetcher {
+        collateral: QuoteCollateral,
+        fetches: AtomicUsize,
+    }
+
+    impl CollateralFetcher for CountingFetcher {
+        fn fetch_collateral(&self, _key: &CollateralKey) -> eyre::Result<QuoteCollateral> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            Ok(self.collateral.clone())
+        }
+    }
+
+    fn fetcher() -> CountingFetcher {
+        CountingFetcher {
+            collateral: quote_collateral(&TestPki::new(), tee_type::SGX),
+            fetches: AtomicUsize::new(0),
+        }
+    }
+
+    fn key() -> CollateralKey {
+        CollateralKey {
+            tee_type: tee_type::SGX,
+            fmspc: [0x00, 0x90, 0x6E, 0xA1, 0x00, 0x00],
+            ca: PckCaType::Platform,
+        }
+    }
+
+    #[test]
+    fn test_key_from_quote() -> eyre::Result<()> {
+        let quote = Quote::parse(&sgx_quote(&TestPki::new()))?;
+        assert_eq!(CollateralKey::from_quote(&quote)?, key());
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_from_collateral() -> eyre::Result<()> {
+        let collateral = quote_collateral(&TestPki::new(), tee_type::SGX);
+        assert_eq!(CollateralKey::from_collateral(&collateral)?, key());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reuses_until_next_update() -> eyre::Result<()> {
+        let cache = CollateralCache::new(fetcher());
+        let next_update = cache.fetcher().collateral.next_update()?;
+
+        let first = cache.get_at(&key(), verification_time())?;
+        let second = cache.get_at(&key(), verification_time())?;
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.fetcher().fetches.load(Ordering::SeqCst), 1);
+
+        // Within the refresh margin of nextUpdate.
+        cache.get_at(&key(), next_update - chrono::Duration::minutes(5))?;
+        assert_eq!(cache.fetcher().fetches.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_refresh_expiring() -> eyre::Result<()> {
+        let refreshed = Arc::new(AtomicUsize::new(0));
+        let hook_count = refreshed.clone();
+        let cache = CollateralCache::new(fetcher()).with_refresh_hook(move |_, result| {
+            assert!(result.is_ok());
+            hook_count.fetch_add(1, Ordering::SeqCst);
+        });
+        let next_update = cache.fetcher().collateral.next_update()?;
+        cache.get_at(&key(), verification_time())?;
+
+        assert!(cache.refresh_expiring(verification_time()).is_empty());
+        assert_eq!(refreshed.load(Ordering::SeqCst), 1);
+        assert!(cache.refresh_expiring(next_update).is_empty());
+        assert_eq!(refreshed.load(Ordering::SeqCst), 2);
+
+        // Ahead of nextUpdate.
+        assert!(cache.refresh_all().is_empty());
+        assert_eq!(refreshed.load(Ordering::SeqCst), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_disk_store() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cache = CollateralCache::new(fetcher()).with_store(dir.path());
+        let collateral = cache.get_at(&key(), verification_time())?;
+        assert!(dir.path().join("sgx-00906ea10000-platform.bin").exists());
+
+        // A new process starts from the stored copy.
+        let restarted = CollateralCache::new(fetcher()).with_store(dir.path());
+        assert_eq!(restarted.get_at(&key(), verification_time())?, collateral);
+        assert_eq!(restarted.fetcher().fetches.load(Ordering::SeqCst), 0);
+
+        restarted.invalidate(&key());
+        assert!(!dir.path().join("sgx-00906ea10000-platform.bin").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_serves_cached_copy_when_fetch_fails() -> eyre::Result<()> {
+        let collateral = quote_collateral(&TestPki::new(), tee_type::SGX);
+        let next_update = collateral.next_update()?;
+        let available = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let fetcher_available = available.clone();
+        let cache = CollateralCache::new(move |_: &CollateralKey| {
+            if !fetcher_available.load(Ordering::SeqCst) {
+                eyre::bail!("PCS unavailable");
+            }
+            Ok(collateral.clone())
+        });
+        cache.get_at(&key(), verification_time())?;
+
+        available.store(false, Ordering::SeqCst);
+        assert_eq!(cache.refresh_expiring(next_update).len(), 1);
+        assert_eq!(cache.refresh_all().len(), 1);
+        cache.get_at(&key(), next_update)?;
+        assert!(cache
+            .get_at(&key(), next_update + chrono::Duration::seconds(1))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_failure_is_reported() {
+        let cache = CollateralCache::new(|_: &CollateralKey| -> eyre::Result<QuoteCollateral> {
+            eyre::bail!("PCS unavailable")
+        });
+        assert!(cache.get_at(&key(), verification_time()).is_err());
+        assert!(cache.keys().is_empty());
+    }
+}
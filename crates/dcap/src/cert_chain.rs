@@ -0,0 +1,335 @@
+use chrono::{DateTime, Utc};
+use der::asn1::ObjectIdentifier;
+use der::{DecodePem, Encode};
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::DerSignature;
+use x509_cert::ext::pkix::{BasicConstraints, KeyUsage};
+use x509_cert::time::Time;
+use x509_cert::Certificate;
+
+use crate::crypto::verifying_key_from_certificate;
+use crate::PckChain;
+
+/// The Intel SGX Root CA certificate that anchors all PCK and TCB signing
+/// chains in production.
+pub const INTEL_SGX_ROOT_CA_PEM: &str = include_str!("data/intel_sgx_root_ca.pem");
+
+/// `ecdsa-with-SHA256`
+pub const ECDSA_WITH_SHA256_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+
+/// Common name of the certificate signing TCB Info and QE Identities.
+pub const TCB_SIGNING_COMMON_NAME: &str = "Intel SGX TCB Signing";
+
+/// The pinned Intel SGX Root CA certificate.
+pub fn intel_sgx_root_ca() -> Certificate {
+    Certificate::from_pem(INTEL_SGX_ROOT_CA_PEM).expect("embedded Intel SGX Root CA is valid")
+}
+
+/// The root certificates accepted as anchors of PCK and collateral signing
+/// chains.
+///
+/// More than one root allows pre-production platforms or a root rotation;
+/// the default only trusts the production Intel SGX Root CA.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustAnchors {
+    roots: Vec<Certificate>,
+}
+
+impl Default for TrustAnchors {
+    fn default() -> Self {
+        Self::from_root(intel_sgx_root_ca())
+    }
+}
+
+impl TrustAnchors {
+    pub fn new(roots: Vec<Certificate>) -> eyre::Result<Self> {
+        if roots.is_empty() {
+            eyre::bail!("no trust anchors");
+        }
+        Ok(Self { roots })
+    }
+
+    pub fn from_root(root: Certificate) -> Self {
+        Self { roots: vec![root] }
+    }
+
+    /// Trust all certificates of a PEM bundle.
+    pub fn from_pem(pem: &[u8]) -> eyre::Result<Self> {
+        let roots = Certificate::load_pem_chain(pem)
+            .map_err(|err| eyre::eyre!("invalid trust anchors: {}", err))?;
+        Self::new(roots)
+    }
+
+    /// Also trust `root`.
+    pub fn with_root(mut self, root: Certificate) -> Self {
+        if !self.roots.contains(&root) {
+            self.roots.push(root);
+        }
+        self
+    }
+
+    pub fn roots(&self) -> &[Certificate] {
+        &self.roots
+    }
+
+    /// The first root `chain` validates against at `at`.
+    pub fn select(&self, chain: &[Certificate], at: DateTime<Utc>) -> eyre::Result<&Certificate> {
+        let mut last_err = None;
+        for root in &self.roots {
+            match validate_certificate_chain(chain, root, at) {
+                Ok(()) => return Ok(root),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err
+            .unwrap_or_else(|| eyre::eyre!("no trust anchors"))
+            .wrap_err("certificate chain is not anchored in any trusted root"))
+    }
+}
+
+/// Validate a certificate chain, ordered leaf first, up to `root`.
+///
+/// The chain may or may not include the root itself; if it does, it must be
+/// byte-for-byte identical to `root`. Every certificate (including the root)
+/// must be valid at `at`, every issuer must be a CA allowed to sign
+/// certificates, and every signature must be ECDSA P-256 with SHA-256.
+pub fn validate_certificate_chain(
+    chain: &[Certificate],
+    root: &Certificate,
+    at: DateTime<Utc>,
+) -> eyre::Result<()> {
+    let Some(last) = chain.last() else {
+        eyre::bail!("certificate chain is empty");
+    };
+
+    let mut path: Vec<&Certificate> = chain.iter().collect();
+    if last != root {
+        if last.tbs_certificate.subject == root.tbs_certificate.subject {
+            eyre::bail!("certificate chain is anchored in an untrusted root");
+        }
+        path.push(root);
+    }
+
+    check_validity(root, at)?;
+    verify_issued_by(root, root)?;
+    check_issuer_constraints(root, path.len().saturating_sub(2))?;
+
+    for (depth, pair) in path.windows(2).enumerate() {
+        let (cert, issuer) = (pair[0], pair[1]);
+        check_validity(cert, at)?;
+        if depth > 0 {
+            check_issuer_constraints(cert, depth - 1)?;
+        }
+        verify_issued_by(cert, issuer)?;
+    }
+
+    Ok(())
+}
+
+impl PckChain {
+    /// Validate the chain against the pinned Intel SGX Root CA.
+    pub fn validate(&self, at: DateTime<Utc>) -> eyre::Result<()> {
+        self.validate_with_root(&intel_sgx_root_ca(), at)
+    }
+
+    /// Validate the chain against a custom root, e.g. for pre-production
+    /// environments.
+    pub fn validate_with_root(&self, root: &Certificate, at: DateTime<Utc>) -> eyre::Result<()> {
+        validate_certificate_chain(self.certificates(), root, at)
+    }
+}
+
+pub(crate) fn time_to_datetime(time: &Time) -> DateTime<Utc> {
+    let secs = time.to_unix_duration().as_secs();
+    DateTime::from_timestamp(secs as i64, 0).unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+fn subject(cert: &Certificate) -> String {
+    cert.tbs_certificate.subject.to_string()
+}
+
+/// Check that `cert` is valid at `at`.
+pub fn check_validity(cert: &Certificate, at: DateTime<Utc>) -> eyre::Result<()> {
+    let validity = &cert.tbs_certificate.validity;
+    let not_before = time_to_datetime(&validity.not_before);
+    let not_after = time_to_datetime(&validity.not_after);
+    if at < not_before || at > not_after {
+        eyre::bail!(
+            "certificate {} is not valid at {} (valid from {} to {})",
+            subject(cert),
+            at,
+            not_before,
+            not_after
+        );
+    }
+    Ok(())
+}
+
+/// Check that `cert` is an Intel SGX TCB Signing certificate, the only one
+/// allowed to sign TCB Info and QE Identities. Its chain is checked
+/// separately.
+pub(crate) fn check_tcb_signer(cert: &Certificate) -> eyre::Result<()> {
+    let common_name = format!("CN={}", TCB_SIGNING_COMMON_NAME);
+    let is_tcb_signing = cert
+        .tbs_certificate
+        .subject
+        .0
+        .iter()
+        .flat_map(|rdn| rdn.0.iter())
+        .any(|attribute| attribute.to_string() == common_name);
+    if !is_tcb_signing {
+        eyre::bail!("{} is not a TCB signing certificate", subject(cert));
+    }
+    let is_ca = cert
+        .tbs_certificate
+        .get::<BasicConstraints>()?
+        .is_some_and(|(_, constraints)| constraints.ca);
+    if is_ca {
+        eyre::bail!("TCB signing certificate {} is a CA", subject(cert));
+    }
+    Ok(())
+}
+
+/// Check that `cert` may issue certificates, with `intermediates_below` CA
+/// certificates between it and the leaf.
+pub fn check_issuer_constraints(
+    cert: &Certificate,
+    intermediates_below: usize,
+) -> eyre::Result<()> {
+    let constraints = cert
+        .tbs_certificate
+        .get::<BasicConstraints>()?
+        .map(|(_, constraints)| constraints);
+    match constraints {
+        Some(constraints) if constraints.ca => {
+            if let Some(path_len) = constraints.path_len_constraint {
+                if intermediates_below > path_len as usize {
+                    eyre::bail!("path length constraint of {} exceeded", subject(cert));
+                }
+            }
+        }
+        _ => eyre::bail!("issuer {} is not a CA", subject(cert)),
+    }
+
+    if let Some((_, key_usage)) = cert.tbs_certificate.get::<KeyUsage>()? {
+        if !key_usage.key_cert_sign() {
+            eyre::bail!("issuer {} may not sign certificates", subject(cert));
+        }
+    }
+    Ok(())
+}
+
+/// Verify that `issuer` signed `cert`.
+pub(crate) fn verify_issued_by(cert: &Certificate, issuer: &Certificate) -> eyre::Result<()> {
+    if cert.tbs_certificate.issuer != issuer.tbs_certificate.subject {
+        eyre::bail!(
+            "certificate {} was not issued by {}",
+            subject(cert),
+            subject(issuer)
+        );
+    }
+    if cert.signature_algorithm.oid != ECDSA_WITH_SHA256_OID {
+        eyre::bail!(
+            "unsupported signature algorithm {} on {}",
+            cert.signature_algorithm.oid,
+            subject(cert)
+        );
+    }
+
+    let key = verifying_key_from_certificate(issuer)?;
+    let signature = cert
+        .signature
+        .as_bytes()
+        .ok_or_else(|| eyre::eyre!("malformed certificate signature"))?;
+    let signature = DerSignature::from_bytes(signature)
+        .map_err(|_| eyre::eyre!("malformed certificate signature"))?;
+    let tbs = cert.tbs_certificate.to_der()?;
+    key.verify(&tbs, &signature)
+        .map_err(|_| eyre::eyre!("invalid signature on certificate {}", subject(cert)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{build_certificate, signing_key, to_pem, TestPki, ROOT_SUBJECT};
+    use x509_cert::builder::Profile;
+
+    fn at(timestamp: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(timestamp, 0).unwrap()
+    }
+
+    #[test]
+    fn test_intel_root_ca_is_self_signed() -> eyre::Result<()> {
+        let root = intel_sgx_root_ca();
+        validate_certificate_chain(std::slice::from_ref(&root), &root, at(1_700_000_000))
+    }
+
+    #[test]
+    fn test_validate_pck_chain() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let chain = PckChain::from_pem(pki.pck_chain_pem().as_bytes())?;
+        chain.validate_with_root(&pki.root_cert, at(1_700_000_000))?;
+
+        // Without the root in the chain.
+        validate_certificate_chain(
+            &chain.certificates()[..2],
+            &pki.root_cert,
+            at(1_700_000_000),
+        )
+    }
+
+    #[test]
+    fn test_validate_rejects_untrusted_root() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let chain = PckChain::from_pem(pki.pck_chain_pem().as_bytes())?;
+        assert!(chain.validate(at(1_700_000_000)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_trust_anchors_select_root() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let key = signing_key(9);
+        let other = build_certificate(Profile::Root, 1, ROOT_SUBJECT, &key, &key);
+        let chain = PckChain::from_pem(pki.pck_chain_pem().as_bytes())?;
+        let at = crate::test_utils::verification_time();
+
+        assert!(TrustAnchors::default()
+            .select(chain.certificates(), at)
+            .is_err());
+        let anchors = TrustAnchors::default()
+            .with_root(other.clone())
+            .with_root(pki.root_cert.clone());
+        assert_eq!(anchors.roots().len(), 3);
+        assert_eq!(anchors.select(chain.certificates(), at)?, &pki.root_cert);
+
+        let bundle = [pki.root_pem(), to_pem(&other)].concat();
+        let anchors = TrustAnchors::from_pem(bundle.as_bytes())?;
+        assert_eq!(anchors.roots().len(), 2);
+        assert!(TrustAnchors::new(Vec::new()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_rejects_expired_chain() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let chain = PckChain::from_pem(pki.pck_chain_pem().as_bytes())?;
+        assert!(chain
+            .validate_with_root(&pki.root_cert, at(1_500_000_000))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_rejects_leaf_as_issuer() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        assert!(validate_certificate_chain(
+            &[pki.intermediate_cert.clone(), pki.pck_cert.clone()],
+            &pki.root_cert,
+            at(1_700_000_000),
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tss_serde::TssReader;
+use x509_cert::Certificate;
+
+use crate::primitives::identity::EnclaveIdentityV2;
+use crate::primitives::signed::{SignedEnclaveIdentity, SignedTcbInfo};
+use crate::primitives::tcb_info::TcbInfo;
+use crate::quote::{read_u16_le, read_u32_le};
+use crate::Crl;
+
+/// Size of the fixed part of the binary collateral encoding: version, TEE
+/// type and seven field sizes.
+pub const QUOTE_COLLATERAL_HEADER_SIZE: usize = 2 + 2 + 4 + 7 * 4;
+
+/// The collateral needed to verify a quote, mirroring the C DCAP
+/// `sgx_ql_qve_collateral_t`.
+///
+/// Fields hold the raw bytes as served by the PCS, so signatures can be
+/// checked over them. C strings may keep their trailing NUL.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuoteCollateral {
+    pub major_version: u16,
+    pub minor_version: u16,
+    /// [`crate::tee_type::SGX`] or [`crate::tee_type::TDX`].
+    pub tee_type: u32,
+    #[serde(with = "hex_bytes")]
+    pub pck_crl_issuer_chain: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub root_ca_crl: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub pck_crl: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub tcb_info_issuer_chain: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub tcb_info: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub qe_identity_issuer_chain: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub qe_identity: Vec<u8>,
+}
+
+impl QuoteCollateral {
+    /// Decode the flat binary encoding of `sgx_ql_qve_collateral_t`: the
+    /// little-endian version, TEE type and field sizes, followed by the field
+    /// contents in declaration order.
+    pub fn from_bytes(bytes: &[u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+        let major_version = read_u16_le(&mut reader)?;
+        let minor_version = read_u16_le(&mut reader)?;
+        let tee_type = read_u32_le(&mut reader)?;
+
+        let mut sizes = [0usize; 7];
+        for size in sizes.iter_mut() {
+            *size = read_u32_le(&mut reader)? as usize;
+        }
+        let total = sizes.iter().sum::<usize>();
+        if reader.remaining() != total {
+            eyre::bail!(
+                "collateral fields take {} bytes, found {}",
+                total,
+                reader.remaining()
+            );
+        }
+        Ok(Self {
+            major_version,
+            minor_version,
+            tee_type,
+            pck_crl_issuer_chain: reader.read_bytes(sizes[0])?,
+            root_ca_crl: reader.read_bytes(sizes[1])?,
+            pck_crl: reader.read_bytes(sizes[2])?,
+            tcb_info_issuer_chain: reader.read_bytes(sizes[3])?,
+            tcb_info: reader.read_bytes(sizes[4])?,
+            qe_identity_issuer_chain: reader.read_bytes(sizes[5])?,
+            qe_identity: reader.read_bytes(sizes[6])?,
+        })
+    }
+
+    /// Encode into the format read by [`QuoteCollateral::from_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let fields = self.fields();
+        let mut bytes = Vec::with_capacity(
+            QUOTE_COLLATERAL_HEADER_SIZE + fields.iter().map(|f| f.len()).sum::<usize>(),
+        );
+        bytes.extend_from_slice(&self.major_version.to_le_bytes());
+        bytes.extend_from_slice(&self.minor_version.to_le_bytes());
+        bytes.extend_from_slice(&self.tee_type.to_le_bytes());
+        for field in fields {
+            bytes.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        }
+        for field in fields {
+            bytes.extend_from_slice(field);
+        }
+        bytes
+    }
+
+    fn fields(&self) -> [&Vec<u8>; 7] {
+        [
+            &self.pck_crl_issuer_chain,
+            &self.root_ca_crl,
+            &self.pck_crl,
+            &self.tcb_info_issuer_chain,
+            &self.tcb_info,
+            &self.qe_identity_issuer_chain,
+            &self.qe_identity,
+        ]
+    }
+
+    /// The TCB Info document, without any trailing NUL.
+    pub fn tcb_info_json(&self) -> eyre::Result<&str> {
+        c_str(&self.tcb_info)
+    }
+
+    /// The QE Identity document, without any trailing NUL.
+    pub fn qe_identity_json(&self) -> eyre::Result<&str> {
+        c_str(&self.qe_identity)
+    }
+
+    pub fn tcb_info(&self) -> eyre::Result<TcbInfo> {
+        Ok(serde_json::from_str(self.tcb_info_json()?)?)
+    }
+
+    pub fn qe_identity(&self) -> eyre::Result<EnclaveIdentityV2> {
+        Ok(serde_json::from_str(self.qe_identity_json()?)?)
+    }
+
+    /// The TCB Info with the raw body its signature covers.
+    pub fn signed_tcb_info(&self) -> eyre::Result<SignedTcbInfo> {
+        SignedTcbInfo::parse(self.tcb_info_json()?)
+    }
+
+    /// The QE Identity with the raw body its signature covers.
+    pub fn signed_qe_identity(&self) -> eyre::Result<SignedEnclaveIdentity> {
+        SignedEnclaveIdentity::parse(self.qe_identity_json()?)
+    }
+
+    pub fn pck_crl(&self) -> eyre::Result<Crl> {
+        Crl::parse(&self.pck_crl)
+    }
+
+    pub fn root_ca_crl(&self) -> eyre::Result<Crl> {
+        Crl::parse(&self.root_ca_crl)
+    }
+
+    /// When the first piece of this collateral needs an update: the earliest
+    /// `nextUpdate` of the TCB Info, the QE Identity and the CRLs.
+    pub fn next_update(&self) -> eyre::Result<DateTime<Utc>> {
+        let mut next_update = self
+            .tcb_info()?
+            .tcb_info
+            .next_update
+            .min(self.qe_identity()?.enclave_identity.next_update);
+        for crl in [self.pck_crl()?, self.root_ca_crl()?] {
+            if let Some(crl_next_update) = crl.next_update() {
+                next_update = next_update.min(crl_next_update);
+            }
+        }
+        Ok(next_update)
+    }
+
+    pub fn pck_crl_issuer_chain(&self) -> eyre::Result<Vec<Certificate>> {
+        pem_chain(&self.pck_crl_issuer_chain)
+    }
+
+    pub fn tcb_info_issuer_chain(&self) -> eyre::Result<Vec<Certificate>> {
+        pem_chain(&self.tcb_info_issuer_chain)
+    }
+
+    pub fn qe_identity_issuer_chain(&self) -> eyre::Result<Vec<Certificate>> {
+        pem_chain(&self.qe_identity_issuer_chain)
+    }
+}
+
+fn trim_nul(bytes: &[u8]) -> &[u8] {
+    bytes.strip_suffix(&[0]).unwrap_or(bytes)
+}
+
+fn c_str(bytes: &[u8]) -> eyre::Result<&str> {
+    Ok(std::str::from_utf8(trim_nul(bytes))?)
+}
+
+fn pem_chain(bytes: &[u8]) -> eyre::Result<Vec<Certificate>> {
+    Certificate::load_pem_chain(trim_nul(bytes).trim_ascii())
+        .map_err(|err| eyre::eyre!("invalid issuer chain: {}", err))
+}
+
+/// Hex (de)serialization of byte fields.
+pub(crate) mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        hex::decode(hex).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tee_type;
+    use crate::test_utils::{quote_collateral, TestPki};
+
+    #[test]
+    fn test_binary_roundtrip() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::SGX);
+        let bytes = collateral.to_bytes();
+        assert_eq!(&bytes[..4], &[3, 0, 0, 0]);
+        assert_eq!(QuoteCollateral::from_bytes(&bytes)?, collateral);
+
+        assert!(QuoteCollateral::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(QuoteCollateral::from_bytes(&bytes[..QUOTE_COLLATERAL_HEADER_SIZE - 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_serde_roundtrip() -> eyre::Result<()> {
+        let collateral = quote_collateral(&TestPki::new(), tee_type::SGX);
+        let json = serde_json::to_string(&collateral)?;
+        assert_eq!(serde_json::from_str::<QuoteCollateral>(&json)?, collateral);
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_update() -> eyre::Result<()> {
+        let collateral = quote_collateral(&TestPki::new(), tee_type::SGX);
+        let tcb_info_next_update = collateral.tcb_info()?.tcb_info.next_update;
+        let qe_identity_next_update = collateral.qe_identity()?.enclave_identity.next_update;
+        assert_eq!(
+            collateral.next_update()?,
+            tcb_info_next_update.min(qe_identity_next_update)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_typed_accessors() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::SGX);
+
+        let tcb_info = collateral.tcb_info()?;
+        tcb_info.verify_signature(
+            collateral.tcb_info_json()?,
+            &collateral.tcb_info_issuer_chain()?[0],
+        )?;
+        collateral.qe_identity()?.verify_signature(
+            collateral.qe_identity_json()?,
+            &collateral.qe_identity_issuer_chain()?[0],
+        )?;
+        collateral
+            .signed_tcb_info()?
+            .verify(&collateral.tcb_info_issuer_chain()?[0])?;
+        collateral
+            .signed_qe_identity()?
+            .verify(&collateral.qe_identity_issuer_chain()?[0])?;
+
+        let pck_crl_chain = collateral.pck_crl_issuer_chain()?;
+        collateral
+            .pck_crl()?
+            .verify(&pck_crl_chain[0], tcb_info.tcb_info.issue_date)?;
+        collateral
+            .root_ca_crl()?
+            .verify(&pck_crl_chain[1], tcb_info.tcb_info.issue_date)?;
+        Ok(())
+    }
+}
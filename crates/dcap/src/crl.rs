@@ -0,0 +1,349 @@
+use chrono::{DateTime, Utc};
+use der::oid::AssociatedOid;
+use der::{Decode, Encode};
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::DerSignature;
+use x509_cert::crl::CertificateList;
+use x509_cert::ext::pkix::{CrlNumber, CrlReason};
+use x509_cert::name::Name;
+use x509_cert::serial_number::SerialNumber;
+use x509_cert::Certificate;
+
+use crate::cert_chain::{time_to_datetime, ECDSA_WITH_SHA256_OID};
+use crate::crypto::verifying_key_from_certificate;
+use crate::PckChain;
+
+const CRL_PEM_LABEL: &str = "X509 CRL";
+
+/// A certificate in the chain has been revoked.
+///
+/// Returned wrapped in an [`eyre::Report`]; use `downcast_ref::<Revoked>()`
+/// to tell revocation apart from other validation failures.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revoked {
+    /// Hex-encoded serial number of the revoked certificate.
+    pub serial: String,
+    /// The reason code of the CRL entry, if any.
+    pub reason: Option<CrlReason>,
+}
+
+impl std::fmt::Display for Revoked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.reason {
+            Some(reason) => write!(f, "certificate {} is revoked ({:?})", self.serial, reason),
+            None => write!(f, "certificate {} is revoked", self.serial),
+        }
+    }
+}
+
+impl std::error::Error for Revoked {}
+
+/// A certificate revocation list, such as the PCK CRL or the Root CA CRL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crl {
+    list: CertificateList,
+}
+
+impl Crl {
+    /// Parse a DER, PEM or hex encoded CRL.
+    ///
+    /// PCCS and older DCAP collateral carry CRLs as hex strings, possibly
+    /// NUL-terminated.
+    pub fn parse(bytes: &[u8]) -> eyre::Result<Self> {
+        let trimmed = bytes.strip_suffix(&[0]).unwrap_or(bytes).trim_ascii();
+        if trimmed.starts_with(b"-----BEGIN") {
+            Self::from_pem(trimmed)
+        } else if !trimmed.is_empty() && trimmed.iter().all(u8::is_ascii_hexdigit) {
+            Self::from_der(&hex::decode(trimmed)?)
+        } else {
+            Self::from_der(bytes)
+        }
+    }
+
+    pub fn from_der(der: &[u8]) -> eyre::Result<Self> {
+        let list = CertificateList::from_der(der)?;
+        Ok(Self { list })
+    }
+
+    pub fn from_pem(pem: &[u8]) -> eyre::Result<Self> {
+        let (label, der) = der::pem::decode_vec(pem.trim_ascii())
+            .map_err(|err| eyre::eyre!("invalid PEM: {}", err))?;
+        if label != CRL_PEM_LABEL {
+            eyre::bail!("expected a PEM encoded {}, found {}", CRL_PEM_LABEL, label);
+        }
+        Self::from_der(&der)
+    }
+
+    pub fn certificate_list(&self) -> &CertificateList {
+        &self.list
+    }
+
+    pub fn issuer(&self) -> &Name {
+        &self.list.tbs_cert_list.issuer
+    }
+
+    pub fn this_update(&self) -> DateTime<Utc> {
+        time_to_datetime(&self.list.tbs_cert_list.this_update)
+    }
+
+    pub fn next_update(&self) -> Option<DateTime<Utc>> {
+        self.list
+            .tbs_cert_list
+            .next_update
+            .as_ref()
+            .map(time_to_datetime)
+    }
+
+    /// The `cRLNumber` extension, if present.
+    pub fn crl_number(&self) -> eyre::Result<Option<u32>> {
+        let extension = self
+            .list
+            .tbs_cert_list
+            .crl_extensions
+            .iter()
+            .flatten()
+            .find(|ext| ext.extn_id == CrlNumber::OID);
+        let Some(extension) = extension else {
+            return Ok(None);
+        };
+        let number = CrlNumber::from_der(extension.extn_value.as_bytes())?;
+        let bytes = number.0.as_bytes();
+        if bytes.len() > 4 {
+            eyre::bail!("CRL number does not fit in 32 bits");
+        }
+        Ok(Some(
+            bytes.iter().fold(0, |acc, b| (acc << 8) | u32::from(*b)),
+        ))
+    }
+
+    pub fn to_der(&self) -> eyre::Result<Vec<u8>> {
+        Ok(self.list.to_der()?)
+    }
+
+    /// Verify that the CRL is signed by `issuer` and current at `at`.
+    pub fn verify(&self, issuer: &Certificate, at: DateTime<Utc>) -> eyre::Result<()> {
+        if *self.issuer() != issuer.tbs_certificate.subject {
+            eyre::bail!(
+                "CRL issued by {} does not match {}",
+                self.issuer(),
+                issuer.tbs_certificate.subject
+            );
+        }
+        if self.list.signature_algorithm.oid != ECDSA_WITH_SHA256_OID {
+            eyre::bail!(
+                "unsupported CRL signature algorithm {}",
+                self.list.signature_algorithm.oid
+            );
+        }
+
+        let key = verifying_key_from_certificate(issuer)?;
+        let signature = self
+            .list
+            .signature
+            .as_bytes()
+            .ok_or_else(|| eyre::eyre!("malformed CRL signature"))?;
+        let signature = DerSignature::from_bytes(signature)
+            .map_err(|_| eyre::eyre!("malformed CRL signature"))?;
+        let tbs = self.list.tbs_cert_list.to_der()?;
+        key.verify(&tbs, &signature)
+            .map_err(|_| eyre::eyre!("invalid signature on CRL issued by {}", self.issuer()))?;
+
+        if !self.is_fresh(at) {
+            eyre::bail!(
+                "CRL issued by {} is not current at {} (this update {}, next update {:?})",
+                self.issuer(),
+                at,
+                self.this_update(),
+                self.next_update()
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether `at` falls between this update and the next update, if any.
+    pub fn is_fresh(&self, at: DateTime<Utc>) -> bool {
+        self.this_update() <= at
+            && self
+                .next_update()
+                .is_none_or(|next_update| at <= next_update)
+    }
+
+    /// Look up `cert` in the CRL.
+    ///
+    /// Only certificates issued by the CRL issuer are considered.
+    pub fn check(&self, cert: &Certificate) -> Result<(), Revoked> {
+        if cert.tbs_certificate.issuer != *self.issuer() {
+            return Ok(());
+        }
+        let serial = &cert.tbs_certificate.serial_number;
+        let entry = self
+            .list
+            .tbs_cert_list
+            .revoked_certificates
+            .iter()
+            .flatten()
+            .find(|entry| entry.serial_number == *serial);
+        match entry {
+            Some(entry) => Err(Revoked {
+                serial: serial_to_hex(serial),
+                reason: entry
+                    .crl_entry_extensions
+                    .iter()
+                    .flatten()
+                    .find(|ext| ext.extn_id == der::oid::db::rfc5280::ID_CE_CRL_REASONS)
+                    .and_then(|ext| CrlReason::from_der(ext.extn_value.as_bytes()).ok()),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Check every certificate of `chain` against `crls`.
+///
+/// Each CRL must be issued by a certificate of `chain` or by `root`, and is
+/// verified against its issuer before use.
+pub fn check_revocation(
+    chain: &[Certificate],
+    root: &Certificate,
+    crls: &[Crl],
+    at: DateTime<Utc>,
+) -> eyre::Result<()> {
+    for crl in crls {
+        let issuer = chain
+            .iter()
+            .chain(std::iter::once(root))
+            .find(|cert| cert.tbs_certificate.subject == *crl.issuer())
+            .ok_or_else(|| eyre::eyre!("CRL issuer {} is not part of the chain", crl.issuer()))?;
+        crl.verify(issuer, at)?;
+    }
+
+    for cert in chain.iter().chain(std::iter::once(root)) {
+        for crl in crls {
+            crl.check(cert)?;
+        }
+    }
+    Ok(())
+}
+
+impl PckChain {
+    /// Validate the chain against `root` like [`PckChain::validate_with_root`],
+    /// then check every certificate against `crls`, typically the PCK CRL and
+    /// the Root CA CRL.
+    ///
+    /// A revoked certificate fails with a [`Revoked`] error.
+    pub fn validate_with_crls(
+        &self,
+        root: &Certificate,
+        crls: &[Crl],
+        at: DateTime<Utc>,
+    ) -> eyre::Result<()> {
+        self.validate_with_root(root, at)?;
+        check_revocation(self.certificates(), root, crls, at)
+    }
+}
+
+fn serial_to_hex(serial: &SerialNumber) -> String {
+    serial
+        .as_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestPki, CRL_NEXT_UPDATE};
+
+    fn at(timestamp: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(timestamp, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_der_and_pem() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let crl = pki.pck_crl(&[]);
+        let der = crl.to_der()?;
+        assert_eq!(Crl::parse(&der)?, crl);
+
+        let pem = der::pem::encode_string(CRL_PEM_LABEL, der::pem::LineEnding::LF, &der).unwrap();
+        assert_eq!(Crl::parse(pem.as_bytes())?, crl);
+
+        assert_eq!(Crl::parse(hex::encode(&der).as_bytes())?, crl);
+        Ok(())
+    }
+
+    #[test]
+    fn test_crl_number() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        assert_eq!(pki.pck_crl(&[]).crl_number()?, Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_crl_is_fresh() {
+        let crl = TestPki::new().pck_crl(&[]);
+        assert!(crl.is_fresh(at(1_700_000_000)));
+        assert!(crl.is_fresh(at(CRL_NEXT_UPDATE as i64)));
+        assert!(!crl.is_fresh(at(CRL_NEXT_UPDATE as i64 + 1)));
+        assert!(!crl.is_fresh(at(1_500_000_000)));
+    }
+
+    #[test]
+    fn test_chain_not_revoked() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let chain = PckChain::from_pem(pki.pck_chain_pem().as_bytes())?;
+        let crls = [pki.pck_crl(&[(9, None)]), pki.root_crl(&[])];
+        chain.validate_with_crls(&pki.root_cert, &crls, at(1_700_000_000))
+    }
+
+    #[test]
+    fn test_revoked_pck() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let chain = PckChain::from_pem(pki.pck_chain_pem().as_bytes())?;
+        let crls = [
+            pki.pck_crl(&[(3, Some(CrlReason::KeyCompromise))]),
+            pki.root_crl(&[]),
+        ];
+
+        let err = chain
+            .validate_with_crls(&pki.root_cert, &crls, at(1_700_000_000))
+            .unwrap_err();
+        let revoked = err.downcast_ref::<Revoked>().unwrap();
+        assert_eq!(revoked.serial, "03");
+        assert_eq!(revoked.reason, Some(CrlReason::KeyCompromise));
+        Ok(())
+    }
+
+    #[test]
+    fn test_revoked_intermediate() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let chain = PckChain::from_pem(pki.pck_chain_pem().as_bytes())?;
+        let crls = [pki.pck_crl(&[]), pki.root_crl(&[(2, None)])];
+
+        let err = chain
+            .validate_with_crls(&pki.root_cert, &crls, at(1_700_000_000))
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<Revoked>().unwrap().serial, "02");
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_expired_or_forged_crl() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let chain = PckChain::from_pem(pki.pck_chain_pem().as_bytes())?;
+
+        let crls = [pki.pck_crl(&[])];
+        assert!(chain
+            .validate_with_crls(&pki.root_cert, &crls, at(CRL_NEXT_UPDATE as i64 + 1))
+            .is_err());
+
+        // A CRL for the Platform CA signed with another key.
+        let other = TestPki::with_pck_key_seed(9);
+        let forged = other.crl_signed_by(&other.pck_key, &pki.intermediate_cert, &[]);
+        assert!(chain
+            .validate_with_crls(&pki.root_cert, &[forged], at(1_700_000_000))
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+use x509_cert::Certificate;
+
+/// Build a P-256 verifying key from a raw `x || y` public key.
+pub(crate) fn verifying_key_from_raw(key: &[u8; 64]) -> eyre::Result<VerifyingKey> {
+    let mut sec1 = [0u8; 65];
+    sec1[0] = 0x04;
+    sec1[1..].copy_from_slice(key);
+    VerifyingKey::from_sec1_bytes(&sec1).map_err(|_| eyre::eyre!("invalid P-256 public key"))
+}
+
+/// Extract the P-256 public key of a certificate.
+pub(crate) fn verifying_key_from_certificate(cert: &Certificate) -> eyre::Result<VerifyingKey> {
+    let spki = &cert.tbs_certificate.subject_public_key_info;
+    VerifyingKey::from_sec1_bytes(spki.subject_public_key.raw_bytes())
+        .map_err(|_| eyre::eyre!("certificate does not carry a P-256 public key"))
+}
+
+/// A 32 byte big-endian ECDSA scalar, which the TPM may return shorter.
+#[cfg(feature = "tpm-tls")]
+pub(crate) fn scalar(bytes: &[u8]) -> eyre::Result<[u8; 32]> {
+    if bytes.len() > 32 {
+        eyre::bail!("ECDSA signature is not P-256");
+    }
+    let mut scalar = [0u8; 32];
+    scalar[32 - bytes.len()..].copy_from_slice(bytes);
+    Ok(scalar)
+}
+
+/// Verify a raw `r || s` ECDSA P-256 signature over `message`.
+pub(crate) fn verify_raw_signature(
+    key: &VerifyingKey,
+    message: &[u8],
+    signature: &[u8; 64],
+) -> eyre::Result<()> {
+    let signature =
+        Signature::from_slice(signature).map_err(|_| eyre::eyre!("malformed ECDSA signature"))?;
+    key.verify(message, &signature)
+        .map_err(|_| eyre::eyre!("ECDSA signature verification failed"))
+}
+
+pub(crate) fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
@@ -0,0 +1,122 @@
+use crate::primitives::tcb_info::TcbStatus;
+use crate::{MissingCollateral, PolicyVerdict, Revoked};
+
+/// The kind of a verification failure, for callers deciding whether to
+/// retry, fail hard or degrade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The quote or the collateral is malformed or unsupported.
+    Parse,
+    /// A signature, certificate chain or enclave identity does not verify.
+    Signature,
+    /// Collateral is past its next update.
+    CollateralExpired,
+    /// Collateral is missing, or is for another platform or TEE.
+    CollateralMissing,
+    /// A certificate in one of the chains is revoked.
+    Revoked,
+    /// The platform TCB is not up to date, or not covered by the TCB Info.
+    TcbOutOfDate,
+    /// The appraisal policy rejected the platform.
+    PolicyRejected,
+}
+
+impl ErrorKind {
+    /// Classify an error returned by verification, appraisal or a
+    /// [`CollateralStore`](crate::CollateralStore).
+    ///
+    /// A revocation, missing collateral, out of date TCB or policy verdict
+    /// anywhere in the chain takes precedence over the [`VerificationError`]
+    /// wrapping it. Returns `None` for errors that were not classified, such
+    /// as I/O errors.
+    pub fn of(err: &eyre::Report) -> Option<Self> {
+        if err.downcast_ref::<Revoked>().is_some() {
+            Some(ErrorKind::Revoked)
+        } else if err.downcast_ref::<MissingCollateral>().is_some() {
+            Some(ErrorKind::CollateralMissing)
+        } else if err.downcast_ref::<TcbOutOfDate>().is_some() {
+            Some(ErrorKind::TcbOutOfDate)
+        } else if err.downcast_ref::<PolicyVerdict>().is_some() {
+            Some(ErrorKind::PolicyRejected)
+        } else {
+            err.downcast_ref::<VerificationError>().map(|err| err.kind)
+        }
+    }
+
+    /// A stable snake case name of the kind, e.g. for metric labels.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Parse => "parse",
+            ErrorKind::Signature => "signature",
+            ErrorKind::CollateralExpired => "collateral_expired",
+            ErrorKind::CollateralMissing => "collateral_missing",
+            ErrorKind::Revoked => "revoked",
+            ErrorKind::TcbOutOfDate => "tcb_out_of_date",
+            ErrorKind::PolicyRejected => "policy_rejected",
+        }
+    }
+
+    /// Whether verifying again with fresh collateral may succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ErrorKind::CollateralExpired | ErrorKind::CollateralMissing
+        )
+    }
+
+    /// Whether the evidence is authentic, so that a caller may still accept
+    /// the platform with reduced trust.
+    pub fn is_authentic(&self) -> bool {
+        matches!(self, ErrorKind::TcbOutOfDate | ErrorKind::PolicyRejected)
+    }
+}
+
+/// A failed step of quote verification.
+///
+/// Returned wrapped in an [`eyre::Report`], either as the error or as the
+/// context of the underlying one; use [`ErrorKind::of`] to classify it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl VerificationError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+/// The platform of a verified quote is not up to date.
+///
+/// Returned wrapped in an [`eyre::Report`] by
+/// [`VerificationResult::require_up_to_date`](crate::VerificationResult::require_up_to_date);
+/// use `downcast_ref::<TcbOutOfDate>()` to inspect the advisories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcbOutOfDate {
+    pub status: TcbStatus,
+    pub advisory_ids: Vec<String>,
+}
+
+impl std::fmt::Display for TcbOutOfDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "platform TCB status is {:?}", self.status)?;
+        if !self.advisory_ids.is_empty() {
+            write!(f, " ({})", self.advisory_ids.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TcbOutOfDate {}
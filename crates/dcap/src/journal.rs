@@ -0,0 +1,291 @@
+//! A canonical byte encoding of a verification outcome.
+//!
+//! The journal is what a zkVM guest commits to, or what a contract stores,
+//! after verifying a quote: the signed quote body, the resulting TCB status
+//! and the hashes of the collateral it was checked against. The encoding is
+//! fixed and big-endian, so equal outcomes always give equal bytes.
+
+use chrono::{DateTime, Utc};
+use der::Encode;
+use tss_serde::TssReader;
+use x509_cert::Certificate;
+
+use crate::crypto::sha256;
+use crate::primitives::tcb_info::TcbStatus;
+use crate::{Quote, QuoteCollateral, QUOTE_HEADER_SIZE};
+
+/// Version of the journal encoding.
+pub const JOURNAL_VERSION: u16 = 1;
+
+/// SHA-256 hashes of the collateral a quote was verified against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollateralHashes {
+    /// Of the TCB Info document, without a trailing NUL.
+    pub tcb_info: [u8; 32],
+    /// Of the QE Identity document, without a trailing NUL.
+    pub qe_identity: [u8; 32],
+    pub pck_crl: [u8; 32],
+    pub root_ca_crl: [u8; 32],
+    /// Of the DER root certificate.
+    pub root_ca: [u8; 32],
+}
+
+impl CollateralHashes {
+    pub fn new(collateral: &QuoteCollateral, root: &Certificate) -> eyre::Result<Self> {
+        Ok(Self {
+            tcb_info: sha256(&[collateral.tcb_info_json()?.as_bytes()]),
+            qe_identity: sha256(&[collateral.qe_identity_json()?.as_bytes()]),
+            pck_crl: sha256(&[&collateral.pck_crl]),
+            root_ca_crl: sha256(&[&collateral.root_ca_crl]),
+            root_ca: sha256(&[&root.to_der()?]),
+        })
+    }
+}
+
+/// The outcome of a quote verification, in a form meant to be hashed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationJournal {
+    pub quote_version: u16,
+    pub tee_type: u32,
+    pub status: TcbStatus,
+    pub qe_status: TcbStatus,
+    pub fmspc: [u8; 6],
+    /// When the quote was verified.
+    pub verified_at: DateTime<Utc>,
+    pub tcb_date: DateTime<Utc>,
+    pub collateral_issue_date: DateTime<Utc>,
+    pub collateral: CollateralHashes,
+    /// Body type of the quote, see [`crate::body_type`].
+    pub body_type: u16,
+    /// The raw quote body: the enclave or TD report with all measurements.
+    pub body: Vec<u8>,
+    /// Sorted advisory IDs.
+    pub advisory_ids: Vec<String>,
+}
+
+impl VerificationJournal {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        quote: &Quote,
+        status: TcbStatus,
+        qe_status: TcbStatus,
+        fmspc: [u8; 6],
+        tcb_date: DateTime<Utc>,
+        collateral_issue_date: DateTime<Utc>,
+        collateral: CollateralHashes,
+        advisory_ids: &[String],
+        verified_at: DateTime<Utc>,
+    ) -> Self {
+        let mut advisory_ids = advisory_ids.to_vec();
+        advisory_ids.sort();
+        Self {
+            quote_version: quote.header.version,
+            tee_type: quote.header.tee_type,
+            status,
+            qe_status,
+            fmspc,
+            verified_at,
+            tcb_date,
+            collateral_issue_date,
+            collateral,
+            body_type: quote.body.body_type(),
+            body: quote.signed_data[QUOTE_HEADER_SIZE..].to_vec(),
+            advisory_ids,
+        }
+    }
+
+    pub fn to_bytes(&self) -> eyre::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&JOURNAL_VERSION.to_be_bytes());
+        bytes.extend_from_slice(&self.quote_version.to_be_bytes());
+        bytes.extend_from_slice(&self.tee_type.to_be_bytes());
+        bytes.push(tcb_status_code(self.status));
+        bytes.push(tcb_status_code(self.qe_status));
+        bytes.extend_from_slice(&self.fmspc);
+        for date in [self.verified_at, self.tcb_date, self.collateral_issue_date] {
+            bytes.extend_from_slice(&encode_date(date)?.to_be_bytes());
+        }
+        for hash in [
+            &self.collateral.tcb_info,
+            &self.collateral.qe_identity,
+            &self.collateral.pck_crl,
+            &self.collateral.root_ca_crl,
+            &self.collateral.root_ca,
+        ] {
+            bytes.extend_from_slice(hash);
+        }
+        bytes.extend_from_slice(&self.body_type.to_be_bytes());
+        bytes.extend_from_slice(&u32::try_from(self.body.len())?.to_be_bytes());
+        bytes.extend_from_slice(&self.body);
+        bytes.extend_from_slice(&u16::try_from(self.advisory_ids.len())?.to_be_bytes());
+        for id in &self.advisory_ids {
+            bytes.extend_from_slice(&u16::try_from(id.len())?.to_be_bytes());
+            bytes.extend_from_slice(id.as_bytes());
+        }
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+        let version = reader.read_u16()?;
+        if version != JOURNAL_VERSION {
+            eyre::bail!("unsupported journal version {}", version);
+        }
+        let quote_version = reader.read_u16()?;
+        let tee_type = reader.read_u32()?;
+        let status = tcb_status_from_code(reader.read_u8()?)?;
+        let qe_status = tcb_status_from_code(reader.read_u8()?)?;
+        let fmspc = reader.read_array()?;
+        let verified_at = decode_date(reader.read_u64()?)?;
+        let tcb_date = decode_date(reader.read_u64()?)?;
+        let collateral_issue_date = decode_date(reader.read_u64()?)?;
+        let collateral = CollateralHashes {
+            tcb_info: reader.read_array()?,
+            qe_identity: reader.read_array()?,
+            pck_crl: reader.read_array()?,
+            root_ca_crl: reader.read_array()?,
+            root_ca: reader.read_array()?,
+        };
+        let body_type = reader.read_u16()?;
+        let body_size = reader.read_u32()?;
+        let body = reader.read_bytes(body_size as usize)?;
+        let count = reader.read_u16()?;
+        let mut advisory_ids = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let size = reader.read_u16()?;
+            advisory_ids.push(String::from_utf8(reader.read_bytes(size as usize)?)?);
+        }
+        if reader.remaining() != 0 {
+            eyre::bail!("trailing bytes after the journal");
+        }
+        Ok(Self {
+            quote_version,
+            tee_type,
+            status,
+            qe_status,
+            fmspc,
+            verified_at,
+            tcb_date,
+            collateral_issue_date,
+            collateral,
+            body_type,
+            body,
+            advisory_ids,
+        })
+    }
+
+    /// SHA-256 of the encoded journal.
+    pub fn digest(&self) -> eyre::Result<[u8; 32]> {
+        Ok(sha256(&[&self.to_bytes()?]))
+    }
+}
+
+/// The journal code of a TCB status, in the order used by on-chain DCAP
+/// verifiers.
+pub fn tcb_status_code(status: TcbStatus) -> u8 {
+    match status {
+        TcbStatus::UpToDate => 0,
+        TcbStatus::OutOfDate => 1,
+        TcbStatus::Revoked => 2,
+        TcbStatus::ConfigurationNeeded => 3,
+        TcbStatus::OutOfDateConfigurationNeeded => 4,
+        TcbStatus::SWHardeningNeeded => 5,
+        TcbStatus::ConfigurationAndSWHardeningNeeded => 6,
+    }
+}
+
+pub fn tcb_status_from_code(code: u8) -> eyre::Result<TcbStatus> {
+    Ok(match code {
+        0 => TcbStatus::UpToDate,
+        1 => TcbStatus::OutOfDate,
+        2 => TcbStatus::Revoked,
+        3 => TcbStatus::ConfigurationNeeded,
+        4 => TcbStatus::OutOfDateConfigurationNeeded,
+        5 => TcbStatus::SWHardeningNeeded,
+        6 => TcbStatus::ConfigurationAndSWHardeningNeeded,
+        other => eyre::bail!("unknown TCB status code {}", other),
+    })
+}
+
+fn encode_date(date: DateTime<Utc>) -> eyre::Result<u64> {
+    u64::try_from(date.timestamp()).map_err(|_| eyre::eyre!("date before 1970 in journal"))
+}
+
+fn decode_date(seconds: u64) -> eyre::Result<DateTime<Utc>> {
+    i64::try_from(seconds)
+        .ok()
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .ok_or_else(|| eyre::eyre!("journal date out of range"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{quote_collateral, tdx_quote, verification_time, TestPki};
+    use crate::{tee_type, verify_quote_with, VerifyOptions};
+
+    #[test]
+    fn test_journal_roundtrip() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::TDX);
+        let options = VerifyOptions {
+            journal: true,
+            ..VerifyOptions::default()
+        };
+        let quote = tdx_quote(&pki);
+        let result = verify_quote_with(
+            &quote,
+            &collateral,
+            &pki.root_cert,
+            &options,
+            verification_time(),
+        )?;
+        let journal = result.journal.expect("requested");
+        assert_eq!(journal.status, result.status);
+        assert_eq!(journal.verified_at, verification_time());
+        assert_eq!(
+            journal.body,
+            quote[QUOTE_HEADER_SIZE..][..journal.body.len()]
+        );
+        assert_eq!(
+            journal.collateral.root_ca,
+            sha256(&[&pki.root_cert.to_der()?])
+        );
+
+        let bytes = journal.to_bytes()?;
+        assert_eq!(VerificationJournal::from_bytes(&bytes)?, journal);
+        assert!(VerificationJournal::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert_eq!(journal.digest()?, sha256(&[&bytes]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_journal_is_canonical() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::TDX);
+        let quote = Quote::parse(&tdx_quote(&pki))?;
+        let hashes = CollateralHashes::new(&collateral, &pki.root_cert)?;
+        let journal = |advisory_ids: &[String]| {
+            VerificationJournal::new(
+                &quote,
+                TcbStatus::OutOfDate,
+                TcbStatus::UpToDate,
+                [0; 6],
+                verification_time(),
+                verification_time(),
+                hashes.clone(),
+                advisory_ids,
+                verification_time(),
+            )
+        };
+        let ids = ["INTEL-SA-00615".to_string(), "INTEL-SA-00219".to_string()];
+        let reversed = [ids[1].clone(), ids[0].clone()];
+        assert_eq!(journal(&ids).to_bytes()?, journal(&reversed).to_bytes()?);
+
+        for code in 0..7 {
+            assert_eq!(tcb_status_code(tcb_status_from_code(code)?), code);
+        }
+        assert!(tcb_status_from_code(7).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+//! Signed attestation result tokens.
+//!
+//! A verifier signs the outcome of a quote verification into an ES256 JWT
+//! so relying parties only need the verifier's public key, not collateral or
+//! a DCAP implementation, to act on it.
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use chrono::{DateTime, Utc};
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::verify_raw_signature;
+use crate::primitives::tcb_info::TcbStatus;
+use crate::{tee_type, VerificationResult};
+
+/// Default lifetime of a token.
+pub const DEFAULT_TOKEN_LIFETIME: chrono::Duration = chrono::Duration::minutes(10);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Header {
+    alg: String,
+    typ: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    kid: Option<String>,
+}
+
+/// What a token says about the attested TEE, taken from a
+/// [`VerificationResult`]. Measurements are hex encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttestationClaims {
+    /// `"sgx"` or `"tdx"`.
+    pub tee: String,
+    pub tcb_status: TcbStatus,
+    pub qe_status: TcbStatus,
+    pub advisory_ids: Vec<String>,
+    pub fmspc: String,
+    pub tcb_date: DateTime<Utc>,
+    pub report_data: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub mr_enclave: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub mr_signer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub isv_prod_id: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub isv_svn: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub mr_td: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rtmrs: Option<Vec<String>>,
+}
+
+impl AttestationClaims {
+    pub fn from_result(result: &VerificationResult) -> Self {
+        let tee = match result.quote.header.tee_type {
+            tee_type::SGX => "sgx".to_string(),
+            tee_type::TDX => "tdx".to_string(),
+            other => format!("{:#x}", other),
+        };
+        let enclave = result.quote.body.as_enclave_report();
+        let td = result.quote.body.as_td_report();
+        Self {
+            tee,
+            tcb_status: result.status,
+            qe_status: result.qe_status,
+            advisory_ids: result.advisory_ids.clone(),
+            fmspc: hex::encode(result.fmspc),
+            tcb_date: result.tcb_date,
+            report_data: hex::encode(result.quote.body.report_data()),
+            mr_enclave: enclave.map(|report| hex::encode(report.mr_enclave)),
+            mr_signer: enclave.map(|report| hex::encode(report.mr_signer)),
+            isv_prod_id: enclave.map(|report| report.isv_prod_id),
+            isv_svn: enclave.map(|report| report.isv_svn),
+            mr_td: td.map(|report| hex::encode(report.mr_td)),
+            rtmrs: td.map(|report| report.rtmrs.iter().map(hex::encode).collect()),
+        }
+    }
+}
+
+/// The claims of a result token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultClaims {
+    pub iss: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub aud: Option<String>,
+    pub iat: i64,
+    pub exp: i64,
+    /// Challenge of the relying party, if the token answers one.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub nonce: Option<String>,
+    #[serde(flatten)]
+    pub attestation: AttestationClaims,
+}
+
+/// Signs verification results into ES256 JWTs.
+#[derive(Debug, Clone)]
+pub struct TokenSigner {
+    key: SigningKey,
+    key_id: Option<String>,
+    issuer: String,
+    audience: Option<String>,
+    lifetime: chrono::Duration,
+}
+
+impl TokenSigner {
+    pub fn new(key: SigningKey, issuer: impl Into<String>) -> Self {
+        Self {
+            key,
+            key_id: None,
+            issuer: issuer.into(),
+            audience: None,
+            lifetime: DEFAULT_TOKEN_LIFETIME,
+        }
+    }
+
+    /// Set the `kid` header, so validators can pick the key.
+    pub fn with_key_id(mut self, key_id: impl Into<String>) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    pub fn with_lifetime(mut self, lifetime: chrono::Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        *self.key.verifying_key()
+    }
+
+    /// Sign `result`, issued now.
+    pub fn sign(&self, result: &VerificationResult, nonce: Option<&str>) -> eyre::Result<String> {
+        self.sign_at(result, nonce, Utc::now())
+    }
+
+    /// Sign `result`, issued at `at`.
+    pub fn sign_at(
+        &self,
+        result: &VerificationResult,
+        nonce: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<String> {
+        self.sign_claims(&ResultClaims {
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            iat: at.timestamp(),
+            exp: (at + self.lifetime).timestamp(),
+            nonce: nonce.map(str::to_string),
+            attestation: AttestationClaims::from_result(result),
+        })
+    }
+
+    /// Sign `claims` as they are.
+    pub fn sign_claims(&self, claims: &ResultClaims) -> eyre::Result<String> {
+        let header = Header {
+            alg: "ES256".to_string(),
+            typ: "JWT".to_string(),
+            kid: self.key_id.clone(),
+        };
+        let mut token = [
+            Base64UrlUnpadded::encode_string(&serde_json::to_vec(&header)?),
+            Base64UrlUnpadded::encode_string(&serde_json::to_vec(claims)?),
+        ]
+        .join(".");
+        let signature: Signature = self.key.sign(token.as_bytes());
+        token.push('.');
+        token.push_str(&Base64UrlUnpadded::encode_string(&signature.to_bytes()));
+        Ok(token)
+    }
+}
+
+/// Validates tokens issued by a [`TokenSigner`].
+#[derive(Debug, Clone)]
+pub struct TokenValidator {
+    key: VerifyingKey,
+    issuer: String,
+    audience: Option<String>,
+    leeway: chrono::Duration,
+}
+
+impl TokenValidator {
+    pub fn new(key: VerifyingKey, issuer: impl Into<String>) -> Self {
+        Self {
+            key,
+            issuer: issuer.into(),
+            audience: None,
+            leeway: chrono::Duration::zero(),
+        }
+    }
+
+    /// Require the `aud` claim to be `audience`.
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Tolerate clock skew of up to `leeway` on `iat` and `exp`.
+    pub fn with_leeway(mut self, leeway: chrono::Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Validate `token` now.
+    pub fn validate(&self, token: &str) -> eyre::Result<ResultClaims> {
+        self.validate_at(token, Utc::now())
+    }
+
+    /// Check the signature, issuer, audience and lifetime of `token` at `at`,
+    /// and return its claims.
+    pub fn validate_at(&self, token: &str, at: DateTime<Utc>) -> eyre::Result<ResultClaims> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(claims), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            eyre::bail!("malformed token");
+        };
+
+        let signed = &token[..header.len() + 1 + claims.len()];
+        let header: Header = serde_json::from_slice(&decode(header)?)?;
+        if header.alg != "ES256" {
+            eyre::bail!("unsupported token algorithm {}", header.alg);
+        }
+        let signature: [u8; 64] = decode(signature)?
+            .try_into()
+            .map_err(|_| eyre::eyre!("malformed token signature"))?;
+        verify_raw_signature(&self.key, signed.as_bytes(), &signature)
+            .map_err(|err| err.wrap_err("token signature is invalid"))?;
+
+        let claims: ResultClaims = serde_json::from_slice(&decode(claims)?)?;
+        if claims.iss != self.issuer {
+            eyre::bail!(
+                "token is issued by {}, expected {}",
+                claims.iss,
+                self.issuer
+            );
+        }
+        if let Some(audience) = &self.audience {
+            if claims.aud.as_ref() != Some(audience) {
+                eyre::bail!("token is not intended for {}", audience);
+            }
+        }
+        let now = at.timestamp();
+        let leeway = self.leeway.num_seconds();
+        if now + leeway < claims.iat {
+            eyre::bail!("token is not valid yet");
+        }
+        if now - leeway >= claims.exp {
+            eyre::bail!("token expired");
+        }
+        Ok(claims)
+    }
+}
+
+fn decode(part: &str) -> eyre::Result<Vec<u8>> {
+    Base64UrlUnpadded::decode_vec(part).map_err(|_| eyre::eyre!("malformed token encoding"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{quote_collateral, signing_key, tdx_quote, verification_time, TestPki};
+    use crate::verify_quote_with_root;
+
+    fn result() -> eyre::Result<VerificationResult> {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::TDX);
+        verify_quote_with_root(
+            &tdx_quote(&pki),
+            &collateral,
+            &pki.root_cert,
+            verification_time(),
+        )
+    }
+
+    #[test]
+    fn test_sign_and_validate() -> eyre::Result<()> {
+        let result = result()?;
+        let signer = TokenSigner::new(signing_key(9), "https://verifier.example")
+            .with_key_id("key-1")
+            .with_audience("kms");
+        let token = signer.sign_at(&result, Some("challenge"), verification_time())?;
+
+        let validator = TokenValidator::new(signer.verifying_key(), "https://verifier.example")
+            .with_audience("kms");
+        let claims = validator.validate_at(&token, verification_time())?;
+        assert_eq!(claims.nonce.as_deref(), Some("challenge"));
+        assert_eq!(claims.attestation, AttestationClaims::from_result(&result));
+        assert_eq!(claims.attestation.tee, "tdx");
+        assert_eq!(claims.attestation.mr_td, Some("11".repeat(48)));
+        assert_eq!(claims.attestation.mr_enclave, None);
+
+        let expired = verification_time() + DEFAULT_TOKEN_LIFETIME;
+        assert!(validator.validate_at(&token, expired).is_err());
+        assert!(TokenValidator::new(signer.verifying_key(), "someone else")
+            .validate_at(&token, verification_time())
+            .is_err());
+        assert!(validator
+            .clone()
+            .with_audience("other")
+            .validate_at(&token, verification_time())
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_tampered_tokens() -> eyre::Result<()> {
+        let result = result()?;
+        let signer = TokenSigner::new(signing_key(9), "verifier");
+        let validator = TokenValidator::new(signer.verifying_key(), "verifier");
+
+        let mut claims = AttestationClaims::from_result(&result);
+        claims.tcb_status = TcbStatus::OutOfDate;
+        let token = signer.sign_at(&result, None, verification_time())?;
+        let parts: Vec<&str> = token.split('.').collect();
+        let forged = ResultClaims {
+            iss: "verifier".to_string(),
+            aud: None,
+            iat: verification_time().timestamp(),
+            exp: verification_time().timestamp() + 60,
+            nonce: None,
+            attestation: claims,
+        };
+        let forged = format!(
+            "{}.{}.{}",
+            parts[0],
+            Base64UrlUnpadded::encode_string(&serde_json::to_vec(&forged)?),
+            parts[2]
+        );
+        assert!(validator.validate_at(&forged, verification_time()).is_err());
+
+        let other = TokenSigner::new(signing_key(10), "verifier");
+        let token = other.sign_at(&result, None, verification_time())?;
+        assert!(validator.validate_at(&token, verification_time()).is_err());
+        assert!(validator.validate_at("a.b", verification_time()).is_err());
+        Ok(())
+    }
+}
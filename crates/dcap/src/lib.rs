@@ -1 +1,99 @@
-mod primitives;
+//! Verification of Intel SGX and TDX quotes against the collateral of the
+//! Intel PCS, and generation of quotes on SGX and TDX machines.
+//!
+//! TPM quotes and certifications by an AK are verified here too: the RA-TLS
+//! certificates of the `tpm-tls` feature carry a TPM certification, and
+//! `dcap` sits below `tee-attest`. The rest of the TPM evidence, event logs,
+//! the challenge protocol, TPM policies, GCP vTPMs and sealing, lives in
+//! `tee-attest`.
+
+pub mod primitives;
+
+mod crypto;
+
+mod quote;
+pub use quote::*;
+
+mod sgx_report;
+pub use sgx_report::*;
+
+mod tdreport;
+pub use tdreport::*;
+
+mod pck;
+pub use pck::*;
+
+mod cert_chain;
+pub use cert_chain::*;
+
+mod crl;
+pub use crl::*;
+
+mod collateral;
+pub use collateral::*;
+
+mod cache;
+pub use cache::*;
+
+mod store;
+pub use store::*;
+
+mod verification;
+pub use verification::*;
+
+mod error;
+pub use error::*;
+
+mod supplemental;
+pub use supplemental::*;
+
+mod journal;
+pub use journal::*;
+
+mod binding;
+pub use binding::*;
+
+mod policy;
+pub use policy::*;
+
+mod sgx_extensions;
+pub use sgx_extensions::*;
+
+mod tpm_quote;
+pub use tpm_quote::*;
+
+#[cfg(feature = "pcs")]
+pub mod pcs;
+
+#[cfg(feature = "maa")]
+pub mod maa;
+
+#[cfg(feature = "azure")]
+pub mod azure;
+
+#[cfg(all(feature = "aesm", unix))]
+pub mod aesm;
+
+#[cfg(all(feature = "tsm", target_os = "linux"))]
+pub mod tsm;
+
+#[cfg(all(feature = "qgs", target_os = "linux"))]
+pub mod qgs;
+
+#[cfg(feature = "ra-tls")]
+pub mod ra_tls;
+
+#[cfg(feature = "jwt")]
+pub mod jwt;
+
+#[cfg(all(
+    feature = "blocking",
+    any(feature = "pcs", feature = "maa", feature = "aesm")
+))]
+mod runtime;
+
+/// Synthetic PKI, quotes, collateral and event logs, for tests of this and
+/// downstream crates. See `tee-ware-testing`.
+#[cfg(any(test, feature = "test-utils"))]
+#[doc(hidden)]
+pub mod test_utils;
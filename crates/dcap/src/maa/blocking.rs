@@ -0,0 +1,51 @@
+//! Blocking variant of [`MaaClient`](super::MaaClient).
+
+use super::Jwks;
+use crate::runtime::BlockingRuntime;
+use crate::VerificationResult;
+
+/// Blocking MAA client, running the async [`MaaClient`](super::MaaClient) on
+/// a runtime of its own.
+#[derive(Debug, Clone)]
+pub struct MaaClient {
+    client: super::MaaClient,
+    runtime: BlockingRuntime,
+}
+
+impl MaaClient {
+    /// A client for the instance at `instance_url`, e.g.
+    /// `https://sharedeus.eus.attest.azure.net`.
+    pub fn new(instance_url: impl Into<String>) -> Self {
+        Self {
+            client: super::MaaClient::new(instance_url),
+            runtime: BlockingRuntime::new(),
+        }
+    }
+
+    pub fn instance_url(&self) -> &str {
+        self.client.instance_url()
+    }
+
+    /// Submit `quote`, and optionally the runtime data bound into its report
+    /// data, and return the token MAA issues for it.
+    pub fn attest(&self, quote: &[u8], runtime_data: Option<&[u8]>) -> eyre::Result<String> {
+        self.runtime
+            .block_on(self.client.attest(quote, runtime_data))
+    }
+
+    /// Fetch the token signing keys of the instance.
+    pub fn signing_keys(&self) -> eyre::Result<Jwks> {
+        self.runtime.block_on(self.client.signing_keys())
+    }
+
+    /// Attest `quote`, validate the token against the current signing keys
+    /// and map it onto the quote.
+    pub fn verify_quote(
+        &self,
+        quote: &[u8],
+        runtime_data: Option<&[u8]>,
+    ) -> eyre::Result<VerificationResult> {
+        self.runtime
+            .block_on(self.client.verify_quote(quote, runtime_data))
+    }
+}
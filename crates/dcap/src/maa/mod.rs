@@ -0,0 +1,420 @@
+//! Client for Microsoft Azure Attestation (MAA).
+//!
+//! MAA verifies SGX and TDX quotes itself and returns an RS256 JWT signed
+//! with one of the keys published under `/certs` of the instance. The
+//! validated claims are mapped back onto the submitted quote as a
+//! [`VerificationResult`], so callers can treat MAA like local verification.
+
+use base64ct::{Base64, Base64UrlUnpadded, Encoding};
+use chrono::{DateTime, Utc};
+use der::Decode;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use x509_cert::Certificate;
+
+use crate::primitives::tcb_info::TcbStatus;
+use crate::{tee_type, PckChain, Quote, VerificationResult};
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+/// API version of the SGX enclave attestation endpoint.
+pub const MAA_SGX_API_VERSION: &str = "2022-08-01";
+
+/// API version of the TDX VM attestation endpoint.
+pub const MAA_TDX_API_VERSION: &str = "2023-04-01-preview";
+
+#[derive(Debug, Serialize)]
+struct AttestRequest {
+    quote: String,
+    #[serde(rename = "runtimeData", skip_serializing_if = "Option::is_none")]
+    runtime_data: Option<RuntimeData>,
+}
+
+#[derive(Debug, Serialize)]
+struct RuntimeData {
+    data: String,
+    #[serde(rename = "dataType")]
+    data_type: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct AttestResponse {
+    token: String,
+}
+
+/// The URL and body of an attestation request for `quote`.
+fn attest_request(
+    instance_url: &str,
+    quote: &[u8],
+    runtime_data: Option<&[u8]>,
+) -> eyre::Result<(String, AttestRequest)> {
+    let header = Quote::parse(quote)?.header;
+    let url = match header.tee_type {
+        tee_type::SGX => format!(
+            "{}/attest/SgxEnclave?api-version={}",
+            instance_url, MAA_SGX_API_VERSION
+        ),
+        tee_type::TDX => format!(
+            "{}/attest/TdxVm?api-version={}",
+            instance_url, MAA_TDX_API_VERSION
+        ),
+        other => eyre::bail!("MAA cannot attest TEE type {:#x}", other),
+    };
+    let request = AttestRequest {
+        quote: Base64UrlUnpadded::encode_string(quote),
+        runtime_data: runtime_data.map(|data| RuntimeData {
+            data: Base64UrlUnpadded::encode_string(data),
+            data_type: "Binary",
+        }),
+    };
+    Ok((url, request))
+}
+
+/// A key of the JSON Web Key Set of an MAA instance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jwk {
+    pub kid: String,
+    /// Standard base64 DER certificates, signing certificate first.
+    #[serde(default)]
+    pub x5c: Vec<String>,
+}
+
+/// The token signing keys of an MAA instance.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Header {
+    alg: String,
+    kid: String,
+}
+
+impl Jwks {
+    /// Check the signature, issuer and lifetime of an MAA `token` at `at`,
+    /// and return its claims. `issuer` is the instance URL.
+    pub fn validate(
+        &self,
+        token: &str,
+        issuer: &str,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<MaaClaims> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(claims), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            eyre::bail!("malformed MAA token");
+        };
+        let signed = &token[..header.len() + 1 + claims.len()];
+
+        let header: Header = serde_json::from_slice(&decode(header)?)?;
+        if header.alg != "RS256" {
+            eyre::bail!("unsupported MAA token algorithm {}", header.alg);
+        }
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.kid == header.kid)
+            .ok_or_else(|| eyre::eyre!("MAA token is signed by unknown key {}", header.kid))?;
+        let cert = key
+            .x5c
+            .first()
+            .ok_or_else(|| eyre::eyre!("MAA key {} has no certificate", key.kid))?;
+        let cert = Certificate::from_der(
+            &Base64::decode_vec(cert).map_err(|_| eyre::eyre!("malformed MAA certificate"))?,
+        )?;
+        let public_key = cert
+            .tbs_certificate
+            .subject_public_key_info
+            .subject_public_key
+            .raw_bytes();
+        ring::signature::UnparsedPublicKey::new(
+            &ring::signature::RSA_PKCS1_2048_8192_SHA256,
+            public_key,
+        )
+        .verify(signed.as_bytes(), &decode(signature)?)
+        .map_err(|_| eyre::eyre!("MAA token signature is invalid"))?;
+
+        let claims: MaaClaims = serde_json::from_slice(&decode(claims)?)?;
+        if claims.iss.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            eyre::bail!("MAA token is issued by {}, expected {}", claims.iss, issuer);
+        }
+        let now = at.timestamp();
+        if claims.nbf.is_some_and(|nbf| now < nbf) {
+            eyre::bail!("MAA token is not valid yet");
+        }
+        if now >= claims.exp {
+            eyre::bail!("MAA token expired");
+        }
+        Ok(claims)
+    }
+}
+
+fn decode(part: &str) -> eyre::Result<Vec<u8>> {
+    Base64UrlUnpadded::decode_vec(part).map_err(|_| eyre::eyre!("malformed MAA token encoding"))
+}
+
+/// The claims of a validated MAA token.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaaClaims {
+    pub iss: String,
+    pub iat: i64,
+    #[serde(default)]
+    pub nbf: Option<i64>,
+    pub exp: i64,
+    /// The MAA specific claims, e.g. `x-ms-sgx-mrenclave` or `tdx_mrtd`.
+    #[serde(flatten)]
+    pub claims: Map<String, Value>,
+}
+
+impl MaaClaims {
+    pub fn claim_str(&self, name: &str) -> Option<&str> {
+        self.claims.get(name)?.as_str()
+    }
+
+    /// The platform TCB status MAA determined, from `attester_tcb_status`.
+    pub fn tcb_status(&self) -> eyre::Result<TcbStatus> {
+        let status = self
+            .claims
+            .get("attester_tcb_status")
+            .ok_or_else(|| eyre::eyre!("MAA token carries no attester_tcb_status"))?;
+        Ok(serde_json::from_value(status.clone())?)
+    }
+
+    /// Map the claims onto `quote`, the evidence the token was issued for.
+    ///
+    /// Fails unless the measurements and report data in the token are those
+    /// of `quote`. MAA reports neither the TCB level date nor collateral
+    /// dates, so `tcb_date` and `collateral_issue_date` are the token's issue
+    /// time, and `qe_status` mirrors the platform status.
+    pub fn to_verification_result(&self, quote: &[u8]) -> eyre::Result<VerificationResult> {
+        let quote = Quote::parse(quote)?;
+        if let Some(report) = quote.body.as_enclave_report() {
+            self.check_claim("x-ms-sgx-mrenclave", &report.mr_enclave)?;
+            self.check_claim("x-ms-sgx-mrsigner", &report.mr_signer)?;
+            self.check_claim("x-ms-sgx-report-data", &report.report_data)?;
+        }
+        if let Some(report) = quote.body.as_td_report() {
+            self.check_claim("tdx_mrtd", &report.mr_td)?;
+            self.check_claim("tdx_report_data", &report.report_data)?;
+        }
+
+        let status = self.tcb_status()?;
+        let advisory_ids = match self.claims.get("attester_advisory_ids") {
+            Some(ids) => serde_json::from_value(ids.clone())?,
+            None => Vec::new(),
+        };
+        let fmspc = PckChain::from_quote(&quote)?.sgx_extensions()?.fmspc;
+        let issued = DateTime::from_timestamp(self.iat, 0)
+            .ok_or_else(|| eyre::eyre!("invalid MAA token issue time"))?;
+        Ok(VerificationResult {
+            quote,
+            status,
+            advisory_ids,
+            fmspc,
+            tcb_date: issued,
+            tdx_module: None,
+            qe_status: status,
+            collateral_issue_date: issued,
+            supplemental_data: None,
+            journal: None,
+        })
+    }
+
+    fn check_claim(&self, name: &str, expected: &[u8]) -> eyre::Result<()> {
+        let value = self
+            .claim_str(name)
+            .ok_or_else(|| eyre::eyre!("MAA token carries no {}", name))?;
+        if !value.eq_ignore_ascii_case(&hex::encode(expected)) {
+            eyre::bail!("MAA token {} does not match the quote", name);
+        }
+        Ok(())
+    }
+}
+
+/// Async MAA client.
+#[derive(Debug, Clone)]
+pub struct MaaClient {
+    client: reqwest::Client,
+    instance_url: String,
+}
+
+impl MaaClient {
+    /// A client for the instance at `instance_url`, e.g.
+    /// `https://sharedeus.eus.attest.azure.net`.
+    pub fn new(instance_url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            instance_url: instance_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn instance_url(&self) -> &str {
+        &self.instance_url
+    }
+
+    /// Submit `quote`, and optionally the runtime data bound into its report
+    /// data, and return the token MAA issues for it.
+    pub async fn attest(&self, quote: &[u8], runtime_data: Option<&[u8]>) -> eyre::Result<String> {
+        let (url, request) = attest_request(&self.instance_url, quote, runtime_data)?;
+        let response = self
+            .client
+            .post(url)
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json::<AttestResponse>().await?.token)
+    }
+
+    /// Fetch the token signing keys of the instance.
+    pub async fn signing_keys(&self) -> eyre::Result<Jwks> {
+        let url = format!("{}/certs", self.instance_url);
+        let response = self.client.get(url).send().await?.error_for_status()?;
+        Ok(response.json().await?)
+    }
+
+    /// Attest `quote`, validate the token against the current signing keys
+    /// and map it onto the quote.
+    pub async fn verify_quote(
+        &self,
+        quote: &[u8],
+        runtime_data: Option<&[u8]>,
+    ) -> eyre::Result<VerificationResult> {
+        let token = self.attest(quote, runtime_data).await?;
+        let claims = self
+            .signing_keys()
+            .await?
+            .validate(&token, &self.instance_url, Utc::now())?;
+        claims.to_verification_result(quote)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{sgx_quote, tdx_quote, TestPki};
+    use serde_json::json;
+
+    const INSTANCE: &str = "https://test.eus.attest.azure.net";
+
+    fn jwks() -> Jwks {
+        Jwks {
+            keys: vec![Jwk {
+                kid: "test-key".to_string(),
+                x5c: vec![Base64::encode_string(include_bytes!(
+                    "../../testdata/maa/signing_cert.der"
+                ))],
+            }],
+        }
+    }
+
+    fn sign(kid: &str, claims: &Value) -> String {
+        let key = ring::signature::RsaKeyPair::from_pkcs8(include_bytes!(
+            "../../testdata/maa/signing_key.pk8"
+        ))
+        .unwrap();
+        let header = json!({"alg": "RS256", "kid": kid, "typ": "JWT"});
+        let mut token = [
+            Base64UrlUnpadded::encode_string(header.to_string().as_bytes()),
+            Base64UrlUnpadded::encode_string(claims.to_string().as_bytes()),
+        ]
+        .join(".");
+        let mut signature = vec![0u8; key.public().modulus_len()];
+        key.sign(
+            &ring::signature::RSA_PKCS1_SHA256,
+            &ring::rand::SystemRandom::new(),
+            token.as_bytes(),
+            &mut signature,
+        )
+        .unwrap();
+        token.push('.');
+        token.push_str(&Base64UrlUnpadded::encode_string(&signature));
+        token
+    }
+
+    fn sgx_claims() -> Value {
+        json!({
+            "iss": INSTANCE,
+            "iat": 1_740_787_200,
+            "exp": 1_740_787_200 + 3600,
+            "x-ms-attestation-type": "sgx",
+            "x-ms-sgx-mrenclave": "aa".repeat(32),
+            "x-ms-sgx-mrsigner": "BB".repeat(32),
+            "x-ms-sgx-report-data": "cc".repeat(64),
+            "attester_tcb_status": "SWHardeningNeeded",
+            "attester_advisory_ids": ["INTEL-SA-00615"],
+        })
+    }
+
+    fn at(timestamp: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(timestamp, 0).unwrap()
+    }
+
+    #[test]
+    fn test_attest_request() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let (url, request) = attest_request(INSTANCE, &sgx_quote(&pki), Some(b"data"))?;
+        assert_eq!(
+            url,
+            "https://test.eus.attest.azure.net/attest/SgxEnclave?api-version=2022-08-01"
+        );
+        assert_eq!(
+            serde_json::to_value(&request)?["runtimeData"],
+            json!({"data": "ZGF0YQ", "dataType": "Binary"})
+        );
+
+        let (url, request) = attest_request(INSTANCE, &tdx_quote(&pki), None)?;
+        assert!(url.ends_with("/attest/TdxVm?api-version=2023-04-01-preview"));
+        assert!(request.runtime_data.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_and_map_token() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let token = sign("test-key", &sgx_claims());
+        let claims = jwks().validate(&token, INSTANCE, at(1_740_787_300))?;
+        assert_eq!(claims.claim_str("x-ms-attestation-type"), Some("sgx"));
+
+        let result = claims.to_verification_result(&sgx_quote(&pki))?;
+        assert_eq!(result.status, TcbStatus::SWHardeningNeeded);
+        assert_eq!(result.advisory_ids, ["INTEL-SA-00615"]);
+        assert_eq!(result.fmspc, pki.sgx_extensions.fmspc);
+
+        // The token is about another quote.
+        assert!(claims.to_verification_result(&tdx_quote(&pki)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_invalid_tokens() {
+        let token = sign("test-key", &sgx_claims());
+        assert!(jwks()
+            .validate(&token, INSTANCE, at(1_740_800_000))
+            .is_err());
+        assert!(jwks()
+            .validate(&token, "https://other.attest.azure.net", at(1_740_787_300))
+            .is_err());
+
+        let unknown_key = sign("other-key", &sgx_claims());
+        assert!(jwks()
+            .validate(&unknown_key, INSTANCE, at(1_740_787_300))
+            .is_err());
+
+        let parts: Vec<&str> = token.split('.').collect();
+        let mut claims = sgx_claims();
+        claims["attester_tcb_status"] = json!("UpToDate");
+        let forged = format!(
+            "{}.{}.{}",
+            parts[0],
+            Base64UrlUnpadded::encode_string(claims.to_string().as_bytes()),
+            parts[2]
+        );
+        assert!(jwks()
+            .validate(&forged, INSTANCE, at(1_740_787_300))
+            .is_err());
+    }
+}
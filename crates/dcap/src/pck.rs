@@ -0,0 +1,195 @@
+use x509_cert::der::Encode;
+use x509_cert::name::Name;
+use x509_cert::Certificate;
+
+use crate::{CertificationData, Quote};
+
+/// Common name of the intermediate CA issuing PCK certificates for
+/// multi-package platforms.
+pub const PLATFORM_CA_COMMON_NAME: &str = "Intel SGX PCK Platform CA";
+
+/// Common name of the intermediate CA issuing PCK certificates for
+/// single-package processors.
+pub const PROCESSOR_CA_COMMON_NAME: &str = "Intel SGX PCK Processor CA";
+
+/// The intermediate CA that issued a PCK certificate. This selects which PCK
+/// CRL applies to the certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PckCaType {
+    Platform,
+    Processor,
+}
+
+impl PckCaType {
+    /// Name of the CA as used by the PCS `pckcrl?ca=` query parameter.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PckCaType::Platform => "platform",
+            PckCaType::Processor => "processor",
+        }
+    }
+
+    /// The CA with the given certificate subject, based on its common name.
+    pub fn from_subject(subject: &Name) -> eyre::Result<Self> {
+        let subject = subject.to_string();
+        if subject.contains(PLATFORM_CA_COMMON_NAME) {
+            Ok(PckCaType::Platform)
+        } else if subject.contains(PROCESSOR_CA_COMMON_NAME) {
+            Ok(PckCaType::Processor)
+        } else {
+            eyre::bail!("unknown PCK intermediate CA: {}", subject)
+        }
+    }
+}
+
+/// Platform registration inputs identifying a PCK certificate at the PCS or
+/// a PCCS, for quotes that carry a PCK identity (certification data types 1
+/// to 3) instead of their PCK chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PckCertQuery {
+    /// PPID encrypted with RSA-3072 (or RSA-2048) for the PCS. Required by
+    /// the Intel PCS.
+    pub encrypted_ppid: Option<Vec<u8>>,
+    /// QE ID, by which a PCCS looks up the certificates it has cached.
+    pub qe_id: Option<[u8; 16]>,
+    pub cpu_svn: [u8; 16],
+    pub pce_svn: u16,
+    pub pce_id: u16,
+}
+
+impl PckCertQuery {
+    /// The query for the PCK certificate of the platform that generated
+    /// `quote`, from its certification data and QE ID.
+    pub fn from_quote(quote: &Quote) -> eyre::Result<Self> {
+        let (identity, encrypted) =
+            match &quote.signature.qe_report_certification.certification_data {
+                CertificationData::PckIdPlainPpid(identity) => (identity, false),
+                CertificationData::PckIdEncryptedPpid2048(identity)
+                | CertificationData::PckIdEncryptedPpid3072(identity) => (identity, true),
+                other => eyre::bail!(
+                    "quote does not carry a PCK identity (certification data type {})",
+                    other.cert_type()
+                ),
+            };
+        let mut qe_id = [0u8; 16];
+        qe_id.copy_from_slice(&quote.header.user_data[..16]);
+        Ok(Self {
+            encrypted_ppid: encrypted.then(|| identity.ppid.clone()),
+            qe_id: Some(qe_id),
+            cpu_svn: identity.cpu_svn,
+            pce_svn: identity.pce_svn,
+            pce_id: identity.pce_id,
+        })
+    }
+}
+
+/// The PCK certificate chain embedded in a quote: PCK leaf certificate,
+/// Platform/Processor CA and Intel SGX Root CA.
+#[derive(Debug, Clone)]
+pub struct PckChain {
+    certificates: Vec<Certificate>,
+}
+
+impl PckChain {
+    /// Parse a concatenated PEM chain, ordered leaf first.
+    pub fn from_pem(pem: &[u8]) -> eyre::Result<Self> {
+        // The certification data is usually NUL-terminated.
+        let end = pem
+            .iter()
+            .rposition(|b| *b != 0 && !b.is_ascii_whitespace())
+            .map_or(0, |pos| pos + 1);
+
+        let certificates = Certificate::load_pem_chain(&pem[..end])
+            .map_err(|err| eyre::eyre!("invalid PCK certificate chain: {}", err))?;
+        Self::from_certificates(certificates)
+    }
+
+    pub fn from_certificates(certificates: Vec<Certificate>) -> eyre::Result<Self> {
+        if certificates.len() != 3 {
+            eyre::bail!(
+                "PCK certificate chain must contain 3 certificates, found {}",
+                certificates.len()
+            );
+        }
+        Ok(Self { certificates })
+    }
+
+    /// Extract the PCK chain from the certification data of a quote.
+    pub fn from_quote(quote: &Quote) -> eyre::Result<Self> {
+        match &quote.signature.qe_report_certification.certification_data {
+            CertificationData::PckCertChain(pem) => Self::from_pem(pem),
+            other => eyre::bail!(
+                "quote does not embed a PCK certificate chain (certification data type {})",
+                other.cert_type()
+            ),
+        }
+    }
+
+    /// A chain of a PCK leaf certificate fetched from the PCS, and the
+    /// issuer chain the PCS returned with it.
+    pub fn from_leaf(pck: Certificate, issuer_chain: Vec<Certificate>) -> eyre::Result<Self> {
+        let mut certificates = vec![pck];
+        certificates.extend(issuer_chain);
+        Self::from_certificates(certificates)
+    }
+
+    /// The PCK leaf certificate.
+    pub fn pck(&self) -> &Certificate {
+        &self.certificates[0]
+    }
+
+    /// The Platform or Processor CA certificate.
+    pub fn intermediate(&self) -> &Certificate {
+        &self.certificates[1]
+    }
+
+    /// The Intel SGX Root CA certificate, as presented in the quote.
+    pub fn root(&self) -> &Certificate {
+        &self.certificates[2]
+    }
+
+    /// All certificates, ordered leaf first.
+    pub fn certificates(&self) -> &[Certificate] {
+        &self.certificates
+    }
+
+    /// Which intermediate CA issued the PCK certificate, based on its common
+    /// name.
+    pub fn ca_type(&self) -> eyre::Result<PckCaType> {
+        PckCaType::from_subject(&self.intermediate().tbs_certificate.subject)
+    }
+
+    /// Encode the chain back to DER, leaf first.
+    pub fn to_der(&self) -> eyre::Result<Vec<Vec<u8>>> {
+        self.certificates
+            .iter()
+            .map(|cert| cert.to_der().map_err(Into::into))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestPki;
+
+    #[test]
+    fn test_pck_chain_from_pem() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let mut pem = pki.pck_chain_pem().into_bytes();
+        pem.push(0);
+
+        let chain = PckChain::from_pem(&pem)?;
+        assert_eq!(chain.pck(), &pki.pck_cert);
+        assert_eq!(chain.intermediate(), &pki.intermediate_cert);
+        assert_eq!(chain.root(), &pki.root_cert);
+        assert_eq!(chain.ca_type()?, PckCaType::Platform);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pck_chain_requires_three_certificates() {
+        let pki = TestPki::new();
+        assert!(PckChain::from_pem(pki.root_pem().as_bytes()).is_err());
+    }
+}
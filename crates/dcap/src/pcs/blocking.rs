@@ -0,0 +1,110 @@
+//! Blocking variant of [`PcsClient`](super::PcsClient).
+
+use x509_cert::Certificate;
+
+use super::{PckCertEntry, PcsConfig, PcsResponse};
+use crate::primitives::identity::{EnclaveIdentityId, EnclaveIdentityV2};
+use crate::primitives::tcb_info::{TcbInfo, TcbInfoId};
+use crate::runtime::BlockingRuntime;
+use crate::{
+    CollateralFetcher, CollateralKey, Crl, PckCaType, PckCertQuery, PckChain, QuoteCollateral,
+};
+
+/// Blocking PCS client, running the async [`PcsClient`](super::PcsClient) on
+/// a runtime of its own.
+#[derive(Debug, Clone)]
+pub struct PcsClient {
+    client: super::PcsClient,
+    runtime: BlockingRuntime,
+}
+
+impl Default for PcsClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PcsClient {
+    pub fn new() -> Self {
+        Self::with_config(PcsConfig::default())
+    }
+
+    pub fn with_config(config: PcsConfig) -> Self {
+        Self {
+            client: super::PcsClient::with_config(config),
+            runtime: BlockingRuntime::new(),
+        }
+    }
+
+    pub fn config(&self) -> &PcsConfig {
+        self.client.config()
+    }
+
+    /// Fetch the SGX or TDX TCB Info of a platform.
+    pub fn tcb_info(&self, id: TcbInfoId, fmspc: &str) -> eyre::Result<PcsResponse<TcbInfo>> {
+        self.runtime.block_on(self.client.tcb_info(id, fmspc))
+    }
+
+    /// Fetch the Enclave Identity `id`.
+    pub fn enclave_identity(
+        &self,
+        id: &EnclaveIdentityId,
+    ) -> eyre::Result<PcsResponse<EnclaveIdentityV2>> {
+        self.runtime.block_on(self.client.enclave_identity(id))
+    }
+
+    /// Fetch the identity of the SGX quoting enclave.
+    pub fn qe_identity(&self) -> eyre::Result<PcsResponse<EnclaveIdentityV2>> {
+        self.runtime.block_on(self.client.qe_identity())
+    }
+
+    /// Fetch the identity of the TD quoting enclave.
+    pub fn td_qe_identity(&self) -> eyre::Result<PcsResponse<EnclaveIdentityV2>> {
+        self.runtime.block_on(self.client.td_qe_identity())
+    }
+
+    /// Fetch the identity of the quote verification enclave.
+    pub fn qve_identity(&self) -> eyre::Result<PcsResponse<EnclaveIdentityV2>> {
+        self.runtime.block_on(self.client.qve_identity())
+    }
+
+    pub fn pck_crl(&self, ca: PckCaType) -> eyre::Result<PcsResponse<Crl>> {
+        self.runtime.block_on(self.client.pck_crl(ca))
+    }
+
+    pub fn root_ca_crl(&self) -> eyre::Result<PcsResponse<Crl>> {
+        self.runtime.block_on(self.client.root_ca_crl())
+    }
+
+    /// Fetch the PCK certificate matching the registration inputs of a
+    /// platform.
+    pub fn pck_cert(&self, query: &PckCertQuery) -> eyre::Result<PcsResponse<Certificate>> {
+        self.runtime.block_on(self.client.pck_cert(query))
+    }
+
+    /// Fetch the PCK chain of a platform, for quotes that do not embed it.
+    pub fn pck_chain(&self, query: &PckCertQuery) -> eyre::Result<PckChain> {
+        self.runtime.block_on(self.client.pck_chain(query))
+    }
+
+    /// Fetch the PCK certificates of a platform for all its TCB levels.
+    pub fn pck_certs(
+        &self,
+        encrypted_ppid: &[u8],
+        pce_id: u16,
+    ) -> eyre::Result<PcsResponse<Vec<PckCertEntry>>> {
+        self.runtime
+            .block_on(self.client.pck_certs(encrypted_ppid, pce_id))
+    }
+
+    /// Fetch everything needed to verify quotes of the platform `key`.
+    pub fn quote_collateral(&self, key: &CollateralKey) -> eyre::Result<QuoteCollateral> {
+        self.runtime.block_on(self.client.quote_collateral(key))
+    }
+}
+
+impl CollateralFetcher for PcsClient {
+    fn fetch_collateral(&self, key: &CollateralKey) -> eyre::Result<QuoteCollateral> {
+        self.quote_collateral(key)
+    }
+}
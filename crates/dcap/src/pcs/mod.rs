@@ -0,0 +1,665 @@
+//! Client for the Intel Provisioning Certification Service (PCS) v4 API, or
+//! a Provisioning Certificate Caching Service (PCCS) serving the same
+//! collateral.
+
+use der::pem::LineEnding;
+use der::{DecodePem, EncodePem};
+use percent_encoding::percent_decode_str;
+use reqwest::header::HeaderMap;
+use serde::Deserialize;
+use tee_observe::{observe_async, Operation};
+use x509_cert::Certificate;
+
+use crate::primitives::identity::{EnclaveIdentityId, EnclaveIdentityV2};
+use crate::primitives::tcb_info::{TcbInfo, TcbInfoId};
+use crate::{tee_type, CollateralKey, Crl, PckCaType, PckCertQuery, PckChain, QuoteCollateral};
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+pub const INTEL_PCS_URL: &str = "https://api.trustedservices.intel.com";
+
+/// The Intel SGX Root CA CRL, published outside of the PCS API.
+pub const INTEL_ROOT_CA_CRL_URL: &str =
+    "https://certificates.trustedservices.intel.com/IntelSGXRootCA.der";
+
+/// Header carrying the PCS subscription key.
+pub const INTEL_API_KEY_HEADER: &str = "Ocp-Apim-Subscription-Key";
+
+/// How collateral paths are laid out on the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathLayout {
+    /// The Intel PCS. The Root CA CRL is fetched from Intel's certificate
+    /// distribution point.
+    #[default]
+    Pcs,
+    /// A PCCS, which also serves the Root CA CRL (hex encoded) under
+    /// `/sgx/certification/v4/rootcacrl`.
+    Pccs,
+}
+
+/// Where and how to fetch collateral.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcsConfig {
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub api_key_header: String,
+    pub layout: PathLayout,
+}
+
+impl Default for PcsConfig {
+    fn default() -> Self {
+        Self {
+            base_url: INTEL_PCS_URL.to_string(),
+            api_key: None,
+            api_key_header: INTEL_API_KEY_HEADER.to_string(),
+            layout: PathLayout::Pcs,
+        }
+    }
+}
+
+impl PcsConfig {
+    /// A PCCS at `base_url`, e.g. `https://localhost:8081`.
+    pub fn pccs(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            layout: PathLayout::Pccs,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn with_api_key_header(mut self, header: impl Into<String>) -> Self {
+        self.api_key_header = header.into();
+        self
+    }
+
+    fn base_url(&self) -> &str {
+        self.base_url.trim_end_matches('/')
+    }
+}
+
+/// Response headers carrying the URL-encoded PEM issuer chain of the body.
+pub mod issuer_chain_headers {
+    pub const TCB_INFO: &str = "TCB-Info-Issuer-Chain";
+    pub const ENCLAVE_IDENTITY: &str = "SGX-Enclave-Identity-Issuer-Chain";
+    pub const PCK_CRL: &str = "SGX-PCK-CRL-Issuer-Chain";
+    pub const PCK_CERT: &str = "SGX-PCK-Certificate-Issuer-Chain";
+}
+
+/// A PCS response: the typed value, the raw body it was parsed from (needed
+/// to check its signature) and the issuer chain of the signer, leaf first.
+#[derive(Debug, Clone)]
+pub struct PcsResponse<T> {
+    pub value: T,
+    pub body: Vec<u8>,
+    pub issuer_chain: Vec<Certificate>,
+}
+
+/// A collateral type served by the PCS.
+pub trait PcsCollateral: Sized {
+    fn from_body(body: &[u8]) -> eyre::Result<Self>;
+}
+
+impl PcsCollateral for TcbInfo {
+    fn from_body(body: &[u8]) -> eyre::Result<Self> {
+        Ok(serde_json::from_slice(body)?)
+    }
+}
+
+impl PcsCollateral for EnclaveIdentityV2 {
+    fn from_body(body: &[u8]) -> eyre::Result<Self> {
+        Ok(serde_json::from_slice(body)?)
+    }
+}
+
+impl PcsCollateral for Crl {
+    fn from_body(body: &[u8]) -> eyre::Result<Self> {
+        Crl::parse(body)
+    }
+}
+
+impl PcsCollateral for Certificate {
+    fn from_body(body: &[u8]) -> eyre::Result<Self> {
+        Certificate::from_pem(body).map_err(|err| eyre::eyre!("invalid PCK certificate: {}", err))
+    }
+}
+
+/// One of the PCK certificates of a platform, as listed by the PCS
+/// `pckcerts` endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PckCertEntry {
+    /// Hex encoded raw TCB (CPUSVN and PCESVN) the certificate is for.
+    pub tcbm: String,
+    /// `None` if the PCS has no certificate for this TCB.
+    pub cert: Option<Certificate>,
+}
+
+impl PcsCollateral for Vec<PckCertEntry> {
+    fn from_body(body: &[u8]) -> eyre::Result<Self> {
+        #[derive(Deserialize)]
+        struct RawEntry {
+            tcbm: String,
+            cert: String,
+        }
+
+        let entries: Vec<RawEntry> = serde_json::from_slice(body)?;
+        entries
+            .into_iter()
+            .map(|entry| {
+                let cert = match entry.cert.as_str() {
+                    "Not available" => None,
+                    pem => {
+                        let pem = percent_decode_str(pem).decode_utf8()?;
+                        Some(Certificate::from_body(pem.as_bytes())?)
+                    }
+                };
+                Ok(PckCertEntry {
+                    tcbm: entry.tcbm,
+                    cert,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A request to the PCS, shared by the async and blocking clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PcsRequest {
+    pub url: String,
+    pub issuer_chain_header: Option<&'static str>,
+}
+
+impl PcsRequest {
+    pub fn tcb_info(config: &PcsConfig, id: TcbInfoId, fmspc: &str) -> Self {
+        Self {
+            url: format!(
+                "{}/{}/certification/v4/tcb?fmspc={}",
+                config.base_url(),
+                platform(id),
+                fmspc
+            ),
+            issuer_chain_header: Some(issuer_chain_headers::TCB_INFO),
+        }
+    }
+
+    pub fn enclave_identity(config: &PcsConfig, id: &EnclaveIdentityId) -> eyre::Result<Self> {
+        let path = match id {
+            EnclaveIdentityId::Qe => "sgx/certification/v4/qe/identity",
+            EnclaveIdentityId::TdQe => "tdx/certification/v4/qe/identity",
+            EnclaveIdentityId::Qve => "sgx/certification/v4/qve/identity",
+            EnclaveIdentityId::Other(id) => eyre::bail!("PCS does not serve the {} identity", id),
+        };
+        Ok(Self {
+            url: format!("{}/{}", config.base_url(), path),
+            issuer_chain_header: Some(issuer_chain_headers::ENCLAVE_IDENTITY),
+        })
+    }
+
+    pub fn pck_crl(config: &PcsConfig, ca: PckCaType) -> Self {
+        Self {
+            url: format!(
+                "{}/sgx/certification/v4/pckcrl?ca={}&encoding=der",
+                config.base_url(),
+                ca.as_str()
+            ),
+            issuer_chain_header: Some(issuer_chain_headers::PCK_CRL),
+        }
+    }
+
+    pub fn pck_cert(config: &PcsConfig, query: &PckCertQuery) -> eyre::Result<Self> {
+        let mut params = Vec::new();
+        match (&query.encrypted_ppid, config.layout) {
+            (Some(ppid), _) => params.push(format!("encrypted_ppid={}", hex::encode(ppid))),
+            (None, PathLayout::Pcs) => eyre::bail!("the PCS needs the encrypted PPID"),
+            (None, PathLayout::Pccs) => {}
+        }
+        params.push(format!("cpusvn={}", hex::encode(query.cpu_svn)));
+        params.push(format!(
+            "pcesvn={}",
+            hex::encode(query.pce_svn.to_le_bytes())
+        ));
+        params.push(format!("pceid={}", hex::encode(query.pce_id.to_le_bytes())));
+        match (&query.qe_id, config.layout) {
+            (Some(qe_id), PathLayout::Pccs) => params.push(format!("qeid={}", hex::encode(qe_id))),
+            (None, PathLayout::Pccs) => eyre::bail!("a PCCS needs the QE ID"),
+            (_, PathLayout::Pcs) => {}
+        }
+        Ok(Self {
+            url: format!(
+                "{}/sgx/certification/v4/pckcert?{}",
+                config.base_url(),
+                params.join("&")
+            ),
+            issuer_chain_header: Some(issuer_chain_headers::PCK_CERT),
+        })
+    }
+
+    pub fn pck_certs(config: &PcsConfig, encrypted_ppid: &[u8], pce_id: u16) -> Self {
+        Self {
+            url: format!(
+                "{}/sgx/certification/v4/pckcerts?encrypted_ppid={}&pceid={}",
+                config.base_url(),
+                hex::encode(encrypted_ppid),
+                hex::encode(pce_id.to_le_bytes())
+            ),
+            issuer_chain_header: Some(issuer_chain_headers::PCK_CERT),
+        }
+    }
+
+    pub fn root_ca_crl(config: &PcsConfig) -> Self {
+        let url = match config.layout {
+            PathLayout::Pcs => INTEL_ROOT_CA_CRL_URL.to_string(),
+            PathLayout::Pccs => format!("{}/sgx/certification/v4/rootcacrl", config.base_url()),
+        };
+        Self {
+            url,
+            issuer_chain_header: None,
+        }
+    }
+
+    pub fn issuer_chain(&self, headers: &HeaderMap) -> eyre::Result<Vec<Certificate>> {
+        let Some(name) = self.issuer_chain_header else {
+            return Ok(Vec::new());
+        };
+        let header = headers
+            .get(name)
+            .ok_or_else(|| eyre::eyre!("PCS response has no {} header", name))?;
+        parse_issuer_chain(header.to_str()?)
+    }
+
+    pub fn response<T: PcsCollateral>(
+        &self,
+        body: Vec<u8>,
+        issuer_chain: Vec<Certificate>,
+    ) -> eyre::Result<PcsResponse<T>> {
+        let value = T::from_body(&body)
+            .map_err(|err| err.wrap_err(format!("invalid PCS response from {}", self.url)))?;
+        Ok(PcsResponse {
+            value,
+            body,
+            issuer_chain,
+        })
+    }
+}
+
+fn platform(id: TcbInfoId) -> &'static str {
+    match id {
+        TcbInfoId::Sgx => "sgx",
+        TcbInfoId::Tdx => "tdx",
+    }
+}
+
+/// Decode a URL-encoded PEM issuer chain header.
+pub fn parse_issuer_chain(header: &str) -> eyre::Result<Vec<Certificate>> {
+    let pem = percent_decode_str(header).decode_utf8()?;
+    Certificate::load_pem_chain(pem.as_bytes())
+        .map_err(|err| eyre::eyre!("invalid issuer chain: {}", err))
+}
+
+/// The TCB Info id and FMSPC query parameter for `key`.
+fn tcb_info_query(key: &CollateralKey) -> (TcbInfoId, String) {
+    let id = match key.tee_type {
+        tee_type::TDX => TcbInfoId::Tdx,
+        _ => TcbInfoId::Sgx,
+    };
+    (id, hex::encode(key.fmspc))
+}
+
+/// Bundle PCS responses into a [`QuoteCollateral`], with issuer chains
+/// re-encoded as PEM.
+fn assemble_collateral(
+    key: &CollateralKey,
+    tcb_info: PcsResponse<TcbInfo>,
+    qe_identity: PcsResponse<EnclaveIdentityV2>,
+    pck_crl: PcsResponse<Crl>,
+    root_ca_crl: PcsResponse<Crl>,
+) -> eyre::Result<QuoteCollateral> {
+    Ok(QuoteCollateral {
+        major_version: 3,
+        minor_version: 0,
+        tee_type: key.tee_type,
+        pck_crl_issuer_chain: pem_chain(&pck_crl.issuer_chain)?,
+        root_ca_crl: root_ca_crl.body,
+        pck_crl: pck_crl.body,
+        tcb_info_issuer_chain: pem_chain(&tcb_info.issuer_chain)?,
+        tcb_info: tcb_info.body,
+        qe_identity_issuer_chain: pem_chain(&qe_identity.issuer_chain)?,
+        qe_identity: qe_identity.body,
+    })
+}
+
+fn pem_chain(chain: &[Certificate]) -> eyre::Result<Vec<u8>> {
+    let mut pem = String::new();
+    for cert in chain {
+        pem.push_str(&cert.to_pem(LineEnding::LF)?);
+    }
+    Ok(pem.into_bytes())
+}
+
+/// Async PCS client.
+#[derive(Debug, Clone)]
+pub struct PcsClient {
+    client: reqwest::Client,
+    config: PcsConfig,
+}
+
+impl Default for PcsClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PcsClient {
+    pub fn new() -> Self {
+        Self::with_config(PcsConfig::default())
+    }
+
+    pub fn with_config(config: PcsConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &PcsConfig {
+        &self.config
+    }
+
+    /// Fetch the SGX or TDX TCB Info of a platform.
+    pub async fn tcb_info(&self, id: TcbInfoId, fmspc: &str) -> eyre::Result<PcsResponse<TcbInfo>> {
+        self.fetch(PcsRequest::tcb_info(&self.config, id, fmspc))
+            .await
+    }
+
+    /// Fetch the Enclave Identity `id`.
+    pub async fn enclave_identity(
+        &self,
+        id: &EnclaveIdentityId,
+    ) -> eyre::Result<PcsResponse<EnclaveIdentityV2>> {
+        self.fetch(PcsRequest::enclave_identity(&self.config, id)?)
+            .await
+    }
+
+    /// Fetch the identity of the SGX quoting enclave.
+    pub async fn qe_identity(&self) -> eyre::Result<PcsResponse<EnclaveIdentityV2>> {
+        self.enclave_identity(&EnclaveIdentityId::Qe).await
+    }
+
+    /// Fetch the identity of the TD quoting enclave.
+    pub async fn td_qe_identity(&self) -> eyre::Result<PcsResponse<EnclaveIdentityV2>> {
+        self.enclave_identity(&EnclaveIdentityId::TdQe).await
+    }
+
+    /// Fetch the identity of the quote verification enclave.
+    pub async fn qve_identity(&self) -> eyre::Result<PcsResponse<EnclaveIdentityV2>> {
+        self.enclave_identity(&EnclaveIdentityId::Qve).await
+    }
+
+    pub async fn pck_crl(&self, ca: PckCaType) -> eyre::Result<PcsResponse<Crl>> {
+        self.fetch(PcsRequest::pck_crl(&self.config, ca)).await
+    }
+
+    pub async fn root_ca_crl(&self) -> eyre::Result<PcsResponse<Crl>> {
+        self.fetch(PcsRequest::root_ca_crl(&self.config)).await
+    }
+
+    /// Fetch the PCK certificate matching the registration inputs of a
+    /// platform.
+    pub async fn pck_cert(&self, query: &PckCertQuery) -> eyre::Result<PcsResponse<Certificate>> {
+        self.fetch(PcsRequest::pck_cert(&self.config, query)?).await
+    }
+
+    /// Fetch the PCK chain of a platform, for quotes that do not embed it.
+    pub async fn pck_chain(&self, query: &PckCertQuery) -> eyre::Result<PckChain> {
+        let response = self.pck_cert(query).await?;
+        PckChain::from_leaf(response.value, response.issuer_chain)
+    }
+
+    /// Fetch the PCK certificates of a platform for all its TCB levels.
+    pub async fn pck_certs(
+        &self,
+        encrypted_ppid: &[u8],
+        pce_id: u16,
+    ) -> eyre::Result<PcsResponse<Vec<PckCertEntry>>> {
+        self.fetch(PcsRequest::pck_certs(&self.config, encrypted_ppid, pce_id))
+            .await
+    }
+
+    /// Fetch everything needed to verify quotes of the platform `key`.
+    pub async fn quote_collateral(&self, key: &CollateralKey) -> eyre::Result<QuoteCollateral> {
+        let fetch = async {
+            let (id, fmspc) = tcb_info_query(key);
+            let tcb_info = self.tcb_info(id, &fmspc).await?;
+            let qe_identity = self
+                .enclave_identity(&EnclaveIdentityId::quoting_enclave(key.tee_type))
+                .await?;
+            let pck_crl = self.pck_crl(key.ca).await?;
+            let root_ca_crl = self.root_ca_crl().await?;
+            assemble_collateral(key, tcb_info, qe_identity, pck_crl, root_ca_crl)
+        };
+        let tee = tee_type::name(key.tee_type);
+        observe_async(Operation::CollateralFetch, tee, fetch).await
+    }
+
+    async fn fetch<T: PcsCollateral>(&self, request: PcsRequest) -> eyre::Result<PcsResponse<T>> {
+        let mut builder = self.client.get(&request.url);
+        if let Some(api_key) = &self.config.api_key {
+            builder = builder.header(&self.config.api_key_header, api_key);
+        }
+        let response = builder.send().await?.error_for_status()?;
+        let issuer_chain = request.issuer_chain(response.headers())?;
+        let body = response.bytes().await?.to_vec();
+        request.response(body, issuer_chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestPki;
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_request_urls() {
+        assert_eq!(
+            PcsRequest::tcb_info(&PcsConfig::default(), TcbInfoId::Tdx, "90c06f000000").url,
+            "https://api.trustedservices.intel.com/tdx/certification/v4/tcb?fmspc=90c06f000000"
+        );
+        assert_eq!(
+            PcsRequest::enclave_identity(&PcsConfig::default(), &EnclaveIdentityId::Qe)
+                .unwrap()
+                .url,
+            "https://api.trustedservices.intel.com/sgx/certification/v4/qe/identity"
+        );
+        assert_eq!(
+            PcsRequest::enclave_identity(&PcsConfig::default(), &EnclaveIdentityId::TdQe)
+                .unwrap()
+                .url,
+            "https://api.trustedservices.intel.com/tdx/certification/v4/qe/identity"
+        );
+        assert!(PcsRequest::enclave_identity(
+            &PcsConfig::default(),
+            &EnclaveIdentityId::Other("QAE".to_string())
+        )
+        .is_err());
+        assert_eq!(
+            PcsRequest::pck_crl(&PcsConfig::default(), PckCaType::Platform).url,
+            "https://api.trustedservices.intel.com/sgx/certification/v4/pckcrl?ca=platform&encoding=der"
+        );
+    }
+
+    #[test]
+    fn test_pck_cert_urls() -> eyre::Result<()> {
+        let query = PckCertQuery {
+            encrypted_ppid: Some(vec![0xAB; 4]),
+            qe_id: Some([0x01; 16]),
+            cpu_svn: [0x02; 16],
+            pce_svn: 13,
+            pce_id: 0,
+        };
+        assert_eq!(
+            PcsRequest::pck_cert(&PcsConfig::default(), &query)?.url,
+            "https://api.trustedservices.intel.com/sgx/certification/v4/pckcert\
+             ?encrypted_ppid=abababab&cpusvn=02020202020202020202020202020202&pcesvn=0d00&pceid=0000"
+        );
+        assert_eq!(
+            PcsRequest::pck_cert(&PcsConfig::pccs("https://pccs:8081"), &query)?.url,
+            "https://pccs:8081/sgx/certification/v4/pckcert?encrypted_ppid=abababab\
+             &cpusvn=02020202020202020202020202020202&pcesvn=0d00&pceid=0000\
+             &qeid=01010101010101010101010101010101"
+        );
+
+        let plain = PckCertQuery {
+            encrypted_ppid: None,
+            ..query.clone()
+        };
+        assert!(PcsRequest::pck_cert(&PcsConfig::default(), &plain).is_err());
+        assert!(PcsRequest::pck_cert(&PcsConfig::pccs("https://pccs:8081"), &plain).is_ok());
+        assert_eq!(
+            PcsRequest::pck_certs(&PcsConfig::default(), &[0xAB; 2], 0).url,
+            "https://api.trustedservices.intel.com/sgx/certification/v4/pckcerts\
+             ?encrypted_ppid=abab&pceid=0000"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_pck_cert_responses() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let pem = crate::test_utils::to_pem(&pki.pck_cert);
+        assert_eq!(Certificate::from_body(pem.as_bytes())?, pki.pck_cert);
+
+        let body = serde_json::json!([
+            {
+                "tcb": {"pcesvn": 13},
+                "tcbm": "0202020202020202020202020202020d00",
+                "cert": utf8_percent_encode(&pem, NON_ALPHANUMERIC).to_string(),
+            },
+            {"tcb": {"pcesvn": 5}, "tcbm": "00", "cert": "Not available"},
+        ]);
+        let entries = Vec::<PckCertEntry>::from_body(body.to_string().as_bytes())?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].cert.as_ref(), Some(&pki.pck_cert));
+        assert_eq!(entries[1].cert, None);
+
+        let chain = PckChain::from_leaf(
+            pki.pck_cert.clone(),
+            vec![pki.intermediate_cert.clone(), pki.root_cert.clone()],
+        )?;
+        assert_eq!(chain.intermediate(), &pki.intermediate_cert);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pccs_layout() -> eyre::Result<()> {
+        let config = PcsConfig::pccs("https://pccs.internal:8081/").with_api_key("secret");
+        assert_eq!(config.api_key_header, INTEL_API_KEY_HEADER);
+        assert_eq!(
+            PcsRequest::tcb_info(&config, TcbInfoId::Sgx, "00606a000000").url,
+            "https://pccs.internal:8081/sgx/certification/v4/tcb?fmspc=00606a000000"
+        );
+        assert_eq!(
+            PcsRequest::root_ca_crl(&config).url,
+            "https://pccs.internal:8081/sgx/certification/v4/rootcacrl"
+        );
+        assert_eq!(
+            PcsRequest::root_ca_crl(&PcsConfig::default()).url,
+            INTEL_ROOT_CA_CRL_URL
+        );
+
+        // The PCCS serves the Root CA CRL hex encoded.
+        let crl = TestPki::new().root_crl(&[]);
+        let der = crl.to_der()?;
+        assert_eq!(Crl::from_body(hex::encode(&der).as_bytes())?, crl);
+        assert_eq!(Crl::from_body(&der)?, crl);
+        Ok(())
+    }
+
+    #[test]
+    fn test_issuer_chain_header() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let pem = [
+            crate::test_utils::to_pem(&pki.tcb_signing_cert),
+            pki.root_pem(),
+        ]
+        .concat();
+        let encoded = utf8_percent_encode(&pem, NON_ALPHANUMERIC).to_string();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            issuer_chain_headers::TCB_INFO,
+            HeaderValue::from_str(&encoded)?,
+        );
+
+        let request = PcsRequest::tcb_info(&PcsConfig::default(), TcbInfoId::Sgx, "00606a000000");
+        let chain = request.issuer_chain(&headers)?;
+        assert_eq!(chain, vec![pki.tcb_signing_cert, pki.root_cert]);
+
+        let request = PcsRequest::pck_crl(&PcsConfig::default(), PckCaType::Processor);
+        assert!(request.issuer_chain(&headers).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_response_keeps_raw_body() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let document = pki.sign_collateral(
+            include_str!("../primitives/data/tcb_info_v2.json"),
+            "tcbInfo",
+        );
+        let request = PcsRequest::tcb_info(&PcsConfig::default(), TcbInfoId::Sgx, "00606a000000");
+        let response: PcsResponse<TcbInfo> = request.response(
+            document.clone().into_bytes(),
+            vec![pki.tcb_signing_cert.clone()],
+        )?;
+        assert_eq!(response.body, document.as_bytes());
+        response
+            .value
+            .verify_signature(&document, &response.issuer_chain[0])
+    }
+
+    #[test]
+    fn test_assemble_collateral() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let key = CollateralKey {
+            tee_type: tee_type::SGX,
+            fmspc: [0x00, 0x90, 0x6E, 0xA1, 0x00, 0x00],
+            ca: PckCaType::Platform,
+        };
+        let mut expected = crate::test_utils::quote_collateral(&pki, tee_type::SGX);
+        // The PCS serves documents without the trailing NUL of the C API.
+        expected.tcb_info = expected.tcb_info_json()?.as_bytes().to_vec();
+        let (id, fmspc) = tcb_info_query(&key);
+        assert_eq!((id, fmspc.as_str()), (TcbInfoId::Sgx, "00906ea10000"));
+
+        let tcb_info = PcsRequest::tcb_info(&PcsConfig::default(), id, &fmspc)
+            .response(expected.tcb_info.clone(), expected.tcb_info_issuer_chain()?)?;
+        let qe_identity = PcsRequest::enclave_identity(
+            &PcsConfig::default(),
+            &EnclaveIdentityId::quoting_enclave(key.tee_type),
+        )?
+        .response(
+            expected.qe_identity.clone(),
+            expected.qe_identity_issuer_chain()?,
+        )?;
+        let pck_crl = PcsRequest::pck_crl(&PcsConfig::default(), key.ca)
+            .response(expected.pck_crl.clone(), expected.pck_crl_issuer_chain()?)?;
+        let root_ca_crl = PcsRequest::root_ca_crl(&PcsConfig::default())
+            .response(expected.root_ca_crl.clone(), Vec::new())?;
+
+        let collateral = assemble_collateral(&key, tcb_info, qe_identity, pck_crl, root_ca_crl)?;
+        assert_eq!(collateral, expected);
+        Ok(())
+    }
+}
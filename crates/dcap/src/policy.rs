@@ -0,0 +1,401 @@
+//! Declarative appraisal of verified quotes.
+//!
+//! A [`Policy`] is plain data, so deployments can keep it in a TOML or JSON
+//! file next to the verifier and change it without recompiling:
+//!
+//! ```toml
+//! tcb_statuses = ["UpToDate", "SWHardeningNeeded"]
+//! tolerated_advisories = ["INTEL-SA-00615"]
+//! max_collateral_age_secs = 2592000
+//! mr_td = ["1111...11"]
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::primitives::tcb_info::TcbStatus;
+use crate::VerificationResult;
+
+/// What a verified quote must satisfy to be accepted.
+///
+/// Measurement lists are hex encoded and allow any value when empty. SGX
+/// measurements can only be satisfied by SGX quotes and TD measurements by
+/// TDX quotes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    /// Accepted TCB statuses, only `UpToDate` by default.
+    pub tcb_statuses: Vec<TcbStatus>,
+    /// Advisories accepted in the result; any other advisory is rejected.
+    pub tolerated_advisories: Vec<String>,
+    /// Maximum age in seconds of the TCB Info and QE Identity used.
+    pub max_collateral_age_secs: Option<u64>,
+    pub mr_enclave: Vec<String>,
+    pub mr_signer: Vec<String>,
+    pub min_isv_svn: Option<u16>,
+    pub mr_td: Vec<String>,
+    pub rtmr0: Vec<String>,
+    pub rtmr1: Vec<String>,
+    pub rtmr2: Vec<String>,
+    pub rtmr3: Vec<String>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            tcb_statuses: vec![TcbStatus::UpToDate],
+            tolerated_advisories: Vec::new(),
+            max_collateral_age_secs: None,
+            mr_enclave: Vec::new(),
+            mr_signer: Vec::new(),
+            min_isv_svn: None,
+            mr_td: Vec::new(),
+            rtmr0: Vec::new(),
+            rtmr1: Vec::new(),
+            rtmr2: Vec::new(),
+            rtmr3: Vec::new(),
+        }
+    }
+}
+
+/// A reason a [`Policy`] rejected a result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    TcbStatus(TcbStatus),
+    Advisory(String),
+    StaleCollateral {
+        issue_date: DateTime<Utc>,
+    },
+    /// The quote has a measurement not in the allowed list.
+    Measurement {
+        name: &'static str,
+        value: String,
+    },
+    /// The policy constrains a measurement the quote does not carry, e.g.
+    /// `mr_td` for an SGX quote.
+    MissingMeasurement(&'static str),
+    IsvSvn {
+        isv_svn: u16,
+        min_isv_svn: u16,
+    },
+}
+
+impl std::fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TcbStatus(status) => write!(f, "TCB status {:?} is not accepted", status),
+            Self::Advisory(id) => write!(f, "advisory {} is not tolerated", id),
+            Self::StaleCollateral { issue_date } => {
+                write!(
+                    f,
+                    "collateral issued at {} is too old",
+                    issue_date.to_rfc3339()
+                )
+            }
+            Self::Measurement { name, value } => write!(f, "{} {} is not allowed", name, value),
+            Self::MissingMeasurement(name) => write!(f, "quote carries no {}", name),
+            Self::IsvSvn {
+                isv_svn,
+                min_isv_svn,
+            } => write!(f, "ISVSVN {} is below {}", isv_svn, min_isv_svn),
+        }
+    }
+}
+
+/// The outcome of [`Policy::evaluate`]: accepted if there are no violations.
+///
+/// Returned wrapped in an [`eyre::Report`] by [`PolicyVerdict::into_result`];
+/// use `downcast_ref::<PolicyVerdict>()` to inspect the violations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PolicyVerdict {
+    pub violations: Vec<PolicyViolation>,
+}
+
+impl PolicyVerdict {
+    pub fn is_accepted(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    pub fn into_result(self) -> eyre::Result<()> {
+        if self.is_accepted() {
+            Ok(())
+        } else {
+            Err(self.into())
+        }
+    }
+}
+
+impl std::fmt::Display for PolicyVerdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_accepted() {
+            return write!(f, "accepted by policy");
+        }
+        write!(f, "rejected by policy: ")?;
+        for (i, violation) in self.violations.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PolicyVerdict {}
+
+impl Policy {
+    pub fn from_json(json: &str) -> eyre::Result<Self> {
+        let policy: Self = serde_json::from_str(json)?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> eyre::Result<Self> {
+        let policy: Self = toml::from_str(toml)?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Check that every measurement is hex of the right size.
+    pub fn validate(&self) -> eyre::Result<()> {
+        let measurements = [
+            ("mr_enclave", &self.mr_enclave, 32),
+            ("mr_signer", &self.mr_signer, 32),
+            ("mr_td", &self.mr_td, 48),
+            ("rtmr0", &self.rtmr0, 48),
+            ("rtmr1", &self.rtmr1, 48),
+            ("rtmr2", &self.rtmr2, 48),
+            ("rtmr3", &self.rtmr3, 48),
+        ];
+        for (name, values, size) in measurements {
+            for value in values {
+                let bytes = hex::decode(value)
+                    .map_err(|err| eyre::eyre!("invalid {} {}: {}", name, value, err))?;
+                if bytes.len() != size {
+                    eyre::bail!("{} {} must be {} bytes", name, value, size);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Appraise `result` at the current time.
+    pub fn evaluate(&self, result: &VerificationResult) -> PolicyVerdict {
+        self.evaluate_at(result, Utc::now())
+    }
+
+    /// Appraise `result` at `at`, collecting every violation.
+    pub fn evaluate_at(&self, result: &VerificationResult, at: DateTime<Utc>) -> PolicyVerdict {
+        let mut violations = Vec::new();
+
+        if !self.tcb_statuses.contains(&result.status) {
+            violations.push(PolicyViolation::TcbStatus(result.status));
+        }
+        for id in &result.advisory_ids {
+            if !self.tolerated_advisories.contains(id) {
+                violations.push(PolicyViolation::Advisory(id.clone()));
+            }
+        }
+        let max_age = self
+            .max_collateral_age_secs
+            .and_then(|secs| chrono::Duration::try_seconds(secs.try_into().ok()?));
+        if let Some(max_age) = max_age {
+            if at.signed_duration_since(result.collateral_issue_date) > max_age {
+                violations.push(PolicyViolation::StaleCollateral {
+                    issue_date: result.collateral_issue_date,
+                });
+            }
+        }
+
+        let enclave = result.quote.body.as_enclave_report();
+        check_measurement(
+            &mut violations,
+            "mr_enclave",
+            &self.mr_enclave,
+            enclave.map(|report| &report.mr_enclave[..]),
+        );
+        check_measurement(
+            &mut violations,
+            "mr_signer",
+            &self.mr_signer,
+            enclave.map(|report| &report.mr_signer[..]),
+        );
+        if let Some(min_isv_svn) = self.min_isv_svn {
+            match enclave {
+                Some(report) if report.isv_svn < min_isv_svn => {
+                    violations.push(PolicyViolation::IsvSvn {
+                        isv_svn: report.isv_svn,
+                        min_isv_svn,
+                    })
+                }
+                Some(_) => {}
+                None => violations.push(PolicyViolation::MissingMeasurement("isv_svn")),
+            }
+        }
+
+        let td = result.quote.body.as_td_report();
+        check_measurement(
+            &mut violations,
+            "mr_td",
+            &self.mr_td,
+            td.map(|report| &report.mr_td[..]),
+        );
+        let rtmrs = [&self.rtmr0, &self.rtmr1, &self.rtmr2, &self.rtmr3];
+        for (index, (name, allowed)) in ["rtmr0", "rtmr1", "rtmr2", "rtmr3"]
+            .into_iter()
+            .zip(rtmrs)
+            .enumerate()
+        {
+            check_measurement(
+                &mut violations,
+                name,
+                allowed,
+                td.map(|report| &report.rtmrs[index][..]),
+            );
+        }
+
+        PolicyVerdict { violations }
+    }
+}
+
+fn check_measurement(
+    violations: &mut Vec<PolicyViolation>,
+    name: &'static str,
+    allowed: &[String],
+    value: Option<&[u8]>,
+) {
+    if allowed.is_empty() {
+        return;
+    }
+    match value {
+        Some(value) => {
+            let value = hex::encode(value);
+            if !allowed
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(&value))
+            {
+                violations.push(PolicyViolation::Measurement { name, value });
+            }
+        }
+        None => violations.push(PolicyViolation::MissingMeasurement(name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{quote_collateral, sgx_quote, tdx_quote, verification_time, TestPki};
+    use crate::{tee_type, verify_quote_with_root};
+
+    fn verify(tee: u32) -> eyre::Result<VerificationResult> {
+        let pki = TestPki::new();
+        let quote = if tee == tee_type::TDX {
+            tdx_quote(&pki)
+        } else {
+            sgx_quote(&pki)
+        };
+        let collateral = quote_collateral(&pki, tee);
+        verify_quote_with_root(&quote, &collateral, &pki.root_cert, verification_time())
+    }
+
+    #[test]
+    fn test_sgx_measurements() -> eyre::Result<()> {
+        let mut result = verify(tee_type::SGX)?;
+        result.status = TcbStatus::UpToDate;
+        result.advisory_ids.clear();
+
+        let policy = Policy::from_json(&format!(
+            r#"{{"mr_enclave": ["{}"], "mr_signer": ["{}"], "min_isv_svn": 2}}"#,
+            "AA".repeat(32),
+            "bb".repeat(32)
+        ))?;
+        assert!(policy
+            .evaluate_at(&result, verification_time())
+            .is_accepted());
+
+        let policy = Policy {
+            mr_enclave: vec!["00".repeat(32)],
+            min_isv_svn: Some(3),
+            mr_td: vec!["11".repeat(48)],
+            ..Policy::default()
+        };
+        let verdict = policy.evaluate_at(&result, verification_time());
+        assert_eq!(
+            verdict.violations,
+            [
+                PolicyViolation::Measurement {
+                    name: "mr_enclave",
+                    value: "aa".repeat(32)
+                },
+                PolicyViolation::IsvSvn {
+                    isv_svn: 2,
+                    min_isv_svn: 3
+                },
+                PolicyViolation::MissingMeasurement("mr_td"),
+            ]
+        );
+        let err = verdict.into_result().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PolicyVerdict>()
+                .unwrap()
+                .violations
+                .len(),
+            3
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_status_advisories_and_age() -> eyre::Result<()> {
+        let mut result = verify(tee_type::TDX)?;
+        result.status = TcbStatus::SWHardeningNeeded;
+        result.advisory_ids = vec!["INTEL-SA-00615".to_string()];
+
+        let verdict = Policy::default().evaluate_at(&result, verification_time());
+        assert_eq!(
+            verdict.violations,
+            [
+                PolicyViolation::TcbStatus(TcbStatus::SWHardeningNeeded),
+                PolicyViolation::Advisory("INTEL-SA-00615".to_string()),
+            ]
+        );
+
+        let policy = Policy {
+            tcb_statuses: vec![TcbStatus::UpToDate, TcbStatus::SWHardeningNeeded],
+            tolerated_advisories: vec!["INTEL-SA-00615".to_string()],
+            max_collateral_age_secs: Some(30 * 24 * 60 * 60),
+            rtmr3: vec!["23".repeat(48)],
+            ..Policy::default()
+        };
+        assert!(policy
+            .evaluate_at(&result, verification_time())
+            .is_accepted());
+
+        let later = verification_time() + chrono::Duration::days(60);
+        assert!(matches!(
+            policy.evaluate_at(&result, later).violations[..],
+            [PolicyViolation::StaleCollateral { .. }]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(Policy::from_json("{}").unwrap(), Policy::default());
+        assert!(Policy::from_json(r#"{"mr_td": ["abcd"]}"#).is_err());
+        assert!(Policy::from_json(r#"{"mrtd": []}"#).is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_policy() -> eyre::Result<()> {
+        let policy = Policy::from_toml(&format!(
+            "tcb_statuses = [\"UpToDate\", \"OutOfDate\"]\nrtmr0 = [\"{}\"]\n",
+            "20".repeat(48)
+        ))?;
+        assert_eq!(policy.tcb_statuses[1], TcbStatus::OutOfDate);
+        assert_eq!(policy.rtmr0.len(), 1);
+        Ok(())
+    }
+}
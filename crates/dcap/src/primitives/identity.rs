@@ -1,5 +1,9 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use x509_cert::Certificate;
+
+use super::signed::{signed_body, verify_body_signature, SignedEnclaveIdentity};
+use crate::tee_type;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EnclaveIdentityV2 {
@@ -8,9 +12,95 @@ pub struct EnclaveIdentityV2 {
     pub signature: String,
 }
 
+impl EnclaveIdentityV2 {
+    /// Parse an Enclave Identity document and verify its signature with
+    /// `tcb_signing_cert`.
+    pub fn from_json_verified(
+        document: &str,
+        tcb_signing_cert: &Certificate,
+    ) -> eyre::Result<Self> {
+        let signed = SignedEnclaveIdentity::parse(document)?;
+        signed.verify(tcb_signing_cert)?;
+        Ok(Self {
+            enclave_identity: signed.body,
+            signature: signed.signature,
+        })
+    }
+
+    pub fn is_fresh(&self, at: DateTime<Utc>) -> bool {
+        self.enclave_identity.is_fresh(at)
+    }
+
+    /// Verify `signature` over the raw `enclaveIdentity` body of `document`.
+    pub fn verify_signature(
+        &self,
+        document: &str,
+        tcb_signing_cert: &Certificate,
+    ) -> eyre::Result<()> {
+        let body = signed_body(document, "enclaveIdentity")?;
+        verify_body_signature(body, &self.signature, tcb_signing_cert)
+            .map_err(|err| err.wrap_err("Enclave Identity signature is invalid"))
+    }
+}
+
+/// The enclave an identity describes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum EnclaveIdentityId {
+    /// The SGX quoting enclave.
+    Qe,
+    /// The TD quoting enclave.
+    TdQe,
+    /// The quote verification enclave.
+    Qve,
+    Other(String),
+}
+
+impl EnclaveIdentityId {
+    /// The identity of the quoting enclave signing quotes of `tee_type`.
+    pub fn quoting_enclave(tee_type: u32) -> Self {
+        match tee_type {
+            tee_type::TDX => Self::TdQe,
+            _ => Self::Qe,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Qe => "QE",
+            Self::TdQe => "TD_QE",
+            Self::Qve => "QVE",
+            Self::Other(id) => id,
+        }
+    }
+}
+
+impl From<String> for EnclaveIdentityId {
+    fn from(id: String) -> Self {
+        match id.as_str() {
+            "QE" => Self::Qe,
+            "TD_QE" => Self::TdQe,
+            "QVE" => Self::Qve,
+            _ => Self::Other(id),
+        }
+    }
+}
+
+impl From<EnclaveIdentityId> for String {
+    fn from(id: EnclaveIdentityId) -> Self {
+        id.as_str().to_string()
+    }
+}
+
+impl std::fmt::Display for EnclaveIdentityId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EnclaveIdentity {
-    pub id: String,
+    pub id: EnclaveIdentityId,
     pub version: u32,
     #[serde(rename = "issueDate")]
     pub issue_date: DateTime<Utc>,
@@ -30,6 +120,13 @@ pub struct EnclaveIdentity {
     pub tcb_levels: Vec<TcbLevel>,
 }
 
+impl EnclaveIdentity {
+    /// Whether `at` falls between the issue date and the next update.
+    pub fn is_fresh(&self, at: DateTime<Utc>) -> bool {
+        self.issue_date <= at && at <= self.next_update
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TcbLevel {
     pub tcb: Tcb,
@@ -46,7 +143,7 @@ pub struct Tcb {
     pub isvsvn: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub enum TcbStatus {
     UpToDate,
@@ -54,9 +151,27 @@ pub enum TcbStatus {
     Revoked,
 }
 
+impl From<TcbStatus> for super::tcb_info::TcbStatus {
+    fn from(status: TcbStatus) -> Self {
+        match status {
+            TcbStatus::UpToDate => Self::UpToDate,
+            TcbStatus::OutOfDate => Self::OutOfDate,
+            TcbStatus::Revoked => Self::Revoked,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::TestPki;
+
+    fn sample_identity() -> EnclaveIdentity {
+        let example = include_str!("./data/enclave_identity_v2.json");
+        serde_json::from_str::<EnclaveIdentityV2>(example)
+            .unwrap()
+            .enclave_identity
+    }
 
     #[test]
     fn test_enclave_identity_v2_serde() -> eyre::Result<()> {
@@ -64,4 +179,52 @@ mod tests {
         let _: EnclaveIdentityV2 = serde_json::from_str(example).unwrap();
         Ok(())
     }
+
+    #[test]
+    fn test_enclave_identity_id() -> eyre::Result<()> {
+        assert_eq!(sample_identity().id, EnclaveIdentityId::Qe);
+        for (id, name) in [
+            (EnclaveIdentityId::Qe, "QE"),
+            (EnclaveIdentityId::TdQe, "TD_QE"),
+            (EnclaveIdentityId::Qve, "QVE"),
+            (EnclaveIdentityId::Other("QAE".to_string()), "QAE"),
+        ] {
+            assert_eq!(serde_json::to_string(&id)?, format!("\"{}\"", name));
+            assert_eq!(
+                serde_json::from_str::<EnclaveIdentityId>(&format!("\"{}\"", name))?,
+                id
+            );
+        }
+        assert_eq!(
+            EnclaveIdentityId::quoting_enclave(tee_type::TDX),
+            EnclaveIdentityId::TdQe
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_enclave_identity_is_fresh() {
+        let example = include_str!("./data/enclave_identity_v2.json");
+        let identity: EnclaveIdentityV2 = serde_json::from_str(example).unwrap();
+        let next_update = identity.enclave_identity.next_update;
+        assert!(identity.is_fresh(next_update));
+        assert!(!identity.is_fresh(next_update + chrono::Duration::seconds(1)));
+        assert!(
+            !identity.is_fresh(identity.enclave_identity.issue_date - chrono::Duration::days(1))
+        );
+    }
+
+    #[test]
+    fn test_enclave_identity_signature() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let example = include_str!("./data/enclave_identity_v2.json");
+        let document = pki.sign_collateral(example, "enclaveIdentity");
+        let identity = EnclaveIdentityV2::from_json_verified(&document, &pki.tcb_signing_cert)?;
+        assert_eq!(identity.enclave_identity.isvprodid, 1);
+
+        let tampered = document.replacen("\"isvprodid\": 1", "\"isvprodid\": 2", 1);
+        assert_ne!(tampered, document);
+        assert!(EnclaveIdentityV2::from_json_verified(&tampered, &pki.tcb_signing_cert).is_err());
+        Ok(())
+    }
 }
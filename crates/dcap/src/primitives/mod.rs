@@ -1,2 +1,4 @@
-mod identity;
-mod tcb_info;
+pub mod identity;
+pub mod normalized;
+pub mod signed;
+pub mod tcb_info;
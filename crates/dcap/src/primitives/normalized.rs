@@ -0,0 +1,384 @@
+//! A single view of every supported TCB Info and Enclave Identity version.
+//!
+//! The serde types in [`tcb_info`](super::tcb_info) and
+//! [`identity`](super::identity) follow the JSON of each schema version. They
+//! are normalized here once, with hex fields decoded and optional fields
+//! resolved, so that verification does not depend on the document shape.
+
+use chrono::{DateTime, Utc};
+
+use super::identity::{EnclaveIdentity, EnclaveIdentityId};
+use super::tcb_info::{
+    TcbInfoData, TcbInfoId, TcbStatus, TdxModule, TdxModuleIdentity, TdxModuleStatus,
+};
+use crate::{EnclaveReportBody, TdReportBody};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcbInfoVersion {
+    V2,
+    V3,
+}
+
+impl TryFrom<u32> for TcbInfoVersion {
+    type Error = eyre::Report;
+
+    fn try_from(version: u32) -> eyre::Result<Self> {
+        match version {
+            2 => Ok(Self::V2),
+            3 => Ok(Self::V3),
+            other => eyre::bail!("unsupported TCB Info version {}", other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnclaveIdentityVersion {
+    V2,
+}
+
+impl TryFrom<u32> for EnclaveIdentityVersion {
+    type Error = eyre::Report;
+
+    fn try_from(version: u32) -> eyre::Result<Self> {
+        match version {
+            2 => Ok(Self::V2),
+            other => eyre::bail!("unsupported Enclave Identity version {}", other),
+        }
+    }
+}
+
+/// TCB Info of any supported version.
+#[derive(Debug, Clone)]
+pub struct NormalizedTcbInfo {
+    pub version: TcbInfoVersion,
+    /// [`TcbInfoId::Sgx`] for v2, which predates the field.
+    pub id: TcbInfoId,
+    pub issue_date: DateTime<Utc>,
+    pub next_update: DateTime<Utc>,
+    pub fmspc: [u8; 6],
+    pub pce_id: [u8; 2],
+    pub tcb_type: u32,
+    pub tcb_evaluation_data_number: u32,
+    pub tdx_module: Option<TdxModule>,
+    pub tdx_module_identities: Vec<TdxModuleIdentity>,
+    pub tcb_levels: Vec<NormalizedTcbLevel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedTcbLevel {
+    pub sgx_tcb_comp_svns: [u8; 16],
+    pub pcesvn: u32,
+    /// Present in TDX TCB Info.
+    pub tdx_tcb_comp_svns: Option<[u8; 16]>,
+    pub tcb_date: DateTime<Utc>,
+    pub tcb_status: TcbStatus,
+    pub advisory_ids: Vec<String>,
+}
+
+impl TcbInfoData {
+    pub fn normalize(&self) -> eyre::Result<NormalizedTcbInfo> {
+        let version = TcbInfoVersion::try_from(self.version)?;
+        let id = match (version, self.id) {
+            (TcbInfoVersion::V2, None) => TcbInfoId::Sgx,
+            (TcbInfoVersion::V3, Some(id)) => id,
+            (TcbInfoVersion::V2, Some(_)) => eyre::bail!("v2 TCB Info has an id"),
+            (TcbInfoVersion::V3, None) => eyre::bail!("v3 TCB Info has no id"),
+        };
+
+        let tcb_levels = self
+            .tcb_levels
+            .iter()
+            .map(|level| {
+                let sgx_tcb_comp_svns = level
+                    .tcb
+                    .sgx_tcb_comp_svns()
+                    .ok_or_else(|| eyre::eyre!("TCB level has no SGX components"))?;
+                let tdx_tcb_comp_svns = level.tcb.tdx_tcb_comp_svns();
+                if (id == TcbInfoId::Tdx) != tdx_tcb_comp_svns.is_some() {
+                    eyre::bail!("TCB level TDX components do not match the TCB Info id");
+                }
+                Ok(NormalizedTcbLevel {
+                    sgx_tcb_comp_svns,
+                    pcesvn: level.tcb.pcesvn,
+                    tdx_tcb_comp_svns,
+                    tcb_date: level.tcb_date,
+                    tcb_status: level.tcb_status,
+                    advisory_ids: level.advisory_ids.clone().unwrap_or_default(),
+                })
+            })
+            .collect::<eyre::Result<_>>()?;
+
+        Ok(NormalizedTcbInfo {
+            version,
+            id,
+            issue_date: self.issue_date,
+            next_update: self.next_update,
+            fmspc: decode_hex(&self.fmspc, "fmspc")?,
+            pce_id: decode_hex(&self.pce_id, "pceId")?,
+            tcb_type: self.tcb_type,
+            tcb_evaluation_data_number: self.tcb_evaluation_data_number,
+            tdx_module: self.tdx_module.clone(),
+            tdx_module_identities: self.tdx_module_identities.clone().unwrap_or_default(),
+            tcb_levels,
+        })
+    }
+}
+
+impl NormalizedTcbInfo {
+    /// Evaluate the TDX module that produced `report`.
+    ///
+    /// The major version of the module is `tee_tcb_svn[1]`. Version 0 modules
+    /// are only checked against `tdxModule`; newer ones must match the
+    /// `TDX_<major>` entry of `tdxModuleIdentities`, whose TCB levels then
+    /// decide the status from the module SVN, `tee_tcb_svn[0]`.
+    pub fn evaluate_tdx_module(&self, report: &TdReportBody) -> eyre::Result<TdxModuleStatus> {
+        let isv_svn = report.tee_tcb_svn[0];
+        let major_version = report.tee_tcb_svn[1];
+
+        if major_version == 0 {
+            let module = self
+                .tdx_module
+                .as_ref()
+                .ok_or_else(|| eyre::eyre!("TCB Info has no tdxModule"))?;
+            if !module.matches(report)? {
+                eyre::bail!("TDX module signer or attributes do not match the TCB Info");
+            }
+            return Ok(TdxModuleStatus {
+                status: TcbStatus::UpToDate,
+                advisory_ids: Vec::new(),
+            });
+        }
+
+        let id = format!("TDX_{:02}", major_version);
+        let identity = self
+            .tdx_module_identities
+            .iter()
+            .find(|identity| identity.id == id)
+            .ok_or_else(|| eyre::eyre!("TCB Info has no TDX module identity {}", id))?;
+        if !identity.matches(report)? {
+            eyre::bail!("TDX module {} signer or attributes do not match", id);
+        }
+
+        let level = identity
+            .tcb_levels
+            .iter()
+            .find(|level| level.tcb.isvsvn <= isv_svn)
+            .ok_or_else(|| eyre::eyre!("TDX module {} SVN {} has no TCB level", id, isv_svn))?;
+        Ok(TdxModuleStatus {
+            status: level.tcb_status,
+            advisory_ids: level.advisory_ids.clone().unwrap_or_default(),
+        })
+    }
+}
+
+/// Enclave Identity of any supported version.
+#[derive(Debug, Clone)]
+pub struct NormalizedEnclaveIdentity {
+    pub version: EnclaveIdentityVersion,
+    pub id: EnclaveIdentityId,
+    pub issue_date: DateTime<Utc>,
+    pub next_update: DateTime<Utc>,
+    pub tcb_evaluation_data_number: u32,
+    pub miscselect: u32,
+    pub miscselect_mask: u32,
+    pub attributes: [u8; 16],
+    pub attributes_mask: [u8; 16],
+    pub mrsigner: [u8; 32],
+    pub isvprodid: u16,
+    pub tcb_levels: Vec<NormalizedQeTcbLevel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedQeTcbLevel {
+    pub isvsvn: u32,
+    pub tcb_date: DateTime<Utc>,
+    pub tcb_status: TcbStatus,
+    pub advisory_ids: Vec<String>,
+}
+
+impl EnclaveIdentity {
+    pub fn normalize(&self) -> eyre::Result<NormalizedEnclaveIdentity> {
+        Ok(NormalizedEnclaveIdentity {
+            version: EnclaveIdentityVersion::try_from(self.version)?,
+            id: self.id.clone(),
+            issue_date: self.issue_date,
+            next_update: self.next_update,
+            tcb_evaluation_data_number: self.tcb_evaluation_data_number,
+            miscselect: u32::from_str_radix(&self.miscselect, 16)?,
+            miscselect_mask: u32::from_str_radix(&self.miscselect_mask, 16)?,
+            attributes: decode_hex(&self.attributes, "attributes")?,
+            attributes_mask: decode_hex(&self.attributes_mask, "attributesMask")?,
+            mrsigner: decode_hex(&self.mrsigner, "mrsigner")?,
+            isvprodid: self.isvprodid,
+            tcb_levels: self
+                .tcb_levels
+                .iter()
+                .map(|level| NormalizedQeTcbLevel {
+                    isvsvn: level.tcb.isvsvn,
+                    tcb_date: level.tcb_date,
+                    tcb_status: level.tcb_status.into(),
+                    advisory_ids: level.advisory_ids.clone().unwrap_or_default(),
+                })
+                .collect(),
+        })
+    }
+}
+
+impl NormalizedEnclaveIdentity {
+    /// Check that `report` comes from this enclave and select its TCB level.
+    ///
+    /// MRSIGNER and ISVPRODID must match exactly, MISCSELECT and the
+    /// attributes after applying their masks. The TCB level is the first one
+    /// whose ISVSVN the report meets.
+    pub fn evaluate(&self, report: &EnclaveReportBody) -> eyre::Result<&NormalizedQeTcbLevel> {
+        if report.mr_signer != self.mrsigner {
+            eyre::bail!("{} MRSIGNER does not match the Enclave Identity", self.id);
+        }
+        if report.isv_prod_id != self.isvprodid {
+            eyre::bail!(
+                "{} ISVPRODID {} does not match the Enclave Identity ({})",
+                self.id,
+                report.isv_prod_id,
+                self.isvprodid
+            );
+        }
+        if report.misc_select & self.miscselect_mask != self.miscselect {
+            eyre::bail!("{} MISCSELECT does not match the Enclave Identity", self.id);
+        }
+        let attributes_match = report
+            .attributes
+            .iter()
+            .zip(&self.attributes_mask)
+            .map(|(attribute, mask)| attribute & mask)
+            .eq(self.attributes);
+        if !attributes_match {
+            eyre::bail!("{} attributes do not match the Enclave Identity", self.id);
+        }
+
+        self.tcb_levels
+            .iter()
+            .find(|level| level.isvsvn <= u32::from(report.isv_svn))
+            .ok_or_else(|| eyre::eyre!("{} ISVSVN {} has no TCB level", self.id, report.isv_svn))
+    }
+}
+
+fn decode_hex<const N: usize>(value: &str, field: &str) -> eyre::Result<[u8; N]> {
+    hex::decode(value)?
+        .try_into()
+        .map_err(|_| eyre::eyre!("{} must be {} bytes", field, N))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::identity::EnclaveIdentityV2;
+    use crate::primitives::tcb_info::TcbInfo;
+    use crate::test_utils::qe_report;
+    use tss_serde::TssReader;
+
+    fn tcb_info(document: &str) -> TcbInfoData {
+        serde_json::from_str::<TcbInfo>(document).unwrap().tcb_info
+    }
+
+    fn sample_identity() -> EnclaveIdentity {
+        let document = include_str!("data/enclave_identity_v2.json");
+        serde_json::from_str::<EnclaveIdentityV2>(document)
+            .unwrap()
+            .enclave_identity
+    }
+
+    fn sample_report() -> EnclaveReportBody {
+        EnclaveReportBody::from_reader(&mut TssReader::new(&qe_report(&[0; 64]))).unwrap()
+    }
+
+    #[test]
+    fn test_normalize_tcb_info_versions() -> eyre::Result<()> {
+        let v2 = tcb_info(include_str!("data/tcb_info_v2.json")).normalize()?;
+        assert_eq!((v2.version, v2.id), (TcbInfoVersion::V2, TcbInfoId::Sgx));
+        assert_eq!(v2.fmspc, [0x00, 0x60, 0x6a, 0x00, 0x00, 0x00]);
+        assert_eq!(
+            v2.tcb_levels[0].sgx_tcb_comp_svns,
+            [14, 14, 3, 3, 255, 255, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(v2.tcb_levels[0].tdx_tcb_comp_svns, None);
+        assert!(v2.tdx_module_identities.is_empty());
+
+        let v3 = tcb_info(include_str!("data/tcb_info_v3.json")).normalize()?;
+        assert_eq!((v3.version, v3.id), (TcbInfoVersion::V3, TcbInfoId::Tdx));
+        assert_eq!(
+            v3.tcb_levels[0].sgx_tcb_comp_svns[..8],
+            [2, 2, 2, 2, 3, 1, 0, 5]
+        );
+        assert_eq!(v3.tcb_levels[0].tdx_tcb_comp_svns.unwrap()[..3], [5, 0, 2]);
+        assert_eq!(v3.tdx_module_identities.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_inconsistent_tcb_info() {
+        let mut v2 = tcb_info(include_str!("data/tcb_info_v2.json"));
+        v2.version = 4;
+        assert!(v2.normalize().is_err());
+        v2.version = 3;
+        assert!(v2.normalize().is_err());
+
+        let mut v3 = tcb_info(include_str!("data/tcb_info_v3.json"));
+        v3.id = Some(TcbInfoId::Sgx);
+        assert!(v3.normalize().is_err());
+        v3.id = Some(TcbInfoId::Tdx);
+        v3.fmspc.push('0');
+        assert!(v3.normalize().is_err());
+    }
+
+    #[test]
+    fn test_normalize_enclave_identity() -> eyre::Result<()> {
+        let mut identity = sample_identity();
+        let normalized = identity.normalize()?;
+        assert_eq!(normalized.version, EnclaveIdentityVersion::V2);
+        assert_eq!(normalized.id, EnclaveIdentityId::Qe);
+        assert_eq!(normalized.isvprodid, 1);
+        assert_eq!(normalized.tcb_levels[0].tcb_status, TcbStatus::UpToDate);
+
+        identity.version = 1;
+        assert!(identity.normalize().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_report() -> eyre::Result<()> {
+        let identity = sample_identity().normalize()?;
+        let mut report = sample_report();
+        assert_eq!(identity.evaluate(&report)?.tcb_status, TcbStatus::UpToDate);
+
+        report.isv_svn = 7;
+        let level = identity.evaluate(&report)?;
+        assert_eq!((level.isvsvn, level.tcb_status), (6, TcbStatus::OutOfDate));
+
+        report.isv_svn = 0;
+        assert!(identity.evaluate(&report).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_masks_attributes() -> eyre::Result<()> {
+        let identity = sample_identity().normalize()?;
+        let mut report = sample_report();
+
+        // MODE64BIT is masked out.
+        report.attributes[0] |= 0x04;
+        identity.evaluate(&report)?;
+
+        // DEBUG is not.
+        report.attributes[0] |= 0x02;
+        assert!(identity.evaluate(&report).is_err());
+
+        let mut report = sample_report();
+        report.misc_select = 1;
+        assert!(identity.evaluate(&report).is_err());
+
+        let mut report = sample_report();
+        report.mr_signer[0] ^= 1;
+        assert!(identity.evaluate(&report).is_err());
+        Ok(())
+    }
+}
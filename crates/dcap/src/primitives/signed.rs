@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+
+use serde::de::DeserializeOwned;
+use serde_json::value::RawValue;
+use x509_cert::Certificate;
+
+use super::identity::{EnclaveIdentity, EnclaveIdentityV2};
+use super::tcb_info::{TcbInfo, TcbInfoData};
+use crate::crypto::{verify_raw_signature, verifying_key_from_certificate};
+
+/// Extract the exact bytes of the top-level `field` of a signed collateral
+/// document, e.g. `tcbInfo` or `enclaveIdentity`.
+///
+/// Intel signs the body as served, so it must not be re-serialized before
+/// hashing.
+pub fn signed_body<'a>(document: &'a str, field: &str) -> eyre::Result<&'a str> {
+    let fields: BTreeMap<String, &'a RawValue> = serde_json::from_str(document)?;
+    let body = fields
+        .get(field)
+        .ok_or_else(|| eyre::eyre!("signed document has no `{}` field", field))?;
+    Ok(body.get())
+}
+
+/// The body of a signed collateral document.
+pub trait SignedBody: DeserializeOwned {
+    /// The top-level field holding the body, e.g. `tcbInfo`.
+    const FIELD: &'static str;
+    /// How the document is named in errors.
+    const NAME: &'static str;
+}
+
+impl SignedBody for TcbInfoData {
+    const FIELD: &'static str = "tcbInfo";
+    const NAME: &'static str = "TCB Info";
+}
+
+impl SignedBody for EnclaveIdentity {
+    const FIELD: &'static str = "enclaveIdentity";
+    const NAME: &'static str = "Enclave Identity";
+}
+
+/// A signed collateral document, parsed once: the typed body together with
+/// the exact bytes it was parsed from, which the signature covers.
+#[derive(Debug, Clone)]
+pub struct SignedCollateral<T> {
+    pub body: T,
+    raw_body: String,
+    /// Hex encoded `r || s` signature over the raw body.
+    pub signature: String,
+}
+
+pub type SignedTcbInfo = SignedCollateral<TcbInfoData>;
+pub type SignedEnclaveIdentity = SignedCollateral<EnclaveIdentity>;
+
+impl<T: SignedBody> SignedCollateral<T> {
+    pub fn parse(document: &str) -> eyre::Result<Self> {
+        #[derive(serde::Deserialize)]
+        struct Signature {
+            signature: String,
+        }
+
+        let raw_body = signed_body(document, T::FIELD)?;
+        let Signature { signature } = serde_json::from_str(document)?;
+        Ok(Self {
+            body: serde_json::from_str(raw_body)?,
+            raw_body: raw_body.to_string(),
+            signature,
+        })
+    }
+
+    /// The body exactly as served.
+    pub fn raw_body(&self) -> &str {
+        &self.raw_body
+    }
+
+    /// Verify the signature over the raw body with `signing_cert`.
+    pub fn verify(&self, signing_cert: &Certificate) -> eyre::Result<()> {
+        verify_body_signature(&self.raw_body, &self.signature, signing_cert)
+            .map_err(|err| err.wrap_err(format!("{} signature is invalid", T::NAME)))
+    }
+}
+
+/// Verify a hex encoded `r || s` ECDSA P-256 `signature` over `body` with the
+/// key of `signing_cert`.
+pub fn verify_body_signature(
+    body: &str,
+    signature: &str,
+    signing_cert: &Certificate,
+) -> eyre::Result<()> {
+    let signature: [u8; 64] = hex::decode(signature)?
+        .try_into()
+        .map_err(|_| eyre::eyre!("collateral signature must be 64 bytes"))?;
+    let key = verifying_key_from_certificate(signing_cert)?;
+    verify_raw_signature(&key, body.as_bytes(), &signature)
+}
+
+/// Parse the TCB Info and QE Identity documents of a collateral bundle and
+/// verify both signatures with `tcb_signing_cert`.
+///
+/// The TCB signing certificate itself must be validated separately, e.g. with
+/// [`crate::validate_certificate_chain`].
+pub fn verify_collateral_signatures(
+    tcb_info: &str,
+    qe_identity: &str,
+    tcb_signing_cert: &Certificate,
+) -> eyre::Result<(TcbInfo, EnclaveIdentityV2)> {
+    let tcb_info = TcbInfo::from_json_verified(tcb_info, tcb_signing_cert)?;
+    let qe_identity = EnclaveIdentityV2::from_json_verified(qe_identity, tcb_signing_cert)?;
+    Ok((tcb_info, qe_identity))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestPki;
+
+    #[test]
+    fn test_signed_body_is_verbatim() -> eyre::Result<()> {
+        let document = r#"{ "body": {"b": 1,  "a" : [2, 3]}, "signature": "00" }"#;
+        assert_eq!(signed_body(document, "body")?, r#"{"b": 1,  "a" : [2, 3]}"#);
+        assert!(signed_body(document, "missing").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_signed_collateral_keeps_raw_body() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let document = pki.sign_collateral(include_str!("data/tcb_info_v2.json"), "tcbInfo");
+        let signed = SignedTcbInfo::parse(&document)?;
+        assert_eq!(signed.raw_body(), signed_body(&document, "tcbInfo")?);
+        assert_eq!(signed.body.fmspc, "00606a000000");
+        signed.verify(&pki.tcb_signing_cert)?;
+        assert!(signed.verify(&pki.pck_cert).is_err());
+
+        assert!(SignedEnclaveIdentity::parse(&document).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_collateral_signatures() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let tcb_info = pki.sign_collateral(include_str!("data/tcb_info_v2.json"), "tcbInfo");
+        let qe_identity = pki.sign_collateral(
+            include_str!("data/enclave_identity_v2.json"),
+            "enclaveIdentity",
+        );
+        verify_collateral_signatures(&tcb_info, &qe_identity, &pki.tcb_signing_cert)?;
+
+        // The documents are not interchangeable.
+        assert!(
+            verify_collateral_signatures(&qe_identity, &tcb_info, &pki.tcb_signing_cert).is_err()
+        );
+        Ok(())
+    }
+}
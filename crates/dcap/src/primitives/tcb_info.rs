@@ -1,4 +1,9 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use x509_cert::Certificate;
+
+use super::signed::{signed_body, verify_body_signature, SignedTcbInfo};
+use crate::TdReportBody;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TcbInfo {
@@ -7,13 +12,48 @@ pub struct TcbInfo {
     pub signature: String,
 }
 
+impl TcbInfo {
+    /// Parse a TCB Info document and verify its signature with
+    /// `tcb_signing_cert`.
+    pub fn from_json_verified(
+        document: &str,
+        tcb_signing_cert: &Certificate,
+    ) -> eyre::Result<Self> {
+        let signed = SignedTcbInfo::parse(document)?;
+        signed.verify(tcb_signing_cert)?;
+        Ok(Self {
+            tcb_info: signed.body,
+            signature: signed.signature,
+        })
+    }
+
+    pub fn is_fresh(&self, at: DateTime<Utc>) -> bool {
+        self.tcb_info.is_fresh(at)
+    }
+
+    /// Verify `signature` over the raw `tcbInfo` body of `document`, the JSON
+    /// this TCB Info was parsed from.
+    pub fn verify_signature(
+        &self,
+        document: &str,
+        tcb_signing_cert: &Certificate,
+    ) -> eyre::Result<()> {
+        let body = signed_body(document, "tcbInfo")?;
+        verify_body_signature(body, &self.signature, tcb_signing_cert)
+            .map_err(|err| err.wrap_err("TCB Info signature is invalid"))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TcbInfoData {
+    /// Present since v3.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<TcbInfoId>,
     pub version: u32,
     #[serde(rename = "issueDate")]
-    pub issue_date: String,
+    pub issue_date: DateTime<Utc>,
     #[serde(rename = "nextUpdate")]
-    pub next_update: String,
+    pub next_update: DateTime<Utc>,
     pub fmspc: String,
     #[serde(rename = "pceId")]
     pub pce_id: String,
@@ -21,38 +61,375 @@ pub struct TcbInfoData {
     pub tcb_type: u32,
     #[serde(rename = "tcbEvaluationDataNumber")]
     pub tcb_evaluation_data_number: u32,
+    /// Present in v3 TDX TCB Info.
+    #[serde(rename = "tdxModule", default, skip_serializing_if = "Option::is_none")]
+    pub tdx_module: Option<TdxModule>,
+    /// Present in v3 TDX TCB Info.
+    #[serde(
+        rename = "tdxModuleIdentities",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub tdx_module_identities: Option<Vec<TdxModuleIdentity>>,
     #[serde(rename = "tcbLevels")]
     pub tcb_levels: Vec<TcbLevel>,
 }
 
+impl TcbInfoData {
+    /// Whether `at` falls between the issue date and the next update.
+    pub fn is_fresh(&self, at: DateTime<Utc>) -> bool {
+        self.issue_date <= at && at <= self.next_update
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TcbInfoId {
+    #[serde(rename = "SGX")]
+    Sgx,
+    #[serde(rename = "TDX")]
+    Tdx,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TdxModule {
+    pub mrsigner: String,
+    pub attributes: String,
+    #[serde(rename = "attributesMask")]
+    pub attributes_mask: String,
+}
+
+impl TdxModule {
+    /// Check the signer and attributes of the TDX module (SEAM) that produced
+    /// `report`.
+    pub fn matches(&self, report: &TdReportBody) -> eyre::Result<bool> {
+        matches_tdx_module(
+            &self.mrsigner,
+            &self.attributes,
+            &self.attributes_mask,
+            report,
+        )
+    }
+}
+
+/// The identity of one TDX module major version, e.g. `TDX_01`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TdxModuleIdentity {
+    pub id: String,
+    pub mrsigner: String,
+    pub attributes: String,
+    #[serde(rename = "attributesMask")]
+    pub attributes_mask: String,
+    #[serde(rename = "tcbLevels")]
+    pub tcb_levels: Vec<TdxModuleTcbLevel>,
+}
+
+impl TdxModuleIdentity {
+    pub fn matches(&self, report: &TdReportBody) -> eyre::Result<bool> {
+        matches_tdx_module(
+            &self.mrsigner,
+            &self.attributes,
+            &self.attributes_mask,
+            report,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TdxModuleTcbLevel {
+    pub tcb: TdxModuleTcb,
+    #[serde(rename = "tcbDate")]
+    pub tcb_date: DateTime<Utc>,
+    #[serde(rename = "tcbStatus")]
+    pub tcb_status: TcbStatus,
+    #[serde(
+        rename = "advisoryIDs",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub advisory_ids: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TdxModuleTcb {
+    pub isvsvn: u8,
+}
+
+/// The outcome of the TDX module TCB evaluation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TdxModuleStatus {
+    pub status: TcbStatus,
+    pub advisory_ids: Vec<String>,
+}
+
+fn matches_tdx_module(
+    mrsigner: &str,
+    attributes: &str,
+    attributes_mask: &str,
+    report: &TdReportBody,
+) -> eyre::Result<bool> {
+    let mrsigner = hex::decode(mrsigner)?;
+    let attributes = hex::decode(attributes)?;
+    let attributes_mask = hex::decode(attributes_mask)?;
+    if attributes.len() != 8 || attributes_mask.len() != 8 {
+        eyre::bail!("TDX module attributes must be 8 bytes");
+    }
+
+    let attributes_match = report
+        .seam_attributes
+        .iter()
+        .zip(&attributes_mask)
+        .map(|(attribute, mask)| attribute & mask)
+        .eq(attributes.iter().copied());
+    Ok(mrsigner == report.mr_signer_seam && attributes_match)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TcbLevel {
     pub tcb: Tcb,
     #[serde(rename = "tcbDate")]
-    pub tcb_date: String,
+    pub tcb_date: DateTime<Utc>,
     #[serde(rename = "tcbStatus")]
     pub tcb_status: TcbStatus,
+    #[serde(
+        rename = "advisoryIDs",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub advisory_ids: Option<Vec<String>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TcbStatus {
-    OutOfDate,
-    OutOfDateConfigurationNeeded,
+    UpToDate,
     SWHardeningNeeded,
+    ConfigurationNeeded,
     ConfigurationAndSWHardeningNeeded,
+    OutOfDate,
+    OutOfDateConfigurationNeeded,
+    Revoked,
 }
 
+/// A TCB level. v2 lists the SGX component SVNs as flat fields, v3 as
+/// `sgxtcbcomponents` (plus `tdxtcbcomponents` for TDX).
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tcb {
+    #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
+    pub sgx_tcb_comp_svns_v2: Option<SgxTcbCompSvnsV2>,
+    #[serde(
+        rename = "sgxtcbcomponents",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sgx_tcb_components: Option<Vec<TcbComponent>>,
     pub pcesvn: u32,
+    #[serde(
+        rename = "tdxtcbcomponents",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub tdx_tcb_components: Option<Vec<TcbComponent>>,
+}
+
+impl Tcb {
+    /// The 16 SGX TCB component SVNs, whichever the schema version.
+    pub fn sgx_tcb_comp_svns(&self) -> Option<[u8; 16]> {
+        match (&self.sgx_tcb_comp_svns_v2, &self.sgx_tcb_components) {
+            (Some(svns), _) => Some(svns.to_array()),
+            (None, Some(components)) => component_svns(components),
+            (None, None) => None,
+        }
+    }
+
+    /// The 16 TDX TCB component SVNs of a v3 TDX TCB level.
+    pub fn tdx_tcb_comp_svns(&self) -> Option<[u8; 16]> {
+        self.tdx_tcb_components.as_deref().and_then(component_svns)
+    }
+}
+
+fn component_svns(components: &[TcbComponent]) -> Option<[u8; 16]> {
+    let svns: Vec<u8> = components.iter().map(|component| component.svn).collect();
+    svns.try_into().ok()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TcbComponent {
+    pub svn: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub component_type: Option<String>,
+}
+
+/// The flat `sgxtcbcompNNsvn` fields of a v2 TCB level.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SgxTcbCompSvnsV2 {
+    pub sgxtcbcomp01svn: u8,
+    pub sgxtcbcomp02svn: u8,
+    pub sgxtcbcomp03svn: u8,
+    pub sgxtcbcomp04svn: u8,
+    pub sgxtcbcomp05svn: u8,
+    pub sgxtcbcomp06svn: u8,
+    pub sgxtcbcomp07svn: u8,
+    pub sgxtcbcomp08svn: u8,
+    pub sgxtcbcomp09svn: u8,
+    pub sgxtcbcomp10svn: u8,
+    pub sgxtcbcomp11svn: u8,
+    pub sgxtcbcomp12svn: u8,
+    pub sgxtcbcomp13svn: u8,
+    pub sgxtcbcomp14svn: u8,
+    pub sgxtcbcomp15svn: u8,
+    pub sgxtcbcomp16svn: u8,
+}
+
+impl SgxTcbCompSvnsV2 {
+    pub fn to_array(&self) -> [u8; 16] {
+        [
+            self.sgxtcbcomp01svn,
+            self.sgxtcbcomp02svn,
+            self.sgxtcbcomp03svn,
+            self.sgxtcbcomp04svn,
+            self.sgxtcbcomp05svn,
+            self.sgxtcbcomp06svn,
+            self.sgxtcbcomp07svn,
+            self.sgxtcbcomp08svn,
+            self.sgxtcbcomp09svn,
+            self.sgxtcbcomp10svn,
+            self.sgxtcbcomp11svn,
+            self.sgxtcbcomp12svn,
+            self.sgxtcbcomp13svn,
+            self.sgxtcbcomp14svn,
+            self.sgxtcbcomp15svn,
+            self.sgxtcbcomp16svn,
+        ]
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::TestPki;
 
     #[test]
     fn test_tcb_info_v2() {
-        let _: TcbInfo = serde_json::from_str(include_str!("data/tcb_info_v2.json")).unwrap();
+        let tcb_info: TcbInfo =
+            serde_json::from_str(include_str!("data/tcb_info_v2.json")).unwrap();
+        let tcb_info = tcb_info.tcb_info;
+        assert_eq!(tcb_info.id, None);
+        assert!(tcb_info.tdx_module.is_none());
+
+        let tcb = &tcb_info.tcb_levels[0].tcb;
+        assert_eq!(
+            tcb.sgx_tcb_comp_svns().unwrap(),
+            [14, 14, 3, 3, 255, 255, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(tcb.pcesvn, 13);
+        assert_eq!(tcb.tdx_tcb_comp_svns(), None);
+    }
+
+    #[test]
+    fn test_tcb_info_v3() {
+        let tcb_info: TcbInfo =
+            serde_json::from_str(include_str!("data/tcb_info_v3.json")).unwrap();
+        let tcb_info = tcb_info.tcb_info;
+        assert_eq!(tcb_info.version, 3);
+        assert_eq!(
+            tcb_info.issue_date.to_rfc3339(),
+            "2025-02-12T10:38:59+00:00"
+        );
+        assert_eq!(tcb_info.id, Some(TcbInfoId::Tdx));
+        assert_eq!(
+            tcb_info.tdx_module.unwrap().attributes_mask,
+            "FFFFFFFFFFFFFFFF"
+        );
+
+        let level = &tcb_info.tcb_levels[0];
+        assert_eq!(level.tcb_status, TcbStatus::UpToDate);
+        assert!(level.tcb.sgx_tcb_comp_svns_v2.is_none());
+
+        let components = level.tcb.sgx_tcb_components.as_ref().unwrap();
+        assert_eq!(components[0].category.as_deref(), Some("BIOS"));
+        assert_eq!(
+            components[0].component_type.as_deref(),
+            Some("Early Microcode Update")
+        );
+        assert_eq!(
+            level.tcb.sgx_tcb_comp_svns().unwrap()[..8],
+            [2, 2, 2, 2, 3, 1, 0, 5]
+        );
+        assert_eq!(level.tcb.tdx_tcb_comp_svns().unwrap()[..3], [5, 0, 2]);
+
+        let out_of_date = tcb_info
+            .tcb_levels
+            .iter()
+            .find(|level| level.tcb_status == TcbStatus::OutOfDate)
+            .unwrap();
+        assert!(out_of_date.advisory_ids.as_ref().unwrap().len() > 1);
+    }
+
+    fn tdx_module_report(major_version: u8, isv_svn: u8) -> TdReportBody {
+        let mut report = crate::test_utils::td_report_body();
+        report[0] = isv_svn;
+        report[1] = major_version;
+        let mut reader = tss_serde::TssReader::new(&report);
+        TdReportBody::from_reader(&mut reader).unwrap()
+    }
+
+    #[test]
+    fn test_evaluate_tdx_module() -> eyre::Result<()> {
+        let tcb_info: TcbInfo = serde_json::from_str(include_str!("data/tcb_info_v3.json"))?;
+        let tcb_info = tcb_info.tcb_info.normalize()?;
+        assert_eq!(tcb_info.tdx_module_identities.len(), 2);
+
+        let status = tcb_info.evaluate_tdx_module(&tdx_module_report(1, 4))?;
+        assert_eq!(status.status, TcbStatus::UpToDate);
+        let status = tcb_info.evaluate_tdx_module(&tdx_module_report(1, 3))?;
+        assert_eq!(status.status, TcbStatus::OutOfDate);
+        assert!(tcb_info
+            .evaluate_tdx_module(&tdx_module_report(1, 1))
+            .is_err());
+
+        // Legacy modules are only matched against tdxModule.
+        let status = tcb_info.evaluate_tdx_module(&tdx_module_report(0, 5))?;
+        assert_eq!(status.status, TcbStatus::UpToDate);
+
+        // Unknown major version.
+        assert!(tcb_info
+            .evaluate_tdx_module(&tdx_module_report(7, 1))
+            .is_err());
+
+        // Wrong SEAM signer.
+        let mut report = tdx_module_report(3, 3);
+        report.mr_signer_seam[0] = 1;
+        assert!(tcb_info.evaluate_tdx_module(&report).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tcb_info_is_fresh() {
+        let tcb_info: TcbInfo =
+            serde_json::from_str(include_str!("data/tcb_info_v2.json")).unwrap();
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        assert!(tcb_info.is_fresh(at("2025-03-01T00:00:00Z")));
+        assert!(tcb_info.is_fresh(at("2025-03-15T03:29:42Z")));
+        assert!(!tcb_info.is_fresh(at("2025-03-15T03:29:43Z")));
+        assert!(!tcb_info.is_fresh(at("2025-02-01T00:00:00Z")));
+    }
+
+    #[test]
+    fn test_tcb_info_signature() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let document = pki.sign_collateral(include_str!("data/tcb_info_v2.json"), "tcbInfo");
+        let tcb_info = TcbInfo::from_json_verified(&document, &pki.tcb_signing_cert)?;
+        assert_eq!(tcb_info.tcb_info.fmspc, "00606a000000");
+
+        // Re-serializing changes the signed bytes.
+        let reserialized = serde_json::to_string_pretty(&tcb_info)?;
+        assert!(tcb_info
+            .verify_signature(&reserialized, &pki.tcb_signing_cert)
+            .is_err());
+
+        // Signed by another key.
+        assert!(TcbInfo::from_json_verified(&document, &pki.pck_cert).is_err());
+        Ok(())
     }
 }
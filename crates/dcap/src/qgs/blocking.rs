@@ -0,0 +1,39 @@
+//! Blocking variant of [`QgsClient`](super::QgsClient).
+
+use std::time::Duration;
+
+use super::get_tdreport;
+
+/// Blocking QGS client.
+#[derive(Debug, Clone, Default)]
+pub struct QgsClient {
+    client: super::QgsClient,
+}
+
+impl QgsClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_address(cid: u32, port: u32) -> Self {
+        Self {
+            client: super::QgsClient::with_address(cid, port),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = self.client.with_timeout(timeout);
+        self
+    }
+
+    /// Get a quote for `tdreport`, a TDREPORT of this guest.
+    pub fn get_quote(&self, tdreport: &[u8]) -> eyre::Result<Vec<u8>> {
+        self.client.request_quote(tdreport)
+    }
+
+    /// Get a TDREPORT over `report_data` from the TDX guest driver, then a
+    /// quote for it.
+    pub fn get_quote_for(&self, report_data: &[u8; 64]) -> eyre::Result<Vec<u8>> {
+        self.get_quote(&get_tdreport(report_data)?)
+    }
+}
@@ -0,0 +1,333 @@
+//! TDX quote generation through the Quote Generation Service (QGS) on the
+//! host, reached over vsock.
+//!
+//! A request carries the TDREPORT of the guest; messages are the QGS
+//! `GET_QUOTE` structures, each preceded by its big-endian length. vsock
+//! and the TDX guest device are blocking interfaces, so the async client
+//! runs them on the blocking pool of tokio.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::time::Duration;
+
+use tss_serde::TssReader;
+
+use crate::quote::{read_u16_le, read_u32_le};
+use crate::TDREPORT_SIZE;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+/// vsock CID of the host.
+pub const VMADDR_CID_HOST: u32 = 2;
+
+/// Port QGS listens on by default.
+pub const QGS_DEFAULT_PORT: u32 = 4050;
+
+const QGS_MSG_MAJOR_VERSION: u16 = 1;
+const QGS_MSG_MINOR_VERSION: u16 = 0;
+const QGS_MSG_HEADER_SIZE: usize = 16;
+const GET_QUOTE_REQ: u32 = 0;
+const GET_QUOTE_RESP: u32 = 1;
+const MAX_RESPONSE_SIZE: usize = 1 << 20;
+
+const TDX_GUEST_DEVICE: &str = "/dev/tdx_guest";
+/// `_IOWR('T', 1, struct tdx_report_req)`
+const TDX_CMD_GET_REPORT0: libc::c_ulong = 0xc440_5401;
+
+/// QGS rejected a request.
+///
+/// Returned wrapped in an [`eyre::Report`]; use `downcast_ref::<QgsError>()`
+/// to inspect the `qgs_msg_error_t` code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QgsError {
+    pub code: u32,
+}
+
+impl std::fmt::Display for QgsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "QGS request failed with error code {:#x}", self.code)
+    }
+}
+
+impl std::error::Error for QgsError {}
+
+/// Requests TDX quotes from the QGS of the host.
+#[derive(Debug, Clone)]
+pub struct QgsClient {
+    cid: u32,
+    port: u32,
+    timeout: Duration,
+}
+
+impl Default for QgsClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QgsClient {
+    pub fn new() -> Self {
+        Self::with_address(VMADDR_CID_HOST, QGS_DEFAULT_PORT)
+    }
+
+    pub fn with_address(cid: u32, port: u32) -> Self {
+        Self {
+            cid,
+            port,
+            timeout: Duration::from_secs(60),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Get a quote for `tdreport`, a TDREPORT of this guest.
+    pub async fn get_quote(&self, tdreport: &[u8]) -> eyre::Result<Vec<u8>> {
+        let client = self.clone();
+        let tdreport = tdreport.to_vec();
+        tokio::task::spawn_blocking(move || client.request_quote(&tdreport)).await?
+    }
+
+    /// Get a TDREPORT over `report_data` from the TDX guest driver, then a
+    /// quote for it.
+    pub async fn get_quote_for(&self, report_data: &[u8; 64]) -> eyre::Result<Vec<u8>> {
+        let client = self.clone();
+        let report_data = *report_data;
+        tokio::task::spawn_blocking(move || client.request_quote(&get_tdreport(&report_data)?))
+            .await?
+    }
+
+    fn request_quote(&self, tdreport: &[u8]) -> eyre::Result<Vec<u8>> {
+        let mut stream = self.connect()?;
+        get_quote(&mut stream, tdreport)
+    }
+
+    fn connect(&self) -> eyre::Result<File> {
+        // SAFETY: plain socket calls; the descriptor is owned by the returned
+        // file from the moment it is created.
+        unsafe {
+            let fd = libc::socket(libc::AF_VSOCK, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0);
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+            let socket = OwnedFd::from_raw_fd(fd);
+
+            let timeout = libc::timeval {
+                tv_sec: self.timeout.as_secs() as libc::time_t,
+                tv_usec: self.timeout.subsec_micros() as libc::suseconds_t,
+            };
+            for option in [libc::SO_RCVTIMEO, libc::SO_SNDTIMEO] {
+                if libc::setsockopt(
+                    fd,
+                    libc::SOL_SOCKET,
+                    option,
+                    &timeout as *const _ as *const libc::c_void,
+                    std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+                ) < 0
+                {
+                    return Err(std::io::Error::last_os_error().into());
+                }
+            }
+
+            let mut address: libc::sockaddr_vm = std::mem::zeroed();
+            address.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+            address.svm_cid = self.cid;
+            address.svm_port = self.port;
+            if libc::connect(
+                fd,
+                &address as *const _ as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t,
+            ) < 0
+            {
+                let err = std::io::Error::last_os_error();
+                eyre::bail!(
+                    "cannot connect to QGS at vsock {}:{}: {}",
+                    self.cid,
+                    self.port,
+                    err
+                );
+            }
+            Ok(File::from(socket))
+        }
+    }
+}
+
+/// Get a TDREPORT over `report_data` from `/dev/tdx_guest`.
+pub fn get_tdreport(report_data: &[u8; 64]) -> eyre::Result<[u8; TDREPORT_SIZE]> {
+    #[repr(C)]
+    struct TdxReportReq {
+        report_data: [u8; 64],
+        tdreport: [u8; TDREPORT_SIZE],
+    }
+
+    let device = File::options()
+        .read(true)
+        .write(true)
+        .open(TDX_GUEST_DEVICE)
+        .map_err(|err| eyre::eyre!("cannot open {}: {}", TDX_GUEST_DEVICE, err))?;
+    let mut request = TdxReportReq {
+        report_data: *report_data,
+        tdreport: [0; TDREPORT_SIZE],
+    };
+    // SAFETY: the request matches `struct tdx_report_req` and outlives the
+    // call.
+    let ret = unsafe {
+        use std::os::fd::AsRawFd;
+        libc::ioctl(
+            device.as_raw_fd(),
+            TDX_CMD_GET_REPORT0 as _,
+            &mut request as *mut TdxReportReq,
+        )
+    };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(request.tdreport)
+}
+
+/// Exchange a `GET_QUOTE` request for `tdreport` over `stream`.
+fn get_quote(stream: &mut (impl Read + Write), tdreport: &[u8]) -> eyre::Result<Vec<u8>> {
+    let request = encode_request(tdreport)?;
+    stream.write_all(&(request.len() as u32).to_be_bytes())?;
+    stream.write_all(&request)?;
+    stream.flush()?;
+
+    let mut size = [0u8; 4];
+    stream.read_exact(&mut size)?;
+    let size = u32::from_be_bytes(size) as usize;
+    if size > MAX_RESPONSE_SIZE {
+        eyre::bail!("QGS response of {} bytes is too large", size);
+    }
+    let mut response = vec![0u8; size];
+    stream.read_exact(&mut response)?;
+    decode_response(&response)
+}
+
+fn encode_request(tdreport: &[u8]) -> eyre::Result<Vec<u8>> {
+    if tdreport.len() != TDREPORT_SIZE {
+        eyre::bail!(
+            "TDREPORT must be {} bytes, got {}",
+            TDREPORT_SIZE,
+            tdreport.len()
+        );
+    }
+    let size = QGS_MSG_HEADER_SIZE + 8 + tdreport.len();
+    let mut message = Vec::with_capacity(size);
+    message.extend_from_slice(&QGS_MSG_MAJOR_VERSION.to_le_bytes());
+    message.extend_from_slice(&QGS_MSG_MINOR_VERSION.to_le_bytes());
+    message.extend_from_slice(&GET_QUOTE_REQ.to_le_bytes());
+    message.extend_from_slice(&(size as u32).to_le_bytes());
+    message.extend_from_slice(&0u32.to_le_bytes()); // error_code
+    message.extend_from_slice(&(tdreport.len() as u32).to_le_bytes());
+    message.extend_from_slice(&0u32.to_le_bytes()); // id_list_size
+    message.extend_from_slice(tdreport);
+    Ok(message)
+}
+
+fn decode_response(message: &[u8]) -> eyre::Result<Vec<u8>> {
+    let mut reader = TssReader::new(message);
+    let major_version = read_u16_le(&mut reader)?;
+    let _minor_version = read_u16_le(&mut reader)?;
+    let message_type = read_u32_le(&mut reader)?;
+    let size = read_u32_le(&mut reader)?;
+    let error_code = read_u32_le(&mut reader)?;
+
+    if major_version != QGS_MSG_MAJOR_VERSION {
+        eyre::bail!("unsupported QGS message version {}", major_version);
+    }
+    if message_type != GET_QUOTE_RESP {
+        eyre::bail!("unexpected QGS message type {}", message_type);
+    }
+    if size as usize != message.len() {
+        eyre::bail!(
+            "QGS response size {} does not match the {} bytes received",
+            size,
+            message.len()
+        );
+    }
+    if error_code != 0 {
+        return Err(QgsError { code: error_code }.into());
+    }
+
+    let selected_id_size = read_u32_le(&mut reader)?;
+    let quote_size = read_u32_le(&mut reader)?;
+    reader.skip(selected_id_size as usize)?;
+    if reader.remaining() != quote_size as usize {
+        eyre::bail!("QGS response sizes are inconsistent");
+    }
+    let quote = reader.read_bytes(quote_size as usize)?;
+    if quote.is_empty() {
+        eyre::bail!("QGS returned an empty quote");
+    }
+    Ok(quote)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    fn response(error_code: u32, quote: &[u8]) -> Vec<u8> {
+        let size = QGS_MSG_HEADER_SIZE + 8 + quote.len();
+        let mut message = Vec::new();
+        message.extend_from_slice(&1u16.to_le_bytes());
+        message.extend_from_slice(&0u16.to_le_bytes());
+        message.extend_from_slice(&GET_QUOTE_RESP.to_le_bytes());
+        message.extend_from_slice(&(size as u32).to_le_bytes());
+        message.extend_from_slice(&error_code.to_le_bytes());
+        message.extend_from_slice(&0u32.to_le_bytes());
+        message.extend_from_slice(&(quote.len() as u32).to_le_bytes());
+        message.extend_from_slice(quote);
+        message
+    }
+
+    #[test]
+    fn test_get_quote() -> eyre::Result<()> {
+        let (mut client, mut server) = UnixStream::pair()?;
+        let host = std::thread::spawn(move || {
+            let mut size = [0u8; 4];
+            server.read_exact(&mut size).unwrap();
+            let mut request = vec![0u8; u32::from_be_bytes(size) as usize];
+            server.read_exact(&mut request).unwrap();
+
+            let reply = response(0, &[4, 0, 2, 0]);
+            server
+                .write_all(&(reply.len() as u32).to_be_bytes())
+                .unwrap();
+            server.write_all(&reply).unwrap();
+            request
+        });
+
+        assert_eq!(
+            get_quote(&mut client, &[0x5a; TDREPORT_SIZE])?,
+            [4, 0, 2, 0]
+        );
+        let request = host.join().unwrap();
+        assert_eq!(request.len(), 24 + TDREPORT_SIZE);
+        assert_eq!(
+            &request[8..12],
+            &((24 + TDREPORT_SIZE) as u32).to_le_bytes()
+        );
+        assert_eq!(&request[16..20], &(TDREPORT_SIZE as u32).to_le_bytes());
+        assert_eq!(&request[24..], &[0x5a; TDREPORT_SIZE]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_qgs_error() {
+        let err = decode_response(&response(0x12001, &[])).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<QgsError>(),
+            Some(&QgsError { code: 0x12001 })
+        );
+
+        let mut truncated = response(0, &[1, 2, 3]);
+        truncated.pop();
+        assert!(decode_response(&truncated).is_err());
+        assert!(encode_request(&[0; 64]).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use tss_serde::{TssError, TssReader};
+
+use super::{read_u16_le, read_u32_le};
+
+/// Size in bytes of an SGX enclave report body.
+pub const ENCLAVE_REPORT_BODY_SIZE: usize = 384;
+
+/// Size in bytes of a TDX 1.0 TD report body.
+pub const TD_REPORT10_BODY_SIZE: usize = 584;
+
+/// Size in bytes of a TDX 1.5 TD report body.
+pub const TD_REPORT15_BODY_SIZE: usize = 648;
+
+/// Body type descriptors used by the v5 quote container.
+pub mod body_type {
+    pub const SGX_ENCLAVE_REPORT: u16 = 1;
+    pub const TD_REPORT10: u16 = 2;
+    pub const TD_REPORT15: u16 = 3;
+}
+
+/// The ISV enclave report body (`sgx_report_body_t`) embedded in SGX quotes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct EnclaveReportBody {
+    pub cpu_svn: [u8; 16],
+    pub misc_select: u32,
+    pub isv_ext_prod_id: [u8; 16],
+    pub attributes: [u8; 16],
+    pub mr_enclave: [u8; 32],
+    pub mr_signer: [u8; 32],
+    pub config_id: [u8; 64],
+    pub isv_prod_id: u16,
+    pub isv_svn: u16,
+    pub config_svn: u16,
+    pub isv_family_id: [u8; 16],
+    pub report_data: [u8; 64],
+}
+
+impl EnclaveReportBody {
+    pub fn from_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        let cpu_svn = reader.read_array()?;
+        let misc_select = read_u32_le(reader)?;
+        reader.skip(12)?;
+        let isv_ext_prod_id = reader.read_array()?;
+        let attributes = reader.read_array()?;
+        let mr_enclave = reader.read_array()?;
+        reader.skip(32)?;
+        let mr_signer = reader.read_array()?;
+        reader.skip(32)?;
+        let config_id = reader.read_array()?;
+        let isv_prod_id = read_u16_le(reader)?;
+        let isv_svn = read_u16_le(reader)?;
+        let config_svn = read_u16_le(reader)?;
+        reader.skip(42)?;
+        let isv_family_id = reader.read_array()?;
+        let report_data = reader.read_array()?;
+
+        Ok(Self {
+            cpu_svn,
+            misc_select,
+            isv_ext_prod_id,
+            attributes,
+            mr_enclave,
+            mr_signer,
+            config_id,
+            isv_prod_id,
+            isv_svn,
+            config_svn,
+            isv_family_id,
+            report_data,
+        })
+    }
+
+    /// Encode into the `sgx_report_body_t` layout, with reserved bytes zero.
+    pub fn to_bytes(&self) -> [u8; ENCLAVE_REPORT_BODY_SIZE] {
+        let mut bytes = [0u8; ENCLAVE_REPORT_BODY_SIZE];
+        bytes[..16].copy_from_slice(&self.cpu_svn);
+        bytes[16..20].copy_from_slice(&self.misc_select.to_le_bytes());
+        bytes[32..48].copy_from_slice(&self.isv_ext_prod_id);
+        bytes[48..64].copy_from_slice(&self.attributes);
+        bytes[64..96].copy_from_slice(&self.mr_enclave);
+        bytes[128..160].copy_from_slice(&self.mr_signer);
+        bytes[192..256].copy_from_slice(&self.config_id);
+        bytes[256..258].copy_from_slice(&self.isv_prod_id.to_le_bytes());
+        bytes[258..260].copy_from_slice(&self.isv_svn.to_le_bytes());
+        bytes[260..262].copy_from_slice(&self.config_svn.to_le_bytes());
+        bytes[304..320].copy_from_slice(&self.isv_family_id);
+        bytes[320..384].copy_from_slice(&self.report_data);
+        bytes
+    }
+}
+
+/// The TD report body (TDX 1.0) embedded in TDX quotes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct TdReportBody {
+    pub tee_tcb_svn: [u8; 16],
+    pub mr_seam: [u8; 48],
+    pub mr_signer_seam: [u8; 48],
+    pub seam_attributes: [u8; 8],
+    pub td_attributes: [u8; 8],
+    pub xfam: [u8; 8],
+    pub mr_td: [u8; 48],
+    pub mr_config_id: [u8; 48],
+    pub mr_owner: [u8; 48],
+    pub mr_owner_config: [u8; 48],
+    pub rtmrs: [[u8; 48]; 4],
+    pub report_data: [u8; 64],
+}
+
+impl TdReportBody {
+    pub fn from_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        Ok(Self {
+            tee_tcb_svn: reader.read_array()?,
+            mr_seam: reader.read_array()?,
+            mr_signer_seam: reader.read_array()?,
+            seam_attributes: reader.read_array()?,
+            td_attributes: reader.read_array()?,
+            xfam: reader.read_array()?,
+            mr_td: reader.read_array()?,
+            mr_config_id: reader.read_array()?,
+            mr_owner: reader.read_array()?,
+            mr_owner_config: reader.read_array()?,
+            rtmrs: [
+                reader.read_array()?,
+                reader.read_array()?,
+                reader.read_array()?,
+                reader.read_array()?,
+            ],
+            report_data: reader.read_array()?,
+        })
+    }
+
+    /// Encode into the TDX 1.0 TD report body layout.
+    pub fn to_bytes(&self) -> [u8; TD_REPORT10_BODY_SIZE] {
+        let mut bytes = [0u8; TD_REPORT10_BODY_SIZE];
+        let fields: [&[u8]; 15] = [
+            &self.tee_tcb_svn,
+            &self.mr_seam,
+            &self.mr_signer_seam,
+            &self.seam_attributes,
+            &self.td_attributes,
+            &self.xfam,
+            &self.mr_td,
+            &self.mr_config_id,
+            &self.mr_owner,
+            &self.mr_owner_config,
+            &self.rtmrs[0],
+            &self.rtmrs[1],
+            &self.rtmrs[2],
+            &self.rtmrs[3],
+            &self.report_data,
+        ];
+        let mut offset = 0;
+        for field in fields {
+            bytes[offset..offset + field.len()].copy_from_slice(field);
+            offset += field.len();
+        }
+        bytes
+    }
+}
+
+/// The TD report body (TDX 1.5), which extends the 1.0 layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct TdReportBody15 {
+    pub base: TdReportBody,
+    pub tee_tcb_svn2: [u8; 16],
+    pub mr_service_td: [u8; 48],
+}
+
+impl TdReportBody15 {
+    pub fn from_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        Ok(Self {
+            base: TdReportBody::from_reader(reader)?,
+            tee_tcb_svn2: reader.read_array()?,
+            mr_service_td: reader.read_array()?,
+        })
+    }
+
+    /// Encode into the TDX 1.5 TD report body layout.
+    pub fn to_bytes(&self) -> [u8; TD_REPORT15_BODY_SIZE] {
+        let mut bytes = [0u8; TD_REPORT15_BODY_SIZE];
+        bytes[..TD_REPORT10_BODY_SIZE].copy_from_slice(&self.base.to_bytes());
+        bytes[TD_REPORT10_BODY_SIZE..TD_REPORT10_BODY_SIZE + 16]
+            .copy_from_slice(&self.tee_tcb_svn2);
+        bytes[TD_REPORT10_BODY_SIZE + 16..].copy_from_slice(&self.mr_service_td);
+        bytes
+    }
+}
+
+/// The report carried by a quote, selected by quote version, TEE type or
+/// (for v5 quotes) the body type descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum QuoteBody {
+    SgxEnclave(EnclaveReportBody),
+    Td10(TdReportBody),
+    Td15(TdReportBody15),
+}
+
+impl QuoteBody {
+    /// Decode a raw body of the given v5 body type.
+    pub fn from_bytes(body_type: u16, bytes: &[u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+        let body = match body_type {
+            body_type::SGX_ENCLAVE_REPORT => {
+                QuoteBody::SgxEnclave(EnclaveReportBody::from_reader(&mut reader)?)
+            }
+            body_type::TD_REPORT10 => QuoteBody::Td10(TdReportBody::from_reader(&mut reader)?),
+            body_type::TD_REPORT15 => QuoteBody::Td15(TdReportBody15::from_reader(&mut reader)?),
+            _ => eyre::bail!("unsupported quote body type {}", body_type),
+        };
+        if reader.remaining() != 0 {
+            eyre::bail!("trailing bytes after quote body type {}", body_type);
+        }
+        Ok(body)
+    }
+
+    /// Encode into the layout [`QuoteBody::from_bytes`] decodes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            QuoteBody::SgxEnclave(report) => report.to_bytes().to_vec(),
+            QuoteBody::Td10(report) => report.to_bytes().to_vec(),
+            QuoteBody::Td15(report) => report.to_bytes().to_vec(),
+        }
+    }
+
+    /// The v5 body type descriptor for this body.
+    pub fn body_type(&self) -> u16 {
+        match self {
+            QuoteBody::SgxEnclave(_) => body_type::SGX_ENCLAVE_REPORT,
+            QuoteBody::Td10(_) => body_type::TD_REPORT10,
+            QuoteBody::Td15(_) => body_type::TD_REPORT15,
+        }
+    }
+
+    pub fn as_enclave_report(&self) -> Option<&EnclaveReportBody> {
+        match self {
+            QuoteBody::SgxEnclave(report) => Some(report),
+            _ => None,
+        }
+    }
+
+    /// The TDX 1.0 fields of the body, for both TD report versions.
+    pub fn as_td_report(&self) -> Option<&TdReportBody> {
+        match self {
+            QuoteBody::Td10(report) => Some(report),
+            QuoteBody::Td15(report) => Some(&report.base),
+            _ => None,
+        }
+    }
+
+    pub fn report_data(&self) -> &[u8; 64] {
+        match self {
+            QuoteBody::SgxEnclave(report) => &report.report_data,
+            QuoteBody::Td10(report) => &report.report_data,
+            QuoteBody::Td15(report) => &report.base.report_data,
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use tss_serde::{TssError, TssReader};
+
+mod body;
+pub use body::*;
+
+mod signature;
+pub use signature::*;
+
+mod verify;
+
+mod view;
+pub use view::*;
+
+/// Quote format version produced by the SGX ECDSA quoting enclave.
+pub const QUOTE_VERSION_3: u16 = 3;
+
+/// Quote format version produced by the TDX quoting enclave.
+pub const QUOTE_VERSION_4: u16 = 4;
+
+/// Quote format version wrapping the body in a type/size descriptor.
+pub const QUOTE_VERSION_5: u16 = 5;
+
+pub mod attestation_key_type {
+    pub const ECDSA_P256: u16 = 2;
+    pub const ECDSA_P384: u16 = 3;
+}
+
+pub mod tee_type {
+    pub const SGX: u32 = 0x00000000;
+    pub const TDX: u32 = 0x00000081;
+
+    /// `sgx`, `tdx` or `unknown`.
+    pub fn name(tee_type: u32) -> &'static str {
+        match tee_type {
+            SGX => "sgx",
+            TDX => "tdx",
+            _ => "unknown",
+        }
+    }
+}
+
+/// Size in bytes of the quote header.
+pub const QUOTE_HEADER_SIZE: usize = 48;
+
+/// The fixed 48-byte header at the start of every DCAP quote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct QuoteHeader {
+    pub version: u16,
+    pub attestation_key_type: u16,
+    /// TEE type for v4+ quotes; reserved (zero) in SGX v3 quotes.
+    pub tee_type: u32,
+    pub qe_svn: u16,
+    pub pce_svn: u16,
+    pub qe_vendor_id: [u8; 16],
+    pub user_data: [u8; 20],
+}
+
+impl QuoteHeader {
+    pub fn from_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        Ok(Self {
+            version: read_u16_le(reader)?,
+            attestation_key_type: read_u16_le(reader)?,
+            tee_type: read_u32_le(reader)?,
+            qe_svn: read_u16_le(reader)?,
+            pce_svn: read_u16_le(reader)?,
+            qe_vendor_id: reader.read_array()?,
+            user_data: reader.read_array()?,
+        })
+    }
+
+    /// Encode into the 48-byte header layout.
+    pub fn to_bytes(&self) -> [u8; QUOTE_HEADER_SIZE] {
+        let mut bytes = [0u8; QUOTE_HEADER_SIZE];
+        bytes[..2].copy_from_slice(&self.version.to_le_bytes());
+        bytes[2..4].copy_from_slice(&self.attestation_key_type.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.tee_type.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.qe_svn.to_le_bytes());
+        bytes[10..12].copy_from_slice(&self.pce_svn.to_le_bytes());
+        bytes[12..28].copy_from_slice(&self.qe_vendor_id);
+        bytes[28..].copy_from_slice(&self.user_data);
+        bytes
+    }
+}
+
+/// A DCAP ECDSA quote.
+///
+/// Versions 3 (SGX), 4 (TDX) and 5 (SGX or TDX behind a body descriptor) are
+/// all parsed into this one type; the report they carry is exposed through
+/// [`QuoteBody`]. Only ECDSA-256 attestation keys are supported.
+#[derive(Debug, Clone)]
+pub struct Quote {
+    pub header: QuoteHeader,
+    pub body: QuoteBody,
+    pub signature: QuoteSignatureData,
+    /// Raw header and body bytes covered by the quote signature.
+    pub signed_data: Vec<u8>,
+}
+
+impl Quote {
+    pub fn parse(bytes: &[u8]) -> eyre::Result<Self> {
+        QuoteRef::parse(bytes)?.to_quote()
+    }
+}
+
+pub(crate) fn read_u16_le(reader: &mut TssReader) -> Result<u16, TssError> {
+    Ok(u16::from_le_bytes(reader.read_array()?))
+}
+
+pub(crate) fn read_u32_le(reader: &mut TssReader) -> Result<u32, TssError> {
+    Ok(u32::from_le_bytes(reader.read_array()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_header(version: u16, tee: u32) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&version.to_le_bytes());
+        header.extend_from_slice(&attestation_key_type::ECDSA_P256.to_le_bytes());
+        header.extend_from_slice(&tee.to_le_bytes());
+        header.extend_from_slice(&8u16.to_le_bytes());
+        header.extend_from_slice(&13u16.to_le_bytes());
+        header.extend_from_slice(&[0x93; 16]);
+        header.extend_from_slice(&[0u8; 20]);
+        header
+    }
+
+    fn sample_signature_data(version: u16) -> Vec<u8> {
+        let mut qe_certification = vec![0u8; ENCLAVE_REPORT_BODY_SIZE];
+        qe_certification.extend_from_slice(&[0x55; 64]);
+        qe_certification.extend_from_slice(&0u16.to_le_bytes());
+        qe_certification.extend_from_slice(&cert_data_type::PCK_CERT_CHAIN.to_le_bytes());
+        qe_certification.extend_from_slice(&5u32.to_le_bytes());
+        qe_certification.extend_from_slice(b"chain");
+
+        let mut signature = vec![0x01; 64];
+        signature.extend_from_slice(&[0x02; 64]);
+        if version == QUOTE_VERSION_3 {
+            signature.extend_from_slice(&qe_certification);
+        } else {
+            signature.extend_from_slice(&cert_data_type::QE_REPORT_CERT.to_le_bytes());
+            signature.extend_from_slice(&(qe_certification.len() as u32).to_le_bytes());
+            signature.extend_from_slice(&qe_certification);
+        }
+
+        let mut bytes = (signature.len() as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(&signature);
+        bytes
+    }
+
+    fn sample_sgx_quote() -> Vec<u8> {
+        let mut quote = sample_header(QUOTE_VERSION_3, tee_type::SGX);
+
+        let mut body = [0u8; ENCLAVE_REPORT_BODY_SIZE];
+        body[16..20].copy_from_slice(&1u32.to_le_bytes());
+        body[64..96].copy_from_slice(&[0xAA; 32]);
+        body[128..160].copy_from_slice(&[0xBB; 32]);
+        body[256..258].copy_from_slice(&7u16.to_le_bytes());
+        body[258..260].copy_from_slice(&2u16.to_le_bytes());
+        body[320..384].copy_from_slice(&[0xCC; 64]);
+        quote.extend_from_slice(&body);
+
+        quote.extend_from_slice(&sample_signature_data(QUOTE_VERSION_3));
+        quote
+    }
+
+    fn sample_td_body(size: usize) -> Vec<u8> {
+        let mut body = vec![0u8; size];
+        body[136..184].copy_from_slice(&[0x11; 48]); // mr_td
+        body[328..376].copy_from_slice(&[0x22; 48]); // rtmr0
+        body[520..584].copy_from_slice(&[0xCC; 64]); // report_data
+        body
+    }
+
+    #[test]
+    fn test_parse_sgx_quote_v3() -> eyre::Result<()> {
+        let quote = Quote::parse(&sample_sgx_quote())?;
+
+        assert_eq!(quote.header.version, 3);
+        assert_eq!(quote.header.qe_svn, 8);
+        assert_eq!(quote.header.pce_svn, 13);
+
+        let report = quote.body.as_enclave_report().unwrap();
+        assert_eq!(report.misc_select, 1);
+        assert_eq!(report.mr_enclave, [0xAA; 32]);
+        assert_eq!(report.mr_signer, [0xBB; 32]);
+        assert_eq!(report.isv_prod_id, 7);
+        assert_eq!(report.isv_svn, 2);
+        assert_eq!(report.report_data, [0xCC; 64]);
+        assert_eq!(quote.signature.attestation_key, [0x02; 64]);
+        assert_eq!(
+            quote.signature.qe_report_certification.certification_data,
+            CertificationData::PckCertChain(b"chain".to_vec())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_truncated_quote() {
+        let quote = sample_sgx_quote();
+        assert!(Quote::parse(&quote[..quote.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_parse_tdx_quote_v4() -> eyre::Result<()> {
+        let mut bytes = sample_header(QUOTE_VERSION_4, tee_type::TDX);
+        bytes.extend_from_slice(&sample_td_body(TD_REPORT10_BODY_SIZE));
+        bytes.extend_from_slice(&sample_signature_data(QUOTE_VERSION_4));
+
+        let quote = Quote::parse(&bytes)?;
+        let report = quote.body.as_td_report().unwrap();
+        assert_eq!(report.mr_td, [0x11; 48]);
+        assert_eq!(report.rtmrs[0], [0x22; 48]);
+        assert_eq!(quote.body.report_data(), &[0xCC; 64]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_tdx_quote_v5() -> eyre::Result<()> {
+        let mut bytes = sample_header(QUOTE_VERSION_5, tee_type::TDX);
+        bytes.extend_from_slice(&body_type::TD_REPORT15.to_le_bytes());
+        bytes.extend_from_slice(&(TD_REPORT15_BODY_SIZE as u32).to_le_bytes());
+        let mut body = sample_td_body(TD_REPORT15_BODY_SIZE);
+        body[600..648].copy_from_slice(&[0x33; 48]); // mr_service_td
+        bytes.extend_from_slice(&body);
+        bytes.extend_from_slice(&sample_signature_data(QUOTE_VERSION_5));
+
+        let quote = Quote::parse(&bytes)?;
+        assert_eq!(quote.body.body_type(), body_type::TD_REPORT15);
+        assert_eq!(quote.body.as_td_report().unwrap().mr_td, [0x11; 48]);
+        match &quote.body {
+            QuoteBody::Td15(report) => assert_eq!(report.mr_service_td, [0x33; 48]),
+            other => panic!("unexpected body {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_ref_borrows_input() -> eyre::Result<()> {
+        let mut bytes = sample_header(QUOTE_VERSION_5, tee_type::TDX);
+        bytes.extend_from_slice(&body_type::TD_REPORT10.to_le_bytes());
+        bytes.extend_from_slice(&(TD_REPORT10_BODY_SIZE as u32).to_le_bytes());
+        bytes.extend_from_slice(&sample_td_body(TD_REPORT10_BODY_SIZE));
+        bytes.extend_from_slice(&sample_signature_data(QUOTE_VERSION_5));
+
+        let view = QuoteRef::parse(&bytes)?;
+        assert_eq!(view.version(), QUOTE_VERSION_5);
+        assert_eq!(view.body_type(), body_type::TD_REPORT10);
+        assert_eq!(view.header().as_ptr(), bytes.as_ptr());
+        assert_eq!(
+            view.body(),
+            &bytes[QUOTE_HEADER_SIZE + 6..][..TD_REPORT10_BODY_SIZE]
+        );
+        assert_eq!(
+            view.signed_data().len(),
+            QUOTE_HEADER_SIZE + 6 + TD_REPORT10_BODY_SIZE
+        );
+        assert_eq!(view.report_data(), &[0xCC; 64]);
+
+        let quote = view.to_quote()?;
+        assert_eq!(quote.body, view.parse_body()?);
+        assert_eq!(quote.signed_data, view.signed_data());
+        assert_eq!(quote.signature, view.parse_signature()?);
+
+        let sgx = sample_sgx_quote();
+        assert_eq!(QuoteRef::parse(&sgx)?.report_data(), &[0xCC; 64]);
+        assert!(QuoteRef::parse(&sgx[..sgx.len() - 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_v5_rejects_size_mismatch() {
+        let mut bytes = sample_header(QUOTE_VERSION_5, tee_type::TDX);
+        bytes.extend_from_slice(&body_type::TD_REPORT10.to_le_bytes());
+        bytes.extend_from_slice(&(TD_REPORT15_BODY_SIZE as u32).to_le_bytes());
+        bytes.extend_from_slice(&sample_td_body(TD_REPORT15_BODY_SIZE));
+        bytes.extend_from_slice(&sample_signature_data(QUOTE_VERSION_5));
+
+        assert!(Quote::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_encode_roundtrip() -> eyre::Result<()> {
+        let mut bytes = sample_header(QUOTE_VERSION_5, tee_type::TDX);
+        bytes.extend_from_slice(&body_type::TD_REPORT15.to_le_bytes());
+        bytes.extend_from_slice(&(TD_REPORT15_BODY_SIZE as u32).to_le_bytes());
+        let mut body = sample_td_body(TD_REPORT15_BODY_SIZE);
+        body[584..600].copy_from_slice(&[0x44; 16]); // tee_tcb_svn2
+        bytes.extend_from_slice(&body);
+        bytes.extend_from_slice(&sample_signature_data(QUOTE_VERSION_5));
+
+        let quote = Quote::parse(&bytes)?;
+        assert_eq!(quote.header.to_bytes(), bytes[..QUOTE_HEADER_SIZE]);
+        assert_eq!(quote.body.to_bytes(), body);
+        let td10 = QuoteBody::Td10(quote.body.as_td_report().unwrap().clone());
+        assert_eq!(td10.to_bytes(), body[..TD_REPORT10_BODY_SIZE]);
+
+        let quote = Quote::parse(&sample_sgx_quote())?;
+        assert_eq!(
+            QuoteBody::from_bytes(quote.body.body_type(), &quote.body.to_bytes())?,
+            quote.body
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,295 @@
+use tss_serde::TssReader;
+
+use super::{
+    read_u16_le, read_u32_le, EnclaveReportBody, ENCLAVE_REPORT_BODY_SIZE, QUOTE_VERSION_3,
+};
+
+/// Certification data types (`sgx_ql_cert_key_type_t`).
+pub mod cert_data_type {
+    pub const PCK_ID_PLAIN_PPID: u16 = 1;
+    pub const PCK_ID_ENCRYPTED_PPID_2048: u16 = 2;
+    pub const PCK_ID_ENCRYPTED_PPID_3072: u16 = 3;
+    pub const PCK_LEAF_CERT: u16 = 4;
+    pub const PCK_CERT_CHAIN: u16 = 5;
+    pub const QE_REPORT_CERT: u16 = 6;
+    pub const PLATFORM_MANIFEST: u16 = 7;
+}
+
+/// The ECDSA-256 quote signature section, normalized across quote versions.
+///
+/// In v3 quotes the QE report follows the attestation key directly; in v4 and
+/// v5 quotes it is wrapped in a type 6 certification data entry. Both layouts
+/// are exposed through [`QeReportCertificationData`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuoteSignatureData {
+    /// Raw `r || s` signature over the quote header and body.
+    pub quote_signature: [u8; 64],
+    /// Raw `x || y` attestation public key.
+    pub attestation_key: [u8; 64],
+    pub qe_report_certification: QeReportCertificationData,
+}
+
+impl QuoteSignatureData {
+    pub fn parse(version: u16, bytes: &[u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+
+        let quote_signature = reader.read_array()?;
+        let attestation_key = reader.read_array()?;
+
+        let qe_report_certification = if version == QUOTE_VERSION_3 {
+            QeReportCertificationData::from_reader(&mut reader)?
+        } else {
+            match CertificationData::from_reader(&mut reader)? {
+                CertificationData::QeReportCertification(data) => *data,
+                other => eyre::bail!(
+                    "expected QE report certification data, found type {}",
+                    other.cert_type()
+                ),
+            }
+        };
+
+        Ok(Self {
+            quote_signature,
+            attestation_key,
+            qe_report_certification,
+        })
+    }
+}
+
+/// The QE report, its signature by the PCK key, the QE authentication data
+/// and the certification data identifying the PCK key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QeReportCertificationData {
+    pub qe_report: EnclaveReportBody,
+    /// Raw bytes of the QE report, as signed by the PCK key.
+    pub qe_report_raw: Vec<u8>,
+    /// Raw `r || s` signature over the QE report.
+    pub qe_report_signature: [u8; 64],
+    pub qe_auth_data: Vec<u8>,
+    pub certification_data: CertificationData,
+}
+
+impl QeReportCertificationData {
+    pub fn from_reader(reader: &mut TssReader) -> eyre::Result<Self> {
+        let qe_report_raw = reader.read_bytes(ENCLAVE_REPORT_BODY_SIZE)?;
+        let qe_report = EnclaveReportBody::from_reader(&mut TssReader::new(&qe_report_raw))?;
+
+        let qe_report_signature = reader.read_array()?;
+        let qe_auth_data_len = read_u16_le(reader)?;
+        let qe_auth_data = reader.read_bytes(qe_auth_data_len as usize)?;
+        // The QE report is certified by the PCK key, never by another QE
+        // report, which also bounds the recursion on adversarial quotes.
+        let certification_data = CertificationData::read(reader, false)?;
+
+        Ok(Self {
+            qe_report,
+            qe_report_raw,
+            qe_report_signature,
+            qe_auth_data,
+            certification_data,
+        })
+    }
+}
+
+/// Platform identity carried by certification data types 1 to 3, used to
+/// look up the PCK certificate from the provisioning service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PckIdentity {
+    /// Plain PPID (16 bytes) or PPID encrypted with RSA-2048/3072.
+    pub ppid: Vec<u8>,
+    pub cpu_svn: [u8; 16],
+    pub pce_svn: u16,
+    pub pce_id: u16,
+}
+
+impl PckIdentity {
+    fn parse(ppid_len: usize, bytes: &[u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+        Ok(Self {
+            ppid: reader.read_bytes(ppid_len)?,
+            cpu_svn: reader.read_array()?,
+            pce_svn: read_u16_le(&mut reader)?,
+            pce_id: read_u16_le(&mut reader)?,
+        })
+    }
+}
+
+/// Certification data identifying the key that signed the QE report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CertificationData {
+    PckIdPlainPpid(PckIdentity),
+    PckIdEncryptedPpid2048(PckIdentity),
+    PckIdEncryptedPpid3072(PckIdentity),
+    /// DER or PEM encoded PCK leaf certificate.
+    PckLeafCert(Vec<u8>),
+    /// Concatenated PEM chain: PCK leaf, intermediate CA, root CA.
+    PckCertChain(Vec<u8>),
+    QeReportCertification(Box<QeReportCertificationData>),
+    PlatformManifest(Vec<u8>),
+    Unknown {
+        cert_type: u16,
+        data: Vec<u8>,
+    },
+}
+
+impl CertificationData {
+    pub fn from_reader(reader: &mut TssReader) -> eyre::Result<Self> {
+        Self::read(reader, true)
+    }
+
+    fn read(reader: &mut TssReader, allow_qe_report: bool) -> eyre::Result<Self> {
+        let cert_type = read_u16_le(reader)?;
+        let size = read_u32_le(reader)?;
+        let data = reader.read_bytes(size as usize)?;
+
+        let certification_data = match cert_type {
+            cert_data_type::PCK_ID_PLAIN_PPID => {
+                CertificationData::PckIdPlainPpid(PckIdentity::parse(16, &data)?)
+            }
+            cert_data_type::PCK_ID_ENCRYPTED_PPID_2048 => {
+                CertificationData::PckIdEncryptedPpid2048(PckIdentity::parse(256, &data)?)
+            }
+            cert_data_type::PCK_ID_ENCRYPTED_PPID_3072 => {
+                CertificationData::PckIdEncryptedPpid3072(PckIdentity::parse(384, &data)?)
+            }
+            cert_data_type::PCK_LEAF_CERT => CertificationData::PckLeafCert(data),
+            cert_data_type::PCK_CERT_CHAIN => CertificationData::PckCertChain(data),
+            cert_data_type::QE_REPORT_CERT if !allow_qe_report => {
+                eyre::bail!("nested QE report certification data")
+            }
+            cert_data_type::QE_REPORT_CERT => {
+                let mut inner = TssReader::new(&data);
+                CertificationData::QeReportCertification(Box::new(
+                    QeReportCertificationData::from_reader(&mut inner)?,
+                ))
+            }
+            cert_data_type::PLATFORM_MANIFEST => CertificationData::PlatformManifest(data),
+            _ => CertificationData::Unknown { cert_type, data },
+        };
+        Ok(certification_data)
+    }
+
+    pub fn cert_type(&self) -> u16 {
+        match self {
+            CertificationData::PckIdPlainPpid(_) => cert_data_type::PCK_ID_PLAIN_PPID,
+            CertificationData::PckIdEncryptedPpid2048(_) => {
+                cert_data_type::PCK_ID_ENCRYPTED_PPID_2048
+            }
+            CertificationData::PckIdEncryptedPpid3072(_) => {
+                cert_data_type::PCK_ID_ENCRYPTED_PPID_3072
+            }
+            CertificationData::PckLeafCert(_) => cert_data_type::PCK_LEAF_CERT,
+            CertificationData::PckCertChain(_) => cert_data_type::PCK_CERT_CHAIN,
+            CertificationData::QeReportCertification(_) => cert_data_type::QE_REPORT_CERT,
+            CertificationData::PlatformManifest(_) => cert_data_type::PLATFORM_MANIFEST,
+            CertificationData::Unknown { cert_type, .. } => *cert_type,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::QUOTE_VERSION_4;
+
+    fn cert_data(cert_type: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&cert_type.to_le_bytes());
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn qe_report_certification(inner: &[u8]) -> Vec<u8> {
+        let mut qe_report = [0u8; ENCLAVE_REPORT_BODY_SIZE];
+        qe_report[256..258].copy_from_slice(&1u16.to_le_bytes());
+
+        let mut bytes = qe_report.to_vec();
+        bytes.extend_from_slice(&[0x55; 64]);
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&[0xA0, 0xA1]);
+        bytes.extend_from_slice(inner);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_v3_signature_data() -> eyre::Result<()> {
+        let mut bytes = vec![0x01; 64];
+        bytes.extend_from_slice(&[0x02; 64]);
+        bytes.extend_from_slice(&qe_report_certification(&cert_data(
+            cert_data_type::PCK_CERT_CHAIN,
+            b"chain",
+        )));
+
+        let signature = QuoteSignatureData::parse(QUOTE_VERSION_3, &bytes)?;
+        assert_eq!(signature.quote_signature, [0x01; 64]);
+        assert_eq!(signature.attestation_key, [0x02; 64]);
+
+        let qe = &signature.qe_report_certification;
+        assert_eq!(qe.qe_report.isv_prod_id, 1);
+        assert_eq!(qe.qe_report_raw.len(), ENCLAVE_REPORT_BODY_SIZE);
+        assert_eq!(qe.qe_report_signature, [0x55; 64]);
+        assert_eq!(qe.qe_auth_data, vec![0xA0, 0xA1]);
+        assert_eq!(
+            qe.certification_data,
+            CertificationData::PckCertChain(b"chain".to_vec())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_v4_signature_data() -> eyre::Result<()> {
+        let mut ppid = vec![0x77; 16];
+        ppid.extend_from_slice(&[0x03; 16]);
+        ppid.extend_from_slice(&13u16.to_le_bytes());
+        ppid.extend_from_slice(&0u16.to_le_bytes());
+        let inner = cert_data(cert_data_type::PCK_ID_PLAIN_PPID, &ppid);
+
+        let mut bytes = vec![0x01; 64];
+        bytes.extend_from_slice(&[0x02; 64]);
+        bytes.extend_from_slice(&cert_data(
+            cert_data_type::QE_REPORT_CERT,
+            &qe_report_certification(&inner),
+        ));
+
+        let signature = QuoteSignatureData::parse(QUOTE_VERSION_4, &bytes)?;
+        match &signature.qe_report_certification.certification_data {
+            CertificationData::PckIdPlainPpid(identity) => {
+                assert_eq!(identity.ppid, vec![0x77; 16]);
+                assert_eq!(identity.cpu_svn, [0x03; 16]);
+                assert_eq!(identity.pce_svn, 13);
+            }
+            other => panic!("unexpected certification data {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_v4_requires_qe_report_cert() {
+        let mut bytes = vec![0x01; 64];
+        bytes.extend_from_slice(&[0x02; 64]);
+        bytes.extend_from_slice(&cert_data(cert_data_type::PCK_CERT_CHAIN, b"chain"));
+
+        assert!(QuoteSignatureData::parse(QUOTE_VERSION_4, &bytes).is_err());
+    }
+
+    #[test]
+    fn test_rejects_nested_qe_report_cert() {
+        let nested = cert_data(
+            cert_data_type::QE_REPORT_CERT,
+            &qe_report_certification(&cert_data(cert_data_type::PCK_CERT_CHAIN, b"chain")),
+        );
+        let mut bytes = vec![0x01; 64];
+        bytes.extend_from_slice(&[0x02; 64]);
+        bytes.extend_from_slice(&cert_data(
+            cert_data_type::QE_REPORT_CERT,
+            &qe_report_certification(&nested),
+        ));
+        assert!(QuoteSignatureData::parse(QUOTE_VERSION_4, &bytes).is_err());
+
+        let mut bytes = vec![0x01; 64];
+        bytes.extend_from_slice(&[0x02; 64]);
+        bytes.extend_from_slice(&qe_report_certification(&nested));
+        assert!(QuoteSignatureData::parse(QUOTE_VERSION_3, &bytes).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::crypto::{
+    sha256, verify_raw_signature, verifying_key_from_certificate, verifying_key_from_raw,
+};
+use crate::{PckChain, Quote};
+
+impl Quote {
+    /// Verify the ECDSA signature chain of the quote:
+    ///
+    /// 1. the QE report binds the attestation key, i.e. its report data starts
+    ///    with `SHA256(attestation_key || qe_auth_data)`;
+    /// 2. the QE report is signed by the PCK key of `pck_chain`;
+    /// 3. the quote header and body are signed by the attestation key.
+    ///
+    /// This does not validate the PCK chain itself.
+    pub fn verify_signature(&self, pck_chain: &PckChain) -> eyre::Result<()> {
+        let signature = &self.signature;
+        let qe = &signature.qe_report_certification;
+
+        let expected = sha256(&[&signature.attestation_key, &qe.qe_auth_data]);
+        let report_data = &qe.qe_report.report_data;
+        if report_data[..32] != expected || report_data[32..].iter().any(|b| *b != 0) {
+            eyre::bail!("QE report data does not bind the attestation key");
+        }
+
+        let pck_key = verifying_key_from_certificate(pck_chain.pck())?;
+        verify_raw_signature(&pck_key, &qe.qe_report_raw, &qe.qe_report_signature)
+            .map_err(|err| err.wrap_err("QE report signature is invalid"))?;
+
+        let attestation_key = verifying_key_from_raw(&signature.attestation_key)?;
+        verify_raw_signature(
+            &attestation_key,
+            &self.signed_data,
+            &signature.quote_signature,
+        )
+        .map_err(|err| err.wrap_err("quote signature is invalid"))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{sgx_quote, tdx_quote, TestPki};
+
+    #[test]
+    fn test_verify_sgx_quote_signature() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let quote = Quote::parse(&sgx_quote(&pki))?;
+        let chain = PckChain::from_quote(&quote)?;
+        quote.verify_signature(&chain)
+    }
+
+    #[test]
+    fn test_verify_tdx_quote_signature() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let quote = Quote::parse(&tdx_quote(&pki))?;
+        let chain = PckChain::from_quote(&quote)?;
+        quote.verify_signature(&chain)
+    }
+
+    #[test]
+    fn test_tampered_body_fails() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let mut bytes = sgx_quote(&pki);
+        // Flip a bit of mr_enclave.
+        bytes[48 + 64] ^= 1;
+
+        let quote = Quote::parse(&bytes)?;
+        let chain = PckChain::from_quote(&quote)?;
+        assert!(quote.verify_signature(&chain).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_pck_fails() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let quote = Quote::parse(&sgx_quote(&pki))?;
+
+        let other = TestPki::with_pck_key_seed(9);
+        let chain = PckChain::from_pem(other.pck_chain_pem().as_bytes())?;
+        assert!(quote.verify_signature(&chain).is_err());
+        Ok(())
+    }
+}
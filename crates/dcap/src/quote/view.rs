@@ -0,0 +1,183 @@
+use tss_serde::TssReader;
+
+use super::{
+    attestation_key_type, body_type, read_u16_le, read_u32_le, tee_type, Quote, QuoteBody,
+    QuoteHeader, QuoteSignatureData, ENCLAVE_REPORT_BODY_SIZE, QUOTE_HEADER_SIZE, QUOTE_VERSION_3,
+    QUOTE_VERSION_4, QUOTE_VERSION_5, TD_REPORT10_BODY_SIZE, TD_REPORT15_BODY_SIZE,
+};
+
+/// Offset of the report data in the SGX enclave report body.
+const ENCLAVE_REPORT_DATA_OFFSET: usize = 320;
+
+/// Offset of the report data in both TD report bodies.
+const TD_REPORT_DATA_OFFSET: usize = 520;
+
+/// A borrowed view of a DCAP ECDSA quote.
+///
+/// Parsing checks the same structure as [`Quote::parse`] but only decodes the
+/// header; the body and signature data are slices of the input. Use
+/// [`QuoteRef::to_quote`] for the fully decoded quote.
+#[derive(Debug, Clone, Copy)]
+pub struct QuoteRef<'a> {
+    header: &'a [u8],
+    version: u16,
+    tee_type: u32,
+    body_type: u16,
+    body: &'a [u8],
+    signed_data: &'a [u8],
+    signature_data: &'a [u8],
+}
+
+impl<'a> QuoteRef<'a> {
+    pub fn parse(bytes: &'a [u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+
+        let version = read_u16_le(&mut reader)?;
+        let key_type = read_u16_le(&mut reader)?;
+        let tee = read_u32_le(&mut reader)?;
+        if tee != tee_type::SGX && tee != tee_type::TDX {
+            eyre::bail!("unsupported TEE type {:#x}", tee);
+        }
+        if key_type != attestation_key_type::ECDSA_P256 {
+            eyre::bail!("unsupported attestation key type {}", key_type);
+        }
+        reader.skip(QUOTE_HEADER_SIZE - reader.position())?;
+
+        let body_type = match version {
+            QUOTE_VERSION_3 => {
+                if tee != tee_type::SGX {
+                    eyre::bail!("v3 quotes must carry an SGX report");
+                }
+                body_type::SGX_ENCLAVE_REPORT
+            }
+            QUOTE_VERSION_4 => match tee {
+                tee_type::TDX => body_type::TD_REPORT10,
+                _ => body_type::SGX_ENCLAVE_REPORT,
+            },
+            QUOTE_VERSION_5 => parse_v5_descriptor(tee, &mut reader)?,
+            version => eyre::bail!("unsupported quote version {}", version),
+        };
+
+        let body_start = reader.position();
+        reader.skip(body_size(body_type))?;
+        let body = &bytes[body_start..reader.position()];
+        let signed_data = &bytes[..reader.position()];
+
+        let signature_data_len = read_u32_le(&mut reader)? as usize;
+        let signature_start = reader.position();
+        reader.skip(signature_data_len)?;
+        let signature_data = &bytes[signature_start..reader.position()];
+
+        Ok(Self {
+            header: &bytes[..QUOTE_HEADER_SIZE],
+            version,
+            tee_type: tee,
+            body_type,
+            body,
+            signed_data,
+            signature_data,
+        })
+    }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    pub fn tee_type(&self) -> u32 {
+        self.tee_type
+    }
+
+    /// The raw 48-byte header.
+    pub fn header(&self) -> &'a [u8] {
+        self.header
+    }
+
+    /// The v5 body type descriptor of the body, also for v3 and v4 quotes.
+    pub fn body_type(&self) -> u16 {
+        self.body_type
+    }
+
+    /// The raw report body, without the v5 descriptor.
+    pub fn body(&self) -> &'a [u8] {
+        self.body
+    }
+
+    /// Raw header and body bytes covered by the quote signature.
+    pub fn signed_data(&self) -> &'a [u8] {
+        self.signed_data
+    }
+
+    /// The raw signature data following the body.
+    pub fn signature_data(&self) -> &'a [u8] {
+        self.signature_data
+    }
+
+    pub fn report_data(&self) -> &'a [u8; 64] {
+        let offset = match self.body_type {
+            body_type::SGX_ENCLAVE_REPORT => ENCLAVE_REPORT_DATA_OFFSET,
+            _ => TD_REPORT_DATA_OFFSET,
+        };
+        self.body[offset..offset + 64]
+            .try_into()
+            .expect("body size is checked by parse")
+    }
+
+    pub fn parse_header(&self) -> eyre::Result<QuoteHeader> {
+        Ok(QuoteHeader::from_reader(&mut TssReader::new(self.header))?)
+    }
+
+    pub fn parse_body(&self) -> eyre::Result<QuoteBody> {
+        QuoteBody::from_bytes(self.body_type, self.body)
+    }
+
+    pub fn parse_signature(&self) -> eyre::Result<QuoteSignatureData> {
+        QuoteSignatureData::parse(self.version, self.signature_data)
+    }
+
+    /// Decode the whole quote.
+    pub fn to_quote(&self) -> eyre::Result<Quote> {
+        Ok(Quote {
+            header: self.parse_header()?,
+            body: self.parse_body()?,
+            signature: self.parse_signature()?,
+            signed_data: self.signed_data.to_vec(),
+        })
+    }
+}
+
+fn parse_v5_descriptor(tee: u32, reader: &mut TssReader) -> eyre::Result<u16> {
+    let body_type = read_u16_le(reader)?;
+    let size = read_u32_le(reader)? as usize;
+    if !matches!(
+        body_type,
+        body_type::SGX_ENCLAVE_REPORT | body_type::TD_REPORT10 | body_type::TD_REPORT15
+    ) {
+        eyre::bail!("unsupported quote body type {}", body_type);
+    }
+    let expected_size = body_size(body_type);
+    if size != expected_size {
+        eyre::bail!(
+            "quote body type {} has size {}, expected {}",
+            body_type,
+            size,
+            expected_size
+        );
+    }
+    let is_sgx_body = body_type == body_type::SGX_ENCLAVE_REPORT;
+    if is_sgx_body != (tee == tee_type::SGX) {
+        eyre::bail!(
+            "quote body type {} does not match TEE type {:#x}",
+            body_type,
+            tee
+        );
+    }
+    Ok(body_type)
+}
+
+fn body_size(body_type: u16) -> usize {
+    match body_type {
+        body_type::SGX_ENCLAVE_REPORT => ENCLAVE_REPORT_BODY_SIZE,
+        body_type::TD_REPORT10 => TD_REPORT10_BODY_SIZE,
+        _ => TD_REPORT15_BODY_SIZE,
+    }
+}
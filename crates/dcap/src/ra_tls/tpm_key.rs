@@ -0,0 +1,502 @@
+//! TLS keys that never leave a TPM.
+//!
+//! A [`TpmTlsKey`] is an unrestricted ECDSA P-256 key resident in the TPM
+//! that implements rustls' [`SigningKey`]. To get it a certificate, the TPM
+//! certifies the key with its attestation key (AK), and a CA that trusts the
+//! AK checks the resulting [`TpmKeyCertification`] before issuing a
+//! certificate for the key.
+
+use std::sync::{Arc, Mutex};
+
+use p256::ecdsa::{Signature, VerifyingKey};
+use rcgen::{CertificateParams, KeyPair, PublicKeyData, SignatureAlgorithm};
+use rustls::pki_types::CertificateDer;
+use rustls::sign::{CertifiedKey, Signer, SigningKey};
+use rustls::SignatureScheme;
+use sha2::{Digest, Sha256};
+use tss_client::{algorithms, curves, ObjectAttributes, TpmSignature, Transport, TssClient};
+use tss_serde::{TssDeserialize, TssReader, TssSerialize};
+use x509_cert::Certificate;
+
+use crate::crypto::scalar;
+use crate::{tpm_object_name, verify_tpm_certify};
+
+/// Attributes a certified TLS key must have: generated inside the TPM, bound
+/// to it, and able to sign anything.
+const TLS_KEY_ATTRIBUTES: ObjectAttributes = ObjectAttributes::FIXED_TPM
+    .union(ObjectAttributes::SENSITIVE_DATA_ORIGIN)
+    .union(ObjectAttributes::SIGN_ENCRYPT);
+
+/// TPMT_PUBLIC template of an unrestricted ECDSA P-256 signing key, as
+/// created by [`TpmTlsKey::create_primary`].
+pub fn tls_key_template() -> Vec<u8> {
+    let attributes = TLS_KEY_ATTRIBUTES
+        | ObjectAttributes::FIXED_PARENT
+        | ObjectAttributes::USER_WITH_AUTH
+        | ObjectAttributes::NO_DA;
+    let mut template = Vec::new();
+    algorithms::ECC.serialize_into(&mut template);
+    algorithms::SHA256.serialize_into(&mut template);
+    attributes.serialize_into(&mut template);
+    0u16.serialize_into(&mut template); // authPolicy
+    algorithms::NULL.serialize_into(&mut template); // symmetric
+    algorithms::ECDSA.serialize_into(&mut template);
+    algorithms::SHA256.serialize_into(&mut template);
+    curves::NIST_P256.serialize_into(&mut template);
+    algorithms::NULL.serialize_into(&mut template); // kdf
+    0u16.serialize_into(&mut template); // x
+    0u16.serialize_into(&mut template); // y
+    template
+}
+
+/// The attributes and public key of the TPMT_PUBLIC of an ECC P-256 key.
+fn parse_ecc_public(public: &[u8]) -> eyre::Result<(ObjectAttributes, VerifyingKey)> {
+    let mut reader = TssReader::new(public);
+    if reader.read_u16()? != algorithms::ECC {
+        eyre::bail!("TPM key is not an ECC key");
+    }
+    reader.read_u16()?; // nameAlg
+    let attributes = ObjectAttributes::from_tss_reader(&mut reader)?;
+    let policy_size = reader.read_u16()?;
+    reader.skip(policy_size as usize)?;
+    if reader.read_u16()? != algorithms::NULL {
+        reader.skip(4)?; // keyBits and mode of the symmetric algorithm
+    }
+    if reader.read_u16()? != algorithms::NULL {
+        reader.read_u16()?; // hash of the scheme
+    }
+    if reader.read_u16()? != curves::NIST_P256 {
+        eyre::bail!("TPM key is not on the P-256 curve");
+    }
+    if reader.read_u16()? != algorithms::NULL {
+        reader.read_u16()?; // hash of the KDF
+    }
+    let x_size = reader.read_u16()?;
+    let x = scalar(&reader.read_bytes(x_size as usize)?)?;
+    let y_size = reader.read_u16()?;
+    let y = scalar(&reader.read_bytes(y_size as usize)?)?;
+    let key = VerifyingKey::from_sec1_bytes(&[&[0x04][..], &x, &y].concat())
+        .map_err(|_| eyre::eyre!("invalid P-256 public key in TPMT_PUBLIC"))?;
+    Ok((attributes, key))
+}
+
+/// An unrestricted ECDSA P-256 key in a TPM, signing TLS handshakes.
+pub struct TpmTlsKey<T: Transport> {
+    tpm: Arc<Mutex<TssClient<T>>>,
+    handle: u32,
+    public: Vec<u8>,
+    key: VerifyingKey,
+    transient: bool,
+}
+
+impl<T: Transport> std::fmt::Debug for TpmTlsKey<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TpmTlsKey")
+            .field("handle", &format_args!("{:#x}", self.handle))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Transport> TpmTlsKey<T> {
+    /// Create the key as a primary object of `hierarchy` from
+    /// [`tls_key_template`], and flush it when dropped.
+    ///
+    /// Primary keys derive from the seed of their hierarchy, so the same key
+    /// comes back after a reboot until the hierarchy is cleared.
+    pub fn create_primary(tpm: Arc<Mutex<TssClient<T>>>, hierarchy: u32) -> eyre::Result<Self> {
+        let handle = lock(&tpm).create_primary(hierarchy, &tls_key_template())?;
+        match Self::load(tpm.clone(), handle) {
+            Ok(mut key) => {
+                key.transient = true;
+                Ok(key)
+            }
+            Err(err) => {
+                let _ = lock(&tpm).flush_context(handle);
+                Err(err)
+            }
+        }
+    }
+
+    /// Use the key at `handle`, e.g. a persistent one.
+    pub fn load(tpm: Arc<Mutex<TssClient<T>>>, handle: u32) -> eyre::Result<Self> {
+        let public = lock(&tpm).read_public(handle)?.public;
+        let (attributes, key) = parse_ecc_public(&public)?;
+        if !attributes.contains(ObjectAttributes::SIGN_ENCRYPT)
+            || attributes.contains(ObjectAttributes::RESTRICTED)
+        {
+            eyre::bail!(
+                "TPM key at {:#x} is not an unrestricted signing key",
+                handle
+            );
+        }
+        Ok(Self {
+            tpm,
+            handle,
+            public,
+            key,
+            transient: false,
+        })
+    }
+
+    pub fn handle(&self) -> u32 {
+        self.handle
+    }
+
+    pub fn verifying_key(&self) -> &VerifyingKey {
+        &self.key
+    }
+
+    /// The TPMT_PUBLIC of the key.
+    pub fn public_area(&self) -> &[u8] {
+        &self.public
+    }
+
+    /// Certify the key with the AK at `ak_handle`, answering `nonce`.
+    pub fn certify(&self, ak_handle: u32, nonce: &[u8]) -> eyre::Result<TpmKeyCertification> {
+        let response = lock(&self.tpm).certify(self.handle, ak_handle, nonce)?;
+        Ok(TpmKeyCertification {
+            public: self.public.clone(),
+            attest: response.attest,
+            signature: raw_signature(response.signature)?,
+        })
+    }
+}
+
+impl<T: Transport + Send + 'static> TpmTlsKey<T> {
+    /// Pair the key with its certificate chain, e.g. for
+    /// [`rustls::sign::SingleCertAndKey`].
+    pub fn certified_key(self, chain: Vec<CertificateDer<'static>>) -> CertifiedKey {
+        CertifiedKey::new(chain, Arc::new(self))
+    }
+}
+
+impl<T: Transport> Drop for TpmTlsKey<T> {
+    fn drop(&mut self) {
+        if self.transient {
+            let _ = lock(&self.tpm).flush_context(self.handle);
+        }
+    }
+}
+
+impl<T: Transport + Send + 'static> SigningKey for TpmTlsKey<T> {
+    fn choose_scheme(&self, offered: &[SignatureScheme]) -> Option<Box<dyn Signer>> {
+        if !offered.contains(&SignatureScheme::ECDSA_NISTP256_SHA256) {
+            return None;
+        }
+        Some(Box::new(TpmTlsSigner {
+            tpm: self.tpm.clone(),
+            handle: self.handle,
+        }))
+    }
+
+    fn algorithm(&self) -> rustls::SignatureAlgorithm {
+        rustls::SignatureAlgorithm::ECDSA
+    }
+}
+
+struct TpmTlsSigner<T> {
+    tpm: Arc<Mutex<TssClient<T>>>,
+    handle: u32,
+}
+
+impl<T> std::fmt::Debug for TpmTlsSigner<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TpmTlsSigner")
+            .field("handle", &format_args!("{:#x}", self.handle))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Transport + Send> Signer for TpmTlsSigner<T> {
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, rustls::Error> {
+        let digest = Sha256::digest(message);
+        let signature = lock(&self.tpm)
+            .sign(self.handle, &digest)
+            .and_then(raw_signature)
+            .and_then(|raw| Ok(Signature::from_slice(&raw)?))
+            .map_err(|err| rustls::Error::General(format!("TPM signing failed: {:#}", err)))?;
+        Ok(signature.to_der().as_bytes().to_vec())
+    }
+
+    fn scheme(&self) -> SignatureScheme {
+        SignatureScheme::ECDSA_NISTP256_SHA256
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// The raw `r || s` of an ECDSA P-256 TPM signature.
+fn raw_signature(signature: TpmSignature) -> eyre::Result<[u8; 64]> {
+    let TpmSignature::Ecdsa { r, s, .. } = signature else {
+        eyre::bail!("the TPM key did not return an ECDSA signature");
+    };
+    let mut raw = [0u8; 64];
+    raw[..32].copy_from_slice(&scalar(&r)?);
+    raw[32..].copy_from_slice(&scalar(&s)?);
+    Ok(raw)
+}
+
+/// The proof, signed by an AK, that a TPM holds a TLS key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpmKeyCertification {
+    /// The TPMT_PUBLIC of the key.
+    pub public: Vec<u8>,
+    /// The TPMS_ATTEST of TPM2_Certify.
+    pub attest: Vec<u8>,
+    /// Raw `r || s` ECDSA signature of `attest` by the AK.
+    pub signature: [u8; 64],
+}
+
+impl TpmKeyCertification {
+    /// Check that the AK certified by `ak_cert` certified the key in answer
+    /// to `nonce`, and that the key cannot leave the TPM. Returns the key.
+    ///
+    /// Whether `ak_cert` belongs to a genuine TPM is up to the caller.
+    pub fn verify(&self, ak_cert: &Certificate, nonce: &[u8]) -> eyre::Result<VerifyingKey> {
+        let info = verify_tpm_certify(&self.attest, &self.signature, ak_cert)?;
+        if info.extra_data != nonce {
+            eyre::bail!("TPM certification does not answer the nonce");
+        }
+        if info.name != tpm_object_name(&self.public)? {
+            eyre::bail!("TPM certification is not about this key");
+        }
+        let (attributes, key) = parse_ecc_public(&self.public)?;
+        if !attributes.contains(TLS_KEY_ATTRIBUTES)
+            || attributes.contains(ObjectAttributes::RESTRICTED)
+        {
+            eyre::bail!("certified TPM key has attributes {:#x}", attributes);
+        }
+        Ok(key)
+    }
+
+    /// Verify the certification as [`TpmKeyCertification::verify`] does, and
+    /// issue a DER certificate of `params` for the key, signed by `issuer`.
+    pub fn issue_certificate(
+        &self,
+        ak_cert: &Certificate,
+        nonce: &[u8],
+        params: CertificateParams,
+        issuer: &rcgen::Certificate,
+        issuer_key: &KeyPair,
+    ) -> eyre::Result<Vec<u8>> {
+        let key = self.verify(ak_cert, nonce)?;
+        let public_key = CertifiedPublicKey(key.to_encoded_point(false).as_bytes().to_vec());
+        let cert = params.signed_by(&public_key, issuer, issuer_key)?;
+        Ok(cert.der().to_vec())
+    }
+}
+
+/// The uncompressed point of a certified P-256 key.
+struct CertifiedPublicKey(Vec<u8>);
+
+impl PublicKeyData for CertifiedPublicKey {
+    fn der_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn algorithm(&self) -> &SignatureAlgorithm {
+        &rcgen::PKCS_ECDSA_P256_SHA256
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use p256::ecdsa::signature::hazmat::PrehashSigner;
+    use p256::ecdsa::signature::Signer as _;
+    use p256::ecdsa::SigningKey as EcdsaKey;
+    use rcgen::{BasicConstraints, IsCa};
+    use rustls::pki_types::ServerName;
+    use rustls::sign::SingleCertAndKey;
+    use rustls::{
+        ClientConfig, ClientConnection, Connection, RootCertStore, ServerConfig, ServerConnection,
+    };
+    use tss_client::{commands, ResponseHeader};
+    use tss_serde::{Tpm2b, TssDeserialize};
+
+    use super::*;
+    use crate::test_utils::{signing_key, TestPki};
+    use crate::{TPM_GENERATED_VALUE, TPM_ST_ATTEST_CERTIFY};
+
+    const KEY_HANDLE: u32 = 0x80000001;
+    const AK_HANDLE: u32 = 0x81010001;
+
+    /// A TPM holding a software TLS key and AK.
+    struct FakeTpm {
+        key: EcdsaKey,
+        ak: EcdsaKey,
+        flushed: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl FakeTpm {
+        fn public(&self) -> Vec<u8> {
+            let template = tls_key_template();
+            let point = self.key.verifying_key().to_encoded_point(false);
+            let mut public = template[..template.len() - 4].to_vec();
+            public.extend_from_slice(&Tpm2b(point.x().unwrap().to_vec()).to_tss_bytes());
+            public.extend_from_slice(&Tpm2b(point.y().unwrap().to_vec()).to_tss_bytes());
+            public
+        }
+
+        fn respond(&mut self, command: &[u8]) -> eyre::Result<Vec<u8>> {
+            let code = u32::from_be_bytes(command[6..10].try_into()?);
+            let with_parameters = |parameters: Vec<u8>| {
+                let mut body = (parameters.len() as u32).to_tss_bytes();
+                body.extend_from_slice(&parameters);
+                body
+            };
+            let ecdsa = |signature: Signature| {
+                let mut body = algorithms::ECDSA.to_tss_bytes();
+                body.extend_from_slice(&algorithms::SHA256.to_tss_bytes());
+                body.extend_from_slice(&Tpm2b(signature.r().to_bytes().to_vec()).to_tss_bytes());
+                body.extend_from_slice(&Tpm2b(signature.s().to_bytes().to_vec()).to_tss_bytes());
+                body
+            };
+            Ok(match code {
+                commands::CREATE_PRIMARY => KEY_HANDLE.to_tss_bytes(),
+                commands::READ_PUBLIC => {
+                    let name = tpm_object_name(&self.public())?;
+                    let mut body = Tpm2b(self.public()).to_tss_bytes();
+                    body.extend_from_slice(&Tpm2b(name.clone()).to_tss_bytes());
+                    body.extend_from_slice(&Tpm2b(name).to_tss_bytes());
+                    body
+                }
+                commands::SIGN => {
+                    // A handle and a password session precede the digest.
+                    let digest = Tpm2b::from_tss_bytes(&command[10 + 4 + 4 + 9..])?;
+                    with_parameters(ecdsa(self.key.sign_prehash(&digest.0)?))
+                }
+                commands::CERTIFY => {
+                    // Two handles and two password sessions precede the nonce.
+                    let nonce = Tpm2b::from_tss_bytes(&command[10 + 8 + 4 + 18..])?;
+                    let mut attest = TPM_GENERATED_VALUE.to_tss_bytes();
+                    attest.extend_from_slice(&TPM_ST_ATTEST_CERTIFY.to_tss_bytes());
+                    attest.extend_from_slice(&Tpm2b(vec![1; 34]).to_tss_bytes());
+                    attest.extend_from_slice(&nonce.to_tss_bytes());
+                    attest.extend_from_slice(&[0; 17 + 8]);
+                    let name = tpm_object_name(&self.public())?;
+                    attest.extend_from_slice(&Tpm2b(name.clone()).to_tss_bytes());
+                    attest.extend_from_slice(&Tpm2b(name).to_tss_bytes());
+                    let signature: Signature = self.ak.sign(&attest);
+                    let mut parameters = Tpm2b(attest).to_tss_bytes();
+                    parameters.extend_from_slice(&ecdsa(signature));
+                    with_parameters(parameters)
+                }
+                commands::FLUSH_CONTEXT => {
+                    let handle = u32::from_be_bytes(command[10..14].try_into()?);
+                    lock(&self.flushed).push(handle);
+                    Vec::new()
+                }
+                _ => eyre::bail!("unexpected command {:#x}", code),
+            })
+        }
+    }
+
+    impl Transport for FakeTpm {
+        fn send_command(&mut self, command: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
+            let body = self.respond(command)?;
+            let header = ResponseHeader {
+                tag: 0x8001,
+                size: 10 + body.len() as u32,
+                response_code: 0,
+            };
+            Ok((header, body))
+        }
+    }
+
+    fn tpm(pki: &TestPki, flushed: &Arc<Mutex<Vec<u32>>>) -> Arc<Mutex<TssClient<FakeTpm>>> {
+        Arc::new(Mutex::new(TssClient::new(FakeTpm {
+            key: signing_key(42),
+            ak: pki.pck_key.clone(),
+            flushed: flushed.clone(),
+        })))
+    }
+
+    #[test]
+    fn test_certify_and_issue() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let flushed = Arc::default();
+        let key = TpmTlsKey::create_primary(tpm(&pki, &flushed), tss_client::handles::OWNER)?;
+        assert_eq!(key.verifying_key(), signing_key(42).verifying_key());
+
+        let certification = key.certify(AK_HANDLE, b"nonce")?;
+        assert_eq!(
+            certification.verify(&pki.pck_cert, b"nonce")?,
+            *key.verifying_key()
+        );
+        assert!(certification.verify(&pki.pck_cert, b"other").is_err());
+        let mut other_key = certification.clone();
+        other_key.public = tls_key_template();
+        assert!(other_key.verify(&pki.pck_cert, b"nonce").is_err());
+
+        drop(key);
+        assert_eq!(*lock(&flushed), [KEY_HANDLE]);
+        Ok(())
+    }
+
+    /// Move the pending TLS records of `from` to `to`.
+    fn transfer(from: &mut Connection, to: &mut Connection) -> eyre::Result<()> {
+        let mut records = Vec::new();
+        while from.wants_write() {
+            from.write_tls(&mut records)?;
+        }
+        let mut records = &records[..];
+        while !records.is_empty() {
+            to.read_tls(&mut records)?;
+        }
+        to.process_new_packets()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_tls_handshake() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let key =
+            TpmTlsKey::create_primary(tpm(&pki, &Arc::default()), tss_client::handles::OWNER)?;
+
+        let ca_key = KeyPair::generate()?;
+        let mut ca_params = CertificateParams::new(Vec::new())?;
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = ca_params.self_signed(&ca_key)?;
+        let cert = key.certify(AK_HANDLE, b"nonce")?.issue_certificate(
+            &pki.pck_cert,
+            b"nonce",
+            CertificateParams::new(vec!["tpm.example".to_string()])?,
+            &ca,
+            &ca_key,
+        )?;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let certified = key.certified_key(vec![CertificateDer::from(cert)]);
+        let server_config = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(SingleCertAndKey::from(certified)));
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from(ca.der().to_vec()))?;
+        let client_config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let mut client = Connection::from(ClientConnection::new(
+            Arc::new(client_config),
+            ServerName::try_from("tpm.example")?,
+        )?);
+        let mut server = Connection::from(ServerConnection::new(Arc::new(server_config))?);
+        while client.is_handshaking() || server.is_handshaking() {
+            transfer(&mut client, &mut server)?;
+            transfer(&mut server, &mut client)?;
+        }
+
+        server.writer().write_all(b"hello")?;
+        transfer(&mut server, &mut client)?;
+        let mut received = [0u8; 5];
+        client.reader().read_exact(&mut received)?;
+        assert_eq!(&received, b"hello");
+        Ok(())
+    }
+}
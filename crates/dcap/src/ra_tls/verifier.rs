@@ -0,0 +1,316 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use der::{Decode, Encode};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::{CertificateError, DigitallySignedStruct, DistinguishedName, OtherError};
+use x509_cert::Certificate;
+
+use super::RA_TLS_QUOTE_OID;
+use crate::primitives::tcb_info::TcbStatus;
+use crate::{
+    verify_quote_with_anchors, CollateralFetcher, CollateralKey, Quote, TrustAnchors,
+    VerificationResult, VerifyOptions,
+};
+
+/// Decides whether a verified quote is acceptable, e.g. by its TCB status and
+/// measurements.
+pub type RaTlsPolicy = Box<dyn Fn(&VerificationResult) -> eyre::Result<()> + Send + Sync>;
+
+/// The quote carried by an RA-TLS certificate.
+pub fn ra_tls_quote(cert: &Certificate) -> eyre::Result<&[u8]> {
+    cert.tbs_certificate
+        .extensions
+        .iter()
+        .flatten()
+        .find(|extension| extension.extn_id == RA_TLS_QUOTE_OID)
+        .map(|extension| extension.extn_value.as_bytes())
+        .ok_or_else(|| eyre::eyre!("certificate carries no RA-TLS quote"))
+}
+
+/// Verifies RA-TLS certificates: the quote must verify against the
+/// collateral of its platform, bind the certificate's public key and pass the
+/// policy.
+///
+/// Implements both [`ServerCertVerifier`] and [`ClientCertVerifier`], so it
+/// can be plugged into either side of a rustls connection. Possession of the
+/// private key is then proven by the TLS handshake itself.
+pub struct RaTlsVerifier {
+    collateral: Arc<dyn CollateralFetcher + Send + Sync>,
+    anchors: TrustAnchors,
+    policy: RaTlsPolicy,
+    provider: Arc<CryptoProvider>,
+}
+
+impl std::fmt::Debug for RaTlsVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RaTlsVerifier")
+            .field(
+                "roots",
+                &self
+                    .anchors
+                    .roots()
+                    .iter()
+                    .map(|root| root.tbs_certificate.subject.to_string())
+                    .collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl RaTlsVerifier {
+    /// A verifier taking collateral from `collateral`, typically a
+    /// [`crate::CollateralCache`] or a [`crate::CollateralStore`], and only
+    /// accepting up to date platforms.
+    pub fn new(collateral: Arc<dyn CollateralFetcher + Send + Sync>) -> Self {
+        Self {
+            collateral,
+            anchors: TrustAnchors::default(),
+            policy: Box::new(require_up_to_date),
+            provider: Arc::new(rustls::crypto::ring::default_provider()),
+        }
+    }
+
+    /// Trust `root` instead of the Intel SGX Root CA.
+    pub fn with_root(mut self, root: Certificate) -> Self {
+        self.anchors = TrustAnchors::from_root(root);
+        self
+    }
+
+    /// Trust the roots of `anchors` instead of the Intel SGX Root CA.
+    pub fn with_trust_anchors(mut self, anchors: TrustAnchors) -> Self {
+        self.anchors = anchors;
+        self
+    }
+
+    /// Replace the default policy, which requires an up to date TCB.
+    pub fn with_policy(
+        mut self,
+        policy: impl Fn(&VerificationResult) -> eyre::Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.policy = Box::new(policy);
+        self
+    }
+
+    /// Use `provider` to check handshake signatures.
+    pub fn with_crypto_provider(mut self, provider: Arc<CryptoProvider>) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    /// Verify the DER certificate `cert` at `at`.
+    pub fn verify(&self, cert: &[u8], at: DateTime<Utc>) -> eyre::Result<VerificationResult> {
+        let cert = Certificate::from_der(cert)?;
+        check_validity(&cert, at)?;
+
+        let quote = ra_tls_quote(&cert)?;
+        let key = CollateralKey::from_quote(&Quote::parse(quote)?)?;
+        let collateral = self.collateral.fetch_collateral(&key)?;
+        let result = verify_quote_with_anchors(
+            quote,
+            &collateral,
+            &self.anchors,
+            &VerifyOptions::default(),
+            at,
+        )?;
+
+        let public_key = cert.tbs_certificate.subject_public_key_info.to_der()?;
+        result.verify_binding(None, Some(&public_key))?;
+        (self.policy)(&result)?;
+        Ok(result)
+    }
+
+    fn verify_peer(&self, cert: &CertificateDer<'_>, now: UnixTime) -> Result<(), rustls::Error> {
+        let at = DateTime::from_timestamp(now.as_secs() as i64, 0)
+            .ok_or(rustls::Error::FailedToGetCurrentTime)?;
+        self.verify(cert, at).map(|_| ()).map_err(|err| {
+            let err: Box<dyn std::error::Error + Send + Sync> = err.into();
+            rustls::Error::InvalidCertificate(CertificateError::Other(OtherError(err.into())))
+        })
+    }
+}
+
+impl ServerCertVerifier for RaTlsVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.verify_peer(end_entity, now)?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+impl ClientCertVerifier for RaTlsVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.verify_peer(end_entity, now)?;
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        ServerCertVerifier::verify_tls12_signature(self, message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        ServerCertVerifier::verify_tls13_signature(self, message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        ServerCertVerifier::supported_verify_schemes(self)
+    }
+}
+
+fn require_up_to_date(result: &VerificationResult) -> eyre::Result<()> {
+    if result.status != TcbStatus::UpToDate {
+        eyre::bail!("TCB status {:?} is not acceptable", result.status);
+    }
+    Ok(())
+}
+
+fn check_validity(cert: &Certificate, at: DateTime<Utc>) -> eyre::Result<()> {
+    let validity = &cert.tbs_certificate.validity;
+    let at = at.timestamp();
+    let not_before = validity.not_before.to_unix_duration().as_secs() as i64;
+    let not_after = validity.not_after.to_unix_duration().as_secs() as i64;
+    if at < not_before || at > not_after {
+        eyre::bail!("RA-TLS certificate is not valid at {}", at);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ra_tls::RaTlsCertificateBuilder;
+    use crate::tee_type;
+    use crate::test_utils::{
+        build_quote, quote_collateral, sgx_report_body, verification_time, TestPki,
+    };
+    use crate::{CollateralStore, QUOTE_VERSION_3};
+    use std::time::{Duration, SystemTime};
+
+    fn verifier(pki: &TestPki) -> eyre::Result<RaTlsVerifier> {
+        let mut store = CollateralStore::new();
+        store.insert(quote_collateral(pki, tee_type::SGX))?;
+        Ok(RaTlsVerifier::new(Arc::new(store))
+            .with_root(pki.root_cert.clone())
+            .with_policy(|_| Ok(())))
+    }
+
+    fn certificate(pki: &TestPki, bind: bool) -> eyre::Result<Vec<u8>> {
+        let not_before = SystemTime::UNIX_EPOCH
+            + Duration::from_secs(verification_time().timestamp() as u64 - 60);
+        let certificate = RaTlsCertificateBuilder::new()
+            .with_not_before(not_before)
+            .build(|report_data| {
+                let mut body = sgx_report_body();
+                if bind {
+                    body[320..384].copy_from_slice(report_data);
+                }
+                Ok(build_quote(
+                    pki,
+                    QUOTE_VERSION_3,
+                    tee_type::SGX,
+                    None,
+                    &body,
+                ))
+            })?;
+        Ok(certificate.cert_der)
+    }
+
+    #[test]
+    fn test_verify_certificate() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let verifier = verifier(&pki)?;
+        let result = verifier.verify(&certificate(&pki, true)?, verification_time())?;
+        assert_eq!(result.quote.header.tee_type, tee_type::SGX);
+
+        // The quote is genuine but does not cover the certificate's key.
+        let err = verifier
+            .verify(&certificate(&pki, false)?, verification_time())
+            .unwrap_err();
+        assert!(err.to_string().contains("public key"));
+
+        let cert = certificate(&pki, true)?;
+        let expired = verification_time() + chrono::Duration::days(2);
+        assert!(verifier.verify(&cert, expired).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_and_rustls_errors() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let verifier = verifier(&pki)?.with_policy(|_| eyre::bail!("rejected"));
+        let cert = CertificateDer::from(certificate(&pki, true)?);
+        let now =
+            UnixTime::since_unix_epoch(Duration::from_secs(verification_time().timestamp() as u64));
+
+        let err = verifier.verify_client_cert(&cert, &[], now).unwrap_err();
+        assert!(matches!(
+            err,
+            rustls::Error::InvalidCertificate(CertificateError::Other(_))
+        ));
+        assert!(err.to_string().contains("rejected"));
+        assert!(!ServerCertVerifier::supported_verify_schemes(&verifier).is_empty());
+        Ok(())
+    }
+}
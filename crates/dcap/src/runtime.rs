@@ -0,0 +1,23 @@
+use std::future::Future;
+use std::sync::Arc;
+
+/// A current-thread tokio runtime driving the futures of a blocking client.
+///
+/// As with `reqwest::blocking`, blocking clients must not be used from within
+/// an async context; use the async clients there.
+#[derive(Debug, Clone)]
+pub(crate) struct BlockingRuntime(Arc<tokio::runtime::Runtime>);
+
+impl BlockingRuntime {
+    pub fn new() -> Self {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("building a current-thread runtime");
+        Self(Arc::new(runtime))
+    }
+
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.0.block_on(future)
+    }
+}
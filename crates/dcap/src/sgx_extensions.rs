@@ -0,0 +1,255 @@
+use der::asn1::{Any, ObjectIdentifier, OctetString};
+use der::{Decode, Sequence, Tag, Tagged};
+use x509_cert::Certificate;
+
+use crate::PckChain;
+
+/// Root OID of the Intel SGX X.509 extension carried by PCK certificates.
+pub const SGX_EXTENSIONS_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1");
+
+pub mod sgx_extension_oids {
+    use der::asn1::ObjectIdentifier;
+
+    pub const PPID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.1");
+    pub const TCB: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.2");
+    pub const PCE_ID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.3");
+    pub const FMSPC: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.4");
+    pub const SGX_TYPE: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.5");
+    pub const PLATFORM_INSTANCE_ID: ObjectIdentifier =
+        ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.6");
+    pub const CONFIGURATION: ObjectIdentifier =
+        ObjectIdentifier::new_unwrap("1.2.840.113741.1.13.1.7");
+
+    /// Arc of the PCESVN entry below [`TCB`]; arcs 1 to 16 are the
+    /// SGX TCB component SVNs.
+    pub const TCB_PCESVN_ARC: u32 = 17;
+    /// Arc of the CPUSVN entry below [`TCB`].
+    pub const TCB_CPUSVN_ARC: u32 = 18;
+
+    pub const DYNAMIC_PLATFORM_ARC: u32 = 1;
+    pub const CACHED_KEYS_ARC: u32 = 2;
+    pub const SMT_ENABLED_ARC: u32 = 3;
+}
+
+/// One `SEQUENCE { OID, ANY }` entry of the SGX extension.
+#[derive(Clone, Debug, PartialEq, Eq, Sequence)]
+pub(crate) struct SgxExtensionEntry {
+    pub id: ObjectIdentifier,
+    pub value: Any,
+}
+
+/// The platform TCB the PCK certificate was issued for.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PckTcb {
+    pub sgx_tcb_comp_svns: [u8; 16],
+    pub pce_svn: u16,
+    pub cpu_svn: [u8; 16],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SgxType {
+    Standard,
+    Scalable,
+    ScalableWithIntegrity,
+    Unknown(u8),
+}
+
+impl From<u8> for SgxType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => SgxType::Standard,
+            1 => SgxType::Scalable,
+            2 => SgxType::ScalableWithIntegrity,
+            other => SgxType::Unknown(other),
+        }
+    }
+}
+
+/// Platform configuration, present for multi-package platforms.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SgxConfiguration {
+    pub dynamic_platform: Option<bool>,
+    pub cached_keys: Option<bool>,
+    pub smt_enabled: Option<bool>,
+}
+
+/// The decoded Intel SGX extension of a PCK certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SgxExtensions {
+    pub ppid: [u8; 16],
+    pub tcb: PckTcb,
+    pub pce_id: [u8; 2],
+    pub fmspc: [u8; 6],
+    pub sgx_type: SgxType,
+    pub platform_instance_id: Option<[u8; 16]>,
+    pub configuration: Option<SgxConfiguration>,
+}
+
+impl SgxExtensions {
+    /// Decode the SGX extension of a PCK certificate.
+    pub fn from_certificate(cert: &Certificate) -> eyre::Result<Self> {
+        let extension = cert
+            .tbs_certificate
+            .extensions
+            .iter()
+            .flatten()
+            .find(|ext| ext.extn_id == SGX_EXTENSIONS_OID)
+            .ok_or_else(|| eyre::eyre!("certificate has no SGX extension"))?;
+        Self::from_der(extension.extn_value.as_bytes())
+    }
+
+    /// Decode the DER-encoded value of the SGX extension.
+    pub fn from_der(der: &[u8]) -> eyre::Result<Self> {
+        let entries = Vec::<SgxExtensionEntry>::from_der(der)?;
+
+        let mut ppid = None;
+        let mut tcb = None;
+        let mut pce_id = None;
+        let mut fmspc = None;
+        let mut sgx_type = None;
+        let mut platform_instance_id = None;
+        let mut configuration = None;
+
+        for entry in entries {
+            match entry.id {
+                sgx_extension_oids::PPID => ppid = Some(octets(&entry.value)?),
+                sgx_extension_oids::TCB => tcb = Some(decode_tcb(&entry.value)?),
+                sgx_extension_oids::PCE_ID => pce_id = Some(octets(&entry.value)?),
+                sgx_extension_oids::FMSPC => fmspc = Some(octets(&entry.value)?),
+                sgx_extension_oids::SGX_TYPE => {
+                    sgx_type = Some(decode_enumerated(&entry.value)?.into())
+                }
+                sgx_extension_oids::PLATFORM_INSTANCE_ID => {
+                    platform_instance_id = Some(octets(&entry.value)?)
+                }
+                sgx_extension_oids::CONFIGURATION => {
+                    configuration = Some(decode_configuration(&entry.value)?)
+                }
+                // Unknown entries are ignored so new fields don't break parsing.
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            ppid: ppid.ok_or_else(|| missing("PPID"))?,
+            tcb: tcb.ok_or_else(|| missing("TCB"))?,
+            pce_id: pce_id.ok_or_else(|| missing("PCE-ID"))?,
+            fmspc: fmspc.ok_or_else(|| missing("FMSPC"))?,
+            sgx_type: sgx_type.ok_or_else(|| missing("SGX Type"))?,
+            platform_instance_id,
+            configuration,
+        })
+    }
+}
+
+impl PckChain {
+    /// Decode the SGX extension of the PCK leaf certificate.
+    pub fn sgx_extensions(&self) -> eyre::Result<SgxExtensions> {
+        SgxExtensions::from_certificate(self.pck())
+    }
+}
+
+fn missing(name: &str) -> eyre::Report {
+    eyre::eyre!("SGX extension is missing the {} entry", name)
+}
+
+fn octets<const N: usize>(value: &Any) -> eyre::Result<[u8; N]> {
+    let octets = value.decode_as::<OctetString>()?;
+    octets.as_bytes().try_into().map_err(|_| {
+        eyre::eyre!(
+            "expected {} bytes in SGX extension entry, found {}",
+            N,
+            octets.as_bytes().len()
+        )
+    })
+}
+
+fn decode_enumerated(value: &Any) -> eyre::Result<u8> {
+    if value.tag() != Tag::Enumerated {
+        eyre::bail!("expected ENUMERATED, found {}", value.tag());
+    }
+    match value.value() {
+        [value] => Ok(*value),
+        other => eyre::bail!("unexpected ENUMERATED length {}", other.len()),
+    }
+}
+
+fn last_arc(id: &ObjectIdentifier, parent: &ObjectIdentifier) -> Option<u32> {
+    if id.parent().as_ref() == Some(parent) {
+        id.arcs().last()
+    } else {
+        None
+    }
+}
+
+fn decode_tcb(value: &Any) -> eyre::Result<PckTcb> {
+    let entries = value.decode_as::<Vec<SgxExtensionEntry>>()?;
+
+    let mut tcb = PckTcb::default();
+    let mut has_cpu_svn = false;
+    let mut has_pce_svn = false;
+    for entry in entries {
+        match last_arc(&entry.id, &sgx_extension_oids::TCB) {
+            Some(arc @ 1..=16) => {
+                tcb.sgx_tcb_comp_svns[arc as usize - 1] = entry.value.decode_as::<u8>()?;
+            }
+            Some(sgx_extension_oids::TCB_PCESVN_ARC) => {
+                tcb.pce_svn = entry.value.decode_as::<u16>()?;
+                has_pce_svn = true;
+            }
+            Some(sgx_extension_oids::TCB_CPUSVN_ARC) => {
+                tcb.cpu_svn = octets(&entry.value)?;
+                has_cpu_svn = true;
+            }
+            _ => {}
+        }
+    }
+
+    if !has_pce_svn || !has_cpu_svn {
+        eyre::bail!("SGX TCB extension is missing PCESVN or CPUSVN");
+    }
+    Ok(tcb)
+}
+
+fn decode_configuration(value: &Any) -> eyre::Result<SgxConfiguration> {
+    let entries = value.decode_as::<Vec<SgxExtensionEntry>>()?;
+
+    let mut configuration = SgxConfiguration::default();
+    for entry in entries {
+        let flag = Some(entry.value.decode_as::<bool>()?);
+        match last_arc(&entry.id, &sgx_extension_oids::CONFIGURATION) {
+            Some(sgx_extension_oids::DYNAMIC_PLATFORM_ARC) => configuration.dynamic_platform = flag,
+            Some(sgx_extension_oids::CACHED_KEYS_ARC) => configuration.cached_keys = flag,
+            Some(sgx_extension_oids::SMT_ENABLED_ARC) => configuration.smt_enabled = flag,
+            _ => {}
+        }
+    }
+    Ok(configuration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestPki;
+
+    #[test]
+    fn test_decode_pck_sgx_extensions() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let chain = PckChain::from_pem(pki.pck_chain_pem().as_bytes())?;
+
+        let extensions = chain.sgx_extensions()?;
+        assert_eq!(extensions, pki.sgx_extensions);
+        assert_eq!(extensions.fmspc, [0x00, 0x90, 0x6E, 0xA1, 0x00, 0x00]);
+        assert_eq!(extensions.tcb.sgx_tcb_comp_svns[0], 200);
+        assert_eq!(extensions.tcb.pce_svn, 13);
+        assert_eq!(extensions.sgx_type, SgxType::Scalable);
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_sgx_extension() {
+        let pki = TestPki::new();
+        assert!(SgxExtensions::from_certificate(&pki.root_cert).is_err());
+    }
+}
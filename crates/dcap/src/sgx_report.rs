@@ -0,0 +1,193 @@
+use tss_serde::{TssError, TssReader};
+
+use crate::quote::{read_u16_le, read_u32_le};
+use crate::{EnclaveReportBody, ENCLAVE_REPORT_BODY_SIZE};
+
+/// Size in bytes of an `sgx_report_t`.
+pub const SGX_REPORT_SIZE: usize = 432;
+
+/// Size in bytes of an `sgx_target_info_t`.
+pub const TARGET_INFO_SIZE: usize = 512;
+
+/// Size in bytes of `sgx_report_data_t`.
+pub const REPORT_DATA_SIZE: usize = 64;
+
+/// The 64 bytes of user data an enclave binds into its report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReportData(pub [u8; REPORT_DATA_SIZE]);
+
+impl Default for ReportData {
+    fn default() -> Self {
+        Self([0; REPORT_DATA_SIZE])
+    }
+}
+
+impl ReportData {
+    /// Report data starting with `data`, padded with zeros.
+    pub fn from_slice(data: &[u8]) -> eyre::Result<Self> {
+        if data.len() > REPORT_DATA_SIZE {
+            eyre::bail!(
+                "report data is limited to {} bytes, got {}",
+                REPORT_DATA_SIZE,
+                data.len()
+            );
+        }
+        let mut report_data = [0; REPORT_DATA_SIZE];
+        report_data[..data.len()].copy_from_slice(data);
+        Ok(Self(report_data))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; REPORT_DATA_SIZE] {
+        &self.0
+    }
+}
+
+impl From<[u8; REPORT_DATA_SIZE]> for ReportData {
+    fn from(bytes: [u8; REPORT_DATA_SIZE]) -> Self {
+        Self(bytes)
+    }
+}
+
+/// `sgx_target_info_t`: the enclave a report is targeted at, i.e. the only
+/// enclave able to verify its MAC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetInfo {
+    pub mr_enclave: [u8; 32],
+    pub attributes: [u8; 16],
+    pub config_svn: u16,
+    pub misc_select: u32,
+    pub config_id: [u8; 64],
+}
+
+impl TargetInfo {
+    /// Target the enclave that produced `report`, as done when answering a
+    /// local attestation request.
+    pub fn from_report_body(report: &EnclaveReportBody) -> Self {
+        Self {
+            mr_enclave: report.mr_enclave,
+            attributes: report.attributes,
+            config_svn: report.config_svn,
+            misc_select: report.misc_select,
+            config_id: report.config_id,
+        }
+    }
+
+    pub fn from_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        let mr_enclave = reader.read_array()?;
+        let attributes = reader.read_array()?;
+        reader.skip(2)?;
+        let config_svn = read_u16_le(reader)?;
+        let misc_select = read_u32_le(reader)?;
+        reader.skip(8)?;
+        let config_id = reader.read_array()?;
+        reader.skip(384)?;
+        Ok(Self {
+            mr_enclave,
+            attributes,
+            config_svn,
+            misc_select,
+            config_id,
+        })
+    }
+
+    pub fn parse(bytes: &[u8]) -> eyre::Result<Self> {
+        check_size("target info", bytes, TARGET_INFO_SIZE)?;
+        Ok(Self::from_reader(&mut TssReader::new(bytes))?)
+    }
+
+    /// Encode into the `sgx_target_info_t` layout, with reserved bytes zero.
+    pub fn to_bytes(&self) -> [u8; TARGET_INFO_SIZE] {
+        let mut bytes = [0u8; TARGET_INFO_SIZE];
+        bytes[..32].copy_from_slice(&self.mr_enclave);
+        bytes[32..48].copy_from_slice(&self.attributes);
+        bytes[50..52].copy_from_slice(&self.config_svn.to_le_bytes());
+        bytes[52..56].copy_from_slice(&self.misc_select.to_le_bytes());
+        bytes[64..128].copy_from_slice(&self.config_id);
+        bytes
+    }
+}
+
+/// `sgx_report_t`: an enclave report body, MACed for the target enclave.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SgxReport {
+    pub body: EnclaveReportBody,
+    pub key_id: [u8; 32],
+    pub mac: [u8; 16],
+}
+
+impl SgxReport {
+    pub fn from_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        Ok(Self {
+            body: EnclaveReportBody::from_reader(reader)?,
+            key_id: reader.read_array()?,
+            mac: reader.read_array()?,
+        })
+    }
+
+    pub fn parse(bytes: &[u8]) -> eyre::Result<Self> {
+        check_size("SGX report", bytes, SGX_REPORT_SIZE)?;
+        Ok(Self::from_reader(&mut TssReader::new(bytes))?)
+    }
+
+    pub fn to_bytes(&self) -> [u8; SGX_REPORT_SIZE] {
+        let mut bytes = [0u8; SGX_REPORT_SIZE];
+        bytes[..ENCLAVE_REPORT_BODY_SIZE].copy_from_slice(&self.body.to_bytes());
+        bytes[ENCLAVE_REPORT_BODY_SIZE..ENCLAVE_REPORT_BODY_SIZE + 32]
+            .copy_from_slice(&self.key_id);
+        bytes[ENCLAVE_REPORT_BODY_SIZE + 32..].copy_from_slice(&self.mac);
+        bytes
+    }
+
+    pub fn report_data(&self) -> ReportData {
+        ReportData(self.body.report_data)
+    }
+}
+
+fn check_size(what: &str, bytes: &[u8], size: usize) -> eyre::Result<()> {
+    if bytes.len() != size {
+        eyre::bail!("{} must be {} bytes, got {}", what, size, bytes.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::sgx_report_body;
+
+    #[test]
+    fn test_report_roundtrip() -> eyre::Result<()> {
+        let mut bytes = sgx_report_body().to_vec();
+        bytes.extend_from_slice(&[0x01; 32]);
+        bytes.extend_from_slice(&[0x02; 16]);
+
+        let report = SgxReport::parse(&bytes)?;
+        assert_eq!(report.body.mr_enclave, [0xaa; 32]);
+        assert_eq!(report.report_data(), ReportData([0xcc; 64]));
+        assert_eq!(report.mac, [0x02; 16]);
+        assert_eq!(report.to_bytes().as_slice(), bytes);
+
+        assert!(SgxReport::parse(&bytes[1..]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_target_info() -> eyre::Result<()> {
+        let body = EnclaveReportBody::from_reader(&mut TssReader::new(&sgx_report_body()))?;
+        let target_info = TargetInfo::from_report_body(&body);
+        let bytes = target_info.to_bytes();
+        assert_eq!(&bytes[..32], &[0xaa; 32]);
+        assert_eq!(bytes[32], 0x07);
+        assert_eq!(TargetInfo::parse(&bytes)?, target_info);
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_data() -> eyre::Result<()> {
+        let report_data = ReportData::from_slice(b"nonce")?;
+        assert_eq!(&report_data.as_bytes()[..5], b"nonce");
+        assert_eq!(report_data.as_bytes()[5..], [0; 59]);
+        assert!(ReportData::from_slice(&[0; 65]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use x509_cert::Certificate;
+
+use crate::{
+    intel_sgx_root_ca, verify_quote_with, CollateralFetcher, CollateralKey, Quote, QuoteCollateral,
+    VerificationResult, VerifyOptions,
+};
+
+/// No collateral is held for the platform of a quote.
+///
+/// Returned wrapped in an [`eyre::Report`]; use
+/// `downcast_ref::<MissingCollateral>()` to find out which platform to fetch
+/// collateral for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingCollateral {
+    pub key: CollateralKey,
+}
+
+impl std::fmt::Display for MissingCollateral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no collateral for {}", self.key)
+    }
+}
+
+impl std::error::Error for MissingCollateral {}
+
+/// Collateral for a fleet of platforms, selected per quote by the FMSPC and
+/// issuing CA of its PCK certificate.
+#[derive(Debug, Clone, Default)]
+pub struct CollateralStore {
+    collateral: HashMap<CollateralKey, QuoteCollateral>,
+}
+
+impl CollateralStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `collateral` under the platform it was issued for, replacing any
+    /// previous collateral of that platform.
+    pub fn insert(&mut self, collateral: QuoteCollateral) -> eyre::Result<CollateralKey> {
+        let key = CollateralKey::from_collateral(&collateral)?;
+        self.collateral.insert(key, collateral);
+        Ok(key)
+    }
+
+    pub fn get(&self, key: &CollateralKey) -> Option<&QuoteCollateral> {
+        self.collateral.get(key)
+    }
+
+    pub fn remove(&mut self, key: &CollateralKey) -> Option<QuoteCollateral> {
+        self.collateral.remove(key)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &CollateralKey> {
+        self.collateral.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.collateral.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.collateral.is_empty()
+    }
+
+    /// The collateral for the platform of `quote`.
+    ///
+    /// Fails with [`MissingCollateral`] if none is held.
+    pub fn for_quote(&self, quote: &Quote) -> eyre::Result<&QuoteCollateral> {
+        let key = CollateralKey::from_quote(quote)?;
+        self.get(&key)
+            .ok_or_else(|| MissingCollateral { key }.into())
+    }
+
+    /// The platforms of `quotes` without collateral in the store, each
+    /// reported once.
+    pub fn missing<'a>(
+        &self,
+        quotes: impl IntoIterator<Item = &'a Quote>,
+    ) -> eyre::Result<Vec<CollateralKey>> {
+        let mut missing = Vec::new();
+        for quote in quotes {
+            let key = CollateralKey::from_quote(quote)?;
+            if !self.collateral.contains_key(&key) && !missing.contains(&key) {
+                missing.push(key);
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Verify `quote` like [`crate::verify_quote`], with the collateral of
+    /// its platform.
+    pub fn verify_quote(
+        &self,
+        quote: &[u8],
+        at: DateTime<Utc>,
+    ) -> eyre::Result<VerificationResult> {
+        self.verify_quote_with(quote, &intel_sgx_root_ca(), &VerifyOptions::default(), at)
+    }
+
+    /// Like [`CollateralStore::verify_quote`], see [`verify_quote_with`].
+    pub fn verify_quote_with(
+        &self,
+        quote: &[u8],
+        root: &Certificate,
+        options: &VerifyOptions,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<VerificationResult> {
+        let collateral = self.for_quote(&Quote::parse(quote)?)?;
+        verify_quote_with(quote, collateral, root, options, at)
+    }
+}
+
+impl CollateralFetcher for CollateralStore {
+    fn fetch_collateral(&self, key: &CollateralKey) -> eyre::Result<QuoteCollateral> {
+        self.get(key)
+            .cloned()
+            .ok_or_else(|| MissingCollateral { key: *key }.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tee_type;
+    use crate::test_utils::{quote_collateral, sgx_quote, tdx_quote, verification_time, TestPki};
+
+    #[test]
+    fn test_selects_collateral_by_platform() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let mut store = CollateralStore::new();
+        store.insert(quote_collateral(&pki, tee_type::SGX))?;
+        store.insert(quote_collateral(&pki, tee_type::TDX))?;
+        assert_eq!(store.len(), 2);
+
+        let quote = tdx_quote(&pki);
+        let collateral = store.for_quote(&Quote::parse(&quote)?)?;
+        assert_eq!(collateral.tee_type, tee_type::TDX);
+
+        store.verify_quote_with(
+            &sgx_quote(&pki),
+            &pki.root_cert,
+            &VerifyOptions::default(),
+            verification_time(),
+        )?;
+        store.verify_quote_with(
+            &quote,
+            &pki.root_cert,
+            &VerifyOptions::default(),
+            verification_time(),
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reports_missing_platforms() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let mut store = CollateralStore::new();
+        store.insert(quote_collateral(&pki, tee_type::SGX))?;
+
+        let sgx = Quote::parse(&sgx_quote(&pki))?;
+        let tdx = Quote::parse(&tdx_quote(&pki))?;
+        let missing = store.missing([&sgx, &tdx, &tdx])?;
+        assert_eq!(missing, vec![CollateralKey::from_quote(&tdx)?]);
+
+        let err = store.for_quote(&tdx).unwrap_err();
+        let missing = err.downcast_ref::<MissingCollateral>().unwrap();
+        assert_eq!(
+            missing.to_string(),
+            "no collateral for TDX FMSPC 00906ea10000 (platform CA)"
+        );
+        Ok(())
+    }
+}
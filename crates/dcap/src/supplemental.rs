@@ -0,0 +1,137 @@
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha384};
+use x509_cert::Certificate;
+
+use crate::cert_chain::time_to_datetime;
+use crate::primitives::normalized::{NormalizedEnclaveIdentity, NormalizedTcbInfo};
+use crate::{Crl, SgxExtensions, SgxType};
+
+pub const SUPPLEMENTAL_DATA_MAJOR_VERSION: u16 = 3;
+pub const SUPPLEMENTAL_DATA_MINOR_VERSION: u16 = 0;
+
+/// Mirrors `pck_cert_flag_enum_t`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PckCertFlag {
+    False = 0,
+    True = 1,
+    Undefined = 2,
+}
+
+impl From<Option<bool>> for PckCertFlag {
+    fn from(flag: Option<bool>) -> Self {
+        match flag {
+            Some(false) => PckCertFlag::False,
+            Some(true) => PckCertFlag::True,
+            None => PckCertFlag::Undefined,
+        }
+    }
+}
+
+/// Field-compatible counterpart of the Intel QVL `sgx_ql_qv_supplemental_t`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupplementalData {
+    pub major_version: u16,
+    pub minor_version: u16,
+    /// Earliest issue date of all collateral, including certificates.
+    pub earliest_issue_date: DateTime<Utc>,
+    /// Latest issue date of all collateral, including certificates.
+    pub latest_issue_date: DateTime<Utc>,
+    /// Earliest expiration date of all collateral, including certificates.
+    pub earliest_expiration_date: DateTime<Utc>,
+    /// Date of the matched platform TCB level.
+    pub tcb_level_date_tag: DateTime<Utc>,
+    pub pck_crl_num: u32,
+    pub root_ca_crl_num: u32,
+    /// Lower of the TCB Info and QE Identity evaluation data numbers.
+    pub tcb_eval_ref_num: u32,
+    /// SHA-384 of the root CA public key.
+    pub root_key_id: [u8; 48],
+    pub pck_ppid: [u8; 16],
+    pub tcb_cpusvn: [u8; 16],
+    pub tcb_pce_isvsvn: u16,
+    pub pce_id: u16,
+    pub tee_type: u32,
+    /// 0 for standard, 1 for scalable, 2 for scalable SGX with integrity.
+    pub sgx_type: u8,
+    pub platform_instance_id: [u8; 16],
+    pub dynamic_platform: PckCertFlag,
+    pub cached_keys: PckCertFlag,
+    pub smt_enabled: PckCertFlag,
+    /// Comma separated advisory IDs.
+    pub sa_list: String,
+}
+
+/// The verified collateral the supplemental data is derived from.
+pub(crate) struct SupplementalInputs<'a> {
+    pub certificates: Vec<&'a Certificate>,
+    pub root: &'a Certificate,
+    pub pck_crl: &'a Crl,
+    pub root_ca_crl: &'a Crl,
+    pub tcb_info: &'a NormalizedTcbInfo,
+    pub qe_identity: &'a NormalizedEnclaveIdentity,
+    pub extensions: &'a SgxExtensions,
+    pub tee_type: u32,
+    pub tcb_level_date: DateTime<Utc>,
+    pub advisory_ids: &'a [String],
+}
+
+impl SupplementalData {
+    pub(crate) fn collect(inputs: SupplementalInputs<'_>) -> eyre::Result<Self> {
+        let mut issue_dates = vec![
+            inputs.pck_crl.this_update(),
+            inputs.root_ca_crl.this_update(),
+            inputs.tcb_info.issue_date,
+            inputs.qe_identity.issue_date,
+        ];
+        let mut expiration_dates =
+            vec![inputs.tcb_info.next_update, inputs.qe_identity.next_update];
+        expiration_dates.extend(inputs.pck_crl.next_update());
+        expiration_dates.extend(inputs.root_ca_crl.next_update());
+        for cert in &inputs.certificates {
+            let validity = &cert.tbs_certificate.validity;
+            issue_dates.push(time_to_datetime(&validity.not_before));
+            expiration_dates.push(time_to_datetime(&validity.not_after));
+        }
+
+        let root_key = inputs
+            .root
+            .tbs_certificate
+            .subject_public_key_info
+            .subject_public_key
+            .raw_bytes();
+
+        let extensions = inputs.extensions;
+        let configuration = extensions.configuration.clone().unwrap_or_default();
+        Ok(Self {
+            major_version: SUPPLEMENTAL_DATA_MAJOR_VERSION,
+            minor_version: SUPPLEMENTAL_DATA_MINOR_VERSION,
+            earliest_issue_date: *issue_dates.iter().min().expect("not empty"),
+            latest_issue_date: *issue_dates.iter().max().expect("not empty"),
+            earliest_expiration_date: *expiration_dates.iter().min().expect("not empty"),
+            tcb_level_date_tag: inputs.tcb_level_date,
+            pck_crl_num: inputs.pck_crl.crl_number()?.unwrap_or_default(),
+            root_ca_crl_num: inputs.root_ca_crl.crl_number()?.unwrap_or_default(),
+            tcb_eval_ref_num: inputs
+                .tcb_info
+                .tcb_evaluation_data_number
+                .min(inputs.qe_identity.tcb_evaluation_data_number),
+            root_key_id: Sha384::digest(root_key).into(),
+            pck_ppid: extensions.ppid,
+            tcb_cpusvn: extensions.tcb.cpu_svn,
+            tcb_pce_isvsvn: extensions.tcb.pce_svn,
+            pce_id: u16::from_le_bytes(extensions.pce_id),
+            tee_type: inputs.tee_type,
+            sgx_type: match extensions.sgx_type {
+                SgxType::Standard => 0,
+                SgxType::Scalable => 1,
+                SgxType::ScalableWithIntegrity => 2,
+                SgxType::Unknown(value) => value,
+            },
+            platform_instance_id: extensions.platform_instance_id.unwrap_or_default(),
+            dynamic_platform: configuration.dynamic_platform.into(),
+            cached_keys: configuration.cached_keys.into(),
+            smt_enabled: configuration.smt_enabled.into(),
+            sa_list: inputs.advisory_ids.join(","),
+        })
+    }
+}
@@ -0,0 +1,226 @@
+use tss_serde::{TssError, TssReader};
+
+use crate::{TdReportBody, TdReportBody15};
+
+/// Size in bytes of a TDREPORT (`TDREPORT_STRUCT`).
+pub const TDREPORT_SIZE: usize = 1024;
+
+/// Size in bytes of the `TEE_TCB_INFO` part of a TDREPORT.
+pub const TEE_TCB_INFO_SIZE: usize = 239;
+
+/// `REPORTMACSTRUCT`, the MAC-protected header of a TDREPORT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportMac {
+    /// TEE type, subtype and version; type `0x81` for TDX.
+    pub report_type: [u8; 4],
+    pub cpu_svn: [u8; 16],
+    /// SHA-384 of the `TEE_TCB_INFO`.
+    pub tee_tcb_info_hash: [u8; 48],
+    /// SHA-384 of the `TDINFO`.
+    pub tee_info_hash: [u8; 48],
+    pub report_data: [u8; 64],
+    pub mac: [u8; 32],
+}
+
+impl ReportMac {
+    pub fn from_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        let report_type = reader.read_array()?;
+        reader.skip(12)?;
+        let cpu_svn = reader.read_array()?;
+        let tee_tcb_info_hash = reader.read_array()?;
+        let tee_info_hash = reader.read_array()?;
+        let report_data = reader.read_array()?;
+        reader.skip(32)?;
+        let mac = reader.read_array()?;
+        Ok(Self {
+            report_type,
+            cpu_svn,
+            tee_tcb_info_hash,
+            tee_info_hash,
+            report_data,
+            mac,
+        })
+    }
+}
+
+/// `TEE_TCB_INFO`, describing the TDX module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TeeTcbInfo {
+    /// Bitmap of the fields below that are valid.
+    pub valid: [u8; 8],
+    pub tee_tcb_svn: [u8; 16],
+    pub mr_seam: [u8; 48],
+    pub mr_signer_seam: [u8; 48],
+    pub attributes: [u8; 8],
+    /// Only set by TDX 1.5 modules.
+    pub tee_tcb_svn2: [u8; 16],
+}
+
+impl TeeTcbInfo {
+    pub fn from_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        let info = Self {
+            valid: reader.read_array()?,
+            tee_tcb_svn: reader.read_array()?,
+            mr_seam: reader.read_array()?,
+            mr_signer_seam: reader.read_array()?,
+            attributes: reader.read_array()?,
+            tee_tcb_svn2: reader.read_array()?,
+        };
+        reader.skip(TEE_TCB_INFO_SIZE - 144)?;
+        Ok(info)
+    }
+}
+
+/// `TDINFO`, the measurements and configuration of the TD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TdInfo {
+    pub attributes: [u8; 8],
+    pub xfam: [u8; 8],
+    pub mr_td: [u8; 48],
+    pub mr_config_id: [u8; 48],
+    pub mr_owner: [u8; 48],
+    pub mr_owner_config: [u8; 48],
+    pub rtmrs: [[u8; 48]; 4],
+    /// Hash of the service TD bindings; TDX 1.5 only.
+    pub servtd_hash: [u8; 48],
+}
+
+impl TdInfo {
+    pub fn from_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        let info = Self {
+            attributes: reader.read_array()?,
+            xfam: reader.read_array()?,
+            mr_td: reader.read_array()?,
+            mr_config_id: reader.read_array()?,
+            mr_owner: reader.read_array()?,
+            mr_owner_config: reader.read_array()?,
+            rtmrs: [
+                reader.read_array()?,
+                reader.read_array()?,
+                reader.read_array()?,
+                reader.read_array()?,
+            ],
+            servtd_hash: reader.read_array()?,
+        };
+        reader.skip(64)?;
+        Ok(info)
+    }
+}
+
+/// A TDREPORT as returned by `TDG.MR.REPORT`, before the quoting enclave
+/// turns it into a quote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TdReport {
+    pub report_mac: ReportMac,
+    pub tee_tcb_info: TeeTcbInfo,
+    pub td_info: TdInfo,
+}
+
+impl TdReport {
+    pub fn from_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        let report_mac = ReportMac::from_reader(reader)?;
+        let tee_tcb_info = TeeTcbInfo::from_reader(reader)?;
+        reader.skip(17)?;
+        let td_info = TdInfo::from_reader(reader)?;
+        Ok(Self {
+            report_mac,
+            tee_tcb_info,
+            td_info,
+        })
+    }
+
+    pub fn parse(bytes: &[u8]) -> eyre::Result<Self> {
+        if bytes.len() != TDREPORT_SIZE {
+            eyre::bail!(
+                "TDREPORT must be {} bytes, got {}",
+                TDREPORT_SIZE,
+                bytes.len()
+            );
+        }
+        Ok(Self::from_reader(&mut TssReader::new(bytes))?)
+    }
+
+    pub fn report_data(&self) -> &[u8; 64] {
+        &self.report_mac.report_data
+    }
+
+    /// The TD report body a TDX 1.0 quote of this report carries.
+    pub fn to_td_report_body(&self) -> TdReportBody {
+        let tcb = &self.tee_tcb_info;
+        let td = &self.td_info;
+        TdReportBody {
+            tee_tcb_svn: tcb.tee_tcb_svn,
+            mr_seam: tcb.mr_seam,
+            mr_signer_seam: tcb.mr_signer_seam,
+            seam_attributes: tcb.attributes,
+            td_attributes: td.attributes,
+            xfam: td.xfam,
+            mr_td: td.mr_td,
+            mr_config_id: td.mr_config_id,
+            mr_owner: td.mr_owner,
+            mr_owner_config: td.mr_owner_config,
+            rtmrs: td.rtmrs,
+            report_data: self.report_mac.report_data,
+        }
+    }
+
+    /// The TD report body a TDX 1.5 quote of this report carries.
+    pub fn to_td_report_body15(&self) -> TdReportBody15 {
+        TdReportBody15 {
+            base: self.to_td_report_body(),
+            tee_tcb_svn2: self.tee_tcb_info.tee_tcb_svn2,
+            mr_service_td: self.td_info.servtd_hash,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_tdreport() -> Vec<u8> {
+        let mut report = vec![0u8; TDREPORT_SIZE];
+        report[0] = 0x81; // TDX
+        report[16..32].copy_from_slice(&[0x01; 16]); // cpu_svn
+        report[128..192].copy_from_slice(&[0xcc; 64]); // report_data
+        report[224..256].copy_from_slice(&[0xee; 32]); // mac
+        report[256] = 0xff; // valid
+        report[264..280].copy_from_slice(&[5, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        report[280..328].copy_from_slice(&[0x22; 48]); // mr_seam
+        report[512..520].copy_from_slice(&[0x10, 0, 0, 0, 0, 0, 0, 0]); // td attributes
+        report[528..576].copy_from_slice(&[0x11; 48]); // mr_td
+        for (i, rtmr) in report[720..912].chunks_mut(48).enumerate() {
+            rtmr.fill(0x30 + i as u8);
+        }
+        report[912..960].copy_from_slice(&[0x44; 48]); // servtd_hash
+        report
+    }
+
+    #[test]
+    fn test_parse_tdreport() -> eyre::Result<()> {
+        let report = TdReport::parse(&sample_tdreport())?;
+        assert_eq!(report.report_mac.report_type, [0x81, 0, 0, 0]);
+        assert_eq!(report.report_mac.cpu_svn, [0x01; 16]);
+        assert_eq!(report.report_data(), &[0xcc; 64]);
+        assert_eq!(report.report_mac.mac, [0xee; 32]);
+        assert_eq!(report.tee_tcb_info.tee_tcb_svn[..3], [5, 0, 2]);
+        assert_eq!(report.tee_tcb_info.mr_seam, [0x22; 48]);
+        assert_eq!(report.td_info.mr_td, [0x11; 48]);
+        assert_eq!(report.td_info.rtmrs[3], [0x33; 48]);
+        assert_eq!(report.td_info.servtd_hash, [0x44; 48]);
+
+        assert!(TdReport::parse(&sample_tdreport()[1..]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_body() -> eyre::Result<()> {
+        let report = TdReport::parse(&sample_tdreport())?;
+        let body = report.to_td_report_body15();
+        assert_eq!(body.base.mr_td, report.td_info.mr_td);
+        assert_eq!(body.base.rtmrs, report.td_info.rtmrs);
+        assert_eq!(body.base.report_data, [0xcc; 64]);
+        assert_eq!(body.mr_service_td, [0x44; 48]);
+        Ok(())
+    }
+}
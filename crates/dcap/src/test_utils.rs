@@ -0,0 +1,704 @@
+//! Synthetic Intel-like PKI and quotes for unit tests.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use der::asn1::{Any, BitString, GeneralizedTime, ObjectIdentifier, OctetString, Uint, UtcTime};
+use der::oid::AssociatedOid;
+use der::pem::LineEnding;
+use der::{Encode, EncodePem, Length, Tag, Writer};
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{DerSignature, Signature, SigningKey};
+use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+use x509_cert::crl::{CertificateList, RevokedCert, TbsCertList};
+use x509_cert::ext::pkix::{CrlNumber, CrlReason};
+use x509_cert::ext::{AsExtension, Extension};
+use x509_cert::name::Name;
+use x509_cert::serial_number::SerialNumber;
+use x509_cert::spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned};
+use x509_cert::time::{Time, Validity};
+use x509_cert::{Certificate, Version};
+
+use crate::crypto::sha256;
+use crate::primitives::signed::signed_body;
+use crate::sgx_extensions::SgxExtensionEntry;
+use crate::{
+    attestation_key_type, cert_data_type, sgx_extension_oids, tee_type, Crl, PckTcb,
+    QuoteCollateral, SgxConfiguration, SgxExtensions, SgxType, ECDSA_WITH_SHA256_OID,
+    ENCLAVE_REPORT_BODY_SIZE, QUOTE_VERSION_3, SGX_EXTENSIONS_OID, TD_REPORT10_BODY_SIZE,
+    TPM_GENERATED_VALUE, TPM_ST_ATTEST_QUOTE,
+};
+
+pub const ROOT_SUBJECT: &str = "CN=Intel SGX Root CA,O=Intel Corporation,L=Santa Clara,ST=CA,C=US";
+pub const PLATFORM_CA_SUBJECT: &str =
+    "CN=Intel SGX PCK Platform CA,O=Intel Corporation,L=Santa Clara,ST=CA,C=US";
+pub const PCK_SUBJECT: &str =
+    "CN=Intel SGX PCK Certificate,O=Intel Corporation,L=Santa Clara,ST=CA,C=US";
+pub const TCB_SIGNING_SUBJECT: &str =
+    "CN=Intel SGX TCB Signing,O=Intel Corporation,L=Santa Clara,ST=CA,C=US";
+
+/// 2020-01-01T00:00:00Z
+pub const NOT_BEFORE: u64 = 1_577_836_800;
+/// 2049-12-31T23:59:59Z
+pub const NOT_AFTER: u64 = 2_524_607_999;
+/// 2033-05-18T03:33:20Z
+pub const CRL_NEXT_UPDATE: u64 = 2_000_000_000;
+
+pub fn signing_key(seed: u8) -> SigningKey {
+    SigningKey::from_bytes(&[seed; 32].into()).unwrap()
+}
+
+pub fn validity(not_before: u64, not_after: u64) -> Validity {
+    Validity {
+        not_before: Time::UtcTime(
+            UtcTime::from_unix_duration(Duration::from_secs(not_before)).unwrap(),
+        ),
+        not_after: Time::GeneralTime(
+            GeneralizedTime::from_unix_duration(Duration::from_secs(not_after)).unwrap(),
+        ),
+    }
+}
+
+/// Build a certificate for `subject_key`, signed by `issuer_key`.
+pub fn build_certificate(
+    profile: Profile,
+    serial: u32,
+    subject: &str,
+    subject_key: &SigningKey,
+    issuer_key: &SigningKey,
+) -> Certificate {
+    build_certificate_with(profile, serial, subject, subject_key, issuer_key, |_| {})
+}
+
+/// Like [`build_certificate`], letting the caller add extensions.
+pub fn build_certificate_with(
+    profile: Profile,
+    serial: u32,
+    subject: &str,
+    subject_key: &SigningKey,
+    issuer_key: &SigningKey,
+    customize: impl FnOnce(&mut CertificateBuilder<'_, SigningKey>),
+) -> Certificate {
+    let spki = SubjectPublicKeyInfoOwned::from_key(*subject_key.verifying_key()).unwrap();
+    let mut builder = CertificateBuilder::new(
+        profile,
+        SerialNumber::from(serial),
+        validity(NOT_BEFORE, NOT_AFTER),
+        Name::from_str(subject).unwrap(),
+        spki,
+        issuer_key,
+    )
+    .unwrap();
+    customize(&mut builder);
+    builder.build::<DerSignature>().unwrap()
+}
+
+pub fn to_pem(cert: &Certificate) -> String {
+    cert.to_pem(LineEnding::LF).unwrap()
+}
+
+/// The SGX extension as added to certificates by the builder.
+pub struct SgxExtensionsExt(Vec<SgxExtensionEntry>);
+
+impl SgxExtensionsExt {
+    pub fn new(extensions: &SgxExtensions) -> Self {
+        let entry = |id: ObjectIdentifier, value: Any| SgxExtensionEntry { id, value };
+        let octets = |bytes: &[u8]| Any::encode_from(&OctetString::new(bytes).unwrap()).unwrap();
+
+        let mut tcb = Vec::new();
+        for (i, svn) in extensions.tcb.sgx_tcb_comp_svns.iter().enumerate() {
+            tcb.push(entry(
+                sgx_extension_oids::TCB.push_arc(i as u32 + 1).unwrap(),
+                Any::encode_from(svn).unwrap(),
+            ));
+        }
+        tcb.push(entry(
+            sgx_extension_oids::TCB
+                .push_arc(sgx_extension_oids::TCB_PCESVN_ARC)
+                .unwrap(),
+            Any::encode_from(&extensions.tcb.pce_svn).unwrap(),
+        ));
+        tcb.push(entry(
+            sgx_extension_oids::TCB
+                .push_arc(sgx_extension_oids::TCB_CPUSVN_ARC)
+                .unwrap(),
+            octets(&extensions.tcb.cpu_svn),
+        ));
+
+        let sgx_type = match extensions.sgx_type {
+            SgxType::Standard => 0,
+            SgxType::Scalable => 1,
+            SgxType::ScalableWithIntegrity => 2,
+            SgxType::Unknown(value) => value,
+        };
+
+        let mut entries = vec![
+            entry(sgx_extension_oids::PPID, octets(&extensions.ppid)),
+            entry(sgx_extension_oids::TCB, Any::encode_from(&tcb).unwrap()),
+            entry(sgx_extension_oids::PCE_ID, octets(&extensions.pce_id)),
+            entry(sgx_extension_oids::FMSPC, octets(&extensions.fmspc)),
+            entry(
+                sgx_extension_oids::SGX_TYPE,
+                Any::new(Tag::Enumerated, vec![sgx_type]).unwrap(),
+            ),
+        ];
+        if let Some(id) = &extensions.platform_instance_id {
+            entries.push(entry(sgx_extension_oids::PLATFORM_INSTANCE_ID, octets(id)));
+        }
+        if let Some(configuration) = &extensions.configuration {
+            let flags = [
+                (
+                    sgx_extension_oids::DYNAMIC_PLATFORM_ARC,
+                    configuration.dynamic_platform,
+                ),
+                (
+                    sgx_extension_oids::CACHED_KEYS_ARC,
+                    configuration.cached_keys,
+                ),
+                (
+                    sgx_extension_oids::SMT_ENABLED_ARC,
+                    configuration.smt_enabled,
+                ),
+            ];
+            let flags = flags
+                .iter()
+                .filter_map(|(arc, flag)| {
+                    flag.map(|flag| {
+                        entry(
+                            sgx_extension_oids::CONFIGURATION.push_arc(*arc).unwrap(),
+                            Any::encode_from(&flag).unwrap(),
+                        )
+                    })
+                })
+                .collect::<Vec<_>>();
+            entries.push(entry(
+                sgx_extension_oids::CONFIGURATION,
+                Any::encode_from(&flags).unwrap(),
+            ));
+        }
+        Self(entries)
+    }
+}
+
+impl AssociatedOid for SgxExtensionsExt {
+    const OID: ObjectIdentifier = SGX_EXTENSIONS_OID;
+}
+
+impl Encode for SgxExtensionsExt {
+    fn encoded_len(&self) -> der::Result<Length> {
+        self.0.encoded_len()
+    }
+
+    fn encode(&self, writer: &mut impl Writer) -> der::Result<()> {
+        self.0.encode(writer)
+    }
+}
+
+impl AsExtension for SgxExtensionsExt {
+    fn critical(&self, _subject: &Name, _extensions: &[Extension]) -> bool {
+        false
+    }
+}
+
+/// SGX extension values used for the test PCK certificate.
+pub fn sample_sgx_extensions() -> SgxExtensions {
+    let mut sgx_tcb_comp_svns = [0u8; 16];
+    sgx_tcb_comp_svns[..8].copy_from_slice(&[200, 14, 3, 3, 255, 255, 1, 5]);
+
+    SgxExtensions {
+        ppid: [0x42; 16],
+        tcb: PckTcb {
+            sgx_tcb_comp_svns,
+            pce_svn: 13,
+            cpu_svn: sgx_tcb_comp_svns,
+        },
+        pce_id: [0x00, 0x00],
+        fmspc: [0x00, 0x90, 0x6E, 0xA1, 0x00, 0x00],
+        sgx_type: SgxType::Scalable,
+        platform_instance_id: Some([0x24; 16]),
+        configuration: Some(SgxConfiguration {
+            dynamic_platform: Some(false),
+            cached_keys: Some(true),
+            smt_enabled: Some(true),
+        }),
+    }
+}
+
+/// A root CA, a PCK Platform CA, a PCK leaf certificate and a TCB signing
+/// certificate.
+pub struct TestPki {
+    pub root_key: SigningKey,
+    pub root_cert: Certificate,
+    pub intermediate_key: SigningKey,
+    pub intermediate_cert: Certificate,
+    pub pck_key: SigningKey,
+    pub pck_cert: Certificate,
+    pub sgx_extensions: SgxExtensions,
+    pub tcb_signing_key: SigningKey,
+    pub tcb_signing_cert: Certificate,
+}
+
+impl Default for TestPki {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestPki {
+    pub fn new() -> Self {
+        Self::with_pck_key_seed(3)
+    }
+
+    pub fn with_pck_key_seed(seed: u8) -> Self {
+        let root_key = signing_key(1);
+        let intermediate_key = signing_key(2);
+        let pck_key = signing_key(seed);
+
+        let root_cert = build_certificate(Profile::Root, 1, ROOT_SUBJECT, &root_key, &root_key);
+        let intermediate_cert = build_certificate(
+            Profile::SubCA {
+                issuer: Name::from_str(ROOT_SUBJECT).unwrap(),
+                path_len_constraint: Some(0),
+            },
+            2,
+            PLATFORM_CA_SUBJECT,
+            &intermediate_key,
+            &root_key,
+        );
+        let sgx_extensions = sample_sgx_extensions();
+        let pck_cert = build_certificate_with(
+            Profile::Leaf {
+                issuer: Name::from_str(PLATFORM_CA_SUBJECT).unwrap(),
+                enable_key_agreement: false,
+                enable_key_encipherment: false,
+            },
+            3,
+            PCK_SUBJECT,
+            &pck_key,
+            &intermediate_key,
+            |builder| {
+                builder
+                    .add_extension(&SgxExtensionsExt::new(&sgx_extensions))
+                    .unwrap()
+            },
+        );
+
+        let tcb_signing_key = signing_key(5);
+        let tcb_signing_cert = build_certificate(
+            Profile::Leaf {
+                issuer: Name::from_str(ROOT_SUBJECT).unwrap(),
+                enable_key_agreement: false,
+                enable_key_encipherment: false,
+            },
+            4,
+            TCB_SIGNING_SUBJECT,
+            &tcb_signing_key,
+            &root_key,
+        );
+
+        Self {
+            root_key,
+            root_cert,
+            intermediate_key,
+            intermediate_cert,
+            pck_key,
+            pck_cert,
+            sgx_extensions,
+            tcb_signing_key,
+            tcb_signing_cert,
+        }
+    }
+
+    /// Re-sign the `field` body of a collateral document with the TCB signing
+    /// key.
+    pub fn sign_collateral(&self, document: &str, field: &str) -> String {
+        self.sign_collateral_with(&self.tcb_signing_key, document, field)
+    }
+
+    /// Re-sign the `field` body of a collateral document with `key`.
+    pub fn sign_collateral_with(&self, key: &SigningKey, document: &str, field: &str) -> String {
+        let body = signed_body(document, field).unwrap();
+        let signature = hex::encode(raw_signature(key, body.as_bytes()));
+        format!(r#"{{"{}":{},"signature":"{}"}}"#, field, body, signature)
+    }
+
+    pub fn root_pem(&self) -> String {
+        to_pem(&self.root_cert)
+    }
+
+    pub fn pck_chain_pem(&self) -> String {
+        [
+            to_pem(&self.pck_cert),
+            to_pem(&self.intermediate_cert),
+            to_pem(&self.root_cert),
+        ]
+        .concat()
+    }
+
+    /// A CRL of the PCK Platform CA revoking the given serial numbers.
+    pub fn pck_crl(&self, revoked: &[(u32, Option<CrlReason>)]) -> Crl {
+        self.crl_signed_by(&self.intermediate_key, &self.intermediate_cert, revoked)
+    }
+
+    /// A CRL of the root CA revoking the given serial numbers.
+    pub fn root_crl(&self, revoked: &[(u32, Option<CrlReason>)]) -> Crl {
+        self.crl_signed_by(&self.root_key, &self.root_cert, revoked)
+    }
+
+    /// A CRL naming `issuer` as its issuer, signed with `key`.
+    pub fn crl_signed_by(
+        &self,
+        key: &SigningKey,
+        issuer: &Certificate,
+        revoked: &[(u32, Option<CrlReason>)],
+    ) -> Crl {
+        let validity = validity(NOT_BEFORE, CRL_NEXT_UPDATE);
+        let revoked_certificates = revoked
+            .iter()
+            .map(|(serial, reason)| RevokedCert {
+                serial_number: SerialNumber::from(*serial),
+                revocation_date: validity.not_before,
+                crl_entry_extensions: reason.map(|reason| {
+                    vec![Extension {
+                        extn_id: CrlReason::OID,
+                        critical: false,
+                        extn_value: OctetString::new(reason.to_der().unwrap()).unwrap(),
+                    }]
+                }),
+            })
+            .collect::<Vec<_>>();
+
+        let tbs_cert_list = TbsCertList {
+            version: Version::V2,
+            signature: AlgorithmIdentifierOwned {
+                oid: ECDSA_WITH_SHA256_OID,
+                parameters: None,
+            },
+            issuer: issuer.tbs_certificate.subject.clone(),
+            this_update: validity.not_before,
+            next_update: Some(validity.not_after),
+            revoked_certificates: (!revoked_certificates.is_empty())
+                .then_some(revoked_certificates),
+            crl_extensions: Some(vec![Extension {
+                extn_id: CrlNumber::OID,
+                critical: false,
+                extn_value: OctetString::new(CrlNumber(Uint::new(&[1]).unwrap()).to_der().unwrap())
+                    .unwrap(),
+            }]),
+        };
+        let signature: DerSignature = key.sign(&tbs_cert_list.to_der().unwrap());
+        let list = CertificateList {
+            tbs_cert_list,
+            signature_algorithm: AlgorithmIdentifierOwned {
+                oid: ECDSA_WITH_SHA256_OID,
+                parameters: None,
+            },
+            signature: BitString::from_bytes(signature.as_bytes()).unwrap(),
+        };
+        Crl::from_der(&list.to_der().unwrap()).unwrap()
+    }
+}
+
+/// Intel's QE vendor ID.
+pub const QE_VENDOR_ID: [u8; 16] = [
+    0x93, 0x9A, 0x72, 0x33, 0xF7, 0x9C, 0x4C, 0xA9, 0x94, 0x0A, 0x0D, 0xB3, 0x95, 0x7F, 0x06, 0x07,
+];
+
+/// QE identity values matching `primitives/data/enclave_identity_v2.json`.
+pub const QE_MRSIGNER: &str = "8C4F5775D796503E96137F77C68A829A0056AC8DED70140B081B094490C57BFF";
+pub const QE_ISV_PROD_ID: u16 = 1;
+pub const QE_ISV_SVN: u16 = 8;
+
+pub fn attestation_key() -> SigningKey {
+    signing_key(4)
+}
+
+fn raw_public_key(key: &SigningKey) -> [u8; 64] {
+    let point = key.verifying_key().to_encoded_point(false);
+    point.as_bytes()[1..].try_into().unwrap()
+}
+
+fn raw_signature(key: &SigningKey, message: &[u8]) -> [u8; 64] {
+    let signature: Signature = key.sign(message);
+    signature.to_bytes().into()
+}
+
+/// A QE report as produced by the Intel quoting enclave.
+pub fn qe_report(report_data: &[u8; 64]) -> [u8; ENCLAVE_REPORT_BODY_SIZE] {
+    let mut report = [0u8; ENCLAVE_REPORT_BODY_SIZE];
+    report[48] = 0x11; // attributes
+    report[128..160].copy_from_slice(&hex::decode(QE_MRSIGNER).unwrap());
+    report[256..258].copy_from_slice(&QE_ISV_PROD_ID.to_le_bytes());
+    report[258..260].copy_from_slice(&QE_ISV_SVN.to_le_bytes());
+    report[320..384].copy_from_slice(report_data);
+    report
+}
+
+/// An application enclave report body.
+pub fn sgx_report_body() -> [u8; ENCLAVE_REPORT_BODY_SIZE] {
+    let mut report = [0u8; ENCLAVE_REPORT_BODY_SIZE];
+    report[..16].copy_from_slice(&sample_sgx_extensions().tcb.cpu_svn);
+    report[48] = 0x07; // attributes: INIT | DEBUG | MODE64BIT
+    report[64..96].copy_from_slice(&[0xAA; 32]);
+    report[128..160].copy_from_slice(&[0xBB; 32]);
+    report[256..258].copy_from_slice(&7u16.to_le_bytes());
+    report[258..260].copy_from_slice(&2u16.to_le_bytes());
+    report[320..384].copy_from_slice(&[0xCC; 64]);
+    report
+}
+
+/// A TD 1.0 report body.
+pub fn td_report_body() -> [u8; TD_REPORT10_BODY_SIZE] {
+    let mut report = [0u8; TD_REPORT10_BODY_SIZE];
+    report[..16].copy_from_slice(&[5, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    report[136..184].copy_from_slice(&[0x11; 48]); // mr_td
+    for (i, rtmr) in report[328..520].chunks_mut(48).enumerate() {
+        rtmr.fill(0x20 + i as u8);
+    }
+    report[520..584].copy_from_slice(&[0xCC; 64]);
+    report
+}
+
+/// Build a quote over `body`, signed through the test PKI.
+///
+/// For v5 quotes `body_type` selects the body descriptor.
+pub fn build_quote(
+    pki: &TestPki,
+    version: u16,
+    tee: u32,
+    body_type: Option<u16>,
+    body: &[u8],
+) -> Vec<u8> {
+    let mut chain = pki.pck_chain_pem().into_bytes();
+    chain.push(0);
+    build_quote_with_certification(
+        pki,
+        version,
+        tee,
+        body_type,
+        body,
+        cert_data_type::PCK_CERT_CHAIN,
+        &chain,
+    )
+}
+
+/// Like [`build_quote`], with the given certification data identifying the
+/// PCK key instead of the embedded PCK chain.
+pub fn build_quote_with_certification(
+    pki: &TestPki,
+    version: u16,
+    tee: u32,
+    body_type: Option<u16>,
+    body: &[u8],
+    cert_type: u16,
+    cert_data: &[u8],
+) -> Vec<u8> {
+    let mut quote = Vec::new();
+    quote.extend_from_slice(&version.to_le_bytes());
+    quote.extend_from_slice(&attestation_key_type::ECDSA_P256.to_le_bytes());
+    quote.extend_from_slice(&tee.to_le_bytes());
+    quote.extend_from_slice(&QE_ISV_SVN.to_le_bytes());
+    quote.extend_from_slice(&pki.sgx_extensions.tcb.pce_svn.to_le_bytes());
+    quote.extend_from_slice(&QE_VENDOR_ID);
+    quote.extend_from_slice(&[0u8; 20]);
+    if let Some(body_type) = body_type {
+        quote.extend_from_slice(&body_type.to_le_bytes());
+        quote.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    }
+    quote.extend_from_slice(body);
+
+    let attestation_key = attestation_key();
+    let attestation_key_raw = raw_public_key(&attestation_key);
+    let qe_auth_data = [0x5A; 32];
+
+    let mut report_data = [0u8; 64];
+    report_data[..32].copy_from_slice(&sha256(&[&attestation_key_raw, &qe_auth_data]));
+    let qe_report = qe_report(&report_data);
+
+    let mut qe_certification = qe_report.to_vec();
+    qe_certification.extend_from_slice(&raw_signature(&pki.pck_key, &qe_report));
+    qe_certification.extend_from_slice(&(qe_auth_data.len() as u16).to_le_bytes());
+    qe_certification.extend_from_slice(&qe_auth_data);
+    qe_certification.extend_from_slice(&cert_type.to_le_bytes());
+    qe_certification.extend_from_slice(&(cert_data.len() as u32).to_le_bytes());
+    qe_certification.extend_from_slice(cert_data);
+
+    let mut signature = raw_signature(&attestation_key, &quote).to_vec();
+    signature.extend_from_slice(&attestation_key_raw);
+    if version == QUOTE_VERSION_3 {
+        signature.extend_from_slice(&qe_certification);
+    } else {
+        signature.extend_from_slice(&cert_data_type::QE_REPORT_CERT.to_le_bytes());
+        signature.extend_from_slice(&(qe_certification.len() as u32).to_le_bytes());
+        signature.extend_from_slice(&qe_certification);
+    }
+
+    quote.extend_from_slice(&(signature.len() as u32).to_le_bytes());
+    quote.extend_from_slice(&signature);
+    quote
+}
+
+/// A signed SGX v3 quote.
+pub fn sgx_quote(pki: &TestPki) -> Vec<u8> {
+    build_quote(
+        pki,
+        QUOTE_VERSION_3,
+        tee_type::SGX,
+        None,
+        &sgx_report_body(),
+    )
+}
+
+/// A signed TDX v4 quote.
+pub fn tdx_quote(pki: &TestPki) -> Vec<u8> {
+    build_quote(
+        pki,
+        crate::QUOTE_VERSION_4,
+        tee_type::TDX,
+        None,
+        &td_report_body(),
+    )
+}
+
+/// 2025-03-01T00:00:00Z, when the collateral fixtures are all current.
+pub fn verification_time() -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::from_timestamp(1_740_787_200, 0).unwrap()
+}
+
+/// Collateral for quotes built by `pki`: the fixture TCB Info (v2 for SGX,
+/// v3 for TDX) moved to the test FMSPC and re-signed, the fixture QE
+/// Identity, and empty CRLs.
+pub fn quote_collateral(pki: &TestPki, tee: u32) -> QuoteCollateral {
+    let tcb_info = if tee == tee_type::TDX {
+        include_str!("primitives/data/tcb_info_v3.json").replace("90c06f000000", "00906ea10000")
+    } else {
+        include_str!("primitives/data/tcb_info_v2.json").replace("00606a000000", "00906ea10000")
+    };
+    let mut tcb_info = pki.sign_collateral(&tcb_info, "tcbInfo").into_bytes();
+    tcb_info.push(0);
+    let tcb_chain = [to_pem(&pki.tcb_signing_cert), pki.root_pem()].concat();
+    let qe_identity = include_str!("primitives/data/enclave_identity_v2.json");
+    let qe_identity = if tee == tee_type::TDX {
+        qe_identity.replacen("\"id\": \"QE\"", "\"id\": \"TD_QE\"", 1)
+    } else {
+        qe_identity.to_string()
+    };
+
+    QuoteCollateral {
+        major_version: 3,
+        minor_version: 0,
+        tee_type: tee,
+        pck_crl_issuer_chain: [to_pem(&pki.intermediate_cert), pki.root_pem()]
+            .concat()
+            .into_bytes(),
+        root_ca_crl: [
+            hex::encode(pki.root_crl(&[]).to_der().unwrap()).as_bytes(),
+            &[0],
+        ]
+        .concat(),
+        pck_crl: pki.pck_crl(&[]).to_der().unwrap(),
+        tcb_info_issuer_chain: tcb_chain.clone().into_bytes(),
+        tcb_info,
+        qe_identity_issuer_chain: tcb_chain.into_bytes(),
+        qe_identity: pki
+            .sign_collateral(&qe_identity, "enclaveIdentity")
+            .into_bytes(),
+    }
+}
+
+/// The TCG event types of the synthetic logs; `tee-attest` parses them.
+mod event_type {
+    pub const NO_ACTION: u32 = 0x3;
+    pub const S_CRTM_VERSION: u32 = 0x8;
+    pub const NONHOST_INFO: u32 = 0x11;
+}
+
+/// Builds crypto-agile event logs with SHA-1 and SHA-256 banks.
+pub struct EventLogBuilder(Vec<u8>);
+
+impl Default for EventLogBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventLogBuilder {
+    pub fn new() -> Self {
+        let mut spec_id = b"Spec ID Event03\0".to_vec();
+        spec_id.extend_from_slice(&[0, 0, 0, 0, 0, 2, 0, 2]);
+        spec_id.extend_from_slice(&2u32.to_le_bytes());
+        spec_id.extend_from_slice(&[0x04, 0, 20, 0, 0x0b, 0, 32, 0]);
+        spec_id.push(0);
+
+        let mut log = Vec::new();
+        log.extend_from_slice(&0u32.to_le_bytes());
+        log.extend_from_slice(&event_type::NO_ACTION.to_le_bytes());
+        log.extend_from_slice(&[0; 20]);
+        log.extend_from_slice(&(spec_id.len() as u32).to_le_bytes());
+        log.extend_from_slice(&spec_id);
+        Self(log)
+    }
+
+    /// Add an event whose digests are those of `data`.
+    pub fn event(mut self, pcr_index: u32, event_type: u32, data: &[u8]) -> Self {
+        self.0.extend_from_slice(&pcr_index.to_le_bytes());
+        self.0.extend_from_slice(&event_type.to_le_bytes());
+        self.0.extend_from_slice(&2u32.to_le_bytes());
+        self.0.extend_from_slice(&0x04u16.to_le_bytes());
+        self.0.extend_from_slice(&[0; 20]);
+        self.0.extend_from_slice(&0x0bu16.to_le_bytes());
+        self.0.extend_from_slice(&sha256(&[data]));
+        self.0.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        self.0.extend_from_slice(data);
+        self
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+/// The event log of a GCE TDX VM, reduced to the GCE specific events and a
+/// PCR 7 event.
+pub fn gce_tdx_log() -> Vec<u8> {
+    let version: Vec<u8> = "GCE Virtual Firmware v2\0"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    EventLogBuilder::new()
+        .event(0, event_type::NO_ACTION, b"StartupLocality\0\x03")
+        .event(0, event_type::S_CRTM_VERSION, &version)
+        .event(
+            0,
+            event_type::NONHOST_INFO,
+            b"GCE NonHostInfo\0\x03\0\0\0\0\0\0\0",
+        )
+        .event(7, 0x80000001, b"SecureBoot")
+        .build()
+}
+
+/// A TPMS_ATTEST quoting the SHA-256 values `pcrs` with `nonce`.
+pub fn tpm_attest(nonce: &[u8], pcrs: &BTreeMap<u32, [u8; 32]>) -> Vec<u8> {
+    let mut attest = Vec::new();
+    attest.extend_from_slice(&TPM_GENERATED_VALUE.to_be_bytes());
+    attest.extend_from_slice(&TPM_ST_ATTEST_QUOTE.to_be_bytes());
+    attest.extend_from_slice(&[0, 2, 0, 0x0b]);
+    attest.extend_from_slice(&(nonce.len() as u16).to_be_bytes());
+    attest.extend_from_slice(nonce);
+    attest.extend_from_slice(&[0; 17]);
+    attest.extend_from_slice(&1u64.to_be_bytes());
+
+    let mut bitmap = [0u8; 3];
+    for pcr in pcrs.keys() {
+        bitmap[*pcr as usize / 8] |= 1 << (pcr % 8);
+    }
+    attest.extend_from_slice(&1u32.to_be_bytes());
+    attest.extend_from_slice(&0x0bu16.to_be_bytes());
+    attest.push(3);
+    attest.extend_from_slice(&bitmap);
+    let values: Vec<&[u8]> = pcrs.values().map(|value| value.as_slice()).collect();
+    attest.extend_from_slice(&32u16.to_be_bytes());
+    attest.extend_from_slice(&sha256(&values));
+    attest
+}
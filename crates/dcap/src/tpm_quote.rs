@@ -0,0 +1,218 @@
+//! TPM2 quotes: a TPMS_ATTEST over PCR values, signed by an attestation key.
+//! Certifications of objects by such a key share the same envelope.
+
+use std::collections::BTreeMap;
+
+use sha2::{Digest, Sha256};
+use tss_serde::TssReader;
+use x509_cert::Certificate;
+
+use crate::crypto::{verify_raw_signature, verifying_key_from_certificate};
+
+/// `TPM_GENERATED_VALUE`, the magic of structures the TPM signs.
+pub const TPM_GENERATED_VALUE: u32 = 0xff544347;
+
+/// `TPM_ST_ATTEST_QUOTE`.
+pub const TPM_ST_ATTEST_QUOTE: u16 = 0x8018;
+
+/// `TPM_ST_ATTEST_CERTIFY`.
+pub const TPM_ST_ATTEST_CERTIFY: u16 = 0x8017;
+
+const TPM_ALG_SHA256: u16 = 0x000B;
+
+/// A TPMS_ATTEST of a quote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpmAttest {
+    pub qualified_signer: Vec<u8>,
+    /// The qualifying data of the quote, i.e. the verifier's nonce.
+    pub extra_data: Vec<u8>,
+    pub clock: u64,
+    pub reset_count: u32,
+    pub restart_count: u32,
+    pub safe: bool,
+    pub firmware_version: u64,
+    /// The quoted PCRs of every bank, by hash algorithm.
+    pub pcr_selection: Vec<(u16, Vec<u32>)>,
+    /// Digest of the concatenated values of the quoted PCRs.
+    pub pcr_digest: Vec<u8>,
+}
+
+impl TpmAttest {
+    pub fn parse(bytes: &[u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+        if reader.read_u32()? != TPM_GENERATED_VALUE {
+            eyre::bail!("attestation was not generated by a TPM");
+        }
+        if reader.read_u16()? != TPM_ST_ATTEST_QUOTE {
+            eyre::bail!("attestation is not a quote");
+        }
+        let qualified_signer = read_tpm2b(&mut reader)?;
+        let extra_data = read_tpm2b(&mut reader)?;
+        let clock = reader.read_u64()?;
+        let reset_count = reader.read_u32()?;
+        let restart_count = reader.read_u32()?;
+        let safe = reader.read_u8()? != 0;
+        let firmware_version = reader.read_u64()?;
+
+        let banks = reader.read_u32()?;
+        let mut pcr_selection = Vec::new();
+        for _ in 0..banks {
+            let hash = reader.read_u16()?;
+            let size = reader.read_u8()?;
+            let bitmap = reader.read_bytes(size as usize)?;
+            let pcrs = (0..bitmap.len() * 8)
+                .filter(|pcr| bitmap[pcr / 8] & (1 << (pcr % 8)) != 0)
+                .map(|pcr| pcr as u32)
+                .collect();
+            pcr_selection.push((hash, pcrs));
+        }
+        let pcr_digest = read_tpm2b(&mut reader)?;
+        if reader.remaining() != 0 {
+            eyre::bail!("trailing bytes after TPMS_ATTEST");
+        }
+        Ok(Self {
+            qualified_signer,
+            extra_data,
+            clock,
+            reset_count,
+            restart_count,
+            safe,
+            firmware_version,
+            pcr_selection,
+            pcr_digest,
+        })
+    }
+
+    /// Check that the quote covers exactly the SHA-256 values `pcrs`.
+    pub fn verify_pcrs(&self, pcrs: &BTreeMap<u32, [u8; 32]>) -> eyre::Result<()> {
+        let [(TPM_ALG_SHA256, selected)] = self.pcr_selection.as_slice() else {
+            eyre::bail!("quote does not cover just the SHA-256 bank");
+        };
+        if !selected.iter().eq(pcrs.keys()) {
+            eyre::bail!("quote covers PCRs {:?}", selected);
+        }
+        let mut hasher = Sha256::new();
+        for value in pcrs.values() {
+            hasher.update(value);
+        }
+        if hasher.finalize()[..] != self.pcr_digest[..] {
+            eyre::bail!("PCR values do not match the quoted digest");
+        }
+        Ok(())
+    }
+}
+
+/// Verify the ECDSA P-256 `signature` (raw `r || s`) of `attest` by the
+/// attestation key certified by `ak_cert`, and parse it.
+///
+/// Whether `ak_cert` belongs to a genuine TPM is up to the caller.
+pub fn verify_tpm_quote(
+    attest: &[u8],
+    signature: &[u8; 64],
+    ak_cert: &Certificate,
+) -> eyre::Result<TpmAttest> {
+    let key = verifying_key_from_certificate(ak_cert)?;
+    verify_raw_signature(&key, attest, signature)
+        .map_err(|err| err.wrap_err("TPM quote signature is invalid"))?;
+    TpmAttest::parse(attest)
+}
+
+/// A TPMS_ATTEST of TPM2_Certify: the signing key vouches that the TPM
+/// holds the object of that name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TpmCertifyInfo {
+    pub qualified_signer: Vec<u8>,
+    /// The qualifying data of the certification, i.e. the verifier's nonce.
+    pub extra_data: Vec<u8>,
+    /// The name of the certified object, see [`tpm_object_name`].
+    pub name: Vec<u8>,
+    pub qualified_name: Vec<u8>,
+}
+
+impl TpmCertifyInfo {
+    pub fn parse(bytes: &[u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+        if reader.read_u32()? != TPM_GENERATED_VALUE {
+            eyre::bail!("attestation was not generated by a TPM");
+        }
+        if reader.read_u16()? != TPM_ST_ATTEST_CERTIFY {
+            eyre::bail!("attestation is not a certification");
+        }
+        let qualified_signer = read_tpm2b(&mut reader)?;
+        let extra_data = read_tpm2b(&mut reader)?;
+        // TPMS_CLOCK_INFO and the firmware version.
+        reader.skip(17 + 8)?;
+        let name = read_tpm2b(&mut reader)?;
+        let qualified_name = read_tpm2b(&mut reader)?;
+        if reader.remaining() != 0 {
+            eyre::bail!("trailing bytes after TPMS_ATTEST");
+        }
+        Ok(Self {
+            qualified_signer,
+            extra_data,
+            name,
+            qualified_name,
+        })
+    }
+}
+
+/// Verify the ECDSA P-256 `signature` (raw `r || s`) of the certification
+/// `attest` by the attestation key certified by `ak_cert`, and parse it.
+pub fn verify_tpm_certify(
+    attest: &[u8],
+    signature: &[u8; 64],
+    ak_cert: &Certificate,
+) -> eyre::Result<TpmCertifyInfo> {
+    let key = verifying_key_from_certificate(ak_cert)?;
+    verify_raw_signature(&key, attest, signature)
+        .map_err(|err| err.wrap_err("TPM certification signature is invalid"))?;
+    TpmCertifyInfo::parse(attest)
+}
+
+/// The name of the object of the TPMT_PUBLIC `public`: its SHA-256 name
+/// algorithm followed by the digest of `public`.
+pub fn tpm_object_name(public: &[u8]) -> eyre::Result<Vec<u8>> {
+    let name_alg = public
+        .get(2..4)
+        .ok_or_else(|| eyre::eyre!("truncated TPMT_PUBLIC"))?;
+    if name_alg != TPM_ALG_SHA256.to_be_bytes() {
+        eyre::bail!("object name algorithm is not SHA-256");
+    }
+    Ok([name_alg, &Sha256::digest(public)[..]].concat())
+}
+
+fn read_tpm2b(reader: &mut TssReader) -> eyre::Result<Vec<u8>> {
+    let size = reader.read_u16()?;
+    Ok(reader.read_bytes(size as usize)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{tpm_attest, TestPki};
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::Signature;
+
+    #[test]
+    fn test_verify_tpm_quote() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let pcrs = BTreeMap::from([(0, [1u8; 32]), (7, [2u8; 32])]);
+        let attest = tpm_attest(b"nonce", &pcrs);
+        let signature: Signature = pki.pck_key.sign(&attest);
+        let signature = signature.to_bytes().into();
+
+        let parsed = verify_tpm_quote(&attest, &signature, &pki.pck_cert)?;
+        assert_eq!(parsed.extra_data, b"nonce");
+        assert_eq!(parsed.pcr_selection, vec![(TPM_ALG_SHA256, vec![0, 7])]);
+        parsed.verify_pcrs(&pcrs)?;
+
+        let mut other = pcrs.clone();
+        other.insert(7, [3; 32]);
+        assert!(parsed.verify_pcrs(&other).is_err());
+        other.remove(&7);
+        assert!(parsed.verify_pcrs(&other).is_err());
+        assert!(verify_tpm_quote(&attest, &signature, &pki.root_cert).is_err());
+        assert!(TpmAttest::parse(&attest[1..]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+//! Blocking variant of [`TsmReport`](super::TsmReport).
+
+use std::path::PathBuf;
+
+/// Requests TDX quotes from the kernel through configfs-tsm, blocking.
+#[derive(Debug, Clone, Default)]
+pub struct TsmReport {
+    tsm: super::TsmReport,
+}
+
+impl TsmReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the configfs-tsm report directory at `root`, e.g. when configfs is
+    /// mounted elsewhere.
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self {
+            tsm: super::TsmReport::with_root(root),
+        }
+    }
+
+    /// Whether the kernel exposes configfs-tsm reports.
+    pub fn is_available(&self) -> bool {
+        self.tsm.is_available()
+    }
+
+    /// Get a TDX quote whose report data is `report_data`.
+    pub fn get_quote(&self, report_data: &[u8; 64]) -> eyre::Result<Vec<u8>> {
+        self.tsm.request(report_data)
+    }
+}
@@ -0,0 +1,152 @@
+//! TDX quote generation through the Linux configfs-tsm report interface.
+//!
+//! Each request gets its own directory under `/sys/kernel/config/tsm/report`:
+//! writing `inblob` asks the kernel for a quote over the report data, which
+//! is then read back from `outblob`. The async client does this on the
+//! blocking pool of tokio.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Where configfs-tsm report entries are created.
+pub const TSM_REPORT_PATH: &str = "/sys/kernel/config/tsm/report";
+
+/// The configfs-tsm provider of TDX guests.
+pub const TDX_GUEST_PROVIDER: &str = "tdx_guest";
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+static NEXT_ENTRY: AtomicU64 = AtomicU64::new(0);
+
+/// Requests TDX quotes from the kernel through configfs-tsm.
+#[derive(Debug, Clone)]
+pub struct TsmReport {
+    root: PathBuf,
+}
+
+impl Default for TsmReport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TsmReport {
+    pub fn new() -> Self {
+        Self::with_root(TSM_REPORT_PATH)
+    }
+
+    /// Use the configfs-tsm report directory at `root`, e.g. when configfs is
+    /// mounted elsewhere.
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Whether the kernel exposes configfs-tsm reports.
+    pub fn is_available(&self) -> bool {
+        self.root.is_dir()
+    }
+
+    /// Get a TDX quote whose report data is `report_data`.
+    pub async fn get_quote(&self, report_data: &[u8; 64]) -> eyre::Result<Vec<u8>> {
+        let tsm = self.clone();
+        let report_data = *report_data;
+        tokio::task::spawn_blocking(move || tsm.request(&report_data)).await?
+    }
+
+    fn request(&self, report_data: &[u8; 64]) -> eyre::Result<Vec<u8>> {
+        let entry = self.root.join(format!(
+            "tee-ware-{}-{}",
+            std::process::id(),
+            NEXT_ENTRY.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir(&entry).map_err(|err| {
+            eyre::eyre!(
+                "cannot create configfs-tsm entry {}: {}",
+                entry.display(),
+                err
+            )
+        })?;
+        let quote = request_quote(&entry, report_data);
+        // configfs entries are removed with rmdir, their attributes go with
+        // them.
+        let _ = std::fs::remove_dir(&entry);
+        quote
+    }
+}
+
+/// Request a quote through an existing report entry.
+fn request_quote(entry: &Path, report_data: &[u8; 64]) -> eyre::Result<Vec<u8>> {
+    let provider = std::fs::read_to_string(entry.join("provider"))?;
+    if provider.trim() != TDX_GUEST_PROVIDER {
+        eyre::bail!(
+            "configfs-tsm provider is {}, expected {}",
+            provider.trim(),
+            TDX_GUEST_PROVIDER
+        );
+    }
+
+    std::fs::write(entry.join("inblob"), report_data)?;
+    let generation = read_generation(entry)?;
+    let quote = std::fs::read(entry.join("outblob"))?;
+    // Another writer to the same entry between our write and read would
+    // have bumped the generation.
+    if read_generation(entry)? != generation {
+        eyre::bail!(
+            "configfs-tsm entry {} was written concurrently",
+            entry.display()
+        );
+    }
+    if quote.is_empty() {
+        eyre::bail!("configfs-tsm returned an empty quote");
+    }
+    Ok(quote)
+}
+
+fn read_generation(entry: &Path) -> eyre::Result<u64> {
+    Ok(std::fs::read_to_string(entry.join("generation"))?
+        .trim()
+        .parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An entry as the kernel would create it, with `outblob` prepared.
+    fn fake_entry(dir: &Path, provider: &str) -> PathBuf {
+        let entry = dir.join("entry");
+        std::fs::create_dir(&entry).unwrap();
+        std::fs::write(entry.join("provider"), format!("{}\n", provider)).unwrap();
+        std::fs::write(entry.join("generation"), "1\n").unwrap();
+        std::fs::write(entry.join("outblob"), [4, 0, 2, 0]).unwrap();
+        entry
+    }
+
+    #[test]
+    fn test_request_quote() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let entry = fake_entry(dir.path(), TDX_GUEST_PROVIDER);
+        assert_eq!(request_quote(&entry, &[0xab; 64])?, [4, 0, 2, 0]);
+        assert_eq!(std::fs::read(entry.join("inblob"))?, [0xab; 64]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_other_provider() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let entry = fake_entry(dir.path(), "sev_guest");
+        assert!(request_quote(&entry, &[0; 64]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unavailable() {
+        let tsm = TsmReport::with_root("/nonexistent/tsm/report");
+        assert!(!tsm.is_available());
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert!(runtime.block_on(tsm.get_quote(&[0; 64])).is_err());
+    }
+}
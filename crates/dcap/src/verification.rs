@@ -0,0 +1,867 @@
+use chrono::{DateTime, Utc};
+use tee_observe::{observe_with, Operation};
+use x509_cert::Certificate;
+
+use crate::cert_chain::check_tcb_signer;
+use crate::primitives::identity::EnclaveIdentityId;
+use crate::primitives::normalized::{
+    NormalizedEnclaveIdentity, NormalizedQeTcbLevel, NormalizedTcbInfo, NormalizedTcbLevel,
+};
+use crate::primitives::tcb_info::{TcbInfoId, TcbStatus, TdxModuleStatus};
+use crate::supplemental::SupplementalInputs;
+use crate::{
+    check_revocation, intel_sgx_root_ca, tee_type, validate_certificate_chain, CertificationData,
+    CollateralHashes, Crl, ErrorKind, PckChain, PckTcb, Quote, QuoteCollateral, SupplementalData,
+    TcbOutOfDate, TdReportBody, TrustAnchors, VerificationError, VerificationJournal,
+};
+
+/// Optional outputs of [`verify_quote_with`].
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    /// Populate [`VerificationResult::supplemental_data`].
+    pub supplemental_data: bool,
+    /// Populate [`VerificationResult::journal`].
+    pub journal: bool,
+    /// PCK chain to verify quotes with that do not embed theirs, e.g. one
+    /// fetched from the PCS with a [`PckCertQuery`](crate::PckCertQuery).
+    pub pck_chain: Option<PckChain>,
+}
+
+/// The outcome of a successful quote verification.
+#[derive(Debug, Clone)]
+pub struct VerificationResult {
+    pub quote: Quote,
+    /// The TCB status of the platform, combined with the QE status and, for
+    /// TDX quotes, the TDX module status.
+    pub status: TcbStatus,
+    /// Advisories of the matched TCB levels.
+    pub advisory_ids: Vec<String>,
+    pub fmspc: [u8; 6],
+    /// Date of the matched platform TCB level.
+    pub tcb_date: DateTime<Utc>,
+    /// Outcome of the TDX module evaluation, for TDX quotes.
+    pub tdx_module: Option<TdxModuleStatus>,
+    /// Status of the quoting enclave's TCB level in the QE Identity.
+    pub qe_status: TcbStatus,
+    /// Issue date of the older of the TCB Info and QE Identity used.
+    pub collateral_issue_date: DateTime<Utc>,
+    /// QVL-compatible supplemental data, if requested.
+    pub supplemental_data: Option<SupplementalData>,
+    /// Canonical journal of the outcome, if requested.
+    pub journal: Option<VerificationJournal>,
+}
+
+impl VerificationResult {
+    /// Fail with [`TcbOutOfDate`] unless the platform is up to date, for
+    /// callers that do not appraise results with a [`Policy`](crate::Policy).
+    pub fn require_up_to_date(&self) -> eyre::Result<()> {
+        if self.status != TcbStatus::UpToDate {
+            return Err(TcbOutOfDate {
+                status: self.status,
+                advisory_ids: self.advisory_ids.clone(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Verify a quote against `collateral` at `at`, without network access.
+///
+/// This checks the PCK chain up to the Intel SGX Root CA and against the
+/// CRLs, the signatures and freshness of the TCB Info and QE Identity, the
+/// quote signature chain and the QE identity, then matches the platform TCB
+/// against the TCB Info and the QE ISVSVN against the QE Identity.
+///
+/// Fails if the quote cannot be trusted at all; otherwise the TCB status in
+/// the result is left for the caller's policy. Failures can be classified
+/// with [`ErrorKind::of`].
+pub fn verify_quote(
+    quote: &[u8],
+    collateral: &QuoteCollateral,
+    at: DateTime<Utc>,
+) -> eyre::Result<VerificationResult> {
+    verify_quote_with_root(quote, collateral, &intel_sgx_root_ca(), at)
+}
+
+/// Like [`verify_quote`], trusting `root` instead of the Intel SGX Root CA.
+pub fn verify_quote_with_root(
+    quote: &[u8],
+    collateral: &QuoteCollateral,
+    root: &Certificate,
+    at: DateTime<Utc>,
+) -> eyre::Result<VerificationResult> {
+    verify_quote_with(quote, collateral, root, &VerifyOptions::default(), at)
+}
+
+/// Like [`verify_quote_with_root`], with optional outputs.
+pub fn verify_quote_with(
+    quote: &[u8],
+    collateral: &QuoteCollateral,
+    root: &Certificate,
+    options: &VerifyOptions,
+    at: DateTime<Utc>,
+) -> eyre::Result<VerificationResult> {
+    observe_verification(quote, || {
+        let quote = parse_quote(quote)?;
+        let pck_chain = resolve_pck_chain(&quote, options)?;
+        verify_parsed_quote(quote, pck_chain, collateral, root, options, at)
+    })
+}
+
+/// Like [`verify_quote_with`], trusting every root of `anchors`. The root
+/// the PCK chain is anchored in must also anchor the collateral.
+pub fn verify_quote_with_anchors(
+    quote: &[u8],
+    collateral: &QuoteCollateral,
+    anchors: &TrustAnchors,
+    options: &VerifyOptions,
+    at: DateTime<Utc>,
+) -> eyre::Result<VerificationResult> {
+    observe_verification(quote, || {
+        let quote = parse_quote(quote)?;
+        let pck_chain = resolve_pck_chain(&quote, options)?;
+        let root = anchors
+            .select(pck_chain.certificates(), at)
+            .map_err(|err| {
+                wrap(
+                    err,
+                    ErrorKind::Signature,
+                    "PCK certificate chain is not trusted",
+                )
+            })?;
+        verify_parsed_quote(quote, pck_chain, collateral, root, options, at)
+    })
+}
+
+/// Run `verify` as a [`Operation::QuoteVerify`] of `quote`, with failures
+/// classified by their [`ErrorKind`].
+fn observe_verification(
+    quote: &[u8],
+    verify: impl FnOnce() -> eyre::Result<VerificationResult>,
+) -> eyre::Result<VerificationResult> {
+    // The TEE type follows the version and attestation key type.
+    let tee = match quote.get(4..8) {
+        Some(bytes) => tee_type::name(u32::from_le_bytes(bytes.try_into().unwrap())),
+        None => tee_type::name(u32::MAX),
+    };
+    observe_with(Operation::QuoteVerify, tee, verify, |result| match result {
+        Ok(_) => tee_observe::OK,
+        Err(err) => ErrorKind::of(err).map_or(tee_observe::ERROR, |kind| kind.as_str()),
+    })
+}
+
+/// The PCK chain embedded in `quote`, or the one passed in `options` for
+/// quotes that carry a PCK identity instead.
+fn resolve_pck_chain(quote: &Quote, options: &VerifyOptions) -> eyre::Result<PckChain> {
+    match (
+        &quote.signature.qe_report_certification.certification_data,
+        &options.pck_chain,
+    ) {
+        (CertificationData::PckCertChain(_), _) => PckChain::from_quote(quote)
+            .map_err(|err| wrap(err, ErrorKind::Parse, "PCK certificate chain is malformed")),
+        (_, Some(chain)) => Ok(chain.clone()),
+        (data, None) => Err(failure(
+            ErrorKind::CollateralMissing,
+            format!(
+                "quote does not embed a PCK certificate chain (certification data type {})",
+                data.cert_type()
+            ),
+        )),
+    }
+}
+
+fn parse_quote(quote: &[u8]) -> eyre::Result<Quote> {
+    Quote::parse(quote).map_err(|err| wrap(err, ErrorKind::Parse, "quote is malformed"))
+}
+
+/// Tag `err` with the kind of the failed step.
+fn wrap(err: eyre::Report, kind: ErrorKind, message: &str) -> eyre::Report {
+    err.wrap_err(VerificationError::new(kind, message))
+}
+
+/// A failed step that has no underlying error.
+fn failure(kind: ErrorKind, message: String) -> eyre::Report {
+    VerificationError::new(kind, message).into()
+}
+
+fn verify_parsed_quote(
+    quote: Quote,
+    pck_chain: PckChain,
+    collateral: &QuoteCollateral,
+    root: &Certificate,
+    options: &VerifyOptions,
+    at: DateTime<Utc>,
+) -> eyre::Result<VerificationResult> {
+    if collateral.tee_type != quote.header.tee_type {
+        return Err(failure(
+            ErrorKind::CollateralMissing,
+            format!(
+                "collateral is for TEE type {:#x}, quote is for {:#x}",
+                collateral.tee_type, quote.header.tee_type
+            ),
+        ));
+    }
+
+    let malformed = |err| wrap(err, ErrorKind::Parse, "collateral is malformed");
+    let root_ca_crl = collateral.root_ca_crl().map_err(malformed)?;
+    let pck_crl = collateral.pck_crl().map_err(malformed)?;
+    pck_chain
+        .validate_with_crls(root, &[pck_crl.clone(), root_ca_crl.clone()], at)
+        .map_err(|err| {
+            wrap(
+                err,
+                ErrorKind::Signature,
+                "PCK certificate chain is not trusted",
+            )
+        })?;
+
+    let tcb_info_chain = collateral.tcb_info_issuer_chain().map_err(malformed)?;
+    validate_signing_chain(&tcb_info_chain, root, &root_ca_crl, at).map_err(|err| {
+        wrap(
+            err,
+            ErrorKind::Signature,
+            "TCB Info issuer chain is not trusted",
+        )
+    })?;
+    let tcb_signer = &tcb_info_chain[0];
+    check_tcb_signer(tcb_signer).map_err(|err| {
+        wrap(
+            err,
+            ErrorKind::Signature,
+            "TCB Info is not signed by the TCB signing certificate",
+        )
+    })?;
+    let tcb_info = collateral.signed_tcb_info().map_err(malformed)?;
+    tcb_info
+        .verify(tcb_signer)
+        .map_err(|err| wrap(err, ErrorKind::Signature, "TCB Info signature is invalid"))?;
+    let tcb_info = tcb_info.body;
+    if !tcb_info.is_fresh(at) {
+        return Err(failure(
+            ErrorKind::CollateralExpired,
+            format!("TCB Info expired at {}", tcb_info.next_update.to_rfc3339()),
+        ));
+    }
+
+    let qe_identity_chain = collateral.qe_identity_issuer_chain().map_err(malformed)?;
+    validate_signing_chain(&qe_identity_chain, root, &root_ca_crl, at).map_err(|err| {
+        wrap(
+            err,
+            ErrorKind::Signature,
+            "QE Identity issuer chain is not trusted",
+        )
+    })?;
+    if &qe_identity_chain[0] != tcb_signer {
+        return Err(failure(
+            ErrorKind::Signature,
+            "QE Identity is not signed by the signer of the TCB Info".to_string(),
+        ));
+    }
+    let qe_identity = collateral.signed_qe_identity().map_err(malformed)?;
+    qe_identity.verify(tcb_signer).map_err(|err| {
+        wrap(
+            err,
+            ErrorKind::Signature,
+            "QE Identity signature is invalid",
+        )
+    })?;
+    let qe_identity = qe_identity.body;
+    if !qe_identity.is_fresh(at) {
+        return Err(failure(
+            ErrorKind::CollateralExpired,
+            format!(
+                "QE Identity expired at {}",
+                qe_identity.next_update.to_rfc3339()
+            ),
+        ));
+    }
+
+    let qe_identity = qe_identity.normalize().map_err(malformed)?;
+    let expected_identity = EnclaveIdentityId::quoting_enclave(quote.header.tee_type);
+    if qe_identity.id != expected_identity {
+        return Err(failure(
+            ErrorKind::CollateralMissing,
+            format!(
+                "QE Identity is for {}, expected {}",
+                qe_identity.id, expected_identity
+            ),
+        ));
+    }
+
+    quote
+        .verify_signature(&pck_chain)
+        .map_err(|err| wrap(err, ErrorKind::Signature, "quote signature is invalid"))?;
+    let qe_level = check_qe_identity(&quote, &qe_identity)?;
+
+    let extensions = pck_chain
+        .sgx_extensions()
+        .map_err(|err| wrap(err, ErrorKind::Parse, "PCK certificate is malformed"))?;
+    let tcb_info = tcb_info.normalize().map_err(malformed)?;
+    if tcb_info.fmspc != extensions.fmspc {
+        return Err(failure(
+            ErrorKind::CollateralMissing,
+            format!(
+                "TCB Info is for FMSPC {}, PCK certificate for {}",
+                hex::encode(tcb_info.fmspc),
+                hex::encode(extensions.fmspc)
+            ),
+        ));
+    }
+    if tcb_info.pce_id != extensions.pce_id {
+        return Err(failure(
+            ErrorKind::CollateralMissing,
+            "TCB Info PCE ID does not match the PCK certificate".to_string(),
+        ));
+    }
+
+    let td_report = quote.body.as_td_report();
+    let expected_id = match quote.header.tee_type {
+        tee_type::TDX => TcbInfoId::Tdx,
+        _ => TcbInfoId::Sgx,
+    };
+    if tcb_info.id != expected_id {
+        return Err(failure(
+            ErrorKind::CollateralMissing,
+            format!("TCB Info is not for {:?} platforms", expected_id),
+        ));
+    }
+
+    let level = match_tcb_level(&tcb_info, &extensions.tcb, td_report)?;
+    let mut status = level.tcb_status;
+    let mut advisory_ids = level.advisory_ids.clone();
+
+    let tdx_module = match td_report {
+        Some(report) => {
+            let module = tcb_info.evaluate_tdx_module(report).map_err(|err| {
+                wrap(
+                    err,
+                    ErrorKind::Signature,
+                    "TDX module does not match the TCB Info",
+                )
+            })?;
+            status = converge_tcb_status(status, module.status);
+            merge_advisories(&mut advisory_ids, &module.advisory_ids);
+            Some(module)
+        }
+        None => None,
+    };
+
+    let collateral_issue_date = tcb_info.issue_date.min(qe_identity.issue_date);
+    let qe_status = qe_level.tcb_status;
+    status = converge_tcb_status(status, qe_status);
+    merge_advisories(&mut advisory_ids, &qe_level.advisory_ids);
+
+    let supplemental_data = if options.supplemental_data {
+        let certificates = pck_chain
+            .certificates()
+            .iter()
+            .chain(&tcb_info_chain)
+            .chain(&qe_identity_chain)
+            .collect();
+        Some(SupplementalData::collect(SupplementalInputs {
+            certificates,
+            root,
+            pck_crl: &pck_crl,
+            root_ca_crl: &root_ca_crl,
+            tcb_info: &tcb_info,
+            qe_identity: &qe_identity,
+            extensions: &extensions,
+            tee_type: quote.header.tee_type,
+            tcb_level_date: level.tcb_date,
+            advisory_ids: &advisory_ids,
+        })?)
+    } else {
+        None
+    };
+
+    let journal = if options.journal {
+        Some(VerificationJournal::new(
+            &quote,
+            status,
+            qe_status,
+            extensions.fmspc,
+            level.tcb_date,
+            collateral_issue_date,
+            CollateralHashes::new(collateral, root)?,
+            &advisory_ids,
+            at,
+        ))
+    } else {
+        None
+    };
+
+    Ok(VerificationResult {
+        status,
+        advisory_ids,
+        fmspc: extensions.fmspc,
+        tcb_date: level.tcb_date,
+        tdx_module,
+        qe_status,
+        collateral_issue_date,
+        supplemental_data,
+        journal,
+        quote,
+    })
+}
+
+/// Validate the issuer chain of signed collateral, which ends at the root CA.
+fn validate_signing_chain(
+    chain: &[Certificate],
+    root: &Certificate,
+    root_ca_crl: &Crl,
+    at: DateTime<Utc>,
+) -> eyre::Result<()> {
+    validate_certificate_chain(chain, root, at)?;
+    check_revocation(chain, root, std::slice::from_ref(root_ca_crl), at)
+}
+
+/// Check that the quote was produced by the quoting enclave described by
+/// `identity`, and select the TCB level of that enclave.
+fn check_qe_identity<'a>(
+    quote: &Quote,
+    identity: &'a NormalizedEnclaveIdentity,
+) -> eyre::Result<&'a NormalizedQeTcbLevel> {
+    identity
+        .evaluate(&quote.signature.qe_report_certification.qe_report)
+        .map_err(|err| {
+            wrap(
+                err,
+                ErrorKind::Signature,
+                "quoting enclave does not match the QE Identity",
+            )
+        })
+}
+
+/// Select the first TCB level the platform is at or above.
+///
+/// SGX components and PCESVN come from the PCK certificate; for TDX the TDX
+/// components come from `TEE_TCB_SVN`, whose first two bytes are covered by
+/// the TDX module identity when the module major version is not 0.
+fn match_tcb_level<'a>(
+    tcb_info: &'a NormalizedTcbInfo,
+    pck_tcb: &PckTcb,
+    td_report: Option<&TdReportBody>,
+) -> eyre::Result<&'a NormalizedTcbLevel> {
+    tcb_info
+        .tcb_levels
+        .iter()
+        .find(|level| {
+            let sgx_matches = pck_tcb
+                .sgx_tcb_comp_svns
+                .iter()
+                .zip(level.sgx_tcb_comp_svns)
+                .all(|(platform, level)| *platform >= level);
+            if !sgx_matches || u32::from(pck_tcb.pce_svn) < level.pcesvn {
+                return false;
+            }
+
+            match td_report {
+                Some(report) => {
+                    let Some(tdx_svns) = level.tdx_tcb_comp_svns else {
+                        return false;
+                    };
+                    let skip = if report.tee_tcb_svn[1] > 0 { 2 } else { 0 };
+                    report
+                        .tee_tcb_svn
+                        .iter()
+                        .zip(tdx_svns)
+                        .skip(skip)
+                        .all(|(platform, level)| *platform >= level)
+                }
+                None => true,
+            }
+        })
+        .ok_or_else(|| {
+            failure(
+                ErrorKind::TcbOutOfDate,
+                "platform TCB is not supported by the TCB Info".to_string(),
+            )
+        })
+}
+
+/// Combine the platform status with the status of the TDX module or the
+/// quoting enclave, which can only make it worse.
+fn converge_tcb_status(platform: TcbStatus, other: TcbStatus) -> TcbStatus {
+    match other {
+        TcbStatus::Revoked => TcbStatus::Revoked,
+        TcbStatus::OutOfDate => match platform {
+            TcbStatus::Revoked => TcbStatus::Revoked,
+            TcbStatus::ConfigurationNeeded
+            | TcbStatus::ConfigurationAndSWHardeningNeeded
+            | TcbStatus::OutOfDateConfigurationNeeded => TcbStatus::OutOfDateConfigurationNeeded,
+            _ => TcbStatus::OutOfDate,
+        },
+        _ => platform,
+    }
+}
+
+fn merge_advisories(advisory_ids: &mut Vec<String>, more: &[String]) {
+    for id in more {
+        if !advisory_ids.contains(id) {
+            advisory_ids.push(id.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        build_quote_with_certification, quote_collateral, sgx_quote, sgx_report_body, tdx_quote,
+        verification_time, TestPki,
+    };
+    use crate::{cert_data_type, PckCertQuery};
+
+    #[test]
+    fn test_verify_sgx_quote() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::SGX);
+        let result = verify_quote_with_root(
+            &sgx_quote(&pki),
+            &collateral,
+            &pki.root_cert,
+            verification_time(),
+        )?;
+        assert_eq!(result.status, TcbStatus::SWHardeningNeeded);
+        assert_eq!(result.qe_status, TcbStatus::UpToDate);
+        assert_eq!(result.fmspc, pki.sgx_extensions.fmspc);
+        assert!(result.tdx_module.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_tdx_quote() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::TDX);
+        let result = verify_quote_with_root(
+            &tdx_quote(&pki),
+            &collateral,
+            &pki.root_cert,
+            verification_time(),
+        )?;
+        assert_eq!(result.status, TcbStatus::UpToDate);
+        assert_eq!(result.tdx_module.unwrap().status, TcbStatus::UpToDate);
+        assert!(result.quote.body.as_td_report().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_supplemental_data() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::SGX);
+        let options = VerifyOptions {
+            supplemental_data: true,
+            ..VerifyOptions::default()
+        };
+        let result = verify_quote_with(
+            &sgx_quote(&pki),
+            &collateral,
+            &pki.root_cert,
+            &options,
+            verification_time(),
+        )?;
+
+        let supplemental = result.supplemental_data.unwrap();
+        let tcb_info = collateral.tcb_info()?.tcb_info;
+        let qe_identity = collateral.qe_identity()?.enclave_identity;
+        // The certificates and CRLs are older than the signed documents.
+        assert_eq!(
+            supplemental.earliest_issue_date.timestamp(),
+            crate::test_utils::NOT_BEFORE as i64
+        );
+        assert_eq!(supplemental.latest_issue_date, tcb_info.issue_date);
+        assert_eq!(
+            supplemental.earliest_expiration_date,
+            qe_identity.next_update.min(tcb_info.next_update)
+        );
+        assert_eq!(supplemental.tcb_level_date_tag, result.tcb_date);
+        assert_eq!(supplemental.pck_crl_num, 1);
+        assert_eq!(supplemental.tcb_eval_ref_num, 17);
+        assert_eq!(supplemental.pck_ppid, pki.sgx_extensions.ppid);
+        assert_eq!(supplemental.sgx_type, 1);
+        assert_eq!(supplemental.cached_keys, crate::PckCertFlag::True);
+        assert_eq!(supplemental.dynamic_platform, crate::PckCertFlag::False);
+        assert_eq!(supplemental.sa_list, result.advisory_ids.join(","));
+
+        let result = verify_quote_with_root(
+            &sgx_quote(&pki),
+            &collateral,
+            &pki.root_cert,
+            verification_time(),
+        )?;
+        assert!(result.supplemental_data.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_untrusted_root() {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::SGX);
+        assert!(verify_quote(&sgx_quote(&pki), &collateral, verification_time()).is_err());
+    }
+
+    #[test]
+    fn test_verify_with_anchors() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::SGX);
+        let quote = sgx_quote(&pki);
+        let options = VerifyOptions::default();
+        let at = verification_time();
+        assert!(verify_quote_with_anchors(
+            &quote,
+            &collateral,
+            &TrustAnchors::default(),
+            &options,
+            at
+        )
+        .is_err());
+
+        let anchors = TrustAnchors::default().with_root(pki.root_cert.clone());
+        let result = verify_quote_with_anchors(&quote, &collateral, &anchors, &options, at)?;
+        assert_eq!(result.fmspc, pki.sgx_extensions.fmspc);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_expired_collateral() {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::SGX);
+        let later = verification_time() + chrono::Duration::days(60);
+        let err = verify_quote_with_root(&sgx_quote(&pki), &collateral, &pki.root_cert, later)
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), Some(ErrorKind::CollateralExpired));
+        assert!(ErrorKind::CollateralExpired.is_retryable());
+    }
+
+    #[test]
+    fn test_rejects_revoked_pck() {
+        let pki = TestPki::new();
+        let mut collateral = quote_collateral(&pki, tee_type::SGX);
+        collateral.pck_crl = pki.pck_crl(&[(3, None)]).to_der().unwrap();
+        let err = verify_quote_with_root(
+            &sgx_quote(&pki),
+            &collateral,
+            &pki.root_cert,
+            verification_time(),
+        )
+        .unwrap_err();
+        assert!(err.chain().any(|cause| cause.is::<crate::Revoked>()));
+        assert_eq!(ErrorKind::of(&err), Some(ErrorKind::Revoked));
+    }
+
+    #[test]
+    fn test_error_kinds() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::SGX);
+        let quote = sgx_quote(&pki);
+        let at = verification_time();
+        let kind = |result: eyre::Result<VerificationResult>| ErrorKind::of(&result.unwrap_err());
+
+        let result = verify_quote_with_root(&quote[..100], &collateral, &pki.root_cert, at);
+        assert_eq!(kind(result), Some(ErrorKind::Parse));
+        let result = verify_quote(&quote, &collateral, at);
+        assert_eq!(kind(result), Some(ErrorKind::Signature));
+        let tdx_collateral = quote_collateral(&pki, tee_type::TDX);
+        let result = verify_quote_with_root(&quote, &tdx_collateral, &pki.root_cert, at);
+        assert_eq!(kind(result), Some(ErrorKind::CollateralMissing));
+
+        let result = verify_quote_with_root(&quote, &collateral, &pki.root_cert, at)?;
+        let err = result.require_up_to_date().unwrap_err();
+        assert_eq!(ErrorKind::of(&err), Some(ErrorKind::TcbOutOfDate));
+        let out_of_date = err.downcast_ref::<TcbOutOfDate>().unwrap();
+        assert_eq!(out_of_date.status, TcbStatus::SWHardeningNeeded);
+        assert_eq!(out_of_date.advisory_ids, result.advisory_ids);
+        assert!(ErrorKind::TcbOutOfDate.is_authentic());
+        assert!(!ErrorKind::TcbOutOfDate.is_retryable());
+
+        let err = crate::Policy::default()
+            .evaluate_at(&result, at)
+            .into_result()
+            .unwrap_err();
+        assert_eq!(ErrorKind::of(&err), Some(ErrorKind::PolicyRejected));
+        assert_eq!(ErrorKind::of(&eyre::eyre!("unrelated")), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_quote_with_pck_identity() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::SGX);
+        let mut identity = vec![0xEE; 384];
+        identity.extend_from_slice(&pki.sgx_extensions.tcb.cpu_svn);
+        identity.extend_from_slice(&pki.sgx_extensions.tcb.pce_svn.to_le_bytes());
+        identity.extend_from_slice(&0u16.to_le_bytes());
+        let quote = build_quote_with_certification(
+            &pki,
+            crate::QUOTE_VERSION_3,
+            tee_type::SGX,
+            None,
+            &sgx_report_body(),
+            cert_data_type::PCK_ID_ENCRYPTED_PPID_3072,
+            &identity,
+        );
+
+        let query = PckCertQuery::from_quote(&Quote::parse(&quote)?)?;
+        assert_eq!(query.encrypted_ppid, Some(vec![0xEE; 384]));
+        assert_eq!(query.cpu_svn, pki.sgx_extensions.tcb.cpu_svn);
+        assert_eq!(query.qe_id, Some([0; 16]));
+
+        assert!(
+            verify_quote_with_root(&quote, &collateral, &pki.root_cert, verification_time())
+                .is_err()
+        );
+        let options = VerifyOptions {
+            pck_chain: Some(PckChain::from_leaf(
+                pki.pck_cert.clone(),
+                vec![pki.intermediate_cert.clone(), pki.root_cert.clone()],
+            )?),
+            ..VerifyOptions::default()
+        };
+        let result = verify_quote_with(
+            &quote,
+            &collateral,
+            &pki.root_cert,
+            &options,
+            verification_time(),
+        )?;
+        assert_eq!(result.status, TcbStatus::SWHardeningNeeded);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_collateral_signed_by_pck() {
+        let pki = TestPki::new();
+        let at = verification_time();
+        let quote = sgx_quote(&pki);
+        let tcb_info = include_str!("primitives/data/tcb_info_v2.json")
+            .replace("00606a000000", "00906ea10000");
+        let qe_identity = include_str!("primitives/data/enclave_identity_v2.json");
+
+        let mut collateral = quote_collateral(&pki, tee_type::SGX);
+        collateral.tcb_info = pki
+            .sign_collateral_with(&pki.pck_key, &tcb_info, "tcbInfo")
+            .into_bytes();
+        collateral.tcb_info_issuer_chain = pki.pck_chain_pem().into_bytes();
+        let err = verify_quote_with_root(&quote, &collateral, &pki.root_cert, at).unwrap_err();
+        assert_eq!(ErrorKind::of(&err), Some(ErrorKind::Signature));
+        assert_eq!(
+            err.to_string(),
+            "TCB Info is not signed by the TCB signing certificate"
+        );
+
+        let mut collateral = quote_collateral(&pki, tee_type::SGX);
+        collateral.qe_identity = pki
+            .sign_collateral_with(&pki.pck_key, qe_identity, "enclaveIdentity")
+            .into_bytes();
+        collateral.qe_identity_issuer_chain = pki.pck_chain_pem().into_bytes();
+        let err = verify_quote_with_root(&quote, &collateral, &pki.root_cert, at).unwrap_err();
+        assert_eq!(ErrorKind::of(&err), Some(ErrorKind::Signature));
+        assert_eq!(
+            err.to_string(),
+            "QE Identity is not signed by the signer of the TCB Info"
+        );
+    }
+
+    fn with_qe_identity(pki: &TestPki, collateral: &mut QuoteCollateral, from: &str, to: &str) {
+        let identity = include_str!("primitives/data/enclave_identity_v2.json").replace(from, to);
+        collateral.qe_identity = pki
+            .sign_collateral(&identity, "enclaveIdentity")
+            .into_bytes();
+    }
+
+    #[test]
+    fn test_out_of_date_qe() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let mut collateral = quote_collateral(&pki, tee_type::SGX);
+        with_qe_identity(&pki, &mut collateral, "\"isvsvn\": 8", "\"isvsvn\": 9");
+        let result = verify_quote_with_root(
+            &sgx_quote(&pki),
+            &collateral,
+            &pki.root_cert,
+            verification_time(),
+        )?;
+        assert_eq!(result.qe_status, TcbStatus::OutOfDate);
+        assert_eq!(result.status, TcbStatus::OutOfDate);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_identity_of_other_enclave() {
+        let pki = TestPki::new();
+        let mut collateral = quote_collateral(&pki, tee_type::SGX);
+        with_qe_identity(&pki, &mut collateral, "\"id\": \"QE\"", "\"id\": \"TD_QE\"");
+        let err = verify_quote_with_root(
+            &sgx_quote(&pki),
+            &collateral,
+            &pki.root_cert,
+            verification_time(),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "QE Identity is for TD_QE, expected QE");
+    }
+
+    #[test]
+    fn test_rejects_foreign_qe() {
+        let pki = TestPki::new();
+        let mut collateral = quote_collateral(&pki, tee_type::SGX);
+        with_qe_identity(
+            &pki,
+            &mut collateral,
+            "\"attributes\": \"11",
+            "\"attributes\": \"13",
+        );
+        assert!(verify_quote_with_root(
+            &sgx_quote(&pki),
+            &collateral,
+            &pki.root_cert,
+            verification_time()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_rejects_mismatched_collateral() {
+        let pki = TestPki::new();
+        let collateral = quote_collateral(&pki, tee_type::TDX);
+        assert!(verify_quote_with_root(
+            &sgx_quote(&pki),
+            &collateral,
+            &pki.root_cert,
+            verification_time()
+        )
+        .is_err());
+
+        // TCB Info for another platform.
+        let mut collateral = quote_collateral(&pki, tee_type::SGX);
+        let tcb_info = String::from_utf8(collateral.tcb_info.clone())
+            .unwrap()
+            .replace("00906ea10000", "00606a000000");
+        collateral.tcb_info = pki
+            .sign_collateral(tcb_info.trim_end_matches('\0'), "tcbInfo")
+            .into_bytes();
+        assert!(verify_quote_with_root(
+            &sgx_quote(&pki),
+            &collateral,
+            &pki.root_cert,
+            verification_time()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_converge_tcb_status() {
+        assert_eq!(
+            converge_tcb_status(TcbStatus::UpToDate, TcbStatus::OutOfDate),
+            TcbStatus::OutOfDate
+        );
+        assert_eq!(
+            converge_tcb_status(TcbStatus::ConfigurationNeeded, TcbStatus::OutOfDate),
+            TcbStatus::OutOfDateConfigurationNeeded
+        );
+        assert_eq!(
+            converge_tcb_status(TcbStatus::SWHardeningNeeded, TcbStatus::UpToDate),
+            TcbStatus::SWHardeningNeeded
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use dcap::{ReportData, NONCE_SIZE};
+use tee_attest::gcp::{GcpEvidence, GcpVtpm};
+use tee_config::{Config, TdxTransport};
+use tss_client::{Transport, TssClient};
+
+/// Collects evidence of the local machine.
+pub trait Collector: Send {
+    /// A quote of `pcrs` by the TPM, with a TD quote bound to the nonce and
+    /// the AK on TDX VMs. `None` if the machine has no TPM.
+    fn tpm(&mut self, nonce: &[u8; NONCE_SIZE], pcrs: &[u32]) -> eyre::Result<Option<GcpEvidence>>;
+
+    /// A TD quote over `report_data`. `None` if this is not a TDX guest.
+    fn td_quote(&mut self, report_data: &ReportData) -> eyre::Result<Option<Vec<u8>>>;
+}
+
+/// The vTPM and TD quotes through configfs-tsm or the QGS of the host,
+/// whichever the machine has.
+pub struct LocalCollector {
+    vtpm: Option<GcpVtpm<Box<dyn Transport + Send>>>,
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    tdx: TdxTransport,
+}
+
+impl LocalCollector {
+    /// Open the vTPM of `config`, if any, reading the event log from
+    /// `event_log_path`.
+    pub fn open(config: &Config, event_log_path: Option<&str>) -> eyre::Result<Self> {
+        let vtpm = config.tpm.open()?.map(|transport| {
+            let vtpm = GcpVtpm::new(TssClient::new(transport));
+            match event_log_path {
+                Some(path) => vtpm.with_event_log_path(path),
+                None => vtpm,
+            }
+        });
+        Ok(Self {
+            vtpm,
+            tdx: config.tdx.clone(),
+        })
+    }
+}
+
+impl Collector for LocalCollector {
+    fn tpm(&mut self, nonce: &[u8; NONCE_SIZE], pcrs: &[u32]) -> eyre::Result<Option<GcpEvidence>> {
+        self.vtpm
+            .as_mut()
+            .map(|vtpm| vtpm.attest(nonce, pcrs))
+            .transpose()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn td_quote(&mut self, report_data: &ReportData) -> eyre::Result<Option<Vec<u8>>> {
+        match self.tdx {
+            TdxTransport::Tsm => {
+                let tsm = dcap::tsm::blocking::TsmReport::new();
+                if !tsm.is_available() {
+                    return Ok(None);
+                }
+                Ok(Some(tsm.get_quote(&report_data.0)?))
+            }
+            TdxTransport::Vsock { cid, port } => {
+                let qgs = dcap::qgs::blocking::QgsClient::with_address(cid, port);
+                Ok(Some(qgs.get_quote_for(&report_data.0)?))
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn td_quote(&mut self, _report_data: &ReportData) -> eyre::Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+}
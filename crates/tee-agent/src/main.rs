@@ -0,0 +1,74 @@
+//! Attestation agent serving the evidence of the local machine over HTTP,
+//! so workloads can fetch it over localhost instead of linking the TPM and
+//! DCAP stacks.
+//!
+//! `GET /evidence?nonce=<hex>[&pcrs=0,1,7]` returns a TPM quote of the PCRs
+//! and the event log, with a TD quote bound to the nonce and the AK on TDX
+//! VMs, or only a TD quote bound to the nonce on TDX guests without a TPM.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use clap::Parser;
+use tee_config::{Config, TpmTransport, CONFIG_ENV};
+
+mod collector;
+mod routes;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Configuration file, for the TPM transport and the TD quote source.
+    #[arg(long, env = CONFIG_ENV)]
+    config: Option<PathBuf>,
+    /// Address to listen on. Evidence is unauthenticated, so keep it local.
+    #[arg(long, env = "TEE_AGENT_LISTEN", default_value = "127.0.0.1:8390")]
+    listen: SocketAddr,
+    /// Do not use the TPM, only serve TD quotes.
+    #[arg(long)]
+    no_tpm: bool,
+    /// Path of the firmware event log.
+    #[arg(long)]
+    event_log: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let cli = Cli::parse();
+    let mut config = Config::load_or_default(cli.config.as_deref())?;
+    if cli.no_tpm {
+        config.tpm = TpmTransport::None;
+    }
+    let collector = collector::LocalCollector::open(&config, cli.event_log.as_deref())?;
+    let listener = tokio::net::TcpListener::bind(cli.listen).await?;
+    println!("listening on {}", listener.local_addr()?);
+    axum::serve(listener, routes::router(Box::new(collector)))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_arguments() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+
+        let cli = Cli::parse_from([
+            "tee-agent",
+            "--no-tpm",
+            "--listen",
+            "[::1]:9000",
+            "--config",
+            "tee-ware.toml",
+        ]);
+        assert!(cli.no_tpm);
+        assert_eq!(cli.config.unwrap(), PathBuf::from("tee-ware.toml"));
+        assert_eq!(cli.listen.port(), 9000);
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use dcap::{ReportData, NONCE_SIZE};
+use serde::{Deserialize, Serialize};
+use tee_attest::gcp::DEFAULT_PCRS;
+
+use crate::collector::Collector;
+
+type SharedCollector = Arc<Mutex<Box<dyn Collector>>>;
+
+pub fn router(collector: Box<dyn Collector>) -> Router {
+    Router::new()
+        .route("/evidence", get(evidence))
+        .route("/health", get(|| async { "ok" }))
+        .with_state(Arc::new(Mutex::new(collector)))
+}
+
+#[derive(Debug, Deserialize)]
+struct EvidenceQuery {
+    /// Hex encoded, 32 bytes.
+    nonce: String,
+    /// Comma separated PCR indices, [`DEFAULT_PCRS`] by default.
+    pcrs: Option<String>,
+}
+
+/// The evidence of the machine, byte fields hex encoded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EvidenceResponse {
+    pub nonce: String,
+    pub tpm: Option<TpmEvidence>,
+    /// Bound to the nonce and, with a TPM, the AK certificate by
+    /// [`ReportData::bind`].
+    pub td_quote: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TpmEvidence {
+    pub ak_cert: String,
+    pub attest: String,
+    pub signature: String,
+    pub pcrs: BTreeMap<u32, String>,
+    pub event_log: String,
+}
+
+async fn evidence(
+    State(collector): State<SharedCollector>,
+    Query(query): Query<EvidenceQuery>,
+) -> Result<Json<EvidenceResponse>, Error> {
+    let nonce: [u8; NONCE_SIZE] = hex::decode(&query.nonce)
+        .ok()
+        .and_then(|nonce| nonce.try_into().ok())
+        .ok_or_else(|| Error::BadRequest(format!("nonce must be {} hex bytes", NONCE_SIZE)))?;
+    let pcrs = match &query.pcrs {
+        Some(pcrs) => parse_pcrs(pcrs)?,
+        None => DEFAULT_PCRS.to_vec(),
+    };
+
+    let response = tokio::task::spawn_blocking(move || {
+        let mut collector = collector.lock().unwrap_or_else(|err| err.into_inner());
+        let response = match collector.tpm(&nonce, &pcrs)? {
+            Some(evidence) => EvidenceResponse {
+                nonce: hex::encode(nonce),
+                td_quote: evidence.td_quote.map(hex::encode),
+                tpm: Some(TpmEvidence {
+                    ak_cert: hex::encode(evidence.ak_cert),
+                    attest: hex::encode(evidence.attest),
+                    signature: hex::encode(evidence.signature),
+                    pcrs: evidence
+                        .pcrs
+                        .into_iter()
+                        .map(|(index, value)| (index, hex::encode(value)))
+                        .collect(),
+                    event_log: hex::encode(evidence.event_log),
+                }),
+            },
+            None => EvidenceResponse {
+                nonce: hex::encode(nonce),
+                tpm: None,
+                td_quote: collector
+                    .td_quote(&ReportData::bind(Some(&nonce), None))?
+                    .map(hex::encode),
+            },
+        };
+        eyre::Ok(response)
+    })
+    .await
+    .map_err(|err| Error::Internal(err.to_string()))?
+    .map_err(|err| Error::Internal(format!("{:#}", err)))?;
+
+    if response.tpm.is_none() && response.td_quote.is_none() {
+        return Err(Error::Unavailable);
+    }
+    Ok(Json(response))
+}
+
+fn parse_pcrs(pcrs: &str) -> Result<Vec<u32>, Error> {
+    pcrs.split(',')
+        .map(|pcr| match pcr.trim().parse() {
+            Ok(pcr) if pcr < 24 => Ok(pcr),
+            _ => Err(Error::BadRequest(format!("invalid PCR {}", pcr))),
+        })
+        .collect()
+}
+
+enum Error {
+    BadRequest(String),
+    /// The machine has neither a TPM nor TDX.
+    Unavailable,
+    Internal(String),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        match self {
+            Error::BadRequest(message) => (StatusCode::BAD_REQUEST, message).into_response(),
+            Error::Unavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "no TPM or TDX evidence on this machine",
+            )
+                .into_response(),
+            Error::Internal(message) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, message).into_response()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use tee_attest::gcp::GcpEvidence;
+    use tower::ServiceExt;
+
+    /// Echoes its inputs back as evidence.
+    struct FakeCollector {
+        tpm: bool,
+        tdx: bool,
+    }
+
+    impl Collector for FakeCollector {
+        fn tpm(
+            &mut self,
+            nonce: &[u8; NONCE_SIZE],
+            pcrs: &[u32],
+        ) -> eyre::Result<Option<GcpEvidence>> {
+            if !self.tpm {
+                return Ok(None);
+            }
+            Ok(Some(GcpEvidence {
+                ak_cert: vec![0x30],
+                attest: nonce.to_vec(),
+                signature: [1; 64],
+                pcrs: pcrs.iter().map(|pcr| (*pcr, [0; 32])).collect(),
+                event_log: vec![2],
+                td_quote: self
+                    .tdx
+                    .then(|| ReportData::bind(Some(nonce), Some(&[0x30])).0.to_vec()),
+            }))
+        }
+
+        fn td_quote(&mut self, report_data: &ReportData) -> eyre::Result<Option<Vec<u8>>> {
+            Ok(self.tdx.then(|| report_data.0.to_vec()))
+        }
+    }
+
+    async fn get(collector: FakeCollector, uri: &str) -> (StatusCode, Vec<u8>) {
+        let response = router(Box::new(collector))
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_evidence() -> eyre::Result<()> {
+        let nonce = [7u8; NONCE_SIZE];
+        let uri = format!("/evidence?nonce={}&pcrs=0,7", hex::encode(nonce));
+
+        let tpm = FakeCollector {
+            tpm: true,
+            tdx: true,
+        };
+        let (status, body) = get(tpm, &uri).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: EvidenceResponse = serde_json::from_slice(&body)?;
+        let evidence = response.tpm.unwrap();
+        assert_eq!(evidence.attest, hex::encode(nonce));
+        assert_eq!(evidence.pcrs.keys().copied().collect::<Vec<_>>(), [0, 7]);
+        let td_quote = hex::decode(response.td_quote.unwrap())?;
+        ReportData(td_quote.try_into().unwrap()).verify_binding(Some(&nonce), Some(&[0x30]))?;
+
+        let tdx = FakeCollector {
+            tpm: false,
+            tdx: true,
+        };
+        let (status, body) = get(tdx, &uri).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: EvidenceResponse = serde_json::from_slice(&body)?;
+        assert!(response.tpm.is_none());
+        let td_quote = hex::decode(response.td_quote.unwrap())?;
+        ReportData(td_quote.try_into().unwrap()).verify_binding(Some(&nonce), None)?;
+
+        let none = FakeCollector {
+            tpm: false,
+            tdx: false,
+        };
+        assert_eq!(get(none, &uri).await.0, StatusCode::SERVICE_UNAVAILABLE);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bad_requests() {
+        let collector = || FakeCollector {
+            tpm: true,
+            tdx: false,
+        };
+        for uri in [
+            "/evidence",
+            "/evidence?nonce=abcd",
+            "/evidence?nonce=zz",
+            &format!("/evidence?nonce={}&pcrs=0,24", "00".repeat(32)),
+        ] {
+            assert_eq!(
+                get(collector(), uri).await.0,
+                StatusCode::BAD_REQUEST,
+                "{}",
+                uri
+            );
+        }
+        assert_eq!(get(collector(), "/health").await.0, StatusCode::OK);
+    }
+}
@@ -0,0 +1,331 @@
+//! Signed, versioned bundles of attestation evidence, so evidence can be
+//! archived and verified again later byte for byte.
+//!
+//! A bundle is a CBOR map with integer keys in deterministic encoding,
+//! signed as the payload of a COSE_Sign1 with ES256. Evidence is kept in the
+//! form the platforms produced it; empty fields are left out of the map:
+//!
+//! | Key | Field | Value |
+//! | --- | --- | --- |
+//! | 0 | version | [`BUNDLE_VERSION`] |
+//! | 1 | collected_at | Seconds since the Unix epoch |
+//! | 2 | nonce | Bytes |
+//! | 3 | quotes | Array of SGX and TDX quotes |
+//! | 4 | tpm_quotes | Array of `[attest, signature]` |
+//! | 5 | event_logs | Array of raw event logs |
+//! | 6 | certificates | Array of DER certificates |
+
+use chrono::{DateTime, Utc};
+use p256::ecdsa::signature::{Signer, Verifier};
+use p256::ecdsa::{Signature, SigningKey, VerifyingKey};
+
+use crate::cbor::Value;
+use crate::cose::{
+    CoseSign1, COSE_ALG_ES256, COSE_HEADER_ALG, COSE_HEADER_CONTENT_TYPE, COSE_HEADER_KID,
+};
+
+/// The version of the bundle layout this crate reads and writes.
+pub const BUNDLE_VERSION: u64 = 1;
+
+/// The content type of the COSE_Sign1 payload.
+pub const BUNDLE_CONTENT_TYPE: &str = "application/vnd.tee-ware.evidence-bundle+cbor";
+
+const KEY_VERSION: u64 = 0;
+const KEY_COLLECTED_AT: u64 = 1;
+const KEY_NONCE: u64 = 2;
+const KEY_QUOTES: u64 = 3;
+const KEY_TPM_QUOTES: u64 = 4;
+const KEY_EVENT_LOGS: u64 = 5;
+const KEY_CERTIFICATES: u64 = 6;
+
+/// Attestation evidence collected at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvidenceBundle {
+    pub collected_at: DateTime<Utc>,
+    /// The challenge the evidence answers.
+    pub nonce: Option<Vec<u8>>,
+    /// SGX and TDX quotes.
+    pub quotes: Vec<Vec<u8>>,
+    pub tpm_quotes: Vec<BundledTpmQuote>,
+    /// Firmware and IMA event logs.
+    pub event_logs: Vec<Vec<u8>>,
+    /// DER certificates, such as TPM AK certificates.
+    pub certificates: Vec<Vec<u8>>,
+}
+
+/// A TPM2_Quote in an [`EvidenceBundle`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundledTpmQuote {
+    /// The marshaled `TPMS_ATTEST`.
+    pub attest: Vec<u8>,
+    /// The ECDSA P-256 signature of `attest`, raw `r || s`.
+    pub signature: [u8; 64],
+}
+
+impl EvidenceBundle {
+    /// An empty bundle collected at `collected_at`.
+    pub fn new(collected_at: DateTime<Utc>) -> Self {
+        Self {
+            collected_at,
+            nonce: None,
+            quotes: Vec::new(),
+            tpm_quotes: Vec::new(),
+            event_logs: Vec::new(),
+            certificates: Vec::new(),
+        }
+    }
+
+    /// The deterministic CBOR encoding of the bundle.
+    pub fn encode(&self) -> Vec<u8> {
+        let byte_strings =
+            |items: &[Vec<u8>]| Value::Array(items.iter().cloned().map(Value::Bytes).collect());
+        let mut entries = vec![
+            (
+                Value::Unsigned(KEY_VERSION),
+                Value::Unsigned(BUNDLE_VERSION),
+            ),
+            (
+                Value::Unsigned(KEY_COLLECTED_AT),
+                Value::integer(self.collected_at.timestamp()),
+            ),
+        ];
+        if let Some(nonce) = &self.nonce {
+            entries.push((Value::Unsigned(KEY_NONCE), Value::Bytes(nonce.clone())));
+        }
+        if !self.quotes.is_empty() {
+            entries.push((Value::Unsigned(KEY_QUOTES), byte_strings(&self.quotes)));
+        }
+        if !self.tpm_quotes.is_empty() {
+            let quotes = self
+                .tpm_quotes
+                .iter()
+                .map(|quote| {
+                    Value::Array(vec![
+                        Value::Bytes(quote.attest.clone()),
+                        Value::Bytes(quote.signature.to_vec()),
+                    ])
+                })
+                .collect();
+            entries.push((Value::Unsigned(KEY_TPM_QUOTES), Value::Array(quotes)));
+        }
+        if !self.event_logs.is_empty() {
+            entries.push((
+                Value::Unsigned(KEY_EVENT_LOGS),
+                byte_strings(&self.event_logs),
+            ));
+        }
+        if !self.certificates.is_empty() {
+            entries.push((
+                Value::Unsigned(KEY_CERTIFICATES),
+                byte_strings(&self.certificates),
+            ));
+        }
+        Value::Map(entries).encode()
+    }
+
+    /// Decode a bundle, which must be in the encoding [`EvidenceBundle::encode`]
+    /// produces, so a decoded bundle always encodes back to `bytes`.
+    pub fn decode(bytes: &[u8]) -> eyre::Result<Self> {
+        let value = Value::decode(bytes)?;
+        let Some(entries) = value.as_map() else {
+            eyre::bail!("evidence bundle is not a map");
+        };
+        let version = value.get_int(KEY_VERSION as i64).and_then(Value::as_u64);
+        if version != Some(BUNDLE_VERSION) {
+            eyre::bail!("unsupported evidence bundle version {:?}", version);
+        }
+
+        let collected_at = value
+            .get_int(KEY_COLLECTED_AT as i64)
+            .and_then(Value::as_i64)
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .ok_or_else(|| eyre::eyre!("evidence bundle has no collection time"))?;
+        let mut bundle = Self::new(collected_at);
+        for (key, item) in entries {
+            match key.as_u64() {
+                Some(KEY_VERSION | KEY_COLLECTED_AT) => {}
+                Some(KEY_NONCE) => bundle.nonce = Some(bytes_of(item, "nonce")?.to_vec()),
+                Some(KEY_QUOTES) => bundle.quotes = byte_strings(item, "quotes")?,
+                Some(KEY_TPM_QUOTES) => {
+                    bundle.tpm_quotes = array(item, "tpm_quotes")?
+                        .iter()
+                        .map(tpm_quote)
+                        .collect::<eyre::Result<_>>()?;
+                }
+                Some(KEY_EVENT_LOGS) => bundle.event_logs = byte_strings(item, "event_logs")?,
+                Some(KEY_CERTIFICATES) => bundle.certificates = byte_strings(item, "certificates")?,
+                _ => eyre::bail!("unknown evidence bundle key {:?}", key),
+            }
+        }
+
+        if bundle.encode() != bytes {
+            eyre::bail!("evidence bundle is not deterministically encoded");
+        }
+        Ok(bundle)
+    }
+
+    /// Sign the bundle into a tagged COSE_Sign1 with ES256, with `key_id` in
+    /// the unprotected header if given.
+    pub fn sign(&self, key: &SigningKey, key_id: Option<&[u8]>) -> Vec<u8> {
+        let protected = Value::Map(vec![
+            (
+                Value::integer(COSE_HEADER_ALG),
+                Value::integer(COSE_ALG_ES256),
+            ),
+            (
+                Value::integer(COSE_HEADER_CONTENT_TYPE),
+                Value::text(BUNDLE_CONTENT_TYPE),
+            ),
+        ]);
+        let unprotected = key_id
+            .map(|kid| (Value::integer(COSE_HEADER_KID), Value::Bytes(kid.to_vec())))
+            .into_iter()
+            .collect();
+        let mut sign1 = CoseSign1 {
+            protected: protected.encode(),
+            unprotected: Value::Map(unprotected),
+            payload: self.encode(),
+            signature: Vec::new(),
+        };
+        let signature: Signature = key.sign(&sign1.signed_data());
+        sign1.signature = signature.to_bytes().to_vec();
+        sign1.encode()
+    }
+
+    /// Check the ES256 signature of a signed bundle with `key` and decode it.
+    pub fn verify(signed: &[u8], key: &VerifyingKey) -> eyre::Result<Self> {
+        let sign1 = CoseSign1::parse(signed)?;
+        let algorithm = sign1.algorithm()?;
+        if algorithm != COSE_ALG_ES256 {
+            eyre::bail!("unsupported COSE algorithm {}", algorithm);
+        }
+        let content_type = sign1.protected_header()?;
+        let content_type = content_type
+            .get_int(COSE_HEADER_CONTENT_TYPE)
+            .and_then(Value::as_text);
+        if content_type != Some(BUNDLE_CONTENT_TYPE) {
+            eyre::bail!("COSE_Sign1 payload is not an evidence bundle");
+        }
+        let signature = Signature::from_slice(&sign1.signature)
+            .map_err(|_| eyre::eyre!("malformed evidence bundle signature"))?;
+        key.verify(&sign1.signed_data(), &signature)
+            .map_err(|_| eyre::eyre!("evidence bundle signature is invalid"))?;
+        Self::decode(&sign1.payload)
+    }
+}
+
+fn bytes_of<'a>(value: &'a Value, field: &str) -> eyre::Result<&'a [u8]> {
+    value
+        .as_bytes()
+        .ok_or_else(|| eyre::eyre!("evidence bundle {} is not a byte string", field))
+}
+
+fn array<'a>(value: &'a Value, field: &str) -> eyre::Result<&'a [Value]> {
+    value
+        .as_array()
+        .ok_or_else(|| eyre::eyre!("evidence bundle {} is not an array", field))
+}
+
+fn tpm_quote(value: &Value) -> eyre::Result<BundledTpmQuote> {
+    let Some([attest, signature]) = value.as_array() else {
+        eyre::bail!("evidence bundle TPM quote is not [attest, signature]");
+    };
+    Ok(BundledTpmQuote {
+        attest: bytes_of(attest, "TPM quote")?.to_vec(),
+        signature: bytes_of(signature, "TPM quote signature")?
+            .try_into()
+            .map_err(|_| eyre::eyre!("evidence bundle TPM quote signature is not 64 bytes"))?,
+    })
+}
+
+fn byte_strings(value: &Value, field: &str) -> eyre::Result<Vec<Vec<u8>>> {
+    array(value, field)?
+        .iter()
+        .map(|item| Ok(bytes_of(item, field)?.to_vec()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use dcap::test_utils::signing_key;
+
+    use super::*;
+
+    fn bundle() -> EvidenceBundle {
+        EvidenceBundle {
+            nonce: Some(vec![7; 32]),
+            quotes: vec![vec![4, 0, 2, 0]],
+            tpm_quotes: vec![BundledTpmQuote {
+                attest: vec![0xff, 0x54, 0x43, 0x47],
+                signature: [1; 64],
+            }],
+            event_logs: vec![vec![0; 8]],
+            certificates: vec![vec![0x30, 0x00]],
+            ..EvidenceBundle::new(DateTime::from_timestamp(1_740_787_200, 0).unwrap())
+        }
+    }
+
+    #[test]
+    fn test_encode_roundtrip() -> eyre::Result<()> {
+        let bundle = bundle();
+        let bytes = bundle.encode();
+        assert_eq!(EvidenceBundle::decode(&bytes)?, bundle);
+        // A map of 7 entries, version first.
+        assert_eq!(bytes[..3], [0xa7, 0x00, 0x01]);
+
+        let empty = EvidenceBundle::new(bundle.collected_at);
+        assert_eq!(EvidenceBundle::decode(&empty.encode())?, empty);
+        assert_eq!(empty.encode()[0], 0xa2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_other_encodings() {
+        let bundle = bundle();
+        let Value::Map(mut entries) = Value::decode(&bundle.encode()).unwrap() else {
+            unreachable!();
+        };
+
+        // Keys out of order.
+        let mut reordered = entries.clone();
+        reordered.swap(2, 3);
+        assert!(EvidenceBundle::decode(&Value::Map(reordered).encode()).is_err());
+
+        // An empty field that is written out.
+        let mut with_empty = entries.clone();
+        with_empty[3].1 = Value::Array(Vec::new());
+        assert!(EvidenceBundle::decode(&Value::Map(with_empty).encode()).is_err());
+
+        let mut unknown = entries.clone();
+        unknown.push((Value::Unsigned(7), Value::Null));
+        assert!(EvidenceBundle::decode(&Value::Map(unknown).encode()).is_err());
+
+        entries[0].1 = Value::Unsigned(2);
+        let err = EvidenceBundle::decode(&Value::Map(entries).encode()).unwrap_err();
+        assert!(err.to_string().contains("version"));
+    }
+
+    #[test]
+    fn test_sign_and_verify() -> eyre::Result<()> {
+        let key = signing_key(1);
+        let bundle = bundle();
+        let signed = bundle.sign(&key, Some(b"archive-1"));
+        assert_eq!(signed, bundle.sign(&key, Some(b"archive-1")));
+        assert_eq!(
+            EvidenceBundle::verify(&signed, key.verifying_key())?,
+            bundle
+        );
+
+        let sign1 = CoseSign1::parse(&signed)?;
+        assert_eq!(
+            sign1.unprotected.get_int(COSE_HEADER_KID),
+            Some(&Value::Bytes(b"archive-1".to_vec()))
+        );
+        assert!(EvidenceBundle::verify(&signed, signing_key(2).verifying_key()).is_err());
+
+        let mut tampered = sign1.clone();
+        tampered.payload = EvidenceBundle::new(bundle.collected_at).encode();
+        assert!(EvidenceBundle::verify(&tampered.encode(), key.verifying_key()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,342 @@
+//! A small CBOR (RFC 8949) codec for the attestation formats built on it.
+//!
+//! Decoding rejects indefinite lengths and nesting deeper than
+//! [`MAX_DEPTH`]. Encoding uses the shortest form of every length and
+//! integer, as deterministic encoding requires; map entries are written in
+//! the order given.
+
+use tss_serde::TssReader;
+
+/// Maximum nesting of arrays, maps and tags accepted when decoding.
+pub const MAX_DEPTH: usize = 32;
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+
+const SIMPLE_FALSE: u8 = 20;
+const SIMPLE_TRUE: u8 = 21;
+const SIMPLE_NULL: u8 = 22;
+const SIMPLE_UNDEFINED: u8 = 23;
+
+/// A CBOR data item.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Unsigned(u64),
+    /// The negative integer `-1 - n`.
+    Negative(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Tag(u64, Box<Value>),
+    Bool(bool),
+    Null,
+    Undefined,
+    Float(f64),
+}
+
+impl Value {
+    /// Decode a single data item spanning all of `bytes`.
+    pub fn decode(bytes: &[u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+        let value = decode_item(&mut reader, 0)?;
+        if reader.remaining() != 0 {
+            eyre::bail!("{} trailing bytes after CBOR item", reader.remaining());
+        }
+        Ok(value)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out);
+        out
+    }
+
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Unsigned(n) => write_head(out, MAJOR_UNSIGNED, *n),
+            Value::Negative(n) => write_head(out, MAJOR_NEGATIVE, *n),
+            Value::Bytes(bytes) => {
+                write_head(out, MAJOR_BYTES, bytes.len() as u64);
+                out.extend_from_slice(bytes);
+            }
+            Value::Text(text) => {
+                write_head(out, MAJOR_TEXT, text.len() as u64);
+                out.extend_from_slice(text.as_bytes());
+            }
+            Value::Array(items) => {
+                write_head(out, MAJOR_ARRAY, items.len() as u64);
+                for item in items {
+                    item.encode_into(out);
+                }
+            }
+            Value::Map(entries) => {
+                write_head(out, MAJOR_MAP, entries.len() as u64);
+                for (key, value) in entries {
+                    key.encode_into(out);
+                    value.encode_into(out);
+                }
+            }
+            Value::Tag(tag, value) => {
+                write_head(out, MAJOR_TAG, *tag);
+                value.encode_into(out);
+            }
+            Value::Bool(false) => out.push(MAJOR_SIMPLE << 5 | SIMPLE_FALSE),
+            Value::Bool(true) => out.push(MAJOR_SIMPLE << 5 | SIMPLE_TRUE),
+            Value::Null => out.push(MAJOR_SIMPLE << 5 | SIMPLE_NULL),
+            Value::Undefined => out.push(MAJOR_SIMPLE << 5 | SIMPLE_UNDEFINED),
+            Value::Float(value) => {
+                out.push(MAJOR_SIMPLE << 5 | 27);
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+    }
+
+    /// An integer item, positive or negative.
+    pub fn integer(value: i64) -> Self {
+        if value < 0 {
+            Value::Negative(!value as u64)
+        } else {
+            Value::Unsigned(value as u64)
+        }
+    }
+
+    pub fn text(text: impl Into<String>) -> Self {
+        Value::Text(text.into())
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Unsigned(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Unsigned(n) => i64::try_from(*n).ok(),
+            Value::Negative(n) => i64::try_from(*n).ok().map(|n| -1 - n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&[(Value, Value)]> {
+        match self {
+            Value::Map(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// The value under the text key `key` of a map.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_map()?
+            .iter()
+            .find(|(k, _)| k.as_text() == Some(key))
+            .map(|(_, value)| value)
+    }
+
+    /// The value under the integer key `key` of a map, as used by COSE.
+    pub fn get_int(&self, key: i64) -> Option<&Value> {
+        self.as_map()?
+            .iter()
+            .find(|(k, _)| k.as_i64() == Some(key))
+            .map(|(_, value)| value)
+    }
+
+    /// The item inside any tags.
+    pub fn untagged(&self) -> &Value {
+        match self {
+            Value::Tag(_, value) => value.untagged(),
+            value => value,
+        }
+    }
+}
+
+fn write_head(out: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+    match argument {
+        0..=23 => out.push(major | argument as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, argument as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(argument as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(argument as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&argument.to_be_bytes());
+        }
+    }
+}
+
+fn read_argument(reader: &mut TssReader, info: u8) -> eyre::Result<u64> {
+    Ok(match info {
+        0..=23 => info.into(),
+        24 => reader.read_u8()?.into(),
+        25 => reader.read_u16()?.into(),
+        26 => reader.read_u32()?.into(),
+        27 => reader.read_u64()?,
+        31 => eyre::bail!("indefinite-length CBOR items are not supported"),
+        _ => eyre::bail!("reserved CBOR additional information {}", info),
+    })
+}
+
+/// A length that the rest of the input can hold, `item_size` bytes per item.
+fn read_length(reader: &mut TssReader, info: u8, item_size: usize) -> eyre::Result<usize> {
+    let length = read_argument(reader, info)?;
+    match usize::try_from(length) {
+        Ok(length) if length.saturating_mul(item_size) <= reader.remaining() => Ok(length),
+        _ => eyre::bail!("CBOR length {} exceeds the input", length),
+    }
+}
+
+fn decode_item(reader: &mut TssReader, depth: usize) -> eyre::Result<Value> {
+    if depth > MAX_DEPTH {
+        eyre::bail!("CBOR nesting exceeds {} levels", MAX_DEPTH);
+    }
+    let initial = reader.read_u8()?;
+    let (major, info) = (initial >> 5, initial & 0x1f);
+    Ok(match major {
+        MAJOR_UNSIGNED => Value::Unsigned(read_argument(reader, info)?),
+        MAJOR_NEGATIVE => Value::Negative(read_argument(reader, info)?),
+        MAJOR_BYTES => {
+            let length = read_length(reader, info, 1)?;
+            Value::Bytes(reader.read_bytes(length)?)
+        }
+        MAJOR_TEXT => {
+            let length = read_length(reader, info, 1)?;
+            Value::Text(String::from_utf8(reader.read_bytes(length)?)?)
+        }
+        MAJOR_ARRAY => {
+            let length = read_length(reader, info, 1)?;
+            let mut items = Vec::with_capacity(length);
+            for _ in 0..length {
+                items.push(decode_item(reader, depth + 1)?);
+            }
+            Value::Array(items)
+        }
+        MAJOR_MAP => {
+            let length = read_length(reader, info, 2)?;
+            let mut entries = Vec::with_capacity(length);
+            for _ in 0..length {
+                let key = decode_item(reader, depth + 1)?;
+                let value = decode_item(reader, depth + 1)?;
+                entries.push((key, value));
+            }
+            Value::Map(entries)
+        }
+        MAJOR_TAG => {
+            let tag = read_argument(reader, info)?;
+            Value::Tag(tag, Box::new(decode_item(reader, depth + 1)?))
+        }
+        _ => match info {
+            SIMPLE_FALSE => Value::Bool(false),
+            SIMPLE_TRUE => Value::Bool(true),
+            SIMPLE_NULL => Value::Null,
+            SIMPLE_UNDEFINED => Value::Undefined,
+            25 => Value::Float(f16_to_f64(reader.read_u16()?)),
+            26 => Value::Float(f32::from_be_bytes(reader.read_array()?).into()),
+            27 => Value::Float(f64::from_be_bytes(reader.read_array()?)),
+            _ => eyre::bail!("unsupported CBOR simple value {}", info),
+        },
+    })
+}
+
+fn f16_to_f64(half: u16) -> f64 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = i32::from((half >> 10) & 0x1f);
+    let mantissa = f64::from(half & 0x3ff);
+    sign * match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        0x1f if mantissa == 0.0 => f64::INFINITY,
+        0x1f => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() -> eyre::Result<()> {
+        let value = Value::Map(vec![
+            (Value::text("a"), Value::Unsigned(1)),
+            (Value::integer(-35), Value::Bytes(vec![0xAB; 300])),
+            (
+                Value::text("list"),
+                Value::Array(vec![
+                    Value::Null,
+                    Value::Bool(true),
+                    Value::Unsigned(70_000),
+                ]),
+            ),
+            (
+                Value::Unsigned(4),
+                Value::Tag(18, Box::new(Value::Float(1.5))),
+            ),
+        ]);
+        let bytes = value.encode();
+        assert_eq!(&bytes[..4], &[0xa4, 0x61, b'a', 0x01]);
+        assert_eq!(Value::decode(&bytes)?, value);
+        assert_eq!(value.get("a").and_then(Value::as_u64), Some(1));
+        assert_eq!(
+            value
+                .get_int(-35)
+                .and_then(Value::as_bytes)
+                .map(<[u8]>::len),
+            Some(300)
+        );
+        assert_eq!(
+            value.get_int(4).map(Value::untagged),
+            Some(&Value::Float(1.5))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_malformed() {
+        // Indefinite-length array.
+        assert!(Value::decode(&[0x9f, 0x01, 0xff]).is_err());
+        // Byte string longer than the input.
+        assert!(Value::decode(&[0x5a, 0xff, 0xff, 0xff, 0xff, 0x00]).is_err());
+        // Trailing bytes.
+        assert!(Value::decode(&[0x01, 0x02]).is_err());
+        // Nesting beyond the limit.
+        assert!(Value::decode(&[0x81; MAX_DEPTH + 2]).is_err());
+        assert_eq!(
+            Value::decode(&[0xf9, 0x3c, 0x00]).unwrap(),
+            Value::Float(1.0)
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! The TCG Canonical Event Log (CEL) format.
+//!
+//! CEL wraps each event of a measured-boot log in a record carrying its
+//! sequence number, PCR and digests, so logs of different sources can be
+//! exchanged and replayed uniformly. Events of a crypto-agile log are
+//! `pcclient_std` records; [`EventLog::to_cel`] and [`EventLog::from_cel`]
+//! convert between the two, and [`CelRecord`] encodes to CEL-JSON and
+//! CEL-CBOR.
+//!
+//! Only the SHA-256 bank of an [`EventLog`] is kept, so records of other
+//! banks are dropped when converting back.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cbor::Value;
+use crate::evidence::hex_bytes;
+use crate::{EventLog, TpmEvent};
+
+/// CBOR keys of a record.
+const CEL_RECNUM: i64 = 0;
+const CEL_PCR: i64 = 1;
+const CEL_NV_INDEX: i64 = 2;
+const CEL_DIGESTS: i64 = 3;
+const CEL_PCCLIENT_STD: i64 = 5;
+
+/// CBOR keys of `pcclient_std` content.
+const PCCLIENT_EVENT_TYPE: i64 = 0;
+const PCCLIENT_EVENT_DATA: i64 = 1;
+
+const TPM_ALG_SHA1: u64 = 0x0004;
+const TPM_ALG_SHA256: u64 = 0x000B;
+const TPM_ALG_SHA384: u64 = 0x000C;
+
+/// A record of a canonical event log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CelRecord {
+    pub recnum: u64,
+    pub pcr: u32,
+    pub digests: Vec<CelDigest>,
+    #[serde(flatten)]
+    pub content: CelContent,
+}
+
+/// The digest of a record's content in one bank.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CelDigest {
+    /// `sha1`, `sha256` or `sha384`.
+    #[serde(rename = "hashAlg")]
+    pub hash_alg: String,
+    #[serde(with = "hex_bytes")]
+    pub digest: Vec<u8>,
+}
+
+/// The content of a record, tagged by its content type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "content_type", content = "content", rename_all = "snake_case")]
+pub enum CelContent {
+    /// An event of a TCG PC Client log.
+    PcclientStd {
+        event_type: u32,
+        #[serde(with = "hex_bytes")]
+        event_data: Vec<u8>,
+    },
+}
+
+impl CelRecord {
+    /// The digest of `hash_alg`, if the record has one.
+    pub fn digest(&self, hash_alg: &str) -> Option<&[u8]> {
+        self.digests
+            .iter()
+            .find(|digest| digest.hash_alg == hash_alg)
+            .map(|digest| digest.digest.as_slice())
+    }
+
+    pub fn to_cbor(&self) -> Value {
+        let digests = self
+            .digests
+            .iter()
+            .filter_map(|digest| {
+                let alg = alg_id(&digest.hash_alg)?;
+                Some((Value::Unsigned(alg), Value::Bytes(digest.digest.clone())))
+            })
+            .collect();
+        let content = match &self.content {
+            CelContent::PcclientStd {
+                event_type,
+                event_data,
+            } => (
+                Value::integer(CEL_PCCLIENT_STD),
+                Value::Map(vec![
+                    (
+                        Value::integer(PCCLIENT_EVENT_TYPE),
+                        Value::Unsigned((*event_type).into()),
+                    ),
+                    (
+                        Value::integer(PCCLIENT_EVENT_DATA),
+                        Value::Bytes(event_data.clone()),
+                    ),
+                ]),
+            ),
+        };
+        Value::Map(vec![
+            (Value::integer(CEL_RECNUM), Value::Unsigned(self.recnum)),
+            (Value::integer(CEL_PCR), Value::Unsigned(self.pcr.into())),
+            (Value::integer(CEL_DIGESTS), Value::Map(digests)),
+            content,
+        ])
+    }
+
+    pub fn from_cbor(value: &Value) -> eyre::Result<Self> {
+        let recnum = value
+            .get_int(CEL_RECNUM)
+            .and_then(Value::as_u64)
+            .ok_or_else(|| eyre::eyre!("CEL record has no recnum"))?;
+        if value.get_int(CEL_NV_INDEX).is_some() {
+            eyre::bail!("unsupported NV index CEL record {}", recnum);
+        }
+        let pcr = value
+            .get_int(CEL_PCR)
+            .and_then(Value::as_u64)
+            .and_then(|pcr| u32::try_from(pcr).ok())
+            .ok_or_else(|| eyre::eyre!("CEL record {} has no PCR", recnum))?;
+        let digests = value
+            .get_int(CEL_DIGESTS)
+            .and_then(Value::as_map)
+            .ok_or_else(|| eyre::eyre!("CEL record {} has no digests", recnum))?
+            .iter()
+            .filter_map(|(alg, digest)| {
+                let hash_alg = alg_name(alg.as_u64()?)?;
+                Some((hash_alg, digest))
+            })
+            .map(|(hash_alg, digest)| {
+                let digest = digest
+                    .as_bytes()
+                    .ok_or_else(|| eyre::eyre!("CEL record {} has an invalid digest", recnum))?;
+                Ok(CelDigest {
+                    hash_alg: hash_alg.to_string(),
+                    digest: digest.to_vec(),
+                })
+            })
+            .collect::<eyre::Result<_>>()?;
+        let content = value
+            .get_int(CEL_PCCLIENT_STD)
+            .ok_or_else(|| eyre::eyre!("unsupported content type of CEL record {}", recnum))?;
+        let event_type = content
+            .get_int(PCCLIENT_EVENT_TYPE)
+            .and_then(Value::as_u64)
+            .and_then(|event_type| u32::try_from(event_type).ok())
+            .ok_or_else(|| eyre::eyre!("CEL record {} has no event type", recnum))?;
+        let event_data = content
+            .get_int(PCCLIENT_EVENT_DATA)
+            .and_then(Value::as_bytes)
+            .ok_or_else(|| eyre::eyre!("CEL record {} has no event data", recnum))?;
+        Ok(Self {
+            recnum,
+            pcr,
+            digests,
+            content: CelContent::PcclientStd {
+                event_type,
+                event_data: event_data.to_vec(),
+            },
+        })
+    }
+}
+
+impl EventLog {
+    /// The events as CEL records, numbered from 0.
+    pub fn to_cel(&self) -> Vec<CelRecord> {
+        self.events
+            .iter()
+            .zip(0..)
+            .map(|(event, recnum)| CelRecord {
+                recnum,
+                pcr: event.pcr_index,
+                digests: event
+                    .sha256
+                    .map(|digest| CelDigest {
+                        hash_alg: "sha256".to_string(),
+                        digest: digest.to_vec(),
+                    })
+                    .into_iter()
+                    .collect(),
+                content: CelContent::PcclientStd {
+                    event_type: event.event_type,
+                    event_data: event.data.clone(),
+                },
+            })
+            .collect()
+    }
+
+    /// Rebuild a log from CEL records, which must be in order.
+    pub fn from_cel(records: &[CelRecord]) -> eyre::Result<Self> {
+        let mut events = Vec::with_capacity(records.len());
+        for (expected, record) in (0..).zip(records) {
+            if record.recnum != expected {
+                eyre::bail!("CEL record {} is out of order", record.recnum);
+            }
+            let sha256 = record
+                .digest("sha256")
+                .map(|digest| {
+                    digest.try_into().map_err(|_| {
+                        eyre::eyre!("CEL record {} has an invalid SHA-256 digest", record.recnum)
+                    })
+                })
+                .transpose()?;
+            let CelContent::PcclientStd {
+                event_type,
+                event_data,
+            } = &record.content;
+            events.push(TpmEvent {
+                pcr_index: record.pcr,
+                event_type: *event_type,
+                sha256,
+                data: event_data.clone(),
+            });
+        }
+        Ok(Self { events })
+    }
+
+    /// The log in CEL-JSON, an array of records.
+    pub fn to_cel_json(&self) -> eyre::Result<String> {
+        Ok(serde_json::to_string(&self.to_cel())?)
+    }
+
+    pub fn from_cel_json(json: &str) -> eyre::Result<Self> {
+        let records: Vec<CelRecord> = serde_json::from_str(json)?;
+        Self::from_cel(&records)
+    }
+
+    /// The log in CEL-CBOR, an array of records.
+    pub fn to_cel_cbor(&self) -> Vec<u8> {
+        let records = self.to_cel().iter().map(CelRecord::to_cbor).collect();
+        Value::Array(records).encode()
+    }
+
+    pub fn from_cel_cbor(bytes: &[u8]) -> eyre::Result<Self> {
+        let value = Value::decode(bytes)?;
+        let records = value
+            .as_array()
+            .ok_or_else(|| eyre::eyre!("CEL-CBOR is not an array of records"))?
+            .iter()
+            .map(CelRecord::from_cbor)
+            .collect::<eyre::Result<Vec<_>>>()?;
+        Self::from_cel(&records)
+    }
+}
+
+fn alg_id(name: &str) -> Option<u64> {
+    match name {
+        "sha1" => Some(TPM_ALG_SHA1),
+        "sha256" => Some(TPM_ALG_SHA256),
+        "sha384" => Some(TPM_ALG_SHA384),
+        _ => None,
+    }
+}
+
+fn alg_name(id: u64) -> Option<&'static str> {
+    match id {
+        TPM_ALG_SHA1 => Some("sha1"),
+        TPM_ALG_SHA256 => Some("sha256"),
+        TPM_ALG_SHA384 => Some("sha384"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dcap::test_utils::gce_tdx_log;
+
+    use super::*;
+
+    #[test]
+    fn test_cel_roundtrip() -> eyre::Result<()> {
+        let log = EventLog::parse(&gce_tdx_log())?;
+
+        let json = log.to_cel_json()?;
+        let records: serde_json::Value = serde_json::from_str(&json)?;
+        assert_eq!(records[1]["content_type"], "pcclient_std");
+        assert_eq!(records[1]["digests"][0]["hashAlg"], "sha256");
+        assert_eq!(EventLog::from_cel_json(&json)?, log);
+
+        let cbor = log.to_cel_cbor();
+        let decoded = EventLog::from_cel_cbor(&cbor)?;
+        assert_eq!(decoded, log);
+        assert_eq!(decoded.replay_sha256()?, log.replay_sha256()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cel_rejects_invalid_records() -> eyre::Result<()> {
+        let log = EventLog::parse(&gce_tdx_log())?;
+        let mut records = log.to_cel();
+        records.swap(0, 1);
+        assert!(EventLog::from_cel(&records).is_err());
+
+        let mut records = log.to_cel();
+        records[1].digests[0].digest.pop();
+        assert!(EventLog::from_cel(&records).is_err());
+
+        let mut record = log.to_cel()[0].to_cbor();
+        if let Value::Map(entries) = &mut record {
+            entries.pop();
+        }
+        assert!(CelRecord::from_cbor(&record).is_err());
+        Ok(())
+    }
+}
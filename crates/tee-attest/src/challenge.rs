@@ -0,0 +1,408 @@
+//! A nonce-based challenge-response protocol for remote attestation.
+//!
+//! The verifier issues a [`Challenge`] carrying a fresh nonce. The attester
+//! answers with an [`AttestationResponse`]: a TPM quote over the nonce, the
+//! PCR values and event log the quote covers, the certificate of its AK and,
+//! on TDX VMs, a TD quote over [`ReportData::bind`] of the nonce and the AK
+//! certificate. Each nonce answers a single response.
+//!
+//! Messages are encoded with `tss_serde`: big-endian integers, and byte
+//! strings and lists prefixed with their `u32` length.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use dcap::{
+    verify_quote_with_anchors, verify_tpm_quote, CollateralFetcher, CollateralKey, Quote,
+    TpmAttest, TrustAnchors, VerificationResult, VerifyOptions, NONCE_SIZE,
+};
+use der::Decode;
+use tss_serde::{TssDeserialize, TssError, TssReader, TssSerialize, TssWriter};
+use x509_cert::Certificate;
+
+use crate::EventLog;
+
+/// Version of the protocol messages.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// How long an issued challenge can be answered by default.
+pub const DEFAULT_CHALLENGE_LIFETIME_SECS: i64 = 60;
+
+/// A request for evidence, from the verifier to the attester.
+#[derive(Debug, Clone, PartialEq, Eq, TssSerialize, TssDeserialize)]
+pub struct Challenge {
+    pub version: u16,
+    pub nonce: [u8; 32],
+    /// When the challenge was issued, in seconds since the Unix epoch.
+    pub issued_at: u64,
+}
+
+/// The value of a quoted SHA-256 PCR.
+#[derive(Debug, Clone, PartialEq, Eq, TssSerialize, TssDeserialize)]
+pub struct PcrValue {
+    pub index: u32,
+    pub digest: [u8; 32],
+}
+
+/// The evidence answering a [`Challenge`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttestationResponse {
+    pub version: u16,
+    /// The nonce of the challenge answered.
+    pub nonce: [u8; NONCE_SIZE],
+    /// DER certificate of the AK.
+    pub ak_cert: Vec<u8>,
+    /// The quoted TPMS_ATTEST.
+    pub attest: Vec<u8>,
+    /// Raw `r || s` ECDSA P-256 signature of `attest`.
+    pub signature: [u8; 64],
+    pub pcrs: BTreeMap<u32, [u8; 32]>,
+    pub event_log: Vec<u8>,
+    /// TD quote over [`ReportData::bind`] of the nonce and `ak_cert`.
+    ///
+    /// [`ReportData::bind`]: dcap::ReportData::bind
+    pub td_quote: Option<Vec<u8>>,
+}
+
+impl TssSerialize for AttestationResponse {
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_u16(self.version);
+        writer.write_bytes(&self.nonce);
+        write_bytes(writer, &self.ak_cert);
+        write_bytes(writer, &self.attest);
+        writer.write_bytes(&self.signature);
+        let count = u32::try_from(self.pcrs.len())
+            .expect("the length of the PCR map does not fit its u32 prefix");
+        writer.write_u32(count);
+        for (&index, &digest) in &self.pcrs {
+            PcrValue { index, digest }.serialize_to(writer);
+        }
+        write_bytes(writer, &self.event_log);
+        match &self.td_quote {
+            Some(td_quote) => {
+                writer.write_u8(1);
+                write_bytes(writer, td_quote);
+            }
+            None => writer.write_u8(0),
+        }
+    }
+}
+
+impl TssDeserialize for AttestationResponse {
+    fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        let version = reader.read_u16()?;
+        let nonce = reader.read_array()?;
+        let ak_cert = read_bytes(reader)?;
+        let attest = read_bytes(reader)?;
+        let signature = reader.read_array()?;
+        let mut pcrs = BTreeMap::new();
+        for pcr in Vec::<PcrValue>::from_tss_reader(reader)? {
+            if pcrs.insert(pcr.index, pcr.digest).is_some() {
+                return Err(TssError::Custom(format!("duplicate PCR{}", pcr.index)));
+            }
+        }
+        let event_log = read_bytes(reader)?;
+        let td_quote = match bool::from_tss_reader(reader)? {
+            true => Some(read_bytes(reader)?),
+            false => None,
+        };
+        Ok(Self {
+            version,
+            nonce,
+            ak_cert,
+            attest,
+            signature,
+            pcrs,
+            event_log,
+            td_quote,
+        })
+    }
+}
+
+fn write_bytes(writer: &mut TssWriter, bytes: &[u8]) {
+    let length = u32::try_from(bytes.len())
+        .expect("the length of a byte string does not fit its u32 prefix");
+    writer.write_u32(length);
+    writer.write_bytes(bytes);
+}
+
+fn read_bytes(reader: &mut TssReader) -> Result<Vec<u8>, TssError> {
+    let length = reader.read_u32()? as usize;
+    reader.read_bytes(length)
+}
+
+/// The attester half of the protocol.
+pub trait Attester {
+    /// Collect evidence answering `challenge`.
+    fn respond(&mut self, challenge: &Challenge) -> eyre::Result<AttestationResponse>;
+}
+
+/// What a verified [`AttestationResponse`] says about the attester.
+#[derive(Debug, Clone)]
+pub struct ChallengeAttestation {
+    pub ak_cert: Certificate,
+    pub attest: TpmAttest,
+    pub event_log: EventLog,
+    /// The verified TD quote, on TDX VMs.
+    pub td_quote: Option<VerificationResult>,
+}
+
+/// The verifier half of the protocol: issues challenges and verifies the
+/// responses to them.
+///
+/// Unless attestation keys are pinned with
+/// [`with_trusted_ak`](Self::with_trusted_ak), checking that the AK
+/// certificate belongs to a genuine TPM is left to the caller. Appraising
+/// the PCRs, the event log and the TD quote is too.
+pub struct ChallengeVerifier {
+    lifetime: Duration,
+    pending: Mutex<HashMap<[u8; NONCE_SIZE], DateTime<Utc>>>,
+    trusted_aks: Vec<Certificate>,
+    collateral: Option<Arc<dyn CollateralFetcher + Send + Sync>>,
+    anchors: TrustAnchors,
+}
+
+impl Default for ChallengeVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChallengeVerifier {
+    pub fn new() -> Self {
+        Self {
+            lifetime: Duration::seconds(DEFAULT_CHALLENGE_LIFETIME_SECS),
+            pending: Mutex::new(HashMap::new()),
+            trusted_aks: Vec::new(),
+            collateral: None,
+            anchors: TrustAnchors::default(),
+        }
+    }
+
+    /// How long an issued challenge can be answered.
+    pub fn with_lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Only accept quotes by the AK certified by `ak_cert`, or another one
+    /// pinned this way.
+    pub fn with_trusted_ak(mut self, ak_cert: Certificate) -> Self {
+        self.trusted_aks.push(ak_cert);
+        self
+    }
+
+    /// Fetch the collateral of TD quotes through `fetcher`. Responses
+    /// carrying a TD quote are rejected without one.
+    pub fn with_collateral(
+        mut self,
+        fetcher: impl CollateralFetcher + Send + Sync + 'static,
+    ) -> Self {
+        self.collateral = Some(Arc::new(fetcher));
+        self
+    }
+
+    /// Trust `anchors` for TD quotes instead of the Intel SGX Root CA.
+    pub fn with_trust_anchors(mut self, anchors: TrustAnchors) -> Self {
+        self.anchors = anchors;
+        self
+    }
+
+    /// Issue a challenge with a fresh random nonce.
+    pub fn challenge(&self) -> eyre::Result<Challenge> {
+        let mut nonce = [0u8; NONCE_SIZE];
+        getrandom::getrandom(&mut nonce)
+            .map_err(|err| eyre::eyre!("failed to generate a nonce: {}", err))?;
+        Ok(self.challenge_with_nonce(nonce, Utc::now()))
+    }
+
+    /// Issue a challenge with `nonce` at `at`.
+    pub fn challenge_with_nonce(&self, nonce: [u8; NONCE_SIZE], at: DateTime<Utc>) -> Challenge {
+        let mut pending = self.pending();
+        pending.retain(|_, issued_at| at - *issued_at <= self.lifetime);
+        pending.insert(nonce, at);
+        Challenge {
+            version: PROTOCOL_VERSION,
+            nonce,
+            issued_at: at.timestamp().max(0) as u64,
+        }
+    }
+
+    /// Verify `response` at the current time.
+    pub fn verify(&self, response: &AttestationResponse) -> eyre::Result<ChallengeAttestation> {
+        self.verify_at(response, Utc::now())
+    }
+
+    /// Check that `response` answers a challenge issued within the lifetime
+    /// before `at`, then the quote signature, that the quote covers the PCR
+    /// values, that the event log replays to them and, if there is a TD
+    /// quote, that it verifies and is bound to the nonce and AK.
+    ///
+    /// The challenge is consumed even if the response does not verify.
+    pub fn verify_at(
+        &self,
+        response: &AttestationResponse,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<ChallengeAttestation> {
+        if response.version != PROTOCOL_VERSION {
+            eyre::bail!("unsupported protocol version {}", response.version);
+        }
+        let Some(issued_at) = self.pending().remove(&response.nonce) else {
+            eyre::bail!("response does not answer an outstanding challenge");
+        };
+        if at - issued_at > self.lifetime {
+            eyre::bail!("challenge issued at {} has expired", issued_at);
+        }
+
+        let ak_cert = Certificate::from_der(&response.ak_cert)?;
+        if !self.trusted_aks.is_empty() && !self.trusted_aks.contains(&ak_cert) {
+            eyre::bail!("AK is not trusted");
+        }
+        let attest = verify_tpm_quote(&response.attest, &response.signature, &ak_cert)?;
+        if attest.extra_data != response.nonce {
+            eyre::bail!("TPM quote does not answer the nonce");
+        }
+        attest.verify_pcrs(&response.pcrs)?;
+        let event_log = EventLog::parse(&response.event_log)?;
+        event_log.verify_pcrs(&response.pcrs)?;
+
+        let td_quote = match &response.td_quote {
+            Some(td_quote) => Some(self.verify_td_quote(td_quote, response, at)?),
+            None => None,
+        };
+        Ok(ChallengeAttestation {
+            ak_cert,
+            attest,
+            event_log,
+            td_quote,
+        })
+    }
+
+    fn verify_td_quote(
+        &self,
+        td_quote: &[u8],
+        response: &AttestationResponse,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<VerificationResult> {
+        let Some(fetcher) = &self.collateral else {
+            eyre::bail!("no collateral source to verify the TD quote");
+        };
+        let key = CollateralKey::from_quote(&Quote::parse(td_quote)?)?;
+        let collateral = fetcher.fetch_collateral(&key)?;
+        let result = verify_quote_with_anchors(
+            td_quote,
+            &collateral,
+            &self.anchors,
+            &VerifyOptions::default(),
+            at,
+        )?;
+        result
+            .verify_binding(Some(&response.nonce), Some(&response.ak_cert))
+            .map_err(|err| err.wrap_err("TD quote is not bound to the TPM quote"))?;
+        Ok(result)
+    }
+
+    fn pending(&self) -> std::sync::MutexGuard<'_, HashMap<[u8; NONCE_SIZE], DateTime<Utc>>> {
+        self.pending.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dcap::test_utils::{gce_tdx_log, tpm_attest, verification_time, TestPki};
+    use der::Encode;
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::Signature;
+
+    use super::*;
+
+    /// Answers challenges with a quote of the replayed GCE TDX log.
+    struct TestAttester(TestPki);
+
+    impl Attester for TestAttester {
+        fn respond(&mut self, challenge: &Challenge) -> eyre::Result<AttestationResponse> {
+            let event_log = gce_tdx_log();
+            let pcrs = EventLog::parse(&event_log)?.replay_sha256()?;
+            let attest = tpm_attest(&challenge.nonce, &pcrs);
+            let signature: Signature = self.0.pck_key.sign(&attest);
+            Ok(AttestationResponse {
+                version: PROTOCOL_VERSION,
+                nonce: challenge.nonce,
+                ak_cert: self.0.pck_cert.to_der()?,
+                attest,
+                signature: signature.to_bytes().into(),
+                pcrs,
+                event_log,
+                td_quote: None,
+            })
+        }
+    }
+
+    #[test]
+    fn test_encoding_round_trip() -> eyre::Result<()> {
+        let challenge = Challenge {
+            version: PROTOCOL_VERSION,
+            nonce: [7; NONCE_SIZE],
+            issued_at: 1_740_787_200,
+        };
+        let bytes = challenge.to_tss_bytes();
+        assert_eq!(bytes.len(), 2 + NONCE_SIZE + 8);
+        assert_eq!(Challenge::from_tss_bytes(&bytes)?, challenge);
+
+        let mut response = TestAttester(TestPki::new()).respond(&challenge)?;
+        response.td_quote = Some(vec![1, 2, 3]);
+        let bytes = response.to_tss_bytes();
+        assert_eq!(AttestationResponse::from_tss_bytes(&bytes)?, response);
+        assert!(AttestationResponse::from_tss_bytes(&bytes[..bytes.len() - 1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_challenge_response() -> eyre::Result<()> {
+        let mut attester = TestAttester(TestPki::new());
+        let verifier = ChallengeVerifier::new();
+        let at = verification_time();
+
+        let challenge = verifier.challenge_with_nonce([1; NONCE_SIZE], at);
+        let response = attester.respond(&challenge)?;
+        let bytes = response.to_tss_bytes();
+        let attestation = verifier.verify_at(&AttestationResponse::from_tss_bytes(&bytes)?, at)?;
+        assert_eq!(attestation.attest.extra_data, [1; NONCE_SIZE]);
+        assert!(attestation.td_quote.is_none());
+
+        // A nonce answers a single response.
+        let err = verifier.verify_at(&response, at).unwrap_err();
+        assert!(err.to_string().contains("outstanding challenge"));
+
+        let challenge = verifier.challenge_with_nonce([2; NONCE_SIZE], at);
+        let response = attester.respond(&challenge)?;
+        let err = verifier
+            .verify_at(&response, at + Duration::seconds(61))
+            .unwrap_err();
+        assert!(err.to_string().contains("expired"));
+
+        let challenge = verifier.challenge_with_nonce([3; NONCE_SIZE], at);
+        let mut response = attester.respond(&challenge)?;
+        response.pcrs.insert(0, [0; 32]);
+        assert!(verifier.verify_at(&response, at).is_err());
+
+        let challenge = verifier.challenge_with_nonce([4; NONCE_SIZE], at);
+        let mut response = attester.respond(&challenge)?;
+        response.td_quote = Some(Vec::new());
+        let err = verifier.verify_at(&response, at).unwrap_err();
+        assert!(err.to_string().contains("no collateral source"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_trusted_ak() -> eyre::Result<()> {
+        let at = verification_time();
+        let verifier = ChallengeVerifier::new().with_trusted_ak(TestPki::new().root_cert);
+        let challenge = verifier.challenge_with_nonce([1; NONCE_SIZE], at);
+        let response = TestAttester(TestPki::new()).respond(&challenge)?;
+        let err = verifier.verify_at(&response, at).unwrap_err();
+        assert!(err.to_string().contains("AK is not trusted"));
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+//! Composite TPM and TDX evidence: a TPM quote and a TD quote whose report
+//! data commits to it, so a verifier knows that the PCR state and the TD
+//! measurements come from the same machine.
+//!
+//! Both quotes answer the same nonce. The TD quote binds it with
+//! [`ReportData::bind`], whose key half holds the SHA-256 of what the
+//! [`TpmBinding`] selects of the TPM quote.
+
+use dcap::{ReportData, TpmAttest, NONCE_SIZE};
+use serde::{Deserialize, Serialize};
+
+/// What of the TPM quote the report data of the TD quote commits to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TpmBinding {
+    /// The qualified name of the AK, which the TPM reports as the signer of
+    /// its quotes. The TD quote can be taken before the TPM quote.
+    AkName,
+    /// The TPMS_ATTEST of the TPM quote, which is taken first.
+    Quote,
+}
+
+impl TpmBinding {
+    /// Check that `td_report_data` binds the TPM quote `attest` and the
+    /// nonce it answers. Authenticating `attest` is up to the caller.
+    pub fn verify(&self, td_report_data: &ReportData, attest: &[u8]) -> eyre::Result<()> {
+        let parsed = TpmAttest::parse(attest)?;
+        let nonce: &[u8; NONCE_SIZE] = parsed
+            .extra_data
+            .as_slice()
+            .try_into()
+            .map_err(|_| eyre::eyre!("TPM quote nonce is not {} bytes", NONCE_SIZE))?;
+        let commitment = match self {
+            TpmBinding::AkName => &parsed.qualified_signer,
+            TpmBinding::Quote => attest,
+        };
+        td_report_data
+            .verify_binding(Some(nonce), Some(commitment))
+            .map_err(|err| err.wrap_err("TD quote is not bound to the TPM quote"))
+    }
+}
+
+#[cfg(feature = "tpm-tdx")]
+pub use producer::*;
+
+#[cfg(feature = "tpm-tdx")]
+mod producer {
+    use std::collections::BTreeMap;
+
+    use dcap::{ReportData, NONCE_SIZE};
+    use tss_client::{TpmSignature, Transport, TssClient};
+
+    use super::TpmBinding;
+    use crate::crypto::scalar;
+
+    /// A TPM quote and a TD quote bound to it.
+    #[derive(Debug, Clone)]
+    pub struct TpmTdxQuotes {
+        pub binding: TpmBinding,
+        /// The quoted TPMS_ATTEST.
+        pub attest: Vec<u8>,
+        /// Raw `r || s` ECDSA signature of `attest`.
+        pub signature: [u8; 64],
+        /// The SHA-256 values of the quoted PCRs.
+        pub pcrs: BTreeMap<u32, [u8; 32]>,
+        pub td_quote: Vec<u8>,
+    }
+
+    /// Quote `pcrs` with the ECC AK loaded at `ak` and get a TD quote over
+    /// the report data passed to `td_quote`, both answering `nonce` and
+    /// bound with `binding`.
+    pub fn attest_tpm_tdx<T: Transport>(
+        tpm: &mut TssClient<T>,
+        ak: u32,
+        nonce: &[u8; NONCE_SIZE],
+        pcrs: &[u32],
+        binding: TpmBinding,
+        td_quote: impl FnOnce(&ReportData) -> eyre::Result<Vec<u8>>,
+    ) -> eyre::Result<TpmTdxQuotes> {
+        let (quote, td_quote) = match binding {
+            TpmBinding::AkName => {
+                let qualified_name = tpm.read_public(ak)?.qualified_name;
+                let td_quote = td_quote(&ReportData::bind(Some(nonce), Some(&qualified_name)))?;
+                (tpm.quote(ak, nonce, pcrs)?, td_quote)
+            }
+            TpmBinding::Quote => {
+                let quote = tpm.quote(ak, nonce, pcrs)?;
+                let td_quote = td_quote(&ReportData::bind(Some(nonce), Some(&quote.attest)))?;
+                (quote, td_quote)
+            }
+        };
+        let TpmSignature::Ecdsa { r, s, .. } = quote.signature else {
+            eyre::bail!("the AK did not return an ECDSA signature");
+        };
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&scalar(&r)?);
+        signature[32..].copy_from_slice(&scalar(&s)?);
+
+        let mut sorted = pcrs.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let values = tpm.read_pcrs_sha256(&sorted)?;
+        Ok(TpmTdxQuotes {
+            binding,
+            attest: quote.attest,
+            signature,
+            pcrs: sorted.into_iter().zip(values).collect(),
+            td_quote,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use dcap::test_utils::tpm_attest;
+
+    use super::*;
+
+    #[test]
+    fn test_verify_binding() -> eyre::Result<()> {
+        let nonce = [7u8; NONCE_SIZE];
+        let attest = tpm_attest(&nonce, &BTreeMap::from([(0, [1; 32])]));
+        let qualified_signer = TpmAttest::parse(&attest)?.qualified_signer;
+
+        let by_name = ReportData::bind(Some(&nonce), Some(&qualified_signer));
+        TpmBinding::AkName.verify(&by_name, &attest)?;
+        assert!(TpmBinding::Quote.verify(&by_name, &attest).is_err());
+
+        let by_quote = ReportData::bind(Some(&nonce), Some(&attest));
+        TpmBinding::Quote.verify(&by_quote, &attest)?;
+        assert!(TpmBinding::AkName.verify(&by_quote, &attest).is_err());
+
+        // Another nonce, or a TPM quote of other PCRs.
+        let other_nonce = ReportData::bind(Some(&[8; NONCE_SIZE]), Some(&qualified_signer));
+        assert!(TpmBinding::AkName.verify(&other_nonce, &attest).is_err());
+        let other_quote = tpm_attest(&nonce, &BTreeMap::from([(0, [2; 32])]));
+        assert!(TpmBinding::Quote.verify(&by_quote, &other_quote).is_err());
+
+        let short_nonce = tpm_attest(b"nonce", &BTreeMap::new());
+        assert!(TpmBinding::AkName
+            .verify(
+                &ReportData::bind(None, Some(&qualified_signer)),
+                &short_nonce
+            )
+            .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+//! COSE_Sign1 (RFC 9052) structures, as signed by Nitro Enclaves and by
+//! [`EvidenceBundle`](crate::EvidenceBundle).
+
+use crate::cbor::Value;
+
+/// The COSE algorithm identifier of ECDSA with SHA-256.
+pub const COSE_ALG_ES256: i64 = -7;
+/// The COSE algorithm identifier of ECDSA with SHA-384.
+pub const COSE_ALG_ES384: i64 = -35;
+
+/// The `alg` header parameter.
+pub const COSE_HEADER_ALG: i64 = 1;
+/// The `content type` header parameter.
+pub const COSE_HEADER_CONTENT_TYPE: i64 = 3;
+/// The `kid` header parameter.
+pub const COSE_HEADER_KID: i64 = 4;
+
+/// The CBOR tag of a COSE_Sign1 structure.
+pub const COSE_SIGN1_TAG: u64 = 18;
+
+/// A COSE_Sign1 structure.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoseSign1 {
+    /// The serialized protected header map.
+    pub protected: Vec<u8>,
+    pub unprotected: Value,
+    pub payload: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl CoseSign1 {
+    /// Parse a COSE_Sign1 structure, tagged or not.
+    pub fn parse(bytes: &[u8]) -> eyre::Result<Self> {
+        let value = Value::decode(bytes)?;
+        let value = match value {
+            Value::Tag(COSE_SIGN1_TAG, value) => *value,
+            Value::Tag(tag, _) => eyre::bail!("unexpected CBOR tag {} on COSE_Sign1", tag),
+            value => value,
+        };
+        let Value::Array(items) = value else {
+            eyre::bail!("COSE_Sign1 is not an array");
+        };
+        let Ok([protected, unprotected, payload, signature]) = <[Value; 4]>::try_from(items) else {
+            eyre::bail!("COSE_Sign1 does not have 4 elements");
+        };
+        let bytes = |value: Value, field: &str| match value {
+            Value::Bytes(bytes) => Ok(bytes),
+            _ => Err(eyre::eyre!("COSE_Sign1 {} is not a byte string", field)),
+        };
+        Ok(Self {
+            protected: bytes(protected, "protected header")?,
+            unprotected,
+            payload: bytes(payload, "payload")?,
+            signature: bytes(signature, "signature")?,
+        })
+    }
+
+    /// Encode as a tagged COSE_Sign1 structure.
+    pub fn encode(&self) -> Vec<u8> {
+        Value::Tag(
+            COSE_SIGN1_TAG,
+            Box::new(Value::Array(vec![
+                Value::Bytes(self.protected.clone()),
+                self.unprotected.clone(),
+                Value::Bytes(self.payload.clone()),
+                Value::Bytes(self.signature.clone()),
+            ])),
+        )
+        .encode()
+    }
+
+    /// The protected header map.
+    pub fn protected_header(&self) -> eyre::Result<Value> {
+        if self.protected.is_empty() {
+            eyre::bail!("COSE_Sign1 has no protected header");
+        }
+        Value::decode(&self.protected)
+    }
+
+    /// The algorithm of the protected header.
+    pub fn algorithm(&self) -> eyre::Result<i64> {
+        self.protected_header()?
+            .get_int(COSE_HEADER_ALG)
+            .and_then(Value::as_i64)
+            .ok_or_else(|| eyre::eyre!("COSE_Sign1 protected header has no algorithm"))
+    }
+
+    /// The `Sig_structure` the signature is computed over.
+    pub fn signed_data(&self) -> Vec<u8> {
+        Value::Array(vec![
+            Value::text("Signature1"),
+            Value::Bytes(self.protected.clone()),
+            Value::Bytes(Vec::new()),
+            Value::Bytes(self.payload.clone()),
+        ])
+        .encode()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_roundtrip() -> eyre::Result<()> {
+        let sign1 = CoseSign1 {
+            protected: Value::Map(vec![(
+                Value::integer(COSE_HEADER_ALG),
+                Value::integer(COSE_ALG_ES256),
+            )])
+            .encode(),
+            unprotected: Value::Map(Vec::new()),
+            payload: b"payload".to_vec(),
+            signature: vec![7; 64],
+        };
+        let bytes = sign1.encode();
+        assert_eq!(bytes[0], 0xd2);
+        assert_eq!(CoseSign1::parse(&bytes)?, sign1);
+        assert_eq!(sign1.algorithm()?, COSE_ALG_ES256);
+
+        let untagged = CoseSign1 {
+            protected: Vec::new(),
+            ..sign1
+        };
+        assert!(untagged.algorithm().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+/// A 32 byte big-endian ECDSA scalar, which the TPM may return shorter.
+pub(crate) fn scalar(bytes: &[u8]) -> eyre::Result<[u8; 32]> {
+    if bytes.len() > 32 {
+        eyre::bail!("ECDSA signature is not P-256");
+    }
+    let mut scalar = [0u8; 32];
+    scalar[32 - bytes.len()..].copy_from_slice(bytes);
+    Ok(scalar)
+}
@@ -0,0 +1,290 @@
+//! TCG crypto-agile event logs, as exposed by the kernel in
+//! `/sys/kernel/security/tpm0/binary_bios_measurements`.
+//!
+//! The log records what the firmware extended into each PCR. Replaying it
+//! and comparing the result with quoted PCR values is what lets a verifier
+//! trust individual events, such as the GCE entries announcing the
+//! confidential computing technology of the VM.
+
+use std::collections::BTreeMap;
+
+use sha2::{Digest, Sha256};
+use tss_serde::{TssError, TssReader};
+
+/// Where Linux exposes the firmware event log.
+pub const BIOS_MEASUREMENTS_PATH: &str = "/sys/kernel/security/tpm0/binary_bios_measurements";
+
+/// TCG event types used by this crate.
+pub mod event_type {
+    pub const NO_ACTION: u32 = 0x3;
+    pub const S_CRTM_VERSION: u32 = 0x8;
+    pub const NONHOST_INFO: u32 = 0x11;
+}
+
+const TPM_ALG_SHA1: u16 = 0x0004;
+const TPM_ALG_SHA256: u16 = 0x000B;
+const SHA1_SIZE: usize = 20;
+
+const SPEC_ID_SIGNATURE: &[u8; 16] = b"Spec ID Event03\0";
+const STARTUP_LOCALITY_SIGNATURE: &[u8; 16] = b"StartupLocality\0";
+const GCE_NONHOST_INFO_SIGNATURE: &[u8; 16] = b"GCE NonHostInfo\0";
+const GCE_FIRMWARE_PREFIX: &str = "GCE Virtual Firmware v";
+
+/// An event of the log, with its SHA-256 digest if the log has that bank.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct TpmEvent {
+    pub pcr_index: u32,
+    pub event_type: u32,
+    pub sha256: Option<[u8; 32]>,
+    pub data: Vec<u8>,
+}
+
+/// Confidential computing technology of a GCE VM, from its NonHostInfo
+/// event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GceConfidentialTechnology {
+    None,
+    Sev,
+    SevEs,
+    Tdx,
+    SevSnp,
+    Unknown(u8),
+}
+
+impl From<u8> for GceConfidentialTechnology {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Self::None,
+            1 => Self::Sev,
+            2 => Self::SevEs,
+            3 => Self::Tdx,
+            4 => Self::SevSnp,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+/// A parsed crypto-agile event log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventLog {
+    /// The events after the Spec ID header event.
+    pub events: Vec<TpmEvent>,
+}
+
+impl EventLog {
+    pub fn parse(bytes: &[u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+
+        // The header event is in the SHA-1 only format and lists the digest
+        // sizes of the banks in the rest of the log.
+        let _pcr_index = read_u32_le(&mut reader)?;
+        let header_type = read_u32_le(&mut reader)?;
+        reader.skip(SHA1_SIZE)?;
+        let header_size = read_u32_le(&mut reader)?;
+        let header = reader.read_bytes(header_size as usize)?;
+        if header_type != event_type::NO_ACTION || !header.starts_with(SPEC_ID_SIGNATURE) {
+            eyre::bail!("not a crypto-agile event log");
+        }
+        let digest_sizes = parse_spec_id(&header)?;
+
+        let mut events = Vec::new();
+        while reader.remaining() > 0 {
+            let pcr_index = read_u32_le(&mut reader)?;
+            let event_type = read_u32_le(&mut reader)?;
+            let count = read_u32_le(&mut reader)?;
+            if count as usize > digest_sizes.len() {
+                eyre::bail!("event has more digests than the log has banks");
+            }
+            let mut sha256 = None;
+            for _ in 0..count {
+                let algorithm = read_u16_le(&mut reader)?;
+                let size = digest_sizes
+                    .get(&algorithm)
+                    .ok_or_else(|| eyre::eyre!("event digest of undeclared algorithm"))?;
+                let digest = reader.read_bytes(*size as usize)?;
+                if algorithm == TPM_ALG_SHA256 {
+                    sha256 = Some(
+                        digest
+                            .try_into()
+                            .map_err(|_| eyre::eyre!("malformed SHA-256 event digest"))?,
+                    );
+                }
+            }
+            let size = read_u32_le(&mut reader)?;
+            let data = reader.read_bytes(size as usize)?;
+            events.push(TpmEvent {
+                pcr_index,
+                event_type,
+                sha256,
+                data,
+            });
+        }
+        Ok(Self { events })
+    }
+
+    /// Replay the SHA-256 bank, returning the value of every PCR the log
+    /// extends.
+    pub fn replay_sha256(&self) -> eyre::Result<BTreeMap<u32, [u8; 32]>> {
+        let mut pcrs = BTreeMap::new();
+        for event in &self.events {
+            if event.event_type == event_type::NO_ACTION {
+                // Not extended, but may set the locality PCR 0 starts from.
+                if event.pcr_index == 0 && event.data.starts_with(STARTUP_LOCALITY_SIGNATURE) {
+                    let locality = *event
+                        .data
+                        .get(STARTUP_LOCALITY_SIGNATURE.len())
+                        .ok_or_else(|| eyre::eyre!("truncated StartupLocality event"))?;
+                    let mut initial = [0u8; 32];
+                    initial[31] = locality;
+                    pcrs.insert(0, initial);
+                }
+                continue;
+            }
+            let digest = event
+                .sha256
+                .ok_or_else(|| eyre::eyre!("event log has no SHA-256 bank"))?;
+            let pcr = pcrs.entry(event.pcr_index).or_insert([0u8; 32]);
+            *pcr = Sha256::new()
+                .chain_update(*pcr)
+                .chain_update(digest)
+                .finalize()
+                .into();
+        }
+        Ok(pcrs)
+    }
+
+    /// Check that replaying the log yields `pcrs`, the quoted SHA-256 values,
+    /// for every PCR the log extends.
+    pub fn verify_pcrs(&self, pcrs: &BTreeMap<u32, [u8; 32]>) -> eyre::Result<()> {
+        for (index, value) in self.replay_sha256()? {
+            if let Some(quoted) = pcrs.get(&index) {
+                if *quoted != value {
+                    eyre::bail!("event log does not match PCR {}", index);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The technology announced by the GCE NonHostInfo event, if any.
+    pub fn gce_confidential_technology(&self) -> Option<GceConfidentialTechnology> {
+        self.events
+            .iter()
+            .filter(|event| event.event_type == event_type::NONHOST_INFO)
+            .find_map(|event| {
+                let data = event.data.strip_prefix(GCE_NONHOST_INFO_SIGNATURE)?;
+                Some(GceConfidentialTechnology::from(*data.first()?))
+            })
+    }
+
+    /// The version of the GCE virtual firmware, from the S-CRTM version
+    /// event.
+    pub fn gce_firmware_version(&self) -> Option<u32> {
+        self.events
+            .iter()
+            .filter(|event| event.pcr_index == 0)
+            .filter(|event| event.event_type == event_type::S_CRTM_VERSION)
+            .find_map(|event| {
+                let version = decode_utf16(&event.data)?;
+                version.strip_prefix(GCE_FIRMWARE_PREFIX)?.parse().ok()
+            })
+    }
+}
+
+/// The digest size of every algorithm listed in a Spec ID event.
+fn parse_spec_id(event: &[u8]) -> eyre::Result<BTreeMap<u16, u16>> {
+    let mut reader = TssReader::new(event);
+    // signature, platformClass, specVersion{Minor,Major}, errata, uintnSize
+    reader.skip(SPEC_ID_SIGNATURE.len() + 4 + 4)?;
+    let count = read_u32_le(&mut reader)?;
+    let mut sizes = BTreeMap::new();
+    for _ in 0..count {
+        let algorithm = read_u16_le(&mut reader)?;
+        let size = read_u16_le(&mut reader)?;
+        sizes.insert(algorithm, size);
+    }
+    if sizes
+        .get(&TPM_ALG_SHA1)
+        .is_some_and(|size| *size as usize != SHA1_SIZE)
+        || sizes.get(&TPM_ALG_SHA256).is_some_and(|size| *size != 32)
+    {
+        eyre::bail!("Spec ID event declares unexpected digest sizes");
+    }
+    Ok(sizes)
+}
+
+fn decode_utf16(data: &[u8]) -> Option<String> {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|unit| *unit != 0)
+        .collect();
+    String::from_utf16(&units).ok()
+}
+
+fn read_u16_le(reader: &mut TssReader) -> Result<u16, TssError> {
+    Ok(u16::from_le_bytes(reader.read_array()?))
+}
+
+fn read_u32_le(reader: &mut TssReader) -> Result<u32, TssError> {
+    Ok(u32::from_le_bytes(reader.read_array()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use dcap::test_utils::{gce_tdx_log, EventLogBuilder};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_gce_log() -> eyre::Result<()> {
+        let log = EventLog::parse(&gce_tdx_log())?;
+        assert_eq!(log.events.len(), 4);
+        assert_eq!(
+            log.gce_confidential_technology(),
+            Some(GceConfidentialTechnology::Tdx)
+        );
+        assert_eq!(log.gce_firmware_version(), Some(2));
+        assert!(EventLog::parse(&gce_tdx_log()[4..]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay() -> eyre::Result<()> {
+        let log = EventLog::parse(&gce_tdx_log())?;
+        let pcrs = log.replay_sha256()?;
+        assert_eq!(pcrs.keys().copied().collect::<Vec<_>>(), vec![0, 7]);
+
+        let mut pcr0 = [0u8; 32];
+        pcr0[31] = 3;
+        for event in &log.events[1..3] {
+            pcr0 = Sha256::new()
+                .chain_update(pcr0)
+                .chain_update(event.sha256.unwrap())
+                .finalize()
+                .into();
+        }
+        assert_eq!(pcrs[&0], pcr0);
+        log.verify_pcrs(&pcrs)?;
+
+        let mut tampered = pcrs.clone();
+        tampered.insert(7, [0; 32]);
+        assert!(log.verify_pcrs(&tampered).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_extra_digests() {
+        let mut log = EventLogBuilder::new().build();
+        log.extend_from_slice(&7u32.to_le_bytes());
+        log.extend_from_slice(&0x80000001u32.to_le_bytes());
+        log.extend_from_slice(&3u32.to_le_bytes());
+        for _ in 0..3 {
+            log.extend_from_slice(&0x0bu16.to_le_bytes());
+            log.extend_from_slice(&[0; 32]);
+        }
+        log.extend_from_slice(&0u32.to_le_bytes());
+        assert!(EventLog::parse(&log).is_err());
+    }
+}
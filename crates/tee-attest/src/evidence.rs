@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::TpmBinding;
+
+/// Attestation evidence of one of the supported TEEs, in the formats the
+/// platforms produce them.
+///
+/// In JSON, evidence is tagged by `type` with its bytes hex encoded, e.g.
+/// `{ "type": "tdx_quote", "evidence": "0400..." }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "evidence", rename_all = "snake_case")]
+pub enum Evidence {
+    TpmQuote(TpmEvidence),
+    /// An SGX ECDSA quote.
+    SgxQuote(#[serde(with = "hex_bytes")] Vec<u8>),
+    /// A TDX ECDSA quote.
+    TdxQuote(#[serde(with = "hex_bytes")] Vec<u8>),
+    SevSnp(SnpEvidence),
+    /// A Nitro Enclaves attestation document.
+    Nitro(#[serde(with = "hex_bytes")] Vec<u8>),
+    TpmTdx(TpmTdxEvidence),
+}
+
+impl Evidence {
+    pub fn tee(&self) -> Tee {
+        match self {
+            Evidence::TpmQuote(_) => Tee::Tpm,
+            Evidence::SgxQuote(_) => Tee::Sgx,
+            Evidence::TdxQuote(_) => Tee::Tdx,
+            Evidence::SevSnp(_) => Tee::SevSnp,
+            Evidence::Nitro(_) => Tee::Nitro,
+            Evidence::TpmTdx(_) => Tee::TpmTdx,
+        }
+    }
+}
+
+/// A TPM2_Quote and the PCR values it covers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TpmEvidence {
+    /// The marshaled `TPMS_ATTEST`.
+    #[serde(with = "hex_bytes")]
+    pub attest: Vec<u8>,
+    /// The ECDSA P-256 signature of `attest`, raw `r || s`.
+    #[serde(with = "hex_bytes")]
+    pub signature: [u8; 64],
+    /// The DER certificate of the attestation key.
+    #[serde(with = "hex_bytes")]
+    pub ak_cert: Vec<u8>,
+    /// The SHA-256 PCR values the quote selects.
+    #[serde(with = "hex_pcrs")]
+    pub pcrs: BTreeMap<u32, [u8; 32]>,
+}
+
+/// A TPM quote and a TDX quote whose report data commits to it, see
+/// [`TpmBinding`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TpmTdxEvidence {
+    pub tpm: TpmEvidence,
+    #[serde(with = "hex_bytes")]
+    pub td_quote: Vec<u8>,
+    pub binding: TpmBinding,
+}
+
+/// An SEV-SNP attestation report and the DER certificates of its VCEK and
+/// ASK. The ARK is pinned by the [`Verifier`](crate::Verifier).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnpEvidence {
+    #[serde(with = "hex_bytes")]
+    pub report: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub vcek: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    pub ask: Vec<u8>,
+}
+
+/// The kind of TEE that produced some evidence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tee {
+    Tpm,
+    Sgx,
+    Tdx,
+    SevSnp,
+    Nitro,
+    /// A TPM and the TD it belongs to.
+    TpmTdx,
+}
+
+impl Tee {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Tee::Tpm => "tpm",
+            Tee::Sgx => "sgx",
+            Tee::Tdx => "tdx",
+            Tee::SevSnp => "sev-snp",
+            Tee::Nitro => "nitro",
+            Tee::TpmTdx => "tpm+tdx",
+        }
+    }
+}
+
+impl std::fmt::Display for Tee {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Hex (de)serialization of byte fields.
+pub(crate) mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bytes: impl AsRef<[u8]>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: TryFrom<Vec<u8>>,
+    {
+        let hex = String::deserialize(deserializer)?;
+        let bytes = hex::decode(hex).map_err(serde::de::Error::custom)?;
+        let size = bytes.len();
+        T::try_from(bytes)
+            .map_err(|_| serde::de::Error::custom(format!("unexpected length {}", size)))
+    }
+}
+
+/// Hex (de)serialization of PCR values by index.
+mod hex_pcrs {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        pcrs: &BTreeMap<u32, [u8; 32]>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        pcrs.iter()
+            .map(|(index, value)| (*index, hex::encode(value)))
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<u32, [u8; 32]>, D::Error> {
+        // Keys are strings in JSON, which the buffering of tagged enums
+        // does not convert to integers.
+        BTreeMap::<String, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(index, value)| {
+                let pcr = index
+                    .parse()
+                    .ok()
+                    .zip(
+                        hex::decode(value)
+                            .ok()
+                            .and_then(|value| value.try_into().ok()),
+                    )
+                    .ok_or_else(|| serde::de::Error::custom(format!("invalid PCR {}", index)))?;
+                Ok(pcr)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_roundtrip() -> eyre::Result<()> {
+        let evidence = Evidence::TpmQuote(TpmEvidence {
+            attest: vec![0xFF, 0x54],
+            signature: [1; 64],
+            ak_cert: vec![0x30],
+            pcrs: BTreeMap::from([(7, [7; 32])]),
+        });
+        let json = serde_json::to_value(&evidence)?;
+        assert_eq!(json["type"], "tpm_quote");
+        assert_eq!(json["evidence"]["pcrs"]["7"], "07".repeat(32));
+        assert_eq!(serde_json::from_value::<Evidence>(json.clone())?, evidence);
+
+        let composite: Evidence = serde_json::from_value(serde_json::json!({
+            "type": "tpm_tdx",
+            "evidence": {
+                "tpm": json["evidence"],
+                "td_quote": "0400",
+                "binding": "ak_name",
+            },
+        }))?;
+        assert_eq!(composite.tee(), Tee::TpmTdx);
+
+        let quote: Evidence =
+            serde_json::from_str(r#"{ "type": "tdx_quote", "evidence": "0400" }"#)?;
+        assert_eq!(quote, Evidence::TdxQuote(vec![4, 0]));
+        assert!(serde_json::from_str::<Evidence>(
+            r#"{ "type": "tpm_quote", "evidence": { "attest": "", "signature": "0101", "ak_cert": "", "pcrs": {} } }"#
+        )
+        .is_err());
+        Ok(())
+    }
+}
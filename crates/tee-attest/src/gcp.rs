@@ -0,0 +1,245 @@
+//! vTPM evidence of GCP confidential VMs.
+//!
+//! GCE provisions an attestation key (AK) template and a certificate for the
+//! key it yields in NV indices of the vTPM. The evidence is a quote of the
+//! SHA-256 PCRs by that AK, the firmware event log, which the quote makes
+//! trustworthy and which announces the confidential computing technology of
+//! the VM, and on TDX VMs a TD quote bound to the same nonce and AK.
+//!
+//! Checking that the AK certificate chains to Google's vTPM CA is left to the
+//! caller, as is the verification of the TD quote itself.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use dcap::{verify_tpm_quote, ReportData, TpmAttest, VerificationResult, NONCE_SIZE};
+use der::Decode;
+use tss_client::{handles, DeviceTransport, TpmSignature, Transport, TssClient};
+use x509_cert::Certificate;
+
+use crate::crypto::scalar;
+use crate::{
+    AttestationResponse, Attester, Challenge, EventLog, GceConfidentialTechnology,
+    BIOS_MEASUREMENTS_PATH, PROTOCOL_VERSION,
+};
+
+/// NV index of the certificate of the RSA AK.
+pub const AK_CERT_RSA_NV_INDEX: u32 = 0x01c10000;
+/// NV index of the template of the RSA AK.
+pub const AK_TEMPLATE_RSA_NV_INDEX: u32 = 0x01c10001;
+/// NV index of the certificate of the ECC AK.
+pub const AK_CERT_ECC_NV_INDEX: u32 = 0x01c10002;
+/// NV index of the template of the ECC AK.
+pub const AK_TEMPLATE_ECC_NV_INDEX: u32 = 0x01c10003;
+
+/// The PCRs quoted by default: those the firmware and boot loader extend.
+pub const DEFAULT_PCRS: [u32; 10] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9];
+
+/// Evidence of a GCP confidential VM.
+#[derive(Debug, Clone)]
+pub struct GcpEvidence {
+    /// DER certificate of the ECC AK.
+    pub ak_cert: Vec<u8>,
+    /// The quoted TPMS_ATTEST.
+    pub attest: Vec<u8>,
+    /// Raw `r || s` ECDSA signature of `attest`.
+    pub signature: [u8; 64],
+    /// The SHA-256 values of the quoted PCRs.
+    pub pcrs: BTreeMap<u32, [u8; 32]>,
+    pub event_log: Vec<u8>,
+    /// TD quote over [`ReportData::bind`] of the nonce and `ak_cert`, on TDX
+    /// VMs.
+    pub td_quote: Option<Vec<u8>>,
+}
+
+/// What verified [`GcpEvidence`] says about the VM.
+#[derive(Debug, Clone)]
+pub struct GcpAttestation {
+    pub attest: TpmAttest,
+    pub event_log: EventLog,
+    pub technology: Option<GceConfidentialTechnology>,
+    pub firmware_version: Option<u32>,
+}
+
+impl GcpEvidence {
+    /// Check the quote signature, that it answers `nonce` and covers the PCR
+    /// values, and that the event log replays to them.
+    pub fn verify(&self, nonce: &[u8; NONCE_SIZE]) -> eyre::Result<GcpAttestation> {
+        let ak_cert = Certificate::from_der(&self.ak_cert)?;
+        let attest = verify_tpm_quote(&self.attest, &self.signature, &ak_cert)?;
+        if attest.extra_data != nonce {
+            eyre::bail!("TPM quote does not answer the nonce");
+        }
+        attest.verify_pcrs(&self.pcrs)?;
+
+        let event_log = EventLog::parse(&self.event_log)?;
+        event_log.verify_pcrs(&self.pcrs)?;
+        Ok(GcpAttestation {
+            technology: event_log.gce_confidential_technology(),
+            firmware_version: event_log.gce_firmware_version(),
+            attest,
+            event_log,
+        })
+    }
+
+    /// Check that the verified TD quote `result` is bound to `nonce` and the
+    /// AK of this evidence.
+    pub fn verify_td_quote_binding(
+        &self,
+        result: &VerificationResult,
+        nonce: &[u8; NONCE_SIZE],
+    ) -> eyre::Result<()> {
+        result.verify_binding(Some(nonce), Some(&self.ak_cert))
+    }
+}
+
+/// Collects evidence through the vTPM of a GCP confidential VM.
+pub struct GcpVtpm<T> {
+    tpm: TssClient<T>,
+    event_log_path: PathBuf,
+}
+
+impl GcpVtpm<DeviceTransport> {
+    /// Use the vTPM through the kernel resource manager.
+    pub fn open() -> eyre::Result<Self> {
+        Ok(Self::new(TssClient::new(DeviceTransport::open_default()?)))
+    }
+}
+
+impl<T: Transport> GcpVtpm<T> {
+    pub fn new(tpm: TssClient<T>) -> Self {
+        Self {
+            tpm,
+            event_log_path: BIOS_MEASUREMENTS_PATH.into(),
+        }
+    }
+
+    pub fn with_event_log_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.event_log_path = path.into();
+        self
+    }
+
+    /// Quote `pcrs` with `nonce`, and collect the rest of the evidence.
+    pub fn attest(&mut self, nonce: &[u8; NONCE_SIZE], pcrs: &[u32]) -> eyre::Result<GcpEvidence> {
+        let ak_cert = self.tpm.nv_read(AK_CERT_ECC_NV_INDEX)?;
+        let template = self.tpm.nv_read(AK_TEMPLATE_ECC_NV_INDEX)?;
+        let ak = self.tpm.create_primary(handles::ENDORSEMENT, &template)?;
+        let quote = self.tpm.quote(ak, nonce, pcrs);
+        self.tpm.flush_context(ak)?;
+        let quote = quote?;
+        let TpmSignature::Ecdsa { r, s, .. } = quote.signature else {
+            eyre::bail!("the AK did not return an ECDSA signature");
+        };
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&scalar(&r)?);
+        signature[32..].copy_from_slice(&scalar(&s)?);
+
+        let mut sorted = pcrs.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let values = self.tpm.read_pcrs_sha256(&sorted)?;
+        let event_log = std::fs::read(&self.event_log_path)?;
+        let td_quote = td_quote(&ReportData::bind(Some(nonce), Some(&ak_cert)))?;
+        Ok(GcpEvidence {
+            ak_cert,
+            attest: quote.attest,
+            signature,
+            pcrs: sorted.into_iter().zip(values).collect(),
+            event_log,
+            td_quote,
+        })
+    }
+}
+
+impl<T: Transport> Attester for GcpVtpm<T> {
+    /// Answer `challenge` with a quote of [`DEFAULT_PCRS`].
+    fn respond(&mut self, challenge: &Challenge) -> eyre::Result<AttestationResponse> {
+        if challenge.version != PROTOCOL_VERSION {
+            eyre::bail!("unsupported protocol version {}", challenge.version);
+        }
+        let evidence = self.attest(&challenge.nonce, &DEFAULT_PCRS)?;
+        Ok(AttestationResponse {
+            version: PROTOCOL_VERSION,
+            nonce: challenge.nonce,
+            ak_cert: evidence.ak_cert,
+            attest: evidence.attest,
+            signature: evidence.signature,
+            pcrs: evidence.pcrs,
+            event_log: evidence.event_log,
+            td_quote: evidence.td_quote,
+        })
+    }
+}
+
+/// A TD quote over `report_data`, if this is a TDX VM.
+#[cfg(target_os = "linux")]
+fn td_quote(report_data: &ReportData) -> eyre::Result<Option<Vec<u8>>> {
+    let tsm = dcap::tsm::blocking::TsmReport::new();
+    if !tsm.is_available() {
+        return Ok(None);
+    }
+    Ok(Some(tsm.get_quote(&report_data.0)?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn td_quote(_report_data: &ReportData) -> eyre::Result<Option<Vec<u8>>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use dcap::test_utils::{gce_tdx_log, tpm_attest, TestPki};
+    use der::Encode;
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::Signature;
+
+    use super::*;
+
+    fn evidence(pki: &TestPki, nonce: &[u8; NONCE_SIZE]) -> eyre::Result<GcpEvidence> {
+        let event_log = gce_tdx_log();
+        let mut pcrs = EventLog::parse(&event_log)?.replay_sha256()?;
+        pcrs.insert(4, [4; 32]);
+        let attest = tpm_attest(nonce, &pcrs);
+        let signature: Signature = pki.pck_key.sign(&attest);
+        Ok(GcpEvidence {
+            ak_cert: pki.pck_cert.to_der()?,
+            attest,
+            signature: signature.to_bytes().into(),
+            pcrs,
+            event_log,
+            td_quote: None,
+        })
+    }
+
+    #[test]
+    fn test_verify_evidence() -> eyre::Result<()> {
+        let pki = TestPki::new();
+        let nonce = [7u8; NONCE_SIZE];
+        let evidence = evidence(&pki, &nonce)?;
+        let attestation = evidence.verify(&nonce)?;
+        assert_eq!(attestation.technology, Some(GceConfidentialTechnology::Tdx));
+        assert_eq!(attestation.firmware_version, Some(2));
+
+        assert!(evidence.verify(&[8; NONCE_SIZE]).is_err());
+        let mut tampered = evidence.clone();
+        tampered.pcrs.insert(0, [0; 32]);
+        assert!(tampered.verify(&nonce).is_err());
+
+        // A consistent quote of PCRs the event log does not replay to.
+        tampered.attest = tpm_attest(&nonce, &tampered.pcrs);
+        let signature: Signature = pki.pck_key.sign(&tampered.attest);
+        tampered.signature = signature.to_bytes().into();
+        let err = tampered.verify(&nonce).unwrap_err();
+        assert!(err.to_string().contains("event log"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_scalar_padding() -> eyre::Result<()> {
+        let mut expected = [0u8; 32];
+        expected[31] = 1;
+        assert_eq!(scalar(&[1])?, expected);
+        assert!(scalar(&[0; 33]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,393 @@
+//! Linux IMA measurement lists, as exposed by the kernel in
+//! `/sys/kernel/security/ima/`.
+//!
+//! IMA extends a digest of every measured file into PCR 10 at runtime.
+//! Replaying the list against the quoted PCR 10 authenticates it, after
+//! which the per-file digests can be appraised the way the firmware event
+//! log is for boot.
+//!
+//! The `ima-ng` and `ima-sig` templates are supported; the legacy `ima`
+//! template, which only carries SHA-1 digests, is not.
+
+use std::collections::BTreeMap;
+
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tss_serde::TssReader;
+
+/// Where Linux exposes the binary measurement list.
+pub const IMA_BINARY_MEASUREMENTS_PATH: &str =
+    "/sys/kernel/security/ima/binary_runtime_measurements";
+/// Where Linux exposes the ASCII measurement list.
+pub const IMA_ASCII_MEASUREMENTS_PATH: &str = "/sys/kernel/security/ima/ascii_runtime_measurements";
+/// The PCR IMA extends by default.
+pub const IMA_PCR: u32 = 10;
+
+const TEMPLATE_IMA_NG: &str = "ima-ng";
+const TEMPLATE_IMA_SIG: &str = "ima-sig";
+const MAX_TEMPLATE_NAME: usize = 16;
+
+/// The digest of a measured file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImaDigest {
+    /// The kernel name of the hash algorithm, e.g. `sha256`.
+    pub algorithm: String,
+    pub digest: Vec<u8>,
+}
+
+/// An entry of the measurement list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImaEntry {
+    pub pcr_index: u32,
+    /// The SHA-1 digest of `template_data`, or zeros for a violation.
+    pub template_digest: [u8; 20],
+    pub template_name: String,
+    /// The template fields, each prefixed with its little-endian length.
+    pub template_data: Vec<u8>,
+    pub file_digest: ImaDigest,
+    pub file_name: String,
+    /// The `security.ima` signature of `ima-sig` entries.
+    pub signature: Option<Vec<u8>>,
+}
+
+impl ImaEntry {
+    /// Whether the entry records a violation, e.g. a file measured while
+    /// open for writing. Violations extend all ones instead of a digest.
+    pub fn is_violation(&self) -> bool {
+        self.template_digest == [0; 20]
+    }
+
+    fn from_template(
+        pcr_index: u32,
+        template_digest: [u8; 20],
+        template_name: String,
+        template_data: Vec<u8>,
+    ) -> eyre::Result<Self> {
+        if template_name != TEMPLATE_IMA_NG && template_name != TEMPLATE_IMA_SIG {
+            eyre::bail!("unsupported IMA template {}", template_name);
+        }
+        if template_digest != [0; 20] && Sha1::digest(&template_data)[..] != template_digest {
+            eyre::bail!("IMA template digest does not match its data");
+        }
+
+        let mut reader = TssReader::new(&template_data);
+        let mut field = || -> eyre::Result<Vec<u8>> {
+            let size = u32::from_le_bytes(reader.read_array()?);
+            Ok(reader.read_bytes(size as usize)?)
+        };
+        let file_digest = parse_digest_field(&field()?)?;
+        let file_name = field()?;
+        let file_name = file_name.strip_suffix(b"\0").unwrap_or(&file_name);
+        let file_name = String::from_utf8_lossy(file_name).into_owned();
+        let signature = if template_name == TEMPLATE_IMA_SIG {
+            Some(field()?).filter(|signature| !signature.is_empty())
+        } else {
+            None
+        };
+        if reader.remaining() > 0 {
+            eyre::bail!("trailing bytes in IMA template data");
+        }
+
+        Ok(Self {
+            pcr_index,
+            template_digest,
+            template_name,
+            template_data,
+            file_digest,
+            file_name,
+            signature,
+        })
+    }
+}
+
+/// A parsed IMA measurement list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImaLog {
+    pub entries: Vec<ImaEntry>,
+}
+
+impl ImaLog {
+    /// Parse the binary list, in the little-endian format of the kernel.
+    pub fn parse_binary(bytes: &[u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+        let mut entries = Vec::new();
+        while reader.remaining() > 0 {
+            let pcr_index = u32::from_le_bytes(reader.read_array()?);
+            let template_digest = reader.read_array()?;
+            let name_size = u32::from_le_bytes(reader.read_array()?) as usize;
+            if name_size > MAX_TEMPLATE_NAME {
+                eyre::bail!("IMA template name too long");
+            }
+            let template_name = String::from_utf8(reader.read_bytes(name_size)?)
+                .map_err(|_| eyre::eyre!("invalid IMA template name"))?;
+            let data_size = u32::from_le_bytes(reader.read_array()?);
+            let template_data = reader.read_bytes(data_size as usize)?;
+            entries.push(ImaEntry::from_template(
+                pcr_index,
+                template_digest,
+                template_name,
+                template_data,
+            )?);
+        }
+        Ok(Self { entries })
+    }
+
+    /// Parse the ASCII list, one
+    /// `<pcr> <template digest> <template> <alg>:<digest> <file> [<signature>]`
+    /// line per entry.
+    pub fn parse_ascii(text: &str) -> eyre::Result<Self> {
+        let entries = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                parse_ascii_line(line)
+                    .map_err(|err| err.wrap_err(format!("IMA list line {}", number + 1)))
+            })
+            .collect::<eyre::Result<_>>()?;
+        Ok(Self { entries })
+    }
+
+    /// Replay the SHA-1 bank, returning the value of every PCR the list
+    /// extends.
+    pub fn replay_sha1(&self) -> BTreeMap<u32, [u8; 20]> {
+        let mut pcrs = BTreeMap::new();
+        for entry in &self.entries {
+            let digest = if entry.is_violation() {
+                [0xFF; 20]
+            } else {
+                entry.template_digest
+            };
+            let pcr = pcrs.entry(entry.pcr_index).or_insert([0u8; 20]);
+            *pcr = Sha1::new()
+                .chain_update(*pcr)
+                .chain_update(digest)
+                .finalize()
+                .into();
+        }
+        pcrs
+    }
+
+    /// Replay the SHA-256 bank, returning the value of every PCR the list
+    /// extends.
+    pub fn replay_sha256(&self) -> BTreeMap<u32, [u8; 32]> {
+        let mut pcrs = BTreeMap::new();
+        for entry in &self.entries {
+            let digest = if entry.is_violation() {
+                [0xFF; 32]
+            } else {
+                Sha256::digest(&entry.template_data).into()
+            };
+            let pcr = pcrs.entry(entry.pcr_index).or_insert([0u8; 32]);
+            *pcr = Sha256::new()
+                .chain_update(*pcr)
+                .chain_update(digest)
+                .finalize()
+                .into();
+        }
+        pcrs
+    }
+
+    /// Check that replaying the list yields `pcrs`, the quoted SHA-256
+    /// values, for every PCR the list extends.
+    ///
+    /// The list may have grown since the quote; callers should read it
+    /// after quoting and retry on a mismatch.
+    pub fn verify_pcrs(&self, pcrs: &BTreeMap<u32, [u8; 32]>) -> eyre::Result<()> {
+        for (index, value) in self.replay_sha256() {
+            match pcrs.get(&index) {
+                Some(quoted) if *quoted == value => {}
+                Some(_) => eyre::bail!("IMA measurement list does not match PCR {}", index),
+                None => eyre::bail!("IMA measurement list extends unquoted PCR {}", index),
+            }
+        }
+        Ok(())
+    }
+
+    /// The digest of every measured file, by path. A file measured several
+    /// times maps to its last measurement.
+    pub fn file_digests(&self) -> BTreeMap<&str, &ImaDigest> {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.is_violation())
+            .map(|entry| (entry.file_name.as_str(), &entry.file_digest))
+            .collect()
+    }
+}
+
+fn parse_ascii_line(line: &str) -> eyre::Result<ImaEntry> {
+    let mut fields = line.split(' ');
+    let mut next = |name: &str| fields.next().ok_or_else(|| eyre::eyre!("missing {}", name));
+    let pcr_index = next("PCR")?
+        .parse()
+        .map_err(|_| eyre::eyre!("invalid PCR"))?;
+    let template_digest = hex::decode(next("template digest")?)?
+        .try_into()
+        .map_err(|_| eyre::eyre!("template digest must be 20 bytes"))?;
+    let template_name = next("template name")?.to_string();
+    let (algorithm, digest) = next("file digest")?
+        .split_once(':')
+        .ok_or_else(|| eyre::eyre!("file digest has no algorithm"))?;
+    let digest = hex::decode(digest)?;
+    let rest = fields.collect::<Vec<_>>();
+    // File names may contain spaces; an ima-sig signature is the last field.
+    let (file_name, signature) = match (template_name.as_str(), rest.split_last()) {
+        (TEMPLATE_IMA_SIG, Some((last, name))) if !name.is_empty() => match hex::decode(last) {
+            Ok(signature) => (name.join(" "), Some(signature)),
+            Err(_) => (rest.join(" "), None),
+        },
+        _ => (rest.join(" "), None),
+    };
+    if file_name.is_empty() {
+        eyre::bail!("missing file name");
+    }
+
+    // Rebuild the template data the digests are over.
+    let mut digest_field = format!("{}:\0", algorithm).into_bytes();
+    digest_field.extend_from_slice(&digest);
+    let mut name_field = file_name.into_bytes();
+    name_field.push(0);
+    let mut fields = vec![digest_field, name_field];
+    if template_name == TEMPLATE_IMA_SIG {
+        fields.push(signature.unwrap_or_default());
+    }
+    let mut template_data = Vec::new();
+    for field in fields {
+        template_data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+        template_data.extend_from_slice(&field);
+    }
+    ImaEntry::from_template(pcr_index, template_digest, template_name, template_data)
+}
+
+/// Parse a `d-ng` field, `<alg>:\0<digest>`.
+fn parse_digest_field(field: &[u8]) -> eyre::Result<ImaDigest> {
+    let separator = field
+        .windows(2)
+        .position(|window| window == b":\0")
+        .ok_or_else(|| eyre::eyre!("IMA file digest has no algorithm"))?;
+    let algorithm = std::str::from_utf8(&field[..separator])
+        .map_err(|_| eyre::eyre!("invalid IMA digest algorithm"))?;
+    Ok(ImaDigest {
+        algorithm: algorithm.to_string(),
+        digest: field[separator + 2..].to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The template data of an `ima-ng` entry.
+    fn ima_ng(file_name: &str, contents: &[u8]) -> Vec<u8> {
+        let mut digest = b"sha256:\0".to_vec();
+        digest.extend_from_slice(&Sha256::digest(contents));
+        let mut name = file_name.as_bytes().to_vec();
+        name.push(0);
+        let mut data = Vec::new();
+        for field in [digest, name] {
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(&field);
+        }
+        data
+    }
+
+    fn binary_entry(template_data: &[u8], violation: bool) -> Vec<u8> {
+        let mut entry = IMA_PCR.to_le_bytes().to_vec();
+        if violation {
+            entry.extend_from_slice(&[0; 20]);
+        } else {
+            entry.extend_from_slice(&Sha1::digest(template_data));
+        }
+        entry.extend_from_slice(&(TEMPLATE_IMA_NG.len() as u32).to_le_bytes());
+        entry.extend_from_slice(TEMPLATE_IMA_NG.as_bytes());
+        entry.extend_from_slice(&(template_data.len() as u32).to_le_bytes());
+        entry.extend_from_slice(template_data);
+        entry
+    }
+
+    fn binary_log() -> Vec<u8> {
+        [
+            binary_entry(&ima_ng("boot_aggregate", b""), false),
+            binary_entry(&ima_ng("/usr/bin/init", b"init"), false),
+            binary_entry(&ima_ng("/var/log/app log", b"log"), true),
+            binary_entry(&ima_ng("/usr/bin/init", b"init v2"), false),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_parse_binary() -> eyre::Result<()> {
+        let log = ImaLog::parse_binary(&binary_log())?;
+        assert_eq!(log.entries.len(), 4);
+        assert!(log.entries[2].is_violation());
+        assert_eq!(log.entries[2].file_name, "/var/log/app log");
+
+        let digests = log.file_digests();
+        assert_eq!(digests.len(), 2);
+        assert_eq!(digests["/usr/bin/init"].algorithm, "sha256");
+        assert_eq!(
+            digests["/usr/bin/init"].digest,
+            Sha256::digest(b"init v2").to_vec()
+        );
+
+        let mut tampered = binary_log();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(ImaLog::parse_binary(&tampered).is_err());
+        assert!(ImaLog::parse_binary(&binary_log()[..10]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay() -> eyre::Result<()> {
+        let log = ImaLog::parse_binary(&binary_log())?;
+        let pcrs = log.replay_sha256();
+        let mut pcr10 = [0u8; 32];
+        for entry in &log.entries {
+            let digest: [u8; 32] = if entry.is_violation() {
+                [0xFF; 32]
+            } else {
+                Sha256::digest(&entry.template_data).into()
+            };
+            pcr10 = Sha256::new()
+                .chain_update(pcr10)
+                .chain_update(digest)
+                .finalize()
+                .into();
+        }
+        assert_eq!(pcrs, BTreeMap::from([(IMA_PCR, pcr10)]));
+        assert_eq!(log.replay_sha1().len(), 1);
+        log.verify_pcrs(&pcrs)?;
+
+        let mut tampered = pcrs.clone();
+        tampered.insert(IMA_PCR, [0; 32]);
+        assert!(log.verify_pcrs(&tampered).is_err());
+        assert!(log.verify_pcrs(&BTreeMap::new()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_ascii() -> eyre::Result<()> {
+        let binary = ImaLog::parse_binary(&binary_log())?;
+        let ascii = binary
+            .entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{} {} {} {}:{} {}\n",
+                    entry.pcr_index,
+                    hex::encode(entry.template_digest),
+                    entry.template_name,
+                    entry.file_digest.algorithm,
+                    hex::encode(&entry.file_digest.digest),
+                    entry.file_name,
+                )
+            })
+            .collect::<String>();
+        assert_eq!(ImaLog::parse_ascii(&ascii)?, binary);
+
+        let tampered = ascii.replacen("boot_aggregate", "boot_aggregatf", 1);
+        assert!(ImaLog::parse_ascii(&tampered).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+//! Blocking variant of [`KbsClient`](super::KbsClient).
+
+use super::{KbsAttester, TeePubKey};
+use crate::runtime::BlockingRuntime;
+
+/// Blocking KBS client, running the async [`KbsClient`](super::KbsClient) on
+/// a runtime of its own.
+pub struct KbsClient {
+    client: super::KbsClient,
+    runtime: BlockingRuntime,
+}
+
+impl KbsClient {
+    /// A client for the KBS at `url`, e.g. `https://kbs:8080`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: super::KbsClient::new(url),
+            runtime: BlockingRuntime::new(),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        self.client.url()
+    }
+
+    /// The public half of the TEE key resources are encrypted to.
+    pub fn tee_pubkey(&self) -> &TeePubKey {
+        self.client.tee_pubkey()
+    }
+
+    /// The token of the last attestation.
+    pub fn token(&self) -> Option<&str> {
+        self.client.token()
+    }
+
+    /// Run the handshake with evidence from `attester` and return the token
+    /// the KBS issues.
+    pub fn attest(&mut self, attester: &mut dyn KbsAttester) -> eyre::Result<String> {
+        self.runtime.block_on(self.client.attest(attester))
+    }
+
+    /// Fetch and decrypt the resource at `path`, e.g.
+    /// `default/key/workload`.
+    pub fn get_resource(&self, path: &str) -> eyre::Result<Vec<u8>> {
+        self.runtime.block_on(self.client.get_resource(path))
+    }
+}
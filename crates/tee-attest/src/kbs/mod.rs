@@ -0,0 +1,507 @@
+//! Client for the Key Broker Service (KBS) of Confidential Containers.
+//!
+//! A workload fetches secrets from a KBS in the RCAR handshake: it requests
+//! a challenge for its TEE type, answers it with evidence binding the nonce
+//! and an ephemeral public key, receives an attestation token, and then
+//! retrieves resources, which the KBS returns as a JWE encrypted to that
+//! key. The key is an ECDH P-256 key (`ECDH-ES+A256KW` with `A256GCM`), so
+//! only the attested workload can decrypt the resources.
+//!
+//! Evidence comes from a [`KbsAttester`]. [`TdxAttester`] produces the TDX
+//! evidence of the CoCo attestation agent from any source of TD quotes.
+
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use base64ct::{Base64, Base64UrlUnpadded, Encoding};
+use p256::ecdh::EphemeralSecret;
+use p256::elliptic_curve::sec1::{EncodedPoint, FromEncodedPoint, ToEncodedPoint};
+use p256::{NistP256, PublicKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256, Sha384};
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+/// Version of the KBS protocol spoken.
+pub const KBS_PROTOCOL_VERSION: &str = "0.1.0";
+
+/// Cookie of the KBS session a challenge belongs to.
+pub const KBS_SESSION_COOKIE: &str = "kbs-session-id";
+
+/// Key management algorithm of the TEE key.
+pub const KBS_KEY_ALG: &str = "ECDH-ES+A256KW";
+
+/// Content encryption algorithm of the resources.
+pub const KBS_CONTENT_ALG: &str = "A256GCM";
+
+/// A source of evidence the KBS can verify.
+pub trait KbsAttester {
+    /// The TEE type the KBS knows the evidence by, e.g. `tdx`.
+    fn tee(&self) -> &str;
+
+    /// Evidence with `report_data` bound into it, as the JSON the verifier
+    /// of the KBS expects for [`tee`](Self::tee).
+    fn evidence(&mut self, report_data: &[u8; 64]) -> eyre::Result<Value>;
+}
+
+/// TDX evidence as produced by the CoCo attestation agent, over TD quotes
+/// from `quote`, e.g. `TsmReport::get_quote` of configfs-tsm.
+pub struct TdxAttester<F> {
+    quote: F,
+    event_log: Option<Vec<u8>>,
+}
+
+impl<F: FnMut(&[u8; 64]) -> eyre::Result<Vec<u8>>> TdxAttester<F> {
+    pub fn new(quote: F) -> Self {
+        Self {
+            quote,
+            event_log: None,
+        }
+    }
+
+    /// Submit the CCEL event log along with the quotes.
+    pub fn with_event_log(mut self, event_log: Vec<u8>) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+}
+
+impl<F: FnMut(&[u8; 64]) -> eyre::Result<Vec<u8>>> KbsAttester for TdxAttester<F> {
+    fn tee(&self) -> &str {
+        "tdx"
+    }
+
+    fn evidence(&mut self, report_data: &[u8; 64]) -> eyre::Result<Value> {
+        let quote = (self.quote)(report_data)?;
+        Ok(serde_json::json!({
+            "cc_eventlog": self.event_log.as_ref().map(|log| Base64::encode_string(log)),
+            "quote": Base64::encode_string(&quote),
+        }))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AuthRequest<'a> {
+    version: &'a str,
+    tee: &'a str,
+    #[serde(rename = "extra-params")]
+    extra_params: &'a str,
+}
+
+/// The challenge of the KBS.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct KbsChallenge {
+    pub nonce: String,
+    #[serde(rename = "extra-params", default)]
+    pub extra_params: Value,
+}
+
+/// The public half of the TEE key, as a JWK.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TeePubKey {
+    pub kty: String,
+    pub crv: String,
+    pub alg: String,
+    pub x: String,
+    pub y: String,
+}
+
+impl TeePubKey {
+    fn from_key(key: &PublicKey) -> Self {
+        let point = key.to_encoded_point(false);
+        Self {
+            kty: "EC".to_string(),
+            crv: "P-256".to_string(),
+            alg: KBS_KEY_ALG.to_string(),
+            x: Base64UrlUnpadded::encode_string(point.x().expect("uncompressed point")),
+            y: Base64UrlUnpadded::encode_string(point.y().expect("uncompressed point")),
+        }
+    }
+
+    fn to_key(&self) -> eyre::Result<PublicKey> {
+        if self.kty != "EC" || self.crv != "P-256" {
+            eyre::bail!("unsupported key {} {}", self.kty, self.crv);
+        }
+        let coordinate = |value: &str| -> eyre::Result<[u8; 32]> {
+            Base64UrlUnpadded::decode_vec(value)
+                .map_err(|err| eyre::eyre!("invalid key coordinate: {}", err))?
+                .try_into()
+                .map_err(|_| eyre::eyre!("invalid key coordinate length"))
+        };
+        let point = EncodedPoint::<NistP256>::from_affine_coordinates(
+            &coordinate(&self.x)?.into(),
+            &coordinate(&self.y)?.into(),
+            false,
+        );
+        Option::from(PublicKey::from_encoded_point(&point))
+            .ok_or_else(|| eyre::eyre!("key is not on P-256"))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AttestRequest<'a> {
+    #[serde(rename = "tee-pubkey")]
+    tee_pubkey: &'a TeePubKey,
+    /// The evidence, as a JSON string.
+    #[serde(rename = "tee-evidence")]
+    tee_evidence: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AttestResponse {
+    token: String,
+}
+
+/// The report data binding the nonce of `challenge` and `tee_pubkey`: the
+/// SHA-384 of their JSON with sorted keys, zero-padded, as the KBS computes
+/// it.
+pub fn kbs_report_data(challenge: &KbsChallenge, tee_pubkey: &TeePubKey) -> [u8; 64] {
+    let runtime_data = serde_json::json!({
+        "nonce": challenge.nonce,
+        "tee-pubkey": tee_pubkey,
+    });
+    let mut report_data = [0u8; 64];
+    report_data[..48].copy_from_slice(&Sha384::digest(runtime_data.to_string().as_bytes()));
+    report_data
+}
+
+/// A resource as returned by the KBS: a JWE in flattened JSON
+/// serialization, encrypted to the TEE key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KbsResponse {
+    pub protected: String,
+    pub encrypted_key: String,
+    pub iv: String,
+    pub ciphertext: String,
+    pub tag: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProtectedHeader {
+    alg: String,
+    enc: String,
+    epk: TeePubKey,
+    #[serde(default)]
+    apu: Option<String>,
+    #[serde(default)]
+    apv: Option<String>,
+}
+
+/// The ephemeral key resources are encrypted to.
+struct TeeKey {
+    secret: EphemeralSecret,
+    public: TeePubKey,
+}
+
+impl TeeKey {
+    fn generate() -> Self {
+        let secret = EphemeralSecret::random(&mut p256::elliptic_curve::rand_core::OsRng);
+        let public = TeePubKey::from_key(&secret.public_key());
+        Self { secret, public }
+    }
+
+    fn decrypt(&self, response: &KbsResponse) -> eyre::Result<Vec<u8>> {
+        let decode = |field: &str, value: &str| {
+            Base64UrlUnpadded::decode_vec(value)
+                .map_err(|err| eyre::eyre!("invalid JWE {}: {}", field, err))
+        };
+        let header: ProtectedHeader =
+            serde_json::from_slice(&decode("protected header", &response.protected)?)?;
+        if header.alg != KBS_KEY_ALG || header.enc != KBS_CONTENT_ALG {
+            eyre::bail!("unsupported JWE algorithms {} {}", header.alg, header.enc);
+        }
+        let shared = self.secret.diffie_hellman(&header.epk.to_key()?);
+        let apu = decode("apu", header.apu.as_deref().unwrap_or(""))?;
+        let apv = decode("apv", header.apv.as_deref().unwrap_or(""))?;
+        let kek: [u8; 32] = concat_kdf(shared.raw_secret_bytes(), KBS_KEY_ALG, &apu, &apv);
+
+        let mut cek = [0u8; 32];
+        aes_kw::KekAes256::from(kek)
+            .unwrap(&decode("encrypted key", &response.encrypted_key)?, &mut cek)
+            .map_err(|_| eyre::eyre!("cannot unwrap the JWE content key"))?;
+        let iv: [u8; 12] = decode("iv", &response.iv)?
+            .try_into()
+            .map_err(|iv: Vec<u8>| eyre::eyre!("invalid JWE iv length {}", iv.len()))?;
+        let mut ciphertext = decode("ciphertext", &response.ciphertext)?;
+        ciphertext.extend_from_slice(&decode("tag", &response.tag)?);
+        Aes256Gcm::new(&cek.into())
+            .decrypt(
+                &Nonce::from(iv),
+                Payload {
+                    msg: &ciphertext,
+                    aad: response.protected.as_bytes(),
+                },
+            )
+            .map_err(|_| eyre::eyre!("cannot decrypt the JWE"))
+    }
+}
+
+/// The single-round Concat KDF of RFC 7518 section 4.6, deriving a key of
+/// up to 256 bits.
+fn concat_kdf<const N: usize>(secret: &[u8], alg: &str, apu: &[u8], apv: &[u8]) -> [u8; N] {
+    let mut hasher = Sha256::new();
+    hasher.update(1u32.to_be_bytes());
+    hasher.update(secret);
+    for field in [alg.as_bytes(), apu, apv] {
+        hasher.update((field.len() as u32).to_be_bytes());
+        hasher.update(field);
+    }
+    hasher.update((N as u32 * 8).to_be_bytes());
+    hasher.finalize()[..N]
+        .try_into()
+        .expect("key of at most 256 bits")
+}
+
+/// Async KBS client.
+///
+/// A client attests once and then fetches any number of resources with the
+/// token it was issued, until [`attest`](Self::attest) is called again.
+pub struct KbsClient {
+    client: reqwest::Client,
+    url: String,
+    key: TeeKey,
+    token: Option<String>,
+}
+
+impl KbsClient {
+    /// A client for the KBS at `url`, e.g. `https://kbs:8080`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into().trim_end_matches('/').to_string(),
+            key: TeeKey::generate(),
+            token: None,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The public half of the TEE key resources are encrypted to.
+    pub fn tee_pubkey(&self) -> &TeePubKey {
+        &self.key.public
+    }
+
+    /// The token of the last attestation.
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// Run the handshake with evidence from `attester` and return the token
+    /// the KBS issues.
+    pub async fn attest(&mut self, attester: &mut dyn KbsAttester) -> eyre::Result<String> {
+        let response = self
+            .client
+            .post(format!("{}/kbs/v0/auth", self.url))
+            .json(&AuthRequest {
+                version: KBS_PROTOCOL_VERSION,
+                tee: attester.tee(),
+                extra_params: "",
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+        let session = session_cookie(response.headers())?;
+        let challenge: KbsChallenge = response.json().await?;
+
+        let report_data = kbs_report_data(&challenge, &self.key.public);
+        let evidence = attester.evidence(&report_data)?;
+        let response = self
+            .client
+            .post(format!("{}/kbs/v0/attest", self.url))
+            .header(reqwest::header::COOKIE, session)
+            .json(&AttestRequest {
+                tee_pubkey: &self.key.public,
+                tee_evidence: evidence.to_string(),
+            })
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            eyre::bail!(
+                "KBS rejected the evidence: {} {}",
+                response.status(),
+                response.text().await.unwrap_or_default()
+            );
+        }
+        let token = response.json::<AttestResponse>().await?.token;
+        self.token = Some(token.clone());
+        Ok(token)
+    }
+
+    /// Fetch and decrypt the resource at `path`, e.g.
+    /// `default/key/workload`.
+    pub async fn get_resource(&self, path: &str) -> eyre::Result<Vec<u8>> {
+        let token = self
+            .token
+            .as_ref()
+            .ok_or_else(|| eyre::eyre!("not attested to the KBS"))?;
+        let response = self
+            .client
+            .get(resource_url(&self.url, path)?)
+            .bearer_auth(token)
+            .send()
+            .await?
+            .error_for_status()?;
+        self.key.decrypt(&response.json().await?)
+    }
+}
+
+/// The `Cookie` header value of the session the KBS started.
+fn session_cookie(headers: &reqwest::header::HeaderMap) -> eyre::Result<String> {
+    headers
+        .get_all(reqwest::header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|cookie| cookie.split(';').next())
+        .find(|pair| pair.trim().starts_with(&format!("{}=", KBS_SESSION_COOKIE)))
+        .map(|pair| pair.trim().to_string())
+        .ok_or_else(|| eyre::eyre!("KBS did not start a session"))
+}
+
+/// The URL of the resource at `path`, `<repository>/<type>/<tag>`.
+fn resource_url(url: &str, path: &str) -> eyre::Result<String> {
+    let path = path.trim_matches('/');
+    let segments: Vec<&str> = path.split('/').collect();
+    if segments.len() != 3 || segments.iter().any(|segment| segment.is_empty()) {
+        eyre::bail!("invalid KBS resource path {:?}", path);
+    }
+    Ok(format!("{}/kbs/v0/resource/{}", url, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue, SET_COOKIE};
+    use serde_json::json;
+
+    /// Encrypt `plaintext` to `tee_pubkey` as the KBS does.
+    fn encrypt(tee_pubkey: &TeePubKey, plaintext: &[u8]) -> KbsResponse {
+        let ephemeral = TeeKey::generate();
+        let protected = Base64UrlUnpadded::encode_string(
+            json!({"alg": KBS_KEY_ALG, "enc": KBS_CONTENT_ALG, "epk": ephemeral.public})
+                .to_string()
+                .as_bytes(),
+        );
+        let shared = ephemeral
+            .secret
+            .diffie_hellman(&tee_pubkey.to_key().unwrap());
+        let kek: [u8; 32] = concat_kdf(shared.raw_secret_bytes(), KBS_KEY_ALG, &[], &[]);
+        let cek = [0x42u8; 32];
+        let mut encrypted_key = [0u8; 40];
+        aes_kw::KekAes256::from(kek)
+            .wrap(&cek, &mut encrypted_key)
+            .unwrap();
+        let iv = [7u8; 12];
+        let mut ciphertext = Aes256Gcm::new(&cek.into())
+            .encrypt(
+                &Nonce::from(iv),
+                Payload {
+                    msg: plaintext,
+                    aad: protected.as_bytes(),
+                },
+            )
+            .unwrap();
+        let tag = ciphertext.split_off(ciphertext.len() - 16);
+        KbsResponse {
+            protected,
+            encrypted_key: Base64UrlUnpadded::encode_string(&encrypted_key),
+            iv: Base64UrlUnpadded::encode_string(&iv),
+            ciphertext: Base64UrlUnpadded::encode_string(&ciphertext),
+            tag: Base64UrlUnpadded::encode_string(&tag),
+        }
+    }
+
+    #[test]
+    fn test_decrypt_resource() -> eyre::Result<()> {
+        let key = TeeKey::generate();
+        let response = encrypt(&key.public, b"workload secret");
+        assert_eq!(key.decrypt(&response)?, b"workload secret");
+
+        // Encrypted to another key.
+        assert!(TeeKey::generate().decrypt(&response).is_err());
+
+        let mut tampered = response.clone();
+        tampered.ciphertext = Base64UrlUnpadded::encode_string(b"workload secreT");
+        assert!(key.decrypt(&tampered).is_err());
+
+        let mut header: Value =
+            serde_json::from_slice(&Base64UrlUnpadded::decode_vec(&response.protected).unwrap())?;
+        header["enc"] = json!("A128GCM");
+        let mut downgraded = response.clone();
+        downgraded.protected = Base64UrlUnpadded::encode_string(header.to_string().as_bytes());
+        assert!(key.decrypt(&downgraded).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_concat_kdf() {
+        // RFC 7518 appendix C.
+        let z = [
+            158, 86, 217, 29, 129, 113, 53, 211, 114, 131, 66, 131, 191, 132, 38, 156, 251, 49,
+            110, 163, 218, 128, 106, 72, 246, 218, 167, 121, 140, 254, 144, 196,
+        ];
+        assert_eq!(
+            concat_kdf::<16>(&z, "A128GCM", b"Alice", b"Bob"),
+            [86, 170, 141, 234, 248, 35, 109, 32, 92, 34, 40, 205, 113, 167, 16, 26]
+        );
+    }
+
+    #[test]
+    fn test_attestation_messages() -> eyre::Result<()> {
+        let key = TeeKey::generate();
+        assert_eq!(key.public.to_key()?, key.secret.public_key());
+
+        let challenge: KbsChallenge =
+            serde_json::from_value(json!({"nonce": "bm9uY2U=", "extra-params": ""}))?;
+        let report_data = kbs_report_data(&challenge, &key.public);
+        let runtime_data = json!({"nonce": "bm9uY2U=", "tee-pubkey": key.public});
+        assert_eq!(
+            report_data[..48],
+            Sha384::digest(runtime_data.to_string().as_bytes())[..]
+        );
+        assert_eq!(report_data[48..], [0; 16]);
+
+        let mut attester = TdxAttester::new(|report_data: &[u8; 64]| Ok(report_data.to_vec()));
+        let evidence = attester.evidence(&report_data)?;
+        assert_eq!(
+            evidence["quote"],
+            json!(Base64::encode_string(&report_data))
+        );
+        assert_eq!(evidence["cc_eventlog"], Value::Null);
+
+        let request = serde_json::to_value(AttestRequest {
+            tee_pubkey: &key.public,
+            tee_evidence: evidence.to_string(),
+        })?;
+        assert_eq!(request["tee-pubkey"]["alg"], "ECDH-ES+A256KW");
+        assert_eq!(
+            serde_json::from_str::<Value>(request["tee-evidence"].as_str().unwrap())?,
+            evidence
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_and_resource_urls() -> eyre::Result<()> {
+        let mut headers = HeaderMap::new();
+        headers.append(SET_COOKIE, HeaderValue::from_static("other=1"));
+        headers.append(
+            SET_COOKIE,
+            HeaderValue::from_static(
+                "kbs-session-id=abc123; Expires=Fri, 17 Oct 2025 00:00:00 GMT",
+            ),
+        );
+        assert_eq!(session_cookie(&headers)?, "kbs-session-id=abc123");
+        assert!(session_cookie(&HeaderMap::new()).is_err());
+
+        assert_eq!(
+            resource_url("https://kbs:8080", "/default/key/workload")?,
+            "https://kbs:8080/kbs/v0/resource/default/key/workload"
+        );
+        assert!(resource_url("https://kbs:8080", "default/key").is_err());
+        assert!(resource_url("https://kbs:8080", "default//workload").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+//! One entry point to verify the attestation evidence of TPMs, SGX, TDX,
+//! SEV-SNP and Nitro Enclaves and appraise it with a single policy.
+//!
+//! SGX and TDX quotes and the TPM quotes of an AK are verified by `dcap`;
+//! the TPM event logs, the challenge protocol and the SEV-SNP, Nitro, IMA
+//! and CEL formats beside them live here.
+
+mod evidence;
+pub use evidence::*;
+
+mod policy;
+pub use policy::*;
+
+mod verifier;
+pub use verifier::*;
+
+mod event_log;
+pub use event_log::*;
+
+mod challenge;
+pub use challenge::*;
+
+mod tpm_policy;
+pub use tpm_policy::*;
+
+mod composite;
+pub use composite::*;
+
+mod cel;
+pub use cel::*;
+
+mod ima;
+pub use ima::*;
+
+mod bundle;
+pub use bundle::*;
+
+pub mod cbor;
+
+pub mod cose;
+
+pub mod snp;
+
+pub mod nitro;
+
+#[cfg(feature = "kbs")]
+pub mod kbs;
+
+#[cfg(feature = "gcp")]
+pub mod gcp;
+
+#[cfg(feature = "sealing")]
+pub mod sealed_secret;
+
+#[cfg(any(feature = "gcp", feature = "tpm-tdx"))]
+mod crypto;
+
+#[cfg(all(feature = "blocking", any(feature = "kds", feature = "kbs")))]
+mod runtime;
+
+#[cfg(test)]
+mod test_utils;
@@ -0,0 +1,237 @@
+//! AWS Nitro Enclaves attestation documents.
+//!
+//! A document is a COSE_Sign1 (RFC 9052) structure whose CBOR payload
+//! carries the PCRs of the enclave, the certificate of the signing key and
+//! the bundle of CA certificates up to the AWS Nitro root.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use dcap::{check_issuer_constraints, check_validity};
+use der::asn1::ObjectIdentifier;
+use der::{Decode, Encode};
+use p384::ecdsa::signature::Verifier;
+use x509_cert::Certificate;
+
+use crate::cbor::Value;
+pub use crate::cose::{CoseSign1, COSE_ALG_ES384};
+
+/// `ecdsa-with-SHA384`, which signs every certificate of the chain.
+pub const ECDSA_WITH_SHA384_OID: ObjectIdentifier =
+    ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.3");
+
+/// The payload of a Nitro attestation document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NitroDocument {
+    pub module_id: String,
+    /// The digest algorithm of the PCRs, `SHA384`.
+    pub digest: String,
+    pub timestamp: DateTime<Utc>,
+    pub pcrs: BTreeMap<u32, Vec<u8>>,
+    /// The certificate of the key that signed the document.
+    pub certificate: Certificate,
+    /// The CA certificates, root first.
+    pub cabundle: Vec<Certificate>,
+    pub public_key: Option<Vec<u8>>,
+    pub user_data: Option<Vec<u8>>,
+    pub nonce: Option<Vec<u8>>,
+}
+
+impl NitroDocument {
+    /// Parse the CBOR payload of a document.
+    pub fn parse(payload: &[u8]) -> eyre::Result<Self> {
+        let map = Value::decode(payload)?;
+        let field = |name: &str| {
+            map.get(name)
+                .ok_or_else(|| eyre::eyre!("attestation document has no {}", name))
+        };
+        let text = |name: &str| {
+            field(name)?
+                .as_text()
+                .map(str::to_string)
+                .ok_or_else(|| eyre::eyre!("attestation document {} is not text", name))
+        };
+        let bytes = |name: &str| {
+            field(name)?
+                .as_bytes()
+                .ok_or_else(|| eyre::eyre!("attestation document {} is not bytes", name))
+        };
+        let optional = |name: &str| match map.get(name) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::Bytes(bytes)) => Ok(Some(bytes.clone())),
+            Some(_) => Err(eyre::eyre!("attestation document {} is not bytes", name)),
+        };
+
+        let timestamp = field("timestamp")?
+            .as_u64()
+            .and_then(|millis| i64::try_from(millis).ok())
+            .and_then(DateTime::from_timestamp_millis)
+            .ok_or_else(|| eyre::eyre!("invalid attestation document timestamp"))?;
+
+        let mut pcrs = BTreeMap::new();
+        for (index, value) in field("pcrs")?
+            .as_map()
+            .ok_or_else(|| eyre::eyre!("attestation document pcrs is not a map"))?
+        {
+            let index = index
+                .as_u64()
+                .and_then(|index| u32::try_from(index).ok())
+                .ok_or_else(|| eyre::eyre!("invalid PCR index"))?;
+            let value = value
+                .as_bytes()
+                .ok_or_else(|| eyre::eyre!("PCR{} is not bytes", index))?;
+            pcrs.insert(index, value.to_vec());
+        }
+
+        let cabundle = field("cabundle")?
+            .as_array()
+            .ok_or_else(|| eyre::eyre!("attestation document cabundle is not an array"))?
+            .iter()
+            .map(|cert| {
+                let der = cert
+                    .as_bytes()
+                    .ok_or_else(|| eyre::eyre!("cabundle entry is not bytes"))?;
+                Ok(Certificate::from_der(der)?)
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            module_id: text("module_id")?,
+            digest: text("digest")?,
+            timestamp,
+            pcrs,
+            certificate: Certificate::from_der(bytes("certificate")?)?,
+            cabundle,
+            public_key: optional("public_key")?,
+            user_data: optional("user_data")?,
+            nonce: optional("nonce")?,
+        })
+    }
+
+    /// The value of PCR `index`.
+    pub fn pcr(&self, index: u32) -> Option<&[u8]> {
+        self.pcrs.get(&index).map(Vec::as_slice)
+    }
+}
+
+/// Verify a Nitro attestation document, through its CA bundle up to
+/// `trusted_root`, and parse its payload.
+///
+/// The root should be pinned to the AWS Nitro Enclaves root certificate;
+/// the one carried in the bundle does not prove anything by itself.
+/// Appraising the PCRs and checking the nonce is up to the caller.
+pub fn verify_nitro_document(
+    document: &[u8],
+    trusted_root: &Certificate,
+    at: DateTime<Utc>,
+) -> eyre::Result<NitroDocument> {
+    let sign1 = CoseSign1::parse(document)?;
+    let algorithm = sign1.algorithm()?;
+    if algorithm != COSE_ALG_ES384 {
+        eyre::bail!("unsupported COSE algorithm {}", algorithm);
+    }
+    let parsed = NitroDocument::parse(&sign1.payload)?;
+    if parsed.digest != "SHA384" {
+        eyre::bail!("unsupported PCR digest {}", parsed.digest);
+    }
+
+    let Some(root) = parsed.cabundle.first() else {
+        eyre::bail!("attestation document has an empty cabundle");
+    };
+    if root != trusted_root {
+        eyre::bail!("cabundle root is not the trusted AWS Nitro root");
+    }
+    check_validity(root, at)?;
+    verify_ecdsa_p384(root, root)?;
+    let chain = parsed
+        .cabundle
+        .iter()
+        .chain(std::iter::once(&parsed.certificate))
+        .collect::<Vec<_>>();
+    for (i, pair) in chain.windows(2).enumerate() {
+        // Every certificate of the bundle issues the next one.
+        check_issuer_constraints(pair[0], parsed.cabundle.len() - 1 - i)?;
+        check_validity(pair[1], at)?;
+        verify_ecdsa_p384(pair[1], pair[0])?;
+    }
+
+    let key = p384_key(&parsed.certificate)?;
+    let signature = p384::ecdsa::Signature::from_slice(&sign1.signature)
+        .map_err(|_| eyre::eyre!("malformed attestation document signature"))?;
+    key.verify(&sign1.signed_data(), &signature)
+        .map_err(|_| eyre::eyre!("attestation document signature is invalid"))?;
+    Ok(parsed)
+}
+
+fn p384_key(cert: &Certificate) -> eyre::Result<p384::ecdsa::VerifyingKey> {
+    let spki = &cert.tbs_certificate.subject_public_key_info;
+    p384::ecdsa::VerifyingKey::from_sec1_bytes(spki.subject_public_key.raw_bytes()).map_err(|_| {
+        eyre::eyre!(
+            "certificate {} does not carry a P-384 public key",
+            cert.tbs_certificate.subject
+        )
+    })
+}
+
+fn verify_ecdsa_p384(cert: &Certificate, issuer: &Certificate) -> eyre::Result<()> {
+    let subject = cert.tbs_certificate.subject.to_string();
+    if cert.tbs_certificate.issuer != issuer.tbs_certificate.subject {
+        eyre::bail!("certificate {} is not issued by its parent", subject);
+    }
+    if cert.signature_algorithm.oid != ECDSA_WITH_SHA384_OID {
+        eyre::bail!("certificate {} is not signed with ECDSA SHA-384", subject);
+    }
+    let signature = p384::ecdsa::DerSignature::from_bytes(cert.signature.raw_bytes())
+        .map_err(|_| eyre::eyre!("malformed signature on certificate {}", subject))?;
+    p384_key(issuer)?
+        .verify(&cert.tbs_certificate.to_der()?, &signature)
+        .map_err(|_| eyre::eyre!("invalid signature on certificate {}", subject))
+}
+
+#[cfg(test)]
+mod tests {
+    use dcap::test_utils::verification_time;
+
+    use super::*;
+    use crate::test_utils::NitroTestPki;
+
+    #[test]
+    fn test_verify_document() -> eyre::Result<()> {
+        let pki = NitroTestPki::new();
+        let document = pki.document(&[0x42; 32]);
+        let at = verification_time();
+
+        let parsed = verify_nitro_document(&document, &pki.root, at)?;
+        assert_eq!(parsed.nonce.as_deref(), Some(&[0x42; 32][..]));
+        assert_eq!(parsed.pcr(0), Some(&[0x10; 48][..]));
+        assert_eq!(parsed.module_id, "i-0123456789abcdef0-enc0123456789abcdef");
+        assert_eq!(parsed.cabundle.len(), 2);
+
+        let mut tampered = document.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(verify_nitro_document(&tampered, &pki.root, at).is_err());
+
+        let err = verify_nitro_document(&document, &pki.intermediate, at).unwrap_err();
+        assert!(err.to_string().contains("trusted AWS Nitro root"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_non_ca_intermediate() {
+        let pki = NitroTestPki::with_non_ca_intermediate();
+        let document = pki.document(&[0x42; 32]);
+        let err = verify_nitro_document(&document, &pki.root, verification_time()).unwrap_err();
+        assert!(err.to_string().contains("is not a CA"));
+    }
+
+    #[test]
+    fn test_cose_sign1() -> eyre::Result<()> {
+        let pki = NitroTestPki::new();
+        let sign1 = CoseSign1::parse(&pki.document(&[]))?;
+        assert_eq!(sign1.algorithm()?, COSE_ALG_ES384);
+        assert_eq!(sign1.signature.len(), 96);
+        assert!(CoseSign1::parse(&Value::Array(vec![]).encode()).is_err());
+        Ok(())
+    }
+}
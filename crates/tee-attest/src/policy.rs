@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+
+use dcap::PolicyViolation;
+use serde::{Deserialize, Serialize};
+
+use crate::snp::{SnpReport, SnpTcb};
+
+/// The SEV-SNP guest policy bit allowing the hypervisor to debug the guest.
+const SNP_POLICY_DEBUG: u64 = 1 << 19;
+
+/// An appraisal policy for every supported TEE.
+///
+/// Measurements are hex encoded and allow any value when empty. Each
+/// section only applies to the evidence of its TEE.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    /// Appraisal of SGX and TDX quotes.
+    pub dcap: dcap::Policy,
+    pub snp: SnpPolicy,
+    /// Allowed SHA-256 PCR values of TPM quotes.
+    pub tpm: PcrPolicy,
+    /// Allowed SHA-384 PCR values of Nitro attestation documents.
+    pub nitro: PcrPolicy,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnpPolicy {
+    pub measurement: Vec<String>,
+    pub host_data: Vec<String>,
+    /// Minimum reported TCB, per component.
+    pub min_tcb: Option<SnpTcb>,
+    /// Accept guests whose policy allows debugging.
+    pub allow_debug: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PcrPolicy {
+    /// Allowed values by PCR index. A listed PCR must be present in the
+    /// evidence.
+    pub pcrs: BTreeMap<u32, Vec<String>>,
+}
+
+/// A reason a [`Policy`] rejected some evidence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// A violation of the [`dcap::Policy`] by an SGX or TDX quote.
+    Dcap(PolicyViolation),
+    /// The evidence has a measurement not in the allowed list.
+    Measurement { name: String, value: String },
+    /// The policy constrains a measurement the evidence does not carry.
+    MissingMeasurement(String),
+    /// The reported SEV-SNP TCB is below the minimum.
+    SnpTcb(SnpTcb),
+    /// The SEV-SNP guest policy allows debugging.
+    Debug,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dcap(violation) => write!(f, "{}", violation),
+            Self::Measurement { name, value } => write!(f, "{} {} is not allowed", name, value),
+            Self::MissingMeasurement(name) => write!(f, "evidence carries no {}", name),
+            Self::SnpTcb(tcb) => write!(f, "SEV-SNP TCB {:?} is below the minimum", tcb),
+            Self::Debug => write!(f, "guest policy allows debugging"),
+        }
+    }
+}
+
+impl Policy {
+    pub fn from_json(json: &str) -> eyre::Result<Self> {
+        let policy: Self = serde_json::from_str(json)?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Check that every measurement is hex of the right size.
+    pub fn validate(&self) -> eyre::Result<()> {
+        self.dcap.validate()?;
+        check_hex("snp measurement", &self.snp.measurement, 48)?;
+        check_hex("snp host_data", &self.snp.host_data, 32)?;
+        for (index, values) in &self.tpm.pcrs {
+            check_hex(&format!("tpm pcr{}", index), values, 32)?;
+        }
+        for (index, values) in &self.nitro.pcrs {
+            check_hex(&format!("nitro pcr{}", index), values, 48)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn evaluate_snp(&self, report: &SnpReport) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        check_allowed(
+            &mut violations,
+            "measurement",
+            &self.snp.measurement,
+            &report.measurement,
+        );
+        check_allowed(
+            &mut violations,
+            "host_data",
+            &self.snp.host_data,
+            &report.host_data,
+        );
+        if let Some(min_tcb) = &self.snp.min_tcb {
+            if !report.reported_tcb.is_at_least(min_tcb) {
+                violations.push(Violation::SnpTcb(report.reported_tcb));
+            }
+        }
+        if !self.snp.allow_debug && report.policy & SNP_POLICY_DEBUG != 0 {
+            violations.push(Violation::Debug);
+        }
+        violations
+    }
+}
+
+impl PcrPolicy {
+    pub(crate) fn evaluate<V: AsRef<[u8]>>(&self, pcrs: &BTreeMap<u32, V>) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for (index, allowed) in &self.pcrs {
+            let name = format!("pcr{}", index);
+            match pcrs.get(index) {
+                Some(value) => check_allowed(&mut violations, &name, allowed, value.as_ref()),
+                None if allowed.is_empty() => {}
+                None => violations.push(Violation::MissingMeasurement(name)),
+            }
+        }
+        violations
+    }
+}
+
+fn check_hex(name: &str, values: &[String], size: usize) -> eyre::Result<()> {
+    for value in values {
+        let bytes =
+            hex::decode(value).map_err(|err| eyre::eyre!("invalid {} {}: {}", name, value, err))?;
+        if bytes.len() != size {
+            eyre::bail!("{} {} must be {} bytes", name, value, size);
+        }
+    }
+    Ok(())
+}
+
+fn check_allowed(violations: &mut Vec<Violation>, name: &str, allowed: &[String], value: &[u8]) {
+    let value = hex::encode(value);
+    if !allowed.is_empty() && !allowed.iter().any(|v| v.eq_ignore_ascii_case(&value)) {
+        violations.push(Violation::Measurement {
+            name: name.to_string(),
+            value,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_json() -> eyre::Result<()> {
+        let policy = Policy::from_json(
+            r#"{
+                "dcap": { "tcb_statuses": ["UpToDate", "SWHardeningNeeded"] },
+                "snp": { "min_tcb": { "boot_loader": 4, "tee": 0, "snp": 22, "microcode": 213 } },
+                "nitro": { "pcrs": { "0": ["101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010101010"] } }
+            }"#,
+        )?;
+        assert_eq!(policy.dcap.tcb_statuses.len(), 2);
+        assert_eq!(policy.snp.min_tcb.map(|tcb| tcb.snp), Some(22));
+        assert_eq!(policy.nitro.pcrs[&0].len(), 1);
+
+        assert!(Policy::from_json(r#"{ "tpm": { "pcrs": { "7": ["abcd"] } } }"#).is_err());
+        assert!(Policy::from_json(r#"{ "sgx": {} }"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_pcrs() {
+        let policy = PcrPolicy {
+            pcrs: BTreeMap::from([(0, vec!["aa".repeat(32)]), (7, vec!["bb".repeat(32)])]),
+        };
+        let pcrs = BTreeMap::from([(0, [0xAA; 32]), (4, [0; 32])]);
+        assert_eq!(
+            policy.evaluate(&pcrs),
+            vec![Violation::MissingMeasurement("pcr7".to_string())]
+        );
+
+        let pcrs = BTreeMap::from([(0, [0xAB; 32]), (7, [0xBB; 32])]);
+        assert!(matches!(
+            &policy.evaluate(&pcrs)[..],
+            [Violation::Measurement { name, .. }] if name == "pcr0"
+        ));
+    }
+}
@@ -0,0 +1,773 @@
+//! Sealing secrets to the TEE a service runs in.
+//!
+//! A [`Sealer`] provides the 32 byte data key of a [`SealedSecret`], and a
+//! key blob from which only the same TEE in the same state recovers it. The
+//! secret itself is encrypted with AES-256-GCM under that key, so services
+//! store the sealed bytes the same way whichever sealer they use:
+//!
+//! - [`TpmSealer`] seals the key in a TPM object bound to the current values
+//!   of some PCRs.
+//! - [`SgxSealer`] derives it from the SGX sealing key of the enclave, as
+//!   returned by `EGETKEY`.
+//! - [`KdfSealer`] derives it from key material provided by the host or a
+//!   key service, optionally bound to the measurements of a TD, for TEEs
+//!   without a sealing key of their own such as TDX.
+
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use dcap::{EnclaveReportBody, TdInfo};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+use tss_client::{
+    algorithms, commands, curves, handles, DeviceTransport, ObjectAttributes, PcrSelection,
+    SessionType, Transport, TssClient,
+};
+use tss_serde::{Tpm2b, TssDeserialize, TssReader, TssSerialize};
+use zeroize::Zeroizing;
+
+/// Version of the [`SealedSecret`] encoding.
+pub const SEALED_SECRET_VERSION: u8 = 1;
+
+/// Size of the data key of a sealed secret.
+pub const DATA_KEY_SIZE: usize = 32;
+
+const NONCE_SIZE: usize = 12;
+
+/// HKDF info prefix of the data keys derived by [`KdfSealer`] and
+/// [`SgxSealer`].
+const KDF_INFO: &[u8] = b"tee-ware sealed secret v1";
+
+/// The data key of a sealed secret, wiped when dropped.
+pub type DataKey = Zeroizing<[u8; DATA_KEY_SIZE]>;
+
+/// The kind of [`Sealer`] that sealed a secret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SealerKind {
+    Kdf = 1,
+    Sgx = 2,
+    Tpm = 3,
+}
+
+impl TryFrom<u8> for SealerKind {
+    type Error = eyre::Report;
+
+    fn try_from(value: u8) -> eyre::Result<Self> {
+        match value {
+            1 => Ok(Self::Kdf),
+            2 => Ok(Self::Sgx),
+            3 => Ok(Self::Tpm),
+            _ => eyre::bail!("unknown sealer kind {}", value),
+        }
+    }
+}
+
+/// Provides the data keys of sealed secrets.
+pub trait Sealer {
+    fn kind(&self) -> SealerKind;
+
+    /// A fresh data key, and the key blob from which
+    /// [`unseal_key`](Self::unseal_key) recovers it.
+    fn seal_key(&mut self) -> eyre::Result<(DataKey, Vec<u8>)>;
+
+    /// Recover the data key of `key_blob`.
+    fn unseal_key(&mut self, key_blob: &[u8]) -> eyre::Result<DataKey>;
+
+    /// Seal `secret`, authenticating `aad` along with it.
+    fn seal(&mut self, secret: &[u8], aad: &[u8]) -> eyre::Result<SealedSecret> {
+        let (key, key_blob) = self.seal_key()?;
+        let mut nonce = [0u8; NONCE_SIZE];
+        random(&mut nonce)?;
+        let mut sealed = SealedSecret {
+            kind: self.kind(),
+            key_blob,
+            nonce,
+            ciphertext: Vec::new(),
+        };
+        let header = sealed.header();
+        sealed.ciphertext = Aes256Gcm::new(key.as_ref().into())
+            .encrypt(
+                &Nonce::from(nonce),
+                Payload {
+                    msg: secret,
+                    aad: &[&header[..], aad].concat(),
+                },
+            )
+            .map_err(|_| eyre::eyre!("failed to encrypt the secret"))?;
+        Ok(sealed)
+    }
+
+    /// Unseal `sealed`, which must have been sealed with `aad`.
+    fn unseal(&mut self, sealed: &SealedSecret, aad: &[u8]) -> eyre::Result<Zeroizing<Vec<u8>>> {
+        if sealed.kind != self.kind() {
+            eyre::bail!(
+                "secret is sealed by a {:?} sealer, not {:?}",
+                sealed.kind,
+                self.kind()
+            );
+        }
+        let key = self.unseal_key(&sealed.key_blob)?;
+        let secret = Aes256Gcm::new(key.as_ref().into())
+            .decrypt(
+                &Nonce::from(sealed.nonce),
+                Payload {
+                    msg: &sealed.ciphertext,
+                    aad: &[&sealed.header()[..], aad].concat(),
+                },
+            )
+            .map_err(|_| eyre::eyre!("sealed secret is corrupted or sealed to another state"))?;
+        Ok(Zeroizing::new(secret))
+    }
+}
+
+/// A secret encrypted under a data key that a [`Sealer`] protects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedSecret {
+    pub kind: SealerKind,
+    pub key_blob: Vec<u8>,
+    pub nonce: [u8; NONCE_SIZE],
+    /// AES-256-GCM ciphertext and tag.
+    pub ciphertext: Vec<u8>,
+}
+
+impl SealedSecret {
+    /// The version, kind and key blob, which the ciphertext authenticates.
+    fn header(&self) -> Vec<u8> {
+        let mut header = vec![SEALED_SECRET_VERSION, self.kind as u8];
+        header.extend_from_slice(&Tpm2b(self.key_blob.clone()).to_tss_bytes());
+        header
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.header()[..], &self.nonce, &self.ciphertext].concat()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> eyre::Result<Self> {
+        let mut reader = TssReader::new(bytes);
+        let version = reader.read_u8()?;
+        if version != SEALED_SECRET_VERSION {
+            eyre::bail!("unsupported sealed secret version {}", version);
+        }
+        let kind = SealerKind::try_from(reader.read_u8()?)?;
+        let key_blob = reader.read_tpm2b()?;
+        let nonce = reader.read_array()?;
+        Ok(Self {
+            kind,
+            key_blob,
+            nonce,
+            ciphertext: reader.peek_remaining().to_vec(),
+        })
+    }
+}
+
+fn random(bytes: &mut [u8]) -> eyre::Result<()> {
+    getrandom::getrandom(bytes).map_err(|err| eyre::eyre!("failed to generate a key: {}", err))
+}
+
+fn hkdf_expand(salt: Option<&[u8]>, ikm: &[u8], context: &[u8]) -> eyre::Result<DataKey> {
+    let mut key = Zeroizing::new([0u8; DATA_KEY_SIZE]);
+    Hkdf::<Sha256>::new(salt, ikm)
+        .expand_multi_info(&[KDF_INFO, context], key.as_mut())
+        .map_err(|_| eyre::eyre!("HKDF expansion failed"))?;
+    Ok(key)
+}
+
+/// Derives data keys with HKDF-SHA256 from key material the host or a key
+/// service provides, e.g. after attesting the TD.
+///
+/// The key blob is the random salt of the key. Keys are bound to the
+/// context, so a secret sealed with one context unseals only with the same.
+pub struct KdfSealer {
+    ikm: Zeroizing<Vec<u8>>,
+    context: Vec<u8>,
+}
+
+impl KdfSealer {
+    pub fn new(ikm: impl Into<Vec<u8>>) -> Self {
+        Self {
+            ikm: Zeroizing::new(ikm.into()),
+            context: Vec::new(),
+        }
+    }
+
+    /// Bind the keys to `context` as well.
+    pub fn with_context(mut self, context: &[u8]) -> Self {
+        self.context.extend_from_slice(context);
+        self
+    }
+
+    /// Bind the keys to the MRTD and the RTMRs 0 to 2 of `td`, which the
+    /// firmware, boot loader and kernel extend. RTMR 3 is left to
+    /// applications.
+    pub fn with_td_measurements(self, td: &TdInfo) -> Self {
+        let mut context = td.mr_td.to_vec();
+        for rtmr in &td.rtmrs[..3] {
+            context.extend_from_slice(rtmr);
+        }
+        self.with_context(&context)
+    }
+}
+
+impl Sealer for KdfSealer {
+    fn kind(&self) -> SealerKind {
+        SealerKind::Kdf
+    }
+
+    fn seal_key(&mut self) -> eyre::Result<(DataKey, Vec<u8>)> {
+        let mut salt = [0u8; 32];
+        random(&mut salt)?;
+        let key = hkdf_expand(Some(&salt), &self.ikm, &self.context)?;
+        Ok((key, salt.to_vec()))
+    }
+
+    fn unseal_key(&mut self, key_blob: &[u8]) -> eyre::Result<DataKey> {
+        if key_blob.len() != 32 {
+            eyre::bail!("KDF key blob must be 32 bytes, got {}", key_blob.len());
+        }
+        hkdf_expand(Some(key_blob), &self.ikm, &self.context)
+    }
+}
+
+/// `KEYNAME` of the seal key.
+pub const SGX_KEYNAME_SEAL: u16 = 4;
+/// `KEYREQUEST` size.
+pub const SGX_KEY_REQUEST_SIZE: usize = 512;
+
+/// Which identity of the enclave an SGX seal key binds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum SgxKeyPolicy {
+    /// Only the same enclave build unseals.
+    MrEnclave = 1,
+    /// Any enclave of the same signer, with the same or a later ISV SVN,
+    /// unseals.
+    MrSigner = 2,
+}
+
+/// An SGX `KEYREQUEST` for a seal key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SgxKeyRequest {
+    pub policy: SgxKeyPolicy,
+    pub isv_svn: u16,
+    pub cpu_svn: [u8; 16],
+    pub key_id: [u8; 32],
+}
+
+impl SgxKeyRequest {
+    pub fn to_bytes(&self) -> [u8; SGX_KEY_REQUEST_SIZE] {
+        let mut bytes = [0u8; SGX_KEY_REQUEST_SIZE];
+        bytes[0..2].copy_from_slice(&SGX_KEYNAME_SEAL.to_le_bytes());
+        bytes[2..4].copy_from_slice(&(self.policy as u16).to_le_bytes());
+        bytes[4..6].copy_from_slice(&self.isv_svn.to_le_bytes());
+        bytes[8..24].copy_from_slice(&self.cpu_svn);
+        // The attribute and MISCSELECT masks of the Intel SGX SDK: INIT,
+        // DEBUG, MODE64BIT and the reserved bits.
+        bytes[24..32].copy_from_slice(&0xFF0000000000000Bu64.to_le_bytes());
+        bytes[40..72].copy_from_slice(&self.key_id);
+        bytes[72..76].copy_from_slice(&0xF0000000u32.to_le_bytes());
+        bytes
+    }
+
+    /// The policy, SVNs and key id, which [`SgxSealer`] keeps as key blob.
+    fn to_blob(&self) -> Vec<u8> {
+        let mut blob = (self.policy as u16).to_le_bytes().to_vec();
+        blob.extend_from_slice(&self.isv_svn.to_le_bytes());
+        blob.extend_from_slice(&self.cpu_svn);
+        blob.extend_from_slice(&self.key_id);
+        blob
+    }
+
+    fn from_blob(blob: &[u8]) -> eyre::Result<Self> {
+        if blob.len() != 52 {
+            eyre::bail!("SGX key blob must be 52 bytes, got {}", blob.len());
+        }
+        let policy = match u16::from_le_bytes([blob[0], blob[1]]) {
+            1 => SgxKeyPolicy::MrEnclave,
+            2 => SgxKeyPolicy::MrSigner,
+            policy => eyre::bail!("unknown SGX key policy {}", policy),
+        };
+        Ok(Self {
+            policy,
+            isv_svn: u16::from_le_bytes([blob[2], blob[3]]),
+            cpu_svn: blob[4..20].try_into()?,
+            key_id: blob[20..].try_into()?,
+        })
+    }
+}
+
+/// Returns SGX keys, i.e. runs `EGETKEY`.
+pub trait SgxKeySource {
+    fn get_key(&mut self, request: &SgxKeyRequest) -> eyre::Result<Zeroizing<[u8; 16]>>;
+}
+
+/// `EGETKEY` of the running enclave.
+#[cfg(all(target_env = "sgx", target_arch = "x86_64"))]
+pub struct Egetkey;
+
+#[cfg(all(target_env = "sgx", target_arch = "x86_64"))]
+impl SgxKeySource for Egetkey {
+    fn get_key(&mut self, request: &SgxKeyRequest) -> eyre::Result<Zeroizing<[u8; 16]>> {
+        #[repr(C, align(512))]
+        struct Request([u8; SGX_KEY_REQUEST_SIZE]);
+        #[repr(C, align(16))]
+        struct Key([u8; 16]);
+
+        let request = Request(request.to_bytes());
+        let mut key = Key([0; 16]);
+        let error: u32;
+        // SAFETY: ENCLU[EGETKEY] reads the aligned request from RBX and
+        // writes the aligned key to RCX. LLVM reserves RBX, so it is swapped
+        // in and out.
+        unsafe {
+            core::arch::asm!(
+                "xchg {request}, rbx",
+                "enclu",
+                "xchg {request}, rbx",
+                request = inout(reg) request.0.as_ptr() => _,
+                inout("eax") 1u32 => error,
+                in("rcx") key.0.as_mut_ptr(),
+                options(nostack),
+            );
+        }
+        let key = Zeroizing::new(key.0);
+        if error != 0 {
+            eyre::bail!("EGETKEY failed with {}", error);
+        }
+        Ok(key)
+    }
+}
+
+/// Derives data keys from the SGX seal key of the enclave.
+///
+/// Each secret gets its own seal key through a random key id, and the key
+/// blob records the key request, so a secret sealed at some CPU and ISV SVN
+/// unseals on later ones too.
+pub struct SgxSealer<K> {
+    source: K,
+    policy: SgxKeyPolicy,
+    isv_svn: u16,
+    cpu_svn: [u8; 16],
+}
+
+impl<K: SgxKeySource> SgxSealer<K> {
+    /// Seal at the SVNs of `report`, a report of the enclave itself, with
+    /// the [`SgxKeyPolicy::MrSigner`] policy.
+    pub fn new(source: K, report: &EnclaveReportBody) -> Self {
+        Self {
+            source,
+            policy: SgxKeyPolicy::MrSigner,
+            isv_svn: report.isv_svn,
+            cpu_svn: report.cpu_svn,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: SgxKeyPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+impl<K: SgxKeySource> Sealer for SgxSealer<K> {
+    fn kind(&self) -> SealerKind {
+        SealerKind::Sgx
+    }
+
+    fn seal_key(&mut self) -> eyre::Result<(DataKey, Vec<u8>)> {
+        let mut key_id = [0u8; 32];
+        random(&mut key_id)?;
+        let request = SgxKeyRequest {
+            policy: self.policy,
+            isv_svn: self.isv_svn,
+            cpu_svn: self.cpu_svn,
+            key_id,
+        };
+        let key = self.source.get_key(&request)?;
+        Ok((hkdf_expand(None, key.as_ref(), &[])?, request.to_blob()))
+    }
+
+    fn unseal_key(&mut self, key_blob: &[u8]) -> eyre::Result<DataKey> {
+        let request = SgxKeyRequest::from_blob(key_blob)?;
+        let key = self.source.get_key(&request)?;
+        hkdf_expand(None, key.as_ref(), &[])
+    }
+}
+
+/// The policy digest of `TPM2_PolicyPCR` over the SHA-256 `values` of the
+/// ascending `pcrs`, starting from an empty policy.
+pub fn tpm_pcr_policy_digest(pcrs: &[u32], values: &[[u8; 32]]) -> [u8; 32] {
+    let pcr_digest = Sha256::digest(values.concat());
+    let mut hasher = Sha256::new();
+    hasher.update([0u8; 32]);
+    hasher.update(commands::POLICY_PCR.to_be_bytes());
+    hasher.update(PcrSelection::sha256(pcrs).to_tss_bytes());
+    hasher.update(pcr_digest);
+    hasher.finalize().into()
+}
+
+/// TPMT_PUBLIC of a primary ECC P-256 storage key, like the SRK templates
+/// of the TCG.
+fn storage_key_template() -> Vec<u8> {
+    let attributes = ObjectAttributes::FIXED_TPM
+        | ObjectAttributes::FIXED_PARENT
+        | ObjectAttributes::SENSITIVE_DATA_ORIGIN
+        | ObjectAttributes::USER_WITH_AUTH
+        | ObjectAttributes::NO_DA
+        | ObjectAttributes::RESTRICTED
+        | ObjectAttributes::DECRYPT;
+    let mut template = Vec::new();
+    algorithms::ECC.serialize_into(&mut template);
+    algorithms::SHA256.serialize_into(&mut template);
+    attributes.serialize_into(&mut template);
+    Tpm2b::default().serialize_into(&mut template); // authPolicy
+    algorithms::AES.serialize_into(&mut template);
+    128u16.serialize_into(&mut template);
+    algorithms::CFB.serialize_into(&mut template);
+    algorithms::NULL.serialize_into(&mut template); // scheme
+    curves::NIST_P256.serialize_into(&mut template);
+    algorithms::NULL.serialize_into(&mut template); // kdf
+    Tpm2b::default().serialize_into(&mut template); // x
+    Tpm2b::default().serialize_into(&mut template); // y
+    template
+}
+
+/// TPMT_PUBLIC of a sealed data object that only `policy` authorizes.
+fn sealed_object_template(policy: &[u8; 32]) -> Vec<u8> {
+    let attributes =
+        ObjectAttributes::FIXED_TPM | ObjectAttributes::FIXED_PARENT | ObjectAttributes::NO_DA;
+    let mut template = Vec::new();
+    algorithms::KEYEDHASH.serialize_into(&mut template);
+    algorithms::SHA256.serialize_into(&mut template);
+    attributes.serialize_into(&mut template);
+    Tpm2b(policy.to_vec()).serialize_into(&mut template);
+    algorithms::NULL.serialize_into(&mut template); // scheme
+    Tpm2b::default().serialize_into(&mut template); // unique
+    template
+}
+
+/// Seals data keys in TPM objects that unseal only while the SHA-256 bank of
+/// some PCRs has the values it had at sealing time.
+///
+/// The objects live under a storage key: a persistent one, or by default a
+/// primary key of the owner hierarchy created for each operation. The key
+/// blob is the PCR selection and the public and private parts of the object.
+pub struct TpmSealer<T> {
+    tpm: TssClient<T>,
+    pcrs: Vec<u32>,
+    parent: Option<u32>,
+}
+
+impl TpmSealer<DeviceTransport> {
+    /// Use the TPM through the kernel resource manager.
+    pub fn open(pcrs: &[u32]) -> eyre::Result<Self> {
+        Ok(Self::new(
+            TssClient::new(DeviceTransport::open_default()?),
+            pcrs,
+        ))
+    }
+}
+
+impl<T: Transport> TpmSealer<T> {
+    /// Seal to the current values of `pcrs`.
+    pub fn new(tpm: TssClient<T>, pcrs: &[u32]) -> Self {
+        let mut pcrs = pcrs.to_vec();
+        pcrs.sort_unstable();
+        pcrs.dedup();
+        Self {
+            tpm,
+            pcrs,
+            parent: None,
+        }
+    }
+
+    /// Seal under the storage key at the persistent handle `parent`, e.g.
+    /// the SRK at 0x81000001.
+    pub fn with_parent(mut self, parent: u32) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Run `f` with the handle of the storage key.
+    fn with_storage_key<R>(
+        &mut self,
+        f: impl FnOnce(&mut TssClient<T>, u32) -> eyre::Result<R>,
+    ) -> eyre::Result<R> {
+        if let Some(parent) = self.parent {
+            return f(&mut self.tpm, parent);
+        }
+        let parent = self
+            .tpm
+            .create_primary(handles::OWNER, &storage_key_template())?;
+        let result = f(&mut self.tpm, parent);
+        self.tpm.flush_context(parent)?;
+        result
+    }
+
+    /// Unseal the data of `item` with a policy session satisfying its PCR
+    /// policy.
+    fn unseal_item(&mut self, item: u32, pcrs: &[u32]) -> eyre::Result<Vec<u8>> {
+        let mut nonce = [0u8; 32];
+        random(&mut nonce)?;
+        let session = self.tpm.start_auth_session(SessionType::Policy, &nonce)?;
+        let result = self
+            .tpm
+            .policy_pcr(session, pcrs)
+            .and_then(|()| self.tpm.unseal(item, session));
+        if result.is_err() {
+            // A successful TPM2_Unseal flushes the session.
+            let _ = self.tpm.flush_context(session);
+        }
+        result.map_err(|err| err.wrap_err("cannot unseal the data key; have the PCRs changed?"))
+    }
+}
+
+impl<T: Transport> Sealer for TpmSealer<T> {
+    fn kind(&self) -> SealerKind {
+        SealerKind::Tpm
+    }
+
+    fn seal_key(&mut self) -> eyre::Result<(DataKey, Vec<u8>)> {
+        let values = self.tpm.read_pcrs_sha256(&self.pcrs)?;
+        let template = sealed_object_template(&tpm_pcr_policy_digest(&self.pcrs, &values));
+        let mut key = Zeroizing::new([0u8; DATA_KEY_SIZE]);
+        random(key.as_mut())?;
+        let created =
+            self.with_storage_key(|tpm, parent| tpm.create(parent, &template, key.as_ref()))?;
+
+        let mut blob = PcrSelection::sha256(&self.pcrs).to_tss_bytes();
+        blob.extend_from_slice(&Tpm2b(created.private).to_tss_bytes());
+        blob.extend_from_slice(&Tpm2b(created.public).to_tss_bytes());
+        Ok((key, blob))
+    }
+
+    fn unseal_key(&mut self, key_blob: &[u8]) -> eyre::Result<DataKey> {
+        let mut reader = TssReader::new(key_blob);
+        let selection = PcrSelection::from_tss_reader(&mut reader)?;
+        let private = reader.read_tpm2b()?;
+        let public = reader.read_tpm2b()?;
+        if selection.hash != algorithms::SHA256 || reader.has_remaining(1) {
+            eyre::bail!("malformed TPM key blob");
+        }
+
+        let item = self.with_storage_key(|tpm, parent| tpm.load(parent, &private, &public))?;
+        let data = self.unseal_item(item, &selection.pcrs);
+        self.tpm.flush_context(item)?;
+        let data = Zeroizing::new(data?);
+        let key: [u8; DATA_KEY_SIZE] = data[..]
+            .try_into()
+            .map_err(|_| eyre::eyre!("TPM unsealed {} bytes, not a data key", data.len()))?;
+        Ok(Zeroizing::new(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use tss_client::ResponseHeader;
+
+    use super::*;
+
+    #[test]
+    fn test_kdf_sealer() -> eyre::Result<()> {
+        let mut sealer = KdfSealer::new([7u8; 32]).with_context(b"service");
+        let sealed = sealer.seal(b"database password", b"db")?;
+        let bytes = sealed.to_bytes();
+        let parsed = SealedSecret::from_bytes(&bytes)?;
+        assert_eq!(parsed, sealed);
+        assert_eq!(&sealer.unseal(&parsed, b"db")?[..], b"database password");
+
+        assert!(sealer.unseal(&parsed, b"other").is_err());
+        let mut other = KdfSealer::new([7u8; 32]).with_context(b"other service");
+        assert!(other.unseal(&parsed, b"db").is_err());
+        let mut tampered = parsed.clone();
+        tampered.key_blob[0] ^= 1;
+        assert!(sealer.unseal(&tampered, b"db").is_err());
+
+        let mut sgx = SgxSealer::new(FakeEgetkey, &report_body());
+        assert!(sgx.unseal(&parsed, b"db").is_err());
+        Ok(())
+    }
+
+    /// Derives keys from the request alone, like one enclave on one CPU.
+    struct FakeEgetkey;
+
+    impl SgxKeySource for FakeEgetkey {
+        fn get_key(&mut self, request: &SgxKeyRequest) -> eyre::Result<Zeroizing<[u8; 16]>> {
+            let digest = Sha256::digest(request.to_bytes());
+            Ok(Zeroizing::new(digest[..16].try_into()?))
+        }
+    }
+
+    fn report_body() -> EnclaveReportBody {
+        EnclaveReportBody {
+            cpu_svn: [3; 16],
+            misc_select: 0,
+            isv_ext_prod_id: [0; 16],
+            attributes: [0; 16],
+            mr_enclave: [1; 32],
+            mr_signer: [2; 32],
+            config_id: [0; 64],
+            isv_prod_id: 0,
+            isv_svn: 5,
+            config_svn: 0,
+            isv_family_id: [0; 16],
+            report_data: [0; 64],
+        }
+    }
+
+    #[test]
+    fn test_sgx_sealer() -> eyre::Result<()> {
+        let mut sealer =
+            SgxSealer::new(FakeEgetkey, &report_body()).with_policy(SgxKeyPolicy::MrEnclave);
+        let sealed = sealer.seal(b"secret", b"")?;
+        let request = SgxKeyRequest::from_blob(&sealed.key_blob)?;
+        assert_eq!(request.policy, SgxKeyPolicy::MrEnclave);
+        assert_eq!(request.isv_svn, 5);
+        assert_eq!(request.cpu_svn, [3; 16]);
+        let bytes = request.to_bytes();
+        assert_eq!(&bytes[..6], &[4, 0, 1, 0, 5, 0]);
+        assert_eq!(&bytes[40..72], &request.key_id);
+
+        assert_eq!(&sealer.unseal(&sealed, b"")?[..], b"secret");
+        Ok(())
+    }
+
+    /// A TPM holding sealed objects in the clear, which checks the PCR
+    /// policy of an object when unsealing it.
+    struct FakeTpm {
+        pcrs: BTreeMap<u32, [u8; 32]>,
+        /// TPM2_Create data, by private blob.
+        objects: Vec<(Vec<u8>, Vec<u8>)>,
+        /// Loaded objects: sealed data and auth policy.
+        loaded: BTreeMap<u32, (Vec<u8>, Vec<u8>)>,
+        /// Policy digests of the open sessions.
+        sessions: BTreeMap<u32, [u8; 32]>,
+        next_handle: u32,
+    }
+
+    impl FakeTpm {
+        fn new() -> Self {
+            Self {
+                pcrs: (0..24).map(|pcr| (pcr, [pcr as u8; 32])).collect(),
+                objects: Vec::new(),
+                loaded: BTreeMap::new(),
+                sessions: BTreeMap::new(),
+                next_handle: 0x80000000,
+            }
+        }
+
+        fn handle(&mut self) -> u32 {
+            self.next_handle += 1;
+            self.next_handle
+        }
+
+        fn respond(&mut self, command: &[u8]) -> eyre::Result<Vec<u8>> {
+            let code = u32::from_be_bytes(command[6..10].try_into()?);
+            // Parameters of commands with one handle and a 9 byte session.
+            let parameters = command.get(10 + 4 + 4 + 9..).unwrap_or_default();
+            let mut reader = TssReader::new(parameters);
+            let with_parameters = |parameters: &[u8]| {
+                let mut body = (parameters.len() as u32).to_tss_bytes();
+                body.extend_from_slice(parameters);
+                body
+            };
+            Ok(match code {
+                commands::READ_PCR => {
+                    let selection = PcrSelection::from_tss_bytes(&command[10..])?;
+                    let mut body = 0u32.to_tss_bytes();
+                    body.extend_from_slice(&selection.to_tss_bytes());
+                    body.extend_from_slice(&(selection.pcrs.len() as u32).to_tss_bytes());
+                    for pcr in &selection.pcrs {
+                        body.extend_from_slice(&Tpm2b(self.pcrs[pcr].to_vec()).to_tss_bytes());
+                    }
+                    body
+                }
+                commands::CREATE_PRIMARY => self.handle().to_tss_bytes(),
+                commands::CREATE => {
+                    let sensitive = reader.read_tpm2b()?;
+                    let public = reader.read_tpm2b()?;
+                    let data = Tpm2b::from_tss_bytes(&sensitive[2..])?.0;
+                    let private = vec![self.objects.len() as u8; 16];
+                    self.objects.push((data, public.clone()));
+                    let mut parameters = Tpm2b(private).to_tss_bytes();
+                    parameters.extend_from_slice(&Tpm2b(public).to_tss_bytes());
+                    with_parameters(&parameters)
+                }
+                commands::LOAD => {
+                    let private = reader.read_tpm2b()?;
+                    let public = reader.read_tpm2b()?;
+                    let (data, created) = self.objects[private[0] as usize].clone();
+                    eyre::ensure!(created == public, "TPM_RC_BINDING");
+                    // The auth policy follows type, name algorithm and
+                    // attributes.
+                    let policy = Tpm2b::from_tss_bytes(&public[8..])?.0;
+                    let handle = self.handle();
+                    self.loaded.insert(handle, (data, policy));
+                    handle.to_tss_bytes()
+                }
+                commands::START_AUTH_SESSION => {
+                    let handle = self.handle();
+                    self.sessions.insert(handle, [0; 32]);
+                    let mut body = handle.to_tss_bytes();
+                    body.extend_from_slice(&Tpm2b(vec![0; 32]).to_tss_bytes());
+                    body
+                }
+                commands::POLICY_PCR => {
+                    let session = u32::from_be_bytes(command[10..14].try_into()?);
+                    let selection = PcrSelection::from_tss_bytes(&command[16..])?;
+                    let values: Vec<_> = selection.pcrs.iter().map(|pcr| self.pcrs[pcr]).collect();
+                    let digest = tpm_pcr_policy_digest(&selection.pcrs, &values);
+                    self.sessions.insert(session, digest);
+                    Vec::new()
+                }
+                commands::UNSEAL => {
+                    let item = u32::from_be_bytes(command[10..14].try_into()?);
+                    let session = u32::from_be_bytes(command[18..22].try_into()?);
+                    let (data, policy) = &self.loaded[&item];
+                    let digest = self.sessions.remove(&session);
+                    eyre::ensure!(
+                        digest.map(Vec::from) == Some(policy.clone()),
+                        "TPM_RC_POLICY_FAIL"
+                    );
+                    with_parameters(&Tpm2b(data.clone()).to_tss_bytes())
+                }
+                commands::FLUSH_CONTEXT => {
+                    let handle = u32::from_be_bytes(command[10..14].try_into()?);
+                    self.loaded.remove(&handle);
+                    self.sessions.remove(&handle);
+                    Vec::new()
+                }
+                _ => eyre::bail!("unexpected command {:#x}", code),
+            })
+        }
+    }
+
+    impl Transport for &mut FakeTpm {
+        fn send_command(&mut self, command: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
+            let body = self.respond(command)?;
+            let header = ResponseHeader {
+                tag: 0x8001,
+                size: 10 + body.len() as u32,
+                response_code: 0,
+            };
+            Ok((header, body))
+        }
+    }
+
+    #[test]
+    fn test_tpm_sealer() -> eyre::Result<()> {
+        let mut tpm = FakeTpm::new();
+        let sealed = TpmSealer::new(TssClient::new(&mut tpm), &[7, 0]).seal(b"secret", b"app")?;
+        let unsealed = TpmSealer::new(TssClient::new(&mut tpm), &[]).unseal(&sealed, b"app")?;
+        assert_eq!(&unsealed[..], b"secret");
+        assert!(tpm.loaded.is_empty() && tpm.sessions.is_empty());
+
+        tpm.pcrs.insert(7, [0xff; 32]);
+        let err = TpmSealer::new(TssClient::new(&mut tpm), &[])
+            .unseal(&sealed, b"app")
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("PCRs changed"));
+        assert!(tpm.loaded.is_empty() && tpm.sessions.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+//! Blocking variant of [`KdsClient`](super::KdsClient).
+
+use x509_cert::Certificate;
+
+use super::{SnpCertChain, SnpProduct, SnpReport};
+use crate::runtime::BlockingRuntime;
+
+/// Blocking KDS client, running the async [`KdsClient`](super::KdsClient) on
+/// a runtime of its own.
+#[derive(Debug, Clone)]
+pub struct KdsClient {
+    client: super::KdsClient,
+    runtime: BlockingRuntime,
+}
+
+impl Default for KdsClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KdsClient {
+    pub fn new() -> Self {
+        Self {
+            client: super::KdsClient::new(),
+            runtime: BlockingRuntime::new(),
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.client = self.client.with_base_url(base_url);
+        self
+    }
+
+    /// Fetch the VCEK that signed `report`.
+    pub fn vcek(&self, product: SnpProduct, report: &SnpReport) -> eyre::Result<Certificate> {
+        self.runtime.block_on(self.client.vcek(product, report))
+    }
+
+    /// Fetch the ASK and ARK of `product`, in that order.
+    pub fn cert_chain(&self, product: SnpProduct) -> eyre::Result<(Certificate, Certificate)> {
+        self.runtime.block_on(self.client.cert_chain(product))
+    }
+
+    /// Fetch the certificates needed to verify `report`.
+    pub fn snp_cert_chain(
+        &self,
+        product: SnpProduct,
+        report: &SnpReport,
+    ) -> eyre::Result<SnpCertChain> {
+        self.runtime
+            .block_on(self.client.snp_cert_chain(product, report))
+    }
+}
@@ -0,0 +1,131 @@
+use der::Decode;
+use x509_cert::Certificate;
+
+use super::{SnpCertChain, SnpReport, SnpTcb};
+
+pub const AMD_KDS_URL: &str = "https://kdsintf.amd.com";
+
+/// An EPYC product line, which has its own ARK and ASK.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnpProduct {
+    Milan,
+    Genoa,
+}
+
+impl SnpProduct {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SnpProduct::Milan => "Milan",
+            SnpProduct::Genoa => "Genoa",
+        }
+    }
+}
+
+/// Client for the AMD Key Distribution Service (KDS).
+#[derive(Debug, Clone)]
+pub struct KdsClient {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl Default for KdsClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KdsClient {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: AMD_KDS_URL.to_string(),
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    pub fn vcek_url(&self, product: SnpProduct, chip_id: &[u8], tcb: &SnpTcb) -> String {
+        format!(
+            "{}/vcek/v1/{}/{}?blSPL={}&teeSPL={}&snpSPL={}&ucodeSPL={}",
+            self.base_url.trim_end_matches('/'),
+            product.as_str(),
+            hex::encode(chip_id),
+            tcb.boot_loader,
+            tcb.tee,
+            tcb.snp,
+            tcb.microcode
+        )
+    }
+
+    pub fn cert_chain_url(&self, product: SnpProduct) -> String {
+        format!(
+            "{}/vcek/v1/{}/cert_chain",
+            self.base_url.trim_end_matches('/'),
+            product.as_str()
+        )
+    }
+
+    /// Fetch the VCEK that signed `report`.
+    pub async fn vcek(&self, product: SnpProduct, report: &SnpReport) -> eyre::Result<Certificate> {
+        if report.is_chip_id_masked() {
+            eyre::bail!("the chip id of the report is masked");
+        }
+        let url = self.vcek_url(product, &report.chip_id, &report.reported_tcb);
+        Ok(Certificate::from_der(&self.get(&url).await?)?)
+    }
+
+    /// Fetch the ASK and ARK of `product`, in that order.
+    pub async fn cert_chain(
+        &self,
+        product: SnpProduct,
+    ) -> eyre::Result<(Certificate, Certificate)> {
+        let pem = self.get(&self.cert_chain_url(product)).await?;
+        match <[Certificate; 2]>::try_from(Certificate::load_pem_chain(&pem)?) {
+            Ok([ask, ark]) => Ok((ask, ark)),
+            Err(certs) => eyre::bail!("KDS returned {} certificates, expected 2", certs.len()),
+        }
+    }
+
+    /// Fetch the certificates needed to verify `report`.
+    pub async fn snp_cert_chain(
+        &self,
+        product: SnpProduct,
+        report: &SnpReport,
+    ) -> eyre::Result<SnpCertChain> {
+        let vcek = self.vcek(product, report).await?;
+        let (ask, ark) = self.cert_chain(product).await?;
+        Ok(SnpCertChain { vcek, ask, ark })
+    }
+
+    async fn get(&self, url: &str) -> eyre::Result<Vec<u8>> {
+        let response = self.client.get(url).send().await?.error_for_status()?;
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kds_urls() {
+        let client = KdsClient::new().with_base_url("https://kds.example/");
+        let tcb = SnpTcb {
+            boot_loader: 7,
+            tee: 0,
+            snp: 21,
+            microcode: 72,
+        };
+        assert_eq!(
+            client.vcek_url(SnpProduct::Genoa, &[0xAB; 2], &tcb),
+            "https://kds.example/vcek/v1/Genoa/abab?blSPL=7&teeSPL=0&snpSPL=21&ucodeSPL=72"
+        );
+        assert_eq!(
+            KdsClient::new().cert_chain_url(SnpProduct::Milan),
+            "https://kdsintf.amd.com/vcek/v1/Milan/cert_chain"
+        );
+    }
+}
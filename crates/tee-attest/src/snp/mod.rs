@@ -0,0 +1,244 @@
+//! AMD SEV-SNP attestation reports.
+//!
+//! A report is signed by the VCEK, a P-384 key derived from the chip and its
+//! TCB, which the AMD Key Distribution Service (KDS) certifies through the
+//! ASK and the ARK of the product line. Milan and Genoa reports are
+//! supported; Turin encodes TCB versions and chip ids differently.
+
+use serde::{Deserialize, Serialize};
+use tss_serde::{TssError, TssReader};
+
+#[cfg(feature = "kds")]
+mod kds;
+#[cfg(feature = "kds")]
+pub use kds::*;
+
+mod verify;
+pub use verify::*;
+
+#[cfg(all(feature = "kds", feature = "blocking"))]
+pub mod blocking;
+
+/// Size in bytes of an attestation report.
+pub const SNP_REPORT_SIZE: usize = 0x4A0;
+
+/// Size in bytes of the part of the report covered by the signature.
+pub const SNP_SIGNED_SIZE: usize = 0x2A0;
+
+/// `SIGNATURE_ALGO` of reports signed with ECDSA P-384 and SHA-384.
+pub const SIGNATURE_ALGO_ECDSA_P384_SHA384: u32 = 1;
+
+/// A `TCB_VERSION`: the security patch levels of the firmware components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct SnpTcb {
+    pub boot_loader: u8,
+    pub tee: u8,
+    pub snp: u8,
+    pub microcode: u8,
+}
+
+impl SnpTcb {
+    pub fn from_u64(value: u64) -> Self {
+        let bytes = value.to_le_bytes();
+        Self {
+            boot_loader: bytes[0],
+            tee: bytes[1],
+            snp: bytes[6],
+            microcode: bytes[7],
+        }
+    }
+
+    pub fn to_u64(&self) -> u64 {
+        u64::from_le_bytes([
+            self.boot_loader,
+            self.tee,
+            0,
+            0,
+            0,
+            0,
+            self.snp,
+            self.microcode,
+        ])
+    }
+
+    /// Whether every component is at least at the level of `other`.
+    pub fn is_at_least(&self, other: &SnpTcb) -> bool {
+        self.boot_loader >= other.boot_loader
+            && self.tee >= other.tee
+            && self.snp >= other.snp
+            && self.microcode >= other.microcode
+    }
+}
+
+/// An SEV-SNP `ATTESTATION_REPORT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnpReport {
+    pub version: u32,
+    pub guest_svn: u32,
+    pub policy: u64,
+    pub family_id: [u8; 16],
+    pub image_id: [u8; 16],
+    pub vmpl: u32,
+    pub signature_algo: u32,
+    pub current_tcb: SnpTcb,
+    pub platform_info: u64,
+    /// `AUTHOR_KEY_EN`, `MASK_CHIP_KEY` and `SIGNING_KEY`.
+    pub flags: u32,
+    pub report_data: [u8; 64],
+    /// Launch digest of the guest.
+    pub measurement: [u8; 48],
+    pub host_data: [u8; 32],
+    pub id_key_digest: [u8; 48],
+    pub author_key_digest: [u8; 48],
+    pub report_id: [u8; 32],
+    pub report_id_ma: [u8; 32],
+    /// The TCB the VCEK signing the report was derived from.
+    pub reported_tcb: SnpTcb,
+    pub chip_id: [u8; 64],
+    pub committed_tcb: SnpTcb,
+    pub launch_tcb: SnpTcb,
+    /// Raw `r || s` signature, big-endian.
+    pub signature: [u8; 96],
+    /// The signed part of the report.
+    pub signed_data: Vec<u8>,
+}
+
+impl SnpReport {
+    pub fn parse(bytes: &[u8]) -> eyre::Result<Self> {
+        if bytes.len() != SNP_REPORT_SIZE {
+            eyre::bail!(
+                "SEV-SNP report has size {}, expected {}",
+                bytes.len(),
+                SNP_REPORT_SIZE
+            );
+        }
+        let mut reader = TssReader::new(bytes);
+        let version = read_u32_le(&mut reader)?;
+        if version < 2 {
+            eyre::bail!("unsupported SEV-SNP report version {}", version);
+        }
+        let guest_svn = read_u32_le(&mut reader)?;
+        let policy = read_u64_le(&mut reader)?;
+        let family_id = reader.read_array()?;
+        let image_id = reader.read_array()?;
+        let vmpl = read_u32_le(&mut reader)?;
+        let signature_algo = read_u32_le(&mut reader)?;
+        let current_tcb = SnpTcb::from_u64(read_u64_le(&mut reader)?);
+        let platform_info = read_u64_le(&mut reader)?;
+        let flags = read_u32_le(&mut reader)?;
+        reader.skip(4)?;
+        let report_data = reader.read_array()?;
+        let measurement = reader.read_array()?;
+        let host_data = reader.read_array()?;
+        let id_key_digest = reader.read_array()?;
+        let author_key_digest = reader.read_array()?;
+        let report_id = reader.read_array()?;
+        let report_id_ma = reader.read_array()?;
+        let reported_tcb = SnpTcb::from_u64(read_u64_le(&mut reader)?);
+        reader.skip(0x1A0 - 0x188)?;
+        let chip_id = reader.read_array()?;
+        let committed_tcb = SnpTcb::from_u64(read_u64_le(&mut reader)?);
+        reader.skip(8)?;
+        let launch_tcb = SnpTcb::from_u64(read_u64_le(&mut reader)?);
+
+        // r and s are little-endian and zero padded to 72 bytes.
+        let raw_signature = &bytes[SNP_SIGNED_SIZE..];
+        let mut signature = [0u8; 96];
+        for (half, component) in signature.chunks_mut(48).zip(raw_signature.chunks(72)) {
+            if component[48..].iter().any(|b| *b != 0) {
+                eyre::bail!("SEV-SNP report signature is not P-384");
+            }
+            half.copy_from_slice(&component[..48]);
+            half.reverse();
+        }
+
+        Ok(Self {
+            version,
+            guest_svn,
+            policy,
+            family_id,
+            image_id,
+            vmpl,
+            signature_algo,
+            current_tcb,
+            platform_info,
+            flags,
+            report_data,
+            measurement,
+            host_data,
+            id_key_digest,
+            author_key_digest,
+            report_id,
+            report_id_ma,
+            reported_tcb,
+            chip_id,
+            committed_tcb,
+            launch_tcb,
+            signature,
+            signed_data: bytes[..SNP_SIGNED_SIZE].to_vec(),
+        })
+    }
+
+    /// Whether the chip id is masked, so the VCEK cannot be looked up by it.
+    pub fn is_chip_id_masked(&self) -> bool {
+        self.flags & 0b10 != 0
+    }
+
+    /// Which key signed the report: 0 for the VCEK, 1 for a VLEK.
+    pub fn signing_key(&self) -> u32 {
+        (self.flags >> 2) & 0b111
+    }
+
+    /// Check the launch digest of the guest.
+    pub fn check_measurement(&self, expected: &[u8; 48]) -> eyre::Result<()> {
+        if self.measurement != *expected {
+            eyre::bail!(
+                "SEV-SNP measurement {} does not match {}",
+                hex::encode(self.measurement),
+                hex::encode(expected)
+            );
+        }
+        Ok(())
+    }
+}
+
+fn read_u32_le(reader: &mut TssReader) -> Result<u32, TssError> {
+    Ok(u32::from_le_bytes(reader.read_array()?))
+}
+
+fn read_u64_le(reader: &mut TssReader) -> Result<u64, TssError> {
+    Ok(u64::from_le_bytes(reader.read_array()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::SnpTestPki;
+
+    #[test]
+    fn test_parse_report() -> eyre::Result<()> {
+        let tcb = SnpTcb {
+            boot_loader: 3,
+            tee: 0,
+            snp: 14,
+            microcode: 209,
+        };
+        assert_eq!(SnpTcb::from_u64(tcb.to_u64()), tcb);
+
+        let bytes = SnpTestPki::new(tcb).report(tcb, &[0x42; 64]);
+        let report = SnpReport::parse(&bytes)?;
+        assert_eq!(report.version, 3);
+        assert_eq!(report.reported_tcb, tcb);
+        assert_eq!(report.report_data, [0x42; 64]);
+        assert_eq!(report.chip_id, [0xC1; 64]);
+        assert_eq!(report.signing_key(), 0);
+        assert!(!report.is_chip_id_masked());
+        report.check_measurement(&[0x33; 48])?;
+        assert!(report.check_measurement(&[0; 48]).is_err());
+
+        assert!(SnpReport::parse(&bytes[1..]).is_err());
+        assert!(tcb.is_at_least(&SnpTcb::default()));
+        assert!(!SnpTcb::default().is_at_least(&tcb));
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+use chrono::{DateTime, Utc};
+use dcap::check_validity;
+use der::asn1::{ObjectIdentifier, OctetString};
+use der::{Decode, Encode};
+use p384::ecdsa::signature::Verifier;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use sha2::Sha384;
+use x509_cert::Certificate;
+
+use super::{SnpReport, SnpTcb, SIGNATURE_ALGO_ECDSA_P384_SHA384};
+
+/// `id-RSASSA-PSS`, which signs the ARK, ASK and VCEK certificates.
+pub const RSASSA_PSS_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.10");
+
+/// Extensions of VCEK certificates.
+pub mod vcek_oids {
+    use der::asn1::ObjectIdentifier;
+
+    pub const BOOT_LOADER_SPL: ObjectIdentifier =
+        ObjectIdentifier::new_unwrap("1.3.6.1.4.1.3704.1.3.1");
+    pub const TEE_SPL: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.3704.1.3.2");
+    pub const SNP_SPL: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.3704.1.3.3");
+    pub const MICROCODE_SPL: ObjectIdentifier =
+        ObjectIdentifier::new_unwrap("1.3.6.1.4.1.3704.1.3.8");
+    pub const HW_ID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.3704.1.4");
+}
+
+/// The certificates of a VCEK: the VCEK itself, the AMD SEV Key (ASK) and
+/// the AMD Root Key (ARK) of the product line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnpCertChain {
+    pub vcek: Certificate,
+    pub ask: Certificate,
+    pub ark: Certificate,
+}
+
+impl SnpCertChain {
+    /// Check that `ark` is the trusted ARK and signed itself and the ASK,
+    /// that the ASK signed the VCEK, and that all are valid at `at`.
+    pub fn validate(&self, trusted_ark: &Certificate, at: DateTime<Utc>) -> eyre::Result<()> {
+        if self.ark != *trusted_ark {
+            eyre::bail!("ARK is not the trusted AMD root key");
+        }
+        for (cert, issuer) in [
+            (&self.ark, &self.ark),
+            (&self.ask, &self.ark),
+            (&self.vcek, &self.ask),
+        ] {
+            check_validity(cert, at)?;
+            verify_rsa_pss(cert, issuer)?;
+        }
+        Ok(())
+    }
+
+    /// The TCB the VCEK was derived from.
+    pub fn vcek_tcb(&self) -> eyre::Result<SnpTcb> {
+        Ok(SnpTcb {
+            boot_loader: vcek_spl(&self.vcek, vcek_oids::BOOT_LOADER_SPL)?,
+            tee: vcek_spl(&self.vcek, vcek_oids::TEE_SPL)?,
+            snp: vcek_spl(&self.vcek, vcek_oids::SNP_SPL)?,
+            microcode: vcek_spl(&self.vcek, vcek_oids::MICROCODE_SPL)?,
+        })
+    }
+
+    /// The chip id the VCEK was issued for.
+    pub fn vcek_hw_id(&self) -> eyre::Result<Vec<u8>> {
+        let value = vcek_extension(&self.vcek, vcek_oids::HW_ID)?;
+        // Milan VCEKs carry the raw id rather than an OCTET STRING.
+        Ok(OctetString::from_der(value)
+            .map(OctetString::into_bytes)
+            .unwrap_or_else(|_| value.to_vec()))
+    }
+}
+
+/// Verify an SEV-SNP attestation report signed by a VCEK, through `chain`
+/// up to `trusted_ark`, and parse it.
+///
+/// The ARK should be pinned to the one AMD publishes for the product line;
+/// the one served with the chain by the KDS does not prove anything by
+/// itself. Appraising the measurement and the TCB is up to the caller.
+pub fn verify_snp_report(
+    report: &[u8],
+    chain: &SnpCertChain,
+    trusted_ark: &Certificate,
+    at: DateTime<Utc>,
+) -> eyre::Result<SnpReport> {
+    let report = SnpReport::parse(report)?;
+    if report.signature_algo != SIGNATURE_ALGO_ECDSA_P384_SHA384 {
+        eyre::bail!(
+            "unsupported SEV-SNP signature algorithm {}",
+            report.signature_algo
+        );
+    }
+    if report.signing_key() != 0 {
+        eyre::bail!("SEV-SNP report is not signed by a VCEK");
+    }
+
+    chain
+        .validate(trusted_ark, at)
+        .map_err(|err| err.wrap_err("VCEK certificate chain is not trusted"))?;
+    let vcek_tcb = chain.vcek_tcb()?;
+    if vcek_tcb != report.reported_tcb {
+        eyre::bail!(
+            "VCEK is for TCB {:?}, report for {:?}",
+            vcek_tcb,
+            report.reported_tcb
+        );
+    }
+    if !report.is_chip_id_masked() && chain.vcek_hw_id()? != report.chip_id {
+        eyre::bail!("VCEK is for another chip");
+    }
+
+    let spki = &chain.vcek.tbs_certificate.subject_public_key_info;
+    let key = p384::ecdsa::VerifyingKey::from_sec1_bytes(spki.subject_public_key.raw_bytes())
+        .map_err(|_| eyre::eyre!("VCEK does not carry a P-384 public key"))?;
+    let signature = p384::ecdsa::Signature::from_slice(&report.signature)
+        .map_err(|_| eyre::eyre!("malformed SEV-SNP report signature"))?;
+    key.verify(&report.signed_data, &signature)
+        .map_err(|_| eyre::eyre!("SEV-SNP report signature is invalid"))?;
+    Ok(report)
+}
+
+fn verify_rsa_pss(cert: &Certificate, issuer: &Certificate) -> eyre::Result<()> {
+    let subject = cert.tbs_certificate.subject.to_string();
+    if cert.tbs_certificate.issuer != issuer.tbs_certificate.subject {
+        eyre::bail!("certificate {} is not issued by its parent", subject);
+    }
+    if cert.signature_algorithm.oid != RSASSA_PSS_OID {
+        eyre::bail!("certificate {} is not signed with RSASSA-PSS", subject);
+    }
+    let spki = &issuer.tbs_certificate.subject_public_key_info;
+    let key = rsa::RsaPublicKey::from_pkcs1_der(spki.subject_public_key.raw_bytes())
+        .map_err(|_| eyre::eyre!("issuer of {} does not carry an RSA key", subject))?;
+    let signature = rsa::pss::Signature::try_from(cert.signature.raw_bytes())
+        .map_err(|_| eyre::eyre!("malformed signature on certificate {}", subject))?;
+    rsa::pss::VerifyingKey::<Sha384>::new(key)
+        .verify(&cert.tbs_certificate.to_der()?, &signature)
+        .map_err(|_| eyre::eyre!("invalid signature on certificate {}", subject))
+}
+
+fn vcek_extension(vcek: &Certificate, oid: ObjectIdentifier) -> eyre::Result<&[u8]> {
+    vcek.tbs_certificate
+        .extensions
+        .iter()
+        .flatten()
+        .find(|extension| extension.extn_id == oid)
+        .map(|extension| extension.extn_value.as_bytes())
+        .ok_or_else(|| eyre::eyre!("VCEK has no extension {}", oid))
+}
+
+fn vcek_spl(vcek: &Certificate, oid: ObjectIdentifier) -> eyre::Result<u8> {
+    Ok(u8::from_der(vcek_extension(vcek, oid)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use dcap::test_utils::verification_time;
+
+    use super::*;
+    use crate::test_utils::SnpTestPki;
+
+    fn tcb() -> SnpTcb {
+        SnpTcb {
+            boot_loader: 4,
+            tee: 0,
+            snp: 22,
+            microcode: 213,
+        }
+    }
+
+    #[test]
+    fn test_verify_report() -> eyre::Result<()> {
+        let pki = SnpTestPki::new(tcb());
+        let report = pki.report(tcb(), &[0x42; 64]);
+        let at = verification_time();
+
+        let parsed = verify_snp_report(&report, &pki.chain, &pki.chain.ark, at)?;
+        assert_eq!(parsed.report_data, [0x42; 64]);
+        assert_eq!(pki.chain.vcek_tcb()?, tcb());
+
+        let mut tampered = report.clone();
+        tampered[0x90] ^= 1;
+        assert!(verify_snp_report(&tampered, &pki.chain, &pki.chain.ark, at).is_err());
+
+        let mut older = tcb();
+        older.microcode -= 1;
+        let err = verify_snp_report(&pki.report(older, &[0; 64]), &pki.chain, &pki.chain.ark, at)
+            .unwrap_err();
+        assert!(err.to_string().contains("TCB"));
+
+        let err = verify_snp_report(&report, &pki.chain, &pki.chain.ask, at).unwrap_err();
+        assert!(format!("{:#}", err).contains("trusted AMD root key"));
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+//! Synthetic SEV-SNP and Nitro Enclaves PKIs for unit tests.
+
+pub use snp_pki::*;
+
+mod snp_pki {
+    use std::str::FromStr;
+    use std::sync::OnceLock;
+
+    use dcap::test_utils::{validity, NOT_AFTER, NOT_BEFORE};
+    use der::asn1::{Any, ObjectIdentifier, OctetString};
+    use der::oid::AssociatedOid;
+    use der::{Encode, Length, Writer};
+    use p384::ecdsa::signature::Signer;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use rsa::pss::{Signature as PssSignature, SigningKey as PssSigningKey};
+    use sha2::Sha384;
+    use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+    use x509_cert::ext::{AsExtension, Extension};
+    use x509_cert::name::Name;
+    use x509_cert::serial_number::SerialNumber;
+    use x509_cert::spki::SubjectPublicKeyInfoOwned;
+
+    use crate::snp::{
+        vcek_oids, SnpCertChain, SnpTcb, SIGNATURE_ALGO_ECDSA_P384_SHA384, SNP_REPORT_SIZE,
+        SNP_SIGNED_SIZE,
+    };
+
+    const ARK_SUBJECT: &str = "CN=ARK-Genoa,O=Advanced Micro Devices,L=Santa Clara,ST=CA,C=US";
+    const ASK_SUBJECT: &str = "CN=SEV-Genoa,O=Advanced Micro Devices,L=Santa Clara,ST=CA,C=US";
+    const VCEK_SUBJECT: &str = "CN=SEV-VCEK,O=Advanced Micro Devices,L=Santa Clara,ST=CA,C=US";
+
+    /// A VCEK extension. The OID varies, so `to_extension` uses `oid`
+    /// rather than the associated one.
+    struct VcekExt {
+        oid: ObjectIdentifier,
+        value: Any,
+    }
+
+    impl AssociatedOid for VcekExt {
+        const OID: ObjectIdentifier = vcek_oids::HW_ID;
+    }
+
+    impl Encode for VcekExt {
+        fn encoded_len(&self) -> der::Result<Length> {
+            self.value.encoded_len()
+        }
+
+        fn encode(&self, writer: &mut impl Writer) -> der::Result<()> {
+            self.value.encode(writer)
+        }
+    }
+
+    impl AsExtension for VcekExt {
+        fn critical(&self, _subject: &Name, _extensions: &[Extension]) -> bool {
+            false
+        }
+
+        fn to_extension(
+            &self,
+            _subject: &Name,
+            _extensions: &[Extension],
+        ) -> der::Result<Extension> {
+            Ok(Extension {
+                extn_id: self.oid,
+                critical: false,
+                extn_value: OctetString::new(self.value.to_der()?)?,
+            })
+        }
+    }
+
+    /// RSA keys of the ARK and ASK. Generating them is slow in debug builds,
+    /// so they are shared between tests.
+    fn rsa_keys() -> &'static (PssSigningKey<Sha384>, PssSigningKey<Sha384>) {
+        static KEYS: OnceLock<(PssSigningKey<Sha384>, PssSigningKey<Sha384>)> = OnceLock::new();
+        KEYS.get_or_init(|| {
+            let mut rng = StdRng::seed_from_u64(7);
+            let mut key = || PssSigningKey::new(rsa::RsaPrivateKey::new(&mut rng, 1024).unwrap());
+            (key(), key())
+        })
+    }
+
+    fn build_rsa_signed(
+        profile: Profile,
+        serial: u32,
+        subject: &str,
+        spki: SubjectPublicKeyInfoOwned,
+        issuer_key: &PssSigningKey<Sha384>,
+        customize: impl FnOnce(&mut CertificateBuilder<'_, PssSigningKey<Sha384>>),
+    ) -> x509_cert::Certificate {
+        let mut builder = CertificateBuilder::new(
+            profile,
+            SerialNumber::from(serial),
+            validity(NOT_BEFORE, NOT_AFTER),
+            Name::from_str(subject).unwrap(),
+            spki,
+            issuer_key,
+        )
+        .unwrap();
+        customize(&mut builder);
+        builder
+            .build_with_rng::<PssSignature>(&mut StdRng::seed_from_u64(serial.into()))
+            .unwrap()
+    }
+
+    /// An ARK, an ASK and a VCEK for `tcb` and the chip id `[0xC1; 64]`.
+    pub struct SnpTestPki {
+        pub vcek_key: p384::ecdsa::SigningKey,
+        pub chain: SnpCertChain,
+    }
+
+    impl SnpTestPki {
+        pub fn new(tcb: SnpTcb) -> Self {
+            let (ark_key, ask_key) = rsa_keys();
+            let rsa_spki = |key: &PssSigningKey<Sha384>| {
+                SubjectPublicKeyInfoOwned::from_key(rsa::RsaPublicKey::from(key.as_ref())).unwrap()
+            };
+            let ark = build_rsa_signed(
+                Profile::Root,
+                1,
+                ARK_SUBJECT,
+                rsa_spki(ark_key),
+                ark_key,
+                |_| {},
+            );
+            let ask = build_rsa_signed(
+                Profile::SubCA {
+                    issuer: Name::from_str(ARK_SUBJECT).unwrap(),
+                    path_len_constraint: Some(0),
+                },
+                2,
+                ASK_SUBJECT,
+                rsa_spki(ask_key),
+                ark_key,
+                |_| {},
+            );
+
+            let vcek_key = p384::ecdsa::SigningKey::from_bytes(&[6; 48].into()).unwrap();
+            let spl = |oid, value: u8| VcekExt {
+                oid,
+                value: Any::encode_from(&value).unwrap(),
+            };
+            let extensions = [
+                spl(vcek_oids::BOOT_LOADER_SPL, tcb.boot_loader),
+                spl(vcek_oids::TEE_SPL, tcb.tee),
+                spl(vcek_oids::SNP_SPL, tcb.snp),
+                spl(vcek_oids::MICROCODE_SPL, tcb.microcode),
+                VcekExt {
+                    oid: vcek_oids::HW_ID,
+                    value: Any::encode_from(&OctetString::new([0xC1; 64]).unwrap()).unwrap(),
+                },
+            ];
+            let vcek = build_rsa_signed(
+                Profile::Leaf {
+                    issuer: Name::from_str(ASK_SUBJECT).unwrap(),
+                    enable_key_agreement: false,
+                    enable_key_encipherment: false,
+                },
+                3,
+                VCEK_SUBJECT,
+                SubjectPublicKeyInfoOwned::from_key(*vcek_key.verifying_key()).unwrap(),
+                ask_key,
+                |builder| {
+                    for extension in &extensions {
+                        builder.add_extension(extension).unwrap();
+                    }
+                },
+            );
+
+            Self {
+                vcek_key,
+                chain: SnpCertChain { vcek, ask, ark },
+            }
+        }
+
+        /// A version 3 report for `tcb` signed by the VCEK, with the
+        /// measurement `[0x33; 48]`.
+        pub fn report(&self, tcb: SnpTcb, report_data: &[u8; 64]) -> Vec<u8> {
+            let mut report = vec![0u8; SNP_REPORT_SIZE];
+            report[0x00..0x04].copy_from_slice(&3u32.to_le_bytes());
+            report[0x08..0x10].copy_from_slice(&0x30000u64.to_le_bytes());
+            report[0x34..0x38].copy_from_slice(&SIGNATURE_ALGO_ECDSA_P384_SHA384.to_le_bytes());
+            for offset in [0x38, 0x180, 0x1E0, 0x1F0] {
+                report[offset..offset + 8].copy_from_slice(&tcb.to_u64().to_le_bytes());
+            }
+            report[0x50..0x90].copy_from_slice(report_data);
+            report[0x90..0xC0].copy_from_slice(&[0x33; 48]);
+            report[0x1A0..0x1E0].copy_from_slice(&[0xC1; 64]);
+
+            let signature: p384::ecdsa::Signature = self.vcek_key.sign(&report[..SNP_SIGNED_SIZE]);
+            let bytes = signature.to_bytes();
+            for (i, component) in bytes.chunks(48).enumerate() {
+                let start = SNP_SIGNED_SIZE + i * 72;
+                let out = &mut report[start..start + 48];
+                out.copy_from_slice(component);
+                out.reverse();
+            }
+            report
+        }
+    }
+}
+
+pub use nitro_pki::*;
+
+mod nitro_pki {
+    use std::str::FromStr;
+
+    use dcap::test_utils::{validity, NOT_AFTER, NOT_BEFORE};
+    use p384::ecdsa::signature::Signer;
+    use p384::ecdsa::{DerSignature, Signature, SigningKey};
+    use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+    use x509_cert::name::Name;
+    use x509_cert::serial_number::SerialNumber;
+    use x509_cert::spki::SubjectPublicKeyInfoOwned;
+    use x509_cert::Certificate;
+
+    use crate::cbor::Value;
+
+    const ROOT_SUBJECT: &str = "CN=aws.nitro-enclaves,OU=AWS,O=Amazon,C=US";
+    const INTERMEDIATE_SUBJECT: &str = "CN=zonal.aws.nitro-enclaves,OU=AWS,O=Amazon,C=US";
+    const LEAF_SUBJECT: &str = "CN=i-0123456789abcdef0-enc0123456789abcdef,OU=AWS,O=Amazon,C=US";
+
+    fn build_p384(
+        profile: Profile,
+        serial: u32,
+        subject: &str,
+        subject_key: &SigningKey,
+        issuer_key: &SigningKey,
+    ) -> Certificate {
+        let spki = SubjectPublicKeyInfoOwned::from_key(*subject_key.verifying_key()).unwrap();
+        CertificateBuilder::new(
+            profile,
+            SerialNumber::from(serial),
+            validity(NOT_BEFORE, NOT_AFTER),
+            Name::from_str(subject).unwrap(),
+            spki,
+            issuer_key,
+        )
+        .unwrap()
+        .build::<DerSignature>()
+        .unwrap()
+    }
+
+    /// A root, an intermediate and a signing certificate shaped like the
+    /// ones of AWS Nitro Enclaves.
+    pub struct NitroTestPki {
+        pub signing_key: SigningKey,
+        pub root: Certificate,
+        pub intermediate: Certificate,
+        pub leaf: Certificate,
+    }
+
+    impl Default for NitroTestPki {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl NitroTestPki {
+        pub fn new() -> Self {
+            Self::with_intermediate(Profile::SubCA {
+                issuer: Name::from_str(ROOT_SUBJECT).unwrap(),
+                path_len_constraint: Some(0),
+            })
+        }
+
+        /// A PKI whose intermediate is a leaf certificate, which may not
+        /// issue the signing certificate.
+        pub fn with_non_ca_intermediate() -> Self {
+            Self::with_intermediate(Profile::Leaf {
+                issuer: Name::from_str(ROOT_SUBJECT).unwrap(),
+                enable_key_agreement: false,
+                enable_key_encipherment: false,
+            })
+        }
+
+        fn with_intermediate(profile: Profile) -> Self {
+            let key = |seed: u8| SigningKey::from_bytes(&[seed; 48].into()).unwrap();
+            let (root_key, intermediate_key, signing_key) = (key(1), key(2), key(3));
+            let root = build_p384(Profile::Root, 1, ROOT_SUBJECT, &root_key, &root_key);
+            let intermediate = build_p384(
+                profile,
+                2,
+                INTERMEDIATE_SUBJECT,
+                &intermediate_key,
+                &root_key,
+            );
+            let leaf = build_p384(
+                Profile::Leaf {
+                    issuer: Name::from_str(INTERMEDIATE_SUBJECT).unwrap(),
+                    enable_key_agreement: false,
+                    enable_key_encipherment: false,
+                },
+                3,
+                LEAF_SUBJECT,
+                &signing_key,
+                &intermediate_key,
+            );
+            Self {
+                signing_key,
+                root,
+                intermediate,
+                leaf,
+            }
+        }
+
+        /// A signed document with PCR0 `[0x10; 48]`, PCR1 `[0x11; 48]` and
+        /// PCR2 `[0x12; 48]`, timestamped at the verification time.
+        pub fn document(&self, nonce: &[u8]) -> Vec<u8> {
+            use der::Encode;
+
+            let pcrs = (0..3u8)
+                .map(|i| (Value::Unsigned(i.into()), Value::Bytes(vec![0x10 + i; 48])))
+                .collect();
+            let payload = Value::Map(vec![
+                (
+                    Value::text("module_id"),
+                    Value::text("i-0123456789abcdef0-enc0123456789abcdef"),
+                ),
+                (Value::text("digest"), Value::text("SHA384")),
+                (Value::text("timestamp"), Value::Unsigned(1_740_787_200_000)),
+                (Value::text("pcrs"), Value::Map(pcrs)),
+                (
+                    Value::text("certificate"),
+                    Value::Bytes(self.leaf.to_der().unwrap()),
+                ),
+                (
+                    Value::text("cabundle"),
+                    Value::Array(vec![
+                        Value::Bytes(self.root.to_der().unwrap()),
+                        Value::Bytes(self.intermediate.to_der().unwrap()),
+                    ]),
+                ),
+                (Value::text("public_key"), Value::Null),
+                (Value::text("user_data"), Value::Null),
+                (Value::text("nonce"), Value::Bytes(nonce.to_vec())),
+            ])
+            .encode();
+            let protected = Value::Map(vec![(Value::Unsigned(1), Value::integer(-35))]).encode();
+            let signed_data = Value::Array(vec![
+                Value::text("Signature1"),
+                Value::Bytes(protected.clone()),
+                Value::Bytes(Vec::new()),
+                Value::Bytes(payload.clone()),
+            ])
+            .encode();
+            let signature: Signature = self.signing_key.sign(&signed_data);
+            Value::Tag(
+                18,
+                Box::new(Value::Array(vec![
+                    Value::Bytes(protected),
+                    Value::Map(Vec::new()),
+                    Value::Bytes(payload),
+                    Value::Bytes(signature.to_bytes().to_vec()),
+                ])),
+            )
+            .encode()
+        }
+    }
+}
@@ -0,0 +1,470 @@
+//! Golden measurements for TPM evidence.
+//!
+//! A [`TpmPolicy`] lists the expected PCR values per bank, the events each
+//! PCR may have in the event log and the accepted firmware versions. It is
+//! evaluated against the quoted PCRs and the replayed log of evidence that
+//! already verified, e.g. through
+//! [`ChallengeVerifier`](crate::ChallengeVerifier), and yields a
+//! [`ComplianceReport`] of every check rather than the first failure.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{event_type, EventLog};
+
+/// A PCR bank, by hash algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PcrBank {
+    Sha1,
+    Sha256,
+    Sha384,
+}
+
+impl PcrBank {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PcrBank::Sha1 => "sha1",
+            PcrBank::Sha256 => "sha256",
+            PcrBank::Sha384 => "sha384",
+        }
+    }
+
+    pub fn digest_size(&self) -> usize {
+        match self {
+            PcrBank::Sha1 => 20,
+            PcrBank::Sha256 => 32,
+            PcrBank::Sha384 => 48,
+        }
+    }
+}
+
+/// An event the log may contain, by type and, optionally, SHA-256 digest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventRule {
+    pub event_type: u32,
+    /// Hex encoded; any digest is allowed when absent.
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+/// Golden measurements of a TPM platform.
+///
+/// Digests are hex encoded. Every constraint is optional; a PCR listed in
+/// `pcrs` or `allowed_events` must be present in the evidence.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TpmPolicy {
+    /// Allowed values by bank and PCR index.
+    pub pcrs: BTreeMap<PcrBank, BTreeMap<u32, Vec<String>>>,
+    /// The events each PCR may have in the log. Every extended event of a
+    /// listed PCR must match one of its rules.
+    pub allowed_events: BTreeMap<u32, Vec<EventRule>>,
+    /// Bounds on the version of the GCE virtual firmware.
+    pub min_firmware_version: Option<u32>,
+    pub max_firmware_version: Option<u32>,
+}
+
+impl TpmPolicy {
+    pub fn from_json(json: &str) -> eyre::Result<Self> {
+        let policy: Self = serde_json::from_str(json)?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> eyre::Result<Self> {
+        let policy: Self = toml::from_str(toml)?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Check that every digest is hex of the size of its bank.
+    pub fn validate(&self) -> eyre::Result<()> {
+        for (bank, pcrs) in &self.pcrs {
+            for (index, values) in pcrs {
+                for value in values {
+                    check_digest(value, bank.digest_size())
+                        .map_err(|err| err.wrap_err(format!("{} PCR{}", bank.as_str(), index)))?;
+                }
+            }
+        }
+        for (index, rules) in &self.allowed_events {
+            for digest in rules.iter().filter_map(|rule| rule.sha256.as_ref()) {
+                check_digest(digest, 32)
+                    .map_err(|err| err.wrap_err(format!("event rule of PCR{}", index)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluate every constraint against `measurements`.
+    pub fn evaluate(&self, measurements: &TpmMeasurements) -> ComplianceReport {
+        let mut checks = Vec::new();
+
+        for (bank, pcrs) in &self.pcrs {
+            for (index, allowed) in pcrs {
+                let name = format!("pcr.{}.{}", bank.as_str(), index);
+                let value = measurements
+                    .banks
+                    .get(bank)
+                    .and_then(|pcrs| pcrs.get(index));
+                let outcome = match value {
+                    None => CheckOutcome::NotEvaluated(format!("PCR{} is not quoted", index)),
+                    Some(value) => {
+                        let value = hex::encode(value);
+                        if allowed.is_empty()
+                            || allowed.iter().any(|v| v.eq_ignore_ascii_case(&value))
+                        {
+                            CheckOutcome::Pass
+                        } else {
+                            CheckOutcome::Fail(format!("value {} is not allowed", value))
+                        }
+                    }
+                };
+                checks.push(ComplianceCheck { name, outcome });
+            }
+        }
+
+        for (index, rules) in &self.allowed_events {
+            let name = format!("events.pcr{}", index);
+            let outcome = match &measurements.event_log {
+                None => CheckOutcome::NotEvaluated("no event log".to_string()),
+                Some(log) => evaluate_events(log, *index, rules),
+            };
+            checks.push(ComplianceCheck { name, outcome });
+        }
+
+        if self.min_firmware_version.is_some() || self.max_firmware_version.is_some() {
+            let version = measurements
+                .event_log
+                .as_ref()
+                .and_then(EventLog::gce_firmware_version);
+            let outcome = match version {
+                None => {
+                    CheckOutcome::NotEvaluated("no firmware version in the event log".to_string())
+                }
+                Some(version) if self.min_firmware_version.is_some_and(|min| version < min) => {
+                    CheckOutcome::Fail(format!("firmware version {} is too old", version))
+                }
+                Some(version) if self.max_firmware_version.is_some_and(|max| version > max) => {
+                    CheckOutcome::Fail(format!("firmware version {} is too new", version))
+                }
+                Some(_) => CheckOutcome::Pass,
+            };
+            checks.push(ComplianceCheck {
+                name: "firmware_version".to_string(),
+                outcome,
+            });
+        }
+
+        ComplianceReport { checks }
+    }
+}
+
+fn evaluate_events(log: &EventLog, index: u32, rules: &[EventRule]) -> CheckOutcome {
+    let mut extended = 0;
+    let mut disallowed = Vec::new();
+    let events = log
+        .events
+        .iter()
+        .enumerate()
+        .filter(|(_, event)| event.pcr_index == index)
+        .filter(|(_, event)| event.event_type != event_type::NO_ACTION);
+    for (position, event) in events {
+        extended += 1;
+        let digest = event.sha256.map(hex::encode);
+        let allowed = rules.iter().any(|rule| {
+            rule.event_type == event.event_type
+                && rule.sha256.as_ref().is_none_or(|expected| {
+                    digest
+                        .as_ref()
+                        .is_some_and(|digest| expected.eq_ignore_ascii_case(digest))
+                })
+        });
+        if !allowed {
+            disallowed.push(format!(
+                "event {} of type {:#x} ({})",
+                position,
+                event.event_type,
+                digest.as_deref().unwrap_or("no SHA-256 digest")
+            ));
+        }
+    }
+    if extended == 0 {
+        CheckOutcome::NotEvaluated(format!("the log extends no event into PCR{}", index))
+    } else if disallowed.is_empty() {
+        CheckOutcome::Pass
+    } else {
+        CheckOutcome::Fail(format!("disallowed {}", disallowed.join(", ")))
+    }
+}
+
+fn check_digest(value: &str, size: usize) -> eyre::Result<()> {
+    let bytes =
+        hex::decode(value).map_err(|err| eyre::eyre!("invalid digest {}: {}", value, err))?;
+    if bytes.len() != size {
+        eyre::bail!("digest {} must be {} bytes", value, size);
+    }
+    Ok(())
+}
+
+/// The measurements of verified TPM evidence.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TpmMeasurements {
+    /// Quoted PCR values by bank and index.
+    pub banks: BTreeMap<PcrBank, BTreeMap<u32, Vec<u8>>>,
+    /// The event log, already replayed against the quoted values.
+    pub event_log: Option<EventLog>,
+}
+
+impl TpmMeasurements {
+    /// Measurements of a quote of the SHA-256 bank, as verified by
+    /// [`TpmAttest::verify_pcrs`](dcap::TpmAttest::verify_pcrs) and
+    /// [`EventLog::verify_pcrs`].
+    pub fn from_sha256(pcrs: &BTreeMap<u32, [u8; 32]>, event_log: Option<EventLog>) -> Self {
+        let sha256 = pcrs
+            .iter()
+            .map(|(index, value)| (*index, value.to_vec()))
+            .collect();
+        Self {
+            banks: BTreeMap::from([(PcrBank::Sha256, sha256)]),
+            event_log,
+        }
+    }
+}
+
+/// The outcome of a single check of a [`TpmPolicy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    Pass,
+    Fail(String),
+    /// The evidence lacks what the check needs, e.g. a PCR bank that was
+    /// not quoted.
+    NotEvaluated(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComplianceCheck {
+    /// What was checked, e.g. `pcr.sha256.7` or `events.pcr0`.
+    pub name: String,
+    pub outcome: CheckOutcome,
+}
+
+/// The outcome of [`TpmPolicy::evaluate`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComplianceReport {
+    pub checks: Vec<ComplianceCheck>,
+}
+
+impl ComplianceReport {
+    /// Whether every check passed. A check that could not be evaluated does
+    /// not.
+    pub fn is_compliant(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.outcome == CheckOutcome::Pass)
+    }
+
+    /// The checks that failed or could not be evaluated.
+    pub fn failures(&self) -> impl Iterator<Item = &ComplianceCheck> {
+        self.checks
+            .iter()
+            .filter(|check| check.outcome != CheckOutcome::Pass)
+    }
+}
+
+impl std::fmt::Display for ComplianceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for check in &self.checks {
+            match &check.outcome {
+                CheckOutcome::Pass => writeln!(f, "pass  {}", check.name)?,
+                CheckOutcome::Fail(reason) => writeln!(f, "FAIL  {}: {}", check.name, reason)?,
+                CheckOutcome::NotEvaluated(reason) => {
+                    writeln!(f, "SKIP  {}: {}", check.name, reason)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Named [`TpmPolicy`]s, e.g. one per released image.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TpmPolicyStore {
+    policies: BTreeMap<String, TpmPolicy>,
+}
+
+impl TpmPolicyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load every `*.json` (and, with the `toml` feature, `*.toml`) file of
+    /// `dir`, named after its file stem.
+    pub fn load_dir(dir: impl AsRef<Path>) -> eyre::Result<Self> {
+        let mut store = Self::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let policy = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("json") => TpmPolicy::from_json(&std::fs::read_to_string(&path)?),
+                #[cfg(feature = "toml")]
+                Some("toml") => TpmPolicy::from_toml(&std::fs::read_to_string(&path)?),
+                _ => continue,
+            };
+            let policy =
+                policy.map_err(|err| err.wrap_err(format!("invalid policy {}", path.display())))?;
+            store.insert(name, policy);
+        }
+        Ok(store)
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, policy: TpmPolicy) -> Option<TpmPolicy> {
+        self.policies.insert(name.into(), policy)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TpmPolicy> {
+        self.policies.get(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<TpmPolicy> {
+        self.policies.remove(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.policies.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.policies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+    }
+
+    /// Evaluate the policy `name`.
+    pub fn evaluate(
+        &self,
+        name: &str,
+        measurements: &TpmMeasurements,
+    ) -> eyre::Result<ComplianceReport> {
+        let policy = self
+            .get(name)
+            .ok_or_else(|| eyre::eyre!("no TPM policy named {}", name))?;
+        Ok(policy.evaluate(measurements))
+    }
+
+    /// The names of the policies `measurements` comply with.
+    pub fn matching<'a>(
+        &'a self,
+        measurements: &'a TpmMeasurements,
+    ) -> impl Iterator<Item = &'a str> {
+        self.policies
+            .iter()
+            .filter(|(_, policy)| policy.evaluate(measurements).is_compliant())
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dcap::test_utils::gce_tdx_log;
+
+    use super::*;
+
+    fn measurements() -> eyre::Result<TpmMeasurements> {
+        let log = EventLog::parse(&gce_tdx_log())?;
+        let pcrs = log.replay_sha256()?;
+        Ok(TpmMeasurements::from_sha256(&pcrs, Some(log)))
+    }
+
+    fn golden(measurements: &TpmMeasurements) -> TpmPolicy {
+        let sha256 = &measurements.banks[&PcrBank::Sha256];
+        let log = measurements.event_log.as_ref().unwrap();
+        TpmPolicy {
+            pcrs: BTreeMap::from([(
+                PcrBank::Sha256,
+                BTreeMap::from([(7, vec![hex::encode(&sha256[&7])])]),
+            )]),
+            allowed_events: BTreeMap::from([(
+                0,
+                log.events[1..3]
+                    .iter()
+                    .map(|event| EventRule {
+                        event_type: event.event_type,
+                        sha256: event.sha256.map(hex::encode),
+                    })
+                    .collect(),
+            )]),
+            min_firmware_version: Some(2),
+            max_firmware_version: None,
+        }
+    }
+
+    #[test]
+    fn test_evaluate() -> eyre::Result<()> {
+        let measurements = measurements()?;
+        let policy = golden(&measurements);
+        policy.validate()?;
+        let report = policy.evaluate(&measurements);
+        assert!(report.is_compliant(), "{}", report);
+        assert_eq!(report.checks.len(), 3);
+
+        let mut strict = policy.clone();
+        strict.allowed_events.get_mut(&0).unwrap().pop();
+        strict.min_firmware_version = Some(3);
+        strict
+            .pcrs
+            .insert(PcrBank::Sha384, BTreeMap::from([(0, Vec::new())]));
+        let report = strict.evaluate(&measurements);
+        let failures = report
+            .failures()
+            .map(|check| check.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            failures,
+            ["pcr.sha384.0", "events.pcr0", "firmware_version"]
+        );
+        assert!(matches!(
+            &report.failures().next().unwrap().outcome,
+            CheckOutcome::NotEvaluated(_)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_store() -> eyre::Result<()> {
+        let measurements = measurements()?;
+        let dir = tempfile::tempdir()?;
+        let golden = serde_json::to_string(&golden(&measurements))?;
+        std::fs::write(dir.path().join("image-v1.json"), &golden)?;
+        std::fs::write(
+            dir.path().join("image-v2.json"),
+            r#"{ "min_firmware_version": 3 }"#,
+        )?;
+        std::fs::write(dir.path().join("README"), "not a policy")?;
+
+        let store = TpmPolicyStore::load_dir(dir.path())?;
+        assert_eq!(store.names().collect::<Vec<_>>(), ["image-v1", "image-v2"]);
+        assert_eq!(
+            store.matching(&measurements).collect::<Vec<_>>(),
+            ["image-v1"]
+        );
+        assert!(!store.evaluate("image-v2", &measurements)?.is_compliant());
+        assert!(store.evaluate("image-v3", &measurements).is_err());
+
+        std::fs::write(
+            dir.path().join("bad.json"),
+            r#"{ "pcrs": { "sha256": { "0": ["abcd"] } } }"#,
+        )?;
+        assert!(TpmPolicyStore::load_dir(dir.path()).is_err());
+        Ok(())
+    }
+}
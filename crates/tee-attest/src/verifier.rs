@@ -0,0 +1,513 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use dcap::primitives::tcb_info::TcbStatus;
+use dcap::{
+    tee_type, verify_quote_with_anchors, verify_tpm_quote, CollateralFetcher, CollateralKey, Quote,
+    QuoteBody, ReportData, TpmAttest, TrustAnchors, VerificationResult, VerifyOptions,
+};
+use der::Decode;
+use tee_observe::{observe_with, Operation};
+use x509_cert::Certificate;
+
+use crate::nitro::{verify_nitro_document, NitroDocument};
+use crate::snp::{verify_snp_report, SnpCertChain, SnpReport};
+use crate::{Evidence, Policy, SnpEvidence, Tee, TpmEvidence, TpmTdxEvidence, Violation};
+
+/// The outcome of [`Verifier::verify`]: the evidence is authentic, and
+/// accepted by the policy if there are no violations.
+#[derive(Debug, Clone)]
+pub struct AttestationResult {
+    pub tee: Tee,
+    /// The data the evidence binds: the report data of SGX, TDX and SEV-SNP,
+    /// the qualifying data of a TPM quote, also for composite TPM and TDX
+    /// evidence, and the nonce of a Nitro document.
+    pub report_data: Vec<u8>,
+    /// Hex encoded measurements by name, e.g. `mr_td` or `pcr7`.
+    pub measurements: BTreeMap<String, String>,
+    /// The TCB status of SGX and TDX platforms.
+    pub tcb_status: Option<TcbStatus>,
+    pub advisory_ids: Vec<String>,
+    pub violations: Vec<Violation>,
+    pub details: Details,
+}
+
+impl AttestationResult {
+    pub fn is_accepted(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// The verified evidence, as parsed by the per-TEE verifier.
+#[derive(Debug, Clone)]
+pub enum Details {
+    Tpm(TpmAttest),
+    Dcap(Box<VerificationResult>),
+    SevSnp(Box<SnpReport>),
+    Nitro(Box<NitroDocument>),
+    TpmTdx {
+        tpm: TpmAttest,
+        dcap: Box<VerificationResult>,
+    },
+}
+
+/// Verifies the evidence of every supported TEE against the roots of trust
+/// it is configured with.
+///
+/// Only the Intel SGX Root CA is trusted by default. SEV-SNP, Nitro and TPM
+/// evidence is rejected until the AMD root keys, the AWS Nitro root and the
+/// enrolled TPM attestation keys are pinned.
+#[derive(Clone, Default)]
+pub struct Verifier {
+    collateral: Option<Arc<dyn CollateralFetcher + Send + Sync>>,
+    anchors: TrustAnchors,
+    amd_arks: Vec<Certificate>,
+    nitro_root: Option<Certificate>,
+    tpm_aks: Vec<Certificate>,
+}
+
+impl Verifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetch the collateral of SGX and TDX quotes through `fetcher`, e.g. a
+    /// [`CollateralCache`](dcap::CollateralCache).
+    pub fn with_collateral(
+        mut self,
+        fetcher: impl CollateralFetcher + Send + Sync + 'static,
+    ) -> Self {
+        self.collateral = Some(Arc::new(fetcher));
+        self
+    }
+
+    /// Trust `anchors` for SGX and TDX quotes instead of the Intel SGX Root
+    /// CA.
+    pub fn with_trust_anchors(mut self, anchors: TrustAnchors) -> Self {
+        self.anchors = anchors;
+        self
+    }
+
+    /// Trust the AMD root key of a product line. The one matching the
+    /// issuer of the ASK is used.
+    pub fn with_amd_ark(mut self, ark: Certificate) -> Self {
+        self.amd_arks.push(ark);
+        self
+    }
+
+    pub fn with_nitro_root(mut self, root: Certificate) -> Self {
+        self.nitro_root = Some(root);
+        self
+    }
+
+    /// Trust a TPM attestation key, enrolled out of band.
+    pub fn with_tpm_ak(mut self, ak_cert: Certificate) -> Self {
+        self.tpm_aks.push(ak_cert);
+        self
+    }
+
+    /// Verify `evidence` at the current time and appraise it with `policy`.
+    pub fn verify(&self, evidence: &Evidence, policy: &Policy) -> eyre::Result<AttestationResult> {
+        self.verify_at(evidence, policy, Utc::now())
+    }
+
+    /// Verify `evidence` at `at` and appraise it with `policy`.
+    ///
+    /// Fails if the evidence cannot be trusted at all; a policy rejection is
+    /// reported in the violations of the result. Checking that the report
+    /// data binds the expected nonce or key is up to the caller.
+    pub fn verify_at(
+        &self,
+        evidence: &Evidence,
+        policy: &Policy,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<AttestationResult> {
+        let verify = || match evidence {
+            Evidence::TpmQuote(tpm) => self.verify_tpm(tpm, policy),
+            Evidence::SgxQuote(quote) => self.verify_dcap(Tee::Sgx, quote, policy, at),
+            Evidence::TdxQuote(quote) => self.verify_dcap(Tee::Tdx, quote, policy, at),
+            Evidence::SevSnp(snp) => self.verify_snp(snp, policy, at),
+            Evidence::Nitro(document) => self.verify_nitro(document, policy, at),
+            Evidence::TpmTdx(composite) => self.verify_tpm_tdx(composite, policy, at),
+        };
+        observe_with(
+            Operation::Appraisal,
+            evidence.tee().as_str(),
+            verify,
+            |result| match result {
+                Ok(result) if result.is_accepted() => "accepted",
+                Ok(_) => "rejected",
+                Err(_) => tee_observe::ERROR,
+            },
+        )
+    }
+
+    fn verify_tpm(
+        &self,
+        evidence: &TpmEvidence,
+        policy: &Policy,
+    ) -> eyre::Result<AttestationResult> {
+        let attest = self.verified_tpm_quote(evidence)?;
+        Ok(AttestationResult {
+            tee: Tee::Tpm,
+            report_data: attest.extra_data.clone(),
+            measurements: pcr_measurements(&evidence.pcrs),
+            tcb_status: None,
+            advisory_ids: Vec::new(),
+            violations: policy.tpm.evaluate(&evidence.pcrs),
+            details: Details::Tpm(attest),
+        })
+    }
+
+    /// The quote of `evidence`, signed by an enrolled AK and covering its
+    /// PCR values.
+    fn verified_tpm_quote(&self, evidence: &TpmEvidence) -> eyre::Result<TpmAttest> {
+        let ak_cert = Certificate::from_der(&evidence.ak_cert)?;
+        if !self.tpm_aks.contains(&ak_cert) {
+            eyre::bail!("TPM attestation key is not enrolled");
+        }
+        let attest = verify_tpm_quote(&evidence.attest, &evidence.signature, &ak_cert)?;
+        attest.verify_pcrs(&evidence.pcrs)?;
+        Ok(attest)
+    }
+
+    fn verify_dcap(
+        &self,
+        tee: Tee,
+        quote: &[u8],
+        policy: &Policy,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<AttestationResult> {
+        let parsed = Quote::parse(quote)?;
+        let expected = match tee {
+            Tee::Sgx => tee_type::SGX,
+            _ => tee_type::TDX,
+        };
+        if parsed.header.tee_type != expected {
+            eyre::bail!("quote is not a {} quote", tee);
+        }
+        let Some(fetcher) = &self.collateral else {
+            eyre::bail!("no collateral source to verify {} quotes", tee);
+        };
+        let collateral = fetcher.fetch_collateral(&CollateralKey::from_quote(&parsed)?)?;
+        let result = verify_quote_with_anchors(
+            quote,
+            &collateral,
+            &self.anchors,
+            &VerifyOptions::default(),
+            at,
+        )?;
+
+        let mut measurements = BTreeMap::new();
+        match &result.quote.body {
+            QuoteBody::SgxEnclave(report) => {
+                measurements.insert("mr_enclave".to_string(), hex::encode(report.mr_enclave));
+                measurements.insert("mr_signer".to_string(), hex::encode(report.mr_signer));
+            }
+            body => {
+                if let Some(report) = body.as_td_report() {
+                    measurements.insert("mr_td".to_string(), hex::encode(report.mr_td));
+                    for (i, rtmr) in report.rtmrs.iter().enumerate() {
+                        measurements.insert(format!("rtmr{}", i), hex::encode(rtmr));
+                    }
+                }
+            }
+        }
+        let violations = policy
+            .dcap
+            .evaluate_at(&result, at)
+            .violations
+            .into_iter()
+            .map(Violation::Dcap)
+            .collect();
+        Ok(AttestationResult {
+            tee,
+            report_data: result.quote.body.report_data().to_vec(),
+            measurements,
+            tcb_status: Some(result.status),
+            advisory_ids: result.advisory_ids.clone(),
+            violations,
+            details: Details::Dcap(Box::new(result)),
+        })
+    }
+
+    /// Verify both quotes, then that the TD quote commits to the TPM quote.
+    /// The result has the measurements and violations of both.
+    fn verify_tpm_tdx(
+        &self,
+        evidence: &TpmTdxEvidence,
+        policy: &Policy,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<AttestationResult> {
+        let attest = self.verified_tpm_quote(&evidence.tpm)?;
+        let td = self.verify_dcap(Tee::Tdx, &evidence.td_quote, policy, at)?;
+        let Details::Dcap(result) = td.details else {
+            unreachable!("DCAP quotes have DCAP details");
+        };
+        evidence.binding.verify(
+            &ReportData(*result.quote.body.report_data()),
+            &evidence.tpm.attest,
+        )?;
+
+        let mut measurements = td.measurements;
+        measurements.extend(pcr_measurements(&evidence.tpm.pcrs));
+        let mut violations = policy.tpm.evaluate(&evidence.tpm.pcrs);
+        violations.extend(td.violations);
+        Ok(AttestationResult {
+            tee: Tee::TpmTdx,
+            report_data: attest.extra_data.clone(),
+            measurements,
+            tcb_status: td.tcb_status,
+            advisory_ids: td.advisory_ids,
+            violations,
+            details: Details::TpmTdx {
+                tpm: attest,
+                dcap: result,
+            },
+        })
+    }
+
+    fn verify_snp(
+        &self,
+        evidence: &SnpEvidence,
+        policy: &Policy,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<AttestationResult> {
+        let vcek = Certificate::from_der(&evidence.vcek)?;
+        let ask = Certificate::from_der(&evidence.ask)?;
+        let Some(ark) = self
+            .amd_arks
+            .iter()
+            .find(|ark| ark.tbs_certificate.subject == ask.tbs_certificate.issuer)
+        else {
+            eyre::bail!(
+                "no trusted AMD root key for ASK {}",
+                ask.tbs_certificate.subject
+            );
+        };
+        let chain = SnpCertChain {
+            vcek,
+            ask,
+            ark: ark.clone(),
+        };
+        let report = verify_snp_report(&evidence.report, &chain, ark, at)?;
+        let measurements = BTreeMap::from([
+            ("measurement".to_string(), hex::encode(report.measurement)),
+            ("host_data".to_string(), hex::encode(report.host_data)),
+        ]);
+        Ok(AttestationResult {
+            tee: Tee::SevSnp,
+            report_data: report.report_data.to_vec(),
+            measurements,
+            tcb_status: None,
+            advisory_ids: Vec::new(),
+            violations: policy.evaluate_snp(&report),
+            details: Details::SevSnp(Box::new(report)),
+        })
+    }
+
+    fn verify_nitro(
+        &self,
+        document: &[u8],
+        policy: &Policy,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<AttestationResult> {
+        let Some(root) = &self.nitro_root else {
+            eyre::bail!("no trusted AWS Nitro root");
+        };
+        let parsed = verify_nitro_document(document, root, at)?;
+        Ok(AttestationResult {
+            tee: Tee::Nitro,
+            report_data: parsed.nonce.clone().unwrap_or_default(),
+            measurements: pcr_measurements(&parsed.pcrs),
+            tcb_status: None,
+            advisory_ids: Vec::new(),
+            violations: policy.nitro.evaluate(&parsed.pcrs),
+            details: Details::Nitro(Box::new(parsed)),
+        })
+    }
+}
+
+fn pcr_measurements<V: AsRef<[u8]>>(pcrs: &BTreeMap<u32, V>) -> BTreeMap<String, String> {
+    pcrs.iter()
+        .map(|(index, value)| (format!("pcr{}", index), hex::encode(value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use der::asn1::{GeneralizedTime, UtcTime};
+    use der::Encode;
+    use p384::ecdsa::signature::Signer;
+    use p384::ecdsa::{DerSignature, Signature, SigningKey};
+    use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+    use x509_cert::name::Name;
+    use x509_cert::serial_number::SerialNumber;
+    use x509_cert::spki::SubjectPublicKeyInfoOwned;
+    use x509_cert::time::{Time, Validity};
+
+    use super::*;
+    use crate::cbor::Value;
+    use crate::PcrPolicy;
+
+    const ROOT_SUBJECT: &str = "CN=aws.nitro-enclaves,OU=AWS,O=Amazon,C=US";
+
+    fn at() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_740_787_200, 0).unwrap()
+    }
+
+    fn certificate(
+        profile: Profile,
+        serial: u32,
+        subject: &str,
+        key: &SigningKey,
+        issuer: &SigningKey,
+    ) -> Certificate {
+        let validity = Validity {
+            not_before: Time::UtcTime(
+                UtcTime::from_unix_duration(Duration::from_secs(1_577_836_800)).unwrap(),
+            ),
+            not_after: Time::GeneralTime(
+                GeneralizedTime::from_unix_duration(Duration::from_secs(2_524_607_999)).unwrap(),
+            ),
+        };
+        CertificateBuilder::new(
+            profile,
+            SerialNumber::from(serial),
+            validity,
+            Name::from_str(subject).unwrap(),
+            SubjectPublicKeyInfoOwned::from_key(*key.verifying_key()).unwrap(),
+            issuer,
+        )
+        .unwrap()
+        .build::<DerSignature>()
+        .unwrap()
+    }
+
+    /// A Nitro root, and a document signed under it with PCR0 `[0x10; 48]`.
+    fn nitro_document(nonce: &[u8]) -> (Certificate, Vec<u8>) {
+        let root_key = SigningKey::from_bytes(&[1; 48].into()).unwrap();
+        let signing_key = SigningKey::from_bytes(&[2; 48].into()).unwrap();
+        let root = certificate(Profile::Root, 1, ROOT_SUBJECT, &root_key, &root_key);
+        let leaf = certificate(
+            Profile::Leaf {
+                issuer: Name::from_str(ROOT_SUBJECT).unwrap(),
+                enable_key_agreement: false,
+                enable_key_encipherment: false,
+            },
+            2,
+            "CN=i-0123456789abcdef0-enc0123456789abcdef,OU=AWS,O=Amazon,C=US",
+            &signing_key,
+            &root_key,
+        );
+        let payload = Value::Map(vec![
+            (
+                Value::text("module_id"),
+                Value::text("i-0123456789abcdef0-enc0123456789abcdef"),
+            ),
+            (Value::text("digest"), Value::text("SHA384")),
+            (Value::text("timestamp"), Value::Unsigned(1_740_787_200_000)),
+            (
+                Value::text("pcrs"),
+                Value::Map(vec![(Value::Unsigned(0), Value::Bytes(vec![0x10; 48]))]),
+            ),
+            (
+                Value::text("certificate"),
+                Value::Bytes(leaf.to_der().unwrap()),
+            ),
+            (
+                Value::text("cabundle"),
+                Value::Array(vec![Value::Bytes(root.to_der().unwrap())]),
+            ),
+            (Value::text("nonce"), Value::Bytes(nonce.to_vec())),
+        ])
+        .encode();
+        let protected = Value::Map(vec![(Value::Unsigned(1), Value::integer(-35))]).encode();
+        let signed_data = Value::Array(vec![
+            Value::text("Signature1"),
+            Value::Bytes(protected.clone()),
+            Value::Bytes(Vec::new()),
+            Value::Bytes(payload.clone()),
+        ])
+        .encode();
+        let signature: Signature = signing_key.sign(&signed_data);
+        let document = Value::Array(vec![
+            Value::Bytes(protected),
+            Value::Map(Vec::new()),
+            Value::Bytes(payload),
+            Value::Bytes(signature.to_bytes().to_vec()),
+        ])
+        .encode();
+        (root, document)
+    }
+
+    #[test]
+    fn test_verify_nitro() -> eyre::Result<()> {
+        let (root, document) = nitro_document(b"nonce");
+        let evidence = Evidence::Nitro(document);
+        let verifier = Verifier::new().with_nitro_root(root);
+
+        let result = verifier.verify_at(&evidence, &Policy::default(), at())?;
+        assert_eq!(result.tee, Tee::Nitro);
+        assert_eq!(result.report_data, b"nonce");
+        assert_eq!(result.measurements["pcr0"], "10".repeat(48));
+        assert!(result.is_accepted());
+
+        let policy = Policy {
+            nitro: PcrPolicy {
+                pcrs: BTreeMap::from([(0, vec!["11".repeat(48)]), (8, vec!["00".repeat(48)])]),
+            },
+            ..Policy::default()
+        };
+        let result = verifier.verify_at(&evidence, &policy, at())?;
+        assert_eq!(result.violations.len(), 2);
+
+        let err = Verifier::new()
+            .verify_at(&evidence, &Policy::default(), at())
+            .unwrap_err();
+        assert!(err.to_string().contains("no trusted AWS Nitro root"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_untrusted_evidence() {
+        let verifier = Verifier::new();
+        let policy = Policy::default();
+
+        let (root, _) = nitro_document(&[]);
+        let tpm = Evidence::TpmQuote(TpmEvidence {
+            attest: Vec::new(),
+            signature: [0; 64],
+            ak_cert: root.to_der().unwrap(),
+            pcrs: BTreeMap::new(),
+        });
+        let err = verifier.verify_at(&tpm, &policy, at()).unwrap_err();
+        assert!(err.to_string().contains("not enrolled"));
+        let Evidence::TpmQuote(tpm) = tpm else {
+            unreachable!()
+        };
+        let composite = Evidence::TpmTdx(TpmTdxEvidence {
+            tpm,
+            td_quote: Vec::new(),
+            binding: crate::TpmBinding::AkName,
+        });
+        let err = verifier.verify_at(&composite, &policy, at()).unwrap_err();
+        assert!(err.to_string().contains("not enrolled"));
+
+        let snp = Evidence::SevSnp(SnpEvidence {
+            report: Vec::new(),
+            vcek: root.to_der().unwrap(),
+            ask: root.to_der().unwrap(),
+        });
+        let err = verifier.verify_at(&snp, &policy, at()).unwrap_err();
+        assert!(err.to_string().contains("no trusted AMD root key"));
+
+        assert!(verifier
+            .verify_at(&Evidence::SgxQuote(vec![0; 16]), &policy, at())
+            .is_err());
+    }
+}
@@ -0,0 +1,346 @@
+//! The configuration file shared by the services and command line tools, so
+//! a deployment describes its TPM, TD quote source, collateral source, roots
+//! of trust, caches and appraisal policy in one TOML file instead of flags
+//! and environment variables:
+//!
+//! ```toml
+//! [tpm]
+//! transport = "device"
+//! path = "/dev/tpmrm0"
+//!
+//! [tdx]
+//! transport = "vsock"
+//! port = 4050
+//!
+//! [pcs]
+//! pccs_url = "https://localhost:8081"
+//!
+//! [trust]
+//! roots = "roots.pem"
+//! tpm_aks = ["aks/worker-1.pem"]
+//!
+//! [cache]
+//! dir = "/var/cache/tee-ware"
+//!
+//! [policy.dcap]
+//! tcb_statuses = ["UpToDate", "SWHardeningNeeded"]
+//! ```
+//!
+//! Every section is optional. Relative paths are relative to the directory
+//! of the file. Flags given to a binary take precedence over the file.
+
+use std::path::{Path, PathBuf};
+
+use dcap::{CollateralCache, CollateralFetcher, TrustAnchors};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tss_client::{DeviceTransport, TcpTransport, Transport, TPM_RESOURCE_MANAGER_PATH};
+use x509_cert::der::DecodePem;
+use x509_cert::Certificate;
+
+/// Environment variable the binaries read the path of the file from.
+pub const CONFIG_ENV: &str = "TEE_WARE_CONFIG";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub tpm: TpmTransport,
+    pub tdx: TdxTransport,
+    pub pcs: PcsSection,
+    pub trust: TrustSection,
+    pub cache: CacheSection,
+    /// The appraisal policy, as the path of a JSON or TOML file or inline.
+    pub policy: Option<PolicySource>,
+}
+
+/// How to reach the TPM.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "transport", rename_all = "lowercase", deny_unknown_fields)]
+pub enum TpmTransport {
+    /// A character device, the kernel resource manager by default.
+    Device {
+        #[serde(default = "default_tpm_device")]
+        path: PathBuf,
+    },
+    /// A TPM simulator, e.g. `localhost:2321`.
+    Tcp { address: String },
+    /// The machine has no TPM to use.
+    None,
+}
+
+impl Default for TpmTransport {
+    fn default() -> Self {
+        TpmTransport::Device {
+            path: default_tpm_device(),
+        }
+    }
+}
+
+fn default_tpm_device() -> PathBuf {
+    PathBuf::from(TPM_RESOURCE_MANAGER_PATH)
+}
+
+impl TpmTransport {
+    /// Open the transport, `None` for [`TpmTransport::None`].
+    pub fn open(&self) -> eyre::Result<Option<Box<dyn Transport + Send>>> {
+        Ok(match self {
+            TpmTransport::Device { path } => Some(Box::new(DeviceTransport::open(path)?)),
+            TpmTransport::Tcp { address } => Some(Box::new(TcpTransport::connect(address)?)),
+            TpmTransport::None => None,
+        })
+    }
+}
+
+/// Where TD quotes come from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "transport", rename_all = "lowercase", deny_unknown_fields)]
+pub enum TdxTransport {
+    /// configfs-tsm.
+    #[default]
+    Tsm,
+    /// The QGS of the host over vsock.
+    Vsock {
+        #[serde(default = "default_qgs_cid")]
+        cid: u32,
+        #[serde(default = "default_qgs_port")]
+        port: u32,
+    },
+}
+
+// VMADDR_CID_HOST and the port the QGS listens on by default.
+fn default_qgs_cid() -> u32 {
+    2
+}
+
+fn default_qgs_port() -> u32 {
+    4050
+}
+
+/// Where collateral is fetched from, the Intel PCS by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PcsSection {
+    /// A PCCS to fetch from instead of the Intel PCS.
+    pub pccs_url: Option<String>,
+    pub api_key: Option<String>,
+    pub api_key_header: Option<String>,
+}
+
+#[cfg(feature = "pcs")]
+impl PcsSection {
+    pub fn config(&self) -> dcap::pcs::PcsConfig {
+        let mut config = match &self.pccs_url {
+            Some(url) => dcap::pcs::PcsConfig::pccs(url),
+            None => dcap::pcs::PcsConfig::default(),
+        };
+        if let Some(api_key) = &self.api_key {
+            config = config.with_api_key(api_key);
+        }
+        if let Some(header) = &self.api_key_header {
+            config = config.with_api_key_header(header);
+        }
+        config
+    }
+}
+
+/// The roots of trust, as paths of PEM files.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrustSection {
+    /// Bundle of the trusted roots of SGX and TDX quotes, the Intel SGX Root
+    /// CA if omitted.
+    pub roots: Option<PathBuf>,
+    pub amd_arks: Vec<PathBuf>,
+    pub nitro_root: Option<PathBuf>,
+    /// Enrolled TPM attestation keys.
+    pub tpm_aks: Vec<PathBuf>,
+}
+
+impl TrustSection {
+    pub fn anchors(&self) -> eyre::Result<TrustAnchors> {
+        match &self.roots {
+            Some(path) => TrustAnchors::from_pem(&read(path)?),
+            None => Ok(TrustAnchors::default()),
+        }
+    }
+}
+
+/// Where collateral is kept between restarts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheSection {
+    pub dir: Option<PathBuf>,
+    /// How long before its `nextUpdate` collateral is fetched again.
+    pub refresh_margin_secs: Option<u64>,
+}
+
+impl CacheSection {
+    pub fn apply<F: CollateralFetcher>(&self, mut cache: CollateralCache<F>) -> CollateralCache<F> {
+        if let Some(dir) = &self.dir {
+            cache = cache.with_store(dir);
+        }
+        if let Some(secs) = self.refresh_margin_secs {
+            cache = cache.with_refresh_margin(chrono::Duration::seconds(secs as i64));
+        }
+        cache
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PolicySource {
+    Path(PathBuf),
+    Inline(toml::Table),
+}
+
+impl PolicySource {
+    /// Read the policy as `P`, a file as TOML if its name says so and JSON
+    /// otherwise. Validating it is up to `P`.
+    pub fn load<P: DeserializeOwned>(&self) -> eyre::Result<P> {
+        match self {
+            PolicySource::Path(path) => {
+                let text = String::from_utf8(read(path)?)?;
+                match path.extension().and_then(|extension| extension.to_str()) {
+                    Some("toml") => Ok(toml::from_str(&text)?),
+                    _ => Ok(serde_json::from_str(&text)?),
+                }
+            }
+            PolicySource::Inline(table) => Ok(toml::Value::Table(table.clone()).try_into()?),
+        }
+    }
+}
+
+impl Config {
+    pub fn from_toml(toml: &str) -> eyre::Result<Self> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Read the file at `path`, resolving relative paths against its
+    /// directory.
+    pub fn load(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| eyre::eyre!("cannot read {}: {}", path.display(), err))?;
+        let config = Self::from_toml(&text)
+            .map_err(|err| eyre::eyre!("invalid config {}: {}", path.display(), err))?;
+        Ok(config.relative_to(path.parent().unwrap_or(Path::new(""))))
+    }
+
+    /// Read the file at `path` if given, the defaults otherwise.
+    pub fn load_or_default(path: Option<&Path>) -> eyre::Result<Self> {
+        path.map_or_else(|| Ok(Self::default()), Self::load)
+    }
+
+    fn relative_to(mut self, base: &Path) -> Self {
+        let resolve = |path: &mut PathBuf| *path = base.join(&*path);
+        if let TpmTransport::Device { path } = &mut self.tpm {
+            resolve(path);
+        }
+        self.trust.roots.iter_mut().for_each(resolve);
+        self.trust.amd_arks.iter_mut().for_each(resolve);
+        self.trust.nitro_root.iter_mut().for_each(resolve);
+        self.trust.tpm_aks.iter_mut().for_each(resolve);
+        self.cache.dir.iter_mut().for_each(resolve);
+        if let Some(PolicySource::Path(path)) = &mut self.policy {
+            resolve(path);
+        }
+        self
+    }
+
+    /// The appraisal policy as `P`, if any.
+    pub fn policy<P: DeserializeOwned>(&self) -> eyre::Result<Option<P>> {
+        self.policy.as_ref().map(PolicySource::load).transpose()
+    }
+}
+
+/// Read a PEM certificate.
+pub fn read_certificate(path: &Path) -> eyre::Result<Certificate> {
+    Certificate::from_pem(read(path)?)
+        .map_err(|err| eyre::eyre!("invalid certificate {}: {}", path.display(), err))
+}
+
+fn read(path: &Path) -> eyre::Result<Vec<u8>> {
+    std::fs::read(path).map_err(|err| eyre::eyre!("cannot read {}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config() -> eyre::Result<()> {
+        let config = Config::from_toml("")?;
+        assert_eq!(config, Config::default());
+        assert_eq!(
+            config.tpm,
+            TpmTransport::Device {
+                path: PathBuf::from("/dev/tpmrm0")
+            }
+        );
+        assert_eq!(config.tdx, TdxTransport::Tsm);
+        assert_eq!(config.policy::<toml::Table>()?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("tee-ware.toml"),
+            r#"
+            [tpm]
+            transport = "tcp"
+            address = "localhost:2321"
+
+            [tdx]
+            transport = "vsock"
+            port = 4051
+
+            [pcs]
+            pccs_url = "https://localhost:8081"
+
+            [trust]
+            roots = "roots.pem"
+            tpm_aks = ["/etc/aks/ak.pem"]
+
+            [cache]
+            dir = "cache"
+
+            [policy.dcap]
+            tcb_statuses = ["UpToDate"]
+            "#,
+        )?;
+        let config = Config::load(dir.path().join("tee-ware.toml"))?;
+
+        assert_eq!(
+            config.tpm,
+            TpmTransport::Tcp {
+                address: "localhost:2321".into()
+            }
+        );
+        assert_eq!(config.tdx, TdxTransport::Vsock { cid: 2, port: 4051 });
+        assert_eq!(config.trust.roots, Some(dir.path().join("roots.pem")));
+        assert_eq!(config.trust.tpm_aks, vec![PathBuf::from("/etc/aks/ak.pem")]);
+        assert_eq!(config.cache.dir, Some(dir.path().join("cache")));
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Policy {
+            dcap: toml::Table,
+        }
+        let policy: Policy = config.policy()?.unwrap();
+        assert_eq!(policy.dcap["tcb_statuses"][0].as_str(), Some("UpToDate"));
+
+        // A policy file is read as TOML or JSON by its name.
+        std::fs::write(dir.path().join("policy.json"), r#"{"dcap": {}}"#)?;
+        let config = Config::from_toml(r#"policy = "policy.json""#)?.relative_to(dir.path());
+        assert!(config.policy::<Policy>()?.unwrap().dcap.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_unknown_settings() {
+        assert!(Config::from_toml("[pcs]\nurl = \"https://pcs\"").is_err());
+        assert!(Config::from_toml("[tpm]\ntransport = \"usb\"").is_err());
+        assert!(Config::from_toml("[tpm]\ntransport = \"tcp\"").is_err());
+    }
+}
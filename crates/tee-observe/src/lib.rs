@@ -0,0 +1,299 @@
+//! Tracing spans and metrics shared by the crates of the stack, so one
+//! subscriber and one recorder cover TPM commands, collateral fetches and
+//! verification alike.
+//!
+//! Every [`Operation`] is a span named after it, e.g. `tpm.command`, with
+//! one label and an `outcome` field. With the `metrics` feature each also
+//! counts in `tee_ware_operations_total` and times in
+//! `tee_ware_operation_duration_seconds`, labeled by `operation`, its label
+//! and `outcome`. Labels and outcomes are `&'static str`, so they come from
+//! fixed sets of names and never from inputs.
+//!
+//! Without the `tracing` and `metrics` features operations are plain calls.
+//! Crates forward both features here, so enabling them on any crate of the
+//! stack lights up all of them.
+
+use std::fmt::Display;
+use std::future::Future;
+
+/// Counter of operations, by `operation`, label and `outcome`.
+pub const OPERATIONS_TOTAL: &str = "tee_ware_operations_total";
+/// Histogram of operation latencies, by `operation`, label and `outcome`.
+pub const OPERATION_DURATION_SECONDS: &str = "tee_ware_operation_duration_seconds";
+
+/// The outcome of a successful operation.
+pub const OK: &str = "ok";
+/// The outcome of a failed operation that has no finer classification.
+pub const ERROR: &str = "error";
+
+/// An observed operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// A TPM command, labeled by its `command` name.
+    TpmCommand,
+    /// A round trip through a TPM `transport`.
+    TpmTransport,
+    /// Fetching the collateral of an SGX or TDX platform, by `tee`.
+    CollateralFetch,
+    /// Verifying an SGX or TDX quote, by `tee`.
+    QuoteVerify,
+    /// Verifying and appraising evidence of any TEE, by `tee`.
+    Appraisal,
+    /// Reloading the configuration of a running service, by `source`.
+    Reload,
+}
+
+impl Operation {
+    /// The span name, and the `operation` label of the metrics.
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::TpmCommand => "tpm.command",
+            Operation::TpmTransport => "tpm.transport",
+            Operation::CollateralFetch => "dcap.collateral_fetch",
+            Operation::QuoteVerify => "dcap.verify_quote",
+            Operation::Appraisal => "attest.appraise",
+            Operation::Reload => "service.reload",
+        }
+    }
+
+    /// The name of the label of the operation.
+    pub fn label(self) -> &'static str {
+        match self {
+            Operation::TpmCommand => "command",
+            Operation::TpmTransport => "transport",
+            Operation::CollateralFetch | Operation::QuoteVerify | Operation::Appraisal => "tee",
+            Operation::Reload => "source",
+        }
+    }
+}
+
+/// Run `f` as `operation`, with the outcome [`OK`] or [`ERROR`].
+pub fn observe<T, E: Display>(
+    operation: Operation,
+    label: &'static str,
+    f: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    observe_with(operation, label, f, default_outcome)
+}
+
+/// Like [`observe`], with the outcome classified by `outcome`, e.g. the
+/// kind of a verification error.
+pub fn observe_with<T, E: Display>(
+    operation: Operation,
+    label: &'static str,
+    f: impl FnOnce() -> Result<T, E>,
+    outcome: impl FnOnce(&Result<T, E>) -> &'static str,
+) -> Result<T, E> {
+    let observation = Observation::start(operation, label);
+    let result = observation.in_scope(f);
+    observation.finish(outcome(&result), result.as_ref().err());
+    result
+}
+
+/// Like [`observe`], for a future.
+pub async fn observe_async<T, E: Display>(
+    operation: Operation,
+    label: &'static str,
+    future: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let observation = Observation::start(operation, label);
+    let result = observation.instrument(future).await;
+    observation.finish(default_outcome(&result), result.as_ref().err());
+    result
+}
+
+fn default_outcome<T, E>(result: &Result<T, E>) -> &'static str {
+    match result {
+        Ok(_) => OK,
+        Err(_) => ERROR,
+    }
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+struct Observation {
+    operation: Operation,
+    label: &'static str,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(feature = "metrics")]
+    start: std::time::Instant,
+}
+
+impl Observation {
+    fn start(operation: Operation, label: &'static str) -> Self {
+        Self {
+            operation,
+            label,
+            #[cfg(feature = "tracing")]
+            span: span(operation, label),
+            #[cfg(feature = "metrics")]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    fn in_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        #[cfg(feature = "tracing")]
+        return self.span.in_scope(f);
+        #[cfg(not(feature = "tracing"))]
+        f()
+    }
+
+    async fn instrument<R>(&self, future: impl Future<Output = R>) -> R {
+        #[cfg(feature = "tracing")]
+        return tracing::Instrument::instrument(future, self.span.clone()).await;
+        #[cfg(not(feature = "tracing"))]
+        future.await
+    }
+
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn finish(self, outcome: &'static str, error: Option<&impl Display>) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("outcome", outcome);
+            if let Some(error) = error {
+                self.span.in_scope(|| {
+                    tracing::debug!(error = %error, "{} failed", self.operation.name());
+                });
+            }
+        }
+        #[cfg(feature = "metrics")]
+        {
+            let labels = [
+                ("operation", self.operation.name()),
+                (self.operation.label(), self.label),
+                ("outcome", outcome),
+            ];
+            metrics::counter!(OPERATIONS_TOTAL, &labels).increment(1);
+            metrics::histogram!(OPERATION_DURATION_SECONDS, &labels)
+                .record(self.start.elapsed().as_secs_f64());
+        }
+    }
+}
+
+/// The span of `operation`. Span names must be literals, hence one arm per
+/// operation.
+#[cfg(feature = "tracing")]
+fn span(operation: Operation, label: &'static str) -> tracing::Span {
+    use tracing::field::Empty;
+    match operation {
+        Operation::TpmCommand => {
+            tracing::debug_span!("tpm.command", command = label, outcome = Empty)
+        }
+        Operation::TpmTransport => {
+            tracing::debug_span!("tpm.transport", transport = label, outcome = Empty)
+        }
+        Operation::CollateralFetch => {
+            tracing::info_span!("dcap.collateral_fetch", tee = label, outcome = Empty)
+        }
+        Operation::QuoteVerify => {
+            tracing::info_span!("dcap.verify_quote", tee = label, outcome = Empty)
+        }
+        Operation::Appraisal => {
+            tracing::info_span!("attest.appraise", tee = label, outcome = Empty)
+        }
+        Operation::Reload => {
+            tracing::info_span!("service.reload", source = label, outcome = Empty)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tracing", feature = "metrics"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+    use tracing_subscriber::Layer;
+
+    use super::*;
+
+    /// Records the fields of every span as `name field=value`.
+    #[derive(Clone, Default)]
+    struct Spans(Arc<Mutex<Vec<String>>>);
+
+    struct Fields<'a>(&'a mut String);
+
+    impl tracing::field::Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0.push_str(&format!(" {}={}", field.name(), value));
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Spans {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            let mut span = attrs.metadata().name().to_string();
+            attrs.record(&mut Fields(&mut span));
+            self.0.lock().unwrap().push(span);
+        }
+
+        fn on_record(&self, _id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            let mut spans = self.0.lock().unwrap();
+            let span = spans.last_mut().unwrap();
+            values.record(&mut Fields(span));
+        }
+    }
+
+    #[test]
+    fn test_observe() {
+        let spans = Spans::default();
+        let subscriber = tracing_subscriber::registry().with(spans.clone());
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        tracing::subscriber::with_default(subscriber, || {
+            metrics::with_local_recorder(&recorder, || {
+                let result: Result<u32, String> = observe(Operation::TpmCommand, "Quote", || Ok(1));
+                assert_eq!(result, Ok(1));
+                let result: Result<(), String> = observe_with(
+                    Operation::QuoteVerify,
+                    "tdx",
+                    || Err("bad signature".to_string()),
+                    |_| "signature",
+                );
+                assert!(result.is_err());
+            });
+        });
+
+        assert_eq!(
+            *spans.0.lock().unwrap(),
+            [
+                "tpm.command command=Quote outcome=ok",
+                "dcap.verify_quote tee=tdx outcome=signature",
+            ]
+        );
+
+        let mut counters = Vec::new();
+        for (key, _, _, value) in snapshotter.snapshot().into_vec() {
+            let key = key.key();
+            if key.name() != OPERATIONS_TOTAL {
+                continue;
+            }
+            let labels: Vec<_> = key
+                .labels()
+                .map(|label| format!("{}={}", label.key(), label.value()))
+                .collect();
+            counters.push((labels.join(","), value));
+        }
+        counters.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            counters,
+            [
+                (
+                    "operation=dcap.verify_quote,tee=tdx,outcome=signature".to_string(),
+                    DebugValue::Counter(1)
+                ),
+                (
+                    "operation=tpm.command,command=Quote,outcome=ok".to_string(),
+                    DebugValue::Counter(1)
+                ),
+            ]
+        );
+    }
+}
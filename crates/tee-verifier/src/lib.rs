@@ -0,0 +1,28 @@
+//! A verifier service: it accepts evidence of any TEE supported by
+//! `tee-attest`, appraises it with a policy and returns the outcome as a
+//! signed attestation result token, so relying parties only need the
+//! verifier's public key to act on it.
+//!
+//! Tokens are ES256 JWTs signed by a pluggable [`ResultSigner`], with the
+//! key in a file, a TPM or a cloud KMS.
+//!
+//! The verifier and policy of a running service can be swapped atomically
+//! with [`VerifierService::reload`], e.g. when a [`FileWatcher`] notices
+//! new golden measurements or roots of trust.
+
+mod signer;
+pub use signer::*;
+
+mod token;
+pub use token::*;
+
+mod service;
+pub use service::*;
+
+mod reload;
+pub use reload::*;
+
+pub mod routes;
+
+#[cfg(test)]
+mod test_utils;
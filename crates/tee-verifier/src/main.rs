@@ -0,0 +1,371 @@
+//! Verifier service appraising TEE evidence over HTTP and returning signed
+//! attestation result tokens.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use clap::Parser;
+use dcap::pcs::blocking::PcsClient;
+use dcap::{CollateralCache, CollateralFetcher, CollateralKey};
+use p256::ecdsa::VerifyingKey;
+use p256::pkcs8::DecodePublicKey;
+use tee_attest::{Policy, Verifier};
+use tee_config::{read_certificate, Config, PolicySource, CONFIG_ENV};
+use tee_observe::{observe, Operation};
+use tee_verifier::{
+    routes, FileWatcher, KeySigner, ResultSigner, TokenIssuer, TpmSigner, VerifierService,
+};
+use tss_client::{DeviceTransport, TssClient};
+
+type Cache = CollateralCache<PcsClient>;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Configuration file, for the collateral source and cache, the roots
+    /// of trust and the policy. Flags take precedence over it.
+    #[arg(long, env = CONFIG_ENV)]
+    config: Option<PathBuf>,
+    #[arg(long, env = "TEE_VERIFIER_LISTEN", default_value = "127.0.0.1:8391")]
+    listen: SocketAddr,
+    /// The `iss` claim of the tokens.
+    #[arg(long)]
+    issuer: String,
+    /// The `aud` claim of the tokens.
+    #[arg(long)]
+    audience: Option<String>,
+    /// Appraisal policy as JSON; only accepts up to date SGX and TDX
+    /// platforms if omitted.
+    #[arg(long)]
+    policy: Option<PathBuf>,
+    #[command(flatten)]
+    key: KeyArgs,
+    /// The `kid` of the tokens.
+    #[arg(long)]
+    key_id: Option<String>,
+    /// PEM bundle of the trusted roots of SGX and TDX quotes, the Intel SGX
+    /// Root CA by default.
+    #[arg(long)]
+    roots: Option<PathBuf>,
+    /// Fetch collateral from the PCCS at this URL instead of the Intel PCS.
+    #[arg(long)]
+    pccs: Option<String>,
+    #[arg(long, env = "PCS_API_KEY")]
+    api_key: Option<String>,
+    /// PEM certificate of a trusted AMD root key, in addition to those of
+    /// the configuration file.
+    #[arg(long)]
+    amd_ark: Vec<PathBuf>,
+    /// PEM certificate of the AWS Nitro root.
+    #[arg(long)]
+    nitro_root: Option<PathBuf>,
+    /// PEM certificate of an enrolled TPM attestation key, in addition to
+    /// those of the configuration file.
+    #[arg(long)]
+    tpm_ak: Vec<PathBuf>,
+    /// Seconds between checks of the configuration, policy and trust files
+    /// for changes, and refreshes of expiring collateral; 0 disables them.
+    /// SIGHUP reloads the files and refreshes all collateral at once.
+    #[arg(long, default_value_t = 60)]
+    reload_interval: u64,
+}
+
+#[derive(Debug, clap::Args)]
+struct KeyArgs {
+    /// PEM private key signing the tokens.
+    #[arg(long, conflicts_with = "tpm_key", required_unless_present = "tpm_key")]
+    key: Option<PathBuf>,
+    /// Hex handle of a TPM-resident key signing the tokens.
+    #[arg(long, requires = "tpm_key_public")]
+    tpm_key: Option<String>,
+    /// PEM public key of the TPM-resident key.
+    #[arg(long)]
+    tpm_key_public: Option<PathBuf>,
+}
+
+impl KeyArgs {
+    fn signer(&self, key_id: Option<&str>) -> eyre::Result<Box<dyn ResultSigner>> {
+        if let Some(path) = &self.key {
+            let signer = KeySigner::from_pem_file(path)?;
+            return Ok(match key_id {
+                Some(key_id) => Box::new(signer.with_key_id(key_id)),
+                None => Box::new(signer),
+            });
+        }
+        let (Some(handle), Some(public)) = (&self.tpm_key, &self.tpm_key_public) else {
+            eyre::bail!("no signing key");
+        };
+        let handle = u32::from_str_radix(handle.trim_start_matches("0x"), 16)?;
+        let public = VerifyingKey::from_public_key_pem(&std::fs::read_to_string(public)?)
+            .map_err(|_| eyre::eyre!("not a P-256 public key in PEM"))?;
+        let tpm = TssClient::new(DeviceTransport::open_default()?);
+        let signer = TpmSigner::new(tpm, handle, public);
+        Ok(match key_id {
+            Some(key_id) => Box::new(signer.with_key_id(key_id)),
+            None => Box::new(signer),
+        })
+    }
+}
+
+impl Cli {
+    /// The configuration file with the flags applied over it.
+    fn config(&self) -> eyre::Result<Config> {
+        let mut config = Config::load_or_default(self.config.as_deref())?;
+        if let Some(url) = &self.pccs {
+            config.pcs.pccs_url = Some(url.clone());
+        }
+        if let Some(api_key) = &self.api_key {
+            config.pcs.api_key = Some(api_key.clone());
+        }
+        if let Some(path) = &self.roots {
+            config.trust.roots = Some(path.clone());
+        }
+        config.trust.amd_arks.extend(self.amd_ark.iter().cloned());
+        if let Some(path) = &self.nitro_root {
+            config.trust.nitro_root = Some(path.clone());
+        }
+        config.trust.tpm_aks.extend(self.tpm_ak.iter().cloned());
+        Ok(config)
+    }
+
+    /// The collateral cache, which outlives reloads: its source and
+    /// directory are only read at startup.
+    fn cache(&self, config: &Config) -> Arc<Cache> {
+        Arc::new(
+            config
+                .cache
+                .apply(CollateralCache::new(PcsClient::with_config(
+                    config.pcs.config(),
+                ))),
+        )
+    }
+
+    /// The verifier and policy, from the files as they are now.
+    fn appraiser(&self, config: &Config, cache: &Arc<Cache>) -> eyre::Result<(Verifier, Policy)> {
+        let cache = cache.clone();
+        let mut verifier =
+            Verifier::new().with_collateral(move |key: &CollateralKey| cache.fetch_collateral(key));
+        if config.trust.roots.is_some() {
+            verifier = verifier.with_trust_anchors(config.trust.anchors()?);
+        }
+        for path in &config.trust.amd_arks {
+            verifier = verifier.with_amd_ark(read_certificate(path)?);
+        }
+        if let Some(path) = &config.trust.nitro_root {
+            verifier = verifier.with_nitro_root(read_certificate(path)?);
+        }
+        for path in &config.trust.tpm_aks {
+            verifier = verifier.with_tpm_ak(read_certificate(path)?);
+        }
+
+        let policy = match &self.policy {
+            Some(path) => Policy::from_json(&std::fs::read_to_string(path)?)?,
+            None => match config.policy::<Policy>()? {
+                Some(policy) => {
+                    policy.validate()?;
+                    policy
+                }
+                None => Policy::default(),
+            },
+        };
+        Ok((verifier, policy))
+    }
+
+    /// The files a reload reads.
+    fn watched_files(&self, config: &Config) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self.config.iter().chain(&self.policy).cloned().collect();
+        if let Some(PolicySource::Path(path)) = &config.policy {
+            files.push(path.clone());
+        }
+        files.extend(config.trust.roots.iter().cloned());
+        files.extend(config.trust.amd_arks.iter().cloned());
+        files.extend(config.trust.nitro_root.iter().cloned());
+        files.extend(config.trust.tpm_aks.iter().cloned());
+        files
+    }
+
+    fn service(&self) -> eyre::Result<(VerifierService, Arc<Cache>, FileWatcher)> {
+        let config = self.config()?;
+        let cache = self.cache(&config);
+        let watcher = FileWatcher::new(self.watched_files(&config));
+        let (verifier, policy) = self.appraiser(&config, &cache)?;
+
+        let mut issuer = TokenIssuer::new(self.key.signer(self.key_id.as_deref())?, &self.issuer);
+        if let Some(audience) = &self.audience {
+            issuer = issuer.with_audience(audience);
+        }
+        Ok((
+            VerifierService::new(verifier, policy, issuer),
+            cache,
+            watcher,
+        ))
+    }
+
+    /// Reload the files into `service` if they changed or `force`, and
+    /// refresh expiring collateral, or all of it if `force`. Failures keep
+    /// what was loaded before.
+    fn reload(
+        &self,
+        service: &VerifierService,
+        cache: &Arc<Cache>,
+        watcher: &mut FileWatcher,
+        force: bool,
+    ) {
+        if watcher.changed() || force {
+            let reloaded = service.reload_with("config", || {
+                let config = self.config()?;
+                *watcher = FileWatcher::new(self.watched_files(&config));
+                self.appraiser(&config, cache)
+            });
+            if let Err(err) = reloaded {
+                eprintln!("keeping the current configuration: {:#}", err);
+            }
+        }
+
+        let refreshed = observe(Operation::Reload, "collateral", || {
+            let failed = match force {
+                true => cache.refresh_all(),
+                false => cache.refresh_expiring(Utc::now()),
+            };
+            match failed.first() {
+                None => Ok(()),
+                Some((key, err)) => Err(eyre::eyre!(
+                    "{} of collateral failed to refresh, {}: {:#}",
+                    failed.len(),
+                    key,
+                    err
+                )),
+            }
+        });
+        if let Err(err) = refreshed {
+            eprintln!("keeping cached collateral: {:#}", err);
+        }
+    }
+}
+
+/// Reload every `interval`, and at once on SIGHUP.
+async fn reload_loop(
+    cli: Arc<Cli>,
+    service: Arc<VerifierService>,
+    cache: Arc<Cache>,
+    mut watcher: FileWatcher,
+) -> eyre::Result<()> {
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    let mut interval = (cli.reload_interval > 0)
+        .then(|| tokio::time::interval(Duration::from_secs(cli.reload_interval)));
+    loop {
+        let force = tokio::select! {
+            _ = hangup.recv() => true,
+            _ = async { interval.as_mut().unwrap().tick().await }, if interval.is_some() => false,
+        };
+        let (cli, service, cache) = (cli.clone(), service.clone(), cache.clone());
+        // Reloading reads files and fetches collateral, which blocks.
+        watcher = tokio::task::spawn_blocking(move || {
+            cli.reload(&service, &cache, &mut watcher, force);
+            watcher
+        })
+        .await?;
+    }
+}
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let cli = Arc::new(Cli::parse());
+    let (service, cache, watcher) = cli.service()?;
+    let service = Arc::new(service);
+    let reloads = reload_loop(cli.clone(), service.clone(), cache, watcher);
+    tokio::spawn(async move {
+        if let Err(err) = reloads.await {
+            eprintln!("reloading stopped: {:#}", err);
+        }
+    });
+    let listener = tokio::net::TcpListener::bind(cli.listen).await?;
+    println!("listening on {}", listener.local_addr()?);
+    axum::serve(listener, routes::router(service))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_arguments() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert();
+
+        let cli = Cli::parse_from([
+            "tee-verifier",
+            "--issuer",
+            "https://verifier.example",
+            "--key",
+            "key.pem",
+            "--tpm-ak",
+            "ak1.pem",
+            "--tpm-ak",
+            "ak2.pem",
+        ]);
+        assert_eq!(cli.tpm_ak.len(), 2);
+        assert!(Cli::try_parse_from(["tee-verifier", "--issuer", "verifier"]).is_err());
+        assert!(Cli::try_parse_from([
+            "tee-verifier",
+            "--issuer",
+            "verifier",
+            "--tpm-key",
+            "81000001"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_config_flags() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("tee-ware.toml");
+        std::fs::write(
+            &path,
+            "[pcs]\npccs_url = \"https://pccs\"\n[trust]\ntpm_aks = [\"ak1.pem\"]\n",
+        )?;
+
+        let cli = Cli::parse_from([
+            "tee-verifier",
+            "--config",
+            path.to_str().unwrap(),
+            "--issuer",
+            "verifier",
+            "--key",
+            "key.pem",
+            "--tpm-ak",
+            "ak2.pem",
+        ]);
+        let config = cli.config()?;
+        assert_eq!(config.pcs.pccs_url.as_deref(), Some("https://pccs"));
+        assert_eq!(
+            config.trust.tpm_aks,
+            vec![dir.path().join("ak1.pem"), PathBuf::from("ak2.pem")]
+        );
+
+        let cli = Cli::parse_from([
+            "tee-verifier",
+            "--config",
+            path.to_str().unwrap(),
+            "--issuer",
+            "verifier",
+            "--key",
+            "key.pem",
+            "--pccs",
+            "https://other-pccs",
+        ]);
+        assert_eq!(
+            cli.config()?.pcs.pccs_url.as_deref(),
+            Some("https://other-pccs")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Notices changes to a set of files, e.g. the configuration and policy of
+/// a running service, by polling their modification time and size.
+///
+/// Symbolic links are followed, so swapping the target of a link counts as
+/// a change, as when Kubernetes updates a mounted ConfigMap.
+#[derive(Debug, Clone)]
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<(SystemTime, u64)>)>,
+}
+
+impl FileWatcher {
+    /// Watch `paths` from their current state.
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let state = state(&path);
+                (path, state)
+            })
+            .collect();
+        Self { files }
+    }
+
+    /// Whether any file was modified, created or removed since the watcher
+    /// was created or last called.
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        for (path, last) in &mut self.files {
+            let current = state(path);
+            if current != *last {
+                *last = current;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn state(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_watcher() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let policy = dir.path().join("policy.json");
+        let roots = dir.path().join("roots.pem");
+        std::fs::write(&policy, "{}")?;
+
+        let mut watcher = FileWatcher::new([policy.clone(), roots.clone()]);
+        assert!(!watcher.changed());
+
+        std::fs::write(&policy, r#"{"tpm": {}}"#)?;
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+
+        std::fs::write(&roots, "")?;
+        assert!(watcher.changed());
+        std::fs::remove_file(&policy)?;
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+        Ok(())
+    }
+}
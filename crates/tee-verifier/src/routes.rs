@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::{EvidenceBundle, VerifierService};
+
+/// The HTTP API of a [`VerifierService`]:
+///
+/// - `POST /attest` appraises an [`EvidenceBundle`] and returns an
+///   [`AttestResponse`].
+/// - `GET /keys` returns the token signing key as a JWK set.
+pub fn router(service: Arc<VerifierService>) -> Router {
+    Router::new()
+        .route("/attest", post(attest))
+        .route("/keys", get(keys))
+        .route("/health", get(|| async { "ok" }))
+        .with_state(service)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttestResponse {
+    pub token: String,
+    pub accepted: bool,
+}
+
+async fn attest(
+    State(service): State<Arc<VerifierService>>,
+    Json(bundle): Json<EvidenceBundle>,
+) -> Response {
+    // Fetching collateral blocks.
+    let appraisal = tokio::task::spawn_blocking(move || service.appraise(&bundle)).await;
+    match appraisal {
+        Ok(Ok(appraisal)) => Json(AttestResponse {
+            accepted: appraisal.result.is_accepted(),
+            token: appraisal.token,
+        })
+        .into_response(),
+        Ok(Err(err)) => (StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", err)).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    }
+}
+
+async fn keys(State(service): State<Arc<VerifierService>>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "keys": [service.issuer().jwk()] }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use axum::body::Body;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use p256::ecdsa::SigningKey;
+    use tee_attest::Policy;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::test_utils::TestAk;
+    use crate::{KeySigner, TokenIssuer};
+
+    async fn request(router: Router, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_routes() -> eyre::Result<()> {
+        let ak = TestAk::new();
+        let signer = KeySigner::new(SigningKey::from_slice(&[9; 32]).unwrap()).with_key_id("k1");
+        let service = VerifierService::new(
+            ak.verifier(),
+            Policy::default(),
+            TokenIssuer::new(signer, "verifier"),
+        );
+        let router = router(Arc::new(service));
+
+        let bundle = EvidenceBundle {
+            evidence: ak.evidence(&[1; 32], &BTreeMap::from([(0, [0; 32])])),
+            nonce: Some("01".repeat(32)),
+        };
+        let attest = Request::post("/attest")
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_vec(&bundle)?))?;
+        let (status, body) = request(router.clone(), attest).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: AttestResponse = serde_json::from_slice(&body)?;
+        assert!(response.accepted);
+        assert_eq!(response.token.split('.').count(), 3);
+
+        let stale = EvidenceBundle {
+            nonce: Some("02".repeat(32)),
+            ..bundle
+        };
+        let attest = Request::post("/attest")
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_vec(&stale)?))?;
+        let (status, _) = request(router.clone(), attest).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, body) = request(router, Request::get("/keys").body(Body::empty())?).await;
+        assert_eq!(status, StatusCode::OK);
+        let keys: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(keys["keys"][0]["kid"], "k1");
+        assert_eq!(keys["keys"][0]["crv"], "P-256");
+        Ok(())
+    }
+}
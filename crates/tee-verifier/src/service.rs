@@ -0,0 +1,245 @@
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Utc};
+use dcap::NONCE_SIZE;
+use serde::{Deserialize, Serialize};
+use tee_attest::{AttestationResult, Evidence, Policy, Verifier};
+use tee_observe::{observe, Operation};
+
+use crate::TokenIssuer;
+
+/// Evidence to appraise, as sent to the service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvidenceBundle {
+    pub evidence: Evidence,
+    /// Hex encoded challenge of the relying party. The evidence must bind
+    /// it, and the token echoes it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+/// The outcome of [`VerifierService::appraise`].
+#[derive(Debug, Clone)]
+pub struct Appraisal {
+    /// The signed result, issued whether or not the policy accepted the
+    /// evidence.
+    pub token: String,
+    pub result: AttestationResult,
+}
+
+/// The verifier and policy of a [`VerifierService`], swapped together.
+struct Appraiser {
+    verifier: Verifier,
+    policy: Policy,
+}
+
+/// Verifies evidence, appraises it with a policy and signs the outcome.
+///
+/// The verifier and policy can be replaced while the service runs, see
+/// [`reload`](Self::reload).
+pub struct VerifierService {
+    appraiser: RwLock<Arc<Appraiser>>,
+    issuer: TokenIssuer,
+}
+
+impl VerifierService {
+    pub fn new(verifier: Verifier, policy: Policy, issuer: TokenIssuer) -> Self {
+        Self {
+            appraiser: RwLock::new(Arc::new(Appraiser { verifier, policy })),
+            issuer,
+        }
+    }
+
+    pub fn issuer(&self) -> &TokenIssuer {
+        &self.issuer
+    }
+
+    /// The current policy.
+    pub fn policy(&self) -> Policy {
+        self.appraiser().policy.clone()
+    }
+
+    /// Replace the verifier and policy at once. Appraisals in progress
+    /// finish with the ones they started with.
+    pub fn reload(&self, verifier: Verifier, policy: Policy) {
+        *self
+            .appraiser
+            .write()
+            .unwrap_or_else(|err| err.into_inner()) = Arc::new(Appraiser { verifier, policy });
+    }
+
+    /// Build a new verifier and policy with `load` and swap them in, or keep
+    /// the current ones if it fails. Observed as a reload of `source`.
+    pub fn reload_with(
+        &self,
+        source: &'static str,
+        load: impl FnOnce() -> eyre::Result<(Verifier, Policy)>,
+    ) -> eyre::Result<()> {
+        let (verifier, policy) = observe(Operation::Reload, source, load)?;
+        self.reload(verifier, policy);
+        Ok(())
+    }
+
+    fn appraiser(&self) -> Arc<Appraiser> {
+        self.appraiser
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Appraise `bundle` now.
+    pub fn appraise(&self, bundle: &EvidenceBundle) -> eyre::Result<Appraisal> {
+        self.appraise_at(bundle, Utc::now())
+    }
+
+    /// Verify and appraise `bundle` at `at`, and sign the result.
+    ///
+    /// Fails without a token if the evidence cannot be trusted at all or
+    /// does not bind the nonce.
+    pub fn appraise_at(
+        &self,
+        bundle: &EvidenceBundle,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<Appraisal> {
+        let appraiser = self.appraiser();
+        let result = appraiser
+            .verifier
+            .verify_at(&bundle.evidence, &appraiser.policy, at)?;
+        if let Some(nonce) = &bundle.nonce {
+            check_nonce(&result, nonce)?;
+        }
+        let token = self.issuer.issue_at(&result, bundle.nonce.as_deref(), at)?;
+        Ok(Appraisal { token, result })
+    }
+}
+
+/// Check that the evidence binds `nonce`: TPM quotes and Nitro documents
+/// carry it as is, and report data binds it in its second half, see
+/// [`ReportData::bind`](dcap::ReportData::bind).
+fn check_nonce(result: &AttestationResult, nonce: &str) -> eyre::Result<()> {
+    let nonce: [u8; NONCE_SIZE] = hex::decode(nonce)
+        .ok()
+        .and_then(|nonce| nonce.try_into().ok())
+        .ok_or_else(|| eyre::eyre!("nonce must be {} hex bytes", NONCE_SIZE))?;
+    let report_data = &result.report_data;
+    let bound = report_data[..] == nonce
+        || (report_data.len() == 2 * NONCE_SIZE && report_data[NONCE_SIZE..] == nonce);
+    if !bound {
+        eyre::bail!("evidence is not bound to the nonce");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use p256::ecdsa::SigningKey;
+    use tee_attest::PcrPolicy;
+
+    use super::*;
+    use crate::test_utils::{at, TestAk};
+    use crate::{KeySigner, TokenValidator, DEFAULT_TOKEN_LIFETIME};
+
+    fn service(ak: &TestAk, policy: Policy) -> VerifierService {
+        let signer = KeySigner::new(SigningKey::from_slice(&[9; 32]).unwrap()).with_key_id("k1");
+        let issuer = TokenIssuer::new(signer, "https://verifier.example").with_audience("kbs");
+        VerifierService::new(ak.verifier(), policy, issuer)
+    }
+
+    #[test]
+    fn test_appraise() -> eyre::Result<()> {
+        let ak = TestAk::new();
+        let nonce = [5u8; NONCE_SIZE];
+        let pcrs = BTreeMap::from([(0, [1; 32]), (7, [2; 32])]);
+        let policy = Policy {
+            tpm: PcrPolicy {
+                pcrs: BTreeMap::from([(7, vec!["02".repeat(32)])]),
+            },
+            ..Policy::default()
+        };
+        let service = service(&ak, policy);
+        let bundle = EvidenceBundle {
+            evidence: ak.evidence(&nonce, &pcrs),
+            nonce: Some(hex::encode(nonce)),
+        };
+        let appraisal = service.appraise_at(&bundle, at())?;
+        assert!(appraisal.result.is_accepted());
+
+        let validator =
+            TokenValidator::new(service.issuer().verifying_key(), "https://verifier.example")
+                .with_audience("kbs");
+        let claims = validator.validate_at(&appraisal.token, at())?;
+        assert!(claims.accepted);
+        assert_eq!(claims.tee, "tpm");
+        assert_eq!(claims.nonce, bundle.nonce);
+        assert_eq!(claims.measurements["pcr7"], "02".repeat(32));
+        assert!(validator
+            .validate_at(&appraisal.token, at() + DEFAULT_TOKEN_LIFETIME)
+            .is_err());
+
+        // A policy rejection is still signed.
+        let rejected = EvidenceBundle {
+            evidence: ak.evidence(&nonce, &BTreeMap::from([(7, [3; 32])])),
+            nonce: None,
+        };
+        let appraisal = service.appraise_at(&rejected, at())?;
+        let claims = validator.validate_at(&appraisal.token, at())?;
+        assert!(!claims.accepted);
+        assert_eq!(claims.violations.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_unbound_evidence() {
+        let ak = TestAk::new();
+        let service = service(&ak, Policy::default());
+        let pcrs = BTreeMap::from([(0, [1; 32])]);
+        let stale = EvidenceBundle {
+            evidence: ak.evidence(&[4; NONCE_SIZE], &pcrs),
+            nonce: Some(hex::encode([5u8; NONCE_SIZE])),
+        };
+        assert!(service.appraise_at(&stale, at()).is_err());
+
+        let untrusted = EvidenceBundle {
+            evidence: ak.evidence(&[4; NONCE_SIZE], &pcrs),
+            nonce: None,
+        };
+        let other =
+            VerifierService::new(Verifier::new(), Policy::default(), service.issuer().clone());
+        assert!(other.appraise_at(&untrusted, at()).is_err());
+    }
+
+    #[test]
+    fn test_reload() -> eyre::Result<()> {
+        let ak = TestAk::new();
+        let nonce = [5u8; NONCE_SIZE];
+        let bundle = EvidenceBundle {
+            evidence: ak.evidence(&nonce, &BTreeMap::from([(7, [2; 32])])),
+            nonce: None,
+        };
+        let service = service(&ak, Policy::default());
+        assert!(service.appraise_at(&bundle, at())?.result.is_accepted());
+
+        let golden = |value: &str| Policy {
+            tpm: PcrPolicy {
+                pcrs: BTreeMap::from([(7, vec![value.repeat(32)])]),
+            },
+            ..Policy::default()
+        };
+        service.reload_with("policy", || Ok((ak.verifier(), golden("03"))))?;
+        assert_eq!(service.policy(), golden("03"));
+        assert!(!service.appraise_at(&bundle, at())?.result.is_accepted());
+
+        // A failed reload keeps the current policy.
+        assert!(service
+            .reload_with("policy", || eyre::bail!("invalid policy"))
+            .is_err());
+        assert_eq!(service.policy(), golden("03"));
+
+        // The AK is no longer trusted.
+        service.reload(Verifier::new(), golden("02"));
+        assert!(service.appraise_at(&bundle, at()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey, VerifyingKey};
+use p256::pkcs8::DecodePrivateKey;
+use sha2::{Digest, Sha256};
+use tss_client::{TpmSignature, Transport, TssClient};
+
+/// A key that signs attestation result tokens with ES256.
+///
+/// Implementations keep the private key wherever the deployment wants it:
+/// in memory ([`KeySigner`]), in a TPM ([`TpmSigner`]) or in a cloud KMS
+/// ([`KmsSigner`]).
+pub trait ResultSigner: Send + Sync {
+    /// The `kid` of the tokens, so validators can pick the key.
+    fn key_id(&self) -> Option<&str>;
+
+    fn verifying_key(&self) -> VerifyingKey;
+
+    /// An ECDSA P-256 signature over the SHA-256 of `message`, raw
+    /// `r || s`.
+    fn sign(&self, message: &[u8]) -> eyre::Result<[u8; 64]>;
+}
+
+impl<S: ResultSigner + ?Sized> ResultSigner for Box<S> {
+    fn key_id(&self) -> Option<&str> {
+        (**self).key_id()
+    }
+
+    fn verifying_key(&self) -> VerifyingKey {
+        (**self).verifying_key()
+    }
+
+    fn sign(&self, message: &[u8]) -> eyre::Result<[u8; 64]> {
+        (**self).sign(message)
+    }
+}
+
+/// A P-256 key held in memory, e.g. loaded from a file.
+pub struct KeySigner {
+    key: SigningKey,
+    key_id: Option<String>,
+}
+
+impl KeySigner {
+    pub fn new(key: SigningKey) -> Self {
+        Self { key, key_id: None }
+    }
+
+    /// Load a PKCS#8 or SEC1 PEM private key.
+    pub fn from_pem_file(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let pem = std::fs::read_to_string(path)?;
+        let key = SigningKey::from_pkcs8_pem(&pem)
+            .or_else(|_| p256::SecretKey::from_sec1_pem(&pem).map(SigningKey::from))
+            .map_err(|_| eyre::eyre!("not a P-256 private key in PKCS#8 or SEC1 PEM"))?;
+        Ok(Self::new(key))
+    }
+
+    pub fn with_key_id(mut self, key_id: impl Into<String>) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+}
+
+impl ResultSigner for KeySigner {
+    fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    fn verifying_key(&self) -> VerifyingKey {
+        *self.key.verifying_key()
+    }
+
+    fn sign(&self, message: &[u8]) -> eyre::Result<[u8; 64]> {
+        let signature: Signature = self.key.sign(message);
+        Ok(signature.to_bytes().into())
+    }
+}
+
+/// An unrestricted ECC P-256 signing key resident in a TPM, e.g. at a
+/// persistent handle.
+pub struct TpmSigner<T> {
+    tpm: Mutex<TssClient<T>>,
+    handle: u32,
+    public_key: VerifyingKey,
+    key_id: Option<String>,
+}
+
+impl<T: Transport> TpmSigner<T> {
+    /// Sign with the key at `handle`, whose public key is `public_key`.
+    pub fn new(tpm: TssClient<T>, handle: u32, public_key: VerifyingKey) -> Self {
+        Self {
+            tpm: Mutex::new(tpm),
+            handle,
+            public_key,
+            key_id: None,
+        }
+    }
+
+    pub fn with_key_id(mut self, key_id: impl Into<String>) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+}
+
+impl<T: Transport + Send> ResultSigner for TpmSigner<T> {
+    fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    fn verifying_key(&self) -> VerifyingKey {
+        self.public_key
+    }
+
+    fn sign(&self, message: &[u8]) -> eyre::Result<[u8; 64]> {
+        let digest = Sha256::digest(message);
+        let signature = self
+            .tpm
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .sign(self.handle, &digest)?;
+        let TpmSignature::Ecdsa { r, s, .. } = signature else {
+            eyre::bail!("the TPM key did not return an ECDSA signature");
+        };
+        let mut raw = [0u8; 64];
+        raw[..32].copy_from_slice(&scalar(&r)?);
+        raw[32..].copy_from_slice(&scalar(&s)?);
+        Ok(raw)
+    }
+}
+
+/// A cloud KMS that signs digests with asymmetric keys it holds, such as
+/// AWS KMS `Sign` or Cloud KMS `AsymmetricSign`.
+pub trait KmsClient: Send + Sync {
+    /// Sign the SHA-256 `digest` with the ECDSA P-256 key `key_id`,
+    /// returning the DER encoded signature.
+    fn sign_digest(&self, key_id: &str, digest: &[u8; 32]) -> eyre::Result<Vec<u8>>;
+}
+
+/// A P-256 key held by a [`KmsClient`].
+pub struct KmsSigner<C> {
+    client: C,
+    key_id: String,
+    public_key: VerifyingKey,
+}
+
+impl<C: KmsClient> KmsSigner<C> {
+    /// Sign with the KMS key `key_id`, whose public key is `public_key`.
+    /// The key id is also the `kid` of the tokens.
+    pub fn new(client: C, key_id: impl Into<String>, public_key: VerifyingKey) -> Self {
+        Self {
+            client,
+            key_id: key_id.into(),
+            public_key,
+        }
+    }
+}
+
+impl<C: KmsClient> ResultSigner for KmsSigner<C> {
+    fn key_id(&self) -> Option<&str> {
+        Some(&self.key_id)
+    }
+
+    fn verifying_key(&self) -> VerifyingKey {
+        self.public_key
+    }
+
+    fn sign(&self, message: &[u8]) -> eyre::Result<[u8; 64]> {
+        let digest: [u8; 32] = Sha256::digest(message).into();
+        let der = self.client.sign_digest(&self.key_id, &digest)?;
+        let signature = Signature::from_der(&der)
+            .map_err(|_| eyre::eyre!("KMS returned a malformed ECDSA signature"))?;
+        Ok(signature.to_bytes().into())
+    }
+}
+
+/// A 32 byte big-endian ECDSA scalar, which the TPM may return shorter.
+fn scalar(bytes: &[u8]) -> eyre::Result<[u8; 32]> {
+    if bytes.len() > 32 {
+        eyre::bail!("ECDSA signature is not P-256");
+    }
+    let mut scalar = [0u8; 32];
+    scalar[32 - bytes.len()..].copy_from_slice(bytes);
+    Ok(scalar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::signature::Verifier;
+    use p256::ecdsa::DerSignature;
+    use p256::pkcs8::{EncodePrivateKey, LineEnding};
+
+    fn key() -> SigningKey {
+        SigningKey::from_slice(&[7; 32]).unwrap()
+    }
+
+    /// Signs with an in-memory key, like a KMS would.
+    struct FakeKms(SigningKey);
+
+    impl KmsClient for FakeKms {
+        fn sign_digest(&self, key_id: &str, digest: &[u8; 32]) -> eyre::Result<Vec<u8>> {
+            use p256::ecdsa::signature::hazmat::PrehashSigner;
+            eyre::ensure!(key_id == "projects/p/keys/k", "unknown key {}", key_id);
+            let signature: DerSignature = self.0.sign_prehash(digest)?;
+            Ok(signature.as_bytes().to_vec())
+        }
+    }
+
+    fn check(signer: &dyn ResultSigner) -> eyre::Result<()> {
+        let raw = signer.sign(b"header.claims")?;
+        signer
+            .verifying_key()
+            .verify(b"header.claims", &Signature::from_slice(&raw)?)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_signers() -> eyre::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("key.pem");
+        std::fs::write(&path, key().to_pkcs8_pem(LineEnding::LF)?.as_bytes())?;
+        let signer = KeySigner::from_pem_file(&path)?.with_key_id("file");
+        assert_eq!(signer.key_id(), Some("file"));
+        check(&signer)?;
+
+        let kms = KmsSigner::new(FakeKms(key()), "projects/p/keys/k", *key().verifying_key());
+        check(&kms)?;
+        let other = KmsSigner::new(FakeKms(key()), "other", *key().verifying_key());
+        assert!(other.sign(b"message").is_err());
+
+        std::fs::write(&path, "not a key")?;
+        assert!(KeySigner::from_pem_file(&path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_scalar_padding() -> eyre::Result<()> {
+        assert_eq!(scalar(&[1])?[31], 1);
+        assert!(scalar(&[0; 33]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use der::asn1::{GeneralizedTime, UtcTime};
+use der::Encode;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{DerSignature, Signature, SigningKey};
+use sha2::{Digest, Sha256};
+use tee_attest::{Evidence, TpmEvidence, Verifier};
+use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+use x509_cert::name::Name;
+use x509_cert::serial_number::SerialNumber;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::time::{Time, Validity};
+use x509_cert::Certificate;
+
+pub(crate) fn at() -> DateTime<Utc> {
+    DateTime::from_timestamp(1_740_787_200, 0).unwrap()
+}
+
+/// A TPM attestation key and its self-signed certificate.
+pub(crate) struct TestAk {
+    pub(crate) key: SigningKey,
+    pub(crate) cert: Certificate,
+}
+
+impl TestAk {
+    pub(crate) fn new() -> Self {
+        let key = SigningKey::from_slice(&[3; 32]).unwrap();
+        let validity = Validity {
+            not_before: Time::UtcTime(
+                UtcTime::from_unix_duration(Duration::from_secs(1_577_836_800)).unwrap(),
+            ),
+            not_after: Time::GeneralTime(
+                GeneralizedTime::from_unix_duration(Duration::from_secs(2_524_607_999)).unwrap(),
+            ),
+        };
+        let cert = CertificateBuilder::new(
+            Profile::Root,
+            SerialNumber::from(1u32),
+            validity,
+            Name::from_str("CN=Test AK").unwrap(),
+            SubjectPublicKeyInfoOwned::from_key(*key.verifying_key()).unwrap(),
+            &key,
+        )
+        .unwrap()
+        .build::<DerSignature>()
+        .unwrap();
+        Self { key, cert }
+    }
+
+    /// A verifier trusting this AK.
+    pub(crate) fn verifier(&self) -> Verifier {
+        Verifier::new().with_tpm_ak(self.cert.clone())
+    }
+
+    /// A quote of `pcrs` answering `nonce`.
+    pub(crate) fn evidence(&self, nonce: &[u8], pcrs: &BTreeMap<u32, [u8; 32]>) -> Evidence {
+        let attest = tpm_attest(nonce, pcrs);
+        let signature: Signature = self.key.sign(&attest);
+        Evidence::TpmQuote(TpmEvidence {
+            attest,
+            signature: signature.to_bytes().into(),
+            ak_cert: self.cert.to_der().unwrap(),
+            pcrs: pcrs.clone(),
+        })
+    }
+}
+
+/// A TPMS_ATTEST of a quote of the SHA-256 bank.
+fn tpm_attest(nonce: &[u8], pcrs: &BTreeMap<u32, [u8; 32]>) -> Vec<u8> {
+    let mut attest = Vec::new();
+    attest.extend_from_slice(&0xff544347u32.to_be_bytes());
+    attest.extend_from_slice(&0x8018u16.to_be_bytes());
+    attest.extend_from_slice(&[0, 2, 0, 0x0b]);
+    attest.extend_from_slice(&(nonce.len() as u16).to_be_bytes());
+    attest.extend_from_slice(nonce);
+    attest.extend_from_slice(&[0; 17]);
+    attest.extend_from_slice(&1u64.to_be_bytes());
+
+    let mut bitmap = [0u8; 3];
+    for pcr in pcrs.keys() {
+        bitmap[*pcr as usize / 8] |= 1 << (pcr % 8);
+    }
+    attest.extend_from_slice(&1u32.to_be_bytes());
+    attest.extend_from_slice(&0x0bu16.to_be_bytes());
+    attest.push(3);
+    attest.extend_from_slice(&bitmap);
+    let mut digest = Sha256::new();
+    for value in pcrs.values() {
+        digest.update(value);
+    }
+    attest.extend_from_slice(&32u16.to_be_bytes());
+    attest.extend_from_slice(&digest.finalize());
+    attest
+}
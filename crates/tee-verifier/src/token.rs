@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use base64ct::{Base64UrlUnpadded, Encoding};
+use chrono::{DateTime, Utc};
+use dcap::primitives::tcb_info::TcbStatus;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use tee_attest::AttestationResult;
+
+use crate::ResultSigner;
+
+/// Default lifetime of a token.
+pub const DEFAULT_TOKEN_LIFETIME: chrono::Duration = chrono::Duration::minutes(10);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Header {
+    alg: String,
+    typ: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    kid: Option<String>,
+}
+
+/// The claims of a result token: what the verifier concluded about some
+/// evidence. Byte values are hex encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultClaims {
+    pub iss: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub aud: Option<String>,
+    pub iat: i64,
+    pub exp: i64,
+    /// Challenge of the relying party, if the token answers one.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub nonce: Option<String>,
+    /// The kind of TEE, e.g. `tdx` or `sev-snp`.
+    pub tee: String,
+    /// Whether the policy accepted the evidence.
+    pub accepted: bool,
+    pub report_data: String,
+    pub measurements: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub tcb_status: Option<TcbStatus>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub advisory_ids: Vec<String>,
+    /// Why the policy rejected the evidence.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub violations: Vec<String>,
+}
+
+/// Signs [`AttestationResult`]s into ES256 JWTs.
+#[derive(Clone)]
+pub struct TokenIssuer {
+    signer: Arc<dyn ResultSigner>,
+    issuer: String,
+    audience: Option<String>,
+    lifetime: chrono::Duration,
+}
+
+impl TokenIssuer {
+    pub fn new(signer: impl ResultSigner + 'static, issuer: impl Into<String>) -> Self {
+        Self {
+            signer: Arc::new(signer),
+            issuer: issuer.into(),
+            audience: None,
+            lifetime: DEFAULT_TOKEN_LIFETIME,
+        }
+    }
+
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    pub fn with_lifetime(mut self, lifetime: chrono::Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    pub fn key_id(&self) -> Option<&str> {
+        self.signer.key_id()
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.signer.verifying_key()
+    }
+
+    /// The claims of a token for `result`, issued at `at`.
+    pub fn claims(
+        &self,
+        result: &AttestationResult,
+        nonce: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> ResultClaims {
+        ResultClaims {
+            iss: self.issuer.clone(),
+            aud: self.audience.clone(),
+            iat: at.timestamp(),
+            exp: (at + self.lifetime).timestamp(),
+            nonce: nonce.map(str::to_string),
+            tee: result.tee.to_string(),
+            accepted: result.is_accepted(),
+            report_data: hex::encode(&result.report_data),
+            measurements: result.measurements.clone(),
+            tcb_status: result.tcb_status,
+            advisory_ids: result.advisory_ids.clone(),
+            violations: result.violations.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    /// Sign a token for `result`, issued at `at`.
+    pub fn issue_at(
+        &self,
+        result: &AttestationResult,
+        nonce: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> eyre::Result<String> {
+        self.sign_claims(&self.claims(result, nonce, at))
+    }
+
+    /// Sign `claims` as they are.
+    pub fn sign_claims(&self, claims: &ResultClaims) -> eyre::Result<String> {
+        let header = Header {
+            alg: "ES256".to_string(),
+            typ: "JWT".to_string(),
+            kid: self.signer.key_id().map(str::to_string),
+        };
+        let mut token = [
+            Base64UrlUnpadded::encode_string(&serde_json::to_vec(&header)?),
+            Base64UrlUnpadded::encode_string(&serde_json::to_vec(claims)?),
+        ]
+        .join(".");
+        let signature = self.signer.sign(token.as_bytes())?;
+        token.push('.');
+        token.push_str(&Base64UrlUnpadded::encode_string(&signature));
+        Ok(token)
+    }
+
+    /// The verifying key as a JSON Web Key.
+    pub fn jwk(&self) -> serde_json::Value {
+        let point = self.verifying_key().to_encoded_point(false);
+        let mut jwk = serde_json::json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "alg": "ES256",
+            "use": "sig",
+            "x": Base64UrlUnpadded::encode_string(point.x().expect("uncompressed point")),
+            "y": Base64UrlUnpadded::encode_string(point.y().expect("uncompressed point")),
+        });
+        if let Some(key_id) = self.key_id() {
+            jwk["kid"] = key_id.into();
+        }
+        jwk
+    }
+}
+
+/// Validates tokens issued by a [`TokenIssuer`].
+#[derive(Debug, Clone)]
+pub struct TokenValidator {
+    key: VerifyingKey,
+    issuer: String,
+    audience: Option<String>,
+}
+
+impl TokenValidator {
+    pub fn new(key: VerifyingKey, issuer: impl Into<String>) -> Self {
+        Self {
+            key,
+            issuer: issuer.into(),
+            audience: None,
+        }
+    }
+
+    /// Require the `aud` claim to be `audience`.
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    /// Check the signature, issuer, audience and lifetime of `token` at `at`,
+    /// and return its claims.
+    pub fn validate_at(&self, token: &str, at: DateTime<Utc>) -> eyre::Result<ResultClaims> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(claims), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            eyre::bail!("malformed token");
+        };
+
+        let signed = &token[..header.len() + 1 + claims.len()];
+        let header: Header = serde_json::from_slice(&decode(header)?)?;
+        if header.alg != "ES256" {
+            eyre::bail!("unsupported token algorithm {}", header.alg);
+        }
+        let signature = Signature::from_slice(&decode(signature)?)
+            .map_err(|_| eyre::eyre!("malformed token signature"))?;
+        self.key
+            .verify(signed.as_bytes(), &signature)
+            .map_err(|_| eyre::eyre!("token signature is invalid"))?;
+
+        let claims: ResultClaims = serde_json::from_slice(&decode(claims)?)?;
+        if claims.iss != self.issuer {
+            eyre::bail!(
+                "token is issued by {}, expected {}",
+                claims.iss,
+                self.issuer
+            );
+        }
+        if let Some(audience) = &self.audience {
+            if claims.aud.as_ref() != Some(audience) {
+                eyre::bail!("token is not intended for {}", audience);
+            }
+        }
+        let now = at.timestamp();
+        if now < claims.iat {
+            eyre::bail!("token is not valid yet");
+        }
+        if now >= claims.exp {
+            eyre::bail!("token expired");
+        }
+        Ok(claims)
+    }
+}
+
+fn decode(part: &str) -> eyre::Result<Vec<u8>> {
+    Base64UrlUnpadded::decode_vec(part).map_err(|_| eyre::eyre!("malformed token encoding"))
+}
@@ -0,0 +1,90 @@
+/* Generated with cbindgen from the tee-ware-ffi crate, see cbindgen.toml. */
+
+#ifndef TEE_WARE_H
+#define TEE_WARE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+// The call succeeded.
+#define TEE_OK 0
+
+// An argument is null, not UTF-8 or malformed.
+#define TEE_ERROR_ARGUMENT 1
+
+// Verification, appraisal or collection failed.
+#define TEE_ERROR_FAILED 2
+
+// The library panicked; this is a bug.
+#define TEE_ERROR_PANIC 3
+
+// Bytes owned by the library, released with `tee_buffer_free`.
+typedef struct TeeBuffer {
+  uint8_t *data;
+  size_t len;
+} TeeBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Release a buffer returned by the library. Freeing an empty buffer is a
+// no-op.
+void tee_buffer_free(struct TeeBuffer *buffer);
+
+// The message of the last error on this thread, or null. The string is
+// valid until the next call into the library on this thread.
+const char *tee_last_error(void);
+
+// Verify the SGX or TDX `quote` with `collateral_json`, a
+// `QuoteCollateral`, and appraise it with `policy_json`, the default policy
+// if null, at `at` in seconds since the epoch, now if zero.
+//
+// Writes an `AppraisalOutput` to `out`. The quote must be signed through
+// the Intel SGX Root CA.
+int tee_verify_quote(const uint8_t *quote,
+                     size_t quote_len,
+                     const char *collateral_json,
+                     const char *policy_json,
+                     int64_t at,
+                     struct TeeBuffer *out);
+
+// Fetch the collateral of the SGX or TDX `quote` from the PCCS at
+// `pccs_url`, or the Intel PCS if null.
+//
+// Writes a `QuoteCollateral` as JSON to `out`, to pass to
+// `tee_verify_quote` now or later.
+int tee_fetch_collateral(const uint8_t *quote,
+                         size_t quote_len,
+                         const char *pccs_url,
+                         struct TeeBuffer *out);
+
+// Quote `pcrs` of the local vTPM with the `NONCE_SIZE` bytes at `nonce`.
+// A `pcr_count` of zero quotes PCRs 0 to 9.
+//
+// Writes the `TpmQuote` evidence as JSON to `out`, ready for
+// `tee_appraise`.
+int tee_tpm_quote(const uint8_t *nonce,
+                  const uint32_t *pcrs,
+                  size_t pcr_count,
+                  struct TeeBuffer *out);
+
+// Verify `evidence_json`, an `Evidence` of any supported TEE, against the
+// roots of trust in `trust_json`, a `TrustConfig`, and appraise it with
+// `policy_json` at `at` in seconds since the epoch, now if zero. Null
+// policies and trust configs are the defaults.
+//
+// Writes an `AppraisalOutput` to `out`. Fails if the evidence cannot be
+// trusted at all; a policy rejection is reported in the output. Checking
+// that the report data binds the expected nonce is up to the caller.
+int tee_appraise(const char *evidence_json,
+                 const char *policy_json,
+                 const char *trust_json,
+                 int64_t at,
+                 struct TeeBuffer *out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TEE_WARE_H */
@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use dcap::pcs::blocking::PcsClient;
+use dcap::pcs::PcsConfig;
+use dcap::primitives::tcb_info::TcbStatus;
+use dcap::{QuoteCollateral, TrustAnchors};
+use serde::{Deserialize, Serialize};
+use tee_attest::{AttestationResult, Verifier};
+use x509_cert::der::DecodePem;
+use x509_cert::Certificate;
+
+/// The roots of trust of [`tee_appraise`](crate::tee_appraise), as JSON.
+/// Certificates are PEM.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrustConfig {
+    /// PEM bundle of the trusted roots of SGX and TDX quotes, the Intel SGX
+    /// Root CA if omitted.
+    pub roots: Option<String>,
+    pub amd_arks: Vec<String>,
+    pub nitro_root: Option<String>,
+    /// Enrolled TPM attestation keys.
+    pub tpm_aks: Vec<String>,
+    /// The collateral of the SGX or TDX quote. Fetched from the PCCS at
+    /// `pccs_url`, or the Intel PCS, if omitted.
+    pub collateral: Option<QuoteCollateral>,
+    pub pccs_url: Option<String>,
+}
+
+impl TrustConfig {
+    pub fn verifier(&self) -> eyre::Result<Verifier> {
+        let mut verifier = match &self.collateral {
+            Some(collateral) => {
+                let collateral = collateral.clone();
+                Verifier::new().with_collateral(move |_: &_| Ok(collateral.clone()))
+            }
+            None => {
+                let config = match &self.pccs_url {
+                    Some(url) => PcsConfig::pccs(url),
+                    None => PcsConfig::default(),
+                };
+                Verifier::new().with_collateral(PcsClient::with_config(config))
+            }
+        };
+        if let Some(roots) = &self.roots {
+            verifier = verifier.with_trust_anchors(TrustAnchors::from_pem(roots.as_bytes())?);
+        }
+        for ark in &self.amd_arks {
+            verifier = verifier.with_amd_ark(certificate("AMD root key", ark)?);
+        }
+        if let Some(root) = &self.nitro_root {
+            verifier = verifier.with_nitro_root(certificate("Nitro root", root)?);
+        }
+        for ak in &self.tpm_aks {
+            verifier = verifier.with_tpm_ak(certificate("TPM attestation key", ak)?);
+        }
+        Ok(verifier)
+    }
+}
+
+fn certificate(name: &str, pem: &str) -> eyre::Result<Certificate> {
+    Certificate::from_pem(pem).map_err(|err| eyre::eyre!("invalid {} certificate: {}", name, err))
+}
+
+/// An [`AttestationResult`] as returned over the C API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppraisalOutput {
+    pub tee: String,
+    pub accepted: bool,
+    /// Hex encoded.
+    pub report_data: String,
+    pub measurements: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tcb_status: Option<TcbStatus>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub advisory_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<String>,
+}
+
+impl From<&AttestationResult> for AppraisalOutput {
+    fn from(result: &AttestationResult) -> Self {
+        Self {
+            tee: result.tee.as_str().to_string(),
+            accepted: result.is_accepted(),
+            report_data: hex::encode(&result.report_data),
+            measurements: result.measurements.clone(),
+            tcb_status: result.tcb_status,
+            advisory_ids: result.advisory_ids.clone(),
+            violations: result.violations.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, UnwindSafe};
+use std::ptr;
+
+/// The call succeeded.
+pub const TEE_OK: c_int = 0;
+/// An argument is null, not UTF-8 or malformed.
+pub const TEE_ERROR_ARGUMENT: c_int = 1;
+/// Verification, appraisal or collection failed.
+pub const TEE_ERROR_FAILED: c_int = 2;
+/// The library panicked; this is a bug.
+pub const TEE_ERROR_PANIC: c_int = 3;
+
+/// Bytes owned by the library, released with [`tee_buffer_free`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct TeeBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl TeeBuffer {
+    pub(crate) fn empty() -> Self {
+        Self {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    pub(crate) fn from_vec(bytes: Vec<u8>) -> Self {
+        let bytes = Box::into_raw(bytes.into_boxed_slice());
+        Self {
+            data: bytes as *mut u8,
+            len: bytes.len(),
+        }
+    }
+
+    /// The bytes of a buffer returned by the library.
+    ///
+    /// # Safety
+    ///
+    /// `self` must have been returned by the library and not freed.
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        if self.data.is_null() {
+            return &[];
+        }
+        std::slice::from_raw_parts(self.data, self.len)
+    }
+}
+
+/// Release a buffer returned by the library. Freeing an empty buffer is a
+/// no-op.
+///
+/// # Safety
+///
+/// `buffer` must be null or point to a buffer returned by the library that
+/// has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tee_buffer_free(buffer: *mut TeeBuffer) {
+    let Some(buffer) = buffer.as_mut() else {
+        return;
+    };
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+    *buffer = TeeBuffer::empty();
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The message of the last error on this thread, or null. The string is
+/// valid until the next call into the library on this thread.
+#[no_mangle]
+pub extern "C" fn tee_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |error| error.as_ptr())
+    })
+}
+
+fn set_last_error(message: String) {
+    // Interior NULs cannot cross the boundary.
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+}
+
+/// An error of an exported function, mapped to its status code.
+pub(crate) enum Error {
+    Argument(String),
+    Failed(eyre::Report),
+}
+
+impl From<eyre::Report> for Error {
+    fn from(err: eyre::Report) -> Self {
+        Self::Failed(err)
+    }
+}
+
+/// Run `f` for an exported function: store its output in `out`, record its
+/// error and keep panics from unwinding into the caller.
+pub(crate) fn call(
+    out: *mut TeeBuffer,
+    f: impl FnOnce() -> Result<Vec<u8>, Error> + UnwindSafe,
+) -> c_int {
+    LAST_ERROR.with(|error| *error.borrow_mut() = None);
+    // SAFETY: the caller passes a valid pointer or null.
+    let Some(out) = (unsafe { out.as_mut() }) else {
+        set_last_error("out is null".to_string());
+        return TEE_ERROR_ARGUMENT;
+    };
+    *out = TeeBuffer::empty();
+    match catch_unwind(f) {
+        Ok(Ok(bytes)) => {
+            *out = TeeBuffer::from_vec(bytes);
+            TEE_OK
+        }
+        Ok(Err(Error::Argument(message))) => {
+            set_last_error(message);
+            TEE_ERROR_ARGUMENT
+        }
+        Ok(Err(Error::Failed(err))) => {
+            set_last_error(format!("{:#}", err));
+            TEE_ERROR_FAILED
+        }
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            set_last_error(format!("panic: {}", message));
+            TEE_ERROR_PANIC
+        }
+    }
+}
+
+/// The `len` bytes at `data`, which may only be null if `len` is zero.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes that outlive the call.
+pub(crate) unsafe fn bytes<'a>(name: &str, data: *const u8, len: usize) -> Result<&'a [u8], Error> {
+    if data.is_null() {
+        if len == 0 {
+            return Ok(&[]);
+        }
+        return Err(Error::Argument(format!("{} is null", name)));
+    }
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+/// The NUL terminated UTF-8 string at `data`, `None` if it is null.
+///
+/// # Safety
+///
+/// `data` must be null or point to a NUL terminated string that outlives
+/// the call.
+pub(crate) unsafe fn optional_str<'a>(
+    name: &str,
+    data: *const c_char,
+) -> Result<Option<&'a str>, Error> {
+    if data.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(data)
+        .to_str()
+        .map(Some)
+        .map_err(|_| Error::Argument(format!("{} is not UTF-8", name)))
+}
+
+/// Like [`optional_str`], but `data` must not be null.
+///
+/// # Safety
+///
+/// See [`optional_str`].
+pub(crate) unsafe fn required_str<'a>(name: &str, data: *const c_char) -> Result<&'a str, Error> {
+    optional_str(name, data)?.ok_or_else(|| Error::Argument(format!("{} is null", name)))
+}
+
+/// Parse `json`, the argument `name`.
+pub(crate) fn json<T: serde::de::DeserializeOwned>(name: &str, json: &str) -> Result<T, Error> {
+    serde_json::from_str(json).map_err(|err| Error::Argument(format!("invalid {}: {}", name, err)))
+}
@@ -0,0 +1,448 @@
+//! C bindings of quote verification, TPM quoting and policy appraisal, so
+//! non-Rust components reuse this implementation instead of their own.
+//!
+//! Every function returns a status code, `TEE_OK` on success, and writes
+//! its output to a [`TeeBuffer`] the caller releases with
+//! [`tee_buffer_free`]. On failure [`tee_last_error`] describes the error.
+//! Structured inputs and outputs are JSON: evidence is a
+//! [`tee_attest::Evidence`], policies a [`tee_attest::Policy`], roots of
+//! trust a [`TrustConfig`] and results an [`AppraisalOutput`].
+//!
+//! The C header is `include/tee_ware.h`.
+
+use std::ffi::{c_char, c_int};
+
+use chrono::{DateTime, Utc};
+use dcap::pcs::blocking::PcsClient;
+use dcap::pcs::PcsConfig;
+use dcap::{tee_type, CollateralFetcher, CollateralKey, Quote, QuoteCollateral, NONCE_SIZE};
+use tee_attest::gcp::{GcpVtpm, DEFAULT_PCRS};
+use tee_attest::{Evidence, Policy, TpmEvidence};
+
+mod ffi;
+pub use ffi::*;
+
+mod config;
+pub use config::*;
+
+use ffi::{bytes, call, json, optional_str, required_str, Error};
+
+/// Verify the SGX or TDX `quote` with `collateral_json`, a
+/// `QuoteCollateral`, and appraise it with `policy_json`, the default policy
+/// if null, at `at` in seconds since the epoch, now if zero.
+///
+/// Writes an `AppraisalOutput` to `out`. The quote must be signed through
+/// the Intel SGX Root CA.
+///
+/// # Safety
+///
+/// `quote` must point to `quote_len` bytes, the strings must be null or NUL
+/// terminated and `out` must point to a writable [`TeeBuffer`].
+#[no_mangle]
+pub unsafe extern "C" fn tee_verify_quote(
+    quote: *const u8,
+    quote_len: usize,
+    collateral_json: *const c_char,
+    policy_json: *const c_char,
+    at: i64,
+    out: *mut TeeBuffer,
+) -> c_int {
+    call(out, || {
+        let quote = bytes("quote", quote, quote_len)?.to_vec();
+        let collateral: QuoteCollateral = json(
+            "collateral",
+            required_str("collateral_json", collateral_json)?,
+        )?;
+        let policy = policy(optional_str("policy_json", policy_json)?)?;
+        let at = timestamp(at)?;
+
+        let header = Quote::parse(&quote)
+            .map_err(|err| Error::Argument(format!("invalid quote: {:#}", err)))?
+            .header;
+        let evidence = match header.tee_type {
+            tee_type::SGX => Evidence::SgxQuote(quote),
+            _ => Evidence::TdxQuote(quote),
+        };
+        let trust = TrustConfig {
+            collateral: Some(collateral),
+            ..TrustConfig::default()
+        };
+        appraise(&trust, &evidence, &policy, at)
+    })
+}
+
+/// Fetch the collateral of the SGX or TDX `quote` from the PCCS at
+/// `pccs_url`, or the Intel PCS if null.
+///
+/// Writes a `QuoteCollateral` as JSON to `out`, to pass to
+/// [`tee_verify_quote`] now or later.
+///
+/// # Safety
+///
+/// `quote` must point to `quote_len` bytes, `pccs_url` must be null or NUL
+/// terminated and `out` must point to a writable [`TeeBuffer`].
+#[no_mangle]
+pub unsafe extern "C" fn tee_fetch_collateral(
+    quote: *const u8,
+    quote_len: usize,
+    pccs_url: *const c_char,
+    out: *mut TeeBuffer,
+) -> c_int {
+    call(out, || {
+        let quote = Quote::parse(bytes("quote", quote, quote_len)?)
+            .map_err(|err| Error::Argument(format!("invalid quote: {:#}", err)))?;
+        let config = match optional_str("pccs_url", pccs_url)? {
+            Some(url) => PcsConfig::pccs(url),
+            None => PcsConfig::default(),
+        };
+        let collateral =
+            PcsClient::with_config(config).fetch_collateral(&CollateralKey::from_quote(&quote)?)?;
+        Ok(serde_json::to_vec(&collateral).map_err(eyre::Report::from)?)
+    })
+}
+
+/// Quote `pcrs` of the local vTPM with the `NONCE_SIZE` bytes at `nonce`.
+/// A `pcr_count` of zero quotes PCRs 0 to 9.
+///
+/// Writes the `TpmQuote` evidence as JSON to `out`, ready for
+/// [`tee_appraise`].
+///
+/// # Safety
+///
+/// `nonce` must point to 32 bytes, `pcrs` to `pcr_count` PCR indices and
+/// `out` to a writable [`TeeBuffer`].
+#[no_mangle]
+pub unsafe extern "C" fn tee_tpm_quote(
+    nonce: *const u8,
+    pcrs: *const u32,
+    pcr_count: usize,
+    out: *mut TeeBuffer,
+) -> c_int {
+    call(out, || {
+        let nonce: &[u8; NONCE_SIZE] = bytes("nonce", nonce, NONCE_SIZE)?
+            .try_into()
+            .map_err(|_| Error::Argument("nonce is null".to_string()))?;
+        let pcrs = match pcr_count {
+            0 => DEFAULT_PCRS.to_vec(),
+            _ => {
+                if pcrs.is_null() {
+                    return Err(Error::Argument("pcrs is null".to_string()));
+                }
+                std::slice::from_raw_parts(pcrs, pcr_count).to_vec()
+            }
+        };
+        if let Some(pcr) = pcrs.iter().find(|pcr| **pcr >= 24) {
+            return Err(Error::Argument(format!("invalid PCR {}", pcr)));
+        }
+        let evidence = GcpVtpm::open()?.attest(nonce, &pcrs)?;
+        let evidence = Evidence::TpmQuote(TpmEvidence {
+            attest: evidence.attest,
+            signature: evidence.signature,
+            ak_cert: evidence.ak_cert,
+            pcrs: evidence.pcrs,
+        });
+        Ok(serde_json::to_vec(&evidence).map_err(eyre::Report::from)?)
+    })
+}
+
+/// Verify `evidence_json`, an `Evidence` of any supported TEE, against the
+/// roots of trust in `trust_json`, a `TrustConfig`, and appraise it with
+/// `policy_json` at `at` in seconds since the epoch, now if zero. Null
+/// policies and trust configs are the defaults.
+///
+/// Writes an `AppraisalOutput` to `out`. Fails if the evidence cannot be
+/// trusted at all; a policy rejection is reported in the output. Checking
+/// that the report data binds the expected nonce is up to the caller.
+///
+/// # Safety
+///
+/// The strings must be null or NUL terminated and `out` must point to a
+/// writable [`TeeBuffer`].
+#[no_mangle]
+pub unsafe extern "C" fn tee_appraise(
+    evidence_json: *const c_char,
+    policy_json: *const c_char,
+    trust_json: *const c_char,
+    at: i64,
+    out: *mut TeeBuffer,
+) -> c_int {
+    call(out, || {
+        let evidence: Evidence = json("evidence", required_str("evidence_json", evidence_json)?)?;
+        let policy = policy(optional_str("policy_json", policy_json)?)?;
+        let trust: TrustConfig = match optional_str("trust_json", trust_json)? {
+            Some(trust) => json("trust config", trust)?,
+            None => TrustConfig::default(),
+        };
+        appraise(&trust, &evidence, &policy, timestamp(at)?)
+    })
+}
+
+fn appraise(
+    trust: &TrustConfig,
+    evidence: &Evidence,
+    policy: &Policy,
+    at: DateTime<Utc>,
+) -> Result<Vec<u8>, Error> {
+    let result = trust.verifier()?.verify_at(evidence, policy, at)?;
+    Ok(serde_json::to_vec(&AppraisalOutput::from(&result)).map_err(eyre::Report::from)?)
+}
+
+fn policy(json: Option<&str>) -> Result<Policy, Error> {
+    match json {
+        Some(json) => Policy::from_json(json)
+            .map_err(|err| Error::Argument(format!("invalid policy: {:#}", err))),
+        None => Ok(Policy::default()),
+    }
+}
+
+fn timestamp(at: i64) -> Result<DateTime<Utc>, Error> {
+    if at == 0 {
+        return Ok(Utc::now());
+    }
+    DateTime::from_timestamp(at, 0).ok_or_else(|| Error::Argument(format!("invalid time {}", at)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::ffi::{CStr, CString};
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use der::asn1::{GeneralizedTime, UtcTime};
+    use der::EncodePem;
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::{DerSignature, Signature, SigningKey};
+    use sha2::{Digest, Sha256};
+    use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+    use x509_cert::der::Encode;
+    use x509_cert::name::Name;
+    use x509_cert::serial_number::SerialNumber;
+    use x509_cert::spki::SubjectPublicKeyInfoOwned;
+    use x509_cert::time::{Time, Validity};
+    use x509_cert::Certificate;
+
+    use super::*;
+
+    const AT: i64 = 1_740_787_200;
+
+    /// A quote of `pcrs` answering `nonce` by a self-signed AK.
+    fn tpm_evidence(nonce: &[u8], pcrs: &BTreeMap<u32, [u8; 32]>) -> (Evidence, Certificate) {
+        let key = SigningKey::from_slice(&[3; 32]).unwrap();
+        let validity = Validity {
+            not_before: Time::UtcTime(
+                UtcTime::from_unix_duration(Duration::from_secs(1_577_836_800)).unwrap(),
+            ),
+            not_after: Time::GeneralTime(
+                GeneralizedTime::from_unix_duration(Duration::from_secs(2_524_607_999)).unwrap(),
+            ),
+        };
+        let cert = CertificateBuilder::new(
+            Profile::Root,
+            SerialNumber::from(1u32),
+            validity,
+            Name::from_str("CN=Test AK").unwrap(),
+            SubjectPublicKeyInfoOwned::from_key(*key.verifying_key()).unwrap(),
+            &key,
+        )
+        .unwrap()
+        .build::<DerSignature>()
+        .unwrap();
+
+        let mut attest = Vec::new();
+        attest.extend_from_slice(&0xff544347u32.to_be_bytes());
+        attest.extend_from_slice(&0x8018u16.to_be_bytes());
+        attest.extend_from_slice(&[0, 2, 0, 0x0b]);
+        attest.extend_from_slice(&(nonce.len() as u16).to_be_bytes());
+        attest.extend_from_slice(nonce);
+        attest.extend_from_slice(&[0; 17]);
+        attest.extend_from_slice(&1u64.to_be_bytes());
+        let mut bitmap = [0u8; 3];
+        for pcr in pcrs.keys() {
+            bitmap[*pcr as usize / 8] |= 1 << (pcr % 8);
+        }
+        attest.extend_from_slice(&1u32.to_be_bytes());
+        attest.extend_from_slice(&0x0bu16.to_be_bytes());
+        attest.push(3);
+        attest.extend_from_slice(&bitmap);
+        let mut digest = Sha256::new();
+        for value in pcrs.values() {
+            digest.update(value);
+        }
+        attest.extend_from_slice(&32u16.to_be_bytes());
+        attest.extend_from_slice(&digest.finalize());
+
+        let signature: Signature = key.sign(&attest);
+        let evidence = Evidence::TpmQuote(TpmEvidence {
+            attest,
+            signature: signature.to_bytes().into(),
+            ak_cert: cert.to_der().unwrap(),
+            pcrs: pcrs.clone(),
+        });
+        (evidence, cert)
+    }
+
+    fn last_error() -> String {
+        let error = tee_last_error();
+        assert!(!error.is_null());
+        unsafe { CStr::from_ptr(error) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_appraise() -> eyre::Result<()> {
+        let nonce = [5u8; NONCE_SIZE];
+        let (evidence, ak) = tpm_evidence(&nonce, &BTreeMap::from([(0, [1; 32]), (7, [2; 32])]));
+        let evidence = CString::new(serde_json::to_string(&evidence)?)?;
+        let trust = TrustConfig {
+            tpm_aks: vec![ak.to_pem(Default::default())?],
+            ..TrustConfig::default()
+        };
+        let trust = CString::new(serde_json::to_string(&trust)?)?;
+        let policy = CString::new(format!(
+            r#"{{"tpm":{{"pcrs":{{"7":["{}"]}}}}}}"#,
+            "03".repeat(32)
+        ))?;
+
+        let mut out = TeeBuffer::empty();
+        let status = unsafe {
+            tee_appraise(
+                evidence.as_ptr(),
+                std::ptr::null(),
+                trust.as_ptr(),
+                AT,
+                &mut out,
+            )
+        };
+        assert_eq!(status, TEE_OK);
+        assert!(tee_last_error().is_null());
+        let output: AppraisalOutput = serde_json::from_slice(unsafe { out.as_slice() })?;
+        unsafe { tee_buffer_free(&mut out) };
+        assert!(out.data.is_null());
+        assert_eq!(output.tee, "tpm");
+        assert!(output.accepted);
+        assert_eq!(output.report_data, hex::encode(nonce));
+        assert_eq!(output.measurements["pcr7"], "02".repeat(32));
+
+        let status = unsafe {
+            tee_appraise(
+                evidence.as_ptr(),
+                policy.as_ptr(),
+                trust.as_ptr(),
+                AT,
+                &mut out,
+            )
+        };
+        assert_eq!(status, TEE_OK);
+        let output: AppraisalOutput = serde_json::from_slice(unsafe { out.as_slice() })?;
+        unsafe { tee_buffer_free(&mut out) };
+        assert!(!output.accepted);
+        assert_eq!(output.violations.len(), 1);
+
+        // The AK is not enrolled by default.
+        let status = unsafe {
+            tee_appraise(
+                evidence.as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+                AT,
+                &mut out,
+            )
+        };
+        assert_eq!(status, TEE_ERROR_FAILED);
+        assert!(out.data.is_null());
+        assert!(last_error().contains("not enrolled"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let mut out = TeeBuffer::empty();
+        let status = unsafe {
+            tee_appraise(
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+                0,
+                &mut out,
+            )
+        };
+        assert_eq!(status, TEE_ERROR_ARGUMENT);
+        assert_eq!(last_error(), "evidence_json is null");
+
+        let evidence = CString::new(r#"{"type":"sgx_quote","evidence":"00"}"#).unwrap();
+        let policy = CString::new(r#"{"tpm":{"pcrs":{"7":["zz"]}}}"#).unwrap();
+        let status = unsafe {
+            tee_appraise(
+                evidence.as_ptr(),
+                policy.as_ptr(),
+                std::ptr::null(),
+                0,
+                &mut out,
+            )
+        };
+        assert_eq!(status, TEE_ERROR_ARGUMENT);
+        assert!(last_error().starts_with("invalid policy"));
+
+        let collateral = CString::new("{}").unwrap();
+        let status = unsafe {
+            tee_verify_quote(
+                [0u8; 4].as_ptr(),
+                4,
+                collateral.as_ptr(),
+                std::ptr::null(),
+                0,
+                &mut out,
+            )
+        };
+        assert_eq!(status, TEE_ERROR_ARGUMENT);
+
+        let status = unsafe { tee_tpm_quote(std::ptr::null(), std::ptr::null(), 0, &mut out) };
+        assert_eq!(status, TEE_ERROR_ARGUMENT);
+        let pcrs = [7u32, 24];
+        let status = unsafe { tee_tpm_quote([0u8; 32].as_ptr(), pcrs.as_ptr(), 2, &mut out) };
+        assert_eq!(status, TEE_ERROR_ARGUMENT);
+        assert_eq!(last_error(), "invalid PCR 24");
+
+        let status = unsafe {
+            tee_fetch_collateral(std::ptr::null(), 0, std::ptr::null(), std::ptr::null_mut())
+        };
+        assert_eq!(status, TEE_ERROR_ARGUMENT);
+        assert_eq!(last_error(), "out is null");
+    }
+
+    #[test]
+    fn test_header_declares_exports() {
+        let header = include_str!("../include/tee_ware.h");
+        let sources = [include_str!("lib.rs"), include_str!("ffi.rs")];
+        let mut exports = 0;
+        for source in sources {
+            for line in source.lines() {
+                let Some(rest) = line
+                    .strip_prefix("pub unsafe extern \"C\" fn ")
+                    .or_else(|| line.strip_prefix("pub extern \"C\" fn "))
+                else {
+                    continue;
+                };
+                let name = rest.split('(').next().unwrap();
+                assert!(
+                    header.contains(&format!("{}(", name)),
+                    "{} is not declared",
+                    name
+                );
+                exports += 1;
+            }
+        }
+        assert_eq!(exports, 6);
+        for constant in [
+            "TEE_OK 0",
+            "TEE_ERROR_ARGUMENT 1",
+            "TEE_ERROR_FAILED 2",
+            "TEE_ERROR_PANIC 3",
+        ] {
+            assert!(header.contains(&format!("#define {}", constant)));
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use dcap::primitives::tcb_info::TcbStatus;
+use dcap::test_utils::{
+    quote_collateral, sgx_quote, tdx_quote, tpm_attest, verification_time, TestPki,
+};
+use dcap::{tee_type, CollateralKey, QuoteCollateral, TrustAnchors};
+use der::Encode;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use tee_attest::{AttestationResult, Evidence, PcrPolicy, Policy, TpmEvidence, Verifier};
+use x509_cert::Certificate;
+
+use crate::ak_certificate;
+
+/// What verifying a [`Fixture`] must yield.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The evidence is authentic and the policy accepts it.
+    Accepted,
+    /// The evidence is authentic and the policy rejects it.
+    Rejected,
+    /// The evidence cannot be trusted, so verification fails.
+    Untrusted,
+}
+
+/// Evidence, the trust and policy to appraise it with, and the known
+/// verdict, for end-to-end tests of verifiers.
+#[derive(Clone)]
+pub struct Fixture {
+    pub name: &'static str,
+    pub evidence: Evidence,
+    /// The collateral served for SGX and TDX quotes.
+    pub collateral: Option<QuoteCollateral>,
+    /// The roots the quotes chain to.
+    pub anchors: TrustAnchors,
+    /// The enrolled TPM attestation keys.
+    pub tpm_aks: Vec<Certificate>,
+    pub policy: Policy,
+    /// When the fixture is verified, while its certificates and collateral
+    /// are current.
+    pub at: DateTime<Utc>,
+    pub verdict: Verdict,
+}
+
+impl Fixture {
+    /// A verifier trusting what the fixture trusts, fetching its
+    /// collateral.
+    pub fn verifier(&self) -> Verifier {
+        let mut verifier = Verifier::new().with_trust_anchors(self.anchors.clone());
+        if let Some(collateral) = self.collateral.clone() {
+            verifier = verifier.with_collateral(move |_: &CollateralKey| Ok(collateral.clone()));
+        }
+        for ak_cert in &self.tpm_aks {
+            verifier = verifier.with_tpm_ak(ak_cert.clone());
+        }
+        verifier
+    }
+
+    /// Verify the fixture with `verifier` and check the verdict, returning
+    /// the result of authentic evidence.
+    pub fn check_with(&self, verifier: &Verifier) -> eyre::Result<Option<AttestationResult>> {
+        let result = verifier.verify_at(&self.evidence, &self.policy, self.at);
+        let verdict = match &result {
+            Ok(result) if result.is_accepted() => Verdict::Accepted,
+            Ok(_) => Verdict::Rejected,
+            Err(_) => Verdict::Untrusted,
+        };
+        if verdict != self.verdict {
+            eyre::bail!(
+                "fixture {}: expected {:?}, got {:?}: {:?}",
+                self.name,
+                self.verdict,
+                verdict,
+                result.map(|result| result.violations)
+            );
+        }
+        Ok(result.ok())
+    }
+
+    /// Like [`check_with`](Self::check_with), with [`Fixture::verifier`].
+    pub fn check(&self) -> eyre::Result<Option<AttestationResult>> {
+        self.check_with(&self.verifier())
+    }
+}
+
+/// The SHA-256 PCR values of the TPM fixtures.
+pub fn golden_pcrs() -> BTreeMap<u32, [u8; 32]> {
+    BTreeMap::from([(0, [0x10; 32]), (7, [0x17; 32])])
+}
+
+/// A policy allowing exactly `pcrs`.
+pub fn pcr_policy(pcrs: &BTreeMap<u32, [u8; 32]>) -> PcrPolicy {
+    PcrPolicy {
+        pcrs: pcrs
+            .iter()
+            .map(|(&index, value)| (index, vec![hex::encode(value)]))
+            .collect(),
+    }
+}
+
+/// The nonce the TPM fixtures answer.
+pub const FIXTURE_NONCE: [u8; 32] = [0x4e; 32];
+
+/// A TPM quote of `pcrs` over `nonce`, signed by `ak`.
+pub fn tpm_evidence(
+    ak: &SigningKey,
+    ak_cert: &Certificate,
+    nonce: &[u8],
+    pcrs: &BTreeMap<u32, [u8; 32]>,
+) -> Evidence {
+    let attest = tpm_attest(nonce, pcrs);
+    let signature: Signature = ak.sign(&attest);
+    Evidence::TpmQuote(TpmEvidence {
+        attest,
+        signature: signature.to_bytes().into(),
+        ak_cert: ak_cert.to_der().expect("encoding the AK certificate"),
+        pcrs: pcrs.clone(),
+    })
+}
+
+/// Every fixture, covering SGX, TDX and TPM evidence that is accepted,
+/// rejected by policy, and untrusted.
+pub fn fixtures() -> Vec<Fixture> {
+    let pki = TestPki::new();
+    let anchors = TrustAnchors::from_pem(pki.root_pem().as_bytes())
+        .expect("parsing the test root certificate");
+    let dcap = |name, evidence, tee, policy: dcap::Policy, verdict| Fixture {
+        name,
+        evidence,
+        collateral: Some(quote_collateral(&pki, tee)),
+        anchors: anchors.clone(),
+        tpm_aks: Vec::new(),
+        policy: Policy {
+            dcap: policy,
+            ..Policy::default()
+        },
+        at: verification_time(),
+        verdict,
+    };
+    // The synthetic TCB info rates the quotes SWHardeningNeeded.
+    let tolerant = dcap::Policy {
+        tcb_statuses: vec![TcbStatus::UpToDate, TcbStatus::SWHardeningNeeded],
+        ..dcap::Policy::default()
+    };
+
+    let mut revoked = quote_collateral(&pki, tee_type::SGX);
+    revoked.pck_crl = pki
+        .pck_crl(&[(3, None)])
+        .to_der()
+        .expect("encoding the PCK CRL");
+
+    let ak = SigningKey::from_slice(&[0x0a; 32]).expect("a valid P-256 scalar");
+    let ak_cert = ak_certificate(
+        ak.verifying_key(),
+        &SigningKey::from_slice(&[0x0c; 32]).unwrap(),
+    );
+    let other_ak = SigningKey::from_slice(&[0x0b; 32]).expect("a valid P-256 scalar");
+    let tpm = |name, evidence, verdict| Fixture {
+        name,
+        evidence,
+        collateral: None,
+        anchors: anchors.clone(),
+        tpm_aks: vec![ak_cert.clone()],
+        policy: Policy {
+            tpm: pcr_policy(&golden_pcrs()),
+            ..Policy::default()
+        },
+        at: verification_time(),
+        verdict,
+    };
+    let mut unexpected_pcrs = golden_pcrs();
+    unexpected_pcrs.insert(7, [0x71; 32]);
+
+    vec![
+        dcap(
+            "sgx",
+            Evidence::SgxQuote(sgx_quote(&pki)),
+            tee_type::SGX,
+            tolerant.clone(),
+            Verdict::Accepted,
+        ),
+        dcap(
+            "sgx-unexpected-mr-enclave",
+            Evidence::SgxQuote(sgx_quote(&pki)),
+            tee_type::SGX,
+            dcap::Policy {
+                mr_enclave: vec!["ee".repeat(32)],
+                ..tolerant.clone()
+            },
+            Verdict::Rejected,
+        ),
+        Fixture {
+            collateral: Some(revoked),
+            ..dcap(
+                "sgx-revoked-pck",
+                Evidence::SgxQuote(sgx_quote(&pki)),
+                tee_type::SGX,
+                tolerant.clone(),
+                Verdict::Untrusted,
+            )
+        },
+        dcap(
+            "tdx",
+            Evidence::TdxQuote(tdx_quote(&pki)),
+            tee_type::TDX,
+            tolerant.clone(),
+            Verdict::Accepted,
+        ),
+        dcap(
+            "tdx-unexpected-mr-td",
+            Evidence::TdxQuote(tdx_quote(&pki)),
+            tee_type::TDX,
+            dcap::Policy {
+                mr_td: vec!["ee".repeat(48)],
+                ..tolerant
+            },
+            Verdict::Rejected,
+        ),
+        tpm(
+            "tpm",
+            tpm_evidence(&ak, &ak_cert, &FIXTURE_NONCE, &golden_pcrs()),
+            Verdict::Accepted,
+        ),
+        tpm(
+            "tpm-unexpected-pcr",
+            tpm_evidence(&ak, &ak_cert, &FIXTURE_NONCE, &unexpected_pcrs),
+            Verdict::Rejected,
+        ),
+        tpm(
+            "tpm-forged-signature",
+            tpm_evidence(&other_ak, &ak_cert, &FIXTURE_NONCE, &golden_pcrs()),
+            Verdict::Untrusted,
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixtures() -> eyre::Result<()> {
+        for fixture in fixtures() {
+            fixture.check()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_detects_wrong_verdicts() {
+        let mut fixture = fixtures().remove(0);
+        fixture.verdict = Verdict::Rejected;
+        assert!(fixture.check().is_err());
+    }
+}
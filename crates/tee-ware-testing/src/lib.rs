@@ -0,0 +1,41 @@
+//! Fixtures and a TPM harness for end-to-end attestation tests, of this
+//! workspace and of downstream crates.
+//!
+//! - [`fixtures`] returns SGX, TDX and TPM evidence with the collateral,
+//!   roots, attestation keys and policy to appraise it with, and the verdict
+//!   a correct verifier reaches.
+//! - [`TestTpm`] launches `swtpm`, or connects to the simulator named by
+//!   [`TPM_SIMULATOR_ENV`], and [`provision`] creates an EK and an AK in it
+//!   and certifies the AK, so tests can quote a real TPM.
+//! - The synthetic Intel-like PKI, quotes, collateral and event logs behind
+//!   the fixtures are re-exported from `dcap` for tests that need variants.
+//!
+//! ```no_run
+//! # fn main() -> eyre::Result<()> {
+//! for fixture in tee_ware_testing::fixtures() {
+//!     fixture.check()?;
+//! }
+//!
+//! if let Some(tpm) = tee_ware_testing::TestTpm::from_env()? {
+//!     let mut tpm = tee_ware_testing::provision(tpm.transport()?)?;
+//!     let evidence = tpm.quote(&[7; 32], &[0, 7])?;
+//!     tpm.verifier()
+//!         .verify(&evidence, &Default::default())?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod fixtures;
+pub use fixtures::*;
+
+mod swtpm;
+pub use swtpm::*;
+
+mod provision;
+pub use provision::*;
+
+pub use dcap::test_utils::{
+    gce_tdx_log, quote_collateral, sgx_quote, tdx_quote, tpm_attest, verification_time,
+    EventLogBuilder, TestPki,
+};
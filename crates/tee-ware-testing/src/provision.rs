@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use dcap::test_utils::{validity, NOT_AFTER, NOT_BEFORE};
+use der::Encode;
+use p256::ecdsa::{DerSignature, SigningKey, VerifyingKey};
+use tee_attest::{Evidence, TpmEvidence, Verifier};
+use tss_client::{
+    algorithms, curves, handles, ObjectAttributes, StartupType, TpmSignature, Transport, TssClient,
+};
+use tss_serde::TssSerialize;
+use x509_cert::builder::{Builder, CertificateBuilder, Profile};
+use x509_cert::name::Name;
+use x509_cert::serial_number::SerialNumber;
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::Certificate;
+
+/// Subject of the CA certifying the AKs of provisioned TPMs.
+pub const TEST_AK_CA_SUBJECT: &str = "CN=tee-ware test AK CA";
+
+/// PolicySecret(TPM_RH_ENDORSEMENT), the auth policy of the TCG default EK.
+const EK_AUTH_POLICY: [u8; 32] = [
+    0x83, 0x71, 0x97, 0x67, 0x44, 0x84, 0xB3, 0xF8, 0x1A, 0x90, 0xCC, 0x8D, 0x46, 0xA5, 0xD7, 0x24,
+    0xFD, 0x52, 0xD7, 0x6E, 0x06, 0x52, 0x0B, 0x64, 0xF2, 0xA1, 0xDA, 0x1B, 0x33, 0x14, 0x69, 0xAA,
+];
+
+/// TPMT_PUBLIC template of the TCG default ECC P-256 EK (template L-2).
+pub fn ek_template() -> Vec<u8> {
+    let attributes = ObjectAttributes::FIXED_TPM
+        | ObjectAttributes::FIXED_PARENT
+        | ObjectAttributes::SENSITIVE_DATA_ORIGIN
+        | ObjectAttributes::ADMIN_WITH_POLICY
+        | ObjectAttributes::RESTRICTED
+        | ObjectAttributes::DECRYPT;
+    let mut template = Vec::new();
+    algorithms::ECC.serialize_into(&mut template);
+    algorithms::SHA256.serialize_into(&mut template);
+    attributes.serialize_into(&mut template);
+    (EK_AUTH_POLICY.len() as u16).serialize_into(&mut template);
+    template.extend_from_slice(&EK_AUTH_POLICY);
+    algorithms::AES.serialize_into(&mut template);
+    128u16.serialize_into(&mut template);
+    algorithms::CFB.serialize_into(&mut template);
+    algorithms::NULL.serialize_into(&mut template); // scheme
+    curves::NIST_P256.serialize_into(&mut template);
+    algorithms::NULL.serialize_into(&mut template); // kdf
+    32u16.serialize_into(&mut template);
+    template.extend_from_slice(&[0; 32]); // x
+    32u16.serialize_into(&mut template);
+    template.extend_from_slice(&[0; 32]); // y
+    template
+}
+
+/// TPMT_PUBLIC template of a restricted ECDSA P-256 signing key, the AK.
+pub fn ak_template() -> Vec<u8> {
+    let attributes = ObjectAttributes::FIXED_TPM
+        | ObjectAttributes::FIXED_PARENT
+        | ObjectAttributes::SENSITIVE_DATA_ORIGIN
+        | ObjectAttributes::USER_WITH_AUTH
+        | ObjectAttributes::RESTRICTED
+        | ObjectAttributes::SIGN_ENCRYPT;
+    let mut template = Vec::new();
+    algorithms::ECC.serialize_into(&mut template);
+    algorithms::SHA256.serialize_into(&mut template);
+    attributes.serialize_into(&mut template);
+    0u16.serialize_into(&mut template); // authPolicy
+    algorithms::NULL.serialize_into(&mut template); // symmetric
+    algorithms::ECDSA.serialize_into(&mut template);
+    algorithms::SHA256.serialize_into(&mut template);
+    curves::NIST_P256.serialize_into(&mut template);
+    algorithms::NULL.serialize_into(&mut template); // kdf
+    0u16.serialize_into(&mut template); // x
+    0u16.serialize_into(&mut template); // y
+    template
+}
+
+/// A certificate of `ak` issued by the test CA `ca`, as an enrollment
+/// would issue.
+pub fn ak_certificate(ak: &VerifyingKey, ca: &SigningKey) -> Certificate {
+    CertificateBuilder::new(
+        Profile::Leaf {
+            issuer: Name::from_str(TEST_AK_CA_SUBJECT).expect("a valid name"),
+            enable_key_agreement: false,
+            enable_key_encipherment: false,
+        },
+        SerialNumber::from(1u32),
+        validity(NOT_BEFORE, NOT_AFTER),
+        Name::from_str("CN=tee-ware test AK").expect("a valid name"),
+        SubjectPublicKeyInfoOwned::from_key(*ak).expect("encoding the AK"),
+        ca,
+    )
+    .and_then(|builder| builder.build::<DerSignature>())
+    .expect("building the AK certificate")
+}
+
+/// A TPM with the TCG default EK and an AK, both primary keys of the
+/// endorsement hierarchy, and a certificate of the AK.
+pub struct ProvisionedTpm<T: Transport> {
+    pub tpm: TssClient<T>,
+    pub ek: u32,
+    pub ak: u32,
+    pub ak_public: VerifyingKey,
+    pub ak_cert: Certificate,
+}
+
+/// Start the TPM behind `transport` and provision it.
+pub fn provision<T: Transport>(transport: T) -> eyre::Result<ProvisionedTpm<T>> {
+    let mut tpm = TssClient::new(transport);
+    // A TPM that is already started refuses a second startup.
+    let _ = tpm.startup(StartupType::Clear);
+
+    let ek = tpm.create_primary(handles::ENDORSEMENT, &ek_template())?;
+    let ak = tpm.create_primary(handles::ENDORSEMENT, &ak_template())?;
+    let ak_public = ecc_public_key(&tpm.read_public(ak)?.public)?;
+    let ca = SigningKey::from_slice(&[0x0c; 32])?;
+    let ak_cert = ak_certificate(&ak_public, &ca);
+    Ok(ProvisionedTpm {
+        tpm,
+        ek,
+        ak,
+        ak_public,
+        ak_cert,
+    })
+}
+
+impl<T: Transport> ProvisionedTpm<T> {
+    /// Quote the SHA-256 bank of `pcrs` with the AK, answering `nonce`.
+    pub fn quote(&mut self, nonce: &[u8], pcrs: &[u32]) -> eyre::Result<Evidence> {
+        let quote = self.tpm.quote(self.ak, nonce, pcrs)?;
+        let TpmSignature::Ecdsa { r, s, .. } = quote.signature else {
+            eyre::bail!("the AK did not return an ECDSA signature");
+        };
+        let mut signature = [0u8; 64];
+        signature[32 - r.len()..32].copy_from_slice(&r);
+        signature[64 - s.len()..].copy_from_slice(&s);
+
+        let mut sorted = pcrs.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let values = self.tpm.read_pcrs_sha256(&sorted)?;
+        Ok(Evidence::TpmQuote(TpmEvidence {
+            attest: quote.attest,
+            signature,
+            ak_cert: self.ak_cert.to_der()?,
+            pcrs: sorted.into_iter().zip(values).collect::<BTreeMap<_, _>>(),
+        }))
+    }
+
+    /// A verifier trusting the AK.
+    pub fn verifier(&self) -> Verifier {
+        Verifier::new().with_tpm_ak(self.ak_cert.clone())
+    }
+}
+
+/// The public key of a TPMT_PUBLIC made from [`ak_template`], whose
+/// unique point follows the template fields.
+fn ecc_public_key(public: &[u8]) -> eyre::Result<VerifyingKey> {
+    let mut unique = public
+        .get(ak_template().len() - 4..)
+        .ok_or_else(|| eyre::eyre!("truncated AK public area"))?;
+    let mut coordinate = || -> eyre::Result<[u8; 32]> {
+        let size = u16::from_be_bytes(
+            unique
+                .get(..2)
+                .ok_or_else(|| eyre::eyre!("truncated AK public area"))?
+                .try_into()?,
+        ) as usize;
+        let bytes = unique
+            .get(2..2 + size)
+            .filter(|bytes| bytes.len() <= 32)
+            .ok_or_else(|| eyre::eyre!("invalid AK public point"))?;
+        let mut padded = [0u8; 32];
+        padded[32 - size..].copy_from_slice(bytes);
+        unique = &unique[2 + size..];
+        Ok(padded)
+    };
+    let (x, y) = (coordinate()?, coordinate()?);
+    Ok(VerifyingKey::from_sec1_bytes(
+        &[&[0x04][..], &x, &y].concat(),
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TestTpm;
+
+    #[test]
+    fn test_ecc_public_key() -> eyre::Result<()> {
+        let key = *SigningKey::from_slice(&[0x0a; 32])?.verifying_key();
+        let point = key.to_encoded_point(false);
+        let mut public = ak_template();
+        public.truncate(public.len() - 4);
+        for coordinate in [point.x().unwrap(), point.y().unwrap()] {
+            public.extend_from_slice(&32u16.to_be_bytes());
+            public.extend_from_slice(coordinate);
+        }
+        assert_eq!(ecc_public_key(&public)?, key);
+        assert!(ecc_public_key(&public[..public.len() - 1]).is_err());
+        Ok(())
+    }
+
+    /// Quotes a real TPM, if one is available.
+    #[test]
+    fn test_provision_and_quote() -> eyre::Result<()> {
+        let Some(tpm) = TestTpm::from_env()? else {
+            eprintln!("no swtpm or TPM simulator, skipping");
+            return Ok(());
+        };
+        let mut tpm = provision(tpm.transport()?)?;
+        let evidence = tpm.quote(&[7; 32], &[0, 7])?;
+        let result = tpm.verifier().verify(&evidence, &Default::default())?;
+        assert!(result.is_accepted());
+        assert_eq!(result.report_data, [7; 32]);
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use tss_client::{ResponseHeader, TcpTransport, Transport};
+use tss_serde::{TssDeserialize, TssFixedSize};
+
+/// Environment variable naming a running TPM simulator speaking the
+/// Microsoft simulator protocol, e.g. `localhost:2321`, with its platform
+/// port on `localhost:2322`.
+pub const TPM_SIMULATOR_ENV: &str = "TEE_WARE_TPM_SIMULATOR";
+
+/// How long to wait for a launched swtpm to accept connections.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A fresh software TPM 2.0: a `swtpm` process with its state in a
+/// temporary directory, killed when dropped.
+pub struct Swtpm {
+    child: Child,
+    port: u16,
+    _state: tempfile::TempDir,
+}
+
+impl Swtpm {
+    /// Whether `swtpm` is on the `PATH`.
+    pub fn is_installed() -> bool {
+        Command::new("swtpm")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    /// Launch `swtpm` on free local ports and wait until it serves.
+    pub fn launch() -> eyre::Result<Self> {
+        let state = tempfile::tempdir()?;
+        let (port, ctrl_port) = (free_port()?, free_port()?);
+        let child = Command::new("swtpm")
+            .arg("socket")
+            .arg("--tpm2")
+            .arg("--server")
+            .arg(format!("type=tcp,port={},bindaddr=127.0.0.1", port))
+            .arg("--ctrl")
+            .arg(format!("type=tcp,port={},bindaddr=127.0.0.1", ctrl_port))
+            .arg("--tpmstate")
+            .arg(format!("dir={}", state.path().display()))
+            .arg("--flags")
+            .arg("not-need-init")
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|err| eyre::eyre!("cannot launch swtpm: {}", err))?;
+        let mut swtpm = Self {
+            child,
+            port,
+            _state: state,
+        };
+
+        let deadline = Instant::now() + LAUNCH_TIMEOUT;
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            if let Some(status) = swtpm.child.try_wait()? {
+                eyre::bail!("swtpm exited with {}", status);
+            }
+            if Instant::now() > deadline {
+                eyre::bail!("swtpm did not start within {:?}", LAUNCH_TIMEOUT);
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        Ok(swtpm)
+    }
+
+    /// The port TPM commands are sent to.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Connect to the TPM. swtpm serves one connection at a time.
+    pub fn transport(&self) -> eyre::Result<SwtpmTransport> {
+        Ok(SwtpmTransport {
+            stream: TcpStream::connect(("127.0.0.1", self.port))?,
+        })
+    }
+}
+
+impl Drop for Swtpm {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A bound port, released for the process about to listen on it.
+fn free_port() -> eyre::Result<u16> {
+    Ok(TcpListener::bind(("127.0.0.1", 0))?.local_addr()?.port())
+}
+
+/// Talks to swtpm, which takes bare TPM commands over its server socket.
+pub struct SwtpmTransport {
+    stream: TcpStream,
+}
+
+impl Transport for SwtpmTransport {
+    fn send_command(&mut self, command: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
+        self.stream.write_all(command)?;
+        self.stream.flush()?;
+
+        let mut response = [0u8; ResponseHeader::SERIALIZED_SIZE];
+        self.stream.read_exact(&mut response)?;
+        let header = ResponseHeader::from_tss_bytes(&response)?;
+        let size = (header.size as usize)
+            .checked_sub(ResponseHeader::SERIALIZED_SIZE)
+            .ok_or_else(|| eyre::eyre!("invalid TPM response size {}", header.size))?;
+        let mut body = vec![0u8; size];
+        self.stream.read_exact(&mut body)?;
+        if header.response_code != 0 {
+            eyre::bail!(
+                "TPM command failed with response code {:#x}",
+                header.response_code
+            );
+        }
+        Ok((header, body))
+    }
+}
+
+/// A TPM for tests: a launched swtpm, or the simulator named by
+/// [`TPM_SIMULATOR_ENV`].
+pub enum TestTpm {
+    Swtpm(Swtpm),
+    Simulator(String),
+}
+
+impl TestTpm {
+    /// The simulator named by [`TPM_SIMULATOR_ENV`] if set, a launched
+    /// swtpm if installed, and `None` otherwise so tests can be skipped.
+    pub fn from_env() -> eyre::Result<Option<Self>> {
+        if let Ok(address) = std::env::var(TPM_SIMULATOR_ENV) {
+            return Ok(Some(TestTpm::Simulator(address)));
+        }
+        if Swtpm::is_installed() {
+            return Ok(Some(TestTpm::Swtpm(Swtpm::launch()?)));
+        }
+        Ok(None)
+    }
+
+    /// Connect to the TPM. The simulator is power cycled first, so it
+    /// needs a TPM2_Startup, which [`provision`](crate::provision) sends.
+    pub fn transport(&self) -> eyre::Result<Box<dyn Transport + Send>> {
+        Ok(match self {
+            TestTpm::Swtpm(swtpm) => Box::new(swtpm.transport()?),
+            TestTpm::Simulator(address) => Box::new(TcpTransport::connect(address.as_str())?),
+        })
+    }
+}
@@ -1,5 +1,13 @@
-use crate::primitives::{self, CapabilitiesResponse, Empty, RawResponse, ResponseHeader};
-use tss_serde::{TssDeserialize, TssSerialize};
+use crate::primitives::{
+    self, AuthCommand, CapabilitiesResponse, Command, Empty, NvPublic, RawResponse, ResponseHeader,
+    SessionsBody,
+};
+use tee_observe::{observe, Operation};
+use tss_serde::{Tpm2b, TssDeserialize, TssReader, TssSerialize};
+
+/// Largest chunk read from or written to an NV index in one command; the
+/// minimum MAX_NV_BUFFER_SIZE TPMs support in practice.
+pub const NV_CHUNK_SIZE: usize = 1024;
 
 /// A trait for abstracting the underlying transport mechanism used to communicate with a TPM.
 ///
@@ -9,6 +17,12 @@ pub trait Transport {
     fn send_command(&mut self, command: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)>;
 }
 
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send_command(&mut self, command: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
+        (**self).send_command(command)
+    }
+}
+
 /// A TSS (TPM Software Stack) client for communicating with Trusted Platform Modules (TPMs).
 ///
 /// The `TssClient` provides a high-level interface for TPM operations, handling command
@@ -25,7 +39,7 @@ pub trait Transport {
 /// use your_crate::{TssClient, TcpTransport};
 ///
 /// let mut client = TssClient::new(TcpTransport::default());
-/// client.startup(primitives::startup_type::CLEAR)?;
+/// client.startup(primitives::StartupType::Clear)?;
 /// ```
 pub struct TssClient<T> {
     transport: T,
@@ -39,7 +53,7 @@ where
         Self { transport }
     }
 
-    pub fn startup(&mut self, startup_type: u16) -> eyre::Result<()> {
+    pub fn startup(&mut self, startup_type: primitives::StartupType) -> eyre::Result<()> {
         let command = primitives::StartupCommand { startup_type };
         let _ = self.run_command::<Empty>(primitives::commands::STARTUP, command)?;
         Ok(())
@@ -67,37 +81,603 @@ where
         Ok(result)
     }
 
-    pub fn run_command<TS: TssDeserialize>(
+    pub fn nv_read_public(&mut self, nv_index: u32) -> eyre::Result<NvPublic> {
+        let result: primitives::NvReadPublicResponse =
+            self.run_command(primitives::commands::NV_READ_PUBLIC, nv_index)?;
+        Ok(result.nv_public)
+    }
+
+    /// Read the whole NV index `nv_index`, authorized by its empty password.
+    pub fn nv_read(&mut self, nv_index: u32) -> eyre::Result<Vec<u8>> {
+        let size = self.nv_read_public(nv_index)?.data_size as usize;
+        let mut data = Vec::with_capacity(size);
+        while data.len() < size {
+            let chunk = (size - data.len()).min(NV_CHUNK_SIZE);
+            let result: Tpm2b = self.run_command_with_password(
+                primitives::commands::NV_READ,
+                &[nv_index, nv_index],
+                primitives::NvReadCommand {
+                    size: chunk as u16,
+                    offset: data.len() as u16,
+                },
+            )?;
+            if result.0.len() != chunk {
+                eyre::bail!(
+                    "TPM returned {} bytes of NV data, expected {}",
+                    result.0.len(),
+                    chunk
+                );
+            }
+            data.extend_from_slice(&result.0);
+        }
+        Ok(data)
+    }
+
+    /// Write `data` at the start of the NV index `nv_index`, authorized by its
+    /// empty password. NV offsets are u16, so `data` must fit in 64 KiB.
+    pub fn nv_write(&mut self, nv_index: u32, data: &[u8]) -> eyre::Result<()> {
+        if data.len() > u16::MAX as usize {
+            eyre::bail!(
+                "{} bytes of NV data do not fit the u16 offsets of TPM2_NV_Write",
+                data.len()
+            );
+        }
+        for (i, chunk) in data.chunks(NV_CHUNK_SIZE).enumerate() {
+            let _: Empty = self.run_command_with_password(
+                primitives::commands::NV_WRITE,
+                &[nv_index, nv_index],
+                primitives::NvWriteCommand {
+                    data: chunk.to_vec(),
+                    offset: (i * NV_CHUNK_SIZE) as u16,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Define an NV index with an empty password, authorized by the empty
+    /// owner password.
+    pub fn nv_define_space(&mut self, public: NvPublic) -> eyre::Result<()> {
+        let _: Empty = self.run_command_with_password(
+            primitives::commands::NV_DEFINE_SPACE,
+            &[primitives::handles::OWNER],
+            primitives::NvDefineSpaceCommand {
+                auth: Vec::new(),
+                public,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Create a primary object in `hierarchy` from the TPMT_PUBLIC
+    /// `template`, and return its handle.
+    pub fn create_primary(&mut self, hierarchy: u32, template: &[u8]) -> eyre::Result<u32> {
+        let command = primitives::CreatePrimaryCommand {
+            template: template.to_vec(),
+        };
+        let response =
+            self.send_with_password(primitives::commands::CREATE_PRIMARY, &[hierarchy], command)?;
+        let mut reader = TssReader::new(&response);
+        let handle = reader.read_u32()?;
+        Ok(handle)
+    }
+
+    /// Quote the SHA-256 bank of `pcrs` with the key `sign_handle`, with
+    /// `qualifying_data` as the caller's nonce. Returns the TPMS_ATTEST and
+    /// its signature.
+    pub fn quote(
+        &mut self,
+        sign_handle: u32,
+        qualifying_data: &[u8],
+        pcrs: &[u32],
+    ) -> eyre::Result<primitives::QuoteResponse> {
+        self.run_command_with_password(
+            primitives::commands::QUOTE,
+            &[sign_handle],
+            primitives::QuoteCommand {
+                qualifying_data: qualifying_data.to_vec(),
+                pcr_selection: primitives::PcrSelection::sha256(pcrs),
+            },
+        )
+    }
+
+    /// Sign `digest` with the unrestricted key `key_handle`, using the
+    /// signing scheme of the key.
+    pub fn sign(
+        &mut self,
+        key_handle: u32,
+        digest: &[u8],
+    ) -> eyre::Result<primitives::TpmSignature> {
+        self.run_command_with_password(
+            primitives::commands::SIGN,
+            &[key_handle],
+            primitives::SignCommand {
+                digest: digest.to_vec(),
+            },
+        )
+    }
+
+    /// Create an object under the storage key `parent` from the TPMT_PUBLIC
+    /// `template`, sealing `data` in it if not empty.
+    pub fn create(
+        &mut self,
+        parent: u32,
+        template: &[u8],
+        data: &[u8],
+    ) -> eyre::Result<primitives::CreateResponse> {
+        self.run_command_with_password(
+            primitives::commands::CREATE,
+            &[parent],
+            primitives::CreateCommand {
+                data: data.to_vec(),
+                template: template.to_vec(),
+            },
+        )
+    }
+
+    /// Load an object created under `parent`, and return its handle.
+    pub fn load(&mut self, parent: u32, private: &[u8], public: &[u8]) -> eyre::Result<u32> {
+        let command = primitives::LoadCommand {
+            private: private.to_vec(),
+            public: public.to_vec(),
+        };
+        let response = self.send_with_password(primitives::commands::LOAD, &[parent], command)?;
+        let mut reader = TssReader::new(&response);
+        let handle = reader.read_u32()?;
+        Ok(handle)
+    }
+
+    /// Start an unbound and unsalted session of `session_type` and return
+    /// its handle.
+    pub fn start_auth_session(
+        &mut self,
+        session_type: primitives::SessionType,
+        nonce_caller: &[u8],
+    ) -> eyre::Result<u32> {
+        let result: primitives::StartAuthSessionResponse = self.run_command(
+            primitives::commands::START_AUTH_SESSION,
+            primitives::StartAuthSessionCommand {
+                nonce_caller: nonce_caller.to_vec(),
+                session_type,
+            },
+        )?;
+        Ok(result.session_handle)
+    }
+
+    /// Extend the policy of `session` with the current values of the SHA-256
+    /// bank of `pcrs`.
+    pub fn policy_pcr(&mut self, session: u32, pcrs: &[u32]) -> eyre::Result<()> {
+        let _: Empty = self.run_command(
+            primitives::commands::POLICY_PCR,
+            primitives::PolicyPcrCommand {
+                session_handle: session,
+                pcr_selection: primitives::PcrSelection::sha256(pcrs),
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Unseal the data of the sealed object `item`, authorized by the policy
+    /// session `session`, which the TPM flushes afterwards.
+    pub fn unseal(&mut self, item: u32, session: u32) -> eyre::Result<Vec<u8>> {
+        let response =
+            self.send_with_sessions(primitives::commands::UNSEAL, &[item], &[session], Empty)?;
+        let data: Tpm2b = response_parameters(&response)?;
+        Ok(data.0)
+    }
+
+    /// Certify with the key `sign_handle` that the TPM holds the object
+    /// `object`, with `qualifying_data` as the caller's nonce. Both are
+    /// authorized by their empty passwords.
+    pub fn certify(
+        &mut self,
+        object: u32,
+        sign_handle: u32,
+        qualifying_data: &[u8],
+    ) -> eyre::Result<primitives::QuoteResponse> {
+        let command = primitives::CertifyCommand {
+            qualifying_data: qualifying_data.to_vec(),
+        };
+        let response = self.send_with_sessions(
+            primitives::commands::CERTIFY,
+            &[object, sign_handle],
+            &[primitives::handles::PASSWORD_SESSION; 2],
+            command,
+        )?;
+        response_parameters(&response)
+    }
+
+    pub fn read_public(&mut self, handle: u32) -> eyre::Result<primitives::ReadPublicResponse> {
+        self.run_command(primitives::commands::READ_PUBLIC, handle)
+    }
+
+    pub fn flush_context(&mut self, handle: u32) -> eyre::Result<()> {
+        let _: Empty = self.run_command(primitives::commands::FLUSH_CONTEXT, handle)?;
+        Ok(())
+    }
+
+    /// Read the SHA-256 bank of `pcrs`, in the order given.
+    pub fn read_pcrs_sha256(&mut self, pcrs: &[u32]) -> eyre::Result<Vec<[u8; 32]>> {
+        let mut values = std::collections::BTreeMap::new();
+        let mut pending: Vec<u32> = pcrs.to_vec();
+        while !pending.is_empty() {
+            let result: primitives::PcrReadResponse = self.run_command(
+                primitives::commands::READ_PCR,
+                primitives::PcrSelection::sha256(&pending),
+            )?;
+            let returned = result.pcr_selection.pcrs;
+            if returned.is_empty() || returned.len() != result.digests.len() {
+                eyre::bail!("TPM returned an inconsistent PCR selection");
+            }
+            for (pcr, digest) in returned.iter().zip(result.digests) {
+                let digest: [u8; 32] = digest
+                    .try_into()
+                    .map_err(|_| eyre::eyre!("PCR {} is not a SHA-256 digest", pcr))?;
+                values.insert(*pcr, digest);
+            }
+            pending.retain(|pcr| !values.contains_key(pcr));
+        }
+        Ok(pcrs.iter().map(|pcr| values[pcr]).collect())
+    }
+
+    /// Run a command whose first handle is authorized by an empty password
+    /// session, and which returns no handles.
+    pub fn run_command_with_password<TS: TssDeserialize>(
         &mut self,
         command_code: u32,
+        handles: &[u32],
         command_body: impl TssSerialize,
     ) -> eyre::Result<TS> {
-        let body = command_body.to_tss_bytes();
+        let response = self.send_with_password(command_code, handles, command_body)?;
+        response_parameters(&response)
+    }
 
-        let header = primitives::CommandHeader {
-            tag: primitives::tags::NO_SESSIONS,
-            command_code: command_code,
-            length: 10 + body.len() as u32,
+    /// Send a command with a password session for its first handle, and
+    /// return the response after the header.
+    fn send_with_password(
+        &mut self,
+        command_code: u32,
+        handles: &[u32],
+        parameters: impl TssSerialize,
+    ) -> eyre::Result<Vec<u8>> {
+        self.send_with_sessions(
+            command_code,
+            handles,
+            &[primitives::handles::PASSWORD_SESSION],
+            parameters,
+        )
+    }
+
+    /// Send a command with `sessions` for its first handles, each either the
+    /// password session or a policy session needing no HMAC, and return the
+    /// response after the header.
+    fn send_with_sessions(
+        &mut self,
+        command_code: u32,
+        handles: &[u32],
+        sessions: &[u32],
+        parameters: impl TssSerialize,
+    ) -> eyre::Result<Vec<u8>> {
+        let command = Command {
+            tag: primitives::tags::SESSIONS,
+            length: 0,
+            command_code,
+            body: SessionsBody {
+                handles: handles.to_vec(),
+                authorization_size: 0,
+                authorizations: sessions.iter().copied().map(AuthCommand::empty).collect(),
+                parameters,
+            },
         };
-        let header_bytes = header.to_tss_bytes();
-        let input = [header_bytes, body].concat();
+        let (_header, body_response) = self.send(command_code, &command.to_tss_bytes())?;
+        Ok(body_response)
+    }
 
-        let (_header, body_response) = self.transport.send_command(&input)?;
+    pub fn run_command<TS: TssDeserialize>(
+        &mut self,
+        command_code: u32,
+        command_body: impl TssSerialize,
+    ) -> eyre::Result<TS> {
+        let command = Command {
+            tag: primitives::tags::NO_SESSIONS,
+            length: 0,
+            command_code,
+            body: command_body,
+        };
+        let (_header, body_response) = self.send(command_code, &command.to_tss_bytes())?;
 
-        let result = TS::from_tss_bytes(&body_response)?;
+        let result = TS::from_tss_bytes_exact(&body_response)?;
         Ok(result)
     }
+
+    fn send(
+        &mut self,
+        command_code: u32,
+        command: &[u8],
+    ) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
+        observe(
+            Operation::TpmCommand,
+            primitives::commands::name(command_code),
+            || self.transport.send_command(command),
+        )
+    }
+}
+
+/// The parameters of a response with sessions, after its handles.
+fn response_parameters<TS: TssDeserialize>(response: &[u8]) -> eyre::Result<TS> {
+    let mut reader = TssReader::new(response);
+    let parameter_size = reader.read_u32()? as usize;
+    let parameters = reader.read_bytes(parameter_size)?;
+    Ok(TS::from_tss_bytes_exact(&parameters)?)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::tcp_transport::TcpTransport;
+    use tss_serde::TssError;
+
+    /// Serves a single NV index of `data`, recording the commands it gets.
+    struct FakeNvTransport {
+        data: Vec<u8>,
+        commands: Vec<Vec<u8>>,
+    }
+
+    impl Transport for FakeNvTransport {
+        fn send_command(&mut self, command: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
+            self.commands.push(command.to_vec());
+            let command_code = u32::from_be_bytes(command[6..10].try_into()?);
+            let body = match command_code {
+                primitives::commands::NV_READ_PUBLIC => {
+                    let public = NvPublic {
+                        nv_index: 0x01400001,
+                        name_alg: primitives::algorithms::SHA256,
+                        attributes: primitives::nv_attributes::AUTHREAD,
+                        auth_policy: Vec::new(),
+                        data_size: self.data.len() as u16,
+                    };
+                    let mut body = public.to_tss_bytes();
+                    body.extend_from_slice(&Tpm2b(vec![0; 34]).to_tss_bytes());
+                    body
+                }
+                primitives::commands::NV_READ => {
+                    // Handles and a 9 byte password session precede the
+                    // parameters.
+                    let parameters = &command[10 + 8 + 4 + 9..];
+                    let size = u16::from_be_bytes(parameters[..2].try_into()?) as usize;
+                    let offset = u16::from_be_bytes(parameters[2..4].try_into()?) as usize;
+                    let data = Tpm2b(self.data[offset..offset + size].to_vec()).to_tss_bytes();
+                    let mut body = (data.len() as u32).to_tss_bytes();
+                    body.extend_from_slice(&data);
+                    body.extend_from_slice(&[0, 0, 1, 0, 0]); // TPMS_AUTH_RESPONSE
+                    body
+                }
+                _ => eyre::bail!("unexpected command {:#x}", command_code),
+            };
+            let header = ResponseHeader {
+                tag: primitives::tags::NO_SESSIONS,
+                size: 10 + body.len() as u32,
+                response_code: 0,
+            };
+            Ok((header, body))
+        }
+    }
+
+    /// Returns PCR `i` as `[i; 32]`, at most eight PCRs per read like real
+    /// TPMs.
+    struct FakePcrTransport;
+
+    impl Transport for FakePcrTransport {
+        fn send_command(&mut self, command: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
+            let selection = primitives::PcrSelection::from_tss_bytes(&command[10..])?;
+            let returned = primitives::PcrSelection {
+                hash: selection.hash,
+                pcrs: selection.pcrs.into_iter().take(8).collect(),
+            };
+            let mut body = 7u32.to_tss_bytes();
+            body.extend_from_slice(&returned.to_tss_bytes());
+            body.extend_from_slice(&(returned.pcrs.len() as u32).to_tss_bytes());
+            for pcr in &returned.pcrs {
+                body.extend_from_slice(&Tpm2b(vec![*pcr as u8; 32]).to_tss_bytes());
+            }
+            let header = ResponseHeader {
+                tag: primitives::tags::NO_SESSIONS,
+                size: 10 + body.len() as u32,
+                response_code: 0,
+            };
+            Ok((header, body))
+        }
+    }
+
+    /// Answers TPM2_Sign with an ECDSA signature of `r = s = digest`.
+    struct FakeSignTransport;
+
+    impl Transport for FakeSignTransport {
+        fn send_command(&mut self, command: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
+            let command_code = u32::from_be_bytes(command[6..10].try_into()?);
+            eyre::ensure!(command_code == primitives::commands::SIGN);
+            // One handle and a 9 byte password session precede the digest.
+            let mut reader = TssReader::new(&command[10 + 4 + 4 + 9..]);
+            let digest = reader.read_tpm2b()?;
+            let scheme = reader.read_u16()?;
+            let ticket_tag = reader.read_u16()?;
+            eyre::ensure!(scheme == primitives::algorithms::NULL);
+            eyre::ensure!(ticket_tag == primitives::tags::HASH_CHECK);
+
+            let mut parameters = primitives::algorithms::ECDSA.to_tss_bytes();
+            parameters.extend_from_slice(&primitives::algorithms::SHA256.to_tss_bytes());
+            parameters.extend_from_slice(&Tpm2b(digest.clone()).to_tss_bytes());
+            parameters.extend_from_slice(&Tpm2b(digest).to_tss_bytes());
+            let mut body = (parameters.len() as u32).to_tss_bytes();
+            body.extend_from_slice(&parameters);
+            body.extend_from_slice(&[0, 0, 1, 0, 0]); // TPMS_AUTH_RESPONSE
+            let header = ResponseHeader {
+                tag: primitives::tags::SESSIONS,
+                size: 10 + body.len() as u32,
+                response_code: 0,
+            };
+            Ok((header, body))
+        }
+    }
+
+    /// Answers every command with `body`.
+    struct FixedTransport(Vec<u8>);
+
+    impl Transport for FixedTransport {
+        fn send_command(&mut self, _: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
+            let header = ResponseHeader {
+                tag: primitives::tags::NO_SESSIONS,
+                size: 10 + self.0.len() as u32,
+                response_code: 0,
+            };
+            Ok((header, self.0.clone()))
+        }
+    }
+
+    #[test]
+    fn test_rejects_trailing_bytes() {
+        // TPM2_ReadPublic answers three TPM2Bs, here all empty.
+        let mut tss_client = TssClient::new(FixedTransport(vec![0; 6]));
+        assert!(tss_client.read_public(0x81000001).is_ok());
+
+        let mut tss_client = TssClient::new(FixedTransport(vec![0; 8]));
+        let error = tss_client.read_public(0x81000001).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<TssError>(),
+            Some(&TssError::TrailingBytes { remaining: 2 })
+        );
+    }
+
+    #[test]
+    fn test_sign() -> eyre::Result<()> {
+        let mut tss_client = TssClient::new(FakeSignTransport);
+        let signature = tss_client.sign(0x81000001, &[7; 32])?;
+        assert_eq!(
+            signature,
+            primitives::TpmSignature::Ecdsa {
+                hash: primitives::algorithms::SHA256,
+                r: vec![7; 32],
+                s: vec![7; 32],
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_pcrs_in_batches() -> eyre::Result<()> {
+        let mut tss_client = TssClient::new(FakePcrTransport);
+        let pcrs = [15, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 14];
+        let values = tss_client.read_pcrs_sha256(&pcrs)?;
+        let expected: Vec<[u8; 32]> = pcrs.iter().map(|pcr| [*pcr as u8; 32]).collect();
+        assert_eq!(values, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nv_read_in_chunks() -> eyre::Result<()> {
+        let data: Vec<u8> = (0..2500).map(|i| i as u8).collect();
+        let mut tss_client = TssClient::new(FakeNvTransport {
+            data: data.clone(),
+            commands: Vec::new(),
+        });
+        assert_eq!(tss_client.nv_read(0x01400001)?, data);
+
+        let commands = &tss_client.transport.commands;
+        assert_eq!(commands.len(), 4);
+        assert_eq!(&commands[1][..2], &primitives::tags::SESSIONS.to_be_bytes());
+        assert_eq!(
+            &commands[1][10..18],
+            &[0x01, 0x40, 0x00, 0x01, 0x01, 0x40, 0x00, 0x01]
+        );
+        assert_eq!(&commands[1][18..22], &9u32.to_be_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sessions_command_framing() {
+        let command = Command {
+            tag: primitives::tags::SESSIONS,
+            length: 0,
+            command_code: primitives::commands::UNSEAL,
+            body: SessionsBody {
+                handles: vec![0x80000001],
+                authorization_size: 0,
+                authorizations: vec![AuthCommand::empty(primitives::handles::PASSWORD_SESSION)],
+                parameters: 0xAABBu16,
+            },
+        };
+        let mut expected = primitives::tags::SESSIONS.to_be_bytes().to_vec();
+        expected.extend_from_slice(&29u32.to_be_bytes());
+        expected.extend_from_slice(&primitives::commands::UNSEAL.to_be_bytes());
+        expected.extend_from_slice(&0x80000001u32.to_be_bytes());
+        expected.extend_from_slice(&9u32.to_be_bytes());
+        expected.extend_from_slice(&primitives::handles::PASSWORD_SESSION.to_be_bytes());
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0xAA, 0xBB]);
+        assert_eq!(command.to_tss_bytes(), expected);
+    }
+
+    #[test]
+    fn test_nv_public_within_its_size() {
+        let public = NvPublic {
+            nv_index: 0x01400001,
+            name_alg: primitives::algorithms::SHA256,
+            attributes: primitives::nv_attributes::AUTHREAD,
+            auth_policy: vec![0xAA; 4],
+            data_size: 32,
+        };
+        let bytes = public.to_tss_bytes();
+        assert_eq!(NvPublic::from_tss_bytes_exact(&bytes), Ok(public));
+
+        // A size short of the fields fails within it, before reading the
+        // bytes after it.
+        let mut short = bytes.clone();
+        short[1] -= 2;
+        assert_eq!(
+            NvPublic::from_tss_bytes(&short),
+            Err(TssError::InsufficientData)
+        );
+        let mut long = bytes;
+        long[1] += 1;
+        long.push(0);
+        assert_eq!(
+            NvPublic::from_tss_bytes(&long),
+            Err(TssError::InvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_response_parameters_exact() -> eyre::Result<()> {
+        let mut response = 6u32.to_tss_bytes();
+        response.extend_from_slice(&Tpm2b(vec![1, 2]).to_tss_bytes());
+        response.extend_from_slice(&[0xFF, 0xFF]);
+        assert_eq!(
+            response_parameters::<Tpm2b>(&response)
+                .unwrap_err()
+                .downcast_ref(),
+            Some(&TssError::TrailingBytes { remaining: 2 })
+        );
+        response.truncate(8);
+        response[3] = 4;
+        assert_eq!(response_parameters::<Tpm2b>(&response)?, Tpm2b(vec![1, 2]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_nv_write_rejects_oversized_data() {
+        let mut tss_client = TssClient::new(FakeNvTransport {
+            data: Vec::new(),
+            commands: Vec::new(),
+        });
+        assert!(tss_client
+            .nv_write(0x01400001, &vec![0; u16::MAX as usize + 1])
+            .is_err());
+        assert!(tss_client.transport.commands.is_empty());
+    }
 
     #[test]
     fn simple_test() -> eyre::Result<()> {
         let mut tss_client = TssClient::new(TcpTransport::default());
-        let _ = tss_client.startup(primitives::startup_type::CLEAR)?;
+        tss_client.startup(primitives::StartupType::Clear)?;
 
         let result = tss_client.get_capabilities(
             primitives::capabilities::TPM_PROPERTIES,
@@ -0,0 +1,65 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use tee_observe::{observe, Operation};
+use tss_serde::{TssDeserialize, TssFixedSize};
+
+use crate::primitives::ResponseHeader;
+use crate::Transport;
+
+/// The kernel TPM resource manager.
+pub const TPM_RESOURCE_MANAGER_PATH: &str = "/dev/tpmrm0";
+
+/// Largest response a TPM returns (TPM_PT_MAX_RESPONSE_SIZE in practice).
+const MAX_RESPONSE_SIZE: usize = 4096;
+
+/// Talks to a TPM through a character device, such as `/dev/tpmrm0`.
+pub struct DeviceTransport {
+    device: File,
+}
+
+impl DeviceTransport {
+    pub fn open(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let device = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|err| eyre::eyre!("cannot open {}: {}", path.display(), err))?;
+        Ok(Self { device })
+    }
+
+    /// Open the kernel resource manager, [`TPM_RESOURCE_MANAGER_PATH`].
+    pub fn open_default() -> eyre::Result<Self> {
+        Self::open(TPM_RESOURCE_MANAGER_PATH)
+    }
+
+    fn exchange(&mut self, command: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
+        // The driver takes a whole command per write and returns the whole
+        // response on the next read.
+        self.device.write_all(command)?;
+
+        let mut response = vec![0u8; MAX_RESPONSE_SIZE];
+        let size = self.device.read(&mut response)?;
+        response.truncate(size);
+
+        let header = ResponseHeader::from_tss_bytes(&response)?;
+        if header.response_code != 0 {
+            return Err(eyre::eyre!(
+                "TPM command failed with response code {:#x}",
+                header.response_code
+            ));
+        }
+        if header.size as usize != response.len() {
+            return Err(eyre::eyre!("truncated TPM response"));
+        }
+        Ok((header, response[ResponseHeader::SERIALIZED_SIZE..].to_vec()))
+    }
+}
+
+impl Transport for DeviceTransport {
+    fn send_command(&mut self, command: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
+        observe(Operation::TpmTransport, "device", || self.exchange(command))
+    }
+}
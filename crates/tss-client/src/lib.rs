@@ -4,5 +4,8 @@ pub use primitives::*;
 mod tcp_transport;
 pub use tcp_transport::*;
 
+mod device_transport;
+pub use device_transport::*;
+
 mod client;
 pub use client::*;
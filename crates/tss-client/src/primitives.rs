@@ -1,9 +1,115 @@
-use tss_serde::{TssDeserialize, TssError, TssSerialize};
+use tss_serde::{
+    Tpm2b, TssBitflags, TssDeserialize, TssError, TssFixedSize, TssSerialize, TssWriter,
+};
 
 pub mod commands {
+    pub const NV_DEFINE_SPACE: u32 = 0x0000012A;
+    pub const CERTIFY: u32 = 0x00000148;
+    pub const CREATE_PRIMARY: u32 = 0x00000131;
+    pub const CREATE: u32 = 0x00000153;
+    pub const NV_WRITE: u32 = 0x00000137;
     pub const STARTUP: u32 = 0x00000144;
+    pub const NV_READ: u32 = 0x0000014E;
+    pub const LOAD: u32 = 0x00000157;
+    pub const QUOTE: u32 = 0x00000158;
+    pub const SIGN: u32 = 0x0000015D;
+    pub const UNSEAL: u32 = 0x0000015E;
+    pub const FLUSH_CONTEXT: u32 = 0x00000165;
+    pub const NV_READ_PUBLIC: u32 = 0x00000169;
+    pub const READ_PUBLIC: u32 = 0x00000173;
+    pub const START_AUTH_SESSION: u32 = 0x00000176;
     pub const GET_CAPABILITY: u32 = 0x0000017A;
     pub const READ_PCR: u32 = 0x0000017E;
+    pub const POLICY_PCR: u32 = 0x0000017F;
+
+    /// The name of `command_code`, `unknown` for commands not listed here.
+    pub fn name(command_code: u32) -> &'static str {
+        match command_code {
+            NV_DEFINE_SPACE => "NV_DefineSpace",
+            CERTIFY => "Certify",
+            CREATE_PRIMARY => "CreatePrimary",
+            CREATE => "Create",
+            NV_WRITE => "NV_Write",
+            STARTUP => "Startup",
+            NV_READ => "NV_Read",
+            LOAD => "Load",
+            QUOTE => "Quote",
+            SIGN => "Sign",
+            UNSEAL => "Unseal",
+            FLUSH_CONTEXT => "FlushContext",
+            NV_READ_PUBLIC => "NV_ReadPublic",
+            READ_PUBLIC => "ReadPublic",
+            START_AUTH_SESSION => "StartAuthSession",
+            GET_CAPABILITY => "GetCapability",
+            READ_PCR => "PCR_Read",
+            POLICY_PCR => "PolicyPCR",
+            _ => "unknown",
+        }
+    }
+}
+
+pub mod handles {
+    pub const OWNER: u32 = 0x40000001;
+    pub const NULL: u32 = 0x40000007;
+    pub const ENDORSEMENT: u32 = 0x4000000B;
+    /// TPM_RS_PW, the password authorization session.
+    pub const PASSWORD_SESSION: u32 = 0x40000009;
+}
+
+pub mod algorithms {
+    pub const AES: u16 = 0x0006;
+    pub const KEYEDHASH: u16 = 0x0008;
+    pub const SHA256: u16 = 0x000B;
+    pub const NULL: u16 = 0x0010;
+    pub const RSASSA: u16 = 0x0014;
+    pub const ECDSA: u16 = 0x0018;
+    pub const ECC: u16 = 0x0023;
+    pub const CFB: u16 = 0x0043;
+}
+
+/// TPM_ECC_CURVE identifiers.
+pub mod curves {
+    pub const NIST_P256: u16 = 0x0003;
+}
+
+bitflags::bitflags! {
+    /// TPMA_OBJECT attributes. Decoding rejects the reserved bits.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, TssBitflags)]
+    pub struct ObjectAttributes: u32 {
+        const FIXED_TPM = 1 << 1;
+        const ST_CLEAR = 1 << 2;
+        const FIXED_PARENT = 1 << 4;
+        const SENSITIVE_DATA_ORIGIN = 1 << 5;
+        const USER_WITH_AUTH = 1 << 6;
+        const ADMIN_WITH_POLICY = 1 << 7;
+        const FIRMWARE_LIMITED = 1 << 8;
+        const SVN_LIMITED = 1 << 9;
+        const NO_DA = 1 << 10;
+        const ENCRYPTED_DUPLICATION = 1 << 11;
+        const RESTRICTED = 1 << 16;
+        const DECRYPT = 1 << 17;
+        const SIGN_ENCRYPT = 1 << 18;
+        const X509_SIGN = 1 << 19;
+    }
+}
+
+/// TPM_SE session types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TssSerialize, TssDeserialize, TssFixedSize)]
+#[repr(u8)]
+pub enum SessionType {
+    Hmac = 0x00,
+    Policy = 0x01,
+    Trial = 0x03,
+}
+
+/// TPMA_NV attributes.
+pub mod nv_attributes {
+    pub const OWNERWRITE: u32 = 1 << 1;
+    pub const AUTHWRITE: u32 = 1 << 2;
+    pub const OWNERREAD: u32 = 1 << 17;
+    pub const AUTHREAD: u32 = 1 << 18;
+    pub const NO_DA: u32 = 1 << 25;
+    pub const WRITTEN: u32 = 1 << 29;
 }
 
 pub mod capabilities {
@@ -30,21 +136,76 @@ pub mod tags {
     pub const HASH_CHECK: u16 = 0x8024;
 }
 
-pub mod startup_type {
-    pub const CLEAR: u16 = 0;
-    pub const STATE: u16 = 1;
+/// TPM_SU, what TPM2_Startup restores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TssSerialize, TssDeserialize, TssFixedSize)]
+#[repr(u16)]
+pub enum StartupType {
+    Clear = 0,
+    State = 1,
 }
 
 #[derive(TssSerialize)]
 pub struct StartupCommand {
-    pub startup_type: u16,
+    pub startup_type: StartupType,
 }
 
+/// A command: its header, then its body.
 #[derive(TssSerialize)]
-pub struct CommandHeader {
+pub struct Command<B> {
     pub tag: u16,
+    /// Computed when serializing.
+    #[tss(size_of = "self")]
     pub length: u32,
     pub command_code: u32,
+    pub body: B,
+}
+
+/// The body of a command with sessions: its handles, the authorization
+/// area for the first of them, then its parameters.
+#[derive(TssSerialize)]
+pub struct SessionsBody<P> {
+    #[tss(with = "unprefixed")]
+    pub handles: Vec<u32>,
+    /// Computed when serializing.
+    #[tss(size_of = "authorizations")]
+    pub authorization_size: u32,
+    #[tss(with = "unprefixed")]
+    pub authorizations: Vec<AuthCommand>,
+    pub parameters: P,
+}
+
+/// A TPMS_AUTH_COMMAND.
+#[derive(TssSerialize)]
+pub struct AuthCommand {
+    pub session_handle: u32,
+    pub nonce: Tpm2b,
+    pub session_attributes: u8,
+    pub hmac: Tpm2b,
+}
+
+impl AuthCommand {
+    /// The authorization of the password session with an empty password, or
+    /// of a policy session needing no HMAC: an empty nonce, no attributes
+    /// and an empty password or HMAC.
+    pub fn empty(session_handle: u32) -> Self {
+        Self {
+            session_handle,
+            nonce: Tpm2b(Vec::new()),
+            session_attributes: 0,
+            hmac: Tpm2b(Vec::new()),
+        }
+    }
+}
+
+/// Lists encoded element by element, their length known from elsewhere.
+mod unprefixed {
+    use tss_serde::{TssSerialize, TssWriter};
+
+    pub fn serialize<T: TssSerialize>(items: &[T], writer: &mut TssWriter) {
+        for item in items {
+            item.serialize_to(writer);
+        }
+    }
 }
 
 #[derive(TssSerialize)]
@@ -59,34 +220,31 @@ pub struct GetCapabilityCommand {
 //    const RETRY: u32 = 0x0000922;
 //}
 
-#[derive(TssDeserialize, Debug)]
+#[derive(TssDeserialize, TssFixedSize, Debug)]
 pub struct ResponseHeader {
     pub tag: u16,
     pub size: u32,
     pub response_code: u32,
 }
 
+// Every TPM response starts with a 10 byte header.
+const _: () = assert!(ResponseHeader::SERIALIZED_SIZE == 10);
+
 pub struct RawResponse {
     pub bytes: Vec<u8>,
 }
 
 impl TssDeserialize for RawResponse {
     fn from_tss_reader(reader: &mut tss_serde::TssReader) -> Result<Self, TssError> {
-        let bytes = Vec::from_tss_reader(reader)?;
+        let bytes = reader.read_bytes(reader.remaining())?;
         Ok(RawResponse { bytes })
     }
 }
 
-pub struct Empty {}
-
-impl TssDeserialize for Empty {
-    fn from_tss_reader(reader: &mut tss_serde::TssReader) -> Result<Self, TssError> {
-        if reader.remaining() != 0 {
-            return Err(TssError::InvalidFormat);
-        }
-        Ok(Self {})
-    }
-}
+/// The parameters of a command or response that has none.
+#[derive(TssSerialize, TssDeserialize)]
+#[tss(exact)]
+pub struct Empty;
 
 #[derive(Debug)]
 pub enum Capabilities {
@@ -109,13 +267,16 @@ pub struct TaggedProperty {
 impl TssDeserialize for CapabilitiesResponse {
     fn from_tss_reader(reader: &mut tss_serde::TssReader) -> Result<Self, TssError> {
         let more_data = bool::from_tss_reader(reader)?;
-        let capability = u32::from_tss_reader(reader)?;
+        let capability = reader.read_u32()?;
 
         let capabilities = match capability {
             1 => Capabilities::Handles(Vec::from_tss_reader(reader)?),
             6 => Capabilities::TaggedProperties(Vec::from_tss_reader(reader)?),
             _ => {
-                panic!("Not implemented");
+                return Err(TssError::InvalidValue {
+                    field: "TPM_CAP",
+                    value: capability.into(),
+                })
             }
         };
 
@@ -126,27 +287,429 @@ impl TssDeserialize for CapabilitiesResponse {
     }
 }
 
+/// The public area of an NV index (TPMS_NV_PUBLIC).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NvPublic {
+    pub nv_index: u32,
+    pub name_alg: u16,
+    pub attributes: u32,
+    pub auth_policy: Vec<u8>,
+    pub data_size: u16,
+}
+
+impl TssSerialize for NvPublic {
+    /// Serialized as a TPM2B_NV_PUBLIC.
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        let mut public = TssWriter::new();
+        public.write_u32(self.nv_index);
+        public.write_u16(self.name_alg);
+        public.write_u32(self.attributes);
+        public.write_tpm2b(&self.auth_policy);
+        public.write_u16(self.data_size);
+        writer.write_tpm2b(public.as_bytes());
+    }
+}
+
+impl TssDeserialize for NvPublic {
+    /// Deserialized from a TPM2B_NV_PUBLIC.
+    fn from_tss_reader(reader: &mut tss_serde::TssReader) -> Result<Self, TssError> {
+        let size = reader.read_u16()? as usize;
+        let mut inner = reader.sub_reader(size)?;
+        let public = NvPublic {
+            nv_index: inner.read_u32()?,
+            name_alg: inner.read_u16()?,
+            attributes: inner.read_u32()?,
+            auth_policy: inner.read_tpm2b()?,
+            data_size: inner.read_u16()?,
+        };
+        if inner.remaining() != 0 {
+            return Err(TssError::InvalidFormat);
+        }
+        Ok(public)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for NvPublic {
+    /// Only public areas that fit a TPM2B_NV_PUBLIC.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Self {
+            nv_index: u.arbitrary()?,
+            name_alg: u.arbitrary()?,
+            attributes: u.arbitrary()?,
+            auth_policy: tss_serde::Tpm2b::arbitrary_with_max(u, u16::MAX as usize - 14)?.0,
+            data_size: u.arbitrary()?,
+        })
+    }
+}
+
+#[derive(TssSerialize)]
+pub struct NvReadCommand {
+    pub size: u16,
+    pub offset: u16,
+}
+
+#[derive(TssSerialize)]
+pub struct NvWriteCommand {
+    #[tss(length_prefix = "u16")]
+    pub data: Vec<u8>,
+    pub offset: u16,
+}
+
+#[derive(TssSerialize)]
+pub struct NvDefineSpaceCommand {
+    /// Authorization value of the new index.
+    #[tss(length_prefix = "u16")]
+    pub auth: Vec<u8>,
+    pub public: NvPublic,
+}
+
+/// The response of TPM2_NV_ReadPublic.
+pub struct NvReadPublicResponse {
+    pub nv_public: NvPublic,
+    pub nv_name: Vec<u8>,
+}
+
+impl TssDeserialize for NvReadPublicResponse {
+    fn from_tss_reader(reader: &mut tss_serde::TssReader) -> Result<Self, TssError> {
+        Ok(Self {
+            nv_public: NvPublic::from_tss_reader(reader)?,
+            nv_name: reader.read_tpm2b()?,
+        })
+    }
+}
+
+/// A TPML_PCR_SELECTION of a single bank.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcrSelection {
+    pub hash: u16,
+    pub pcrs: Vec<u32>,
+}
+
+impl PcrSelection {
+    pub fn sha256(pcrs: &[u32]) -> Self {
+        Self {
+            hash: algorithms::SHA256,
+            pcrs: pcrs.to_vec(),
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for PcrSelection {
+    /// Only selections of the 24 PCRs the serialized bitmap covers, in
+    /// ascending order as deserialized.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let hash = u.arbitrary()?;
+        let bitmap: u32 = u.arbitrary()?;
+        Ok(Self {
+            hash,
+            pcrs: (0..24).filter(|pcr| bitmap & (1 << pcr) != 0).collect(),
+        })
+    }
+}
+
+impl TssSerialize for PcrSelection {
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        let mut bitmap = [0u8; 3];
+        for &pcr in &self.pcrs {
+            bitmap[pcr as usize / 8] |= 1 << (pcr % 8);
+        }
+        writer.write_u32(1);
+        writer.write_u16(self.hash);
+        writer.write_u8(bitmap.len() as u8);
+        writer.write_bytes(&bitmap);
+    }
+}
+
+impl TssDeserialize for PcrSelection {
+    /// Deserialized from a TPML_PCR_SELECTION of at most one bank.
+    fn from_tss_reader(reader: &mut tss_serde::TssReader) -> Result<Self, TssError> {
+        let count = reader.read_u32()?;
+        let mut selection = PcrSelection {
+            hash: algorithms::NULL,
+            pcrs: Vec::new(),
+        };
+        match count {
+            0 => {}
+            1 => {
+                selection.hash = reader.read_u16()?;
+                let size = reader.read_u8()? as usize;
+                let bitmap = reader.read_bytes(size)?;
+                for (byte, bits) in bitmap.iter().enumerate() {
+                    for bit in 0..8 {
+                        if bits & (1 << bit) != 0 {
+                            selection.pcrs.push((byte * 8 + bit) as u32);
+                        }
+                    }
+                }
+            }
+            _ => return Err(TssError::InvalidFormat),
+        }
+        Ok(selection)
+    }
+}
+
+/// The response of TPM2_PCR_Read.
+pub struct PcrReadResponse {
+    pub update_counter: u32,
+    pub pcr_selection: PcrSelection,
+    pub digests: Vec<Vec<u8>>,
+}
+
+impl TssDeserialize for PcrReadResponse {
+    fn from_tss_reader(reader: &mut tss_serde::TssReader) -> Result<Self, TssError> {
+        let update_counter = reader.read_u32()?;
+        let pcr_selection = PcrSelection::from_tss_reader(reader)?;
+        let count = reader.read_u32()?;
+        let digests = (0..count)
+            .map(|_| reader.read_tpm2b())
+            .collect::<Result<_, TssError>>()?;
+        Ok(Self {
+            update_counter,
+            pcr_selection,
+            digests,
+        })
+    }
+}
+
+pub struct CreatePrimaryCommand {
+    /// The TPMT_PUBLIC template of the object.
+    pub template: Vec<u8>,
+}
+
+impl TssSerialize for CreatePrimaryCommand {
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        // TPM2B_SENSITIVE_CREATE with an empty auth value and no data.
+        writer.write_tpm2b(&[0; 4]);
+        writer.write_tpm2b(&self.template);
+        writer.write_tpm2b(&[]); // outsideInfo
+        writer.write_u32(0); // creationPCR
+    }
+}
+
+pub struct CreateCommand {
+    /// Data to seal in the object, if any.
+    pub data: Vec<u8>,
+    /// The TPMT_PUBLIC template of the object.
+    pub template: Vec<u8>,
+}
+
+impl TssSerialize for CreateCommand {
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        // TPM2B_SENSITIVE_CREATE with an empty auth value.
+        writer.write_u16(4 + self.data.len() as u16);
+        writer.write_tpm2b(&[]);
+        writer.write_tpm2b(&self.data);
+        writer.write_tpm2b(&self.template);
+        writer.write_tpm2b(&[]); // outsideInfo
+        writer.write_u32(0); // creationPCR
+    }
+}
+
+/// The response of TPM2_Create, without the creation data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateResponse {
+    /// The TPM2B_PRIVATE of the object, wrapped by its parent.
+    pub private: Vec<u8>,
+    /// The TPMT_PUBLIC of the object.
+    pub public: Vec<u8>,
+}
+
+impl TssDeserialize for CreateResponse {
+    fn from_tss_reader(reader: &mut tss_serde::TssReader) -> Result<Self, TssError> {
+        Ok(Self {
+            private: reader.read_tpm2b()?,
+            public: reader.read_tpm2b()?,
+        })
+    }
+}
+
+pub struct LoadCommand {
+    pub private: Vec<u8>,
+    pub public: Vec<u8>,
+}
+
+impl TssSerialize for LoadCommand {
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_tpm2b(&self.private);
+        writer.write_tpm2b(&self.public);
+    }
+}
+
+/// An unbound and unsalted TPM2_StartAuthSession.
+pub struct StartAuthSessionCommand {
+    pub nonce_caller: Vec<u8>,
+    pub session_type: SessionType,
+}
+
+impl TssSerialize for StartAuthSessionCommand {
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_u32(handles::NULL); // tpmKey
+        writer.write_u32(handles::NULL); // bind
+        writer.write_tpm2b(&self.nonce_caller);
+        writer.write_tpm2b(&[]); // encryptedSalt
+        self.session_type.serialize_to(writer);
+        writer.write_u16(algorithms::NULL); // symmetric
+        writer.write_u16(algorithms::SHA256); // authHash
+    }
+}
+
+pub struct StartAuthSessionResponse {
+    pub session_handle: u32,
+    pub nonce_tpm: Vec<u8>,
+}
+
+impl TssDeserialize for StartAuthSessionResponse {
+    fn from_tss_reader(reader: &mut tss_serde::TssReader) -> Result<Self, TssError> {
+        Ok(Self {
+            session_handle: reader.read_u32()?,
+            nonce_tpm: reader.read_tpm2b()?,
+        })
+    }
+}
+
+/// TPM2_PolicyPCR on the current values of `pcr_selection`.
+pub struct PolicyPcrCommand {
+    pub session_handle: u32,
+    pub pcr_selection: PcrSelection,
+}
+
+impl TssSerialize for PolicyPcrCommand {
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_u32(self.session_handle);
+        // An empty pcrDigest, so the TPM uses the current values.
+        writer.write_tpm2b(&[]);
+        self.pcr_selection.serialize_to(writer);
+    }
+}
+
+pub struct QuoteCommand {
+    pub qualifying_data: Vec<u8>,
+    pub pcr_selection: PcrSelection,
+}
+
+impl TssSerialize for QuoteCommand {
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_tpm2b(&self.qualifying_data);
+        // Use the signing scheme of the key.
+        writer.write_u16(algorithms::NULL);
+        self.pcr_selection.serialize_to(writer);
+    }
+}
+
+pub struct CertifyCommand {
+    pub qualifying_data: Vec<u8>,
+}
+
+impl TssSerialize for CertifyCommand {
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_tpm2b(&self.qualifying_data);
+        // Use the signing scheme of the key.
+        writer.write_u16(algorithms::NULL);
+    }
+}
+
+/// The response of TPM2_ReadPublic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadPublicResponse {
+    /// The TPMT_PUBLIC of the object.
+    pub public: Vec<u8>,
+    pub name: Vec<u8>,
+    pub qualified_name: Vec<u8>,
+}
+
+impl TssDeserialize for ReadPublicResponse {
+    fn from_tss_reader(reader: &mut tss_serde::TssReader) -> Result<Self, TssError> {
+        Ok(Self {
+            public: reader.read_tpm2b()?,
+            name: reader.read_tpm2b()?,
+            qualified_name: reader.read_tpm2b()?,
+        })
+    }
+}
+
+pub struct SignCommand {
+    pub digest: Vec<u8>,
+}
+
+impl TssSerialize for SignCommand {
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_tpm2b(&self.digest);
+        // Use the signing scheme of the key.
+        writer.write_u16(algorithms::NULL);
+        // A NULL TPMT_TK_HASHCHECK, which only unrestricted keys accept.
+        writer.write_u16(tags::HASH_CHECK);
+        writer.write_u32(handles::NULL);
+        writer.write_tpm2b(&[]);
+    }
+}
+
+/// A TPMT_SIGNATURE.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TpmSignature {
+    Ecdsa { hash: u16, r: Vec<u8>, s: Vec<u8> },
+    RsaSsa { hash: u16, signature: Vec<u8> },
+}
+
+impl TssDeserialize for TpmSignature {
+    fn from_tss_reader(reader: &mut tss_serde::TssReader) -> Result<Self, TssError> {
+        let algorithm = reader.read_u16()?;
+        let hash = reader.read_u16()?;
+        match algorithm {
+            algorithms::ECDSA => Ok(TpmSignature::Ecdsa {
+                hash,
+                r: reader.read_tpm2b()?,
+                s: reader.read_tpm2b()?,
+            }),
+            algorithms::RSASSA => Ok(TpmSignature::RsaSsa {
+                hash,
+                signature: reader.read_tpm2b()?,
+            }),
+            _ => Err(TssError::InvalidValue {
+                field: "TPMI_ALG_SIG_SCHEME",
+                value: algorithm.into(),
+            }),
+        }
+    }
+}
+
+/// The response of TPM2_Quote and TPM2_Certify.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuoteResponse {
+    /// The signed TPMS_ATTEST.
+    pub attest: Vec<u8>,
+    pub signature: TpmSignature,
+}
+
+impl TssDeserialize for QuoteResponse {
+    fn from_tss_reader(reader: &mut tss_serde::TssReader) -> Result<Self, TssError> {
+        Ok(Self {
+            attest: reader.read_tpm2b()?,
+            signature: TpmSignature::from_tss_reader(reader)?,
+        })
+    }
+}
+
 pub struct ReadPcrCommand {
     pub hash: u16,
     pub pcr_index: Vec<u32>,
 }
 
 impl TssSerialize for ReadPcrCommand {
-    fn to_tss_bytes(&self) -> Vec<u8> {
-        let mut buffer = Vec::new();
-
+    fn serialize_to(&self, writer: &mut TssWriter) {
         if self.pcr_index.is_empty() {
             // If no PCRs selected, write count of 0
-            buffer.extend_from_slice(&0u32.to_tss_bytes());
-            return buffer;
+            writer.write_u32(0);
+            return;
         }
 
         // Write hash algorithm
-        buffer.extend_from_slice(&self.hash.to_tss_bytes());
+        writer.write_u16(self.hash);
 
         // Write size of PCR select (3 bytes for up to 24 PCRs)
         const SIZE_OF_PCR_SELECT: u8 = 3;
-        buffer.push(SIZE_OF_PCR_SELECT);
+        writer.write_u8(SIZE_OF_PCR_SELECT);
 
         // Create PCR bitmask
         let mut pcr_mask = [0u8; SIZE_OF_PCR_SELECT as usize];
@@ -166,8 +729,6 @@ impl TssSerialize for ReadPcrCommand {
         }
 
         // Write the PCR mask
-        buffer.extend_from_slice(&pcr_mask);
-
-        buffer
+        writer.write_bytes(&pcr_mask);
     }
 }
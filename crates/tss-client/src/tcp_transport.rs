@@ -2,7 +2,8 @@ use std::io::{Read, Write};
 use std::net::TcpStream;
 use std::net::ToSocketAddrs;
 
-use tss_serde::TssDeserialize;
+use tee_observe::{observe, Operation};
+use tss_serde::{TssDeserialize, TssFixedSize};
 
 use crate::primitives::ResponseHeader;
 use crate::Transport;
@@ -12,7 +13,9 @@ pub struct TcpTransport {
 }
 
 impl TcpTransport {
-    fn connect<A: ToSocketAddrs>(addr: A) -> eyre::Result<Self> {
+    /// Connect to a TPM simulator at `addr`, after power cycling it through
+    /// its platform port on `localhost:2322`.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> eyre::Result<Self> {
         Self::reset_platform()?;
 
         let stream = TcpStream::connect(addr)?;
@@ -56,6 +59,12 @@ impl Default for TcpTransport {
 
 impl Transport for TcpTransport {
     fn send_command(&mut self, command: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
+        observe(Operation::TpmTransport, "tcp", || self.exchange(command))
+    }
+}
+
+impl TcpTransport {
+    fn exchange(&mut self, command: &[u8]) -> eyre::Result<(ResponseHeader, Vec<u8>)> {
         // 1. Command type: TPM_SEND_COMMAND
         self.stream.write_all(&[0x00, 0x00, 0x00, 0x08])?; // TPM_SEND_COMMAND
 
@@ -67,7 +76,7 @@ impl Transport for TcpTransport {
         self.stream.write_all(&tpm_len)?;
 
         // 4. TPM packet
-        self.stream.write_all(&command)?;
+        self.stream.write_all(command)?;
         self.stream.flush()?;
 
         // 5. Wait for the response
@@ -93,7 +102,7 @@ impl Transport for TcpTransport {
         }
 
         // Try to read the rest of the body response
-        let body_response = &tpm_response[10..];
+        let body_response = &tpm_response[ResponseHeader::SERIALIZED_SIZE..];
         Ok((header, body_response.to_vec()))
     }
 }
@@ -1,9 +1,256 @@
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
-use quote::{format_ident, quote};
-use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, Type};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Expr, Field, Fields, Ident, Member, Meta, Token, Type,
+};
+
+/// A field of a derived struct or enum variant.
+struct TssField {
+    member: Member,
+    ty: Type,
+    /// `#[tss(sized)]`: an `Option` prefixed with its u16 size, 0 when `None`.
+    sized: bool,
+    /// `#[tss(with = "module")]`: encoded by `module::serialize` and
+    /// decoded by `module::deserialize`.
+    with: Option<syn::Path>,
+    /// `#[tss(size_of = "...")]`: an integer holding the encoded size of
+    /// another field, or of the whole structure.
+    size_of: Option<SizeOf>,
+    /// `#[tss(length_prefix = "u16")]`: a `Vec` prefixed with its length.
+    length_prefix: Option<LengthPrefix>,
+    /// `#[tss(validate = "path")]`: checks the field once decoded.
+    validate: Option<syn::Path>,
+    /// `#[tss(selector = "alg")]`: the index of the earlier field selecting
+    /// the variant of the union.
+    selector: Option<usize>,
+    /// `#[tss(const = 0x8001u16)]`: constants encoded before the field,
+    /// which it does not store.
+    consts: Vec<Expr>,
+}
+
+/// The length before a `#[tss(length_prefix = "...")]` list.
+struct LengthPrefix {
+    ty: Type,
+    /// `#[tss(length_unit = "bytes")]`: the length is the size of the
+    /// elements rather than their count.
+    bytes: bool,
+}
+
+/// Integer types `#[tss(length_prefix = "...")]` can have.
+const LENGTH_INTEGERS: [&str; 3] = ["u8", "u16", "u32"];
+
+/// What a `#[tss(size_of = "...")]` field is the size of.
+#[derive(Clone, Copy)]
+enum SizeOf {
+    /// `"self"`: the whole structure, or the fields of the variant.
+    Structure,
+    /// The field with this index.
+    Field(usize),
+}
+
+/// Integer types `#[tss(size_of = "...")]` fields can have.
+const SIZE_INTEGERS: [&str; 4] = ["u8", "u16", "u32", "u64"];
+
+impl TssField {
+    /// The local the field is bound to while serializing.
+    fn binding(&self, index: usize) -> Ident {
+        format_ident!("field_{}", index)
+    }
+
+    fn name(&self) -> String {
+        match &self.member {
+            Member::Named(ident) => ident.to_string(),
+            Member::Unnamed(index) => index.index.to_string(),
+        }
+    }
+}
+
+/// A variant of a derived enum, `#[tss(discriminant = ...)]`: encoded as
+/// its discriminant, of the type of the enum's tag, followed by its fields.
+struct TssVariant {
+    ident: Ident,
+    discriminant: Expr,
+    fields: Vec<TssField>,
+}
+
+/// The byte order of the integer fields of a struct, `#[tss(endian = "...")]`.
+/// TPM structures are big-endian; other TEE structures, like SEV-SNP
+/// reports, are little-endian. Nested types keep their own byte order.
+#[derive(Clone, Copy, PartialEq)]
+enum Endian {
+    Big,
+    Little,
+}
+
+/// The attributes of a derived struct or enum.
+struct TssContainer {
+    endian: Endian,
+    /// `#[tss(tag = u16)]`: the type of the discriminant of an enum, or
+    /// `#[tss(selector = u16)]` of the selector before it.
+    tag: Option<Type>,
+    /// `#[tss(selector = u16)]`: the enum is a union encoded without its
+    /// discriminant, which the structure holding it has as another field.
+    selected: bool,
+    /// `#[repr(u16)]`: the integer type of a fieldless enum.
+    repr: Option<Type>,
+    /// `#[tss(validate = "path")]`: checks the value once decoded.
+    validate: Option<syn::Path>,
+    /// `#[tss(exact)]`: the struct ends the bytes it is decoded from.
+    exact: bool,
+}
+
+/// Integer types whose fields follow the byte order of the struct.
+const MULTI_BYTE_INTEGERS: [&str; 6] = ["u16", "u32", "u64", "i16", "i32", "i64"];
+
+/// The primitive integer types, which fieldless enums can be encoded as.
+const INTEGERS: [&str; 8] = ["u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64"];
+
+/// How a type is encoded, from its syntax. Type aliases are opaque to the
+/// derive, so they are encoded with the implementations of the types they
+/// name.
+enum TypeKind<'a> {
+    /// A primitive integer, like `u16` or `core::primitive::u16`.
+    Integer(&'static str),
+    Array {
+        element: &'a Type,
+        len: &'a Expr,
+    },
+    Option,
+    Vec,
+    /// Any other type, encoded with its `TssSerialize` and `TssDeserialize`
+    /// implementations.
+    Other,
+}
+
+impl TypeKind<'_> {
+    fn of(ty: &Type) -> TypeKind<'_> {
+        match ty {
+            Type::Group(group) => TypeKind::of(&group.elem),
+            Type::Paren(paren) => TypeKind::of(&paren.elem),
+            Type::Array(array) => TypeKind::Array {
+                element: &array.elem,
+                len: &array.len,
+            },
+            Type::Path(path) if path.qself.is_none() => TypeKind::of_path(&path.path),
+            _ => TypeKind::Other,
+        }
+    }
+
+    fn of_path(path: &syn::Path) -> TypeKind<'static> {
+        let Some(last) = path.segments.last() else {
+            return TypeKind::Other;
+        };
+        let module = path
+            .segments
+            .iter()
+            .rev()
+            .skip(1)
+            .rev()
+            .map(|segment| segment.ident.to_string())
+            .collect::<Vec<_>>()
+            .join("::");
+        let name = last.ident.to_string();
+        let generic = matches!(
+            &last.arguments,
+            syn::PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1
+        );
+        match (module.as_str(), name.as_str()) {
+            ("" | "core::primitive" | "std::primitive", name) if last.arguments.is_empty() => {
+                match INTEGERS.iter().find(|integer| **integer == name) {
+                    Some(integer) => TypeKind::Integer(integer),
+                    None => TypeKind::Other,
+                }
+            }
+            ("" | "core::option" | "std::option", "Option") if generic => TypeKind::Option,
+            ("" | "alloc::vec" | "std::vec", "Vec") if generic => TypeKind::Vec,
+            _ => TypeKind::Other,
+        }
+    }
+
+    fn is_integer(&self, names: &[&str]) -> bool {
+        matches!(self, TypeKind::Integer(name) if names.contains(name))
+    }
+}
+
+/// Reject types that cannot be decoded into, like references and slices.
+fn check_supported(ty: &Type) -> syn::Result<()> {
+    match ty {
+        Type::Group(group) => check_supported(&group.elem),
+        Type::Paren(paren) => check_supported(&paren.elem),
+        Type::Array(array) => check_supported(&array.elem),
+        Type::Tuple(tuple) => tuple.elems.iter().try_for_each(check_supported),
+        Type::Path(_) | Type::Macro(_) => Ok(()),
+        _ => Err(syn::Error::new_spanned(
+            ty,
+            "Unsupported type, expected an owned type implementing the tss_serde traits",
+        )),
+    }
+}
+
+/// The generics of `input`, with its type parameters bound by `bound`.
+fn bounded_generics(input: &DeriveInput, bound: TokenStream2) -> syn::Generics {
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(syn::parse_quote!(#bound));
+    }
+    generics
+}
 
 /// Derive macro for TSS serialization
+///
+/// Structs, including tuple structs, encode their fields in order. Enums
+/// are tagged unions, like the TPMU types with the selector before them: a
+/// discriminant, then the fields of the variant it selects. Fieldless enums
+/// without a tag encode as their `#[repr]` integer. Arrays `[T; N]`, of any
+/// element type and of any constant length, like a named constant or a
+/// const generic, encode their elements back to back. Unit structs, like
+/// the empty responses of TPM commands, encode as no bytes.
+///
+/// Attributes:
+/// - `#[tss(endian = "little")]` on the struct encodes its integer fields
+///   little-endian instead of big-endian. The default u32 count of a `Vec`,
+///   the u16 size of `#[tss(sized)]` and the integers of an `Option` would
+///   stay big-endian, so `Vec` fields need `#[tss(length_prefix)]` there,
+///   and `Option` fields are rejected.
+/// - `#[tss(sized)]` on an `Option` field prefixes it with its u16 size,
+///   panicking like `TssWriter::write_tpm2b` if it does not fit.
+/// - `#[tss(with = "module")]` on a field encodes it with
+///   `module::serialize(&T, &mut TssWriter)` and decodes it with
+///   `module::deserialize(&mut TssReader) -> Result<T, TssError>`, for
+///   encodings of its own, like PCR select bitmaps.
+/// - `#[tss(size_of = "body")]` on an unsigned integer field encodes it as
+///   the size of the field `body`, or of the whole structure with
+///   `"self"`, instead of its value. Serializing panics if the size does
+///   not fit the field, and decoding checks it against the bytes the field
+///   or structure took.
+/// - `#[tss(length_prefix = "u16")]` on a `Vec` field prefixes it with its
+///   element count as a u8, u16 or u32 instead of the u32 of TPML lists,
+///   and `#[tss(length_unit = "bytes")]` with its size instead, decoded
+///   with `TssReader::read_sized_list`. Serializing panics if the length
+///   does not fit the prefix.
+/// - `#[tss(const = TPM_GENERATED)]` on a field encodes the constant, a
+///   typed integer, before it, such as a magic or a tag of the structure,
+///   and checks it when decoding, failing with `TssError::InvalidValue`.
+/// - `#[tss(validate = "path")]` on a field, or on the struct or enum,
+///   checks it once decoded with `path(&T) -> Result<(), u64>`. An error,
+///   such as the reserved bits that are set, fails the decoding with
+///   `TssError::InvalidValue`.
+/// - `#[tss(exact)]` on a struct fails the decoding with
+///   `TssError::InvalidFormat` unless it ends the bytes, like a response
+///   that must be empty.
+/// - `#[tss(tag = u16)]` on an enum sets the integer type of its
+///   discriminants, and `#[tss(discriminant = ...)]` on each variant its
+///   value, a literal or a constant.
+/// - `#[tss(selector = u16)]` instead of a tag on an enum makes it a union,
+///   like the TPMU types, encoded without its discriminant and decoded
+///   with `TssSelect`. A field of such a union takes
+///   `#[tss(selector = "alg")]`, naming the earlier field of the
+///   discriminant.
+/// - `#[repr(u16)]` on a fieldless enum without a tag encodes it as the
+///   integer value of its variants.
 #[proc_macro_derive(TssSerialize, attributes(tss))]
 pub fn derive_tss_serialize(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -14,7 +261,10 @@ pub fn derive_tss_serialize(input: TokenStream) -> TokenStream {
     }
 }
 
-/// Derive macro for TSS deserialization
+/// Derive macro for TSS deserialization, with the attributes of
+/// [`TssSerialize`](macro@TssSerialize). An unknown discriminant, or value of
+/// a fieldless enum, fails with `TssError::InvalidValue`. On unions, it
+/// implements `TssSelect` instead.
 #[proc_macro_derive(TssDeserialize, attributes(tss))]
 pub fn derive_tss_deserialize(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -25,116 +275,1095 @@ pub fn derive_tss_deserialize(input: TokenStream) -> TokenStream {
     }
 }
 
+/// Derive macro for `TssFixedSize`, on structures whose fields all have a
+/// fixed serialized size and on fieldless enums, with the attributes of
+/// [`TssSerialize`](macro@TssSerialize)
+#[proc_macro_derive(TssFixedSize, attributes(tss))]
+pub fn derive_tss_fixed_size(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match generate_fixed_size_impl(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Derive macro for `TssSerialize`, `TssDeserialize` and `TssFixedSize` on
+/// a type of the `bitflags!` macro, like the TPMA attribute words, encoded
+/// as its bits integer.
+///
+/// Decoding fails with `TssError::InvalidValue` holding the reserved bits,
+/// those without a flag, that are set. With `#[tss(retain)]` on the type,
+/// they are kept instead, for attribute words that later TPM revisions
+/// extend.
+#[proc_macro_derive(TssBitflags, attributes(tss))]
+pub fn derive_tss_bitflags(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match generate_bitflags_impl(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
 fn generate_serialize_impl(input: &DeriveInput) -> syn::Result<TokenStream2> {
     let name = &input.ident;
+    let container = parse_container(input)?;
+
+    let (serialize, size) = match &input.data {
+        Data::Enum(_) if container.tag.is_none() => {
+            let (repr, variants) = extract_unit_variants(input, &container)?;
+            let serialize_value =
+                generate_field_serialize(&format_ident!("value"), repr, container.endian)?;
+            (
+                quote! {
+                    let value: &#repr = &match self {
+                        #(Self::#variants => Self::#variants as #repr,)*
+                    };
+                    #serialize_value
+                },
+                quote! { <#repr as ::tss_serde::TssFixedSize>::SERIALIZED_SIZE },
+            )
+        }
+        Data::Enum(_) => {
+            let tag = enum_tag(input, &container)?;
+            let variants = extract_variants(input)?;
+            let mut serialize_arms = Vec::new();
+            let mut size_arms = Vec::new();
+            for variant in &variants {
+                let ident = &variant.ident;
+                let pattern = generate_pattern(&variant.fields);
+                let discriminant = &variant.discriminant;
+                let serialize_tag =
+                    generate_field_serialize(&format_ident!("tag"), tag, container.endian)?;
+                let serialize_fields = generate_fields_serialize(&variant.fields, &container)?;
+                let field_sizes = generate_fields_size(&variant.fields);
+                let (serialize_tag, tag_size) = if container.selected {
+                    (quote! {}, quote! { 0 })
+                } else {
+                    (
+                        quote! {
+                            let tag: &#tag = &#discriminant;
+                            #serialize_tag
+                        },
+                        quote! { <#tag as ::tss_serde::TssFixedSize>::SERIALIZED_SIZE },
+                    )
+                };
+                serialize_arms.push(quote! {
+                    Self::#ident #pattern => {
+                        #serialize_tag
+                        #(#serialize_fields)*
+                    }
+                });
+                size_arms.push(quote! {
+                    Self::#ident #pattern => {
+                        #tag_size #(+ #field_sizes)*
+                    }
+                });
+            }
+            (
+                quote! { match self { #(#serialize_arms)* } },
+                quote! { match self { #(#size_arms)* } },
+            )
+        }
+        _ => {
+            let fields = extract_fields(input)?;
+            let pattern = generate_pattern(&fields);
+            let serialize_fields = generate_fields_serialize(&fields, &container)?;
+            let field_sizes = generate_fields_size(&fields);
+            let unused_writer = fields.is_empty().then(|| quote! { let _ = writer; });
+            (
+                quote! {
+                    let Self #pattern = self;
+                    #unused_writer
+                    #(#serialize_fields)*
+                },
+                quote! {
+                    let Self #pattern = self;
+                    0 #(+ #field_sizes)*
+                },
+            )
+        }
+    };
+
+    let generics = bounded_generics(input, quote! { ::tss_serde::TssSerialize });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::tss_serde::TssSerialize for #name #ty_generics #where_clause {
+            fn serialize_to(&self, writer: &mut ::tss_serde::TssWriter) {
+                #serialize
+            }
+
+            fn tss_size(&self) -> usize {
+                #size
+            }
+        }
+    })
+}
+
+/// A pattern binding each field by reference to [`TssField::binding`].
+fn generate_pattern(fields: &[TssField]) -> TokenStream2 {
+    let bindings = fields.iter().enumerate().map(|(i, field)| {
+        let member = &field.member;
+        let binding = field.binding(i);
+        quote! { #member: #binding }
+    });
+    quote! { { #(#bindings),* } }
+}
+
+fn generate_fields_serialize(
+    fields: &[TssField],
+    container: &TssContainer,
+) -> syn::Result<Vec<TokenStream2>> {
+    check_byte_order(fields, container)?;
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let consts = field.consts.iter().map(|value| match container.endian {
+                Endian::Big => quote! { writer.write_bytes(&(#value).to_be_bytes()); },
+                Endian::Little => quote! { writer.write_bytes(&(#value).to_le_bytes()); },
+            });
+            let serialize = generate_member_serialize(fields, i, field, container)?;
+            Ok(quote! {
+                #(#consts)*
+                #serialize
+            })
+        })
+        .collect()
+}
+
+/// The serialization of the field with index `i` of `fields`.
+fn generate_member_serialize(
+    fields: &[TssField],
+    i: usize,
+    field: &TssField,
+    container: &TssContainer,
+) -> syn::Result<TokenStream2> {
+    let binding = field.binding(i);
+    if let Some(size_of) = field.size_of {
+        // Shadow the stored value with the size, computed from the
+        // fields as they are.
+        let size = match size_of {
+            SizeOf::Structure => {
+                let sizes = generate_fields_size(fields);
+                quote! { 0 #(+ #sizes)* }
+            }
+            SizeOf::Field(index) => generate_fields_size(fields).swap_remove(index),
+        };
+        let ty = &field.ty;
+        let serialize = generate_field_serialize(&binding, ty, container.endian)?;
+        let overflow = format!(
+            "the size of {} does not fit its {}",
+            match size_of {
+                SizeOf::Structure => "the structure".to_string(),
+                SizeOf::Field(index) => fields[index].name(),
+            },
+            type_to_string(ty),
+        );
+        Ok(quote! {
+            let #binding: &#ty = &match <#ty>::try_from(#size) {
+                Ok(size) => size,
+                Err(_) => panic!("{}", #overflow),
+            };
+            #serialize
+        })
+    } else if let Some(with) = &field.with {
+        Ok(quote! { #with::serialize(#binding, writer); })
+    } else if let Some(prefix) = &field.length_prefix {
+        generate_prefixed_serialize(&binding, &field.name(), prefix, container.endian)
+    } else if field.sized {
+        Ok(generate_sized_serialize(&binding))
+    } else {
+        generate_field_serialize(&binding, &field.ty, container.endian)
+    }
+}
+
+fn generate_fixed_size_impl(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let generics = bounded_generics(input, quote! { ::tss_serde::TssFixedSize });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    if let Data::Enum(_) = &input.data {
+        let container = parse_container(input)?;
+        if container.tag.is_some() {
+            return Err(syn::Error::new_spanned(
+                input,
+                "Only structs and fieldless enums have a fixed size",
+            ));
+        }
+        let (repr, _) = extract_unit_variants(input, &container)?;
+        return Ok(quote! {
+            impl #impl_generics ::tss_serde::TssFixedSize for #name #ty_generics #where_clause {
+                const SERIALIZED_SIZE: usize = <#repr as ::tss_serde::TssFixedSize>::SERIALIZED_SIZE;
+            }
+        });
+    }
     let fields = extract_fields(input)?;
 
-    let serialize_fields = fields
+    let field_sizes = fields
         .iter()
-        .map(|(field_name, field_type)| generate_field_serialize(field_name, field_type))
+        .map(|field| {
+            if field.sized || field.with.is_some() || field.length_prefix.is_some() {
+                return Err(syn::Error::new_spanned(
+                    &field.member,
+                    "#[tss(sized)], #[tss(with)] and #[tss(length_prefix)] fields have no fixed size",
+                ));
+            }
+            let ty = &field.ty;
+            let consts = &field.consts;
+            Ok(quote_spanned! {ty.span()=>
+                <#ty as ::tss_serde::TssFixedSize>::SERIALIZED_SIZE
+                    #(+ ::core::mem::size_of_val(&(#consts)))*
+            })
+        })
         .collect::<syn::Result<Vec<_>>>()?;
 
+    Ok(quote! {
+        impl #impl_generics ::tss_serde::TssFixedSize for #name #ty_generics #where_clause {
+            const SERIALIZED_SIZE: usize = 0 #(+ #field_sizes)*;
+        }
+    })
+}
+
+fn generate_bitflags_impl(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "Generic bitflags are not supported",
+        ));
+    }
+    let mut retain = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("tss"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("retain") {
+                retain = true;
+                Ok(())
+            } else {
+                Err(meta.error("Unsupported tss attribute"))
+            }
+        })?;
+    }
+
+    let field = name.to_string();
+    let from_bits = if retain {
+        quote! { Ok(Self::from_bits_retain(bits)) }
+    } else {
+        quote! {
+            Self::from_bits(bits).ok_or_else(|| ::tss_serde::TssError::InvalidValue {
+                field: #field,
+                value: (bits & !Self::all().bits()) as u64,
+            })
+        }
+    };
+
     Ok(quote! {
         impl ::tss_serde::TssSerialize for #name {
-            fn to_tss_bytes(&self) -> Vec<u8> {
-                let mut buffer = Vec::new();
-                #(#serialize_fields)*
-                buffer
+            fn serialize_to(&self, writer: &mut ::tss_serde::TssWriter) {
+                ::tss_serde::TssSerialize::serialize_to(&self.bits(), writer);
+            }
+
+            fn tss_size(&self) -> usize {
+                ::tss_serde::TssSerialize::tss_size(&self.bits())
+            }
+        }
+
+        impl ::tss_serde::TssDeserialize for #name {
+            fn from_tss_reader(reader: &mut ::tss_serde::TssReader) -> Result<Self, ::tss_serde::TssError> {
+                let bits = ::tss_serde::TssDeserialize::from_tss_reader(reader)?;
+                #from_bits
             }
         }
+
+        impl ::tss_serde::TssFixedSize for #name {
+            const SERIALIZED_SIZE: usize = ::core::mem::size_of_val(&Self::empty().bits());
+        }
     })
 }
 
 fn generate_deserialize_impl(input: &DeriveInput) -> syn::Result<TokenStream2> {
     let name = &input.ident;
-    let fields = extract_fields(input)?;
+    let container = parse_container(input)?;
+
+    let deserialize = match &input.data {
+        Data::Enum(_) if container.tag.is_none() => {
+            let (repr, variants) = extract_unit_variants(input, &container)?;
+            let deserialize_value = generate_field_deserialize(repr, container.endian)?;
+            let enum_name = name.to_string();
+            quote! {
+                let value: #repr = #deserialize_value;
+                match value {
+                    #(value if value == Self::#variants as #repr => Ok(Self::#variants),)*
+                    value => Err(::tss_serde::TssError::InvalidValue {
+                        field: #enum_name,
+                        value: value as u64,
+                    }),
+                }
+            }
+        }
+        Data::Enum(_) => {
+            let tag = enum_tag(input, &container)?;
+            let variants = extract_variants(input)?;
+            let deserialize_tag = if container.selected {
+                quote! { *selector }
+            } else {
+                generate_field_deserialize(tag, container.endian)?
+            };
+            let arms = variants
+                .iter()
+                .map(|variant| {
+                    let ident = &variant.ident;
+                    let discriminant = &variant.discriminant;
+                    let structure = format!("{}::{}", name, ident);
+                    let (deserialize_fields, constructor) =
+                        generate_fields_deserialize(&structure, &variant.fields, &container)?;
+                    Ok(quote! {
+                        tag if tag == #discriminant => {
+                            #(#deserialize_fields)*
+                            Ok(Self::#ident #constructor)
+                        }
+                    })
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            let enum_name = name.to_string();
+            quote! {
+                let tag: #tag = #deserialize_tag;
+                match tag {
+                    #(#arms)*
+                    tag => Err(::tss_serde::TssError::InvalidValue {
+                        field: #enum_name,
+                        value: tag as u64,
+                    }),
+                }
+            }
+        }
+        _ => {
+            let fields = extract_fields(input)?;
+            let (deserialize_fields, constructor) =
+                generate_fields_deserialize(&name.to_string(), &fields, &container)?;
+            let check_end = if container.exact {
+                quote! {
+                    if reader.remaining() != 0 {
+                        return Err(::tss_serde::TssError::InvalidFormat);
+                    }
+                }
+            } else if fields.is_empty() {
+                // Unit structs decode from no bytes.
+                quote! { let _ = reader; }
+            } else {
+                quote! {}
+            };
+            quote! {
+                #(#deserialize_fields)*
+                #check_end
+
+                Ok(Self #constructor)
+            }
+        }
+    };
+
+    let deserialize = match &container.validate {
+        Some(validate) => {
+            let name = name.to_string();
+            quote! {
+                let value = (|| -> Result<Self, ::tss_serde::TssError> { #deserialize })()?;
+                #validate(&value).map_err(|value| ::tss_serde::TssError::InvalidValue {
+                    field: #name,
+                    value,
+                })?;
+                Ok(value)
+            }
+        }
+        None => deserialize,
+    };
+
+    if container.selected {
+        let tag = &container.tag;
+        let generics = bounded_generics(input, quote! { ::tss_serde::TssDeserialize });
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        return Ok(quote! {
+            impl #impl_generics ::tss_serde::TssSelect for #name #ty_generics #where_clause {
+                type Selector = #tag;
+
+                fn from_tss_reader_with(
+                    selector: &#tag,
+                    reader: &mut ::tss_serde::TssReader,
+                ) -> Result<Self, ::tss_serde::TssError> {
+                    #deserialize
+                }
+            }
+        });
+    }
+
+    let generics = bounded_generics(input, quote! { ::tss_serde::TssDeserialize });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::tss_serde::TssDeserialize for #name #ty_generics #where_clause {
+            fn from_tss_reader(reader: &mut ::tss_serde::TssReader) -> Result<Self, ::tss_serde::TssError> {
+                #deserialize
+            }
+        }
+    })
+}
 
+/// The statements decoding `fields` of `structure`, and the braces building
+/// it from them.
+fn generate_fields_deserialize(
+    structure: &str,
+    fields: &[TssField],
+    container: &TssContainer,
+) -> syn::Result<(Vec<TokenStream2>, TokenStream2)> {
+    check_byte_order(fields, container)?;
+    let has_sizes = fields.iter().any(|field| field.size_of.is_some());
     let deserialize_fields = fields
         .iter()
         .enumerate()
-        .map(|(i, (_field_name, field_type))| {
+        .map(|(i, field)| {
             let value_var = format_ident!("value_{}", i);
-            let deserialize_logic = generate_field_deserialize(field_type)?;
+            let deserialize_logic = if let Some(with) = &field.with {
+                quote! { #with::deserialize(reader)? }
+            } else if let Some(selector) = field.selector {
+                let selector = format_ident!("value_{}", selector);
+                let ty = &field.ty;
+                quote! {
+                    <#ty as ::tss_serde::TssSelect>::from_tss_reader_with(&#selector, reader)?
+                }
+            } else if let Some(prefix) = &field.length_prefix {
+                generate_prefixed_deserialize(prefix, container.endian)?
+            } else if field.sized {
+                generate_sized_deserialize()
+            } else {
+                generate_field_deserialize(&field.ty, container.endian)?
+            };
 
+            // Decode in a closure, so errors of the field get its context.
+            let ty = &field.ty;
+            let field_name = field.name();
+            let expected = type_to_string(ty);
+            let consts = field.consts.iter().map(|value| {
+                let invalid = format!("constant {}", value.to_token_stream());
+                let (to_bytes, bytes) = match container.endian {
+                    Endian::Big => (quote! { to_be_bytes }, quote! { iter() }),
+                    Endian::Little => (quote! { to_le_bytes }, quote! { iter().rev() }),
+                };
+                quote! {
+                    let found = reader.read_bytes(::core::mem::size_of_val(&(#value)))?;
+                    if found != (#value).#to_bytes() {
+                        return Err(::tss_serde::TssError::InvalidValue {
+                            field: #invalid,
+                            value: found.#bytes.fold(0, |value, byte| value << 8 | u64::from(*byte)),
+                        });
+                    }
+                }
+            });
+            let validate = field.validate.as_ref().map(|validate| {
+                quote! {
+                    #validate(&value).map_err(|value| ::tss_serde::TssError::InvalidValue {
+                        field: #field_name,
+                        value,
+                    })?;
+                }
+            });
+            // Where each field starts, to check the sizes against.
+            let start = has_sizes.then(|| {
+                let start_var = format_ident!("start_{}", i);
+                quote! { let #start_var = position; }
+            });
             Ok(quote! {
-                let #value_var = #deserialize_logic;
+                let position = reader.position();
+                #start
+                let #value_var = (|| -> Result<#ty, ::tss_serde::TssError> {
+                    #(#consts)*
+                    let value = #deserialize_logic;
+                    #validate
+                    Ok(value)
+                })()
+                .map_err(|error| error.in_field(#structure, #field_name, #expected, position))?;
             })
         })
         .collect::<syn::Result<Vec<_>>>()?;
 
-    let field_names: Vec<_> = fields
-        .iter()
-        .enumerate()
-        .map(|(i, (field_name, _))| {
-            let value_var = format_ident!("value_{}", i);
-            quote! { #field_name: #value_var }
+    // Check the sizes once every field is decoded, as they can come after
+    // the size fields.
+    let start = |index: usize| {
+        if index < fields.len() {
+            let start_var = format_ident!("start_{}", index);
+            quote! { #start_var }
+        } else {
+            quote! { reader.position() }
+        }
+    };
+    let size_checks = fields.iter().enumerate().filter_map(|(i, field)| {
+        let (first, last, target) = match field.size_of? {
+            SizeOf::Structure => (0, fields.len(), "structure".to_string()),
+            SizeOf::Field(index) => (index, index + 1, fields[index].name()),
+        };
+        let (first, last) = (start(first), start(last));
+        let value_var = format_ident!("value_{}", i);
+        let start_var = format_ident!("start_{}", i);
+        let invalid = format!("size of {}", target);
+        let field_name = field.name();
+        let expected = type_to_string(&field.ty);
+        Some(quote! {
+            if u64::from(#value_var) != (#last - #first) as u64 {
+                return Err(::tss_serde::TssError::InvalidValue {
+                    field: #invalid,
+                    value: u64::from(#value_var),
+                }
+                .in_field(#structure, #field_name, #expected, #start_var));
+            }
         })
-        .collect();
+    });
+    let deserialize_fields = deserialize_fields.into_iter().chain(size_checks).collect();
 
-    Ok(quote! {
-        impl ::tss_serde::TssDeserialize for #name {
-            fn from_tss_reader(reader: &mut ::tss_serde::TssReader) -> Result<Self, ::tss_serde::TssError> {
-                #(#deserialize_fields)*
+    let field_values = fields.iter().enumerate().map(|(i, field)| {
+        let member = &field.member;
+        let value_var = format_ident!("value_{}", i);
+        quote! { #member: #value_var }
+    });
 
-                Ok(Self {
-                    #(#field_names),*
-                })
+    Ok((deserialize_fields, quote! { { #(#field_values),* } }))
+}
+
+fn extract_fields(input: &DeriveInput) -> syn::Result<Vec<TssField>> {
+    match &input.data {
+        Data::Struct(data_struct) => parse_fields(&data_struct.fields),
+        _ => Err(syn::Error::new_spanned(
+            input,
+            "Only structs and enums are supported",
+        )),
+    }
+}
+
+fn parse_fields(fields: &Fields) -> syn::Result<Vec<TssField>> {
+    let parsed = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| parse_field(i, field))
+        .collect::<syn::Result<Vec<_>>>()?;
+    let names = parsed
+        .iter()
+        .map(|(field, ..)| field.name())
+        .collect::<Vec<_>>();
+    let fields = parsed
+        .into_iter()
+        .enumerate()
+        .map(|(i, (mut field, size_of, selector))| {
+            // Selectors are decoded before the union they select.
+            if let Some(selector) = selector {
+                match names[..i]
+                    .iter()
+                    .position(|field| *field == selector.value())
+                {
+                    Some(index) => field.selector = Some(index),
+                    None => {
+                        return Err(syn::Error::new_spanned(
+                            selector,
+                            "Expected the name of an earlier field",
+                        ))
+                    }
+                }
+            }
+            if let Some(target) = size_of {
+                field.size_of = Some(match target.value().as_str() {
+                    "self" => SizeOf::Structure,
+                    name => match names.iter().position(|field| field == name) {
+                        Some(index) if index != i => SizeOf::Field(index),
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                target,
+                                "Expected the name of another field, or \"self\"",
+                            ))
+                        }
+                    },
+                });
             }
+            Ok(field)
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    // An unsized Option is present until the end of the buffer, so nothing
+    // but other unsized Options can follow it.
+    let trailing = |field: &TssField| is_option(&field.ty) && !field.sized && field.with.is_none();
+    if let Some(first) = fields.iter().position(trailing) {
+        if let Some(field) = fields[first..].iter().find(|field| !trailing(field)) {
+            return Err(syn::Error::new_spanned(
+                &field.member,
+                "Only trailing Option fields can omit #[tss(sized)]",
+            ));
         }
-    })
+    }
+    Ok(fields)
 }
 
-fn extract_fields(input: &DeriveInput) -> syn::Result<Vec<(Ident, Type)>> {
-    match &input.data {
-        Data::Struct(data_struct) => match &data_struct.fields {
-            Fields::Named(fields) => fields
-                .named
+fn extract_variants(input: &DeriveInput) -> syn::Result<Vec<TssVariant>> {
+    let Data::Enum(data_enum) = &input.data else {
+        unreachable!("only called on enums");
+    };
+    if data_enum.variants.is_empty() {
+        return Err(syn::Error::new_spanned(
+            input,
+            "Enums need at least one variant",
+        ));
+    }
+    data_enum
+        .variants
+        .iter()
+        .map(|variant| {
+            let mut discriminant = None;
+            for attr in variant
+                .attrs
                 .iter()
-                .map(|field| {
-                    let name = field.ident.as_ref().unwrap().clone();
-                    let ty = field.ty.clone();
-                    Ok((name, ty))
-                })
-                .collect(),
+                .filter(|attr| attr.path().is_ident("tss"))
+            {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("discriminant") {
+                        discriminant = Some(meta.value()?.parse()?);
+                        Ok(())
+                    } else {
+                        Err(meta.error("Unsupported tss attribute"))
+                    }
+                })?;
+            }
+            let discriminant = discriminant.ok_or_else(|| {
+                syn::Error::new_spanned(
+                    &variant.ident,
+                    "Expected #[tss(discriminant = ...)] on the variant",
+                )
+            })?;
+            Ok(TssVariant {
+                ident: variant.ident.clone(),
+                discriminant,
+                fields: parse_fields(&variant.fields)?,
+            })
+        })
+        .collect()
+}
+
+/// The `#[repr]` integer of a fieldless enum, and its variants.
+fn extract_unit_variants<'a>(
+    input: &'a DeriveInput,
+    container: &'a TssContainer,
+) -> syn::Result<(&'a Type, Vec<&'a Ident>)> {
+    let Data::Enum(data_enum) = &input.data else {
+        unreachable!("only called on enums");
+    };
+    let repr = container.repr.as_ref().ok_or_else(|| {
+        syn::Error::new_spanned(
+            &input.ident,
+            "Expected #[tss(tag = ...)], or #[repr] with an integer type on a fieldless enum",
+        )
+    })?;
+    if data_enum.variants.is_empty() {
+        return Err(syn::Error::new_spanned(
+            input,
+            "Enums need at least one variant",
+        ));
+    }
+    data_enum
+        .variants
+        .iter()
+        .map(|variant| match variant.fields {
+            Fields::Unit => Ok(&variant.ident),
             _ => Err(syn::Error::new_spanned(
-                input,
-                "Only named fields are supported",
+                variant,
+                "Enums with fields need #[tss(tag = ...)]",
             )),
-        },
-        _ => Err(syn::Error::new_spanned(input, "Only structs are supported")),
+        })
+        .collect::<syn::Result<Vec<_>>>()
+        .map(|variants| (repr, variants))
+}
+
+/// The tag type of an enum, which it must have.
+fn enum_tag<'a>(input: &DeriveInput, container: &'a TssContainer) -> syn::Result<&'a Type> {
+    container.tag.as_ref().ok_or_else(|| {
+        syn::Error::new_spanned(
+            &input.ident,
+            "Expected #[tss(tag = ...)] with the integer type of the discriminants",
+        )
+    })
+}
+
+fn parse_container(input: &DeriveInput) -> syn::Result<TssContainer> {
+    let mut container = TssContainer {
+        endian: Endian::Big,
+        tag: None,
+        selected: false,
+        repr: None,
+        validate: None,
+        exact: false,
+    };
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+    {
+        let reprs = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+        for repr in reprs {
+            if let Some(ident) = repr.path().get_ident() {
+                if INTEGERS.contains(&ident.to_string().as_str()) {
+                    container.repr = Some(syn::parse_quote!(#ident));
+                }
+            }
+        }
+    }
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("tss"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("endian") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                container.endian = match value.value().as_str() {
+                    "big" => Endian::Big,
+                    "little" => Endian::Little,
+                    _ => return Err(meta.error("Expected endian = \"big\" or \"little\"")),
+                };
+                Ok(())
+            } else if meta.path.is_ident("tag") || meta.path.is_ident("selector") {
+                if !matches!(input.data, Data::Enum(_)) {
+                    return Err(meta.error("tag and selector are only supported on enums"));
+                }
+                if container.tag.is_some() {
+                    return Err(meta.error("Expected one tag or selector"));
+                }
+                container.tag = Some(meta.value()?.parse()?);
+                container.selected = meta.path.is_ident("selector");
+                Ok(())
+            } else if meta.path.is_ident("validate") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                container.validate = Some(value.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("exact") {
+                if !matches!(input.data, Data::Struct(_)) {
+                    return Err(meta.error("exact is only supported on structs"));
+                }
+                container.exact = true;
+                Ok(())
+            } else {
+                Err(meta.error("Unsupported tss attribute"))
+            }
+        })?;
     }
+    Ok(container)
 }
 
-fn generate_field_serialize(field_name: &Ident, field_type: &Type) -> syn::Result<TokenStream2> {
-    let serialize_logic = match type_to_string(field_type).as_str() {
-        "u8" => quote! { buffer.push(self.#field_name); },
-        "u16" => quote! { buffer.extend_from_slice(&self.#field_name.to_be_bytes()); },
-        "u32" => quote! { buffer.extend_from_slice(&self.#field_name.to_be_bytes()); },
-        "u64" => quote! { buffer.extend_from_slice(&self.#field_name.to_be_bytes()); },
-        "i8" => quote! { buffer.push(self.#field_name as u8); },
-        "i16" => quote! { buffer.extend_from_slice(&self.#field_name.to_be_bytes()); },
-        "i32" => quote! { buffer.extend_from_slice(&self.#field_name.to_be_bytes()); },
-        "i64" => quote! { buffer.extend_from_slice(&self.#field_name.to_be_bytes()); },
-        ty if ty.starts_with("[u8;") => {
-            quote! { buffer.extend_from_slice(&self.#field_name); }
+/// The field, and the names of the fields its `size_of` is the size of and
+/// its `selector` is selected by.
+fn parse_field(
+    index: usize,
+    field: &Field,
+) -> syn::Result<(TssField, Option<syn::LitStr>, Option<syn::LitStr>)> {
+    let mut sized = false;
+    let mut with = None;
+    let mut size_of: Option<syn::LitStr> = None;
+    let mut length_prefix: Option<syn::LitStr> = None;
+    let mut length_unit: Option<syn::LitStr> = None;
+    let mut validate = None;
+    let mut selector: Option<syn::LitStr> = None;
+    let mut consts = Vec::new();
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("tss"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("sized") {
+                sized = true;
+                Ok(())
+            } else if meta.path.is_ident("with") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                with = Some(value.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("size_of") {
+                size_of = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("length_prefix") {
+                length_prefix = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("length_unit") {
+                length_unit = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("validate") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                validate = Some(value.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("selector") {
+                selector = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("const") {
+                let value: Expr = meta.value()?.parse()?;
+                // The type sets the size of the constant.
+                if let Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Int(int),
+                    ..
+                }) = &value
+                {
+                    if int.suffix().is_empty() {
+                        return Err(syn::Error::new_spanned(
+                            int,
+                            "Expected a typed constant, like 0x8001u16",
+                        ));
+                    }
+                }
+                consts.push(value);
+                Ok(())
+            } else {
+                Err(meta.error("Unsupported tss attribute"))
+            }
+        })?;
+    }
+    check_supported(&field.ty)?;
+    let kind = TypeKind::of(&field.ty);
+    if size_of.is_some() && (sized || with.is_some() || !kind.is_integer(&SIZE_INTEGERS)) {
+        return Err(syn::Error::new_spanned(
+            field,
+            "#[tss(size_of)] is only supported on unsigned integer fields",
+        ));
+    }
+    let length_prefix = match (length_prefix, length_unit) {
+        (Some(prefix), unit) => {
+            if sized || with.is_some() || !matches!(kind, TypeKind::Vec) {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "#[tss(length_prefix)] is only supported on Vec fields",
+                ));
+            }
+            if !LENGTH_INTEGERS.contains(&prefix.value().as_str()) {
+                return Err(syn::Error::new_spanned(
+                    prefix,
+                    "Expected length_prefix = \"u8\", \"u16\" or \"u32\"",
+                ));
+            }
+            let bytes = match unit.as_ref().map(|unit| unit.value()).as_deref() {
+                None | Some("elements") => false,
+                Some("bytes") => true,
+                Some(_) => {
+                    return Err(syn::Error::new_spanned(
+                        unit,
+                        "Expected length_unit = \"elements\" or \"bytes\"",
+                    ))
+                }
+            };
+            Some(LengthPrefix {
+                ty: prefix.parse()?,
+                bytes,
+            })
         }
-        ty if ty.starts_with("[") && ty.contains("u16") => {
-            quote! {
-                for item in &self.#field_name {
-                    buffer.extend_from_slice(&item.to_be_bytes());
+        (None, Some(unit)) => {
+            return Err(syn::Error::new_spanned(
+                unit,
+                "length_unit needs #[tss(length_prefix)]",
+            ))
+        }
+        (None, None) => None,
+    };
+    if sized && with.is_some() {
+        return Err(syn::Error::new_spanned(
+            field,
+            "#[tss(sized)] and #[tss(with)] cannot be combined",
+        ));
+    }
+    if selector.is_some()
+        && (sized || with.is_some() || size_of.is_some() || length_prefix.is_some())
+    {
+        return Err(syn::Error::new_spanned(
+            field,
+            "#[tss(selector)] cannot be combined with other encodings",
+        ));
+    }
+    if sized && !is_option(&field.ty) {
+        return Err(syn::Error::new_spanned(
+            &field.ty,
+            "#[tss(sized)] is only supported on Option fields",
+        ));
+    }
+    let member = match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(index.into()),
+    };
+    let field = TssField {
+        member,
+        ty: field.ty.clone(),
+        sized,
+        with,
+        size_of: None,
+        length_prefix,
+        validate,
+        selector: None,
+        consts,
+    };
+    Ok((field, size_of, selector))
+}
+
+/// Reject fields of little-endian structures whose encoding would mix in
+/// big-endian integers.
+fn check_byte_order(fields: &[TssField], container: &TssContainer) -> syn::Result<()> {
+    if container.endian == Endian::Big {
+        return Ok(());
+    }
+    for field in fields.iter().filter(|field| field.with.is_none()) {
+        let message = match TypeKind::of(&field.ty) {
+            TypeKind::Option => "Little-endian structures cannot have Option fields",
+            TypeKind::Vec if field.length_prefix.is_none() => {
+                "Vec fields of little-endian structures need #[tss(length_prefix)]"
+            }
+            _ => continue,
+        };
+        return Err(syn::Error::new_spanned(&field.member, message));
+    }
+    Ok(())
+}
+
+fn is_option(ty: &Type) -> bool {
+    matches!(TypeKind::of(ty), TypeKind::Option)
+}
+
+/// `value` is a reference to the `Option` of the field.
+fn generate_sized_serialize(value: &Ident) -> TokenStream2 {
+    quote! {
+        match #value {
+            Some(value) => {
+                let mut inner = ::tss_serde::TssWriter::new();
+                ::tss_serde::TssSerialize::serialize_to(value, &mut inner);
+                writer.write_tpm2b(inner.as_bytes());
+            }
+            None => writer.write_u16(0),
+        }
+    }
+}
+
+fn generate_sized_deserialize() -> TokenStream2 {
+    quote! {
+        {
+            let size = <u16 as ::tss_serde::TssDeserialize>::from_tss_reader(reader)? as usize;
+            if size == 0 {
+                None
+            } else {
+                let start = reader.position();
+                let mut inner = reader.sub_reader(size)?;
+                // Report positions in the outer reader, not the inner one.
+                let value = ::tss_serde::TssDeserialize::from_tss_reader(&mut inner).map_err(
+                    |mut error| {
+                        if let ::tss_serde::TssError::Field { position, .. } = &mut error {
+                            *position += start;
+                        }
+                        error
+                    },
+                )?;
+                if inner.remaining() != 0 {
+                    return Err(::tss_serde::TssError::InvalidFormat);
                 }
+                Some(value)
+            }
+        }
+    }
+}
+
+/// `value` is a reference to the `Vec` of the field.
+fn generate_prefixed_serialize(
+    value: &Ident,
+    name: &str,
+    prefix: &LengthPrefix,
+    endian: Endian,
+) -> syn::Result<TokenStream2> {
+    let ty = &prefix.ty;
+    let length = format_ident!("length");
+    let serialize_length = generate_field_serialize(&length, ty, endian)?;
+    let overflow = format!(
+        "the length of {} does not fit its {} prefix",
+        name,
+        type_to_string(ty)
+    );
+    let checked = |length: TokenStream2| {
+        quote! {
+            match <#ty>::try_from(#length) {
+                Ok(length) => length,
+                Err(_) => panic!("{}", #overflow),
+            }
+        }
+    };
+    Ok(if prefix.bytes {
+        let size = checked(quote! { inner.position() });
+        quote! {
+            let mut inner = ::tss_serde::TssWriter::new();
+            for item in #value {
+                ::tss_serde::TssSerialize::serialize_to(item, &mut inner);
+            }
+            let #length: &#ty = &#size;
+            #serialize_length
+            writer.write_bytes(inner.as_bytes());
+        }
+    } else {
+        let count = checked(quote! { #value.len() });
+        quote! {
+            let #length: &#ty = &#count;
+            #serialize_length
+            for item in #value {
+                ::tss_serde::TssSerialize::serialize_to(item, writer);
             }
         }
-        ty if ty.starts_with("[") && ty.contains("u32") => {
+    })
+}
+
+fn generate_prefixed_deserialize(
+    prefix: &LengthPrefix,
+    endian: Endian,
+) -> syn::Result<TokenStream2> {
+    let ty = &prefix.ty;
+    let deserialize_length = generate_field_deserialize(ty, endian)?;
+    Ok(if prefix.bytes {
+        quote! {
+            {
+                let length: #ty = #deserialize_length;
+                reader.read_sized_list(length as usize)?
+            }
+        }
+    } else {
+        quote! {
+            {
+                let length: #ty = #deserialize_length;
+                reader.read_list(length as usize)?
+            }
+        }
+    })
+}
+
+/// `value` is a reference to the field.
+fn generate_field_serialize(
+    value: &Ident,
+    field_type: &Type,
+    endian: Endian,
+) -> syn::Result<TokenStream2> {
+    let serialize_logic = match TypeKind::of(field_type) {
+        kind if endian == Endian::Little && kind.is_integer(&MULTI_BYTE_INTEGERS) => {
+            quote! { writer.write_bytes(&#value.to_le_bytes()); }
+        }
+        TypeKind::Integer("u8") => quote! { writer.write_u8(*#value); },
+        TypeKind::Integer("u16") => quote! { writer.write_u16(*#value); },
+        TypeKind::Integer("u32") => quote! { writer.write_u32(*#value); },
+        TypeKind::Integer("u64") => quote! { writer.write_u64(*#value); },
+        TypeKind::Array { element, .. } if TypeKind::of(element).is_integer(&["u8"]) => {
+            quote! { writer.write_bytes(#value); }
+        }
+        // Elements of little-endian structures follow their byte order.
+        TypeKind::Array { element, .. } if endian == Endian::Little => {
+            let item = format_ident!("item");
+            let serialize_item = generate_field_serialize(&item, element, endian)?;
             quote! {
-                for item in &self.#field_name {
-                    buffer.extend_from_slice(&item.to_be_bytes());
+                for #item in #value {
+                    #serialize_item
                 }
             }
         }
         _ => {
             quote! {
-                buffer.extend_from_slice(&::tss_serde::TssSerialize::to_tss_bytes(&self.#field_name));
+                ::tss_serde::TssSerialize::serialize_to(#value, writer);
             }
         }
     };
@@ -142,68 +1371,65 @@ fn generate_field_serialize(field_name: &Ident, field_type: &Type) -> syn::Resul
     Ok(serialize_logic)
 }
 
-fn generate_field_deserialize(field_type: &Type) -> syn::Result<TokenStream2> {
-    let deserialize_logic = match type_to_string(field_type).as_str() {
-        "u8" => quote! {
-            ::tss_serde::TssDeserialize::from_tss_reader(reader)?
-        },
-        "u16" => quote! {
-            ::tss_serde::TssDeserialize::from_tss_reader(reader)?
-        },
-        "u32" => quote! {
-            ::tss_serde::TssDeserialize::from_tss_reader(reader)?
-        },
-        "u64" => quote! {
-            ::tss_serde::TssDeserialize::from_tss_reader(reader)?
-        },
-        "i8" => quote! {
-            reader.read_u8()? as i8
-        },
-        "i16" => quote! {
-            {
-                let bytes = reader.read_array::<2>()?;
-                i16::from_be_bytes(bytes)
-            }
-        },
-        "i32" => quote! {
-            {
-                let bytes = reader.read_array::<4>()?;
-                i32::from_be_bytes(bytes)
-            }
-        },
-        "i64" => quote! {
+fn generate_fields_size(fields: &[TssField]) -> Vec<TokenStream2> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let size = generate_member_size(i, field);
+            let consts = &field.consts;
+            quote! { #size #(+ ::core::mem::size_of_val(&(#consts)))* }
+        })
+        .collect()
+}
+
+fn generate_member_size(i: usize, field: &TssField) -> TokenStream2 {
+    let binding = field.binding(i);
+    if let Some(with) = &field.with {
+        quote! {
             {
-                let bytes = reader.read_array::<8>()?;
-                i64::from_be_bytes(bytes)
-            }
-        },
-        ty if ty.starts_with("[u8;") => {
-            let size = extract_array_size(ty)?;
-            quote! {
-                reader.read_array::<#size>()?
+                let mut writer = ::tss_serde::TssWriter::new();
+                #with::serialize(#binding, &mut writer);
+                writer.position()
             }
         }
-        ty if ty.starts_with("[") && ty.contains("u16") => {
-            let count = extract_array_size(ty)?;
+    } else if let Some(prefix) = &field.length_prefix {
+        let ty = &prefix.ty;
+        quote! {
+            <#ty as ::tss_serde::TssFixedSize>::SERIALIZED_SIZE
+                + #binding.iter().map(::tss_serde::TssSerialize::tss_size).sum::<usize>()
+        }
+    } else if field.sized {
+        quote! {
+            2 + #binding.as_ref().map_or(0, ::tss_serde::TssSerialize::tss_size)
+        }
+    } else {
+        quote! { ::tss_serde::TssSerialize::tss_size(#binding) }
+    }
+}
+
+fn generate_field_deserialize(field_type: &Type, endian: Endian) -> syn::Result<TokenStream2> {
+    let deserialize_logic = match TypeKind::of(field_type) {
+        kind if endian == Endian::Little && kind.is_integer(&MULTI_BYTE_INTEGERS) => {
+            quote! { <#field_type>::from_le_bytes(reader.read_array()?) }
+        }
+        TypeKind::Array { element, len } if TypeKind::of(element).is_integer(&["u8"]) => {
             quote! {
-                {
-                    let mut array = [0u16; #count];
-                    for i in 0..#count {
-                        array[i] = ::tss_serde::TssDeserialize::from_tss_reader(reader)?;
-                    }
-                    array
-                }
+                reader.read_array::<{ #len }>()?
             }
         }
-        ty if ty.starts_with("[") && ty.contains("u32") => {
-            let count = extract_array_size(ty)?;
+        TypeKind::Array { element, len } if endian == Endian::Little => {
+            let deserialize_item = generate_field_deserialize(element, endian)?;
             quote! {
                 {
-                    let mut array = [0u32; #count];
-                    for i in 0..#count {
-                        array[i] = ::tss_serde::TssDeserialize::from_tss_reader(reader)?;
+                    let mut items = Vec::with_capacity(#len);
+                    for _ in 0..#len {
+                        items.push(#deserialize_item);
+                    }
+                    match <[#element; #len]>::try_from(items) {
+                        Ok(array) => array,
+                        Err(_) => unreachable!("every element was read"),
                     }
-                    array
                 }
             }
         }
@@ -220,24 +1446,3 @@ fn generate_field_deserialize(field_type: &Type) -> syn::Result<TokenStream2> {
 fn type_to_string(ty: &Type) -> String {
     quote! { #ty }.to_string().replace(" ", "")
 }
-
-fn extract_array_size(type_str: &str) -> syn::Result<usize> {
-    if let Some(start) = type_str.find(';') {
-        if let Some(end) = type_str.find(']') {
-            let size_str = &type_str[start + 1..end];
-            size_str
-                .parse()
-                .map_err(|_| syn::Error::new(proc_macro2::Span::call_site(), "Invalid array size"))
-        } else {
-            Err(syn::Error::new(
-                proc_macro2::Span::call_site(),
-                "Invalid array syntax",
-            ))
-        }
-    } else {
-        Err(syn::Error::new(
-            proc_macro2::Span::call_site(),
-            "Not a fixed-size array",
-        ))
-    }
-}
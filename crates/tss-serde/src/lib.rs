@@ -4,19 +4,53 @@
 //! to/from the binary format used by TPM (Trusted Platform Module) via TSS.
 
 // Re-export the derive macros
-pub use tss_serde_derive::{TssDeserialize, TssSerialize};
+pub use tss_serde_derive::{TssBitflags, TssDeserialize, TssFixedSize, TssSerialize};
+
+mod stream;
+pub use stream::*;
+
+/// The most elements a list may declare by default, see
+/// [`TssReader::max_list_length`]
+pub const DEFAULT_MAX_LIST_LENGTH: usize = 1 << 20;
+
+/// A position to rewind a [`TssReader`] to, see [`TssReader::checkpoint`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint(usize);
 
 /// A reader that consumes bytes from a buffer, tracking position automatically
 #[derive(Debug)]
 pub struct TssReader<'a> {
     data: &'a [u8],
     position: usize,
+    lenient_bools: bool,
+    max_list_length: usize,
 }
 
 impl<'a> TssReader<'a> {
     /// Create a new TssReader from a byte slice
     pub fn new(data: &'a [u8]) -> Self {
-        Self { data, position: 0 }
+        Self {
+            data,
+            position: 0,
+            lenient_bools: false,
+            max_list_length: DEFAULT_MAX_LIST_LENGTH,
+        }
+    }
+
+    /// Decode any nonzero byte as a `true` TPMI_YES_NO, as some TPMs send,
+    /// instead of rejecting all but 0x00 and 0x01
+    pub fn lenient_bools(mut self, lenient: bool) -> Self {
+        self.lenient_bools = lenient;
+        self
+    }
+
+    /// Reject lists declaring more than `max` elements with
+    /// [`TssError::LengthOverflow`], before decoding any of them. The
+    /// count of a list comes from the input, so it bounds what a malicious
+    /// quote or response can make a verifier allocate and loop over
+    pub fn max_list_length(mut self, max: usize) -> Self {
+        self.max_list_length = max;
+        self
     }
 
     /// Get the current position in the buffer
@@ -24,6 +58,18 @@ impl<'a> TssReader<'a> {
         self.position
     }
 
+    /// Mark the current position, to [`restore`](Self::restore) it if one
+    /// interpretation of the bytes that follow fails and another is tried
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.position)
+    }
+
+    /// Rewind to a checkpoint of this reader, to decode the bytes since
+    /// again
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.position = checkpoint.0.min(self.data.len());
+    }
+
     /// Get the remaining bytes count
     pub fn remaining(&self) -> usize {
         self.data.len().saturating_sub(self.position)
@@ -44,6 +90,21 @@ impl<'a> TssReader<'a> {
         Ok(value)
     }
 
+    /// Read a big-endian u16
+    pub fn read_u16(&mut self) -> Result<u16, TssError> {
+        self.read_array().map(u16::from_be_bytes)
+    }
+
+    /// Read a big-endian u32
+    pub fn read_u32(&mut self) -> Result<u32, TssError> {
+        self.read_array().map(u32::from_be_bytes)
+    }
+
+    /// Read a big-endian u64
+    pub fn read_u64(&mut self) -> Result<u64, TssError> {
+        self.read_array().map(u64::from_be_bytes)
+    }
+
     /// Read exactly `count` bytes into a new Vec
     pub fn read_bytes(&mut self, count: usize) -> Result<Vec<u8>, TssError> {
         if !self.has_remaining(count) {
@@ -65,6 +126,104 @@ impl<'a> TssReader<'a> {
         Ok(array)
     }
 
+    /// Read a TPM2B: a u16 size followed by that many bytes
+    pub fn read_tpm2b(&mut self) -> Result<Vec<u8>, TssError> {
+        let size = self.read_u16()? as usize;
+        self.read_bytes(size)
+    }
+
+    /// Split off the next `len` bytes as a reader of their own, with the
+    /// settings of this one, and advance past them. Size-prefixed members,
+    /// like a TPM2B_PUBLIC, are decoded with it so they cannot read beyond
+    /// their declared size. Positions in the child start at 0
+    pub fn sub_reader(&mut self, len: usize) -> Result<TssReader<'a>, TssError> {
+        if !self.has_remaining(len) {
+            return Err(TssError::InsufficientData);
+        }
+        let data = &self.data[self.position..self.position + len];
+        self.position += len;
+        Ok(TssReader {
+            data,
+            position: 0,
+            lenient_bools: self.lenient_bools,
+            max_list_length: self.max_list_length,
+        })
+    }
+
+    /// Read `length` elements of a list whose length was read already,
+    /// failing with `LengthOverflow` beyond
+    /// [`max_list_length`](Self::max_list_length)
+    pub fn read_list<T: TssDeserialize>(&mut self, length: usize) -> Result<Vec<T>, TssError> {
+        if length > self.max_list_length {
+            return Err(TssError::LengthOverflow {
+                length,
+                max: self.max_list_length,
+            });
+        }
+
+        // The length comes from the input, so only reserve what the
+        // remaining bytes could hold.
+        let mut list = Vec::with_capacity(length.min(self.remaining()));
+        for _ in 0..length {
+            list.push(T::from_tss_reader(self)?);
+        }
+        Ok(list)
+    }
+
+    /// Read the elements of a list that takes `size` bytes, whose size was
+    /// read already. Fails with `InvalidFormat` if an element takes no bytes,
+    /// so the list would never end, and with `LengthOverflow` beyond
+    /// [`max_list_length`](Self::max_list_length) elements
+    pub fn read_sized_list<T: TssDeserialize>(&mut self, size: usize) -> Result<Vec<T>, TssError> {
+        let start = self.position;
+        let mut inner = self.sub_reader(size)?;
+        let mut list = Vec::new();
+        while inner.remaining() > 0 {
+            if list.len() == self.max_list_length {
+                return Err(TssError::LengthOverflow {
+                    length: list.len() + 1,
+                    max: self.max_list_length,
+                });
+            }
+            let remaining = inner.remaining();
+            // Report positions in the outer reader, not the inner one.
+            let item = T::from_tss_reader(&mut inner).map_err(|mut error| {
+                if let TssError::Field { position, .. } = &mut error {
+                    *position += start;
+                }
+                error
+            })?;
+            if inner.remaining() == remaining {
+                return Err(TssError::InvalidFormat);
+            }
+            list.push(item);
+        }
+        Ok(list)
+    }
+
+    /// Read the next byte without consuming it
+    pub fn peek_u8(&self) -> Result<u8, TssError> {
+        self.peek_array().map(u8::from_be_bytes)
+    }
+
+    /// Read the next big-endian u16, such as the TPM_ALG_ID selecting a
+    /// union member, without consuming it
+    pub fn peek_u16(&self) -> Result<u16, TssError> {
+        self.peek_array().map(u16::from_be_bytes)
+    }
+
+    /// Read the next big-endian u32 without consuming it
+    pub fn peek_u32(&self) -> Result<u32, TssError> {
+        self.peek_array().map(u32::from_be_bytes)
+    }
+
+    fn peek_array<const N: usize>(&self) -> Result<[u8; N], TssError> {
+        self.peek_remaining()
+            .get(..N)
+            .map(|bytes| bytes.try_into().unwrap())
+            .ok_or(TssError::InsufficientData)
+    }
+
     /// Get a slice of the remaining data without consuming it
     pub fn peek_remaining(&self) -> &[u8] {
         &self.data[self.position..]
@@ -80,9 +239,192 @@ impl<'a> TssReader<'a> {
     }
 }
 
+/// A writer that appends bytes to a single growing buffer, so nested
+/// structures serialize without allocating their own. A writer to a stream
+/// only buffers up to a chunk before passing the bytes on
+#[derive(Default)]
+pub struct TssWriter<'a> {
+    data: Vec<u8>,
+    /// Where full chunks of `data` go, for a writer to a stream
+    sink: Option<&'a mut dyn std::io::Write>,
+    /// Bytes already passed on to `sink`
+    flushed: usize,
+    /// The first error of `sink`, after which writes are dropped
+    error: Option<std::io::Error>,
+}
+
+/// How many bytes a writer to a stream buffers
+const WRITE_CHUNK_SIZE: usize = 8192;
+
+impl<'a> TssWriter<'a> {
+    /// Create an empty TssWriter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty TssWriter with room for `capacity` bytes
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            data: Vec::with_capacity(capacity),
+            ..Self::default()
+        }
+    }
+
+    /// Create a TssWriter passing the bytes on to `sink` in chunks, until
+    /// [`finish`](Self::finish)
+    pub fn to_stream(sink: &'a mut dyn std::io::Write) -> Self {
+        Self {
+            data: Vec::with_capacity(WRITE_CHUNK_SIZE),
+            sink: Some(sink),
+            ..Self::default()
+        }
+    }
+
+    /// Get the number of bytes written so far
+    pub fn position(&self) -> usize {
+        self.flushed + self.data.len()
+    }
+
+    /// Write a single byte
+    pub fn write_u8(&mut self, value: u8) {
+        self.write_bytes(&[value]);
+    }
+
+    /// Write a big-endian u16
+    pub fn write_u16(&mut self, value: u16) {
+        self.write_bytes(&value.to_be_bytes());
+    }
+
+    /// Write a big-endian u32
+    pub fn write_u32(&mut self, value: u32) {
+        self.write_bytes(&value.to_be_bytes());
+    }
+
+    /// Write a big-endian u64
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_be_bytes());
+    }
+
+    /// Write raw bytes, without a size prefix
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        if self.sink.is_some() && self.data.len() + bytes.len() > WRITE_CHUNK_SIZE {
+            self.flush();
+            if bytes.len() >= WRITE_CHUNK_SIZE {
+                self.pass_on(bytes);
+                return;
+            }
+        }
+        self.data.extend_from_slice(bytes);
+    }
+
+    /// Write `bytes` as a TPM2B: their u16 size followed by the bytes.
+    ///
+    /// Panics if there are more than `u16::MAX` bytes, which the size
+    /// cannot hold
+    pub fn write_tpm2b(&mut self, bytes: &[u8]) {
+        let size = u16::try_from(bytes.len())
+            .unwrap_or_else(|_| panic!("a TPM2B of {} bytes does not fit its size", bytes.len()));
+        self.write_u16(size);
+        self.write_bytes(bytes);
+    }
+
+    /// Get the bytes written so far. A writer to a stream only has those
+    /// not passed on yet
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consume the writer, returning the bytes written
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Pass the remaining bytes on to the stream and flush it, returning the
+    /// first error of the stream
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.flush();
+        if let (Some(sink), None) = (self.sink.as_mut(), &self.error) {
+            if let Err(error) = sink.flush() {
+                self.error = Some(error);
+            }
+        }
+        self.error.map_or(Ok(()), Err)
+    }
+
+    fn flush(&mut self) {
+        let data = std::mem::take(&mut self.data);
+        self.pass_on(&data);
+        self.data = data;
+        self.data.clear();
+    }
+
+    fn pass_on(&mut self, bytes: &[u8]) {
+        self.flushed += bytes.len();
+        if let (Some(sink), None) = (self.sink.as_mut(), &self.error) {
+            if let Err(error) = sink.write_all(bytes) {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for TssWriter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TssWriter")
+            .field("data", &self.data)
+            .field("stream", &self.sink.is_some())
+            .field("flushed", &self.flushed)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl From<Vec<u8>> for TssWriter<'_> {
+    /// Create a TssWriter appending to `data`
+    fn from(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            ..Self::default()
+        }
+    }
+}
+
 /// Trait for types that can be serialized to TSS binary format
 pub trait TssSerialize {
-    fn to_tss_bytes(&self) -> Vec<u8>;
+    /// Serialize to a new Vec (convenience method)
+    fn to_tss_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        self.serialize_into(&mut buffer);
+        buffer
+    }
+
+    /// Serialize by appending to `buffer`, reusing its allocation
+    fn serialize_into(&self, buffer: &mut Vec<u8>) {
+        let mut writer = TssWriter::from(std::mem::take(buffer));
+        self.serialize_to(&mut writer);
+        *buffer = writer.into_bytes();
+    }
+
+    /// Serialize into a TssWriter (primary method)
+    fn serialize_to(&self, writer: &mut TssWriter);
+
+    /// Serialize straight to a stream, such as a `TcpStream` or a device,
+    /// buffering at most a chunk
+    fn write_tss(&self, stream: &mut impl std::io::Write) -> std::io::Result<()>
+    where
+        Self: Sized,
+    {
+        let mut writer = TssWriter::to_stream(stream);
+        self.serialize_to(&mut writer);
+        writer.finish()
+    }
+
+    /// Get the number of bytes `serialize_to` writes. The default
+    /// serializes to measure; implementations that know their size override
+    /// it, so command lengths are computed without serializing twice
+    fn tss_size(&self) -> usize {
+        self.to_tss_bytes().len()
+    }
 }
 
 /// Trait for types that can be deserialized from TSS binary format
@@ -93,16 +435,256 @@ pub trait TssDeserialize: Sized {
         Self::from_tss_reader(&mut reader)
     }
 
+    /// Deserialize from raw bytes that must hold exactly one value, failing
+    /// with [`TssError::TrailingBytes`] if any are left over
+    fn from_tss_bytes_exact(bytes: &[u8]) -> Result<Self, TssError> {
+        let mut reader = TssReader::new(bytes);
+        let value = Self::from_tss_reader(&mut reader)?;
+        match reader.remaining() {
+            0 => Ok(value),
+            remaining => Err(TssError::TrailingBytes { remaining }),
+        }
+    }
+
     /// Deserialize from a TssReader (primary method)
     fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError>;
 }
 
+/// Trait for unions whose member is selected by a value before them, like
+/// the TPMU types: they serialize without it and deserialize with it.
+/// Deriving `TssDeserialize` on an enum with `#[tss(selector = ...)]`
+/// implements it
+pub trait TssSelect: Sized {
+    /// The type of the value selecting the member, such as a TPM_ALG_ID
+    type Selector;
+
+    /// Deserialize the member selected by `selector` from a TssReader
+    fn from_tss_reader_with(
+        selector: &Self::Selector,
+        reader: &mut TssReader,
+    ) -> Result<Self, TssError>;
+}
+
+/// Trait for types that serialize to the same number of bytes whatever
+/// their value, such as command and response headers. Structures of such
+/// fields derive it
+pub trait TssFixedSize {
+    /// The number of bytes every value serializes to
+    const SERIALIZED_SIZE: usize;
+}
+
+/// A TPM2B sized buffer: a big-endian u16 size followed by that many
+/// bytes, such as a TPM2B_DIGEST or TPM2B_DATA. To write borrowed bytes
+/// without copying them into a `Tpm2b`, use [`TssWriter::write_tpm2b`].
+///
+/// Serializing a buffer of more than `u16::MAX` bytes panics.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Tpm2b(pub Vec<u8>);
+
+impl From<Vec<u8>> for Tpm2b {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<&[u8]> for Tpm2b {
+    fn from(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl std::ops::Deref for Tpm2b {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl TssSerialize for Tpm2b {
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_tpm2b(&self.0);
+    }
+
+    fn tss_size(&self) -> usize {
+        2 + self.0.len()
+    }
+}
+
+impl TssDeserialize for Tpm2b {
+    fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        reader.read_tpm2b().map(Self)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl Tpm2b {
+    /// An arbitrary buffer of at most `max` bytes, for TPM2Bs nested in
+    /// other sized structures.
+    pub fn arbitrary_with_max(
+        u: &mut arbitrary::Unstructured<'_>,
+        max: usize,
+    ) -> arbitrary::Result<Self> {
+        // Choosing the length consumes input too, so bound it after.
+        let len = u.int_in_range(0..=max)?.min(u.len());
+        Ok(Self(u.bytes(len)?.to_vec()))
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Tpm2b {
+    /// Only buffers whose size fits the u16 prefix.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Self::arbitrary_with_max(u, u16::MAX as usize)
+    }
+}
+
+/// Check that `T` survives an encode/decode round trip, for a value of it
+/// generated from `u`: decoding its bytes yields it back, consuming them
+/// all, and [`TssSerialize::tss_size`] is their length. Fuzz targets and
+/// property tests of derived structures call it with their input; it fails
+/// only if `u` cannot generate a value.
+///
+/// # Panics
+///
+/// If the round trip does not hold
+#[cfg(feature = "arbitrary")]
+pub fn assert_roundtrip<'a, T>(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<()>
+where
+    T: arbitrary::Arbitrary<'a> + TssSerialize + TssDeserialize + PartialEq + std::fmt::Debug,
+{
+    let value = T::arbitrary(u)?;
+    let bytes = value.to_tss_bytes();
+    assert_eq!(value.tss_size(), bytes.len(), "tss_size of {:?}", value);
+    match T::from_tss_bytes_exact(&bytes) {
+        Ok(decoded) => assert_eq!(decoded, value, "decoding {:02x?}", bytes),
+        Err(error) => panic!("decoding {:?} from {:02x?}: {}", value, bytes, error),
+    }
+    Ok(())
+}
+
 /// Errors that can occur during TSS serialization/deserialization
 #[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum TssError {
     InsufficientData,
     InvalidFormat,
     Custom(String),
+    /// A value outside of those its type allows, such as an unknown
+    /// algorithm or a TPMI_YES_NO other than 0 or 1
+    InvalidValue {
+        /// What the value is, such as the type or field it was read as
+        field: &'static str,
+        value: u64,
+    },
+    /// Reading or writing the underlying stream failed
+    Io(IoError),
+    /// A list declared more elements than the reader allows, see
+    /// [`TssReader::max_list_length`]
+    LengthOverflow {
+        length: usize,
+        max: usize,
+    },
+    /// Bytes were left over after the value, see
+    /// [`TssDeserialize::from_tss_bytes_exact`]
+    TrailingBytes {
+        remaining: usize,
+    },
+    /// Decoding a field of a derived structure failed
+    Field {
+        /// The outermost structure being decoded
+        structure: &'static str,
+        /// The fields from `structure` down to the one that failed
+        path: Vec<&'static str>,
+        /// The type of the field that failed
+        expected: &'static str,
+        /// The reader position where that field starts
+        position: usize,
+        source: Box<TssError>,
+    },
+}
+
+/// An I/O error in a [`TssError`], shared so errors stay cheap to clone.
+/// Errors compare equal by kind and message
+#[derive(Debug, Clone)]
+pub struct IoError(std::sync::Arc<std::io::Error>);
+
+impl IoError {
+    /// The wrapped error
+    pub fn get_ref(&self) -> &std::io::Error {
+        &self.0
+    }
+
+    /// The kind of the wrapped error
+    pub fn kind(&self) -> std::io::ErrorKind {
+        self.0.kind()
+    }
+}
+
+impl PartialEq for IoError {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind() == other.kind() && self.0.to_string() == other.0.to_string()
+    }
+}
+
+impl std::fmt::Display for IoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<std::io::Error> for TssError {
+    fn from(error: std::io::Error) -> Self {
+        TssError::Io(IoError(std::sync::Arc::new(error)))
+    }
+}
+
+impl TssError {
+    /// Record that this error occurred decoding `field`, of type
+    /// `expected`, of `structure`, starting at `position`. Errors of nested
+    /// structures keep their innermost field, type and position and extend
+    /// the path outwards.
+    pub fn in_field(
+        self,
+        structure: &'static str,
+        field: &'static str,
+        expected: &'static str,
+        position: usize,
+    ) -> Self {
+        match self {
+            TssError::Field {
+                mut path,
+                expected,
+                position,
+                source,
+                ..
+            } => {
+                path.insert(0, field);
+                TssError::Field {
+                    structure,
+                    path,
+                    expected,
+                    position,
+                    source,
+                }
+            }
+            error => TssError::Field {
+                structure,
+                path: vec![field],
+                expected,
+                position,
+                source: Box::new(error),
+            },
+        }
+    }
+
+    /// The error without the field context
+    pub fn root(&self) -> &TssError {
+        match self {
+            TssError::Field { source, .. } => source.root(),
+            error => error,
+        }
+    }
 }
 
 impl std::fmt::Display for TssError {
@@ -111,34 +693,87 @@ impl std::fmt::Display for TssError {
             TssError::InsufficientData => write!(f, "Insufficient data for deserialization"),
             TssError::InvalidFormat => write!(f, "Invalid TSS format"),
             TssError::Custom(msg) => write!(f, "TSS error: {}", msg),
+            TssError::InvalidValue { field, value } => {
+                write!(f, "Invalid {} {:#x}", field, value)
+            }
+            TssError::Io(error) => write!(f, "I/O error: {}", error),
+            TssError::TrailingBytes { remaining } => {
+                write!(f, "{} unexpected bytes after the value", remaining)
+            }
+            TssError::LengthOverflow { length, max } => {
+                write!(
+                    f,
+                    "List of {} elements exceeds the maximum of {}",
+                    length, max
+                )
+            }
+            TssError::Field {
+                structure,
+                path,
+                expected,
+                position,
+                source,
+            } => write!(
+                f,
+                "{} decoding {}.{} ({}) at byte {}",
+                source,
+                structure,
+                path.join("."),
+                expected,
+                position
+            ),
         }
     }
 }
 
-impl std::error::Error for TssError {}
+impl std::error::Error for TssError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TssError::Io(error) => Some(error.get_ref()),
+            TssError::Field { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 // Implement TssSerialize/TssDeserialize for basic types
 impl TssSerialize for u8 {
-    fn to_tss_bytes(&self) -> Vec<u8> {
-        vec![*self]
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_u8(*self);
+    }
+
+    fn tss_size(&self) -> usize {
+        1
     }
 }
 
 impl TssSerialize for u16 {
-    fn to_tss_bytes(&self) -> Vec<u8> {
-        self.to_be_bytes().to_vec()
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_u16(*self);
+    }
+
+    fn tss_size(&self) -> usize {
+        2
     }
 }
 
 impl TssSerialize for u32 {
-    fn to_tss_bytes(&self) -> Vec<u8> {
-        self.to_be_bytes().to_vec()
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_u32(*self);
+    }
+
+    fn tss_size(&self) -> usize {
+        4
     }
 }
 
 impl TssSerialize for u64 {
-    fn to_tss_bytes(&self) -> Vec<u8> {
-        self.to_be_bytes().to_vec()
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_u64(*self);
+    }
+
+    fn tss_size(&self) -> usize {
+        8
     }
 }
 
@@ -150,67 +785,233 @@ impl TssDeserialize for u8 {
 
 impl TssDeserialize for u16 {
     fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError> {
-        let bytes = reader.read_array::<2>()?;
-        Ok(u16::from_be_bytes(bytes))
+        reader.read_u16()
     }
 }
 
 impl TssDeserialize for u32 {
     fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError> {
-        let bytes = reader.read_array::<4>()?;
-        Ok(u32::from_be_bytes(bytes))
+        reader.read_u32()
     }
 }
 
 impl TssDeserialize for u64 {
     fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError> {
-        let bytes = reader.read_array::<8>()?;
-        Ok(u64::from_be_bytes(bytes))
+        reader.read_u64()
+    }
+}
+
+macro_rules! impl_fixed_size {
+    ($($integer:ty),*) => {
+        $(
+            impl TssFixedSize for $integer {
+                const SERIALIZED_SIZE: usize = std::mem::size_of::<$integer>();
+            }
+        )*
+    };
+}
+
+impl_fixed_size!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+// Signed integers are encoded as the two's complement of their width, like
+// the INT8 to INT64 types of the TPM.
+macro_rules! impl_signed {
+    ($($signed:ty => $unsigned:ty),*) => {
+        $(
+            impl TssSerialize for $signed {
+                fn serialize_to(&self, writer: &mut TssWriter) {
+                    (*self as $unsigned).serialize_to(writer);
+                }
+
+                fn tss_size(&self) -> usize {
+                    std::mem::size_of::<$signed>()
+                }
+            }
+
+            impl TssDeserialize for $signed {
+                fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+                    Ok(<$unsigned>::from_tss_reader(reader)? as $signed)
+                }
+            }
+        )*
+    };
+}
+
+impl_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64);
+
+/// A TPMI_YES_NO: 0x00 or 0x01, see [`TssReader::lenient_bools`].
+impl TssSerialize for bool {
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_u8(*self as u8);
+    }
+
+    fn tss_size(&self) -> usize {
+        1
     }
 }
 
+impl TssFixedSize for bool {
+    const SERIALIZED_SIZE: usize = 1;
+}
+
 impl TssDeserialize for bool {
     fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError> {
         let bytes = reader.read_array::<1>()?;
         match bytes[0] {
             0 => Ok(false),
             1 => Ok(true),
-            _ => Err(TssError::InvalidFormat),
+            _ if reader.lenient_bools => Ok(true),
+            value => Err(TssError::InvalidValue {
+                field: "TPMI_YES_NO",
+                value: value.into(),
+            }),
         }
     }
 }
 
-// Implementation for fixed-size arrays
-impl<const N: usize> TssSerialize for [u8; N] {
-    fn to_tss_bytes(&self) -> Vec<u8> {
-        self.to_vec()
+/// A fixed-size array: its elements back to back, with no count prefix,
+/// like the digest buffers and PCR selection arrays of the TPM.
+impl<T, const N: usize> TssSerialize for [T; N]
+where
+    T: TssSerialize,
+{
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        for item in self {
+            item.serialize_to(writer);
+        }
+    }
+
+    fn tss_size(&self) -> usize {
+        self.iter().map(T::tss_size).sum()
     }
 }
 
-impl<const N: usize> TssDeserialize for [u8; N] {
+impl<T, const N: usize> TssDeserialize for [T; N]
+where
+    T: TssDeserialize,
+{
     fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError> {
-        reader.read_array::<N>()
+        let mut items = Vec::with_capacity(N);
+        for _ in 0..N {
+            items.push(T::from_tss_reader(reader)?);
+        }
+        match items.try_into() {
+            Ok(array) => Ok(array),
+            Err(_) => unreachable!("read exactly {} elements", N),
+        }
     }
 }
 
-impl<T> TssDeserialize for Vec<T>
+impl<T, const N: usize> TssFixedSize for [T; N]
+where
+    T: TssFixedSize,
+{
+    const SERIALIZED_SIZE: usize = T::SERIALIZED_SIZE * N;
+}
+
+/// An optional trailing member, such as the creation data at the end of a
+/// response. `None` serializes to nothing, and deserializes when the reader
+/// is at the end of its buffer, so only the last members of a structure can
+/// be optional this way. Members followed by others are size-prefixed
+/// instead, with `#[tss(sized)]` in the derive: a u16 size of 0 is `None`.
+impl<T> TssSerialize for Option<T>
+where
+    T: TssSerialize,
+{
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        if let Some(value) = self {
+            value.serialize_to(writer);
+        }
+    }
+
+    fn tss_size(&self) -> usize {
+        self.as_ref().map_or(0, T::tss_size)
+    }
+}
+
+impl<T> TssDeserialize for Option<T>
 where
     T: TssDeserialize,
 {
     fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError> {
-        // Read length as u32
-        let length = u32::from_tss_reader(reader)? as usize;
+        if reader.remaining() == 0 {
+            return Ok(None);
+        }
+        T::from_tss_reader(reader).map(Some)
+    }
+}
 
-        // Read each element
-        let mut vec = Vec::with_capacity(length);
-        for _ in 0..length {
-            vec.push(T::from_tss_reader(reader)?);
+impl<T> TssSerialize for Vec<T>
+where
+    T: TssSerialize,
+{
+    /// Serialized as a TPML-style list: the u32 element count followed by
+    /// each element.
+    fn serialize_to(&self, writer: &mut TssWriter) {
+        writer.write_u32(self.len() as u32);
+        for item in self {
+            item.serialize_to(writer);
         }
+    }
 
-        Ok(vec)
+    fn tss_size(&self) -> usize {
+        4 + self.iter().map(T::tss_size).sum::<usize>()
+    }
+}
+
+impl<T> TssDeserialize for Vec<T>
+where
+    T: TssDeserialize,
+{
+    fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+        let length = reader.read_u32()? as usize;
+        reader.read_list(length)
     }
 }
 
+// Tuples encode their members in order, like the fields of a structure,
+// for command bodies that are not worth a named type.
+macro_rules! impl_tuple {
+    ($(($($name:ident),+)),*) => {
+        $(
+            impl<$($name: TssSerialize),+> TssSerialize for ($($name,)+) {
+                #[allow(non_snake_case)]
+                fn serialize_to(&self, writer: &mut TssWriter) {
+                    let ($($name,)+) = self;
+                    $($name.serialize_to(writer);)+
+                }
+
+                #[allow(non_snake_case)]
+                fn tss_size(&self) -> usize {
+                    let ($($name,)+) = self;
+                    0 $(+ $name.tss_size())+
+                }
+            }
+
+            impl<$($name: TssDeserialize),+> TssDeserialize for ($($name,)+) {
+                fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+                    Ok(($($name::from_tss_reader(reader)?,)+))
+                }
+            }
+
+            impl<$($name: TssFixedSize),+> TssFixedSize for ($($name,)+) {
+                const SERIALIZED_SIZE: usize = 0 $(+ $name::SERIALIZED_SIZE)+;
+            }
+        )*
+    };
+}
+
+impl_tuple!(
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+    (A, B, C, D, E, F, G),
+    (A, B, C, D, E, F, G, H)
+);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -248,6 +1049,91 @@ mod tests {
         assert_eq!(reader.remaining(), 0);
     }
 
+    #[test]
+    fn test_sub_reader() {
+        let data = [0x00, 0x02, 0x00, 0x00, 0x00, 0x07, 0xFF];
+        let mut reader = TssReader::new(&data).lenient_bools(true);
+        let size = u16::from_tss_reader(&mut reader).unwrap() as usize;
+        let mut inner = reader.sub_reader(size).unwrap();
+        assert_eq!(reader.position(), 4);
+        assert_eq!(reader.remaining(), 3);
+
+        // The child ends at the region, even though the parent goes on.
+        assert_eq!(inner.position(), 0);
+        assert_eq!(
+            u32::from_tss_reader(&mut inner),
+            Err(TssError::InsufficientData)
+        );
+        assert_eq!(bool::from_tss_reader(&mut inner), Ok(false));
+        assert_eq!(bool::from_tss_reader(&mut inner), Ok(false));
+        assert_eq!(inner.remaining(), 0);
+
+        assert_eq!(u16::from_tss_reader(&mut reader), Ok(7));
+        assert!(reader.sub_reader(2).is_err());
+        assert_eq!(reader.position(), 6);
+        let mut inner = reader.sub_reader(1).unwrap();
+        assert_eq!(bool::from_tss_reader(&mut inner), Ok(true));
+    }
+
+    #[test]
+    fn test_peek() {
+        let mut reader = TssReader::new(&[0x00, 0x23, 0x00, 0x0B, 0xFF]);
+        assert_eq!(reader.peek_u8(), Ok(0x00));
+        assert_eq!(reader.peek_u16(), Ok(0x0023));
+        assert_eq!(reader.peek_u32(), Ok(0x0023_000B));
+        assert_eq!(reader.position(), 0);
+
+        reader.skip(2).unwrap();
+        assert_eq!(reader.peek_u16(), Ok(0x000B));
+        assert_eq!(reader.peek_u32(), Err(TssError::InsufficientData));
+        assert_eq!(u16::from_tss_reader(&mut reader), Ok(0x000B));
+        assert_eq!(reader.peek_u8(), Ok(0xFF));
+        assert_eq!(reader.peek_u16(), Err(TssError::InsufficientData));
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let data = [0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x02];
+        let mut reader = TssReader::new(&data);
+        let start = reader.checkpoint();
+
+        // Try a list of u32s, which does not fit, then fall back to u16s.
+        assert!(Vec::<u32>::from_tss_reader(&mut reader).is_err());
+        reader.restore(start);
+        assert_eq!(reader.position(), 0);
+        assert_eq!(Vec::<u16>::from_tss_reader(&mut reader), Ok(vec![1, 2]));
+        assert_eq!(reader.remaining(), 0);
+
+        reader.restore(start);
+        assert_eq!(u32::from_tss_reader(&mut reader), Ok(2));
+    }
+
+    #[test]
+    fn test_read_integers() {
+        let data = [0x01, 0x02, 0x00, 0x00, 0x00, 0x03, 0, 0, 0, 0, 0, 0x01, 0];
+        let mut reader = TssReader::new(&data);
+        assert_eq!(reader.read_u16(), Ok(0x0102));
+        assert_eq!(reader.read_u32(), Ok(3));
+        assert_eq!(reader.read_u64(), Err(TssError::InsufficientData));
+        assert_eq!(reader.position(), 6);
+        reader.restore(Checkpoint(4));
+        assert_eq!(reader.read_u64(), Ok(0x0003_0000_0000_0001));
+    }
+
+    #[test]
+    fn test_from_tss_bytes_exact() {
+        assert_eq!(u16::from_tss_bytes_exact(&[0x00, 0x01]), Ok(1));
+        assert_eq!(u16::from_tss_bytes(&[0x00, 0x01, 0xFF, 0xFF]), Ok(1));
+        assert_eq!(
+            u16::from_tss_bytes_exact(&[0x00, 0x01, 0xFF, 0xFF]),
+            Err(TssError::TrailingBytes { remaining: 2 })
+        );
+        assert_eq!(
+            u16::from_tss_bytes_exact(&[0x00]),
+            Err(TssError::InsufficientData)
+        );
+    }
+
     #[test]
     fn test_struct_deserialize() {
         let data = vec![0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0xAA, 0xBB, 0xCC, 0xDD];
@@ -257,4 +1143,326 @@ mod tests {
         assert_eq!(test_struct.b, 2);
         assert_eq!(test_struct.c, [0xAA, 0xBB, 0xCC, 0xDD]);
     }
+
+    #[test]
+    fn test_tss_writer() {
+        let mut writer = TssWriter::with_capacity(15);
+        writer.write_u8(0x01);
+        writer.write_u16(0x0203);
+        0x0405_0607u32.serialize_to(&mut writer);
+        writer.write_u64(0x0809_0A0B_0C0D_0E0F);
+        assert_eq!(writer.position(), 15);
+        writer.write_bytes(&[0x10]);
+
+        let expected: Vec<u8> = (0x01..=0x10).collect();
+        assert_eq!(writer.as_bytes(), expected);
+        assert_eq!(writer.into_bytes(), expected);
+    }
+
+    #[test]
+    fn test_serialize_into() {
+        let mut buffer = vec![0xAA];
+        0x0102u16.serialize_into(&mut buffer);
+        [0x03u8, 0x04].serialize_into(&mut buffer);
+        assert_eq!(buffer, [0xAA, 0x01, 0x02, 0x03, 0x04]);
+
+        let mut writer = TssWriter::from(buffer);
+        writer.write_u8(0x05);
+        assert_eq!(writer.position(), 6);
+    }
+
+    #[test]
+    fn test_vec_serialize() {
+        let data = [0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x02];
+        assert_eq!(vec![1u16, 2].to_tss_bytes(), data);
+        assert_eq!(Vec::<u32>::new().to_tss_bytes(), [0, 0, 0, 0]);
+
+        let nested = vec![vec![0xAAu8], vec![]];
+        let bytes = nested.to_tss_bytes();
+        assert_eq!(bytes, [0, 0, 0, 2, 0, 0, 0, 1, 0xAA, 0, 0, 0, 0],);
+        assert_eq!(Vec::<Vec<u8>>::from_tss_bytes(&bytes).unwrap(), nested);
+    }
+
+    #[test]
+    fn test_option() {
+        assert_eq!(Some(0x0102u16).to_tss_bytes(), [0x01, 0x02]);
+        assert_eq!(None::<u16>.to_tss_bytes(), []);
+
+        assert_eq!(
+            Option::<u16>::from_tss_bytes(&[0x01, 0x02]),
+            Ok(Some(0x0102))
+        );
+        assert_eq!(Option::<u16>::from_tss_bytes(&[]), Ok(None));
+        assert_eq!(
+            Option::<u16>::from_tss_bytes(&[0x01]),
+            Err(TssError::InsufficientData)
+        );
+    }
+
+    #[test]
+    fn test_tpm2b() {
+        let buffer = Tpm2b::from(&[0xAA, 0xBB][..]);
+        let bytes = buffer.to_tss_bytes();
+        assert_eq!(bytes, [0x00, 0x02, 0xAA, 0xBB]);
+        assert_eq!(Tpm2b::from_tss_bytes(&bytes), Ok(buffer.clone()));
+        assert_eq!(&*buffer, [0xAA, 0xBB]);
+
+        assert_eq!(Tpm2b::default().to_tss_bytes(), [0x00, 0x00]);
+        assert_eq!(
+            Tpm2b::from_tss_bytes(&[0x00, 0x03, 0xAA]),
+            Err(TssError::InsufficientData)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "a TPM2B of 65536 bytes does not fit its size")]
+    fn test_tpm2b_overflow() {
+        Tpm2b(vec![0; u16::MAX as usize + 1]).to_tss_bytes();
+    }
+
+    #[test]
+    fn test_error_context() {
+        let error = TssError::InsufficientData
+            .in_field("TpmsAttest", "extra_data", "Tpm2b", 12)
+            .in_field("QuoteResponse", "attest", "TpmsAttest", 0);
+        assert_eq!(
+            error.to_string(),
+            "Insufficient data for deserialization decoding \
+             QuoteResponse.attest.extra_data (Tpm2b) at byte 12"
+        );
+        assert_eq!(error.root(), &TssError::InsufficientData);
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(source.to_string(), TssError::InsufficientData.to_string());
+    }
+
+    #[test]
+    fn test_io_error() {
+        let error = TssError::from(std::io::Error::other("device gone"));
+        assert_eq!(error.to_string(), "I/O error: device gone");
+        assert_eq!(error, error.clone());
+        assert_ne!(error, TssError::from(std::io::Error::other("busy")));
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(source.to_string(), "device gone");
+        assert!(matches!(
+            &error,
+            TssError::Io(io) if io.kind() == std::io::ErrorKind::Other
+        ));
+    }
+
+    #[test]
+    fn test_signed_integers() {
+        assert_eq!((-2i8).to_tss_bytes(), [0xFE]);
+        assert_eq!((-2i16).to_tss_bytes(), [0xFF, 0xFE]);
+        assert_eq!(0x0102_0304i32.to_tss_bytes(), [0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(i64::MIN.to_tss_bytes(), [0x80, 0, 0, 0, 0, 0, 0, 0]);
+
+        assert_eq!(i8::from_tss_bytes(&[0x80]), Ok(i8::MIN));
+        assert_eq!(i16::from_tss_bytes(&[0xFF, 0xFE]), Ok(-2));
+        assert_eq!(i32::from_tss_bytes(&[0xFF; 4]), Ok(-1));
+        assert_eq!(i64::from_tss_bytes(&[0x7F; 8]), Ok(0x7F7F_7F7F_7F7F_7F7F));
+        assert_eq!(
+            i32::from_tss_bytes(&[0xFF]),
+            Err(TssError::InsufficientData)
+        );
+    }
+
+    #[test]
+    fn test_tss_size() {
+        fn check(value: &impl TssSerialize) {
+            assert_eq!(value.tss_size(), value.to_tss_bytes().len());
+        }
+        check(&0u8);
+        check(&0u64);
+        check(&-1i16);
+        check(&[0u8; 5]);
+        check(&Tpm2b(vec![1, 2, 3]));
+        check(&Some(0u32));
+        check(&None::<u32>);
+        check(&vec![Tpm2b::default(), Tpm2b(vec![1])]);
+    }
+
+    #[test]
+    fn test_write_tss() {
+        let mut stream = Vec::new();
+        vec![Tpm2b(vec![0xAA; 5000]), Tpm2b(vec![0xBB; 20000])]
+            .write_tss(&mut stream)
+            .unwrap();
+        assert_eq!(stream.len(), 4 + 2 + 5000 + 2 + 20000);
+        assert_eq!(
+            Vec::<Tpm2b>::from_tss_bytes(&stream).unwrap()[1],
+            Tpm2b(vec![0xBB; 20000])
+        );
+
+        /// Accepts a few bytes, then fails.
+        struct Full(usize);
+        impl std::io::Write for Full {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.0 < buf.len() {
+                    return Err(std::io::ErrorKind::WriteZero.into());
+                }
+                self.0 -= buf.len();
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        assert!(Tpm2b(vec![0; 10]).write_tss(&mut Full(12)).is_ok());
+        let error = Tpm2b(vec![0; 10000]).write_tss(&mut Full(12)).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn test_bool() {
+        assert_eq!(true.to_tss_bytes(), [0x01]);
+        assert_eq!(false.to_tss_bytes(), [0x00]);
+        assert_eq!(bool::from_tss_bytes(&[0x01]), Ok(true));
+        assert_eq!(bool::from_tss_bytes(&[0x00]), Ok(false));
+        assert_eq!(
+            bool::from_tss_bytes(&[0x02]),
+            Err(TssError::InvalidValue {
+                field: "TPMI_YES_NO",
+                value: 2
+            })
+        );
+
+        let mut reader = TssReader::new(&[0x00, 0x02]).lenient_bools(true);
+        assert_eq!(bool::from_tss_reader(&mut reader), Ok(false));
+        assert_eq!(bool::from_tss_reader(&mut reader), Ok(true));
+    }
+
+    #[test]
+    fn test_arrays() {
+        let words = [0x0102u16, 0x0304];
+        assert_eq!(words.to_tss_bytes(), [0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(
+            <[u16; 2]>::from_tss_bytes(&[0x01, 0x02, 0x03, 0x04]),
+            Ok(words)
+        );
+
+        let buffers = [Tpm2b(vec![0xAA]), Tpm2b(vec![])];
+        let bytes = buffers.to_tss_bytes();
+        assert_eq!(bytes, [0x00, 0x01, 0xAA, 0x00, 0x00]);
+        assert_eq!(buffers.tss_size(), bytes.len());
+        assert_eq!(<[Tpm2b; 2]>::from_tss_bytes(&bytes), Ok(buffers));
+
+        let nested = [[1u8, 2], [3, 4], [5, 6]];
+        assert_eq!(nested.to_tss_bytes(), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            <[[u8; 2]; 3]>::from_tss_bytes(&[1, 2, 3, 4, 5, 6]),
+            Ok(nested)
+        );
+        assert_eq!(
+            <[u32; 2]>::from_tss_bytes(&[0, 0, 0, 1, 0, 0]),
+            Err(TssError::InsufficientData)
+        );
+    }
+
+    #[test]
+    fn test_vec_deserialize() {
+        let data = [0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x02];
+        assert_eq!(Vec::<u16>::from_tss_bytes(&data).unwrap(), vec![1, 2]);
+
+        // A declared length within the limit fails on the missing data
+        // rather than reserving it up front.
+        let data = [0x00, 0x01, 0x00, 0x00, 0x00, 0x01];
+        assert_eq!(
+            Vec::<u64>::from_tss_bytes(&data),
+            Err(TssError::InsufficientData)
+        );
+
+        // Longer lists are rejected outright, even of elements taking no
+        // bytes to decode.
+        let data = [0xFF, 0xFF, 0xFF, 0xFF];
+        assert_eq!(
+            Vec::<Option<u8>>::from_tss_bytes(&data),
+            Err(TssError::LengthOverflow {
+                length: u32::MAX as usize,
+                max: DEFAULT_MAX_LIST_LENGTH,
+            })
+        );
+        let data = [0x00, 0x00, 0x00, 0x03, 0x01, 0x02, 0x03];
+        let mut reader = TssReader::new(&data).max_list_length(2);
+        assert_eq!(
+            Vec::<u8>::from_tss_reader(&mut reader),
+            Err(TssError::LengthOverflow { length: 3, max: 2 })
+        );
+        let mut reader = TssReader::new(&data).max_list_length(3);
+        assert_eq!(Vec::<u8>::from_tss_reader(&mut reader), Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn test_tuples() {
+        let command = (0x8000_0001u32, Tpm2b(vec![0xAA]), [0x01u8, 0x02]);
+        let bytes = command.to_tss_bytes();
+        assert_eq!(
+            bytes,
+            [0x80, 0x00, 0x00, 0x01, 0x00, 0x01, 0xAA, 0x01, 0x02]
+        );
+        assert_eq!(command.tss_size(), bytes.len());
+        assert_eq!(<(u32, Tpm2b, [u8; 2])>::from_tss_bytes(&bytes), Ok(command));
+
+        assert_eq!((7u8,).to_tss_bytes(), [7]);
+        assert_eq!(
+            <(u8, u8, u8, u8, u8, u8, u8, u16)>::from_tss_bytes(&[1, 2, 3, 4, 5, 6, 7, 0, 8]),
+            Ok((1, 2, 3, 4, 5, 6, 7, 8))
+        );
+        assert_eq!(
+            <(u16, u32)>::from_tss_bytes(&[0, 1, 0]),
+            Err(TssError::InsufficientData)
+        );
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_assert_roundtrip() {
+        for seed in 0..64u8 {
+            let data: Vec<u8> = (0..1024).map(|i| (i as u8).wrapping_mul(seed)).collect();
+            let mut u = arbitrary::Unstructured::new(&data);
+            assert_roundtrip::<Tpm2b>(&mut u).unwrap();
+            assert_roundtrip::<(u16, [Tpm2b; 2], bool)>(&mut u).unwrap();
+            assert_roundtrip::<Vec<(i32, Option<u64>)>>(&mut u).unwrap();
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    #[should_panic(expected = "decoding")]
+    fn test_assert_roundtrip_detects_asymmetry() {
+        /// Decodes the byte it encodes plus one.
+        #[derive(Debug, PartialEq)]
+        struct Skewed(u8);
+
+        impl<'a> arbitrary::Arbitrary<'a> for Skewed {
+            fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+                u.arbitrary().map(Skewed)
+            }
+        }
+
+        impl TssSerialize for Skewed {
+            fn serialize_to(&self, writer: &mut TssWriter) {
+                writer.write_u8(self.0);
+            }
+        }
+
+        impl TssDeserialize for Skewed {
+            fn from_tss_reader(reader: &mut TssReader) -> Result<Self, TssError> {
+                reader.read_u8().map(|byte| Skewed(byte.wrapping_add(1)))
+            }
+        }
+
+        assert_roundtrip::<Skewed>(&mut arbitrary::Unstructured::new(&[7])).unwrap();
+    }
+
+    #[test]
+    fn test_fixed_size() {
+        assert_eq!(u8::SERIALIZED_SIZE, 1);
+        assert_eq!(i64::SERIALIZED_SIZE, 8);
+        assert_eq!(bool::SERIALIZED_SIZE, 1);
+        assert_eq!(<[u16; 3]>::SERIALIZED_SIZE, 6);
+        assert_eq!(<(u32, [[u8; 2]; 2], bool)>::SERIALIZED_SIZE, 9);
+
+        let buffer = [0u8; <(u16, u32)>::SERIALIZED_SIZE];
+        assert_eq!(buffer.len(), (0u16, 0u32).to_tss_bytes().len());
+    }
 }
@@ -0,0 +1,187 @@
+use std::io::Read;
+
+use crate::{TssDeserialize, TssError, TssReader};
+
+/// How many bytes to read from the source at a time
+const CHUNK_SIZE: usize = 8192;
+
+/// Deserializes values as they arrive from a `std::io::Read`, such as a
+/// socket or a device, buffering internally
+///
+/// [`read`](Self::read) decodes from the bytes buffered so far and reads
+/// more from the source whenever they run out. A trailing `Option` member is
+/// decoded against the end of what is buffered, so values ending in one are
+/// read with [`read_exact`](Self::read_exact), from their known size.
+#[derive(Debug)]
+pub struct TssStreamReader<R> {
+    source: R,
+    buffer: Vec<u8>,
+    /// Start of the bytes not decoded yet in `buffer`
+    start: usize,
+    /// Bytes decoded since the start of the stream
+    position: u64,
+    eof: bool,
+}
+
+impl<R: Read> TssStreamReader<R> {
+    /// Create a new TssStreamReader reading from `source`
+    pub fn new(source: R) -> Self {
+        Self {
+            source,
+            buffer: Vec::new(),
+            start: 0,
+            position: 0,
+            eof: false,
+        }
+    }
+
+    /// Get the number of bytes decoded since the start of the stream
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Check if the source is exhausted and every byte read was decoded
+    pub fn is_at_end(&mut self) -> Result<bool, TssError> {
+        if self.start == self.buffer.len() {
+            self.fill()?;
+        }
+        Ok(self.start == self.buffer.len())
+    }
+
+    /// Deserialize the next value, reading from the source until the bytes
+    /// it needs have arrived
+    pub fn read<T: TssDeserialize>(&mut self) -> Result<T, TssError> {
+        loop {
+            let mut reader = TssReader::new(&self.buffer[self.start..]);
+            match T::from_tss_reader(&mut reader) {
+                Ok(value) => {
+                    let consumed = reader.position();
+                    self.consume(consumed);
+                    return Ok(value);
+                }
+                Err(error) if error.root() == &TssError::InsufficientData && !self.eof => {
+                    self.fill()?;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Read exactly `size` bytes and deserialize them as one value, failing
+    /// with [`TssError::TrailingBytes`] if it does not consume them all
+    pub fn read_exact<T: TssDeserialize>(&mut self, size: usize) -> Result<T, TssError> {
+        while self.buffer.len() - self.start < size {
+            if self.eof {
+                return Err(TssError::InsufficientData);
+            }
+            self.fill()?;
+        }
+        let mut reader = TssReader::new(&self.buffer[self.start..self.start + size]);
+        let value = T::from_tss_reader(&mut reader)?;
+        if reader.remaining() != 0 {
+            return Err(TssError::TrailingBytes {
+                remaining: reader.remaining(),
+            });
+        }
+        self.consume(size);
+        Ok(value)
+    }
+
+    /// Consume the stream reader, returning the source and the bytes read
+    /// from it but not decoded
+    pub fn into_inner(self) -> (R, Vec<u8>) {
+        (self.source, self.buffer[self.start..].to_vec())
+    }
+
+    fn consume(&mut self, count: usize) {
+        self.start += count;
+        self.position += count as u64;
+    }
+
+    /// Read the next chunk from the source, first dropping the decoded
+    /// bytes from the buffer
+    fn fill(&mut self) -> Result<(), TssError> {
+        self.buffer.drain(..self.start);
+        self.start = 0;
+
+        let filled = self.buffer.len();
+        self.buffer.resize(filled + CHUNK_SIZE, 0);
+        let count = loop {
+            match self.source.read(&mut self.buffer[filled..]) {
+                Ok(count) => break count,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(error) => {
+                    self.buffer.truncate(filled);
+                    return Err(error.into());
+                }
+            }
+        };
+        self.buffer.truncate(filled + count);
+        self.eof = count == 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Tpm2b, TssSerialize};
+
+    /// Yields its data a few bytes per read, like a socket
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let count = buf.len().min(self.0.len()).min(3);
+            buf[..count].copy_from_slice(&self.0[..count]);
+            self.0 = &self.0[count..];
+            Ok(count)
+        }
+    }
+
+    #[test]
+    fn test_read_across_chunks() {
+        let mut data = 0x0102u16.to_tss_bytes();
+        Tpm2b(vec![0xAA; 10]).serialize_into(&mut data);
+        vec![1u32, 2, 3].serialize_into(&mut data);
+
+        let mut stream = TssStreamReader::new(Trickle(&data));
+        assert_eq!(stream.read::<u16>(), Ok(0x0102));
+        assert_eq!(stream.read::<Tpm2b>(), Ok(Tpm2b(vec![0xAA; 10])));
+        assert_eq!(stream.position(), 14);
+        assert_eq!(stream.read::<Vec<u32>>(), Ok(vec![1, 2, 3]));
+        assert_eq!(stream.is_at_end(), Ok(true));
+        assert_eq!(stream.read::<u8>(), Err(TssError::InsufficientData));
+    }
+
+    #[test]
+    fn test_read_exact() {
+        let data = [0x00, 0x01, 0x00, 0x02, 0xFF];
+        let mut stream = TssStreamReader::new(Trickle(&data));
+        assert_eq!(stream.read_exact::<Option<u16>>(2), Ok(Some(1)));
+        assert_eq!(stream.read_exact::<Option<u16>>(0), Ok(None));
+        assert_eq!(
+            stream.read_exact::<u8>(2),
+            Err(TssError::TrailingBytes { remaining: 1 })
+        );
+        assert_eq!(stream.read_exact::<[u8; 3]>(3), Ok([0x00, 0x02, 0xFF]));
+        assert_eq!(stream.read_exact::<u8>(1), Err(TssError::InsufficientData));
+
+        let (_, rest) = TssStreamReader::new(&data[..]).into_inner();
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_read_error() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("device gone"))
+            }
+        }
+        assert_eq!(
+            TssStreamReader::new(Failing).read::<u32>(),
+            Err(std::io::Error::other("device gone").into())
+        );
+    }
+}
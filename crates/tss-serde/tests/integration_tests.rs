@@ -1,6 +1,9 @@
-use tss_serde::{TssDeserialize, TssSerialize};
+use tss_serde::{
+    TssBitflags, TssDeserialize, TssError, TssFixedSize, TssReader, TssSelect, TssSerialize,
+    TssWriter,
+};
 
-#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
 struct TpmGetRandomCommand {
     tag: u16,
     length: u32,
@@ -16,6 +19,348 @@ struct TpmResponse {
     data: [u8; 4],
 }
 
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+struct TpmNestedCommand {
+    header: TpmGetRandomCommand,
+    data: [u8; 2],
+}
+
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+struct TpmHandleList {
+    capability: u32,
+    handles: Vec<u32>,
+}
+
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+struct TpmCreateResponse {
+    handle: u32,
+    #[tss(sized)]
+    public: Option<TpmHandleList>,
+    name: [u8; 2],
+    creation_data: Option<u32>,
+}
+
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
+struct TpmSignedValues {
+    a: i8,
+    b: i16,
+    c: i32,
+    d: i64,
+}
+
+/// A little-endian layout, like the SEV-SNP attestation report.
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
+#[tss(endian = "little")]
+struct SnpReportHeader {
+    version: u32,
+    guest_svn: u32,
+    policy: u64,
+    vmpl: i32,
+    family_id: [u8; 4],
+    svns: [u16; 2],
+    // Nested types keep their own byte order.
+    header: TpmSignedValues,
+}
+
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+struct TpmCapabilityData {
+    more_data: bool,
+    capability: u32,
+}
+
+/// A TPMS_PCR_SELECTION, which TPM structures hold arrays of.
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
+struct TpmPcrSelection {
+    hash: u16,
+    size_of_select: u8,
+    pcr_select: [u8; 3],
+}
+
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+struct TpmPcrBanks {
+    banks: [TpmPcrSelection; 2],
+    counters: [u32; 2],
+}
+
+/// A TPMS_PCR_SELECTION holding the selected PCR indices instead of the
+/// bitmap.
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+struct TpmPcrList {
+    hash: u16,
+    #[tss(with = "pcr_select")]
+    pcrs: Vec<u32>,
+    count: u8,
+}
+
+mod pcr_select {
+    use tss_serde::{TssError, TssReader, TssWriter};
+
+    pub fn serialize(pcrs: &[u32], writer: &mut TssWriter) {
+        let mut select = [0u8; 3];
+        for pcr in pcrs {
+            select[*pcr as usize / 8] |= 1 << (pcr % 8);
+        }
+        writer.write_u8(select.len() as u8);
+        writer.write_bytes(&select);
+    }
+
+    pub fn deserialize(reader: &mut TssReader) -> Result<Vec<u32>, TssError> {
+        let size = reader.read_u8()?;
+        let select = reader.read_bytes(size as usize)?;
+        Ok((0..select.len() as u32 * 8)
+            .filter(|pcr| select[*pcr as usize / 8] & (1 << (pcr % 8)) != 0)
+            .collect())
+    }
+}
+
+/// A TPM command with its length computed from its encoding.
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+struct TpmSizedCommand {
+    tag: u16,
+    #[tss(size_of = "self")]
+    length: u32,
+    command_code: u32,
+    #[tss(size_of = "handles")]
+    handles_size: u16,
+    handles: Vec<u32>,
+}
+
+/// Lists with the prefixes of TPM2B buffers and of SEV-SNP tables.
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+struct TpmPrefixedLists {
+    #[tss(length_prefix = "u16")]
+    digest: Vec<u8>,
+    #[tss(length_prefix = "u8")]
+    algorithms: Vec<u16>,
+    #[tss(length_prefix = "u16", length_unit = "bytes")]
+    selections: Vec<TpmPcrSelection>,
+}
+
+/// A TPM_HANDLE newtype.
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, Clone, Copy, PartialEq)]
+struct TpmHandle(u32);
+
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+#[tss(endian = "little")]
+struct SnpTcbVersion(u8, u16, #[tss(length_prefix = "u8")] Vec<TpmHandle>);
+
+/// A TPMS_NV_PUBLIC-like structure rejecting reserved attributes and
+/// indices that cannot hold their data.
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+#[tss(validate = "TpmNvIndex::check_size")]
+struct TpmNvIndex {
+    #[tss(validate = "check_nv_attributes")]
+    attributes: u32,
+    data_size: u16,
+    #[tss(length_prefix = "u16")]
+    data: Vec<u8>,
+}
+
+impl TpmNvIndex {
+    fn check_size(&self) -> Result<(), u64> {
+        if self.data.len() > self.data_size as usize {
+            return Err(self.data.len() as u64);
+        }
+        Ok(())
+    }
+}
+
+/// Bits 7 to 9 of TPMA_NV are reserved.
+fn check_nv_attributes(attributes: &u32) -> Result<(), u64> {
+    match attributes & 0x380 {
+        0 => Ok(()),
+        reserved => Err(reserved.into()),
+    }
+}
+
+const TPM_GENERATED: u32 = 0xFF544347;
+
+/// The start of a TPMS_ATTEST of a quote: the magic and type before the
+/// signer.
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
+struct TpmQuoteHeader {
+    #[tss(const = TPM_GENERATED)]
+    #[tss(const = 0x8018u16)]
+    qualified_signer: [u8; 4],
+}
+
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+#[tss(endian = "little")]
+struct SnpMagic {
+    version: u8,
+    #[tss(const = 0x5350u16)]
+    body: u32,
+}
+
+const SHA256_DIGEST_SIZE: usize = 32;
+
+/// A TPM2B_DIGEST of a fixed hash, over any digest size.
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
+struct TpmDigest<const N: usize> {
+    size: u16,
+    buffer: [u8; N],
+}
+
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+struct TpmTagged<T> {
+    tag: u16,
+    value: T,
+}
+
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+#[tss(endian = "little")]
+struct SnpTypePaths {
+    version: ::core::primitive::u16,
+    measurement: [u8; SHA256_DIGEST_SIZE],
+    tcb: [[u16; 2]; 2],
+    #[tss(length_prefix = "u32")]
+    reserved: std::vec::Vec<u8>,
+}
+
+const TPM_ALG_NULL: u16 = 0x0010;
+
+/// A TPMU_PUBLIC_PARMS, selected by the type of the key.
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+#[tss(selector = u16)]
+enum TpmPublicParms {
+    #[tss(discriminant = 0x0001)]
+    Rsa { key_bits: u16, exponent: u32 },
+    #[tss(discriminant = 0x0023)]
+    Ecc { curve_id: u16 },
+    #[tss(discriminant = TPM_ALG_NULL)]
+    Null,
+}
+
+/// The start of a TPMT_PUBLIC, whose parameters depend on its type.
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+struct TpmPublic {
+    key_type: u16,
+    name_alg: u16,
+    #[tss(selector = "key_type")]
+    parameters: TpmPublicParms,
+}
+
+/// A TPMT_SIGNATURE: a TPMU_SIGNATURE selected by its algorithm.
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+#[tss(tag = u16)]
+enum TpmSignature {
+    #[tss(discriminant = 0x0018)]
+    Ecdsa { hash: u16, r: Vec<u8>, s: Vec<u8> },
+    #[tss(discriminant = 0x0014)]
+    Rsassa(u16, Vec<u8>),
+    #[tss(discriminant = TPM_ALG_NULL)]
+    Null,
+}
+
+/// A TPM_ALG_ID.
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, Clone, Copy, PartialEq)]
+#[repr(u16)]
+enum TpmAlgId {
+    Sha256 = 0x000B,
+    Null = TPM_ALG_NULL,
+    Ecdsa = 0x0018,
+}
+
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
+#[tss(endian = "little")]
+#[repr(u64)]
+enum SnpPolicyBit {
+    Smt = 1 << 16,
+    Debug = 1 << 19,
+}
+
+const PCR_BANKS: usize = 3;
+
+/// The PCR banks of a TPM, with the algorithms of its first `N` banks.
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
+struct TpmBankSet<const N: usize> {
+    selections: [TpmPcrSelection; PCR_BANKS],
+    algs: [TpmAlgId; N],
+    handles: [TpmHandle; PCR_BANKS - 1],
+    counters: [u64; 4],
+}
+
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
+#[tss(endian = "little")]
+struct SnpVmplDigests {
+    counters: [u64; 4],
+    policies: [SnpPolicyBit; 2],
+    digests: [[u16; 2]; PCR_BANKS],
+}
+
+/// The response parameters of TPM2_Startup, of which there are none.
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
+#[tss(exact)]
+struct TpmStartupResponse;
+
+/// A marker type, taking no bytes where it is held.
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
+struct SnpMarker;
+
+/// Markers in a list of a byte size, which no number of them fills.
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+struct SnpMarkerList {
+    #[tss(length_prefix = "u16", length_unit = "bytes")]
+    markers: Vec<SnpMarker>,
+}
+
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
+struct SnpMarked {
+    marker: SnpMarker,
+    version: u32,
+}
+
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+struct TpmtHa {
+    hash_alg: TpmAlgId,
+    digest: [u8; 4],
+}
+
+#[derive(TssSerialize, TssDeserialize, Debug, PartialEq)]
+#[tss(endian = "little", tag = u32)]
+enum SnpKey {
+    #[tss(discriminant = 1)]
+    Vcek { chip_id: u32 },
+    #[tss(discriminant = 2)]
+    Vlek {
+        hash: u16,
+        #[tss(length_prefix = "u16")]
+        id: Vec<u8>,
+    },
+}
+
+bitflags::bitflags! {
+    /// TPMA_SESSION.
+    #[derive(TssBitflags, Debug, Clone, Copy, PartialEq)]
+    struct TpmaSession: u8 {
+        const CONTINUE_SESSION = 1 << 0;
+        const AUDIT_EXCLUSIVE = 1 << 1;
+        const AUDIT_RESET = 1 << 2;
+        const DECRYPT = 1 << 5;
+        const ENCRYPT = 1 << 6;
+        const AUDIT = 1 << 7;
+    }
+}
+
+bitflags::bitflags! {
+    /// The attributes of TPMA_CC, around its command index.
+    #[derive(TssBitflags, Debug, Clone, Copy, PartialEq)]
+    #[tss(retain)]
+    struct TpmaCc: u32 {
+        const NV = 1 << 22;
+        const EXTENSIVE = 1 << 23;
+        const FLUSHED = 1 << 24;
+        const R_HANDLE = 1 << 28;
+        const V = 1 << 29;
+    }
+}
+
+#[derive(TssSerialize, TssDeserialize, TssFixedSize, Debug, PartialEq)]
+struct TpmsAuthResponse {
+    nonce_size: u16,
+    session_attributes: TpmaSession,
+}
+
 #[test]
 fn test_serialize_command() {
     let cmd = TpmGetRandomCommand {
@@ -67,3 +412,989 @@ fn test_roundtrip() {
 
     assert_eq!(original, decoded);
 }
+
+#[test]
+fn test_serialize_nested_into_writer() {
+    let nested = TpmNestedCommand {
+        header: TpmGetRandomCommand {
+            tag: 0x8001,
+            length: 14,
+            command_code: 0x017B,
+            bytes_requested: 32,
+        },
+        data: [0xAB, 0xCD],
+    };
+
+    let mut writer = TssWriter::new();
+    writer.write_u8(0xFF);
+    nested.serialize_to(&mut writer);
+    let bytes = writer.into_bytes();
+
+    assert_eq!(bytes[0], 0xFF);
+    assert_eq!(&bytes[1..], nested.to_tss_bytes());
+    assert_eq!(
+        TpmNestedCommand::from_tss_bytes(&bytes[1..]).unwrap(),
+        nested
+    );
+}
+
+#[test]
+fn test_serialize_into_appends() {
+    let cmd = TpmGetRandomCommand {
+        tag: 0x8001,
+        length: 12,
+        command_code: 0x017B,
+        bytes_requested: 32,
+    };
+
+    let mut buffer = vec![0xFF];
+    cmd.serialize_into(&mut buffer);
+    cmd.serialize_into(&mut buffer);
+
+    assert_eq!(buffer.len(), 25);
+    assert_eq!(&buffer[1..13], cmd.to_tss_bytes());
+    assert_eq!(&buffer[13..], cmd.to_tss_bytes());
+}
+
+#[test]
+fn test_roundtrip_list() {
+    let original = TpmHandleList {
+        capability: 1,
+        handles: vec![0x8100_0001, 0x8100_0002],
+    };
+
+    let bytes = original.to_tss_bytes();
+    assert_eq!(bytes.len(), 4 + 4 + 2 * 4);
+    assert_eq!(&bytes[4..8], [0, 0, 0, 2]);
+    assert_eq!(TpmHandleList::from_tss_bytes(&bytes).unwrap(), original);
+}
+
+#[test]
+fn test_roundtrip_optional_fields() {
+    let present = TpmCreateResponse {
+        handle: 0x8000_0000,
+        public: Some(TpmHandleList {
+            capability: 1,
+            handles: vec![],
+        }),
+        name: [0xAB, 0xCD],
+        creation_data: Some(7),
+    };
+    let bytes = present.to_tss_bytes();
+    assert_eq!(
+        bytes,
+        [
+            0x80, 0x00, 0x00, 0x00, // handle
+            0x00, 0x08, // public size
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, // public
+            0xAB, 0xCD, // name
+            0x00, 0x00, 0x00, 0x07, // creation_data
+        ]
+    );
+    assert_eq!(TpmCreateResponse::from_tss_bytes(&bytes).unwrap(), present);
+
+    let absent = TpmCreateResponse {
+        handle: 0x8000_0000,
+        public: None,
+        name: [0xAB, 0xCD],
+        creation_data: None,
+    };
+    let bytes = absent.to_tss_bytes();
+    assert_eq!(bytes, [0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0xAB, 0xCD]);
+    assert_eq!(TpmCreateResponse::from_tss_bytes(&bytes).unwrap(), absent);
+
+    // A sized member must fill exactly its declared size.
+    let padded = [
+        0x80, 0x00, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xAB, 0xCD,
+    ];
+    assert_eq!(
+        TpmCreateResponse::from_tss_bytes(&padded)
+            .unwrap_err()
+            .root(),
+        &TssError::InvalidFormat
+    );
+
+    // Sized members are decoded with the settings of the outer reader.
+    let two_handles = [
+        0x80, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x81,
+        0x00, 0x00, 0x01, 0x81, 0x00, 0x00, 0x02, 0xAB, 0xCD,
+    ];
+    assert!(TpmCreateResponse::from_tss_bytes(&two_handles).is_ok());
+    let mut reader = TssReader::new(&two_handles).max_list_length(1);
+    assert_eq!(
+        TpmCreateResponse::from_tss_reader(&mut reader)
+            .unwrap_err()
+            .root(),
+        &TssError::LengthOverflow { length: 2, max: 1 }
+    );
+}
+
+#[test]
+#[should_panic(expected = "a TPM2B of 65540 bytes does not fit its size")]
+fn test_sized_overflow() {
+    TpmCreateResponse {
+        handle: 0x80000000,
+        public: Some(TpmHandleList {
+            capability: 1,
+            handles: vec![0; 0x3FFF],
+        }),
+        name: [0; 2],
+        creation_data: None,
+    }
+    .to_tss_bytes();
+}
+
+#[test]
+fn test_error_context() {
+    // The list in `public` declares two handles but carries one.
+    let bytes = [
+        0x80, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x81,
+        0x00, 0x00, 0x01,
+    ];
+    let error = TpmCreateResponse::from_tss_bytes(&bytes).unwrap_err();
+    assert_eq!(
+        error,
+        TssError::Field {
+            structure: "TpmCreateResponse",
+            path: vec!["public", "handles"],
+            expected: "Vec<u32>",
+            position: 10,
+            source: Box::new(TssError::InsufficientData),
+        }
+    );
+
+    let nested = [0x80, 0x01, 0x00, 0x00, 0x00, 0x0C, 0x00, 0x00, 0x01];
+    let error = TpmNestedCommand::from_tss_bytes(&nested).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Insufficient data for deserialization decoding \
+         TpmNestedCommand.header.command_code (u32) at byte 6"
+    );
+}
+
+#[test]
+fn test_roundtrip_signed() {
+    let original = TpmSignedValues {
+        a: -1,
+        b: i16::MIN,
+        c: -2,
+        d: i64::MAX,
+    };
+
+    let bytes = original.to_tss_bytes();
+    assert_eq!(bytes.len(), 1 + 2 + 4 + 8);
+    assert_eq!(&bytes[..3], [0xFF, 0x80, 0x00]);
+    assert_eq!(TpmSignedValues::from_tss_bytes(&bytes).unwrap(), original);
+}
+
+#[test]
+fn test_tss_size() {
+    let response = TpmCreateResponse {
+        handle: 0x8000_0000,
+        public: Some(TpmHandleList {
+            capability: 1,
+            handles: vec![0x8100_0001],
+        }),
+        name: [0xAB, 0xCD],
+        creation_data: None,
+    };
+    assert_eq!(response.tss_size(), 4 + 2 + 12 + 2);
+    assert_eq!(response.tss_size(), response.to_tss_bytes().len());
+
+    let signed = TpmSignedValues {
+        a: 0,
+        b: 0,
+        c: 0,
+        d: 0,
+    };
+    assert_eq!(signed.tss_size(), 15);
+}
+
+#[test]
+fn test_roundtrip_little_endian() {
+    let original = SnpReportHeader {
+        version: 2,
+        guest_svn: 0x0102_0304,
+        policy: 0x30000,
+        vmpl: -1,
+        family_id: [1, 2, 3, 4],
+        svns: [0x0A0B, 0x0C0D],
+        header: TpmSignedValues {
+            a: 0,
+            b: 0x0102,
+            c: 0,
+            d: 0,
+        },
+    };
+
+    let bytes = original.to_tss_bytes();
+    assert_eq!(
+        &bytes[..28],
+        [
+            0x02, 0x00, 0x00, 0x00, // version
+            0x04, 0x03, 0x02, 0x01, // guest_svn
+            0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, // policy
+            0xFF, 0xFF, 0xFF, 0xFF, // vmpl
+            0x01, 0x02, 0x03, 0x04, // family_id
+            0x0B, 0x0A, 0x0D, 0x0C, // svns
+        ]
+    );
+    assert_eq!(&bytes[29..31], [0x01, 0x02]);
+    assert_eq!(original.tss_size(), bytes.len());
+    assert_eq!(SnpReportHeader::from_tss_bytes(&bytes).unwrap(), original);
+}
+
+#[test]
+fn test_roundtrip_bool() {
+    let original = TpmCapabilityData {
+        more_data: true,
+        capability: 6,
+    };
+    let bytes = original.to_tss_bytes();
+    assert_eq!(bytes, [0x01, 0x00, 0x00, 0x00, 0x06]);
+    assert_eq!(TpmCapabilityData::from_tss_bytes(&bytes).unwrap(), original);
+
+    let nonzero = [0x80, 0x00, 0x00, 0x00, 0x06];
+    assert_eq!(
+        TpmCapabilityData::from_tss_bytes(&nonzero)
+            .unwrap_err()
+            .root(),
+        &TssError::InvalidValue {
+            field: "TPMI_YES_NO",
+            value: 0x80
+        }
+    );
+    let mut reader = TssReader::new(&nonzero).lenient_bools(true);
+    assert_eq!(
+        TpmCapabilityData::from_tss_reader(&mut reader).unwrap(),
+        original
+    );
+}
+
+#[test]
+fn test_roundtrip_struct_array() {
+    let original = TpmPcrBanks {
+        banks: [
+            TpmPcrSelection {
+                hash: 0x000B,
+                size_of_select: 3,
+                pcr_select: [0x81, 0x00, 0x00],
+            },
+            TpmPcrSelection {
+                hash: 0x000C,
+                size_of_select: 3,
+                pcr_select: [0x00, 0x00, 0x01],
+            },
+        ],
+        counters: [1, 2],
+    };
+    let bytes = original.to_tss_bytes();
+    assert_eq!(
+        bytes,
+        [
+            0x00, 0x0B, 0x03, 0x81, 0x00, 0x00, // banks[0]
+            0x00, 0x0C, 0x03, 0x00, 0x00, 0x01, // banks[1]
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, // counters
+        ]
+    );
+    assert_eq!(original.tss_size(), bytes.len());
+    assert_eq!(TpmPcrBanks::from_tss_bytes(&bytes).unwrap(), original);
+    assert_eq!(
+        TpmPcrBanks::from_tss_bytes(&bytes[..10])
+            .unwrap_err()
+            .root(),
+        &TssError::InsufficientData
+    );
+}
+
+#[test]
+fn test_fixed_size() {
+    const _: () = assert!(TpmGetRandomCommand::SERIALIZED_SIZE == 12);
+    assert_eq!(TpmSignedValues::SERIALIZED_SIZE, 15);
+    assert_eq!(SnpReportHeader::SERIALIZED_SIZE, 4 + 4 + 8 + 4 + 4 + 4 + 15);
+
+    let header = SnpReportHeader {
+        version: 2,
+        guest_svn: 1,
+        policy: 0,
+        vmpl: 0,
+        family_id: [0; 4],
+        svns: [0; 2],
+        header: TpmSignedValues {
+            a: 0,
+            b: 0,
+            c: 0,
+            d: 0,
+        },
+    };
+    let mut buffer = [0u8; SnpReportHeader::SERIALIZED_SIZE];
+    buffer.copy_from_slice(&header.to_tss_bytes());
+    assert_eq!(
+        SnpReportHeader::from_tss_bytes_exact(&buffer).unwrap(),
+        header
+    );
+}
+
+#[test]
+fn test_roundtrip_enum() {
+    let ecdsa = TpmSignature::Ecdsa {
+        hash: 0x000B,
+        r: vec![0x01, 0x02],
+        s: vec![0x03],
+    };
+    let bytes = ecdsa.to_tss_bytes();
+    assert_eq!(
+        bytes,
+        [
+            0x00, 0x18, 0x00, 0x0B, 0x00, 0x00, 0x00, 0x02, 0x01, 0x02, 0x00, 0x00, 0x00, 0x01,
+            0x03
+        ]
+    );
+    assert_eq!(ecdsa.tss_size(), bytes.len());
+    assert_eq!(TpmSignature::from_tss_bytes_exact(&bytes).unwrap(), ecdsa);
+
+    let rsassa = TpmSignature::Rsassa(0x000B, vec![0xAA; 4]);
+    let bytes = rsassa.to_tss_bytes();
+    assert_eq!(&bytes[..4], [0x00, 0x14, 0x00, 0x0B]);
+    assert_eq!(TpmSignature::from_tss_bytes_exact(&bytes).unwrap(), rsassa);
+
+    assert_eq!(TpmSignature::Null.to_tss_bytes(), [0x00, 0x10]);
+    assert_eq!(TpmSignature::Null.tss_size(), 2);
+    assert_eq!(
+        TpmSignature::from_tss_bytes_exact(&[0x00, 0x10]).unwrap(),
+        TpmSignature::Null
+    );
+
+    assert_eq!(
+        TpmSignature::from_tss_bytes(&[0x00, 0x99]).unwrap_err(),
+        TssError::InvalidValue {
+            field: "TpmSignature",
+            value: 0x99,
+        }
+    );
+    assert_eq!(
+        TpmSignature::from_tss_bytes(&[0x00, 0x18, 0x00]).unwrap_err(),
+        TssError::Field {
+            structure: "TpmSignature::Ecdsa",
+            path: vec!["hash"],
+            expected: "u16",
+            position: 2,
+            source: Box::new(TssError::InsufficientData),
+        }
+    );
+
+    let vlek = SnpKey::Vlek {
+        hash: 0x0102,
+        id: vec![0x0A, 0x0B, 0x0C, 0x0D],
+    };
+    let bytes = vlek.to_tss_bytes();
+    assert_eq!(
+        bytes,
+        [0x02, 0x00, 0x00, 0x00, 0x02, 0x01, 0x04, 0x00, 0x0A, 0x0B, 0x0C, 0x0D]
+    );
+    assert_eq!(vlek.tss_size(), bytes.len());
+    assert_eq!(SnpKey::from_tss_bytes_exact(&bytes).unwrap(), vlek);
+    let vcek = SnpKey::Vcek { chip_id: 7 };
+    assert_eq!(
+        SnpKey::from_tss_bytes_exact(&vcek.to_tss_bytes()).unwrap(),
+        vcek
+    );
+}
+
+#[test]
+fn test_roundtrip_fieldless_enum() {
+    assert_eq!(TpmAlgId::Sha256.to_tss_bytes(), [0x00, 0x0B]);
+    assert_eq!(TpmAlgId::Null.tss_size(), 2);
+    assert_eq!(TpmAlgId::SERIALIZED_SIZE, 2);
+    assert_eq!(
+        TpmAlgId::from_tss_bytes_exact(&[0x00, 0x18]).unwrap(),
+        TpmAlgId::Ecdsa
+    );
+    assert_eq!(
+        TpmAlgId::from_tss_bytes(&[0x00, 0x0C]).unwrap_err(),
+        TssError::InvalidValue {
+            field: "TpmAlgId",
+            value: 0x0C,
+        }
+    );
+
+    assert_eq!(
+        SnpPolicyBit::Debug.to_tss_bytes(),
+        [0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00]
+    );
+    assert_eq!(SnpPolicyBit::SERIALIZED_SIZE, 8);
+    assert_eq!(
+        SnpPolicyBit::from_tss_bytes_exact(&SnpPolicyBit::Smt.to_tss_bytes()).unwrap(),
+        SnpPolicyBit::Smt
+    );
+
+    let ha = TpmtHa {
+        hash_alg: TpmAlgId::Sha256,
+        digest: [1, 2, 3, 4],
+    };
+    let bytes = ha.to_tss_bytes();
+    assert_eq!(bytes, [0x00, 0x0B, 1, 2, 3, 4]);
+    assert_eq!(TpmtHa::from_tss_bytes_exact(&bytes).unwrap(), ha);
+    assert_eq!(
+        TpmtHa::from_tss_bytes(&[0x00, 0x01, 1, 2, 3, 4]).unwrap_err(),
+        TssError::Field {
+            structure: "TpmtHa",
+            path: vec!["hash_alg"],
+            expected: "TpmAlgId",
+            position: 0,
+            source: Box::new(TssError::InvalidValue {
+                field: "TpmAlgId",
+                value: 0x01,
+            }),
+        }
+    );
+}
+
+#[test]
+fn test_roundtrip_with() {
+    let list = TpmPcrList {
+        hash: 0x000B,
+        pcrs: vec![0, 7, 16],
+        count: 3,
+    };
+    let bytes = list.to_tss_bytes();
+    assert_eq!(bytes, [0x00, 0x0B, 0x03, 0x81, 0x00, 0x01, 0x03]);
+    assert_eq!(list.tss_size(), bytes.len());
+    assert_eq!(TpmPcrList::from_tss_bytes_exact(&bytes).unwrap(), list);
+
+    assert_eq!(
+        TpmPcrList::from_tss_bytes(&[0x00, 0x0B, 0x03, 0x81]).unwrap_err(),
+        TssError::Field {
+            structure: "TpmPcrList",
+            path: vec!["pcrs"],
+            expected: "Vec<u32>",
+            position: 2,
+            source: Box::new(TssError::InsufficientData),
+        }
+    );
+}
+
+#[test]
+fn test_roundtrip_size_of() {
+    let command = TpmSizedCommand {
+        tag: 0x8001,
+        length: 0,
+        command_code: 0x0000017A,
+        handles_size: 0,
+        handles: vec![0x81000001],
+    };
+    let bytes = command.to_tss_bytes();
+    assert_eq!(
+        bytes,
+        [
+            0x80, 0x01, 0x00, 0x00, 0x00, 0x14, 0x00, 0x00, 0x01, 0x7A, 0x00, 0x08, 0x00, 0x00,
+            0x00, 0x01, 0x81, 0x00, 0x00, 0x01
+        ]
+    );
+    assert_eq!(command.tss_size(), bytes.len());
+    assert_eq!(
+        TpmSizedCommand::from_tss_bytes_exact(&bytes).unwrap(),
+        TpmSizedCommand {
+            length: 20,
+            handles_size: 8,
+            ..command
+        }
+    );
+
+    let mut wrong_length = bytes.clone();
+    wrong_length[5] = 0x15;
+    assert_eq!(
+        TpmSizedCommand::from_tss_bytes(&wrong_length).unwrap_err(),
+        TssError::Field {
+            structure: "TpmSizedCommand",
+            path: vec!["length"],
+            expected: "u32",
+            position: 2,
+            source: Box::new(TssError::InvalidValue {
+                field: "size of structure",
+                value: 0x15,
+            }),
+        }
+    );
+    let mut wrong_size = bytes;
+    wrong_size[11] = 0x04;
+    assert_eq!(
+        TpmSizedCommand::from_tss_bytes(&wrong_size)
+            .unwrap_err()
+            .to_string(),
+        "Invalid size of handles 0x4 decoding TpmSizedCommand.handles_size (u16) at byte 10"
+    );
+}
+
+#[test]
+#[should_panic(expected = "the size of handles does not fit its u16")]
+fn test_size_of_overflow() {
+    TpmSizedCommand {
+        tag: 0x8001,
+        length: 0,
+        command_code: 0x17B,
+        handles_size: 0,
+        handles: vec![0; 0x4000],
+    }
+    .to_tss_bytes();
+}
+
+#[test]
+fn test_roundtrip_length_prefix() {
+    let lists = TpmPrefixedLists {
+        digest: vec![0xAA, 0xBB],
+        algorithms: vec![0x000B, 0x000C],
+        selections: vec![TpmPcrSelection {
+            hash: 0x000B,
+            size_of_select: 3,
+            pcr_select: [0x81, 0x00, 0x00],
+        }],
+    };
+    let bytes = lists.to_tss_bytes();
+    assert_eq!(
+        bytes,
+        [
+            0x00, 0x02, 0xAA, 0xBB, 0x02, 0x00, 0x0B, 0x00, 0x0C, 0x00, 0x06, 0x00, 0x0B, 0x03,
+            0x81, 0x00, 0x00
+        ]
+    );
+    assert_eq!(lists.tss_size(), bytes.len());
+    assert_eq!(
+        TpmPrefixedLists::from_tss_bytes_exact(&bytes).unwrap(),
+        lists
+    );
+
+    // The selections take 6 bytes, which end within the second one.
+    let mut truncated = bytes.clone();
+    truncated[10] = 0x08;
+    truncated.extend_from_slice(&[0x00, 0x0C]);
+    assert_eq!(
+        TpmPrefixedLists::from_tss_bytes(&truncated).unwrap_err(),
+        TssError::Field {
+            structure: "TpmPrefixedLists",
+            path: vec!["selections", "size_of_select"],
+            expected: "u8",
+            position: 19,
+            source: Box::new(TssError::InsufficientData),
+        }
+    );
+
+    let mut reader = TssReader::new(&bytes).max_list_length(1);
+    assert_eq!(
+        TpmPrefixedLists::from_tss_reader(&mut reader)
+            .unwrap_err()
+            .root(),
+        &TssError::LengthOverflow { length: 2, max: 1 }
+    );
+
+    // Counted as they are decoded when the prefix is a byte size.
+    let selection = || TpmPcrSelection {
+        hash: 0x000B,
+        size_of_select: 3,
+        pcr_select: [0x81, 0x00, 0x00],
+    };
+    let lists = TpmPrefixedLists {
+        digest: Vec::new(),
+        algorithms: Vec::new(),
+        selections: vec![selection(), selection()],
+    };
+    let bytes = lists.to_tss_bytes();
+    let mut reader = TssReader::new(&bytes).max_list_length(1);
+    assert_eq!(
+        TpmPrefixedLists::from_tss_reader(&mut reader)
+            .unwrap_err()
+            .root(),
+        &TssError::LengthOverflow { length: 2, max: 1 }
+    );
+}
+
+#[test]
+fn test_length_unit_rejects_empty_elements() {
+    assert_eq!(
+        SnpMarkerList::from_tss_bytes(&[0x00, 0x01, 0xAA])
+            .unwrap_err()
+            .root(),
+        &TssError::InvalidFormat
+    );
+    let empty = SnpMarkerList {
+        markers: Vec::new(),
+    };
+    assert_eq!(SnpMarkerList::from_tss_bytes(&[0x00, 0x00]).unwrap(), empty);
+}
+
+#[test]
+#[should_panic(expected = "the length of algorithms does not fit its u8 prefix")]
+fn test_length_prefix_overflow() {
+    TpmPrefixedLists {
+        digest: Vec::new(),
+        algorithms: vec![0x000B; 256],
+        selections: Vec::new(),
+    }
+    .to_tss_bytes();
+}
+
+#[test]
+#[should_panic(expected = "the length of selections does not fit its u16 prefix")]
+fn test_length_unit_overflow() {
+    let selection = || TpmPcrSelection {
+        hash: 0x000B,
+        size_of_select: 3,
+        pcr_select: [0; 3],
+    };
+    TpmPrefixedLists {
+        digest: Vec::new(),
+        algorithms: Vec::new(),
+        selections: (0..0x2AAB).map(|_| selection()).collect(),
+    }
+    .to_tss_bytes();
+}
+
+#[test]
+fn test_roundtrip_tuple_struct() {
+    let handle = TpmHandle(0x81000001);
+    assert_eq!(handle.to_tss_bytes(), [0x81, 0x00, 0x00, 0x01]);
+    assert_eq!(TpmHandle::SERIALIZED_SIZE, 4);
+    assert_eq!(
+        TpmHandle::from_tss_bytes_exact(&handle.to_tss_bytes()).unwrap(),
+        handle
+    );
+
+    let version = SnpTcbVersion(3, 0x0102, vec![handle]);
+    let bytes = version.to_tss_bytes();
+    assert_eq!(bytes, [0x03, 0x02, 0x01, 0x01, 0x81, 0x00, 0x00, 0x01]);
+    assert_eq!(version.tss_size(), bytes.len());
+    assert_eq!(
+        SnpTcbVersion::from_tss_bytes_exact(&bytes).unwrap(),
+        version
+    );
+    assert_eq!(
+        SnpTcbVersion::from_tss_bytes(&bytes[..2]).unwrap_err(),
+        TssError::Field {
+            structure: "SnpTcbVersion",
+            path: vec!["1"],
+            expected: "u16",
+            position: 1,
+            source: Box::new(TssError::InsufficientData),
+        }
+    );
+}
+
+#[test]
+fn test_validate() {
+    let index = TpmNvIndex {
+        attributes: 0x0002,
+        data_size: 2,
+        data: vec![0xAA],
+    };
+    let bytes = index.to_tss_bytes();
+    assert_eq!(TpmNvIndex::from_tss_bytes_exact(&bytes).unwrap(), index);
+
+    let reserved = TpmNvIndex {
+        attributes: 0x0082,
+        ..index
+    };
+    assert_eq!(
+        TpmNvIndex::from_tss_bytes(&reserved.to_tss_bytes()).unwrap_err(),
+        TssError::Field {
+            structure: "TpmNvIndex",
+            path: vec!["attributes"],
+            expected: "u32",
+            position: 0,
+            source: Box::new(TssError::InvalidValue {
+                field: "attributes",
+                value: 0x80,
+            }),
+        }
+    );
+
+    let overfull = TpmNvIndex {
+        attributes: 0x0002,
+        data_size: 2,
+        data: vec![0xAA; 3],
+    };
+    assert_eq!(
+        TpmNvIndex::from_tss_bytes(&overfull.to_tss_bytes()).unwrap_err(),
+        TssError::InvalidValue {
+            field: "TpmNvIndex",
+            value: 3,
+        }
+    );
+}
+
+#[test]
+fn test_roundtrip_const() {
+    let header = TpmQuoteHeader {
+        qualified_signer: [1, 2, 3, 4],
+    };
+    let bytes = header.to_tss_bytes();
+    assert_eq!(bytes, [0xFF, 0x54, 0x43, 0x47, 0x80, 0x18, 1, 2, 3, 4]);
+    assert_eq!(header.tss_size(), bytes.len());
+    assert_eq!(TpmQuoteHeader::SERIALIZED_SIZE, bytes.len());
+    assert_eq!(
+        TpmQuoteHeader::from_tss_bytes_exact(&bytes).unwrap(),
+        header
+    );
+
+    let mut certify = bytes.clone();
+    certify[5] = 0x17;
+    assert_eq!(
+        TpmQuoteHeader::from_tss_bytes(&certify).unwrap_err(),
+        TssError::Field {
+            structure: "TpmQuoteHeader",
+            path: vec!["qualified_signer"],
+            expected: "[u8;4]",
+            position: 0,
+            source: Box::new(TssError::InvalidValue {
+                field: "constant 0x8018u16",
+                value: 0x8017,
+            }),
+        }
+    );
+    assert_eq!(
+        TpmQuoteHeader::from_tss_bytes(&[0xFF, 0x54, 0x43, 0x48])
+            .unwrap_err()
+            .root(),
+        &TssError::InvalidValue {
+            field: "constant TPM_GENERATED",
+            value: 0xFF544348,
+        }
+    );
+
+    let magic = SnpMagic {
+        version: 2,
+        body: 0x01020304,
+    };
+    let bytes = magic.to_tss_bytes();
+    assert_eq!(bytes, [0x02, 0x50, 0x53, 0x04, 0x03, 0x02, 0x01]);
+    assert_eq!(SnpMagic::from_tss_bytes_exact(&bytes).unwrap(), magic);
+    assert_eq!(
+        SnpMagic::from_tss_bytes(&[0x02, 0x51, 0x53, 0x04, 0x03, 0x02, 0x01])
+            .unwrap_err()
+            .root(),
+        &TssError::InvalidValue {
+            field: "constant 0x5350u16",
+            value: 0x5351,
+        }
+    );
+}
+
+#[test]
+fn test_roundtrip_type_syntax() {
+    let digest = TpmDigest {
+        size: 4,
+        buffer: [1, 2, 3, 4],
+    };
+    assert_eq!(TpmDigest::<4>::SERIALIZED_SIZE, 6);
+    assert_eq!(digest.to_tss_bytes(), [0x00, 0x04, 1, 2, 3, 4]);
+    assert_eq!(
+        TpmDigest::from_tss_bytes_exact(&digest.to_tss_bytes()).unwrap(),
+        digest
+    );
+
+    let tagged = TpmTagged {
+        tag: 0x8001,
+        value: digest,
+    };
+    assert_eq!(
+        TpmTagged::<TpmDigest<4>>::from_tss_bytes_exact(&tagged.to_tss_bytes()).unwrap(),
+        tagged
+    );
+
+    let paths = SnpTypePaths {
+        version: 0x0102,
+        measurement: [0xAA; SHA256_DIGEST_SIZE],
+        tcb: [[0x0304, 0x0506], [0x0708, 0x090A]],
+        reserved: vec![0xFF],
+    };
+    let bytes = paths.to_tss_bytes();
+    assert_eq!(&bytes[..2], [0x02, 0x01]);
+    assert_eq!(
+        &bytes[34..42],
+        [0x04, 0x03, 0x06, 0x05, 0x08, 0x07, 0x0A, 0x09]
+    );
+    assert_eq!(&bytes[42..], [0x01, 0x00, 0x00, 0x00, 0xFF]);
+    assert_eq!(paths.tss_size(), bytes.len());
+    assert_eq!(SnpTypePaths::from_tss_bytes_exact(&bytes).unwrap(), paths);
+}
+
+#[test]
+fn test_roundtrip_selector() {
+    let ecc = TpmPublic {
+        key_type: 0x0023,
+        name_alg: 0x000B,
+        parameters: TpmPublicParms::Ecc { curve_id: 0x0003 },
+    };
+    let bytes = ecc.to_tss_bytes();
+    assert_eq!(bytes, [0x00, 0x23, 0x00, 0x0B, 0x00, 0x03]);
+    assert_eq!(ecc.tss_size(), bytes.len());
+    assert_eq!(TpmPublic::from_tss_bytes_exact(&bytes).unwrap(), ecc);
+
+    let rsa = TpmPublic {
+        key_type: 0x0001,
+        name_alg: 0x000B,
+        parameters: TpmPublicParms::Rsa {
+            key_bits: 2048,
+            exponent: 0,
+        },
+    };
+    assert_eq!(rsa.tss_size(), 10);
+    assert_eq!(
+        TpmPublic::from_tss_bytes_exact(&rsa.to_tss_bytes()).unwrap(),
+        rsa
+    );
+
+    assert_eq!(TpmPublicParms::Null.to_tss_bytes(), []);
+    let mut reader = TssReader::new(&[]);
+    assert_eq!(
+        TpmPublicParms::from_tss_reader_with(&TPM_ALG_NULL, &mut reader).unwrap(),
+        TpmPublicParms::Null
+    );
+
+    assert_eq!(
+        TpmPublic::from_tss_bytes(&[0x00, 0x25, 0x00, 0x0B, 0x00, 0x03]).unwrap_err(),
+        TssError::Field {
+            structure: "TpmPublic",
+            path: vec!["parameters"],
+            expected: "TpmPublicParms",
+            position: 4,
+            source: Box::new(TssError::InvalidValue {
+                field: "TpmPublicParms",
+                value: 0x25,
+            }),
+        }
+    );
+}
+
+#[test]
+fn test_roundtrip_bitflags() {
+    let attributes = TpmaSession::CONTINUE_SESSION | TpmaSession::ENCRYPT;
+    assert_eq!(attributes.to_tss_bytes(), [0x41]);
+    assert_eq!(attributes.tss_size(), 1);
+    assert_eq!(TpmaSession::SERIALIZED_SIZE, 1);
+    assert_eq!(
+        TpmaSession::from_tss_bytes_exact(&[0x41]).unwrap(),
+        attributes
+    );
+    assert_eq!(
+        TpmaSession::from_tss_bytes(&[0x19]).unwrap_err(),
+        TssError::InvalidValue {
+            field: "TpmaSession",
+            value: 0x18,
+        }
+    );
+
+    let response = TpmsAuthResponse {
+        nonce_size: 0,
+        session_attributes: TpmaSession::AUDIT,
+    };
+    assert_eq!(TpmsAuthResponse::SERIALIZED_SIZE, 3);
+    assert_eq!(
+        TpmsAuthResponse::from_tss_bytes_exact(&response.to_tss_bytes()).unwrap(),
+        response
+    );
+    assert_eq!(
+        TpmsAuthResponse::from_tss_bytes(&[0x00, 0x00, 0x08]).unwrap_err(),
+        TssError::Field {
+            structure: "TpmsAuthResponse",
+            path: vec!["session_attributes"],
+            expected: "TpmaSession",
+            position: 2,
+            source: Box::new(TssError::InvalidValue {
+                field: "TpmaSession",
+                value: 0x08,
+            }),
+        }
+    );
+
+    // TPM2_CC_NV_Read, with its index in the reserved bits
+    let command = TpmaCc::from_tss_bytes_exact(&[0x10, 0x00, 0x01, 0x4E]).unwrap();
+    assert!(command.contains(TpmaCc::R_HANDLE));
+    assert_eq!(command.bits() & 0xFFFF, 0x014E);
+    assert_eq!(command.to_tss_bytes(), [0x10, 0x00, 0x01, 0x4E]);
+    assert_eq!(TpmaCc::SERIALIZED_SIZE, 4);
+}
+
+#[test]
+fn test_roundtrip_arrays() {
+    let selection = |hash| TpmPcrSelection {
+        hash,
+        size_of_select: 3,
+        pcr_select: [0x81, 0x00, 0x00],
+    };
+    let banks = TpmBankSet {
+        selections: [selection(0x0004), selection(0x000B), selection(0x000C)],
+        algs: [TpmAlgId::Sha256, TpmAlgId::Null],
+        handles: [TpmHandle(0x81000001), TpmHandle(0x81000002)],
+        counters: [1, 2, 3, 0x0102030405060708],
+    };
+    let bytes = banks.to_tss_bytes();
+    assert_eq!(TpmBankSet::<2>::SERIALIZED_SIZE, 18 + 4 + 8 + 32);
+    assert_eq!(bytes.len(), TpmBankSet::<2>::SERIALIZED_SIZE);
+    assert_eq!(&bytes[18..22], [0x00, 0x0B, 0x00, 0x10]);
+    assert_eq!(&bytes[54..], [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(TpmBankSet::from_tss_bytes_exact(&bytes).unwrap(), banks);
+
+    let mut unknown = bytes.clone();
+    unknown[21] = 0x11;
+    assert_eq!(
+        TpmBankSet::<2>::from_tss_bytes(&unknown).unwrap_err(),
+        TssError::Field {
+            structure: "TpmBankSet",
+            path: vec!["algs"],
+            expected: "[TpmAlgId;N]",
+            position: 18,
+            source: Box::new(TssError::InvalidValue {
+                field: "TpmAlgId",
+                value: 0x11,
+            }),
+        }
+    );
+
+    // Elements of a little-endian structure follow its byte order, unless
+    // they are types of their own.
+    let digests = SnpVmplDigests {
+        counters: [1, 2, 3, 4],
+        policies: [SnpPolicyBit::Debug, SnpPolicyBit::Smt],
+        digests: [[0x0102, 0x0304], [5, 6], [7, 8]],
+    };
+    let bytes = digests.to_tss_bytes();
+    assert_eq!(SnpVmplDigests::SERIALIZED_SIZE, 32 + 16 + 12);
+    assert_eq!(bytes.len(), SnpVmplDigests::SERIALIZED_SIZE);
+    assert_eq!(&bytes[..8], [1, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(&bytes[48..52], [0x02, 0x01, 0x04, 0x03]);
+    assert_eq!(
+        SnpVmplDigests::from_tss_bytes_exact(&bytes).unwrap(),
+        digests
+    );
+}
+
+#[test]
+fn test_roundtrip_unit_struct() {
+    assert_eq!(TpmStartupResponse.to_tss_bytes(), []);
+    assert_eq!(TpmStartupResponse.tss_size(), 0);
+    assert_eq!(TpmStartupResponse::SERIALIZED_SIZE, 0);
+    assert_eq!(
+        TpmStartupResponse::from_tss_bytes(&[]).unwrap(),
+        TpmStartupResponse
+    );
+    assert_eq!(
+        TpmStartupResponse::from_tss_bytes(&[0x00]).unwrap_err(),
+        TssError::InvalidFormat
+    );
+
+    let mut reader = TssReader::new(&[0x01]);
+    assert_eq!(SnpMarker::from_tss_reader(&mut reader).unwrap(), SnpMarker);
+    assert_eq!(reader.remaining(), 1);
+
+    let marked = SnpMarked {
+        marker: SnpMarker,
+        version: 2,
+    };
+    assert_eq!(SnpMarked::SERIALIZED_SIZE, 4);
+    assert_eq!(marked.to_tss_bytes(), [0x00, 0x00, 0x00, 0x02]);
+    assert_eq!(
+        SnpMarked::from_tss_bytes_exact(&marked.to_tss_bytes()).unwrap(),
+        marked
+    );
+}
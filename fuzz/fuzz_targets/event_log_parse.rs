@@ -0,0 +1,12 @@
+#![no_main]
+
+use tee_attest::EventLog;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(log) = EventLog::parse(data) {
+        let _ = log.replay_sha256();
+        let _ = log.gce_confidential_technology();
+        let _ = log.gce_firmware_version();
+    }
+});
@@ -0,0 +1,39 @@
+#![no_main]
+
+use tee_attest::{EventLog, TpmEvent};
+use libfuzzer_sys::fuzz_target;
+
+// `events` in a log with SHA-1 and SHA-256 banks, which must parse back.
+fuzz_target!(|events: Vec<TpmEvent>| {
+    let mut spec_id = b"Spec ID Event03\0".to_vec();
+    spec_id.extend_from_slice(&[0, 0, 0, 0, 0, 2, 0, 2]);
+    spec_id.extend_from_slice(&2u32.to_le_bytes());
+    spec_id.extend_from_slice(&[0x04, 0, 20, 0, 0x0b, 0, 32, 0]);
+    spec_id.push(0);
+
+    let mut log = Vec::new();
+    log.extend_from_slice(&0u32.to_le_bytes());
+    log.extend_from_slice(&tee_attest::event_type::NO_ACTION.to_le_bytes());
+    log.extend_from_slice(&[0; 20]);
+    log.extend_from_slice(&(spec_id.len() as u32).to_le_bytes());
+    log.extend_from_slice(&spec_id);
+
+    let mut expected = Vec::new();
+    for mut event in events {
+        let sha256 = *event.sha256.get_or_insert([0; 32]);
+        log.extend_from_slice(&event.pcr_index.to_le_bytes());
+        log.extend_from_slice(&event.event_type.to_le_bytes());
+        log.extend_from_slice(&2u32.to_le_bytes());
+        log.extend_from_slice(&0x04u16.to_le_bytes());
+        log.extend_from_slice(&[0; 20]);
+        log.extend_from_slice(&0x0bu16.to_le_bytes());
+        log.extend_from_slice(&sha256);
+        log.extend_from_slice(&(event.data.len() as u32).to_le_bytes());
+        log.extend_from_slice(&event.data);
+        expected.push(event);
+    }
+
+    let parsed = EventLog::parse(&log).expect("well-formed event log");
+    assert_eq!(parsed.events, expected);
+    let _ = parsed.replay_sha256();
+});
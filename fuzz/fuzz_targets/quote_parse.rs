@@ -0,0 +1,14 @@
+#![no_main]
+
+use dcap::{Quote, QuoteRef};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(view) = QuoteRef::parse(data) {
+        let _ = view.parse_body();
+        let _ = view.parse_signature();
+    }
+    if let Ok(quote) = Quote::parse(data) {
+        assert_eq!(quote.header.to_bytes(), data[..dcap::QUOTE_HEADER_SIZE]);
+    }
+});
@@ -0,0 +1,50 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use dcap::{attestation_key_type, tee_type, Quote, QuoteBody, QuoteHeader};
+use libfuzzer_sys::fuzz_target;
+
+/// The parts of a quote, laid out as v5 if `v5` or the body is a TDX 1.5
+/// report, as v3 or v4 otherwise.
+#[derive(Debug, Arbitrary)]
+struct Input {
+    header: QuoteHeader,
+    body: QuoteBody,
+    v5: bool,
+    signature_data: Vec<u8>,
+}
+
+fuzz_target!(|input: Input| {
+    let Input {
+        mut header,
+        body,
+        v5,
+        signature_data,
+    } = input;
+    header.attestation_key_type = attestation_key_type::ECDSA_P256;
+    header.tee_type = match body {
+        QuoteBody::SgxEnclave(_) => tee_type::SGX,
+        _ => tee_type::TDX,
+    };
+    let v5 = v5 || matches!(body, QuoteBody::Td15(_));
+    header.version = match (v5, &body) {
+        (true, _) => 5,
+        (false, QuoteBody::SgxEnclave(_)) => 3,
+        (false, _) => 4,
+    };
+
+    let mut bytes = header.to_bytes().to_vec();
+    let body_bytes = body.to_bytes();
+    if v5 {
+        bytes.extend_from_slice(&body.body_type().to_le_bytes());
+        bytes.extend_from_slice(&(body_bytes.len() as u32).to_le_bytes());
+    }
+    bytes.extend_from_slice(&body_bytes);
+    bytes.extend_from_slice(&(signature_data.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&signature_data);
+
+    if let Ok(quote) = Quote::parse(&bytes) {
+        assert_eq!(quote.header, header);
+        assert_eq!(quote.body, body);
+    }
+});
@@ -0,0 +1,33 @@
+#![no_main]
+
+use arbitrary::Unstructured;
+use libfuzzer_sys::fuzz_target;
+use tss_client::{
+    CapabilitiesResponse, CreateResponse, Empty, NvPublic, NvReadPublicResponse, PcrReadResponse,
+    PcrSelection, QuoteResponse, RawResponse, ReadPublicResponse, ResponseHeader,
+    StartAuthSessionResponse, TpmSignature,
+};
+use tss_serde::{assert_roundtrip, Tpm2b, TssDeserialize};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ResponseHeader::from_tss_bytes(data);
+    let _ = RawResponse::from_tss_bytes(data);
+    let _ = Empty::from_tss_bytes(data);
+    let _ = CapabilitiesResponse::from_tss_bytes(data);
+    let _ = NvPublic::from_tss_bytes(data);
+    let _ = NvReadPublicResponse::from_tss_bytes(data);
+    let _ = PcrSelection::from_tss_bytes(data);
+    let _ = PcrReadResponse::from_tss_bytes(data);
+    let _ = CreateResponse::from_tss_bytes(data);
+    let _ = StartAuthSessionResponse::from_tss_bytes(data);
+    let _ = ReadPublicResponse::from_tss_bytes(data);
+    let _ = TpmSignature::from_tss_bytes(data);
+    let _ = QuoteResponse::from_tss_bytes(data);
+    let _ = dcap::TpmAttest::parse(data);
+    let _ = dcap::TpmCertifyInfo::parse(data);
+
+    let mut u = Unstructured::new(data);
+    let _ = assert_roundtrip::<Tpm2b>(&mut u)
+        .and_then(|()| assert_roundtrip::<NvPublic>(&mut u))
+        .and_then(|()| assert_roundtrip::<PcrSelection>(&mut u));
+});
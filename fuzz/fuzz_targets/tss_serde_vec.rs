@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tss_serde::TssDeserialize;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Vec::<u8>::from_tss_bytes(data);
+    let _ = Vec::<u16>::from_tss_bytes(data);
+    let _ = Vec::<u32>::from_tss_bytes(data);
+    let _ = Vec::<u64>::from_tss_bytes(data);
+    let _ = Vec::<bool>::from_tss_bytes(data);
+    let _ = Vec::<[u8; 48]>::from_tss_bytes(data);
+    let _ = Vec::<Vec<u16>>::from_tss_bytes(data);
+});